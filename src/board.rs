@@ -0,0 +1,262 @@
+//! The kanban board: tasks in To do, In progress and Done columns, as
+//! `todo tui` shows them after `b` and `todo board` prints them. A task is
+//! in progress while it has the mark `todo start` or a board move sets, and
+//! stays in Done for `DONE_DAYS` days after it was completed. `todo board`
+//! can also split pending tasks into columns by priority or project.
+
+use chrono::{DateTime, Duration, Utc};
+use colored::*;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::models::Task;
+
+/// How long completed tasks stay in the board's Done column.
+pub const DONE_DAYS: i64 = 7;
+
+/// Room between two columns of `todo board`.
+const SEPARATOR: &str = " │ ";
+/// Narrowest a column of `todo board` gets, however many there are.
+const MIN_COLUMN_WIDTH: usize = 12;
+
+/// A column of the board.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum Column {
+    Todo = 0,
+    InProgress = 1,
+    Done = 2,
+}
+
+impl Column {
+    pub const ALL: [Column; 3] = [Column::Todo, Column::InProgress, Column::Done];
+
+    pub fn of(task: &Task) -> Column {
+        if task.completed {
+            Column::Done
+        } else if task.in_progress {
+            Column::InProgress
+        } else {
+            Column::Todo
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            Column::Todo => "To do",
+            Column::InProgress => "In progress",
+            Column::Done => "Done",
+        }
+    }
+
+    pub fn left(&self) -> Option<Column> {
+        Column::ALL.get((*self as usize).checked_sub(1)?).copied()
+    }
+
+    pub fn right(&self) -> Option<Column> {
+        Column::ALL.get(*self as usize + 1).copied()
+    }
+}
+
+/// Work-in-progress limits of the board columns; 0 means no limit.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct WipLimits {
+    pub todo: usize,
+    pub in_progress: usize,
+}
+
+impl WipLimits {
+    pub fn of(&self, column: Column) -> Option<usize> {
+        let limit = match column {
+            Column::Todo => self.todo,
+            Column::InProgress => self.in_progress,
+            Column::Done => 0,
+        };
+        (limit > 0).then_some(limit)
+    }
+}
+
+/// Whether a completed task still belongs in the Done column at `now`.
+pub fn recently_done(task: &Task, now: DateTime<Utc>) -> bool {
+    task.completed
+        && task.completed_at.unwrap_or(task.updated_at) >= now - Duration::days(DONE_DAYS)
+}
+
+/// How `todo board` splits tasks into columns.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum GroupBy {
+    /// To do, In progress and Done
+    Status,
+    /// High, Medium and Low, pending tasks only
+    Priority,
+    /// One column per project, pending tasks only
+    Project,
+}
+
+/// Split `tasks` into titled columns. Status and priority boards keep their
+/// empty columns so they have a stable shape; a project board has a column
+/// per project in use, then `No project`.
+pub fn group(tasks: &[Task], group_by: GroupBy, now: DateTime<Utc>) -> Vec<(String, Vec<&Task>)> {
+    let pending = || tasks.iter().filter(|task| !task.completed);
+    match group_by {
+        GroupBy::Status => Column::ALL
+            .iter()
+            .map(|&column| {
+                let mut cards: Vec<&Task> = tasks
+                    .iter()
+                    .filter(|task| Column::of(task) == column)
+                    .filter(|task| column != Column::Done || recently_done(task, now))
+                    .collect();
+                if column == Column::Done {
+                    cards.sort_by_key(|task| {
+                        std::cmp::Reverse(task.completed_at.unwrap_or(task.updated_at))
+                    });
+                }
+                (column.title().to_string(), cards)
+            })
+            .collect(),
+        GroupBy::Priority => [(2, "High"), (1, "Medium"), (0, "Low")]
+            .into_iter()
+            .map(|(priority, name)| {
+                let cards = pending().filter(|task| task.priority == priority).collect();
+                (name.to_string(), cards)
+            })
+            .collect(),
+        GroupBy::Project => {
+            let mut projects: Vec<&str> = pending().filter_map(|t| t.project.as_deref()).collect();
+            projects.sort();
+            projects.dedup();
+            let mut columns: Vec<(String, Vec<&Task>)> = projects
+                .into_iter()
+                .map(|name| {
+                    let cards = pending()
+                        .filter(|task| task.project.as_deref() == Some(name))
+                        .collect();
+                    (format!("@{}", name), cards)
+                })
+                .collect();
+            let loose: Vec<&Task> = pending().filter(|task| task.project.is_none()).collect();
+            if !loose.is_empty() {
+                columns.push(("No project".to_string(), loose));
+            }
+            columns
+        }
+    }
+}
+
+/// `text` padded or cut to exactly `width` terminal columns, ending in `…`
+/// when cut.
+fn fit(text: &str, width: usize) -> String {
+    if text.width() <= width {
+        return format!("{}{}", text, " ".repeat(width - text.width()));
+    }
+    let mut cut = String::new();
+    for c in text.chars() {
+        if cut.width() + c.width().unwrap_or(0) + 1 > width {
+            break;
+        }
+        cut.push(c);
+    }
+    let cut = format!("{}…", cut);
+    format!("{}{}", cut, " ".repeat(width.saturating_sub(cut.width())))
+}
+
+/// The columns side by side in `width` terminal columns: a heading each,
+/// a rule, then a card per task with its ID and title, cut to fit.
+pub fn render(columns: &[(String, Vec<&Task>)], width: usize) -> Vec<String> {
+    if columns.is_empty() {
+        return Vec::new();
+    }
+    let gaps = SEPARATOR.width() * (columns.len() - 1);
+    let column_width = (width.saturating_sub(gaps) / columns.len()).max(MIN_COLUMN_WIDTH);
+    let tallest = columns
+        .iter()
+        .map(|(_, cards)| cards.len())
+        .max()
+        .unwrap_or(0);
+
+    let row = |cells: Vec<String>| cells.join(SEPARATOR).trim_end().to_string();
+    let mut lines = vec![
+        row(columns
+            .iter()
+            .map(|(heading, _)| fit(heading, column_width).bold().to_string())
+            .collect()),
+        row(vec!["─".repeat(column_width); columns.len()]),
+    ];
+    for index in 0..tallest {
+        lines.push(row(columns
+            .iter()
+            .map(|(_, cards)| match cards.get(index) {
+                Some(task) => {
+                    let card = fit(
+                        &format!("[{}] {}", task.id.unwrap_or(0), task.title),
+                        column_width,
+                    );
+                    if task.is_overdue() {
+                        crate::style::overdue(&card).to_string()
+                    } else {
+                        card
+                    }
+                }
+                None => " ".repeat(column_width),
+            })
+            .collect()));
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(id: i32, title: &str) -> Task {
+        let mut task = Task::new(title.to_string(), None, None, 1);
+        task.id = Some(id);
+        task
+    }
+
+    #[test]
+    fn test_board_columns() {
+        let now = Utc::now();
+        let mut started = task(2, "Write the quarterly report for finance");
+        started.in_progress = true;
+        let mut done = task(3, "Ship");
+        done.completed = true;
+        done.completed_at = Some(now - Duration::days(1));
+        let mut old = task(4, "Long gone");
+        old.completed = true;
+        old.completed_at = Some(now - Duration::days(DONE_DAYS + 1));
+        let mut work = task(5, "Plan");
+        work.project = Some("work".to_string());
+        let tasks = vec![task(1, "Buy milk"), started, done, old, work];
+
+        let columns = group(&tasks, GroupBy::Status, now);
+        let counts: Vec<(&str, usize)> = columns
+            .iter()
+            .map(|(title, cards)| (title.as_str(), cards.len()))
+            .collect();
+        assert_eq!(counts, [("To do", 2), ("In progress", 1), ("Done", 1)]);
+
+        let codes = regex::Regex::new("\x1b\\[[0-9;]*m").unwrap();
+        let lines: Vec<String> = render(&columns, 42)
+            .iter()
+            .map(|line| codes.replace_all(line, "").into_owned())
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                "To do        │ In progress  │ Done",
+                "──────────── │ ──────────── │ ────────────",
+                "[1] Buy milk │ [2] Write t… │ [3] Ship",
+                "[5] Plan     │              │",
+            ]
+        );
+
+        let columns = group(&tasks, GroupBy::Project, now);
+        let titles: Vec<&str> = columns.iter().map(|(title, _)| title.as_str()).collect();
+        assert_eq!(titles, ["@work", "No project"]);
+        assert_eq!(columns[1].1.len(), 2);
+        assert_eq!(group(&tasks, GroupBy::Priority, now)[1].1.len(), 3);
+
+        assert_eq!(Column::Todo.right(), Some(Column::InProgress));
+        assert_eq!(Column::Todo.left(), None);
+    }
+}
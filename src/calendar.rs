@@ -0,0 +1,318 @@
+//! Calendar arithmetic in one place: month grids with due-task counts for
+//! calendar views, and the periods of whole days reports count by. Weeks
+//! start on the `week_start` day. Due dates fall on the days of the
+//! configured zone (see `zone`); completion times are counted in the days
+//! of the local time zone. Either way, day bounds move with daylight saving
+//! time.
+
+use chrono::{DateTime, Datelike, Duration, Months, NaiveDate, NaiveTime, TimeZone, Utc, Weekday};
+use std::collections::HashMap;
+
+use crate::models::Task;
+
+/// A week of a month grid; days of the neighbouring months are `None`.
+pub type Week = [Option<NaiveDate>; 7];
+
+/// The weeks of the month containing `date`, starting on `first`.
+pub fn month_weeks(date: NaiveDate, first: Weekday) -> Vec<Week> {
+    let mut weeks = Vec::new();
+    let mut week: Week = [None; 7];
+    for day in Period::month(date).days() {
+        let weekday = day.weekday().days_since(first) as usize;
+        week[weekday] = Some(day);
+        if weekday == 6 {
+            weeks.push(week);
+            week = [None; 7];
+        }
+    }
+    if week.iter().any(Option::is_some) {
+        weeks.push(week);
+    }
+    weeks
+}
+
+/// Two-letter names of the days of a week starting on `first`, e.g.
+/// `Mo Tu We Th Fr Sa Su`.
+pub fn weekday_header(first: Weekday) -> String {
+    let names: Vec<String> = (0..7)
+        .scan(first, |day, _| {
+            let name = day.to_string()[..2].to_string();
+            *day = day.succ();
+            Some(name)
+        })
+        .collect();
+    names.join(" ")
+}
+
+/// The day `week_start` names, e.g. `monday` or `sun`.
+pub fn parse_weekday(text: &str) -> Option<Weekday> {
+    text.trim().parse().ok()
+}
+
+pub fn weekday_name(day: Weekday) -> &'static str {
+    match day {
+        Weekday::Mon => "monday",
+        Weekday::Tue => "tuesday",
+        Weekday::Wed => "wednesday",
+        Weekday::Thu => "thursday",
+        Weekday::Fri => "friday",
+        Weekday::Sat => "saturday",
+        Weekday::Sun => "sunday",
+    }
+}
+
+/// Whole days from `first` through `last`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Period {
+    pub first: NaiveDate,
+    pub last: NaiveDate,
+}
+
+impl Period {
+    /// The `count` days up to and including `today`.
+    pub fn last_days(today: NaiveDate, count: i64) -> Self {
+        Period {
+            first: today - Duration::days(count - 1),
+            last: today,
+        }
+    }
+
+    /// The week containing `date`, starting on `first`.
+    pub fn week(date: NaiveDate, first: Weekday) -> Self {
+        let start = date - Duration::days(date.weekday().days_since(first).into());
+        Period {
+            first: start,
+            last: start + Duration::days(6),
+        }
+    }
+
+    /// The month containing `date`.
+    pub fn month(date: NaiveDate) -> Self {
+        let first = date.with_day(1).unwrap();
+        Period {
+            first,
+            last: add_months(first, 1).pred_opt().unwrap(),
+        }
+    }
+
+    pub fn days(&self) -> impl Iterator<Item = NaiveDate> {
+        let last = self.last;
+        self.first.iter_days().take_while(move |day| *day <= last)
+    }
+}
+
+/// The instant `day` starts at in `zone`. A day whose midnight falls in a
+/// daylight saving gap starts when the clocks go forward; one whose
+/// midnight comes twice starts at the first.
+pub fn day_start<Tz: TimeZone>(day: NaiveDate, zone: &Tz) -> DateTime<Utc> {
+    let midnight = day.and_time(NaiveTime::MIN);
+    (0..=24 * 60)
+        .map(|minutes| midnight + Duration::minutes(minutes))
+        .find_map(|local| zone.from_local_datetime(&local).earliest())
+        .map(|start| start.with_timezone(&Utc))
+        .unwrap_or_else(|| midnight.and_utc())
+}
+
+/// The day of `zone` that `at` falls on.
+pub fn local_day<Tz: TimeZone>(at: DateTime<Utc>, zone: &Tz) -> NaiveDate {
+    at.with_timezone(zone).date_naive()
+}
+
+/// Number of pending tasks due on each day.
+pub fn due_counts<'a>(tasks: impl IntoIterator<Item = &'a Task>) -> HashMap<NaiveDate, usize> {
+    let mut counts = HashMap::new();
+    for task in tasks.into_iter().filter(|task| !task.completed) {
+        if let Some(due) = task.due_date {
+            *counts.entry(crate::zone::day_of(due)).or_insert(0) += 1;
+        }
+    }
+    counts
+}
+
+/// `date` moved by whole months, clamped to the end of shorter months.
+pub fn add_months(date: NaiveDate, months: i32) -> NaiveDate {
+    let step = Months::new(months.unsigned_abs());
+    let moved = if months < 0 {
+        date.checked_sub_months(step)
+    } else {
+        date.checked_add_months(step)
+    };
+    moved.unwrap_or(date)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{FixedOffset, LocalResult, NaiveDateTime, TimeZone, Utc};
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn utc(text: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(text)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    /// A Santiago-like zone that changes its clocks around midnight: UTC-3
+    /// in summer, UTC-4 from 00:30 on 6 April 2030, when the clocks go back
+    /// to 23:30, to 00:00 on 8 September, when they skip to 01:00.
+    #[derive(Clone, Copy)]
+    struct Zone;
+
+    impl TimeZone for Zone {
+        type Offset = FixedOffset;
+
+        fn from_offset(_: &FixedOffset) -> Self {
+            Zone
+        }
+
+        fn offset_from_local_date(&self, local: &NaiveDate) -> LocalResult<FixedOffset> {
+            self.offset_from_local_datetime(&local.and_time(NaiveTime::MIN))
+        }
+
+        fn offset_from_local_datetime(&self, local: &NaiveDateTime) -> LocalResult<FixedOffset> {
+            let offsets: Vec<FixedOffset> = [-3, -4]
+                .map(|hours| FixedOffset::east_opt(hours * 3600).unwrap())
+                .into_iter()
+                .filter(|offset| self.offset_from_utc_datetime(&(*local - *offset)) == *offset)
+                .collect();
+            match offsets[..] {
+                [offset] => LocalResult::Single(offset),
+                [first, second] => LocalResult::Ambiguous(first, second),
+                _ => LocalResult::None,
+            }
+        }
+
+        fn offset_from_utc_date(&self, utc: &NaiveDate) -> FixedOffset {
+            self.offset_from_utc_datetime(&utc.and_time(NaiveTime::MIN))
+        }
+
+        fn offset_from_utc_datetime(&self, at: &NaiveDateTime) -> FixedOffset {
+            let winter =
+                utc("2030-04-06T03:30:00Z").naive_utc()..utc("2030-09-08T04:00:00Z").naive_utc();
+            let hours = if winter.contains(at) { -4 } else { -3 };
+            FixedOffset::east_opt(hours * 3600).unwrap()
+        }
+    }
+
+    #[test]
+    fn test_month_grid_and_due_counts() {
+        // February 2026 starts on a Sunday and ends on a Saturday
+        let weeks = month_weeks(date(2026, 2, 14), Weekday::Mon);
+        assert_eq!(weeks.len(), 5);
+        assert_eq!(weeks[0][..6], [None; 6]);
+        assert_eq!(weeks[0][6], Some(date(2026, 2, 1)));
+        assert_eq!(weeks[4][5], Some(date(2026, 2, 28)));
+        assert_eq!(weeks[4][6], None);
+        let weeks = month_weeks(date(2026, 2, 14), Weekday::Sun);
+        assert_eq!(weeks.len(), 4);
+        assert_eq!(weeks[0][0], Some(date(2026, 2, 1)));
+        assert_eq!(weeks[3][6], Some(date(2026, 2, 28)));
+        assert_eq!(weekday_header(Weekday::Sun), "Su Mo Tu We Th Fr Sa");
+
+        assert_eq!(add_months(date(2026, 1, 31), 1), date(2026, 2, 28));
+        assert_eq!(add_months(date(2026, 1, 15), -1), date(2025, 12, 15));
+
+        let due = Utc.with_ymd_and_hms(2026, 2, 3, 9, 0, 0).unwrap();
+        let mut tasks = Vec::new();
+        for title in ["Pay rent", "Call bank", "Old"] {
+            tasks.push(Task::new(title.to_string(), None, Some(due), 1));
+        }
+        tasks[2].completed = true;
+        tasks.push(Task::new("Undated".to_string(), None, None, 1));
+        let counts = due_counts(&tasks);
+        assert_eq!(counts.len(), 1);
+        assert_eq!(counts[&date(2026, 2, 3)], 2);
+    }
+
+    #[test]
+    fn test_periods() {
+        // 2030-01-10 is a Thursday
+        let week = Period::week(date(2030, 1, 10), Weekday::Mon);
+        assert_eq!(
+            (week.first, week.last),
+            (date(2030, 1, 7), date(2030, 1, 13))
+        );
+        let week = Period::week(date(2030, 1, 10), Weekday::Sun);
+        assert_eq!(
+            (week.first, week.last),
+            (date(2030, 1, 6), date(2030, 1, 12))
+        );
+        let week = Period::week(date(2030, 1, 6), Weekday::Sun);
+        assert_eq!(week.first, date(2030, 1, 6));
+        assert_eq!(week.days().count(), 7);
+
+        let month = Period::month(date(2028, 2, 10));
+        assert_eq!(
+            (month.first, month.last),
+            (date(2028, 2, 1), date(2028, 2, 29))
+        );
+        let days = Period::last_days(date(2030, 1, 2), 7);
+        assert_eq!(days.first, date(2029, 12, 27));
+
+        assert_eq!(parse_weekday("Sunday"), Some(Weekday::Sun));
+        assert_eq!(parse_weekday("sat"), Some(Weekday::Sat));
+        assert_eq!(parse_weekday("someday"), None);
+    }
+
+    #[test]
+    fn test_local_days_across_daylight_saving() {
+        // An ordinary day starts at local midnight
+        assert_eq!(
+            day_start(date(2030, 1, 10), &Zone),
+            utc("2030-01-10T03:00:00Z")
+        );
+        assert_eq!(
+            day_start(date(2030, 1, 10), &Utc),
+            utc("2030-01-10T00:00:00Z")
+        );
+
+        // Midnight comes twice when the clocks go back; the day starts at
+        // the first and lasts 25 hours
+        assert_eq!(
+            day_start(date(2030, 4, 6), &Zone),
+            utc("2030-04-06T03:00:00Z")
+        );
+        assert_eq!(
+            day_start(date(2030, 4, 7), &Zone),
+            utc("2030-04-07T04:00:00Z")
+        );
+        assert_eq!(
+            local_day(utc("2030-04-06T03:45:00Z"), &Zone),
+            date(2030, 4, 5)
+        );
+        assert_eq!(
+            local_day(utc("2030-04-06T04:00:00Z"), &Zone),
+            date(2030, 4, 6)
+        );
+
+        // Midnight is skipped when they go forward; the day starts at 01:00
+        // and lasts 23 hours
+        assert_eq!(
+            day_start(date(2030, 9, 8), &Zone),
+            utc("2030-09-08T04:00:00Z")
+        );
+        assert_eq!(
+            day_start(date(2030, 9, 9), &Zone),
+            utc("2030-09-09T03:00:00Z")
+        );
+        assert_eq!(
+            local_day(utc("2030-09-08T03:59:00Z"), &Zone),
+            date(2030, 9, 7)
+        );
+        assert_eq!(
+            local_day(utc("2030-09-08T04:00:00Z"), &Zone),
+            date(2030, 9, 8)
+        );
+
+        // A week's days cover every instant between its bounds once
+        let week = Period::week(date(2030, 9, 8), Weekday::Mon);
+        let start = day_start(week.first, &Zone);
+        let end = day_start(week.last + Duration::days(1), &Zone);
+        assert_eq!(end - start, Duration::hours(7 * 24 - 1));
+        assert_eq!(local_day(start, &Zone), week.first);
+        assert_eq!(local_day(end - Duration::seconds(1), &Zone), week.last);
+    }
+}
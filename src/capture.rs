@@ -0,0 +1,126 @@
+//! One-line task syntax, used by `todo add --from-file`: a title with
+//! metadata tokens mixed in.
+//!
+//! ```text
+//! Send invoice #work/clients +acme due:friday p:high repeat:monthly
+//! ```
+//!
+//! `#tag` (repeatable), `+project`, `due:WHEN`, `start:WHEN` (hidden from
+//! `todo list` until then), `p:PRIORITY` (or
+//! `priority:`), `repeat:RULE`, the energy a task takes (`!low`,
+//! `!medium`, `!high`) and dependencies on existing tasks (`after:12` waits
+//! for task 12, `before:15` makes task 15 wait for this one) are taken out
+//! and the remaining words, in order, are the title. Values are one word; `_` stands for a space,
+//! as in `due:next_friday` or `repeat:every_2_weeks`. `#42` and `+1` stay
+//! in the title, since issue numbers and votes are not tags or projects.
+
+use anyhow::Result;
+
+use crate::{Energy, Priority};
+
+/// A task as written on one line; values are checked when the task is
+/// built, like the matching `todo add` flags.
+#[derive(Debug, Default, PartialEq)]
+pub struct Captured {
+    pub title: String,
+    pub tags: Vec<String>,
+    pub project: Option<String>,
+    pub due: Option<String>,
+    pub start: Option<String>,
+    pub priority: Option<Priority>,
+    pub repeat: Option<String>,
+    pub energy: Option<Energy>,
+    /// Tasks the new one waits for.
+    pub after: Vec<i32>,
+    /// Tasks that wait for the new one.
+    pub before: Vec<i32>,
+}
+
+pub fn parse(line: &str) -> Result<Captured> {
+    let mut captured = Captured::default();
+    let mut title = Vec::new();
+    for word in line.split_whitespace() {
+        let value = |rest: &str| rest.replace('_', " ");
+        if let Some(tag) = word.strip_prefix('#').filter(|rest| is_name(rest)) {
+            captured.tags.push(tag.to_string());
+        } else if let Some(project) = word.strip_prefix('+').filter(|rest| is_name(rest)) {
+            captured.project = Some(value(project));
+        } else if let Some(energy) = word.strip_prefix('!').and_then(Energy::from_name) {
+            captured.energy = Some(energy);
+        } else if let Some((key, rest)) = word.split_once(':').filter(|(_, rest)| !rest.is_empty())
+        {
+            match key {
+                "due" => captured.due = Some(value(rest)),
+                "start" => captured.start = Some(value(rest)),
+                "repeat" => captured.repeat = Some(value(rest)),
+                "after" => captured.after.push(task_id(rest)?),
+                "before" => captured.before.push(task_id(rest)?),
+                "p" | "priority" => {
+                    captured.priority = Some(
+                        <Priority as clap::ValueEnum>::from_str(rest, true).map_err(|_| {
+                            anyhow::anyhow!("Invalid priority '{}': use low, medium or high", rest)
+                        })?,
+                    )
+                }
+                _ => title.push(word),
+            }
+        } else {
+            title.push(word);
+        }
+    }
+    captured.title = title.join(" ");
+    if captured.title.is_empty() {
+        return Err(anyhow::anyhow!("Task title cannot be empty"));
+    }
+    Ok(captured)
+}
+
+fn task_id(text: &str) -> Result<i32> {
+    text.parse()
+        .map_err(|_| anyhow::anyhow!("Invalid task ID '{}': use a number", text))
+}
+
+/// Whether the text after `#` or `+` names something: not empty and not
+/// just a number.
+pub(crate) fn is_name(text: &str) -> bool {
+    !text.is_empty() && !text.chars().all(|c| c.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tokens() {
+        let captured =
+            parse("Send invoice #work/clients +acme start:monday due:next_friday p:HIGH repeat:monthly !low")
+                .unwrap();
+        assert_eq!(
+            captured,
+            Captured {
+                title: "Send invoice".to_string(),
+                tags: vec!["work/clients".to_string()],
+                project: Some("acme".to_string()),
+                due: Some("next friday".to_string()),
+                start: Some("monday".to_string()),
+                priority: Some(Priority::High),
+                repeat: Some("monthly".to_string()),
+                energy: Some(Energy::Low),
+                after: Vec::new(),
+                before: Vec::new(),
+            }
+        );
+
+        let deploy = parse("deploy app after:12 after:15 before:20").unwrap();
+        assert_eq!(deploy.title, "deploy app");
+        assert_eq!((deploy.after, deploy.before), (vec![12, 15], vec![20]));
+        assert!(parse("deploy app after:tomorrow").is_err());
+
+        let plain = parse("Fix bug #42 before 10:30, +1 from QA !!").unwrap();
+        assert_eq!(plain.title, "Fix bug #42 before 10:30, +1 from QA !!");
+        assert!(plain.tags.is_empty() && plain.project.is_none());
+
+        assert!(parse("#errands +home").is_err());
+        assert!(parse("Call mom p:urgent").is_err());
+    }
+}
@@ -1,264 +1,6233 @@
 use anyhow::Result;
-use chrono::{DateTime, NaiveDate, Utc};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use colored::Colorize;
 
-use crate::db::Database;
-use crate::models::Task;
+use crate::board::WipLimits;
+use crate::db::{Database, DueRange, Placement, SomedayFilter, StatusFilter, TaskFilter};
+use crate::error::TodoError;
+use crate::models::{duration_text, Task};
+use crate::signing::Shared;
 
+#[allow(clippy::too_many_arguments)]
 pub fn add_task(
     db: &Database,
     title: &str,
     description: Option<&str>,
     due_date: Option<&str>,
+    start_date: Option<&str>,
+    priority: Option<&crate::Priority>,
+    tags: &[String],
+    project: Option<&str>,
+    repeat: Option<&str>,
+    parent: Option<i32>,
+    energy: Option<crate::Energy>,
+    estimate: Option<&str>,
+    force: bool,
+) -> Result<()> {
+    let estimate = estimate.map(parse_estimate).transpose()?;
+    let task = new_task(
+        db,
+        title,
+        description,
+        due_date,
+        start_date,
+        priority,
+        tags,
+        project,
+        repeat,
+        parent,
+        energy,
+    )?;
+
+    if !force {
+        if let Some(existing) = db.find_pending_duplicate(&task)? {
+            outln!(
+                "♻️  Already pending as task {}: {} (pass --force to add it anyway)",
+                existing.id.unwrap_or(0),
+                existing.title
+            );
+            return Ok(());
+        }
+    }
+
+    let tx = db.transaction()?;
+    let id = db.add_task(&task)?;
+    if estimate.is_some() {
+        db.set_estimate(id, estimate)?;
+    }
+    tx.commit()?;
+    outln!("✅ Task added successfully with ID: {}", id);
+    Ok(())
+}
+
+/// `todo clone`: a new pending task with the title, description, priority,
+/// tags and project of task `id`, due `due` when given. An inherited
+/// priority stays inherited.
+pub fn clone_task(db: &Database, id: i32, due: Option<&str>) -> Result<()> {
+    let source = db.get_task_by_id(id)?.ok_or(TodoError::NotFound(id))?;
+    let due_date = due.map(parse_due_date).transpose()?;
+    let mut task = Task::new(source.title, source.description, due_date, source.priority);
+    task.priority_inherited = source.priority_inherited;
+    task.tags = source.tags;
+    task.project = source.project;
+    let clone = db.add_task(&task)?;
+    outln!("✅ Task {} cloned as task {}", id, clone);
+    Ok(())
+}
+
+/// `todo add --from-file`: one task per non-blank line of `text`, written
+/// in the `capture` syntax. `priority`, `project` and `parent` apply to
+/// lines that don't say otherwise, and `tags` are added to every task.
+/// All lines are checked before anything is added, and the tasks go in
+/// as one transaction. Pending duplicates are skipped unless `force`.
+#[allow(clippy::too_many_arguments)]
+pub fn add_tasks_from(
+    db: &Database,
+    text: &str,
     priority: &crate::Priority,
+    tags: &[String],
+    project: Option<&str>,
+    parent: Option<i32>,
+    force: bool,
 ) -> Result<()> {
+    let mut tasks: Vec<(Task, Vec<i32>, Vec<i32>)> = Vec::new();
+    let mut hashes = std::collections::HashSet::new();
+    let mut skipped = 0;
+    for (number, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (task, after, before) = crate::capture::parse(line)
+            .and_then(|captured| {
+                check_dependencies(db, &captured.after, &captured.before)?;
+                let mut line_tags = tags.to_vec();
+                line_tags.extend(captured.tags);
+                let task = new_task(
+                    db,
+                    &captured.title,
+                    None,
+                    captured.due.as_deref(),
+                    captured.start.as_deref(),
+                    Some(captured.priority.as_ref().unwrap_or(priority)),
+                    &line_tags,
+                    captured.project.as_deref().or(project),
+                    captured.repeat.as_deref(),
+                    parent,
+                    captured.energy,
+                )?;
+                Ok((task, captured.after, captured.before))
+            })
+            .map_err(|e| anyhow::anyhow!("Line {}: {:#}", number + 1, e))?;
+        let duplicate = !force
+            && (!hashes.insert(task.content_hash()) || db.find_pending_duplicate(&task)?.is_some());
+        if duplicate {
+            skipped += 1;
+        } else {
+            tasks.push((task, after, before));
+        }
+    }
+
+    let ids = db.add_tasks_with_dependencies(&tasks)?;
+    match (ids.first(), ids.last()) {
+        (Some(first), Some(last)) if first == last => {
+            outln!("✅ Added 1 task with ID: {}", first)
+        }
+        (Some(first), Some(last)) => {
+            outln!("✅ Added {} tasks with IDs {}-{}", ids.len(), first, last)
+        }
+        _ => outln!("No tasks added."),
+    }
+    if skipped > 0 {
+        outln!(
+            "♻️  Skipped {} already pending (pass --force to add them anyway)",
+            skipped
+        );
+    }
+    Ok(())
+}
+
+/// A task from `todo add` arguments, checked and normalized; subtasks
+/// inherit their parent's project. Without a `priority` the task inherits
+/// one from its project or parent.
+#[allow(clippy::too_many_arguments)]
+fn new_task(
+    db: &Database,
+    title: &str,
+    description: Option<&str>,
+    due_date: Option<&str>,
+    start_date: Option<&str>,
+    priority: Option<&crate::Priority>,
+    tags: &[String],
+    project: Option<&str>,
+    repeat: Option<&str>,
+    parent: Option<i32>,
+    energy: Option<crate::Energy>,
+) -> Result<Task> {
     let due_date_parsed = if let Some(due_str) = due_date {
         Some(parse_due_date(due_str)?)
     } else {
         None
     };
 
-    let task = Task::new(
+    let mut task = Task::new(
         title.to_string(),
         description.map(|s| s.to_string()),
         due_date_parsed,
-        priority.to_int(),
+        priority.unwrap_or(&crate::Priority::Medium).to_int(),
     );
+    task.priority_inherited = priority.is_none();
+    task.start_date = start_date.map(parse_due_date).transpose()?;
+    task.tags = tags
+        .iter()
+        .map(|tag| crate::tags::normalize(tag))
+        .collect::<Result<_>>()?;
+    task.project = project.map(normalize_project).transpose()?;
+    task.recurrence = repeat.map(crate::recurrence::normalize).transpose()?;
+    task.energy = energy;
+    if let Some(parent_id) = parent {
+        // Subtasks stay in their parent's project unless told otherwise
+        let parent = db
+            .get_task_by_id(parent_id)?
+            .ok_or_else(|| TodoError::Missing(format!("Parent task {} not found", parent_id)))?;
+        task.parent_id = Some(parent_id);
+        if task.project.is_none() {
+            task.project = parent.project;
+        }
+    }
+    Ok(task)
+}
 
-    let id = db.add_task(&task)?;
-    println!("✅ Task added successfully with ID: {}", id);
-    Ok(())
+/// How plain list output is split into groups.
+enum Grouping {
+    None,
+    Project,
+    /// Task ID to the value it is grouped under.
+    Values(std::collections::HashMap<i32, Option<String>>),
+}
+
+impl From<Option<crate::ListGroupBy>> for Grouping {
+    fn from(group_by: Option<crate::ListGroupBy>) -> Self {
+        match group_by {
+            None => Grouping::None,
+            Some(crate::ListGroupBy::Project) => Grouping::Project,
+        }
+    }
 }
 
+/// List tasks matching `filter`, grouped by `group_by` or by the value of
+/// the SQL expression `group_expr` (see `sql_expr`).
+#[allow(clippy::too_many_arguments)]
 pub fn list_tasks(
     db: &Database,
-    include_completed: bool,
-    priority_filter: Option<&crate::Priority>,
+    filter: &TaskFilter,
+    due_soon_window: Duration,
+    format: &crate::OutputFormat,
+    group_by: Option<crate::ListGroupBy>,
+    group_expr: Option<&str>,
+    table: &crate::table::TableOptions,
+    page: crate::db::Page,
 ) -> Result<()> {
-    let priority_int = priority_filter.map(|p| p.to_int());
-    let tasks = db.get_all_tasks(include_completed, priority_int)?;
+    if let Some(pattern) = &filter.title_pattern {
+        regex::Regex::new(pattern)
+            .map_err(|e| anyhow::anyhow!("Invalid --title-matches pattern: {}", e))?;
+    }
+    let grouping = match group_expr {
+        Some(expr) => {
+            crate::sql_expr::validate(expr, &db.extension_functions())?;
+            Grouping::Values(
+                db.expression_values(expr)
+                    .map_err(|e| anyhow::anyhow!("Invalid expression: {}", e))?,
+            )
+        }
+        None => group_by.into(),
+    };
+    let tasks = db.get_tasks_page(filter, page)?;
+    let estimates = db.estimates()?;
+    let estimated: Vec<i64> = tasks
+        .iter()
+        .filter_map(|task| task.id.and_then(|id| estimates.get(&id).copied()))
+        .collect();
+    let footer = Footer {
+        paged: (page != crate::db::Page::default())
+            .then(|| db.count_tasks(filter).map(|total| (page.offset, total)))
+            .transpose()?,
+        estimated: (!estimated.is_empty()).then(|| (estimated.iter().sum(), estimated.len())),
+    };
+    print_tasks(
+        tasks,
+        "📋",
+        "Your tasks",
+        due_soon_window,
+        format,
+        grouping,
+        table,
+        footer,
+    )
+}
 
-    if tasks.is_empty() {
-        println!("📝 No tasks found.");
+/// How often `todo list --watch` asks SQLite whether the tasks changed.
+const WATCH_POLL: std::time::Duration = std::time::Duration::from_millis(500);
+/// How often `todo list --watch` redraws anyway, so due dates and the
+/// due-soon window keep up with the clock.
+const WATCH_REDRAW: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// `todo list --watch`: keep what `draw` prints on screen and redraw it
+/// whenever another process commits to the database, until interrupted.
+pub fn watch_list(db: &Database, mut draw: impl FnMut() -> Result<()>) -> Result<()> {
+    use std::io::Write;
+
+    let mut seen = None;
+    let mut drawn_at = std::time::Instant::now();
+    loop {
+        let version = db.data_version()?;
+        if seen != Some(version) || drawn_at.elapsed() >= WATCH_REDRAW {
+            let (result, text) = crate::style::capture(&mut draw);
+            let footer = match result {
+                Ok(()) => {
+                    seen = Some(version);
+                    format!(
+                        "Updated {}; Ctrl-C to stop",
+                        chrono::Local::now().format("%H:%M:%S")
+                    )
+                }
+                // Most likely another process holding a write lock; the
+                // next poll tries again
+                Err(error) => format!("⚠️  {}; retrying", error),
+            };
+            // Draw the whole frame at once so the screen doesn't flicker
+            let mut stdout = std::io::stdout().lock();
+            write!(stdout, "\x1b[H\x1b[2J{}\n{}\n", text, footer.dimmed())?;
+            stdout.flush()?;
+            drawn_at = std::time::Instant::now();
+        }
+        std::thread::sleep(WATCH_POLL);
+    }
+}
+
+/// `todo next`: the most urgent actionable tasks, best first.
+pub fn next_tasks(
+    db: &Database,
+    energy: Option<crate::Energy>,
+    limit: usize,
+    weights: &crate::urgency::Weights,
+    formula: Option<&crate::urgency::Formula>,
+    tags: &[String],
+    due_soon_window: Duration,
+) -> Result<()> {
+    let ranked = next_up(db, energy, limit, weights, formula, tags)?;
+    if ranked.is_empty() {
+        outln!("🎉 Nothing to do right now.");
         return Ok(());
     }
+    match energy {
+        Some(energy) => outln!("⚡ Next up for {} energy:", energy.name()),
+        None => outln!("⚡ Next up:"),
+    }
+    for (score, task) in &ranked {
+        outln!("{:>5.1}  {}", score, task.display_summary(due_soon_window));
+    }
+    Ok(())
+}
 
-    println!("📋 Your tasks:");
-    println!("{}", "─".repeat(80));
+/// Up to `limit` actionable tasks with their urgency, most urgent first.
+/// With `energy`, tasks that take more than that are left out and those
+/// that fit it rank higher; see `urgency`. A `formula` replaces the
+/// built-in urgency. `tags` narrows them, as the `context` key does.
+fn next_up(
+    db: &Database,
+    energy: Option<crate::Energy>,
+    limit: usize,
+    weights: &crate::urgency::Weights,
+    formula: Option<&crate::urgency::Formula>,
+    tags: &[String],
+) -> Result<Vec<(f64, Task)>> {
+    use crate::urgency::{energy_of, urgency};
 
-    let task_count = tasks.len();
-    for task in tasks {
-        println!("{}", task.display_summary());
+    let now = Utc::now();
+    let tasks = db.get_tasks(&TaskFilter {
+        tags: tags.to_vec(),
+        ..TaskFilter::default()
+    })?;
+    // Tasks with open subtasks wait for them, as `todo why` explains
+    let parents: std::collections::HashSet<i32> =
+        tasks.iter().filter_map(|task| task.parent_id).collect();
+    let mut ranked: Vec<(f64, Task)> = tasks
+        .into_iter()
+        .filter(|task| !task.id.is_some_and(|id| parents.contains(&id)))
+        .filter(|task| energy.is_none_or(|energy| energy_of(task) <= energy))
+        .map(|task| {
+            let score = match formula {
+                Some(formula) => formula.evaluate(&task, now, weights, energy),
+                None => urgency(&task, now, weights, energy),
+            };
+            (score, task)
+        })
+        .collect();
+    ranked.sort_by(|a, b| b.0.total_cmp(&a.0));
+    ranked.truncate(limit);
+    Ok(ranked)
+}
+
+/// `todo move`: put a task just before or after another in the order
+/// `todo list --sort manual` shows.
+pub fn move_task(db: &Database, id: i32, placement: Placement) -> Result<()> {
+    let (Placement::Before(target) | Placement::After(target)) = placement;
+    if id == target {
+        return Err(anyhow::anyhow!("A task cannot be moved next to itself"));
+    }
+    for task_id in [id, target] {
+        if db.get_task_by_id(task_id)?.is_none() {
+            return Err(TodoError::NotFound(task_id).into());
+        }
     }
+    db.move_task(id, placement)?;
+    let side = match placement {
+        Placement::Before(_) => "before",
+        Placement::After(_) => "after",
+    };
+    outln!("✅ Task {} moved {} task {}", id, side, target);
+    Ok(())
+}
 
-    println!("{}", "─".repeat(80));
-    println!("Total: {} tasks", task_count);
+/// `todo board`: the tasks in columns side by side, headed by their count
+/// and, on a status board, the column's WIP limit.
+pub fn board(db: &Database, group_by: crate::board::GroupBy, limits: WipLimits) -> Result<()> {
+    use crate::board::{Column, GroupBy};
+    let tasks = db.get_tasks(&TaskFilter::any_status())?;
+    let mut columns = crate::board::group(&tasks, group_by, Utc::now());
+    if columns.iter().all(|(_, cards)| cards.is_empty()) {
+        outln!("📭 No tasks found.");
+        return Ok(());
+    }
+    for (index, (title, cards)) in columns.iter_mut().enumerate() {
+        let limit = match group_by {
+            GroupBy::Status => limits.of(Column::ALL[index]),
+            _ => None,
+        };
+        *title = match limit {
+            Some(limit) => format!("{} {}/{}", title, cards.len(), limit),
+            None => format!("{} {}", title, cards.len()),
+        };
+    }
+    let width = crate::table::output_width().unwrap_or(80);
+    for line in crate::board::render(&columns, width) {
+        outln!("{}", line);
+    }
     Ok(())
 }
 
-pub fn complete_task(db: &Database, id: i32) -> Result<()> {
-    if !db.task_exists(id)? {
-        return Err(anyhow::anyhow!("Task with ID {} not found", id));
+/// `todo move ID STATUS`: put a task in a board column, as `H`/`L` do in
+/// the TUI. Done completes it like `todo complete`; the other columns
+/// reopen it and set or clear its in-progress mark.
+pub fn move_to_column(
+    db: &Database,
+    id: i32,
+    column: crate::board::Column,
+    limits: WipLimits,
+    auto_complete_parents: bool,
+) -> Result<()> {
+    use crate::board::Column;
+    let mut task = db.get_task_by_id(id)?.ok_or(TodoError::NotFound(id))?;
+    if column == Column::Done {
+        return complete_task(db, &[id], auto_complete_parents);
     }
+    if Column::of(&task) == column {
+        outln!("Task {} is already in {}.", id, column.title());
+        return Ok(());
+    }
+    task.completed = false;
+    task.in_progress = column == Column::InProgress;
+    db.edit_task(id, &task)?;
+    outln!("✅ Task {} moved to {}", id, column.title());
 
-    db.complete_task(id)?;
-    println!("✅ Task {} marked as completed!", id);
+    if let Some(limit) = limits.of(column) {
+        let count = db
+            .get_tasks(&TaskFilter::default())?
+            .iter()
+            .filter(|task| Column::of(task) == column)
+            .count();
+        if count > limit {
+            errln!(
+                "⚠️  {} is over its WIP limit ({}/{})",
+                column.title(),
+                count,
+                limit
+            );
+        }
+    }
     Ok(())
 }
 
-pub fn delete_task(db: &Database, id: i32) -> Result<()> {
-    if !db.task_exists(id)? {
-        return Err(anyhow::anyhow!("Task with ID {} not found", id));
+/// `todo today`: overdue tasks, then the rest of what is due today, each
+/// group in due order. `tags` narrows both, like the `context` key does for
+/// `todo list`.
+pub fn agenda(db: &Database, tags: &[String], due_soon_window: Duration) -> Result<()> {
+    let now = Utc::now();
+    let due_in = |range: DueRange| -> Result<Vec<Task>> {
+        let mut tasks = db.get_tasks(&TaskFilter {
+            due_range: Some(range),
+            tags: tags.to_vec(),
+            ..TaskFilter::default()
+        })?;
+        tasks.sort_by_key(|task| (task.due_date, -task.priority));
+        Ok(tasks)
+    };
+    let overdue = due_in(crate::DueFilter::Overdue.range(now))?;
+    let today = due_in(DueRange {
+        from: Some(now),
+        until: crate::DueFilter::Today.range(now).until,
+    })?;
+
+    if overdue.is_empty() && today.is_empty() {
+        outln!("🎉 Nothing overdue or due today.");
+        return Ok(());
     }
 
-    db.delete_task(id)?;
-    println!("🗑️  Task {} deleted successfully!", id);
+    outln!("📅 Agenda for {}", crate::style::date(now));
+    outln!("{}", "─".repeat(80));
+    for (heading, tasks) in [("⚠️  Overdue", &overdue), ("📆 Due today", &today)] {
+        if tasks.is_empty() {
+            continue;
+        }
+        outln!("{} ({})", heading.bold(), tasks.len());
+        for task in tasks {
+            outln!("  {}", task.display_summary(due_soon_window));
+        }
+    }
+    outln!("{}", "─".repeat(80));
+    outln!("Total: {} tasks", overdue.len() + today.len());
     Ok(())
 }
 
-pub fn update_task(
+/// `todo list --archived`: archived tasks, most recently completed first.
+pub fn list_archived(
     db: &Database,
-    id: i32,
-    title: Option<&str>,
-    description: Option<&str>,
-    due_date: Option<&str>,
-    priority: Option<&crate::Priority>,
+    due_soon_window: Duration,
+    format: &crate::OutputFormat,
+    group_by: Option<crate::ListGroupBy>,
+    table: &crate::table::TableOptions,
 ) -> Result<()> {
-    if !db.task_exists(id)? {
-        return Err(anyhow::anyhow!("Task with ID {} not found", id));
+    let tasks = db.get_archived_tasks()?;
+    print_tasks(
+        tasks,
+        "🗄️ ",
+        "Archived tasks",
+        due_soon_window,
+        format,
+        group_by.into(),
+        table,
+        Footer::default(),
+    )
+}
+
+/// What the line under a plain task list says besides the task count.
+#[derive(Default)]
+struct Footer {
+    /// The offset of the first task and the number of tasks on all pages,
+    /// when the list is one page of a longer one.
+    paged: Option<(usize, usize)>,
+    /// Minutes of estimated work in the list, and how many tasks that is.
+    estimated: Option<(i64, usize)>,
+}
+
+/// Print `tasks` in `format`, with `footer` under plain output.
+#[allow(clippy::too_many_arguments)]
+fn print_tasks(
+    tasks: Vec<Task>,
+    icon: &str,
+    title: &str,
+    due_soon_window: Duration,
+    format: &crate::OutputFormat,
+    grouping: Grouping,
+    table: &crate::table::TableOptions,
+    footer: Footer,
+) -> Result<()> {
+    match format {
+        crate::OutputFormat::Plain => {}
+        crate::OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&tasks)?);
+            return Ok(());
+        }
+        crate::OutputFormat::Csv => {
+            print!("{}", crate::csv_output::render(&tasks)?);
+            return Ok(());
+        }
+        crate::OutputFormat::Html => {
+            print!(
+                "{}",
+                crate::html::render_page(title, &tasks, due_soon_window)
+            );
+            return Ok(());
+        }
+    }
+
+    if tasks.is_empty() {
+        match footer.paged {
+            Some((_, total)) if total > 0 => {
+                outln!("📝 No tasks on this page; there are {} in all.", total)
+            }
+            _ => outln!("📝 No tasks found."),
+        }
+        return Ok(());
     }
 
-    let mut task = db.get_task_by_id(id)?.unwrap();
+    outln!("{} {}:", icon, title);
+    outln!("{}", "─".repeat(80));
+
+    let task_count = tasks.len();
+    let groups = match grouping {
+        Grouping::None => vec![(None, tasks)],
+        Grouping::Project => group_by_project(tasks)
+            .into_iter()
+            .map(|(project, tasks)| {
+                let heading = project
+                    .map(|name| format!("@{}", name))
+                    .unwrap_or_else(|| "(no project)".to_string());
+                (Some(heading), tasks)
+            })
+            .collect(),
+        Grouping::Values(values) => group_by_value(tasks, &values)
+            .into_iter()
+            .map(|(value, tasks)| (Some(value.unwrap_or_else(|| "(none)".to_string())), tasks))
+            .collect(),
+    };
+
+    // Plain mode keeps one labelled line per task; tables don't read well
+    // aloud
+    if crate::style::plain() {
+        for (heading, tasks) in &groups {
+            let indent = match heading {
+                Some(heading) => {
+                    outln!("{} ({})", heading, tasks.len());
+                    "  "
+                }
+                None => "",
+            };
+            for line in crate::display::render_tree(tasks, due_soon_window) {
+                outln!("{}{}", indent, line);
+            }
+        }
+    } else {
+        let rows: Vec<Vec<(String, &Task)>> = groups
+            .iter()
+            .map(|(_, tasks)| crate::display::tree_rows(tasks))
+            .collect();
+        let grouped = groups.iter().any(|(heading, _)| heading.is_some());
+        let indent = if grouped { "  " } else { "" };
+        let width = crate::table::output_width().map(|width| width - indent.len().min(width));
+        let all_rows: Vec<(String, &Task)> = rows.iter().flatten().cloned().collect();
+        let layout = crate::table::Table::new(table, &all_rows, width, due_soon_window);
+        outln!("{}{}", indent, layout.header());
+        for ((heading, tasks), rows) in groups.iter().zip(&rows) {
+            if let Some(heading) = heading {
+                outln!("{} ({})", heading.bold(), tasks.len());
+            }
+            for line in layout.lines(rows) {
+                outln!("{}{}", indent, line);
+            }
+        }
+    }
 
-    if let Some(new_title) = title {
-        task.title = new_title.to_string();
+    outln!("{}", "─".repeat(80));
+    let mut summary = match footer.paged {
+        Some((offset, total)) => format!(
+            "Showing {}–{} of {} tasks",
+            offset + 1,
+            offset + task_count,
+            total
+        ),
+        None => format!("Total: {} tasks", task_count),
+    };
+    if let Some((minutes, count)) = footer.estimated {
+        summary.push_str(&format!(
+            " · {} estimated",
+            duration_text(Duration::minutes(minutes))
+        ));
+        if count < task_count {
+            summary.push_str(&format!(" for {} of them", count));
+        }
     }
+    outln!("{}", summary);
+    Ok(())
+}
 
-    if let Some(new_description) = description {
-        task.description = Some(new_description.to_string());
+/// Group tasks by project name, keeping their order within each group.
+/// Named projects come first, alphabetically; tasks without one come last.
+fn group_by_project(tasks: Vec<Task>) -> Vec<(Option<String>, Vec<Task>)> {
+    let mut groups: std::collections::BTreeMap<(bool, Option<String>), Vec<Task>> =
+        std::collections::BTreeMap::new();
+    for task in tasks {
+        groups
+            .entry((task.project.is_none(), task.project.clone()))
+            .or_default()
+            .push(task);
     }
+    groups
+        .into_iter()
+        .map(|((_, project), tasks)| (project, tasks))
+        .collect()
+}
 
-    if let Some(due_str) = due_date {
-        task.due_date = Some(parse_due_date(due_str)?);
+/// Group tasks by their entry in `values`, keeping their order within each
+/// group. Groups are sorted by value, numerically when both are numbers;
+/// tasks without a value come last.
+fn group_by_value(
+    tasks: Vec<Task>,
+    values: &std::collections::HashMap<i32, Option<String>>,
+) -> Vec<(Option<String>, Vec<Task>)> {
+    let mut groups: Vec<(Option<String>, Vec<Task>)> = Vec::new();
+    for task in tasks {
+        let value = task.id.and_then(|id| values.get(&id).cloned().flatten());
+        match groups.iter_mut().find(|(group, _)| *group == value) {
+            Some((_, group)) => group.push(task),
+            None => groups.push((value, vec![task])),
+        }
     }
+    groups.sort_by(|(a, _), (b, _)| match (a, b) {
+        (None, None) => std::cmp::Ordering::Equal,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (Some(a), Some(b)) => match (a.parse::<f64>(), b.parse::<f64>()) {
+            (Ok(x), Ok(y)) => x.total_cmp(&y),
+            _ => a.cmp(b),
+        },
+    });
+    groups
+}
 
-    if let Some(new_priority) = priority {
-        task.priority = new_priority.to_int();
+pub(crate) fn normalize_project(name: &str) -> Result<String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(anyhow::anyhow!("Project name cannot be empty"));
     }
+    Ok(name.to_string())
+}
 
-    task.updated_at = Utc::now();
+pub fn project_list(db: &Database) -> Result<()> {
+    let projects = db.get_projects()?;
+    if projects.is_empty() {
+        outln!("📁 No projects yet. Use `todo add --project NAME` to start one.");
+        return Ok(());
+    }
 
-    db.update_task(id, &task)?;
-    println!("✅ Task {} updated successfully!", id);
+    let priorities = db.project_priorities()?;
+    outln!("📁 Projects:");
+    outln!("{}", "─".repeat(80));
+    for (name, pending, total) in &projects {
+        let priority = priorities
+            .get(name)
+            .map(|priority| {
+                format!(
+                    ", {} by default",
+                    crate::Priority::from_int(*priority).name()
+                )
+            })
+            .unwrap_or_default();
+        outln!(
+            "@{:<30} {} open / {} total{}",
+            name,
+            pending,
+            total,
+            priority
+        );
+    }
+    outln!("{}", "─".repeat(80));
+    outln!("Total: {} projects", projects.len());
     Ok(())
 }
 
-pub fn show_task(db: &Database, id: i32) -> Result<()> {
-    let task = db.get_task_by_id(id)?;
+pub fn project_rename(db: &Database, old: &str, new: &str) -> Result<()> {
+    let new = normalize_project(new)?;
+    if !db.project_exists(old)? {
+        return Err(TodoError::Missing(format!("Project '{}' not found", old)).into());
+    }
+    if db.project_exists(&new)? {
+        return Err(anyhow::anyhow!("Project '{}' already exists", new));
+    }
+    db.rename_project(old, &new)?;
+    outln!("✏️  Project '{}' renamed to '{}'", old, new);
+    Ok(())
+}
 
-    match task {
-        Some(task) => {
-            println!("📋 Task Details:");
-            println!("{}", "─".repeat(80));
-            println!("{}", task.display_detailed());
-            println!("{}", "─".repeat(80));
-        }
-        None => {
-            return Err(anyhow::anyhow!("Task with ID {} not found", id));
+/// Show, set or clear the default priority of project `name`.
+pub fn project_priority(
+    db: &Database,
+    name: &str,
+    priority: Option<&crate::Priority>,
+    clear: bool,
+) -> Result<()> {
+    if !db.project_exists(name)? {
+        return Err(TodoError::Missing(format!("Project '{}' not found", name)).into());
+    }
+    if clear {
+        db.set_project_priority(name, None)?;
+        outln!("✏️  Project '{}' has no default priority now", name);
+    } else if let Some(priority) = priority {
+        db.set_project_priority(name, Some(priority.to_int()))?;
+        outln!(
+            "✏️  Tasks in '{}' without a priority of their own are {} priority",
+            name,
+            priority.name()
+        );
+    } else {
+        match db.project_priorities()?.get(name) {
+            Some(priority) => outln!(
+                "@{}: {} by default",
+                name,
+                crate::Priority::from_int(*priority).name()
+            ),
+            None => outln!("@{} has no default priority", name),
         }
     }
+    Ok(())
+}
+
+pub fn project_delete(db: &Database, name: &str) -> Result<()> {
+    if !db.project_exists(name)? {
+        return Err(TodoError::Missing(format!("Project '{}' not found", name)).into());
+    }
+    db.delete_project(name)?;
+    outln!("🗑️  Project '{}' deleted; its tasks were kept", name);
+    Ok(())
+}
 
+pub fn report_aging(db: &Database, oldest: usize) -> Result<()> {
+    let now = Utc::now();
+    let report = db.get_aging_report(now, oldest)?;
+    if report.buckets.iter().all(|(_, count)| *count == 0) {
+        outln!("✨ No open tasks.");
+        return Ok(());
+    }
+    outln!("{}", crate::report::render_aging(&report, now).trim_end());
     Ok(())
 }
 
-fn parse_due_date(date_str: &str) -> Result<DateTime<Utc>> {
-    // Try parsing as YYYY-MM-DD format
-    let parsed = if let Ok(naive_date) = NaiveDate::parse_from_str(date_str, "%Y-%m-%d") {
-        let naive_datetime = naive_date.and_hms_opt(0, 0, 0).unwrap();
-        DateTime::<Utc>::from_naive_utc_and_offset(naive_datetime, Utc)
-    } else if let Ok(datetime) = DateTime::parse_from_rfc3339(date_str) {
-        datetime.with_timezone(&Utc)
+/// `todo report week`: tasks completed on each of the last seven days, or
+/// with `last` on each day of the previous calendar week, starting on
+/// `week_start`. Days are those of the configured time zone.
+pub fn report_week(db: &Database, last: bool, week_start: chrono::Weekday) -> Result<()> {
+    use crate::calendar::{day_start, Period};
+    let zone = crate::zone::current();
+    let today = crate::zone::day_of(Utc::now());
+    let (period, when) = if last {
+        (
+            Period::week(today - Duration::days(7), week_start),
+            "last week",
+        )
     } else {
-        return Err(anyhow::anyhow!(
-            "Invalid date format. Please use YYYY-MM-DD or RFC3339 format"
-        ));
+        (Period::last_days(today, 7), "in the last 7 days")
     };
-
-    if parsed < Utc::now() {
-        return Err(anyhow::anyhow!("Due date must be in the future"));
+    let end = day_start(period.last + Duration::days(1), &zone);
+    let mut completed = db.get_completed_since(day_start(period.first, &zone))?;
+    completed.retain(|task| task.completed_at.is_some_and(|at| at < end));
+    if completed.is_empty() {
+        outln!("💤 Nothing completed {}.", when);
+        return Ok(());
     }
-
-    Ok(parsed)
+    outln!(
+        "{}",
+        crate::report::render_week(&completed, &period, &format!("Completed {}", when), &zone)
+            .trim_end()
+    );
+    Ok(())
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::db::Database;
-    use tempfile::NamedTempFile;
+pub fn stale_tasks(db: &Database, days: i64, tag: bool) -> Result<()> {
+    let cutoff = Utc::now() - Duration::days(days);
+    let tasks = db.get_stale_tasks(cutoff)?;
 
-    fn create_test_db() -> (Database, NamedTempFile) {
-        let temp_file = NamedTempFile::new().unwrap();
-        let db = Database::new(temp_file.path()).unwrap();
-        db.init().unwrap();
-        (db, temp_file)
+    if tasks.is_empty() {
+        outln!("✨ No pending tasks untouched for {} days.", days);
+        return Ok(());
     }
 
-    #[test]
-    fn test_add_task() {
-        let (db, _temp_file) = create_test_db();
-
-        let priority = crate::Priority::High;
-        add_task(
-            &db,
-            "Test task",
-            Some("Test description"),
-            Some("2024-12-31"),
-            &priority,
-        )
-        .unwrap();
+    outln!("🕸️  Tasks untouched for {}+ days:", days);
+    outln!("{}", "─".repeat(80));
 
-        let tasks = db.get_all_tasks(true, None).unwrap();
-        assert_eq!(tasks.len(), 1);
-        assert_eq!(tasks[0].title, "Test task");
-        assert_eq!(tasks[0].priority, 2); // High priority
+    let window = Duration::hours(crate::models::DEFAULT_DUE_SOON_HOURS);
+    for task in &tasks {
+        outln!("{}", task.display_summary(window));
     }
 
-    #[test]
-    fn test_parse_due_date() {
-        // Test YYYY-MM-DD format
-        let date = parse_due_date("2024-12-31").unwrap();
-        assert_eq!(date.format("%Y-%m-%d").to_string(), "2024-12-31");
+    outln!("{}", "─".repeat(80));
+    outln!("Total: {} stale tasks", tasks.len());
 
-        // Test RFC3339 format
-        let rfc_date = parse_due_date("2024-12-31T00:00:00Z").unwrap();
-        assert_eq!(rfc_date.format("%Y-%m-%d").to_string(), "2024-12-31");
+    if tag {
+        for task in &tasks {
+            db.add_tag(task.id.unwrap(), STALE_TAG)?;
+        }
+        outln!("🏷️  Tagged {} tasks as #{}", tasks.len(), STALE_TAG);
+    }
+    Ok(())
+}
 
-        // Test invalid format
-        assert!(parse_due_date("invalid-date").is_err());
+pub fn maintain(db: &Database, options: &crate::maintenance::MaintenanceOptions) -> Result<()> {
+    let progress = crate::progress::Progress::spinner("Maintenance");
+    let report = crate::maintenance::run(db, options, &progress)?;
+    progress.finish();
+    outln!("🧹 Maintenance complete");
+    if options.stale_tagging {
+        outln!("  Tagged as #{}: {}", STALE_TAG, report.stale_tagged);
+    }
+    if options.priority_decay {
+        outln!("  Priority decayed: {}", report.decayed);
+        outln!("  Moved to someday: {}", report.parked);
     }
+    Ok(())
+}
 
-    #[test]
-    fn test_complete_task() {
-        let (db, _temp_file) = create_test_db();
+/// The new due date when snoozing `task` by `days`: counted from its due
+/// date, or from now once that has passed.
+pub fn snooze_target(task: &Task, days: i64) -> DateTime<Utc> {
+    let now = Utc::now();
+    task.due_date.filter(|due| *due > now).unwrap_or(now) + Duration::days(days)
+}
 
-        // Add a task first
-        let priority = crate::Priority::Medium;
-        add_task(&db, "Test task", None, None, &priority).unwrap();
+/// Where `when` moves `task`: a duration such as `2d` or `1w` counts like
+/// `snooze_target`, and a date such as `tomorrow` or `2030-01-31` is taken
+/// as is, as long as it is later than the current due date.
+fn snooze_until(task: &Task, when: &str) -> Result<DateTime<Utc>> {
+    let now = Utc::now();
+    if let Some((count, unit)) = crate::dates::parse_duration(when) {
+        let from = task.due_date.filter(|due| *due > now).unwrap_or(now);
+        return crate::dates::shift(from, count, unit)
+            .ok_or_else(|| anyhow::anyhow!("Snoozing by {} is out of range", when));
+    }
+    let until = parse_due_date(when)?;
+    if let Some(due) = task.due_date.filter(|due| *due >= until) {
+        return Err(anyhow::anyhow!(
+            "Task {} is already due {}; snoozing only moves it later",
+            task.id.unwrap_or(0),
+            crate::style::date(due)
+        ));
+    }
+    Ok(until)
+}
 
-        // Complete the task
-        complete_task(&db, 1).unwrap();
+/// Push a task's due date back by a duration or to a later date.
+pub fn snooze_task(db: &Database, id: i32, when: &str) -> Result<()> {
+    let task = db.get_task_by_id(id)?.ok_or(TodoError::NotFound(id))?;
 
-        let task = db.get_task_by_id(1).unwrap().unwrap();
-        assert!(task.completed);
-    }
+    let until = snooze_until(&task, when)?;
+    db.snooze_task(id, until)?;
 
-    #[test]
-    fn test_complete_nonexistent_task() {
-        let (db, _temp_file) = create_test_db();
+    outln!(
+        "💤 Task {} snoozed until {} {}",
+        id,
+        crate::style::date(until),
+        until.format("%H:%M")
+    );
+    Ok(())
+}
 
-        let result = complete_task(&db, 999);
-        assert!(result.is_err());
-        assert!(result.unwrap_err().to_string().contains("not found"));
+/// Show a task's estimate, or set it from a duration such as `45m` or `2h`,
+/// or remove it with `clear`.
+pub fn estimate_task(db: &Database, id: i32, duration: Option<&str>, clear: bool) -> Result<()> {
+    let task = db.get_task_by_id(id)?.ok_or(TodoError::NotFound(id))?;
+
+    if clear {
+        db.set_estimate(id, None)?;
+        outln!("⏱️  Task {} has no estimate now", id);
+        return Ok(());
     }
+    let Some(duration) = duration else {
+        match db.estimates()?.get(&id) {
+            Some(&minutes) => outln!(
+                "⏱️  Task {} ({}) is estimated at {}",
+                id,
+                task.title,
+                duration_text(Duration::minutes(minutes))
+            ),
+            None => outln!(
+                "⏱️  Task {} has no estimate; `todo schedule` counts it as {}",
+                id,
+                duration_text(Duration::minutes(crate::schedule::DEFAULT_ESTIMATE_MINUTES))
+            ),
+        }
+        return Ok(());
+    };
 
-    #[test]
-    fn test_delete_task() {
-        let (db, _temp_file) = create_test_db();
+    let minutes = parse_estimate(duration)?;
+    db.set_estimate(id, Some(minutes))?;
+    outln!(
+        "⏱️  Task {} estimated at {}",
+        id,
+        duration_text(Duration::minutes(minutes))
+    );
+    Ok(())
+}
 
-        // Add a task first
-        let priority = crate::Priority::Medium;
-        add_task(&db, "Test task", None, None, &priority).unwrap();
+/// Minutes in an estimate such as `45m` or `2h`.
+pub fn parse_estimate(text: &str) -> Result<i64> {
+    let minutes = match crate::dates::parse_duration(text) {
+        Some((count, "minute")) => i64::from(count),
+        Some((count, "hour")) => i64::from(count) * 60,
+        _ => {
+            return Err(TodoError::Invalid(format!(
+                "Invalid estimate `{}`: use minutes or hours, e.g. 45m or 2h",
+                text
+            ))
+            .into())
+        }
+    };
+    if minutes == 0 {
+        return Err(TodoError::Invalid("An estimate must be longer than zero".to_string()).into());
+    }
+    Ok(minutes)
+}
 
-        // Delete the task
-        delete_task(&db, 1).unwrap();
+/// Propose due dates for the week for tasks that have none (see
+/// `schedule`), then write them once accepted, or right away with `yes`.
+pub fn schedule_week(db: &Database, hours_per_day: i64, yes: bool) -> Result<()> {
+    if hours_per_day <= 0 {
+        return Err(anyhow::anyhow!("Working hours per day must be positive"));
+    }
+    let tasks = db.get_tasks(&TaskFilter::default())?;
+    let estimates = db.estimates()?;
+    let today = crate::zone::day_of(Utc::now());
+    let mut plan = crate::schedule::plan(today, hours_per_day * 60, &tasks, &estimates);
+    if plan.placements().is_empty() && plan.unplaced.is_empty() {
+        outln!("📅 Nothing to schedule: every pending task has a due date.");
+        return Ok(());
+    }
 
-        // Verify task is deleted
-        assert!(db.get_task_by_id(1).unwrap().is_none());
+    let accepted = if yes {
+        outln!("{}", render_plan(&plan, &tasks));
+        true
+    } else {
+        let stdin = std::io::stdin();
+        review_plan(&mut plan, &tasks, &mut stdin.lock(), &mut std::io::stdout())?
+    };
+    if !accepted {
+        outln!("📅 Plan rejected; nothing was changed.");
+        return Ok(());
     }
 
-    #[test]
-    fn test_update_task() {
-        let (db, _temp_file) = create_test_db();
+    let edits: Vec<(i32, Task)> = plan
+        .placements()
+        .into_iter()
+        .filter_map(|(id, date)| {
+            let mut task = tasks.iter().find(|task| task.id == Some(id))?.clone();
+            // Like `--due today`, today's tasks are due at the end of the
+            // day rather than already overdue
+            task.due_date = Some(if date == today {
+                crate::zone::end_of(date)
+            } else {
+                crate::zone::start_of(date)
+            });
+            Some((id, task))
+        })
+        .collect();
+    db.edit_tasks(&edits)?;
+    outln!(
+        "📅 Scheduled {} task{} (`todo undo` takes them back)",
+        edits.len(),
+        if edits.len() == 1 { "" } else { "s" }
+    );
+    Ok(())
+}
 
-        // Add a task first
-        let priority = crate::Priority::Medium;
-        add_task(&db, "Original title", None, None, &priority).unwrap();
+fn render_plan(plan: &crate::schedule::Plan, tasks: &[Task]) -> String {
+    plan.render(|id| {
+        tasks
+            .iter()
+            .find(|task| task.id == Some(id))
+            .map(|task| task.title.clone())
+            .unwrap_or_default()
+    })
+}
 
-        // Update the task
-        let new_priority = crate::Priority::High;
-        update_task(
-            &db,
-            1,
+/// `todo plan`: the pending tasks to work on within a time budget such as
+/// `4h` (see `schedule::budget`). Tasks waiting on open subtasks are left
+/// out, as in `todo next`.
+pub fn plan_budget(db: &Database, budget: &str, due_soon_window: Duration) -> Result<()> {
+    let minutes = parse_estimate(budget).map_err(|_| {
+        TodoError::Invalid(format!(
+            "Invalid time budget `{}`: use minutes or hours, e.g. 90m or 4h",
+            budget
+        ))
+    })?;
+    let tasks = db.get_tasks(&TaskFilter::default())?;
+    let parents: std::collections::HashSet<i32> =
+        tasks.iter().filter_map(|task| task.parent_id).collect();
+    let tasks: Vec<Task> = tasks
+        .into_iter()
+        .filter(|task| !task.id.is_some_and(|id| parents.contains(&id)))
+        .collect();
+    let picked = crate::schedule::budget(
+        &tasks,
+        &db.estimates()?,
+        minutes,
+        Utc::now(),
+        due_soon_window,
+    );
+    if picked.is_empty() {
+        outln!(
+            "🗓️  Nothing fits in {}.",
+            duration_text(Duration::minutes(minutes))
+        );
+        return Ok(());
+    }
+
+    let planned: i64 = picked.iter().map(|(_, minutes)| minutes).sum();
+    outln!(
+        "🗓️  {} of work for {}:",
+        duration_text(Duration::minutes(planned)),
+        duration_text(Duration::minutes(minutes))
+    );
+    for (id, estimate) in &picked {
+        if let Some(task) = tasks.iter().find(|task| task.id == Some(*id)) {
+            outln!(
+                "  {:>7}  {}",
+                duration_text(Duration::minutes(*estimate)),
+                task.display_summary(due_soon_window)
+            );
+        }
+    }
+    let left_out = tasks.len() - picked.len();
+    if left_out > 0 {
+        outln!(
+            "{} more task{} did not fit.",
+            left_out,
+            if left_out == 1 { "" } else { "s" }
+        );
+    }
+    Ok(())
+}
+
+/// Show the plan until it is accepted (true) or rejected (false). Answers
+/// like `12 wed` move task 12 to Wednesday, and `12 -` unschedules it.
+fn review_plan(
+    plan: &mut crate::schedule::Plan,
+    tasks: &[Task],
+    input: &mut impl std::io::BufRead,
+    output: &mut impl std::io::Write,
+) -> Result<bool> {
+    writeln!(output, "{}", render_plan(plan, tasks))?;
+    loop {
+        write!(
+            output,
+            "[a]ccept, [r]eject, or move a task with `ID DAY` (`ID -` leaves it unscheduled)? "
+        )?;
+        output.flush()?;
+
+        let mut answer = String::new();
+        if input.read_line(&mut answer)? == 0 {
+            writeln!(output)?;
+            return Ok(false);
+        }
+        let words: Vec<&str> = answer.split_whitespace().collect();
+        match words.as_slice() {
+            ["a" | "accept" | "y" | "yes"] => return Ok(true),
+            ["r" | "reject" | "q" | "n" | "no"] => return Ok(false),
+            [id, day] => {
+                let Ok(id) = id.parse::<i32>() else {
+                    writeln!(output, "`{}` is not a task ID", id)?;
+                    continue;
+                };
+                let date = match *day {
+                    "-" => None,
+                    day => match plan.find_day(day) {
+                        Some(date) => Some(date),
+                        None => {
+                            writeln!(output, "`{}` is not a working day in this plan", day)?;
+                            continue;
+                        }
+                    },
+                };
+                if plan.move_task(id, date) {
+                    writeln!(output, "{}", render_plan(plan, tasks))?;
+                } else {
+                    writeln!(output, "Task {} is not part of this plan", id)?;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Move a task into the someday bucket, or back out with `promote`.
+/// Without an ID, list the bucket.
+pub fn someday(db: &Database, id: Option<i32>, promote: bool) -> Result<()> {
+    let Some(id) = id else {
+        return list_someday(db);
+    };
+    if !db.task_exists(id)? {
+        return Err(TodoError::NotFound(id).into());
+    }
+
+    db.set_someday(id, !promote)?;
+    if promote {
+        outln!("🚀 Task {} promoted back to your active list", id);
+    } else {
+        outln!("🌱 Task {} moved to someday/maybe", id);
+    }
+    Ok(())
+}
+
+fn list_someday(db: &Database) -> Result<()> {
+    let filter = TaskFilter {
+        someday: SomedayFilter::Only,
+        ..TaskFilter::default()
+    };
+    let tasks = db.get_tasks(&filter)?;
+
+    if tasks.is_empty() {
+        outln!("🌱 Nothing in someday/maybe.");
+        return Ok(());
+    }
+
+    outln!("🌱 Someday / maybe:");
+    outln!("{}", "─".repeat(80));
+
+    let window = Duration::hours(crate::models::DEFAULT_DUE_SOON_HOURS);
+    for task in &tasks {
+        let snoozes = db.get_snooze_count(task.id.unwrap())?;
+        let suffix = if snoozes > 0 {
+            format!(" (snoozed {}x)", snoozes)
+        } else {
+            String::new()
+        };
+        outln!("{}{}", task.display_summary(window), suffix);
+    }
+
+    outln!("{}", "─".repeat(80));
+    outln!("Total: {} tasks", tasks.len());
+    Ok(())
+}
+
+/// Someday tasks come up in `todo review` once this many days have passed
+/// since they were parked or last kept.
+pub const SOMEDAY_REVIEW_DAYS: i64 = 30;
+
+#[derive(Debug, Default, PartialEq)]
+struct ReviewSummary {
+    promoted: usize,
+    dropped: usize,
+    kept: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ReviewDecision {
+    Promote,
+    Drop,
+    Keep,
+}
+
+/// `todo review` decisions given as flags rather than at the prompt.
+#[derive(Debug, Default)]
+pub struct ReviewDecisions {
+    pub promote: Vec<i32>,
+    pub drop: Vec<i32>,
+    pub keep: Vec<i32>,
+}
+
+impl ReviewDecisions {
+    fn iter(&self) -> impl Iterator<Item = (i32, ReviewDecision)> + '_ {
+        self.promote
+            .iter()
+            .map(|&id| (id, ReviewDecision::Promote))
+            .chain(self.drop.iter().map(|&id| (id, ReviewDecision::Drop)))
+            .chain(self.keep.iter().map(|&id| (id, ReviewDecision::Keep)))
+    }
+}
+
+/// Interactive review. Currently walks the someday items that are due for
+/// another look and asks whether to promote, drop or keep each one. With
+/// `list` it only prints them, and `decisions` settles items without asking.
+pub fn review(db: &Database, all: bool, list: bool, decisions: &ReviewDecisions) -> Result<()> {
+    if decisions.iter().next().is_some() {
+        return review_with_flags(db, decisions);
+    }
+    let cutoff = if all {
+        Utc::now() + Duration::days(1)
+    } else {
+        Utc::now() - Duration::days(SOMEDAY_REVIEW_DAYS)
+    };
+    let tasks = db.get_someday_due_for_review(cutoff)?;
+
+    if tasks.is_empty() {
+        outln!("✨ Nothing to review.");
+        return Ok(());
+    }
+    if list {
+        let window = Duration::hours(crate::models::DEFAULT_DUE_SOON_HOURS);
+        outln!("🔍 {} someday/maybe tasks are due for review:", tasks.len());
+        for task in &tasks {
+            outln!("{}", task.display_summary(window));
+        }
+        return Ok(());
+    }
+
+    outln!("🔍 {} someday/maybe tasks are due for review", tasks.len());
+    let stdin = std::io::stdin();
+    let summary = review_someday(db, &tasks, &mut stdin.lock(), &mut std::io::stdout())?;
+    outln!(
+        "Promoted: {}, dropped: {}, kept: {}",
+        summary.promoted,
+        summary.dropped,
+        summary.kept
+    );
+    Ok(())
+}
+
+/// Apply review decisions from flags, after checking every ID is in the
+/// someday bucket.
+fn review_with_flags(db: &Database, decisions: &ReviewDecisions) -> Result<()> {
+    for (id, _) in decisions.iter() {
+        if !db.get_task_by_id(id)?.is_some_and(|task| task.someday) {
+            return Err(anyhow::anyhow!(
+                "Task {} is not in the someday/maybe bucket",
+                id
+            ));
+        }
+    }
+    let mut summary = ReviewSummary::default();
+    for (id, decision) in decisions.iter() {
+        decide(db, id, decision, &mut summary)?;
+    }
+    outln!(
+        "Promoted: {}, dropped: {}, kept: {}",
+        summary.promoted,
+        summary.dropped,
+        summary.kept
+    );
+    Ok(())
+}
+
+fn decide(
+    db: &Database,
+    id: i32,
+    decision: ReviewDecision,
+    summary: &mut ReviewSummary,
+) -> Result<()> {
+    match decision {
+        ReviewDecision::Promote => {
+            db.set_someday(id, false)?;
+            summary.promoted += 1;
+        }
+        ReviewDecision::Drop => {
+            db.delete_task(id)?;
+            summary.dropped += 1;
+        }
+        ReviewDecision::Keep => {
+            db.mark_someday_reviewed(id)?;
+            summary.kept += 1;
+        }
+    }
+    Ok(())
+}
+
+fn review_someday(
+    db: &Database,
+    tasks: &[Task],
+    input: &mut impl std::io::BufRead,
+    output: &mut impl std::io::Write,
+) -> Result<ReviewSummary> {
+    let mut summary = ReviewSummary::default();
+    let window = Duration::hours(crate::models::DEFAULT_DUE_SOON_HOURS);
+
+    for task in tasks {
+        let id = task.id.unwrap();
+        writeln!(output, "{}", task.display_summary(window))?;
+        loop {
+            write!(output, "  [p]romote, [d]rop, [k]eep, [q]uit? ")?;
+            output.flush()?;
+
+            let mut answer = String::new();
+            if input.read_line(&mut answer)? == 0 {
+                return Ok(summary);
+            }
+            let decision = match answer.trim() {
+                "p" => ReviewDecision::Promote,
+                "d" => ReviewDecision::Drop,
+                "k" | "" => ReviewDecision::Keep,
+                "q" => return Ok(summary),
+                _ => continue,
+            };
+            decide(db, id, decision, &mut summary)?;
+            break;
+        }
+    }
+    Ok(summary)
+}
+
+#[derive(Debug, Default)]
+struct Triage {
+    /// Tasks whose priority changed, with the new priority.
+    edits: Vec<(i32, Task)>,
+    deletes: Vec<i32>,
+    skipped: usize,
+}
+
+/// Interactive re-triage: steps through the pending tasks matching
+/// `filter`, asking for a new priority (or delete/skip) for each, and saves
+/// every decision at the end in one transaction. With `list` it only prints
+/// the tasks, to be changed with `todo update` and `todo delete`.
+pub fn reprioritize(
+    db: &Database,
+    filter: crate::TriageFilter,
+    due_soon_window: Duration,
+    list: bool,
+) -> Result<()> {
+    let tasks: Vec<Task> = match filter {
+        crate::TriageFilter::Stale => {
+            db.get_stale_tasks(Utc::now() - Duration::days(crate::maintenance::STALE_AFTER_DAYS))?
+        }
+        _ => db
+            .get_tasks(&TaskFilter::default())?
+            .into_iter()
+            .filter(|task| match filter {
+                crate::TriageFilter::Overdue => task.is_overdue(),
+                crate::TriageFilter::DueSoon => task.is_due_soon(due_soon_window),
+                crate::TriageFilter::NoDue => task.due_date.is_none(),
+                crate::TriageFilter::All | crate::TriageFilter::Stale => true,
+            })
+            .collect(),
+    };
+
+    if tasks.is_empty() {
+        outln!("✨ No tasks to triage.");
+        return Ok(());
+    }
+
+    if list {
+        outln!("🗂️  {} tasks to triage:", tasks.len());
+        for task in &tasks {
+            outln!("{}", task.display_summary(due_soon_window));
+        }
+        return Ok(());
+    }
+    outln!("🗂️  {} tasks to triage", tasks.len());
+    let stdin = std::io::stdin();
+    let triage = triage_tasks(&tasks, &mut stdin.lock(), &mut std::io::stdout())?;
+    if triage.edits.is_empty() && triage.deletes.is_empty() {
+        outln!("No changes.");
+        return Ok(());
+    }
+
+    db.apply_changes(&triage.edits, &triage.deletes)?;
+    outln!(
+        "Re-prioritized: {}, deleted: {}, skipped: {} (`todo undo` reverts this session)",
+        triage.edits.len(),
+        triage.deletes.len(),
+        triage.skipped
+    );
+    Ok(())
+}
+
+fn triage_tasks(
+    tasks: &[Task],
+    input: &mut impl std::io::BufRead,
+    output: &mut impl std::io::Write,
+) -> Result<Triage> {
+    let mut triage = Triage::default();
+    let window = Duration::hours(crate::models::DEFAULT_DUE_SOON_HOURS);
+
+    for task in tasks {
+        let id = task.id.unwrap();
+        writeln!(output, "{}", task.display_summary(window))?;
+        loop {
+            write!(
+                output,
+                "  [l]ow, [m]edium, [h]igh, [s]kip, [d]elete, [q]uit? "
+            )?;
+            output.flush()?;
+
+            let mut answer = String::new();
+            if input.read_line(&mut answer)? == 0 {
+                return Ok(triage);
+            }
+            let priority = match answer.trim() {
+                "l" => 0,
+                "m" => 1,
+                "h" => 2,
+                "s" | "" => {
+                    triage.skipped += 1;
+                    break;
+                }
+                "d" => {
+                    triage.deletes.push(id);
+                    break;
+                }
+                "q" => return Ok(triage),
+                _ => continue,
+            };
+            if priority == task.priority && !task.priority_inherited {
+                triage.skipped += 1;
+            } else {
+                let mut edited = task.clone();
+                edited.priority = priority;
+                edited.priority_inherited = false;
+                edited.updated_at = Utc::now();
+                triage.edits.push((id, edited));
+            }
+            break;
+        }
+    }
+    Ok(triage)
+}
+
+pub fn import_habitica(db: &Database, user_id: Option<&str>, force: bool) -> Result<()> {
+    use crate::habitica::{self, Credentials};
+    use crate::progress::Progress;
+
+    let credentials = match user_id {
+        Some(user_id) => {
+            let credentials = Credentials {
+                user_id: user_id.to_string(),
+                api_token: Credentials::read_token()?,
+            };
+            credentials.save()?;
+            outln!("🔑 Habitica credentials saved to the system keyring");
+            credentials
+        }
+        None => Credentials::load()?,
+    };
+
+    let spinner = Progress::spinner("Fetching tasks from Habitica");
+    let remote_tasks = habitica::fetch_tasks(&credentials)?;
+    spinner.finish();
+    let bar = Progress::bar("Importing from Habitica", remote_tasks.len() as u64);
+    let (imported, skipped) = db.with_actor(crate::habitica::ACTOR, || {
+        import_habitica_tasks(db, &remote_tasks, &bar, force)
+    })?;
+    bar.finish();
+
+    crate::logging::info(
+        habitica::ACTOR,
+        format!(
+            "imported {} tasks, {} already imported, pending or completed",
+            imported, skipped
+        ),
+    );
+    outln!(
+        "📥 Imported {} tasks from Habitica ({} already imported, pending or completed)",
+        imported,
+        skipped
+    );
+    Ok(())
+}
+
+fn import_habitica_tasks(
+    db: &Database,
+    remote_tasks: &[crate::habitica::HabiticaTask],
+    progress: &crate::progress::Progress,
+    force: bool,
+) -> Result<(usize, usize)> {
+    let mut imported = 0;
+    let mut skipped = 0;
+
+    for remote in remote_tasks {
+        crate::shutdown::check()?;
+        progress.inc(1);
+        if remote.completed
+            || db
+                .find_linked_task(crate::habitica::LINK_SOURCE, &remote.id)?
+                .is_some()
+        {
+            skipped += 1;
+            continue;
+        }
+
+        // A task that is already pending gets linked instead of duplicated
+        let task = remote.to_task();
+        let duplicate = if force {
+            None
+        } else {
+            db.find_pending_duplicate(&task)?
+        };
+        if let Some(duplicate) = duplicate {
+            db.add_task_link(
+                duplicate.id.unwrap(),
+                crate::habitica::LINK_SOURCE,
+                &remote.id,
+            )?;
+            skipped += 1;
+            continue;
+        }
+
+        // Together, so a task is never left without the link that stops
+        // the next import from adding it again
+        let tx = db.transaction()?;
+        let id = db.add_task(&task)?;
+        db.add_task_link(id, crate::habitica::LINK_SOURCE, &remote.id)?;
+        tx.commit()?;
+        imported += 1;
+    }
+
+    Ok((imported, skipped))
+}
+
+/// Import tasks from a file in chunks of `import::CHUNK_SIZE`, recording
+/// progress after each one. With `resume`, the interrupted import recorded
+/// in the database is picked up where it stopped; the format, path and ID
+/// assignments come from that record.
+pub fn import_file(db: &Database, options: &crate::import::ImportOptions) -> Result<()> {
+    use crate::db::META_IMPORT_PROGRESS;
+    use crate::import::{self, FileFormat, Progress};
+    use std::collections::HashSet;
+
+    let pending: Option<Progress> = db
+        .get_meta(META_IMPORT_PROGRESS)?
+        .and_then(|value| serde_json::from_str(&value).ok());
+
+    let (format, path) = match (options.resume, &pending) {
+        (true, Some(progress)) => (progress.format, progress.path.clone()),
+        (true, None) => return Err(anyhow::anyhow!("There is no interrupted import to resume")),
+        (false, _) => {
+            let path = options
+                .path
+                .ok_or_else(|| anyhow::anyhow!("Pass the file to import"))?;
+            let format = options
+                .format
+                .or_else(|| FileFormat::from_extension(path))
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "Cannot tell the format of {} from its extension. Pass --from",
+                        path.display()
+                    )
+                })?;
+            (format, std::path::absolute(path)?)
+        }
+    };
+
+    let contents = std::fs::read(&path)
+        .map_err(|e| anyhow::anyhow!("Cannot read {}: {}", path.display(), e))?;
+    let checksum = import::checksum(&contents);
+    let resumed = match &pending {
+        Some(progress) if options.resume => {
+            if progress.checksum != checksum {
+                return Err(anyhow::anyhow!(
+                    "{} changed since the import was interrupted. Run the import again without --resume",
+                    path.display()
+                ));
+            }
+            Some(progress)
+        }
+        Some(progress) if progress.path == path && progress.checksum == checksum => {
+            return Err(anyhow::anyhow!(
+                "An import of {} was interrupted after {} of {} tasks. Run `todo import --resume` to finish it",
+                path.display(),
+                progress.done,
+                progress.total
+            ));
+        }
+        _ => None,
+    };
+
+    let contents = String::from_utf8(contents)
+        .map_err(|_| anyhow::anyhow!("{} is not valid UTF-8", path.display()))?;
+    let mut tasks = import::parse(format, &contents, options.default_priority)?;
+
+    let remap = match resumed {
+        Some(progress) => progress.remap.clone(),
+        None if options.interactive => {
+            let mut remap = options.remap.clone();
+            let projects = db.get_projects()?.into_iter().map(|(name, ..)| name);
+            let tags = db.tag_names()?;
+            let stdin = std::io::stdin();
+            ask_remap(
+                &tasks,
+                &projects
+                    .chain(tags.iter().map(|tag| format!("#{}", tag)))
+                    .collect(),
+                &mut remap,
+                &mut stdin.lock(),
+                &mut std::io::stdout(),
+            )?;
+            remap
+        }
+        None => options.remap.clone(),
+    };
+    remap.apply(&mut tasks);
+
+    let existing: HashSet<i32> = db.task_ids()?.into_iter().collect();
+    let mut progress = match resumed {
+        Some(progress) => progress.clone(),
+        None => {
+            let file_ids = tasks.iter().filter_map(|(task, _)| task.id);
+            let mut taken: Vec<i32> = file_ids
+                .clone()
+                .filter(|id| existing.contains(id))
+                .collect();
+            taken.sort_unstable();
+            taken.dedup();
+            let highest = existing.iter().copied().chain(file_ids).max().unwrap_or(0);
+            Progress {
+                format,
+                path,
+                checksum,
+                done: 0,
+                total: tasks.len(),
+                on_conflict: options.on_conflict,
+                taken,
+                next_id: highest + 1,
+                force: options.force,
+                remap,
+            }
+        }
+    };
+    let taken: HashSet<i32> = progress.taken.iter().copied().collect();
+    let changes = import::assign_ids(
+        &mut tasks,
+        &taken,
+        progress.next_id,
+        progress.on_conflict,
+        |id| existing.contains(&id),
+    )?;
+
+    if options.dry_run {
+        let mut skipped = 0;
+        for (task, external_id) in &tasks {
+            let linked = match external_id {
+                Some(external_id) => db
+                    .find_linked_task(format.link_source(), external_id)?
+                    .is_some(),
+                None => false,
+            };
+            let merged = task.id.is_some_and(|id| existing.contains(&id));
+            let duplicate = !options.force
+                && !merged
+                && !task.completed
+                && db.find_pending_duplicate(task)?.is_some();
+            if linked || duplicate {
+                skipped += 1;
+            }
+        }
+        outln!(
+            "🔍 Dry run: would import {} tasks from {} ({} already imported or pending)",
+            tasks.len() - skipped,
+            format.name(),
+            skipped
+        );
+        print_remap(&progress.remap);
+        print_id_changes(&changes);
+        return Ok(());
+    }
+
+    if progress.done > 0 {
+        outln!(
+            "⏩ Resuming import of {} at task {} of {}",
+            progress.path.display(),
+            progress.done + 1,
+            tasks.len()
+        );
+    }
+    let start = progress.done;
+    let bar = crate::progress::Progress::bar("Importing", tasks.len() as u64);
+    bar.set_position(start as u64);
+    let actor = format!("import:{}", progress.format.link_source());
+    let imported = db.with_actor(&actor, || {
+        import_chunks(db, &tasks, &mut progress, import::CHUNK_SIZE, |done| {
+            bar.set_position(done as u64)
+        })
+    })?;
+    bar.finish();
+    db.delete_meta(META_IMPORT_PROGRESS)?;
+
+    crate::logging::info(
+        &actor,
+        format!(
+            "imported {} tasks from {}, {} already imported or pending",
+            imported,
+            progress.path.display(),
+            tasks.len() - start - imported
+        ),
+    );
+    outln!(
+        "📥 Imported {} tasks from {} ({} already imported or pending)",
+        imported,
+        format.name(),
+        tasks.len() - start - imported
+    );
+    print_remap(&progress.remap);
+    print_id_changes(&changes);
+    Ok(())
+}
+
+/// Ask what to do with each project and tag in `tasks` that isn't in
+/// `known` (where tags start with `#`) and that `remap` doesn't already
+/// cover. An empty answer keeps the name, `-` drops it and anything else
+/// is the name to use instead.
+fn ask_remap(
+    tasks: &[crate::import::ImportedTask],
+    known: &std::collections::HashSet<String>,
+    remap: &mut crate::import::Remap,
+    input: &mut impl std::io::BufRead,
+    output: &mut impl std::io::Write,
+) -> Result<()> {
+    use std::collections::BTreeMap;
+
+    let mut projects: BTreeMap<&str, usize> = BTreeMap::new();
+    let mut tags: BTreeMap<&str, usize> = BTreeMap::new();
+    for (task, _) in tasks {
+        if let Some(project) = &task.project {
+            *projects.entry(project).or_default() += 1;
+        }
+        for tag in &task.tags {
+            *tags.entry(tag).or_default() += 1;
+        }
+    }
+    if remap.project.is_some() {
+        projects.clear();
+    }
+
+    let questions = projects
+        .into_iter()
+        .map(|(name, count)| (false, name, count))
+        .chain(tags.into_iter().map(|(name, count)| (true, name, count)));
+    for (is_tag, name, count) in questions {
+        let (kind, shown) = if is_tag {
+            ("Tag", format!("#{}", name))
+        } else {
+            ("Project", name.to_string())
+        };
+        let covered = if is_tag {
+            remap.apply_tag(name).as_deref() != Some(name)
+        } else {
+            remap.projects.contains_key(name)
+        };
+        if covered || known.contains(&shown) {
+            continue;
+        }
+        write!(
+            output,
+            "{} {} ({} task{}) is new. Name to use instead, - to drop it, Enter to keep it: ",
+            kind,
+            shown,
+            count,
+            if count == 1 { "" } else { "s" }
+        )?;
+        output.flush()?;
+
+        let mut answer = String::new();
+        if input.read_line(&mut answer)? == 0 {
+            writeln!(output)?;
+            break;
+        }
+        let mapped = match answer.trim().trim_start_matches('#') {
+            "" => continue,
+            "-" => None,
+            mine => Some(mine.to_string()),
+        };
+        if is_tag {
+            remap.tags.insert(name.to_string(), mapped);
+        } else {
+            remap.projects.insert(name.to_string(), mapped);
+        }
+    }
+    Ok(())
+}
+
+/// Report how projects and tags were remapped, if they were.
+fn print_remap(remap: &crate::import::Remap) {
+    if remap.is_empty() {
+        return;
+    }
+    let change = |kind: &str, theirs: &str, mine: &Option<String>| match mine {
+        Some(mine) => format!("{} {} → {}", kind, theirs, mine),
+        None => format!("{} {} dropped", kind, theirs),
+    };
+    let mut changes: Vec<String> = remap
+        .project
+        .iter()
+        .map(|project| format!("every task into project {}", project))
+        .collect();
+    if remap.project.is_none() {
+        changes.extend(
+            remap
+                .projects
+                .iter()
+                .map(|(theirs, mine)| change("project", theirs, mine)),
+        );
+    }
+    changes.extend(
+        remap
+            .tags
+            .iter()
+            .map(|(theirs, mine)| change("tag", theirs, mine)),
+    );
+    outln!("🗂️  Remapped {}", changes.join(", "));
+}
+
+/// Report merged and renumbered IDs, listing at most a handful of each.
+fn print_id_changes(changes: &crate::import::IdChanges) {
+    const SHOWN: usize = 10;
+    let more = |count: usize| {
+        if count > SHOWN {
+            format!(" and {} more", count - SHOWN)
+        } else {
+            String::new()
+        }
+    };
+
+    if !changes.merged.is_empty() {
+        let ids: Vec<String> = changes
+            .merged
+            .iter()
+            .take(SHOWN)
+            .map(|id| format!("#{}", id))
+            .collect();
+        outln!(
+            "🔀 Merged into existing tasks: {}{}",
+            ids.join(", "),
+            more(changes.merged.len())
+        );
+    }
+    if !changes.renumbered.is_empty() {
+        let pairs: Vec<String> = changes
+            .renumbered
+            .iter()
+            .take(SHOWN)
+            .map(|(old, new)| format!("#{} → #{}", old, new))
+            .collect();
+        outln!(
+            "🔢 Renumbered: {}{}",
+            pairs.join(", "),
+            more(changes.renumbered.len())
+        );
+    }
+}
+
+/// Commit `tasks[progress.done..]` chunk by chunk, advancing `progress`
+/// with each one. Returns how many tasks were written.
+fn import_chunks(
+    db: &Database,
+    tasks: &[crate::import::ImportedTask],
+    progress: &mut crate::import::Progress,
+    chunk_size: usize,
+    on_chunk: impl Fn(usize),
+) -> Result<usize> {
+    let mut imported = 0;
+    while progress.done < tasks.len() {
+        // Each chunk commits together with the progress record, so stopping
+        // between chunks leaves an import that `--resume` can finish
+        crate::shutdown::check().map_err(|interrupted| {
+            anyhow::Error::new(interrupted).context(format!(
+                "Import stopped after {} of {} tasks. Run `todo import --resume` to finish it",
+                progress.done,
+                tasks.len()
+            ))
+        })?;
+        let end = (progress.done + chunk_size).min(tasks.len());
+        let chunk = &tasks[progress.done..end];
+        let record = serde_json::to_string(&crate::import::Progress {
+            done: end,
+            ..progress.clone()
+        })?;
+        imported += db.import_chunk(
+            chunk,
+            progress.format.link_source(),
+            &record,
+            !progress.force,
+        )?;
+        progress.done = end;
+        on_chunk(end);
+    }
+    Ok(imported)
+}
+
+/// `todo obsidian sync`. With `plan` the changes are listed first and
+/// made only with `apply`, or when the answer to a prompt is yes.
+pub fn obsidian_sync(
+    db: &Database,
+    vault: &std::path::Path,
+    plan: bool,
+    apply: bool,
+) -> Result<()> {
+    if !vault.is_dir() {
+        return Err(anyhow::anyhow!(
+            "Vault {} is not a directory",
+            vault.display()
+        ));
+    }
+
+    let progress = crate::progress::Progress::spinner("Syncing");
+    let changes = crate::obsidian::plan(db, vault, &progress)?;
+    progress.finish();
+    if plan {
+        outln!("📋 Plan for Obsidian vault {}", vault.display());
+        for change in &changes {
+            outln!("  {}", change.describe());
+        }
+        outln!("Plan: {}.", crate::obsidian::summarize(&changes));
+        if changes.is_empty() {
+            return Ok(());
+        }
+        if !apply {
+            use std::io::IsTerminal;
+            let stdin = std::io::stdin();
+            let approved = stdin.is_terminal()
+                && confirm(
+                    "Apply these changes?",
+                    &mut stdin.lock(),
+                    &mut std::io::stdout(),
+                )?;
+            if !approved {
+                outln!("Nothing was changed; pass --apply to make these changes.");
+                return Ok(());
+            }
+        }
+    }
+    let progress = crate::progress::Progress::spinner("Syncing");
+    let report = crate::obsidian::apply(db, vault, &changes, &progress)?;
+    progress.finish();
+    crate::logging::info(
+        crate::obsidian::ACTOR,
+        format!(
+            "vault {}: imported {}, linked {}, completed {}, checked off {}",
+            vault.display(),
+            report.imported,
+            report.linked,
+            report.completed_locally,
+            report.checked_off
+        ),
+    );
+    outln!("🔄 Synced with Obsidian vault {}", vault.display());
+    outln!("  Imported: {}", report.imported);
+    outln!("  Linked to pending tasks: {}", report.linked);
+    outln!("  Completed from vault: {}", report.completed_locally);
+    outln!("  Checked off in notes: {}", report.checked_off);
+    Ok(())
+}
+
+/// `todo sync`: merge the tasks with a git repository and, unless
+/// `offline`, with its `remote`.
+pub fn git_sync(db: &Database, repo: &std::path::Path, remote: &str, offline: bool) -> Result<()> {
+    let progress = crate::progress::Progress::spinner("Syncing");
+    let report = crate::git_sync::sync(db, repo, remote, !offline, &progress)?;
+    progress.finish();
+    crate::logging::info(
+        crate::git_sync::ACTOR,
+        format!(
+            "repository {}: imported {}, updated {}, removed {}, wrote {} files",
+            repo.display(),
+            report.imported,
+            report.updated,
+            report.removed,
+            report.written
+        ),
+    );
+    if report.pushed {
+        outln!("🔄 Synced with {} of {}", remote, repo.display());
+    } else {
+        outln!("🔄 Synced with {}", repo.display());
+    }
+    outln!("  Imported: {}", report.imported);
+    outln!("  Updated from elsewhere: {}", report.updated);
+    outln!("  Deleted elsewhere: {}", report.removed);
+    outln!("  Task files changed: {}", report.written);
+    for reason in &report.skipped {
+        errln!("⚠️  {}", reason);
+    }
+    Ok(())
+}
+
+/// The ID of the task `task` names. A UUID prefix must match exactly one
+/// task, trashed ones included.
+pub fn resolve_task(db: &Database, task: &crate::TaskRef) -> Result<i32> {
+    let prefix = match task {
+        crate::TaskRef::Id(id) => return Ok(*id),
+        crate::TaskRef::Uuid(prefix) => prefix,
+    };
+    match db.find_tasks_by_uuid(prefix)?.as_slice() {
+        [] => {
+            Err(TodoError::Missing(format!("No task has a UUID starting with '{}'", prefix)).into())
+        }
+        [id] => Ok(*id),
+        ids => Err(TodoError::Invalid(format!(
+            "UUID prefix '{}' matches {} tasks; give more of it",
+            prefix,
+            ids.len()
+        ))
+        .into()),
+    }
+}
+
+/// `resolve_task` for each of `tasks`, in order.
+pub fn resolve_tasks(db: &Database, tasks: &[crate::TaskRef]) -> Result<Vec<i32>> {
+    tasks.iter().map(|task| resolve_task(db, task)).collect()
+}
+
+/// Expand task ID arguments such as `3 5 7-10` or UUID prefixes into IDs,
+/// in the order given and without duplicates. Digits with a dash are read as
+/// a range, not a UUID prefix; a range may not reach past the highest task
+/// ID, so a typo such as `1-99999999` fails at once.
+pub fn parse_ids(db: &Database, specs: &[String]) -> Result<Vec<i32>> {
+    let mut ids = Vec::new();
+    let mut seen = std::collections::HashSet::new();
+    let mut highest = None;
+    for spec in specs {
+        let invalid = || {
+            anyhow::anyhow!(
+                "Invalid task ID '{}'. Use e.g. 3, 7-10 or the start of a task UUID",
+                spec
+            )
+        };
+        let parse = |text: &str| text.trim().parse::<i32>().map_err(|_| invalid());
+        let range = match spec.split_once('-') {
+            Some((start, end)) => parse(start).and_then(|start| Ok(start..=parse(end)?)),
+            None => parse(spec).map(|id| id..=id),
+        };
+        let range = match range {
+            Ok(range) => range,
+            Err(error) => match spec.parse::<crate::TaskRef>() {
+                Ok(task) => {
+                    let id = resolve_task(db, &task)?;
+                    id..=id
+                }
+                Err(_) => return Err(error),
+            },
+        };
+        if range.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Invalid range '{}': the start is after the end",
+                spec
+            ));
+        }
+        if range.start() != range.end() {
+            let highest = match highest {
+                Some(highest) => highest,
+                None => *highest.insert(db.max_task_id()?),
+            };
+            if *range.end() > highest {
+                return Err(anyhow::anyhow!(
+                    "Invalid range '{}': there is no task above {}",
+                    spec,
+                    highest
+                ));
+            }
+        }
+        for id in range {
+            if seen.insert(id) {
+                ids.push(id);
+            }
+        }
+    }
+    Ok(ids)
+}
+
+/// Fetch every task in `ids`, failing before anything is changed if any of
+/// them doesn't exist.
+fn get_tasks_by_ids(db: &Database, ids: &[i32]) -> Result<Vec<Task>> {
+    let mut tasks = Vec::new();
+    let mut missing = Vec::new();
+    for &id in ids {
+        match db.get_task_by_id(id)? {
+            Some(task) => tasks.push(task),
+            None => missing.push(id),
+        }
+    }
+    match missing.as_slice() {
+        [] => Ok(tasks),
+        [id] => Err(TodoError::NotFound(*id).into()),
+        _ => Err(TodoError::Missing(format!(
+            "Tasks with IDs {} not found; nothing was changed",
+            missing
+                .iter()
+                .map(|id| id.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))
+        .into()),
+    }
+}
+
+/// Complete `ids`. With `auto_complete_parents`, parents whose last open
+/// subtasks these are get completed too, in the same undo step.
+pub fn complete_task(db: &Database, ids: &[i32], auto_complete_parents: bool) -> Result<()> {
+    let tasks = get_tasks_by_ids(db, ids)?;
+    let (done, pending): (Vec<&Task>, Vec<&Task>) = tasks.iter().partition(|t| t.completed);
+    let mut pending_ids: Vec<i32> = pending.iter().filter_map(|task| task.id).collect();
+    let parents = if auto_complete_parents {
+        finished_parents(&db.get_tasks(&whole_hierarchy())?, &pending_ids)
+    } else {
+        Vec::new()
+    };
+    pending_ids.extend(&parents);
+
+    let next_ids = db.complete_tasks(&pending_ids)?;
+    for (id, next_id) in pending_ids.iter().zip(next_ids) {
+        if parents.contains(id) {
+            outln!("✅ Task {} completed: all of its subtasks are done", id);
+        } else {
+            outln!("✅ Task {} marked as completed!", id);
+        }
+        if let Some(next) = next_id
+            .map(|next_id| db.get_task_by_id(next_id))
+            .transpose()?
+            .flatten()
+        {
+            outln!(
+                "🔁 Next occurrence: task {} due {}",
+                next.id.unwrap_or(0),
+                next.due_date_text()
+            );
+        }
+    }
+    for task in done {
+        outln!("⏭️  Task {} was already completed", task.id.unwrap_or(0));
+    }
+    Ok(())
+}
+
+/// Every live task, pending or not and someday or not, to check the
+/// hierarchy against.
+fn whole_hierarchy() -> TaskFilter {
+    TaskFilter {
+        someday: crate::db::SomedayFilter::Include,
+        ..TaskFilter::any_status()
+    }
+}
+
+/// Pending parents left with no open subtasks once `ids` are completed,
+/// walking up the hierarchy as long as that holds.
+fn finished_parents(tasks: &[Task], ids: &[i32]) -> Vec<i32> {
+    let parent_of = |id: i32| {
+        tasks
+            .iter()
+            .find(|task| task.id == Some(id))
+            .and_then(|task| task.parent_id)
+    };
+    let mut done: std::collections::HashSet<i32> = tasks
+        .iter()
+        .filter(|task| task.completed)
+        .filter_map(|task| task.id)
+        .chain(ids.iter().copied())
+        .collect();
+    let mut parents = Vec::new();
+    let mut queue = ids.to_vec();
+    while let Some(id) = queue.pop() {
+        let Some(parent_id) = parent_of(id) else {
+            continue;
+        };
+        let finished = tasks
+            .iter()
+            .filter(|task| task.parent_id == Some(parent_id))
+            .all(|task| task.id.is_some_and(|id| done.contains(&id)));
+        let exists = tasks.iter().any(|task| task.id == Some(parent_id));
+        if finished && exists && done.insert(parent_id) {
+            parents.push(parent_id);
+            queue.push(parent_id);
+        }
+    }
+    parents
+}
+
+pub fn delete_task(db: &Database, ids: &[i32]) -> Result<()> {
+    get_tasks_by_ids(db, ids)?;
+
+    db.delete_tasks(ids)?;
+    match ids {
+        [id] => outln!(
+            "🗑️  Task {} moved to the trash (`todo undo` or `todo restore {}` brings it back)",
+            id,
+            id
+        ),
+        _ => outln!(
+            "🗑️  Tasks {} moved to the trash (`todo undo` brings them back)",
+            join_ids(ids)
+        ),
+    }
+    Ok(())
+}
+
+fn join_ids(ids: &[i32]) -> String {
+    ids.iter()
+        .map(|id| id.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// `todo note ID TEXT`: append a timestamped note to a task.
+pub fn add_note(db: &Database, id: i32, text: &str) -> Result<()> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err(anyhow::anyhow!("Note cannot be empty"));
+    }
+    if db.get_task_by_id(id)?.is_none() {
+        return Err(TodoError::NotFound(id).into());
+    }
+    db.add_note(id, text)?;
+    outln!("📝 Note added to task {}", id);
+    Ok(())
+}
+
+/// `todo attach`: link a URL or a file to a task. Files are stored by
+/// absolute path, so `todo open` finds them from any directory.
+pub fn attach(db: &Database, id: i32, target: &str) -> Result<()> {
+    let target = target.trim();
+    if target.is_empty() {
+        return Err(TodoError::Invalid("Attachment cannot be empty".to_string()).into());
+    }
+    if db.get_task_by_id(id)?.is_none() {
+        return Err(TodoError::NotFound(id).into());
+    }
+    let target = if crate::models::Attachment::is_link(target) {
+        target.to_string()
+    } else {
+        std::fs::canonicalize(target)
+            .map_err(|_| TodoError::Missing(format!("No file at {}", target)))?
+            .display()
+            .to_string()
+    };
+    db.add_attachment(id, &target)?;
+    outln!("📎 Attached {} to task {}", target, id);
+    Ok(())
+}
+
+/// `todo open`: open a task's attachments, or only the `number`th one as
+/// `todo show` numbers them.
+pub fn open_attachments(db: &Database, id: i32, number: Option<usize>) -> Result<()> {
+    if db.get_task_by_id(id)?.is_none() {
+        return Err(TodoError::NotFound(id).into());
+    }
+    let attachments = db.get_attachments(id)?;
+    if attachments.is_empty() {
+        return Err(TodoError::Missing(format!(
+            "Task {} has no attachments; add one with `todo attach {} URL`",
+            id, id
+        ))
+        .into());
+    }
+    let chosen: Vec<_> = match number {
+        None => attachments.iter().collect(),
+        Some(number) => vec![number
+            .checked_sub(1)
+            .and_then(|index| attachments.get(index))
+            .ok_or_else(|| {
+                TodoError::Missing(format!(
+                    "Task {} has {} attachment(s), not {}",
+                    id,
+                    attachments.len(),
+                    number
+                ))
+            })?],
+    };
+    for attachment in chosen {
+        let target = &attachment.target;
+        if !crate::models::Attachment::is_link(target) && !std::path::Path::new(target).exists() {
+            return Err(TodoError::Missing(format!("{} no longer exists", target)).into());
+        }
+        crate::opener::open(target)?;
+        outln!("🔗 Opened {}", target);
+    }
+    Ok(())
+}
+
+pub fn start_timer(db: &Database, id: i32) -> Result<()> {
+    let task = db.get_task_by_id(id)?.ok_or(TodoError::NotFound(id))?;
+    if task.completed {
+        return Err(anyhow::anyhow!("Task {} is already completed", id));
+    }
+    if let Some(entry) = db.get_running_timer()? {
+        return Err(anyhow::anyhow!(
+            "A timer is already running on task {} ({}). Stop it with `todo stop`",
+            entry.task_id,
+            duration_text(entry.duration())
+        ));
+    }
+    db.start_timer(id)?;
+    db.set_in_progress(id, true)?;
+    outln!("⏱️  Timer started on task {}: {}", id, task.title);
+    Ok(())
+}
+
+pub fn stop_timer(db: &Database) -> Result<()> {
+    match db.stop_timer()? {
+        Some(entry) => outln!(
+            "⏹️  Timer stopped on task {} after {}",
+            entry.task_id,
+            duration_text(entry.duration())
+        ),
+        None => outln!("No timer is running."),
+    }
+    Ok(())
+}
+
+/// Tracked time per day over the last `days` days, today included, with
+/// each day's tasks. Entries count towards the day they started on.
+pub fn timesheet(db: &Database, days: i64) -> Result<()> {
+    if days < 1 {
+        return Err(anyhow::anyhow!("--days must be at least 1"));
+    }
+    let first_day = crate::zone::day_of(Utc::now()) - Duration::days(days - 1);
+    let entries = db.get_time_entries_since(crate::zone::start_of(first_day))?;
+    if entries.is_empty() {
+        outln!("🕒 No time tracked in the last {} days.", days);
+        return Ok(());
+    }
+
+    let mut by_day: std::collections::BTreeMap<NaiveDate, Vec<(i32, String, Duration)>> =
+        std::collections::BTreeMap::new();
+    for (entry, title) in entries {
+        let tasks = by_day
+            .entry(crate::zone::day_of(entry.started_at))
+            .or_default();
+        match tasks.iter_mut().find(|(id, ..)| *id == entry.task_id) {
+            Some((.., spent)) => *spent += entry.duration(),
+            None => tasks.push((entry.task_id, title, entry.duration())),
+        }
+    }
+
+    outln!("🕒 Timesheet for the last {} days:", days);
+    let mut total = Duration::zero();
+    for (day, tasks) in &by_day {
+        let day_total = tasks
+            .iter()
+            .fold(Duration::zero(), |sum, (.., spent)| sum + *spent);
+        total += day_total;
+        outln!(
+            "\n{}  {}",
+            crate::style::day(*day),
+            duration_text(day_total).bold()
+        );
+        for (id, title, spent) in tasks {
+            outln!("  {:>7}  [{}] {}", duration_text(*spent), id, title);
+        }
+    }
+    outln!("\nTotal: {}", duration_text(total).bold());
+    Ok(())
+}
+
+pub fn task_log(db: &Database, id: i32) -> Result<()> {
+    let history = db.get_task_history(id)?;
+    if history.is_empty() {
+        if db.task_exists(id)? {
+            outln!("📜 No recorded changes for task {}.", id);
+            return Ok(());
+        }
+        return Err(TodoError::NotFound(id).into());
+    }
+
+    match db.get_task_by_id(id)? {
+        Some(task) => outln!("📜 History of task {}: {}", id, task.title),
+        None if history.last().map(|entry| entry.action.as_str()) == Some("archived") => {
+            outln!("📜 History of task {} (archived)", id)
+        }
+        None => outln!("📜 History of task {} (in the trash)", id),
+    }
+    for entry in &history {
+        outln!("{}", history_line(entry, None));
+    }
+    Ok(())
+}
+
+/// `todo log` without an ID: the last `limit` changes to any task, oldest
+/// first.
+pub fn recent_log(db: &Database, limit: usize) -> Result<()> {
+    let history = db.get_recent_history(limit)?;
+    if history.is_empty() {
+        outln!("📜 No recorded changes yet.");
+        return Ok(());
+    }
+
+    outln!("📜 Latest changes:");
+    let mut titles: std::collections::HashMap<i32, String> = std::collections::HashMap::new();
+    for entry in &history {
+        let title = match titles.entry(entry.task_id) {
+            std::collections::hash_map::Entry::Occupied(title) => title.into_mut(),
+            // Archived tasks are no longer in `tasks`; they go by ID only
+            std::collections::hash_map::Entry::Vacant(slot) => slot.insert(
+                db.get_task_with_trashed(entry.task_id)?
+                    .map(|task| format!(" {}", task.title))
+                    .unwrap_or_default(),
+            ),
+        };
+        let task = format!("[{}]{}", entry.task_id, title);
+        outln!("{}", history_line(entry, Some(&task)));
+    }
+    Ok(())
+}
+
+/// One entry of `todo log`, naming the `task` it is about when the log
+/// covers several.
+fn history_line(entry: &crate::models::HistoryEntry, task: Option<&str>) -> String {
+    let mut line = format!(
+        "{}  {} {}",
+        entry.changed_at.format("%Y-%m-%d %H:%M"),
+        format!("{:<16}", entry.actor).dimmed(),
+        entry.action
+    );
+    if let Some(task) = task {
+        line.push_str(&format!(" {}", task));
+    }
+    if let Some(details) = &entry.details {
+        line.push_str(&format!(": {}", details));
+    }
+    line
+}
+
+pub fn archive(db: &Database, days: i64) -> Result<()> {
+    let archived = db.archive_completed(Utc::now() - Duration::days(days))?;
+    if archived == 0 {
+        outln!("✨ No tasks completed more than {} days ago.", days);
+    } else {
+        outln!(
+            "🗄️  Archived {} tasks completed more than {} days ago. See them with `todo list --archived`.",
+            archived, days
+        );
+    }
+    Ok(())
+}
+
+/// Permanently delete completed tasks, or archive them with `archive`;
+/// with `older_than` (such as `30d`) only those completed longer ago than
+/// that. Asks first unless `yes`.
+pub fn purge(db: &Database, older_than: Option<&str>, archive: bool, yes: bool) -> Result<()> {
+    let now = Utc::now();
+    let cutoff = older_than
+        .map(|age| {
+            crate::dates::parse_duration(age)
+                .and_then(|(count, unit)| crate::dates::shift(now, count, unit))
+                .and_then(|later| now.checked_sub_signed(later - now))
+                .ok_or_else(|| {
+                    anyhow::anyhow!("Invalid age '{}': use a length such as 30d or 6w", age)
+                })
+        })
+        .transpose()?;
+    let which = match older_than {
+        Some(age) => format!("completed tasks older than {}", age),
+        None => "completed tasks".to_string(),
+    };
+    let count = db.completed_task_ids(cutoff)?.len();
+    if count == 0 {
+        outln!("✨ No {}.", which);
+        return Ok(());
+    }
+
+    if !yes {
+        let question = if archive {
+            format!("Archive {} {}?", count, which)
+        } else {
+            format!(
+                "Permanently delete {} {}? This cannot be undone.",
+                count, which
+            )
+        };
+        let tasks = get_tasks_by_ids(db, &db.completed_task_ids(cutoff)?)?;
+        let stdin = std::io::stdin();
+        if !confirm_tasks_with(&tasks, &question, &mut stdin.lock(), &mut std::io::stdout())? {
+            outln!("Nothing was changed.");
+            return Ok(());
+        }
+    }
+    if archive {
+        let archived = db.archive_completed(cutoff.unwrap_or(now))?;
+        outln!(
+            "🗄️  Archived {} {}. See them with `todo list --archived`.",
+            archived,
+            which
+        );
+    } else {
+        let purged = db.purge_completed(cutoff)?;
+        outln!("🔥 Permanently deleted {} {}", purged, which);
+    }
+    Ok(())
+}
+
+/// Tasks a confirmation prompt lists before the rest are counted.
+const CONFIRM_SHOWN: usize = 10;
+
+/// Before a command changes or deletes tasks `ids`, list them and ask
+/// `question` when someone is at the terminal. With `yes`, or when stdin
+/// is not a terminal, as in scripts, the command goes ahead unasked.
+pub fn confirm_tasks(db: &Database, ids: &[i32], question: &str, yes: bool) -> Result<bool> {
+    use std::io::IsTerminal;
+    let tasks = get_tasks_by_ids(db, ids)?;
+    if yes || !std::io::stdin().is_terminal() {
+        return Ok(true);
+    }
+    let stdin = std::io::stdin();
+    let confirmed =
+        confirm_tasks_with(&tasks, question, &mut stdin.lock(), &mut std::io::stdout())?;
+    if !confirmed {
+        outln!("Nothing was changed.");
+    }
+    Ok(confirmed)
+}
+
+/// List `tasks`, the first `CONFIRM_SHOWN` of them, then `confirm`.
+fn confirm_tasks_with(
+    tasks: &[Task],
+    question: &str,
+    input: &mut impl std::io::BufRead,
+    output: &mut impl std::io::Write,
+) -> Result<bool> {
+    let window = Duration::hours(crate::models::DEFAULT_DUE_SOON_HOURS);
+    for task in tasks.iter().take(CONFIRM_SHOWN) {
+        writeln!(output, "  {}", task.display_summary(window))?;
+    }
+    if tasks.len() > CONFIRM_SHOWN {
+        writeln!(output, "  … and {} more", tasks.len() - CONFIRM_SHOWN)?;
+    }
+    confirm(question, input, output)
+}
+
+/// Ask a yes/no `question`; anything but `y` or `yes` is a no.
+fn confirm(
+    question: &str,
+    input: &mut impl std::io::BufRead,
+    output: &mut impl std::io::Write,
+) -> Result<bool> {
+    write!(output, "{} [y/N] ", question)?;
+    output.flush()?;
+    let mut answer = String::new();
+    input.read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+pub fn trash(db: &Database, empty: bool) -> Result<()> {
+    if empty {
+        let purged = db.empty_trash()?;
+        outln!("🔥 Permanently deleted {} tasks from the trash", purged);
+        return Ok(());
+    }
+
+    let trashed = db.get_trashed_tasks()?;
+    if trashed.is_empty() {
+        outln!("🗑️  The trash is empty.");
+        return Ok(());
+    }
+
+    let window = Duration::hours(crate::models::DEFAULT_DUE_SOON_HOURS);
+    outln!("🗑️  Trash:");
+    outln!("{}", "─".repeat(80));
+    for (task, deleted_at) in &trashed {
+        outln!(
+            "{} {}",
+            task.display_summary(window),
+            format!("(deleted {})", deleted_at.format("%Y-%m-%d %H:%M")).dimmed()
+        );
+    }
+    outln!("{}", "─".repeat(80));
+    outln!(
+        "Total: {} tasks. `todo restore <id>` brings one back, `todo trash --empty` removes them for good",
+        trashed.len()
+    );
+    Ok(())
+}
+
+pub fn restore_task(db: &Database, id: i32) -> Result<()> {
+    if !db.restore_task(id)? {
+        return Err(anyhow::anyhow!("Task {} is not in the trash", id));
+    }
+    outln!("♻️  Task {} restored", id);
+    Ok(())
+}
+
+pub fn undo(db: &Database) -> Result<()> {
+    let operations = db.undo_last()?;
+    if operations.is_empty() {
+        outln!("Nothing to undo.");
+        return Ok(());
+    }
+    for operation in operations {
+        let id = operation.task_id();
+        match operation {
+            crate::models::Operation::Delete { .. } => {
+                outln!("↩️  Undid delete: task {} is back", id)
+            }
+            crate::models::Operation::Update { .. } => {
+                outln!("↩️  Undid update: task {} is as it was", id)
+            }
+            crate::models::Operation::Complete { next_id, .. } => {
+                outln!("↩️  Undid complete: task {} is pending again", id);
+                if let Some(next_id) = next_id {
+                    outln!("  Removed its next occurrence, task {}", next_id);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Make `context`, a normalized tag, the one context among `tags`: tags
+/// used as a context before are dropped, so a task moves from one context
+/// to another, and `context` is remembered as one.
+pub fn set_context(db: &Database, tags: &mut Vec<String>, context: &str) -> Result<()> {
+    db.add_context(context)?;
+    let contexts = db.get_contexts()?;
+    tags.retain(|tag| tag == context || !contexts.contains(tag));
+    if !tags.iter().any(|tag| tag == context) {
+        tags.push(context.to_string());
+    }
+    Ok(())
+}
+
+/// Apply the same changes to every task in `ids`. All of them are checked
+/// first and saved in one transaction, so a bad ID or parent changes nothing.
+#[allow(clippy::too_many_arguments)]
+pub fn update_task(
+    db: &Database,
+    ids: &[i32],
+    title: Option<&str>,
+    description: Option<&str>,
+    due_date: Option<&str>,
+    start_date: Option<&str>,
+    no_start: bool,
+    priority: Option<&crate::Priority>,
+    inherit_priority: bool,
+    project: Option<&str>,
+    repeat: Option<&str>,
+    no_repeat: bool,
+    parent: Option<i32>,
+    no_parent: bool,
+    energy: Option<crate::Energy>,
+    context: Option<&str>,
+    estimate: Option<&str>,
+    adjust_subtask_due: bool,
+) -> Result<()> {
+    let tasks = get_tasks_by_ids(db, ids)?;
+    let context = context.map(crate::tags::normalize_context).transpose()?;
+    let estimate = estimate.map(parse_estimate).transpose()?;
+
+    let due_date = due_date.map(parse_due_date).transpose()?;
+    let start_date = start_date.map(parse_due_date).transpose()?;
+    let project = project.map(normalize_project).transpose()?;
+    let recurrence = repeat.map(crate::recurrence::normalize).transpose()?;
+
+    let mut edits = Vec::new();
+    for mut task in tasks {
+        let id = task.id.unwrap();
+
+        if let Some(new_title) = title {
+            task.title = new_title.to_string();
+        }
+
+        if let Some(new_description) = description {
+            task.description = Some(new_description.to_string());
+        }
+
+        if let Some(due_date) = due_date {
+            task.due_date = Some(due_date);
+        }
+
+        if let Some(start_date) = start_date {
+            task.start_date = Some(start_date);
+        } else if no_start {
+            task.start_date = None;
+        }
+
+        if let Some(new_priority) = priority {
+            task.priority = new_priority.to_int();
+            task.priority_inherited = false;
+        }
+        if inherit_priority {
+            task.priority_inherited = true;
+        }
+
+        if let Some(new_project) = &project {
+            task.project = Some(new_project.clone());
+        }
+
+        if let Some(rule) = &recurrence {
+            task.recurrence = Some(rule.clone());
+        } else if no_repeat {
+            task.recurrence = None;
+        }
+
+        if let Some(parent_id) = parent {
+            check_parent(db, id, parent_id)?;
+            task.parent_id = Some(parent_id);
+        } else if no_parent {
+            task.parent_id = None;
+        }
+
+        if energy.is_some() {
+            task.energy = energy;
+        }
+
+        if let Some(context) = &context {
+            set_context(db, &mut task.tags, context)?;
+        }
+
+        task.updated_at = Utc::now();
+        edits.push((id, task));
+    }
+
+    // Subtasks due after a parent's new due date either follow it or are
+    // reported, depending on `adjust_subtask_due`
+    let mut late_subtasks = Vec::new();
+    if let Some(due_date) = due_date {
+        for &id in ids {
+            for mut subtask in db.get_subtasks(id)? {
+                let subtask_id = subtask.id.unwrap();
+                let late = subtask.due_date.is_some_and(|due| due > due_date);
+                if subtask.completed || !late || ids.contains(&subtask_id) {
+                    continue;
+                }
+                if late_subtasks
+                    .iter()
+                    .all(|(late_id, _)| *late_id != subtask_id)
+                {
+                    late_subtasks.push((subtask_id, id));
+                    if adjust_subtask_due {
+                        subtask.due_date = Some(due_date);
+                        subtask.updated_at = Utc::now();
+                        edits.push((subtask_id, subtask));
+                    }
+                }
+            }
+        }
+    }
+
+    let tx = db.transaction()?;
+    db.edit_tasks(&edits)?;
+    if estimate.is_some() {
+        for &id in ids {
+            db.set_estimate(id, estimate)?;
+        }
+    }
+    tx.commit()?;
+    match ids {
+        [id] => outln!("✅ Task {} updated successfully!", id),
+        _ => outln!("✅ Tasks {} updated successfully!", join_ids(ids)),
+    }
+    for (subtask_id, parent_id) in late_subtasks {
+        if adjust_subtask_due {
+            outln!(
+                "📅 Subtask {} is now due {} too",
+                subtask_id,
+                crate::style::date(due_date.unwrap())
+            );
+        } else {
+            let subtask = db.get_task_by_id(subtask_id)?.unwrap();
+            outln!(
+                "⚠️  Subtask {} is due {}, after task {} (set `adjust_subtask_due` to move it up)",
+                subtask_id,
+                subtask.due_date_text(),
+                parent_id
+            );
+        }
+    }
+    Ok(())
+}
+
+pub fn search(db: &Database, query: &str, filter: &TaskFilter) -> Result<()> {
+    if query.trim().is_empty() {
+        return Err(anyhow::anyhow!("Search query cannot be empty"));
+    }
+    let hits = db.search_tasks(query, filter)?;
+    if hits.is_empty() {
+        outln!("🔍 No tasks match '{}'.", query);
+        return Ok(());
+    }
+
+    outln!("🔍 Tasks matching '{}':", query);
+    outln!("{}", "─".repeat(80));
+    let window = Duration::hours(crate::models::DEFAULT_DUE_SOON_HOURS);
+    for hit in &hits {
+        let mut task = hit.task.clone();
+        task.title = highlight_matches(&hit.title);
+        let mut line = task.display_summary(window);
+        if let Some(excerpt) = &hit.excerpt {
+            line.push_str(&format!(
+                " {} {}",
+                format!("— {}:", hit.field.name()).dimmed(),
+                highlight_matches(excerpt)
+            ));
+        }
+        outln!("{}", line);
+    }
+    outln!("{}", "─".repeat(80));
+    outln!("Total: {} tasks", hits.len());
+    Ok(())
+}
+
+/// Replace the match markers from a search with colored text.
+fn highlight_matches(text: &str) -> String {
+    use crate::models::{MATCH_END, MATCH_START};
+
+    let mut output = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find(MATCH_START) {
+        output.push_str(&rest[..start]);
+        let matched = &rest[start + MATCH_START.len_utf8()..];
+        let end = matched.find(MATCH_END).unwrap_or(matched.len());
+        output.push_str(&matched[..end].yellow().bold().to_string());
+        rest = matched.get(end + MATCH_END.len_utf8()..).unwrap_or("");
+    }
+    output.push_str(rest);
+    output
+}
+
+/// A task may be nested under any existing task that is not itself or one
+/// of its own subtasks.
+fn check_parent(db: &Database, id: i32, parent_id: i32) -> Result<()> {
+    if !db.task_exists(parent_id)? {
+        return Err(TodoError::Missing(format!("Parent task {} not found", parent_id)).into());
+    }
+    if db.is_ancestor(id, parent_id)? {
+        return Err(anyhow::anyhow!(
+            "Task {} cannot be a subtask of {}: that would create a cycle",
+            id,
+            parent_id
+        ));
+    }
+    Ok(())
+}
+
+/// `todo pick ACTION`: choose an open task in the fuzzy picker, then run
+/// `action` on it.
+pub fn pick(db: &Database, action: crate::PickAction, auto_complete_parents: bool) -> Result<()> {
+    use crate::PickAction;
+
+    let tasks = db.get_tasks(&TaskFilter::default())?;
+    if tasks.is_empty() {
+        outln!("🎉 No open tasks to pick from.");
+        return Ok(());
+    }
+    let prompt = match action {
+        PickAction::Show => "Show which task?",
+        PickAction::Complete => "Complete which task?",
+        PickAction::Delete => "Delete which task?",
+        PickAction::Start => "Start which task?",
+    };
+    let Some(id) = crate::picker::pick(prompt, tasks)? else {
+        return Ok(());
+    };
+    match action {
+        PickAction::Show => show_task(db, id, &crate::OutputFormat::Plain),
+        PickAction::Complete => complete_task(db, &[id], auto_complete_parents),
+        PickAction::Delete => delete_task(db, &[id]),
+        PickAction::Start => start_timer(db, id),
+    }
+}
+
+/// `todo doctor`: report inconsistencies in the task hierarchy.
+pub fn doctor(db: &Database) -> Result<()> {
+    let problems = hierarchy_problems(&db.get_tasks(&whole_hierarchy())?);
+    if problems.is_empty() {
+        outln!("✅ No problems found in the task hierarchy.");
+        return Ok(());
+    }
+    outln!(
+        "🩺 Found {} problem{}:",
+        problems.len(),
+        if problems.len() == 1 { "" } else { "s" }
+    );
+    for problem in &problems {
+        outln!("  - {}", problem);
+    }
+    Ok(())
+}
+
+/// Parent links that don't add up: missing or completed parents of open
+/// subtasks, subtasks due after their parent, open parents with nothing
+/// left to do, and cycles.
+fn hierarchy_problems(tasks: &[Task]) -> Vec<String> {
+    let by_id: std::collections::HashMap<i32, &Task> = tasks
+        .iter()
+        .filter_map(|task| task.id.map(|id| (id, task)))
+        .collect();
+    let mut problems = Vec::new();
+    for task in tasks {
+        let id = task.id.unwrap_or(0);
+
+        let mut seen = std::collections::HashSet::from([id]);
+        let mut ancestor = task.parent_id;
+        while let Some(ancestor_id) = ancestor {
+            if ancestor_id == id {
+                problems.push(format!("Task {} is its own ancestor", id));
+                break;
+            }
+            if !seen.insert(ancestor_id) {
+                break;
+            }
+            ancestor = by_id.get(&ancestor_id).and_then(|task| task.parent_id);
+        }
+
+        let subtasks: Vec<&Task> = tasks
+            .iter()
+            .filter(|subtask| subtask.parent_id == Some(id))
+            .collect();
+        if !task.completed && !subtasks.is_empty() && subtasks.iter().all(|s| s.completed) {
+            problems.push(format!(
+                "Task {} is pending but all of its subtasks are completed",
+                id
+            ));
+        }
+
+        let Some(parent_id) = task.parent_id.filter(|_| !task.completed) else {
+            continue;
+        };
+        match by_id.get(&parent_id) {
+            None => problems.push(format!(
+                "Task {} belongs to task {}, which is deleted or archived",
+                id, parent_id
+            )),
+            Some(parent) if parent.completed => problems.push(format!(
+                "Task {} is pending but its parent {} is completed",
+                id, parent_id
+            )),
+            Some(parent) => {
+                if let (Some(due), Some(parent_due)) = (task.due_date, parent.due_date) {
+                    if due > parent_due {
+                        problems.push(format!(
+                            "Subtask {} is due {}, after its parent {} (due {})",
+                            id,
+                            task.due_date_text(),
+                            parent_id,
+                            parent.due_date_text()
+                        ));
+                    }
+                }
+            }
+        }
+    }
+    problems
+}
+
+/// `show --format json`: the task with its subtasks nested in.
+#[derive(serde::Serialize)]
+struct TaskWithSubtasks<'a> {
+    #[serde(flatten)]
+    task: &'a Task,
+    subtasks: &'a [Task],
+    notes: &'a [crate::models::Note],
+    attachments: &'a [crate::models::Attachment],
+    time_spent_minutes: i64,
+}
+
+pub fn show_task(db: &Database, id: i32, format: &crate::OutputFormat) -> Result<()> {
+    let task = db.get_task_by_id(id)?.ok_or(TodoError::NotFound(id))?;
+    let subtasks = db.get_subtasks(id)?;
+    let notes = db.get_notes(id)?;
+    let attachments = db.get_attachments(id)?;
+    let time_entries = db.get_time_entries(id)?;
+    let time_spent = time_entries
+        .iter()
+        .fold(Duration::zero(), |sum, entry| sum + entry.duration());
+    let window = Duration::hours(crate::models::DEFAULT_DUE_SOON_HOURS);
+
+    match format {
+        crate::OutputFormat::Plain => {}
+        crate::OutputFormat::Json => {
+            let document = TaskWithSubtasks {
+                task: &task,
+                subtasks: &subtasks,
+                notes: &notes,
+                attachments: &attachments,
+                time_spent_minutes: time_spent.num_minutes(),
+            };
+            println!("{}", serde_json::to_string_pretty(&document)?);
+            return Ok(());
+        }
+        crate::OutputFormat::Csv => {
+            let mut rows = vec![task];
+            rows.extend(subtasks);
+            print!("{}", crate::csv_output::render(&rows)?);
+            return Ok(());
+        }
+        crate::OutputFormat::Html => {
+            let title = format!("Task #{}", id);
+            let mut rows = vec![task];
+            rows.extend(subtasks);
+            print!("{}", crate::html::render_page(&title, &rows, window));
+            return Ok(());
+        }
+    }
+
+    outln!("📋 Task Details:");
+    outln!("{}", "─".repeat(80));
+    outln!("{}", task.display_detailed(&notes));
+    if !attachments.is_empty() {
+        outln!("Attachments:");
+        for (number, attachment) in attachments.iter().enumerate() {
+            outln!("  {}. {}", number + 1, attachment.target);
+        }
+    }
+    if !time_entries.is_empty() {
+        let running = time_entries.iter().any(|entry| entry.stopped_at.is_none());
+        outln!(
+            "Time spent: {}{}",
+            duration_text(time_spent),
+            if running { " (timer running)" } else { "" }
+        );
+    }
+    if !subtasks.is_empty() {
+        let done = subtasks.iter().filter(|task| task.completed).count();
+        outln!(
+            "Subtasks: {}",
+            crate::models::progress_bar(done, subtasks.len(), 10)
+        );
+        for line in crate::display::render_tree(&subtasks, window) {
+            outln!("  {}", line);
+        }
+    }
+    outln!("{}", "─".repeat(80));
+    Ok(())
+}
+
+pub fn get_task(db: &Database, id: i32, format: &crate::OutputFormat) -> Result<()> {
+    let task = db.get_task_by_id(id)?.ok_or(TodoError::NotFound(id))?;
+
+    match format {
+        crate::OutputFormat::Plain => outln!("{}", task.display_detailed(&db.get_notes(id)?)),
+        crate::OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&task)?),
+        crate::OutputFormat::Csv => print!("{}", crate::csv_output::render(&[task])?),
+        crate::OutputFormat::Html => {
+            let window = Duration::hours(crate::models::DEFAULT_DUE_SOON_HOURS);
+            print!(
+                "{}",
+                crate::html::render_page(&format!("Task #{}", id), &[task], window)
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Replace a task with the JSON document read from `source` (`-` for stdin).
+pub fn set_task_from_json(db: &Database, id: i32, source: &str) -> Result<()> {
+    let json = if source == "-" {
+        let mut buffer = String::new();
+        std::io::Read::read_to_string(&mut std::io::stdin(), &mut buffer)?;
+        buffer
+    } else {
+        std::fs::read_to_string(source)?
+    };
+
+    apply_task_json(db, id, &json)?;
+    outln!("✅ Task {} updated successfully!", id);
+    Ok(())
+}
+
+fn apply_task_json(db: &Database, id: i32, json: &str) -> Result<()> {
+    if !db.task_exists(id)? {
+        return Err(TodoError::NotFound(id).into());
+    }
+
+    let mut task: Task =
+        serde_json::from_str(json).map_err(|e| anyhow::anyhow!("Invalid task JSON: {}", e))?;
+
+    if let Some(json_id) = task.id {
+        if json_id != id {
+            return Err(anyhow::anyhow!(
+                "Task JSON has id {} but task {} was requested",
+                json_id,
+                id
+            ));
+        }
+    }
+    if task.title.trim().is_empty() {
+        return Err(anyhow::anyhow!("Task title cannot be empty"));
+    }
+    if !(0..=2).contains(&task.priority) {
+        return Err(anyhow::anyhow!(
+            "Invalid priority {}. Expected 0 (low), 1 (medium) or 2 (high)",
+            task.priority
+        ));
+    }
+    task.tags = task
+        .tags
+        .iter()
+        .map(|tag| crate::tags::normalize(tag))
+        .collect::<Result<_>>()?;
+    task.project = task.project.as_deref().map(normalize_project).transpose()?;
+    task.recurrence = task
+        .recurrence
+        .as_deref()
+        .map(crate::recurrence::normalize)
+        .transpose()?;
+    if let Some(parent_id) = task.parent_id {
+        check_parent(db, id, parent_id)?;
+    }
+
+    db.edit_task(id, &task)?;
+    Ok(())
+}
+
+/// `todo completions ids`: pending tasks as `ID<tab>title`, one per line,
+/// for the shell completion scripts.
+pub fn complete_ids(db: &Database) -> Result<()> {
+    for task in db.get_tasks(&TaskFilter::default())? {
+        outln!(
+            "{}\t{}",
+            task.id.unwrap_or(0),
+            task.title.replace('\t', " ")
+        );
+    }
+    Ok(())
+}
+
+/// `todo completions tags`: every tag in use, one per line.
+pub fn complete_tags(db: &Database) -> Result<()> {
+    for tag in db.tag_names()? {
+        outln!("{}", tag);
+    }
+    Ok(())
+}
+
+/// `todo completions projects`: every project name, one per line.
+pub fn complete_projects(db: &Database) -> Result<()> {
+    for (name, _, _) in db.get_projects()? {
+        outln!("{}", name);
+    }
+    Ok(())
+}
+
+/// Reminders shown one notification each; more are summed up in one.
+const MAX_SEPARATE_REMINDERS: usize = 3;
+/// Tasks a digest names before summing up the rest.
+const MAX_DIGEST_LINES: usize = 5;
+
+/// `todo notify`: remind of pending tasks due within `window`, overdue ones
+/// included, once per due date; moving a task's due date re-arms its
+/// reminder. Priorities with a digest window in `digests` have their
+/// reminders held and sent together, at most once per window; the others
+/// are sent right away. With `print` the reminders go to stdout instead of
+/// the desktop, e.g. for cron to mail. Prints nothing when there is nothing
+/// to remind of, or while a focus session runs.
+pub fn notify_due(
+    db: &Database,
+    window: Duration,
+    digests: crate::notify::DigestWindows,
+    print: bool,
+) -> Result<()> {
+    // Held, not dropped: the tasks stay unreminded until the session ends
+    if db.get_active_focus_session()?.is_some() {
+        return Ok(());
+    }
+    let now = Utc::now();
+    let tasks = db.get_unreminded_due(now + window)?;
+    for priority in [
+        crate::Priority::High,
+        crate::Priority::Medium,
+        crate::Priority::Low,
+    ] {
+        let tasks: Vec<Task> = tasks
+            .iter()
+            .filter(|task| task.priority == priority.to_int())
+            .cloned()
+            .collect();
+        if tasks.is_empty() {
+            continue;
+        }
+        let notes = match digests.of(&priority) {
+            Some(every) => {
+                let key = format!("{}_{}", crate::db::META_LAST_DIGEST, priority.name());
+                if db
+                    .get_meta_datetime(&key)?
+                    .is_some_and(|last| now - last < every)
+                {
+                    continue;
+                }
+                db.set_meta_datetime(&key, now)?;
+                digest(&tasks, &priority)
+            }
+            None => reminders(&tasks),
+        };
+        for (summary, body) in notes {
+            if print {
+                println!("{}\n{}", summary, body);
+            } else {
+                crate::notify::send(&summary, &body)?;
+            }
+        }
+        for task in &tasks {
+            db.mark_reminded(task)?;
+        }
+    }
+    Ok(())
+}
+
+/// One notification for the held reminders of `priority`; a single task
+/// gets the usual notification of its own.
+fn digest(tasks: &[Task], priority: &crate::Priority) -> Vec<(String, String)> {
+    if tasks.len() == 1 {
+        return reminders(tasks);
+    }
+    let mut lines: Vec<String> = tasks
+        .iter()
+        .take(MAX_DIGEST_LINES)
+        .map(|task| format!("{} ({})", task.title, task.due_date_text()))
+        .collect();
+    if tasks.len() > MAX_DIGEST_LINES {
+        lines.push(format!("and {} more", tasks.len() - MAX_DIGEST_LINES));
+    }
+    let summary = format!("{} {} priority tasks due", tasks.len(), priority.name());
+    vec![(summary, lines.join("\n"))]
+}
+
+/// Notifications, as summary and body, reminding of `tasks`.
+fn reminders(tasks: &[Task]) -> Vec<(String, String)> {
+    let due = |task: &Task| {
+        let verb = if task.is_overdue() { "was" } else { "is" };
+        format!(
+            "Task {} {} due {}",
+            task.id.unwrap_or(0),
+            verb,
+            task.due_date_text()
+        )
+    };
+    if tasks.len() <= MAX_SEPARATE_REMINDERS {
+        return tasks
+            .iter()
+            .map(|task| {
+                let label = if task.is_overdue() {
+                    "Overdue"
+                } else {
+                    "Due soon"
+                };
+                (format!("{}: {}", label, task.title), due(task))
+            })
+            .collect();
+    }
+    let mut lines: Vec<String> = tasks
+        .iter()
+        .take(MAX_SEPARATE_REMINDERS)
+        .map(|task| format!("{} ({})", task.title, task.due_date_text()))
+        .collect();
+    lines.push(format!("and {} more", tasks.len() - MAX_SEPARATE_REMINDERS));
+    vec![(format!("{} tasks due", tasks.len()), lines.join("\n"))]
+}
+
+/// One-line summary for shell prompts and status bars, e.g.
+/// `5 open · 1 overdue · 2 due soon`. Kept cheap and uncolored.
+pub fn status_line(db: &Database) -> Result<()> {
+    outln!("{}", status_line_text(db)?);
+    Ok(())
+}
+
+fn status_line_text(db: &Database) -> Result<String> {
+    let tasks = db.get_tasks(&TaskFilter::default())?;
+    let focus = db.get_active_focus_session()?;
+    let window = Duration::hours(crate::models::DEFAULT_DUE_SOON_HOURS);
+    let overdue = tasks.iter().filter(|t| t.is_overdue()).count();
+    let due_soon = tasks.iter().filter(|t| t.is_due_soon(window)).count();
+
+    let mut parts = vec![format!("{} open", tasks.len())];
+    if overdue > 0 {
+        parts.push(format!("{} overdue", overdue));
+    }
+    if due_soon > 0 {
+        parts.push(format!("{} due soon", due_soon));
+    }
+    if let Some(session) = focus {
+        parts.push(format!("focus {} left", session.remaining_text()));
+    }
+    Ok(parts.join(" · "))
+}
+
+pub fn focus_start(db: &Database, minutes: i64, task_id: Option<i32>) -> Result<()> {
+    if minutes <= 0 {
+        return Err(anyhow::anyhow!(
+            "Focus sessions must last at least a minute"
+        ));
+    }
+    if let Some(id) = task_id {
+        if !db.task_exists(id)? {
+            return Err(TodoError::NotFound(id).into());
+        }
+    }
+    if let Some(session) = db.get_active_focus_session()? {
+        return Err(anyhow::anyhow!(
+            "A focus session is already running ({} left). Stop it with `todo focus stop`",
+            session.remaining_text()
+        ));
+    }
+
+    let ends_at = Utc::now() + Duration::minutes(minutes);
+    db.start_focus_session(task_id, ends_at)?;
+
+    match task_id {
+        Some(id) => outln!("🎯 Focusing on task {} for {} minutes", id, minutes),
+        None => outln!("🎯 Focus session started for {} minutes", minutes),
+    }
+    Ok(())
+}
+
+pub fn focus_stop(db: &Database) -> Result<()> {
+    match db.get_active_focus_session()? {
+        Some(session) => {
+            db.end_focus_session(session.id)?;
+            let minutes = (Utc::now() - session.started_at).num_minutes();
+            outln!("⏹️  Focus session ended after {} minutes", minutes);
+        }
+        None => outln!("No focus session is running."),
+    }
+    Ok(())
+}
+
+pub fn focus_status(db: &Database) -> Result<()> {
+    match db.get_active_focus_session()? {
+        Some(session) => {
+            let task = match session.task_id {
+                Some(id) => format!(" on task {}", id),
+                None => String::new(),
+            };
+            outln!("🎯 Focusing{}: {} left", task, session.remaining_text());
+        }
+        None => outln!("No focus session is running."),
+    }
+    Ok(())
+}
+
+pub fn snapshot(
+    db: &Database,
+    group_by: crate::snapshot::GroupBy,
+    format: crate::snapshot::SnapshotFormat,
+    output: Option<&std::path::Path>,
+) -> Result<()> {
+    let tasks = db.get_tasks(&TaskFilter::any_status())?;
+    let rendered = match format {
+        crate::snapshot::SnapshotFormat::Svg => crate::snapshot::render_svg(&tasks, group_by),
+    };
+
+    match output {
+        Some(path) => {
+            crate::files::write_locked(path, rendered.as_bytes())?;
+            outln!("🖼️  Snapshot written to {}", path.display());
+        }
+        None => print!("{}", rendered),
+    }
+    Ok(())
+}
+
+/// Write every task, completed and someday ones included, in ID order.
+/// Files are replaced atomically, so an interrupted export never leaves a
+/// truncated backup behind.
+pub fn export(
+    db: &Database,
+    format: crate::ExportFormat,
+    output: Option<&std::path::Path>,
+) -> Result<()> {
+    let mut tasks = db.get_tasks(&TaskFilter {
+        status: StatusFilter::Any,
+        someday: SomedayFilter::Include,
+        ..TaskFilter::default()
+    })?;
+    tasks.sort_by_key(|task| task.id);
+
+    let progress = crate::progress::Progress::spinner("Exporting tasks");
+    progress.set_position(tasks.len() as u64);
+    let rendered = match format {
+        crate::ExportFormat::Json => format!("{}\n", serde_json::to_string_pretty(&tasks)?),
+        crate::ExportFormat::Csv => crate::csv_output::render(&tasks)?,
+        crate::ExportFormat::Markdown => crate::markdown::render(&tasks),
+    };
+    if let Some(path) = output {
+        crate::files::write_locked(path, rendered.as_bytes())?;
+    }
+    progress.finish();
+
+    match output {
+        Some(path) => {
+            outln!("💾 Exported {} tasks to {}", tasks.len(), path.display());
+        }
+        None => print!("{}", rendered),
+    }
+    Ok(())
+}
+
+/// Print the weekly plan as an HTML email with signed Done/Snooze links.
+pub fn plan_email(db: &Database, base_url: &str, to: Option<&str>) -> Result<()> {
+    let secret = db
+        .get_meta(crate::db::META_LINK_SECRET)?
+        .ok_or_else(|| anyhow::anyhow!("Link signing secret is missing"))?;
+    let now = Utc::now();
+    let tasks = crate::email::plan_tasks(db.get_tasks(&TaskFilter::default())?, now);
+
+    let email = crate::email::PlanEmail {
+        to,
+        base_url,
+        secret: &secret,
+        now,
+    };
+    print!("{}", crate::email::render_weekly_plan(&tasks, &email)?);
+    Ok(())
+}
+
+/// The commands of a `todo batch` input, each as its arguments with the
+/// number `todo batch` reports it under. The input is either one command
+/// line per line, numbered by line and skipping blank lines and `#`
+/// comments, or a JSON array of command lines or argument arrays, numbered
+/// from 1. A leading `todo` is optional.
+pub fn parse_batch(text: &str) -> Result<Vec<(usize, Vec<String>)>> {
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum Item {
+        Line(String),
+        Args(Vec<String>),
+    }
+
+    let split = |number: usize, line: &str| {
+        crate::config::split_args(line)
+            .map_err(|error| anyhow::anyhow!("Command {}: {}", number, error))
+    };
+    let mut commands = if text.trim_start().starts_with('[') {
+        let items: Vec<Item> = serde_json::from_str(text).map_err(|error| {
+            anyhow::anyhow!("A JSON batch must be an array of commands: {}", error)
+        })?;
+        items
+            .into_iter()
+            .zip(1..)
+            .map(|(item, number)| match item {
+                Item::Line(line) => Ok((number, split(number, &line)?)),
+                Item::Args(args) => Ok((number, args)),
+            })
+            .collect::<Result<Vec<_>>>()?
+    } else {
+        text.lines()
+            .zip(1..)
+            .filter(|(line, _)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+            .map(|(line, number)| Ok((number, split(number, line)?)))
+            .collect::<Result<Vec<_>>>()?
+    };
+    for (number, args) in &mut commands {
+        if args.first().is_some_and(|first| first == "todo") {
+            args.remove(0);
+        }
+        if args.is_empty() {
+            return Err(anyhow::anyhow!("Command {} is empty", number));
+        }
+    }
+    Ok(commands)
+}
+
+/// Print a signed link to a read-only page of task `id`, or of every task
+/// in `project`, that `todo serve` shows until the link expires.
+pub fn share(
+    db: &Database,
+    id: Option<i32>,
+    project: Option<&str>,
+    expires: &str,
+    base_url: &str,
+) -> Result<()> {
+    let secret = db
+        .get_meta(crate::db::META_LINK_SECRET)?
+        .ok_or_else(|| anyhow::anyhow!("Link signing secret is missing"))?;
+    let (shared, what) = match (id, project) {
+        (Some(id), _) => {
+            let task = db.get_task_by_id(id)?.ok_or(TodoError::NotFound(id))?;
+            (Shared::Task(id), format!("“{}”", task.title))
+        }
+        (None, Some(name)) => {
+            if !db.project_exists(name)? {
+                return Err(TodoError::Missing(format!("Project '{}' not found", name)).into());
+            }
+            (
+                Shared::Project(name.to_string()),
+                format!("project '{}'", name),
+            )
+        }
+        (None, None) => return Err(anyhow::anyhow!("Give a task ID or --project")),
+    };
+    let expires = crate::dates::parse_duration(expires)
+        .and_then(|(count, unit)| crate::dates::shift(Utc::now(), count, unit))
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "Invalid expiry '{}': use a length such as 7d or 2w",
+                expires
+            )
+        })?;
+
+    let url = crate::signing::share_url(base_url, &secret, &shared, expires);
+    outln!(
+        "🔗 Anyone with this link can see {} until {}:",
+        what,
+        crate::style::date(expires)
+    );
+    outln!("{}", url);
+    Ok(())
+}
+
+pub fn goal_add(db: &Database, title: &str, by: Option<&str>) -> Result<()> {
+    let target_date = by.map(parse_due_date).transpose()?;
+    let id = db.add_goal(title, target_date)?;
+    outln!("🎯 Goal added with ID: {}", id);
+    Ok(())
+}
+
+pub fn goal_list(db: &Database) -> Result<()> {
+    let goals = db.get_goals()?;
+    if goals.is_empty() {
+        outln!("🎯 No goals yet.");
+        return Ok(());
+    }
+
+    outln!("🎯 Your goals:");
+    outln!("{}", "─".repeat(80));
+    for goal in &goals {
+        let tasks = db.get_goal_tasks(goal.id)?;
+        let done = tasks.iter().filter(|t| t.completed).count();
+        outln!(
+            "[{}] {} {} {}",
+            goal.id,
+            goal.title,
+            crate::models::progress_bar(done, tasks.len(), 10),
+            goal.time_left_text().dimmed()
+        );
+    }
+    outln!("{}", "─".repeat(80));
+    outln!("Total: {} goals", goals.len());
+    Ok(())
+}
+
+pub fn goal_show(db: &Database, id: i32) -> Result<()> {
+    let goal = db
+        .get_goal(id)?
+        .ok_or_else(|| TodoError::Missing(format!("Goal with ID {} not found", id)))?;
+    let tasks = db.get_goal_tasks(id)?;
+    let done = tasks.iter().filter(|t| t.completed).count();
+
+    outln!("Goal #{}: {}", goal.id, goal.title);
+    outln!(
+        "By: {} ({})",
+        goal.target_date
+            .map(crate::style::date)
+            .unwrap_or_else(|| "-".to_string()),
+        goal.time_left_text()
+    );
+    outln!(
+        "Progress: {}",
+        crate::models::progress_bar(done, tasks.len(), 20)
+    );
+    outln!("Remaining: {}", tasks.len() - done);
+
+    if !tasks.is_empty() {
+        outln!("{}", "─".repeat(80));
+        let window = Duration::hours(crate::models::DEFAULT_DUE_SOON_HOURS);
+        for task in &tasks {
+            outln!("{}", task.display_summary(window));
+        }
+    }
+    Ok(())
+}
+
+pub fn goal_link(db: &Database, goal_id: i32, task_ids: &[i32]) -> Result<()> {
+    if db.get_goal(goal_id)?.is_none() {
+        return Err(TodoError::Missing(format!("Goal with ID {} not found", goal_id)).into());
+    }
+    for &task_id in task_ids {
+        if !db.task_exists(task_id)? {
+            return Err(TodoError::NotFound(task_id).into());
+        }
+    }
+    for &task_id in task_ids {
+        db.link_task_to_goal(task_id, goal_id)?;
+    }
+    outln!("🔗 Linked {} tasks to goal {}", task_ids.len(), goal_id);
+    Ok(())
+}
+
+pub fn goal_unlink(db: &Database, task_ids: &[i32]) -> Result<()> {
+    for &task_id in task_ids {
+        db.unlink_task_from_goal(task_id)?;
+    }
+    outln!("✂️  Unlinked {} tasks from their goals", task_ids.len());
+    Ok(())
+}
+
+pub fn goal_delete(db: &Database, id: i32) -> Result<()> {
+    if db.get_goal(id)?.is_none() {
+        return Err(TodoError::Missing(format!("Goal with ID {} not found", id)).into());
+    }
+    db.delete_goal(id)?;
+    outln!("🗑️  Goal {} deleted; its tasks were kept", id);
+    Ok(())
+}
+
+/// `todo template save`: keep task `id`'s title, description, priority,
+/// tags, project and due date as template `name`, replacing any template
+/// of that name. The due date is kept relative to the day the task was
+/// added, such as `in 3 days`, unless `due` gives an expression to use.
+pub fn template_save(db: &Database, name: &str, id: i32, due: Option<&str>) -> Result<()> {
+    if name.is_empty() || name.chars().any(char::is_whitespace) {
+        return Err(anyhow::anyhow!(
+            "Invalid template name '{}': use one word, e.g. weekly-report",
+            name
+        ));
+    }
+    let task = db.get_task_by_id(id)?.ok_or(TodoError::NotFound(id))?;
+    if let Some(due) = due {
+        parse_due_date(due)?;
+    }
+    let template = crate::models::Template {
+        name: name.to_string(),
+        due: due.map(str::to_string).or_else(|| relative_due(&task)),
+        priority: task.own_priority(),
+        title: task.title,
+        description: task.description,
+        tags: task.tags,
+        project: task.project,
+    };
+    let replaced = db.get_template(name)?.is_some();
+    db.save_template(&template)?;
+    outln!(
+        "📝 Template '{}' {} from task {}",
+        name,
+        if replaced { "updated" } else { "saved" },
+        id
+    );
+    Ok(())
+}
+
+/// How long after it was added `task` is due, as a date expression.
+fn relative_due(task: &Task) -> Option<String> {
+    let days =
+        (crate::zone::day_of(task.due_date?) - crate::zone::day_of(task.created_at)).num_days();
+    Some(match days {
+        ..=0 => "today".to_string(),
+        1 => "tomorrow".to_string(),
+        days => format!("in {} days", days),
+    })
+}
+
+/// The template called `name`, for `todo add --template`.
+pub fn find_template(db: &Database, name: &str) -> Result<crate::models::Template> {
+    db.get_template(name)?.ok_or_else(|| {
+        TodoError::Missing(format!(
+            "No template named '{}'. `todo template list` shows them",
+            name
+        ))
+        .into()
+    })
+}
+
+pub fn template_list(db: &Database) -> Result<()> {
+    let templates = db.get_templates()?;
+    if templates.is_empty() {
+        outln!("📝 No templates yet. Save one with `todo template save NAME TASK_ID`.");
+        return Ok(());
+    }
+    outln!("📝 Templates:");
+    for template in &templates {
+        let mut details = Vec::new();
+        if let Some(priority) = template.priority {
+            details.push(crate::Priority::from_int(priority).name().to_string());
+        }
+        if let Some(due) = &template.due {
+            details.push(format!("due {}", due));
+        }
+        if let Some(project) = &template.project {
+            details.push(format!("+{}", project));
+        }
+        details.extend(template.tags.iter().map(|tag| format!("#{}", tag)));
+        outln!(
+            "  {:<16} {} {}",
+            template.name,
+            template.title,
+            details.join(" ").dimmed()
+        );
+    }
+    Ok(())
+}
+
+pub fn template_delete(db: &Database, name: &str) -> Result<()> {
+    if !db.delete_template(name)? {
+        return Err(TodoError::Missing(format!("No template named '{}'", name)).into());
+    }
+    outln!("🗑️  Template '{}' deleted", name);
+    Ok(())
+}
+
+/// Show locally recorded command usage. Nothing here ever leaves the
+/// machine.
+pub fn logs(log: Option<&crate::logging::LogFile>, count: usize) -> Result<()> {
+    use crate::logging::Level;
+
+    let Some(log) = log else {
+        outln!("📜 No log file is set. Run `todo config set log_file ~/.todo.log` to keep one.");
+        return Ok(());
+    };
+    let entries = log.tail(count)?;
+    if entries.is_empty() {
+        outln!("📜 Nothing logged to {} yet.", log.path.display());
+        return Ok(());
+    }
+    for entry in entries {
+        let level = format!("{:<5}", entry.level.name());
+        let level = match entry.level {
+            Level::Info => level.normal(),
+            Level::Warn => level.yellow(),
+            Level::Error => level.red().bold(),
+        };
+        outln!(
+            "{} {} {:<16} {}",
+            entry
+                .time
+                .with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string()
+                .dimmed(),
+            level,
+            entry.component,
+            entry.message
+        );
+    }
+    Ok(())
+}
+
+/// Commands kept for `todo history`; older ones are forgotten.
+pub const COMMAND_HISTORY_LIMIT: usize = 100;
+
+/// Flags whose value is a secret; a command line with one of them is not
+/// remembered, so the secret doesn't end up in the database.
+const SECRET_FLAGS: [&str; 1] = ["--token"];
+
+/// A command line remembered for `todo history`, stored as JSON in the
+/// meta table.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+struct HistoryEntry {
+    at: DateTime<Utc>,
+    args: Vec<String>,
+}
+
+fn command_history(db: &Database) -> Result<Vec<HistoryEntry>> {
+    Ok(db
+        .get_meta(crate::db::META_COMMAND_HISTORY)?
+        .and_then(|value| serde_json::from_str(&value).ok())
+        .unwrap_or_default())
+}
+
+/// Remember `args` (without the program name) as the latest command, unless
+/// they hold a secret.
+pub fn record_command(db: &Database, args: &[String]) -> Result<()> {
+    let secret = args.iter().any(|arg| {
+        SECRET_FLAGS
+            .iter()
+            .any(|flag| arg.split_once('=').map_or(arg.as_str(), |(name, _)| name) == *flag)
+    });
+    if secret {
+        return Ok(());
+    }
+    let mut history = command_history(db)?;
+    history.push(HistoryEntry {
+        at: Utc::now(),
+        args: args.to_vec(),
+    });
+    let excess = history.len().saturating_sub(COMMAND_HISTORY_LIMIT);
+    history.drain(..excess);
+    db.set_meta(
+        crate::db::META_COMMAND_HISTORY,
+        &serde_json::to_string(&history)?,
+    )?;
+    Ok(())
+}
+
+/// `args` as they could be typed again, quoting the ones that need it.
+pub fn command_line(args: &[String]) -> String {
+    let quoted: Vec<String> = args
+        .iter()
+        .map(|arg| {
+            let plain = !arg.is_empty()
+                && arg
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || "-_=.,:/@#+%".contains(c));
+            if plain {
+                arg.clone()
+            } else {
+                format!("'{}'", arg.replace('\'', "'\\''"))
+            }
+        })
+        .collect();
+    format!("todo {}", quoted.join(" "))
+}
+
+/// The arguments of history entry `number`, or of the last command.
+pub fn history_entry(db: &Database, number: Option<usize>) -> Result<Vec<String>> {
+    let history = command_history(db)?;
+    let entry = match number {
+        Some(number) => number.checked_sub(1).and_then(|index| history.get(index)),
+        None => history.last(),
+    };
+    match (entry, number) {
+        (Some(entry), _) => Ok(entry.args.clone()),
+        (None, Some(number)) => Err(anyhow::anyhow!(
+            "There is no command {} in the history (see `todo history`)",
+            number
+        )),
+        (None, None) => Err(anyhow::anyhow!(
+            "No commands recorded yet. Run `todo config set command_history true` to remember them"
+        )),
+    }
+}
+
+/// Show the last `limit` commands, numbered for `todo !! N`, or forget
+/// them all with `clear`.
+pub fn history(db: &Database, enabled: bool, limit: usize, clear: bool) -> Result<()> {
+    if clear {
+        db.delete_meta(crate::db::META_COMMAND_HISTORY)?;
+        outln!("🧽 Command history cleared");
+        return Ok(());
+    }
+
+    let history = command_history(db)?;
+    if history.is_empty() {
+        if enabled {
+            outln!("📜 No commands recorded yet.");
+        } else {
+            outln!("📜 Command history is off. Run `todo config set command_history true` to remember commands.");
+        }
+        return Ok(());
+    }
+
+    let start = history.len().saturating_sub(limit);
+    for (index, entry) in history.iter().enumerate().skip(start) {
+        outln!(
+            "{:>4}  {}  {}",
+            index + 1,
+            entry
+                .at
+                .with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M")
+                .to_string()
+                .dimmed(),
+            command_line(&entry.args)
+        );
+    }
+    if !enabled {
+        outln!("(recording is off)");
+    }
+    Ok(())
+}
+
+pub fn usage(db: &Database, enabled: bool, reset: bool) -> Result<()> {
+    if reset {
+        db.clear_command_usage()?;
+        outln!("🧽 Usage history cleared");
+        return Ok(());
+    }
+
+    let stats = db.get_usage_stats()?;
+    if stats.is_empty() {
+        if enabled {
+            outln!("📊 No usage recorded yet.");
+        } else {
+            outln!("📊 Usage metrics are off. Run `todo config set usage_metrics true` to record them locally.");
+        }
+        return Ok(());
+    }
+
+    outln!("📊 Command usage:");
+    outln!("{}", "─".repeat(80));
+    outln!(
+        "{:<16} {:>6} {:>10} {:>10}  LAST USED",
+        "COMMAND",
+        "RUNS",
+        "AVG",
+        "MAX"
+    );
+    for stat in &stats {
+        outln!(
+            "{:<16} {:>6} {:>8.0}ms {:>8}ms  {}",
+            stat.command,
+            stat.count,
+            stat.avg_ms,
+            stat.max_ms,
+            stat.last_used.format("%Y-%m-%d %H:%M")
+        );
+    }
+    outln!("{}", "─".repeat(80));
+    outln!(
+        "Total: {} runs{}",
+        stats.iter().map(|s| s.count).sum::<i64>(),
+        if enabled { "" } else { " (recording is off)" }
+    );
+    Ok(())
+}
+
+/// Check GitHub for a newer release and, unless `check_only`, install it
+/// over the running binary.
+pub fn self_update(check_only: bool) -> Result<()> {
+    let current = env!("CARGO_PKG_VERSION");
+    let release = crate::update::fetch_latest_release()?;
+
+    if !crate::update::is_newer(&release.tag_name, current) {
+        outln!("✅ todo {} is up to date", current);
+        return Ok(());
+    }
+
+    outln!(
+        "⬆️  Update available: {} → {}",
+        current,
+        release.tag_name.trim_start_matches('v')
+    );
+    if check_only {
+        return Ok(());
+    }
+
+    let exe = std::env::current_exe()?;
+    crate::update::install(&release, &exe)?;
+    outln!("✅ Updated to {}", release.tag_name);
+    Ok(())
+}
+
+/// Show the tag hierarchy with pending task counts rolled up to each level.
+pub fn list_tag_tree(db: &Database) -> Result<()> {
+    let rows = crate::tags::rollup(&db.get_pending_tag_pairs()?);
+    if rows.is_empty() {
+        outln!("🏷️  No tags on pending tasks.");
+        return Ok(());
+    }
+
+    outln!("🏷️  Tags:");
+    outln!("{}", crate::tags::render_tree(&rows));
+    Ok(())
+}
+
+/// Explain why a task is not actionable, in the current `context` if any.
+pub fn why_task(db: &Database, id: i32, context: Option<&str>) -> Result<()> {
+    let task = db.get_task_by_id(id)?.ok_or(TodoError::NotFound(id))?;
+
+    let reasons = blocking_reasons(db, &task, context)?;
+    let window = Duration::hours(crate::models::DEFAULT_DUE_SOON_HOURS);
+    outln!("{}", task.display_summary(window));
+
+    if reasons.is_empty() {
+        outln!("✅ Nothing is blocking task {}; it is actionable now.", id);
+        return Ok(());
+    }
+
+    outln!("⛔ Task {} is not actionable:", id);
+    for reason in reasons {
+        outln!("  • {}", reason);
+    }
+    Ok(())
+}
+
+/// Check that a new task can wait for the tasks in `after` and be waited
+/// for by those in `before`: they exist, and no chain of dependencies would
+/// lead back to it.
+fn check_dependencies(db: &Database, after: &[i32], before: &[i32]) -> Result<()> {
+    for &id in after.iter().chain(before) {
+        if db.get_task_by_id(id)?.is_none() {
+            return Err(TodoError::NotFound(id).into());
+        }
+    }
+    for &first in after {
+        for &then in before {
+            if first == then || db.depends_on(first, then)? {
+                return Err(anyhow::anyhow!(
+                    "after:{} and before:{} would make the task wait for itself",
+                    first,
+                    then
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Collect human-readable reasons why a task cannot be worked on right now.
+/// These are the conditions the list filters hide it for, given the current
+/// `context`. An empty list means the task is actionable.
+fn blocking_reasons(db: &Database, task: &Task, context: Option<&str>) -> Result<Vec<String>> {
+    let mut reasons = Vec::new();
+    let now = Utc::now();
+
+    if task.completed {
+        reasons.push("it is already completed".to_string());
+    } else if let Some(start) = task.start_date.filter(|start| *start > now) {
+        reasons.push(format!(
+            "it starts on {} {}",
+            crate::style::date(start),
+            start.format("%H:%M")
+        ));
+    }
+
+    if task.someday {
+        reasons.push("it is in the someday bucket".to_string());
+    }
+
+    if let Some(context) = context.filter(|context| !task.tags.iter().any(|tag| tag == context)) {
+        reasons.push(format!("it is not in the current context @{}", context));
+    }
+
+    if let Some(id) = task.id {
+        let open = db
+            .get_subtasks(id)?
+            .iter()
+            .filter(|subtask| !subtask.completed)
+            .count();
+        if open > 0 {
+            reasons.push(format!(
+                "{} subtask{} still open",
+                open,
+                if open == 1 { " is" } else { "s are" }
+            ));
+        }
+
+        for dependency in db.get_dependencies(id)? {
+            if !dependency.completed {
+                reasons.push(format!(
+                    "it waits for task {} ({})",
+                    dependency.id.unwrap_or(0),
+                    dependency.title
+                ));
+            }
+        }
+
+        match task.due_date {
+            Some(due) if !task.completed && due > now && db.get_snooze_count(id)? > 0 => {
+                reasons.push(format!(
+                    "it is snoozed until {} {}",
+                    crate::style::date(due),
+                    due.format("%H:%M")
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    Ok(reasons)
+}
+
+/// Tag applied to tasks detected as stale.
+pub const STALE_TAG: &str = "stale";
+
+pub fn parse_due_date(date_str: &str) -> Result<DateTime<Utc>> {
+    let now = Utc::now();
+    // A day on its own is due by its end, as `today` is, so it is not
+    // overdue while it lasts
+    let parsed = if let Ok(naive_date) = NaiveDate::parse_from_str(date_str, "%Y-%m-%d") {
+        crate::zone::end_of(naive_date)
+    } else if let Ok(datetime) = DateTime::parse_from_rfc3339(date_str) {
+        datetime.with_timezone(&Utc)
+    } else {
+        crate::dates::parse_relative(date_str, now).map_err(TodoError::InvalidDate)?
+    };
+
+    if parsed < now {
+        return Err(TodoError::InvalidDate(crate::diagnostic::Diagnostic::new(
+            "due date",
+            date_str,
+            0..date_str.len(),
+            "must be in the future".to_string(),
+        ))
+        .into());
+    }
+
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{create_test_db, Database};
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_add_task() {
+        let (db, _temp_file) = create_test_db();
+
+        let priority = crate::Priority::High;
+        add_task(
+            &db,
+            "Test task",
+            Some("Test description"),
+            Some("2099-12-31"),
+            None,
+            Some(&priority),
+            &["work".to_string(), "#urgent".to_string()],
+            Some(" work "),
+            Some("Every 2 Weeks"),
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+
+        let tasks = db.get_tasks(&TaskFilter::any_status()).unwrap();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].title, "Test task");
+        assert_eq!(tasks[0].priority, 2); // High priority
+        assert_eq!(tasks[0].tags, vec!["urgent", "work"]);
+        assert_eq!(tasks[0].project.as_deref(), Some("work"));
+        assert_eq!(tasks[0].recurrence.as_deref(), Some("every 2 weeks"));
+    }
+
+    #[test]
+    fn test_add_note() {
+        let (db, _temp_file) = create_test_db();
+        let id = db
+            .add_task(&Task::new("Order desk".to_string(), None, None, 1))
+            .unwrap();
+
+        add_note(&db, id, "  Asked for a quote ").unwrap();
+        add_note(&db, id, "Quote arrived").unwrap();
+        let notes: Vec<String> = db
+            .get_notes(id)
+            .unwrap()
+            .into_iter()
+            .map(|note| note.text)
+            .collect();
+        assert_eq!(notes, ["Asked for a quote", "Quote arrived"]);
+
+        assert!(add_note(&db, id, "   ").is_err());
+        assert!(add_note(&db, 99, "Lost").is_err());
+    }
+
+    #[test]
+    fn test_attach() {
+        let (db, _temp_file) = create_test_db();
+        let id = db
+            .add_task(&Task::new("Review design".to_string(), None, None, 1))
+            .unwrap();
+        let file = NamedTempFile::new().unwrap();
+
+        attach(&db, id, " https://example.com/spec ").unwrap();
+        attach(&db, id, file.path().to_str().unwrap()).unwrap();
+        let targets: Vec<String> = db
+            .get_attachments(id)
+            .unwrap()
+            .into_iter()
+            .map(|attachment| attachment.target)
+            .collect();
+        let path = std::fs::canonicalize(file.path()).unwrap();
+        assert_eq!(
+            targets,
+            ["https://example.com/spec", path.to_str().unwrap()]
+        );
+
+        let code = |error: anyhow::Error| TodoError::exit_code_of(&error);
+        assert_eq!(
+            code(attach(&db, id, "./no-such-design.pdf").unwrap_err()),
+            3
+        );
+        assert_eq!(code(attach(&db, 99, "https://example.com").unwrap_err()), 3);
+        assert_eq!(code(attach(&db, id, "  ").unwrap_err()), 4);
+        assert_eq!(code(open_attachments(&db, id, Some(3)).unwrap_err()), 3);
+        let other = db
+            .add_task(&Task::new("Nothing attached".to_string(), None, None, 1))
+            .unwrap();
+        assert_eq!(code(open_attachments(&db, other, None).unwrap_err()), 3);
+
+        db.purge_tasks(&[id]).unwrap();
+        assert!(db.get_attachments(id).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_time_tracking() {
+        let (db, _temp_file) = create_test_db();
+        let report = db
+            .add_task(&Task::new("Write report".to_string(), None, None, 1))
+            .unwrap();
+        let review = db
+            .add_task(&Task::new("Review PR".to_string(), None, None, 1))
+            .unwrap();
+
+        start_timer(&db, report).unwrap();
+        assert!(db.get_task_by_id(report).unwrap().unwrap().in_progress);
+        // Only one timer runs at a time
+        let error = start_timer(&db, review).unwrap_err().to_string();
+        assert!(error.contains("already running on task 1"));
+
+        stop_timer(&db).unwrap();
+        assert!(db.get_running_timer().unwrap().is_none());
+        start_timer(&db, review).unwrap();
+        let entries = db
+            .get_time_entries_since(Utc::now() - Duration::hours(1))
+            .unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].1, "Review PR");
+        timesheet(&db, 7).unwrap();
+        assert!(timesheet(&db, 0).is_err());
+
+        // Completing a task stops its timer
+        db.complete_tasks(&[review]).unwrap();
+        assert!(db.get_running_timer().unwrap().is_none());
+        assert!(start_timer(&db, review).is_err());
+        assert!(start_timer(&db, 99).is_err());
+    }
+
+    #[test]
+    fn test_add_tasks_from_lines() {
+        let (db, _temp_file) = create_test_db();
+        add_task(
+            &db,
+            "Buy milk",
+            None,
+            None,
+            None,
+            Some(&crate::Priority::Medium),
+            &["errands".to_string()],
+            None,
+            None,
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+
+        let text = "Buy milk #errands\n\nCall plumber +home p:high\nCall plumber +home p:high\nRead #books";
+        let priority = crate::Priority::Low;
+        add_tasks_from(
+            &db,
+            text,
+            &priority,
+            &["batch".to_string()],
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        let tasks = db.get_tasks(&TaskFilter::default()).unwrap();
+        let plumber = tasks.iter().find(|t| t.title == "Call plumber").unwrap();
+        assert_eq!(plumber.priority, 2);
+        assert_eq!(plumber.project.as_deref(), Some("home"));
+        assert_eq!(plumber.tags, ["batch"]);
+        let read = tasks.iter().find(|t| t.title == "Read").unwrap();
+        assert_eq!(read.priority, 0);
+        assert_eq!(tasks.len(), 3);
+
+        // One bad line stops the whole batch
+        let error = add_tasks_from(&db, "Fine\nBad p:urgent", &priority, &[], None, None, false)
+            .unwrap_err();
+        assert!(error.to_string().starts_with("Line 2:"));
+        assert_eq!(db.get_tasks(&TaskFilter::default()).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_add_tasks_with_dependencies() {
+        let (db, _temp_file) = create_test_db();
+        let priority = crate::Priority::Medium;
+        add_tasks_from(
+            &db,
+            "Build app
+Tell users",
+            &priority,
+            &[],
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        add_tasks_from(
+            &db,
+            "Deploy app after:1 before:2",
+            &priority,
+            &[],
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        let task = |id| db.get_task_by_id(id).unwrap().unwrap();
+        assert_eq!(
+            blocking_reasons(&db, &task(3), None).unwrap(),
+            ["it waits for task 1 (Build app)"]
+        );
+        assert_eq!(
+            blocking_reasons(&db, &task(2), None).unwrap(),
+            ["it waits for task 3 (Deploy app)"]
+        );
+        db.complete_tasks(&[1]).unwrap();
+        assert!(blocking_reasons(&db, &task(3), None).unwrap().is_empty());
+
+        // Task 2 already waits for task 1 through task 3
+        let error = add_tasks_from(
+            &db,
+            "Loop after:2 before:1",
+            &priority,
+            &[],
+            None,
+            None,
+            false,
+        )
+        .unwrap_err();
+        assert!(format!("{:#}", error).contains("wait for itself"));
+        assert!(
+            add_tasks_from(&db, "Missing after:99", &priority, &[], None, None, false).is_err()
+        );
+        assert_eq!(db.get_tasks(&TaskFilter::default()).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_next_up_by_energy() {
+        let (db, _temp_file) = create_test_db();
+        add_tasks_from(
+            &db,
+            "Plan roadmap p:high !high\nSort email !low\nWater plants\nFile receipts p:low !low",
+            &crate::Priority::Medium,
+            &[],
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        let weights = crate::urgency::Weights::default();
+        let titles = |energy, limit| -> Vec<String> {
+            let ranked = next_up(&db, energy, limit, &weights, None, &[]).unwrap();
+            ranked.into_iter().map(|(_, task)| task.title).collect()
+        };
+
+        assert_eq!(titles(None, 1), ["Plan roadmap"]);
+        assert_eq!(
+            titles(Some(crate::Energy::Low), 5),
+            ["Sort email", "File receipts"]
+        );
+        assert_eq!(titles(Some(crate::Energy::Medium), 5).len(), 3);
+
+        // A task waits for its open subtasks
+        let mut step = Task::new("Draft section".to_string(), None, None, 0);
+        step.parent_id = Some(1);
+        db.add_task(&step).unwrap();
+        assert!(!titles(None, 5).contains(&"Plan roadmap".to_string()));
+
+        // A formula ranks by its own score
+        let formula = crate::urgency::Formula::parse("2 - priority").unwrap();
+        let ranked = next_up(&db, None, 1, &weights, Some(&formula), &[]).unwrap();
+        assert_eq!(ranked[0].0, 2.0);
+        assert_eq!(ranked[0].1.title, "File receipts");
+    }
+
+    #[test]
+    fn test_add_task_invalid_tag() {
+        let (db, _temp_file) = create_test_db();
+
+        let priority = crate::Priority::Medium;
+        let result = add_task(
+            &db,
+            "Task",
+            None,
+            None,
+            None,
+            Some(&priority),
+            &["a,b".to_string()],
+            None,
+            None,
+            None,
+            None,
+            None,
+            false,
+        );
+        assert!(result.is_err());
+        assert!(db.get_tasks(&TaskFilter::any_status()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_stale_tasks_tagging() {
+        let (db, _temp_file) = create_test_db();
+
+        let mut task = Task::new("Forgotten".to_string(), None, None, 1);
+        task.created_at = Utc::now() - Duration::days(45);
+        task.updated_at = task.created_at;
+        let id = db.add_task(&task).unwrap();
+
+        stale_tasks(&db, 30, true).unwrap();
+
+        let task = db.get_task_by_id(id).unwrap().unwrap();
+        assert_eq!(task.tags, vec![STALE_TAG]);
+        // Tagging must not reset staleness
+        assert!(task.updated_at < Utc::now() - Duration::days(30));
+    }
+
+    #[test]
+    fn test_parse_due_date() {
+        // Test YYYY-MM-DD format
+        let date = parse_due_date("2099-12-31").unwrap();
+        assert_eq!(date.format("%Y-%m-%d").to_string(), "2099-12-31");
+
+        // Test RFC3339 format
+        let rfc_date = parse_due_date("2099-12-31T00:00:00Z").unwrap();
+        assert_eq!(rfc_date.format("%Y-%m-%d").to_string(), "2099-12-31");
+
+        // Today's date is due by its end, like `today`, not already past
+        let today = crate::zone::day_of(Utc::now());
+        let date = parse_due_date(&today.format("%Y-%m-%d").to_string()).unwrap();
+        assert_eq!(date, crate::zone::end_of(today));
+        assert_eq!(date, parse_due_date("today").unwrap());
+
+        // Relative expressions
+        let tomorrow = parse_due_date("tomorrow").unwrap();
+        assert_eq!(
+            tomorrow.date_naive(),
+            Utc::now().date_naive().succ_opt().unwrap()
+        );
+        assert!(parse_due_date("in 2 weeks").unwrap() > tomorrow);
+        // The same day written either way is the same deadline
+        let next_day = today.succ_opt().unwrap().format("%Y-%m-%d").to_string();
+        assert_eq!(tomorrow, parse_due_date(&next_day).unwrap());
+
+        // Test invalid format
+        for text in ["invalid-date", "2000-01-01"] {
+            let error = parse_due_date(text).unwrap_err();
+            assert!(matches!(
+                error.downcast_ref::<TodoError>(),
+                Some(TodoError::InvalidDate(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_complete_task() {
+        let (db, _temp_file) = create_test_db();
+
+        // Add a task first
+        let priority = crate::Priority::Medium;
+        add_task(
+            &db,
+            "Test task",
+            None,
+            None,
+            None,
+            Some(&priority),
+            &[],
+            None,
+            None,
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+
+        // Complete the task
+        complete_task(&db, &[1], false).unwrap();
+
+        let task = db.get_task_by_id(1).unwrap().unwrap();
+        assert!(task.completed);
+    }
+
+    #[test]
+    fn test_blocking_reasons() {
+        let (db, _temp_file) = create_test_db();
+
+        let priority = crate::Priority::Medium;
+        add_task(
+            &db,
+            "Test task",
+            None,
+            None,
+            None,
+            Some(&priority),
+            &[],
+            None,
+            None,
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+
+        let task = db.get_task_by_id(1).unwrap().unwrap();
+        assert!(blocking_reasons(&db, &task, None).unwrap().is_empty());
+
+        // Each condition the list filters hide a task for is reported
+        let mut hidden = task.clone();
+        hidden.start_date = Some(Utc::now() + Duration::days(2));
+        hidden.someday = true;
+        hidden.tags = vec!["home".to_string()];
+        db.edit_task(1, &hidden).unwrap();
+        db.snooze_task(1, Utc::now() + Duration::days(1)).unwrap();
+        let task = db.get_task_by_id(1).unwrap().unwrap();
+        let reasons = blocking_reasons(&db, &task, Some("office")).unwrap();
+        assert_eq!(reasons.len(), 4);
+        assert!(reasons[0].starts_with("it starts on"));
+        assert_eq!(reasons[1], "it is in the someday bucket");
+        assert_eq!(reasons[2], "it is not in the current context @office");
+        assert!(reasons[3].starts_with("it is snoozed until"));
+        assert_eq!(blocking_reasons(&db, &task, Some("home")).unwrap().len(), 3);
+
+        complete_task(&db, &[1], false).unwrap();
+        let task = db.get_task_by_id(1).unwrap().unwrap();
+        let reasons = blocking_reasons(&db, &task, None).unwrap();
+        assert_eq!(reasons.len(), 2);
+        assert!(reasons[0].contains("completed"));
+
+        assert!(why_task(&db, 999, None).is_err());
+    }
+
+    #[test]
+    fn test_import_habitica_tasks_skips_duplicates() {
+        let (db, _temp_file) = create_test_db();
+
+        let remote: Vec<crate::habitica::HabiticaTask> = serde_json::from_str(
+            r#"[
+                {"id": "a1", "text": "File taxes", "type": "todo", "priority": 1.5},
+                {"id": "b2", "text": "Done already", "type": "todo", "completed": true}
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            import_habitica_tasks(&db, &remote, &crate::progress::Progress::hidden(), false)
+                .unwrap(),
+            (1, 1)
+        );
+        assert_eq!(
+            import_habitica_tasks(&db, &remote, &crate::progress::Progress::hidden(), false)
+                .unwrap(),
+            (0, 2)
+        );
+
+        let tasks = db.get_tasks(&TaskFilter::any_status()).unwrap();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].priority, 1);
+    }
+
+    #[test]
+    fn test_apply_task_json_roundtrip() {
+        let (db, _temp_file) = create_test_db();
+
+        let priority = crate::Priority::Low;
+        add_task(
+            &db,
+            "Original",
+            None,
+            None,
+            None,
+            Some(&priority),
+            &[],
+            None,
+            None,
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+
+        let mut task = db.get_task_by_id(1).unwrap().unwrap();
+        task.title = "Edited".to_string();
+        task.priority = 2;
+        task.tags = vec!["editor".to_string()];
+        let json = serde_json::to_string(&task).unwrap();
+
+        apply_task_json(&db, 1, &json).unwrap();
+
+        let task = db.get_task_by_id(1).unwrap().unwrap();
+        assert_eq!(task.title, "Edited");
+        assert_eq!(task.priority, 2);
+        assert_eq!(task.tags, vec!["editor"]);
+    }
+
+    #[test]
+    fn test_apply_task_json_validation() {
+        let (db, _temp_file) = create_test_db();
+
+        let priority = crate::Priority::Low;
+        add_task(
+            &db,
+            "Original",
+            None,
+            None,
+            None,
+            Some(&priority),
+            &[],
+            None,
+            None,
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        let mut task = db.get_task_by_id(1).unwrap().unwrap();
+
+        assert!(apply_task_json(&db, 1, "{not json").is_err());
+        assert!(apply_task_json(&db, 2, &serde_json::to_string(&task).unwrap()).is_err());
+
+        task.priority = 7;
+        let err = apply_task_json(&db, 1, &serde_json::to_string(&task).unwrap()).unwrap_err();
+        assert!(err.to_string().contains("Invalid priority"));
+
+        task.priority = 1;
+        task.title = "  ".to_string();
+        assert!(apply_task_json(&db, 1, &serde_json::to_string(&task).unwrap()).is_err());
+
+        assert_eq!(db.get_task_by_id(1).unwrap().unwrap().title, "Original");
+    }
+
+    #[test]
+    fn test_status_line_text() {
+        let (db, _temp_file) = create_test_db();
+        assert_eq!(status_line_text(&db).unwrap(), "0 open");
+
+        let mut overdue = Task::new("Late".to_string(), None, None, 1);
+        overdue.due_date = Some(Utc::now() - Duration::days(1));
+        db.add_task(&overdue).unwrap();
+        let mut soon = Task::new("Soon".to_string(), None, None, 1);
+        soon.due_date = Some(Utc::now() + Duration::hours(3));
+        db.add_task(&soon).unwrap();
+        db.add_task(&Task::new("Someday".to_string(), None, None, 1))
+            .unwrap();
+
+        assert_eq!(
+            status_line_text(&db).unwrap(),
+            "3 open · 1 overdue · 1 due soon"
+        );
+    }
+
+    #[test]
+    fn test_focus_session_lifecycle() {
+        let (db, _temp_file) = create_test_db();
+
+        let priority = crate::Priority::Medium;
+        add_task(
+            &db,
+            "Deep work",
+            None,
+            None,
+            None,
+            Some(&priority),
+            &[],
+            None,
+            None,
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+
+        assert!(focus_start(&db, 25, Some(99)).is_err());
+        focus_start(&db, 25, Some(1)).unwrap();
+        assert!(focus_start(&db, 25, None).is_err()); // already running
+        assert!(status_line_text(&db).unwrap().contains("focus 25m left"));
+
+        focus_stop(&db).unwrap();
+        assert!(db.get_active_focus_session().unwrap().is_none());
+        assert!(!status_line_text(&db).unwrap().contains("focus"));
+    }
+
+    #[test]
+    fn test_complete_nonexistent_task() {
+        let (db, _temp_file) = create_test_db();
+
+        let result = complete_task(&db, &[999], false);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("not found"));
+    }
+
+    #[test]
+    fn test_delete_task() {
+        let (db, _temp_file) = create_test_db();
+
+        // Add a task first
+        let priority = crate::Priority::Medium;
+        add_task(
+            &db,
+            "Test task",
+            None,
+            None,
+            None,
+            Some(&priority),
+            &[],
+            None,
+            None,
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+
+        // Delete the task
+        delete_task(&db, &[1]).unwrap();
+
+        // Verify task is deleted
+        assert!(db.get_task_by_id(1).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_subtask_parents() {
+        let (db, _temp_file) = create_test_db();
+        let priority = crate::Priority::Medium;
+        let add = |title: &str, project: Option<&str>, parent: Option<i32>| {
+            add_task(
+                &db,
+                title,
+                None,
+                None,
+                None,
+                Some(&priority),
+                &[],
+                project,
+                None,
+                parent,
+                None,
+                None,
+                false,
+            )
+        };
+        add("Release", Some("work"), None).unwrap();
+        add("Write tests", None, Some(1)).unwrap();
+        add("Unit tests", None, Some(2)).unwrap();
+        assert!(add("Orphan", None, Some(99)).is_err());
+
+        // Subtasks inherit the parent's project
+        let subtask = db.get_task_by_id(3).unwrap().unwrap();
+        assert_eq!(subtask.parent_id, Some(2));
+        assert_eq!(subtask.project.as_deref(), Some("work"));
+
+        let reparent = |id: i32, parent: Option<i32>, no_parent: bool| {
+            update_task(
+                &db,
+                &[id],
+                None,
+                None,
+                None,
+                None,
+                false,
+                None,
+                false,
+                None,
+                None,
+                false,
+                parent,
+                no_parent,
+                None,
+                None,
+                None,
+                false,
+            )
+        };
+        assert!(reparent(1, Some(3), false).is_err());
+        assert!(reparent(2, Some(2), false).is_err());
+        reparent(3, None, true).unwrap();
+        assert_eq!(db.get_task_by_id(3).unwrap().unwrap().parent_id, None);
+
+        let task = db.get_task_by_id(1).unwrap().unwrap();
+        assert_eq!(
+            blocking_reasons(&db, &task, None).unwrap(),
+            vec!["1 subtask is still open"]
+        );
+    }
+
+    #[test]
+    fn test_subtask_due_dates_and_completion() {
+        let (db, _temp_file) = create_test_db();
+        add_tasks_from(
+            &db,
+            "Release due:2030-06-30\nWrite tests due:2030-06-20\nDocs\nChangelog due:2030-06-25",
+            &crate::Priority::Medium,
+            &[],
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        for id in [2, 3, 4] {
+            let mut task = db.get_task_by_id(id).unwrap().unwrap();
+            task.parent_id = Some(1);
+            db.update_task(id, &task).unwrap();
+        }
+        let due = |id: i32| db.get_task_by_id(id).unwrap().unwrap().due_date_text();
+        let set_due = |date: &str, adjust: bool| {
+            update_task(
+                &db,
+                &[1],
+                None,
+                None,
+                Some(date),
+                None,
+                false,
+                None,
+                false,
+                None,
+                None,
+                false,
+                None,
+                false,
+                None,
+                None,
+                None,
+                adjust,
+            )
+        };
+
+        // Without adjusting, a late subtask keeps its date and is reported
+        set_due("2030-06-22", false).unwrap();
+        assert_eq!(due(4), "2030-06-25");
+        assert_eq!(
+            hierarchy_problems(&db.get_tasks(&whole_hierarchy()).unwrap()),
+            vec!["Subtask 4 is due 2030-06-25, after its parent 1 (due 2030-06-22)"]
+        );
+        set_due("2030-06-15", true).unwrap();
+        assert_eq!((due(2), due(3), due(4)), (due(1), due(3), due(1)));
+
+        complete_task(&db, &[2, 3], true).unwrap();
+        assert!(!db.get_task_by_id(1).unwrap().unwrap().completed);
+        complete_task(&db, &[4], true).unwrap();
+        assert!(db.get_task_by_id(1).unwrap().unwrap().completed);
+        assert!(hierarchy_problems(&db.get_tasks(&whole_hierarchy()).unwrap()).is_empty());
+
+        // Undo reopens the parent together with its last subtask
+        undo(&db).unwrap();
+        assert!(!db.get_task_by_id(1).unwrap().unwrap().completed);
+        let mut reopened = db.get_task_by_id(2).unwrap().unwrap();
+        reopened.completed = false;
+        reopened.parent_id = Some(99);
+        db.update_task(2, &reopened).unwrap();
+        assert_eq!(
+            hierarchy_problems(&db.get_tasks(&whole_hierarchy()).unwrap()),
+            vec!["Task 2 belongs to task 99, which is deleted or archived"]
+        );
+    }
+
+    #[test]
+    fn test_group_by_expression() {
+        let (db, _temp_file) = create_test_db();
+        add_tasks_from(
+            &db,
+            "Ten due:2030-01-10\nNine due:2030-01-09\nUndated\nAlso ten due:2030-01-10",
+            &crate::Priority::Medium,
+            &[],
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        let values = db
+            .expression_values("CAST(strftime('%d', due_date) AS INTEGER) * 1")
+            .unwrap();
+        let groups = group_by_value(db.get_tasks(&TaskFilter::default()).unwrap(), &values);
+        let summary: Vec<(Option<&str>, usize)> = groups
+            .iter()
+            .map(|(value, tasks)| (value.as_deref(), tasks.len()))
+            .collect();
+        assert_eq!(summary, vec![(Some("9"), 1), (Some("10"), 2), (None, 1)]);
+
+        assert!(list_tasks(
+            &db,
+            &TaskFilter::default(),
+            Duration::hours(24),
+            &crate::OutputFormat::Plain,
+            None,
+            Some("substr(title)"),
+            &crate::table::TableOptions::default(),
+            crate::db::Page::default(),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_parse_ids() {
+        let (db, _temp_file) = create_test_db();
+        for n in 1..=10 {
+            db.add_task(&Task::new(format!("Task {}", n), None, None, 1))
+                .unwrap();
+        }
+        let specs =
+            |specs: &[&str]| -> Vec<String> { specs.iter().map(|s| s.to_string()).collect() };
+        assert_eq!(
+            parse_ids(&db, &specs(&["3", "5", "7-10", "5"])).unwrap(),
+            vec![3, 5, 7, 8, 9, 10]
+        );
+        assert!(parse_ids(&db, &specs(&["10-7"])).is_err());
+        assert_eq!(
+            parse_ids(&db, &specs(&["1-99999999"]))
+                .unwrap_err()
+                .to_string(),
+            "Invalid range '1-99999999': there is no task above 10"
+        );
+        assert!(parse_ids(&db, &specs(&["x"])).is_err());
+        assert!(parse_ids(&db, &specs(&["3-"])).is_err());
+    }
+
+    #[test]
+    fn test_task_uuids() {
+        let (db, _temp_file) = create_test_db();
+        let first = db
+            .add_task(&Task::new("First".to_string(), None, None, 2))
+            .unwrap();
+        let second = db
+            .add_task(&Task::new("Second".to_string(), None, None, 2))
+            .unwrap();
+        let uuid = db.get_task_by_id(first).unwrap().unwrap().uuid.unwrap();
+        assert_eq!(uuid.len(), 36);
+        assert_ne!(
+            Some(uuid.clone()),
+            db.get_task_by_id(second).unwrap().unwrap().uuid
+        );
+
+        let prefix: crate::TaskRef = uuid[..8].to_uppercase().parse().unwrap();
+        assert_eq!(resolve_task(&db, &prefix).unwrap(), first);
+        assert_eq!(
+            parse_ids(&db, &[uuid[..8].to_string(), second.to_string()]).unwrap(),
+            vec![first, second]
+        );
+
+        // Undoing a delete brings the task back with the same UUID
+        delete_task(&db, &[first]).unwrap();
+        undo(&db).unwrap();
+        assert_eq!(db.get_task_by_id(first).unwrap().unwrap().uuid, Some(uuid));
+    }
+
+    #[test]
+    fn test_bulk_operations() {
+        let (db, _temp_file) = create_test_db();
+        for title in ["One", "Two", "Three"] {
+            db.add_task(&Task::new(title.to_string(), None, None, 1))
+                .unwrap();
+        }
+
+        // A missing ID fails the whole batch before anything changes
+        assert!(complete_task(&db, &[1, 2, 9], false).is_err());
+        assert_eq!(db.get_tasks(&TaskFilter::default()).unwrap().len(), 3);
+
+        let high = crate::Priority::High;
+        update_task(
+            &db,
+            &[1, 2],
+            None,
+            None,
+            None,
+            None,
+            false,
+            Some(&high),
+            false,
+            None,
+            None,
+            false,
+            None,
+            false,
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        complete_task(&db, &[1, 2, 3], false).unwrap();
+        assert!(db.get_tasks(&TaskFilter::default()).unwrap().is_empty());
+
+        // One undo reverses the whole command
+        assert_eq!(db.undo_last().unwrap().len(), 3);
+        assert_eq!(db.get_tasks(&TaskFilter::default()).unwrap().len(), 3);
+        assert_eq!(db.undo_last().unwrap().len(), 2);
+        assert!(db
+            .get_tasks(&TaskFilter::default())
+            .unwrap()
+            .iter()
+            .all(|task| task.priority == 1));
+
+        delete_task(&db, &[2, 3]).unwrap();
+        assert_eq!(db.get_trashed_tasks().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_update_task() {
+        let (db, _temp_file) = create_test_db();
+
+        // Add a task first
+        let priority = crate::Priority::Medium;
+        add_task(
+            &db,
+            "Original title",
+            None,
+            None,
+            None,
+            Some(&priority),
+            &[],
+            None,
+            None,
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+
+        // Update the task
+        let new_priority = crate::Priority::High;
+        update_task(
+            &db,
+            &[1],
             Some("New title"),
             Some("New description"),
-            Some("2024-12-31"),
+            Some("2099-12-31"),
+            None,
+            false,
             Some(&new_priority),
+            false,
+            Some("home"),
+            None,
+            false,
+            None,
+            false,
+            None,
+            None,
+            None,
+            false,
         )
         .unwrap();
 
         let task = db.get_task_by_id(1).unwrap().unwrap();
+        assert_eq!(task.project.as_deref(), Some("home"));
         assert_eq!(task.title, "New title");
         assert_eq!(task.description, Some("New description".to_string()));
         assert_eq!(task.priority, 2); // High priority
+
+        // A task has one context: setting another moves it there, and
+        // other tags stay
+        let mut task = db.get_task_by_id(1).unwrap().unwrap();
+        task.tags = vec!["phone".to_string()];
+        db.edit_task(1, &task).unwrap();
+        let set_context = |context: &str| {
+            update_task(
+                &db,
+                &[1],
+                None,
+                None,
+                None,
+                None,
+                false,
+                None,
+                false,
+                None,
+                None,
+                false,
+                None,
+                false,
+                None,
+                Some(context),
+                None,
+                false,
+            )
+            .unwrap();
+            db.get_task_by_id(1).unwrap().unwrap().tags
+        };
+        assert_eq!(set_context("@home"), ["home", "phone"]);
+        assert_eq!(set_context("@home"), ["home", "phone"]);
+        assert_eq!(set_context("@office"), ["office", "phone"]);
+    }
+
+    #[test]
+    fn test_command_history() {
+        let (db, _temp_file) = create_test_db();
+        assert!(history_entry(&db, None).is_err());
+
+        for i in 0..COMMAND_HISTORY_LIMIT + 2 {
+            record_command(&db, &["show".to_string(), i.to_string()]).unwrap();
+        }
+        assert_eq!(command_history(&db).unwrap().len(), COMMAND_HISTORY_LIMIT);
+        assert_eq!(history_entry(&db, Some(1)).unwrap(), ["show", "2"]);
+        assert_eq!(
+            history_entry(&db, None).unwrap(),
+            ["show", &(COMMAND_HISTORY_LIMIT + 1).to_string()]
+        );
+        assert!(history_entry(&db, Some(0)).is_err());
+        assert!(history_entry(&db, Some(COMMAND_HISTORY_LIMIT + 1)).is_err());
+
+        let args = ["add", "Call Bob's dentist", "--tag", "health/teeth", ""].map(String::from);
+        assert_eq!(
+            command_line(&args),
+            "todo add 'Call Bob'\\''s dentist' --tag health/teeth ''"
+        );
+
+        // Command lines with a secret in them are not kept
+        let before = command_history(&db).unwrap().len();
+        for args in [
+            &["serve", "--token", "SECRET"][..],
+            &["serve", "--token=SECRET"],
+        ] {
+            let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+            record_command(&db, &args).unwrap();
+        }
+        assert_eq!(command_history(&db).unwrap().len(), before);
+
+        history(&db, true, 5, true).unwrap();
+        assert!(command_history(&db).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_schedule_week() {
+        let (db, _temp_file) = create_test_db();
+        for title in ["Write report", "Plan trip", "Fix bike"] {
+            add_task(
+                &db,
+                title,
+                None,
+                None,
+                None,
+                Some(&crate::Priority::Medium),
+                &[],
+                None,
+                None,
+                None,
+                None,
+                None,
+                false,
+            )
+            .unwrap();
+        }
+        estimate_task(&db, 1, Some("3h"), false).unwrap();
+        assert!(estimate_task(&db, 2, Some("2d"), false).is_err());
+        assert!(estimate_task(&db, 2, Some("0m"), false).is_err());
+        assert_eq!(db.estimates().unwrap(), [(1, 180)].into());
+
+        let tasks = db.get_tasks(&TaskFilter::default()).unwrap();
+        let today = Utc::now().date_naive();
+        let mut plan = crate::schedule::plan(today, 180, &tasks, &db.estimates().unwrap());
+        let mut output = Vec::new();
+        let mut input = std::io::Cursor::new("3 someday\n2 -\naccept\n");
+        assert!(review_plan(&mut plan, &tasks, &mut input, &mut output).unwrap());
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("`someday` is not a working day"));
+        assert!(output.contains("Not scheduled:\n     2  Plan trip (30m)"));
+
+        schedule_week(&db, 6, true).unwrap();
+        let week = today + Duration::days(7);
+        for task in db.get_tasks(&TaskFilter::default()).unwrap() {
+            let due = task.due_date.unwrap().date_naive();
+            assert!(due >= today && due < week, "{}", task.title);
+        }
+        undo(&db).unwrap();
+        assert!(db
+            .get_tasks(&TaskFilter::default())
+            .unwrap()
+            .iter()
+            .all(|task| task.due_date.is_none()));
+    }
+
+    #[test]
+    fn test_estimates_and_plan_budget() {
+        let (db, _temp_file) = create_test_db();
+        let add = |title: &str, estimate: Option<&str>| {
+            add_task(
+                &db,
+                title,
+                None,
+                None,
+                None,
+                None,
+                &[],
+                None,
+                None,
+                None,
+                None,
+                estimate,
+                false,
+            )
+        };
+        add("Write report", Some("2h")).unwrap();
+        add("Plan trip", None).unwrap();
+        assert!(add("Fix bike", Some("soon")).is_err());
+        assert_eq!(db.get_tasks(&TaskFilter::default()).unwrap().len(), 2);
+
+        update_task(
+            &db,
+            &[2],
+            None,
+            None,
+            None,
+            None,
+            false,
+            None,
+            false,
+            None,
+            None,
+            false,
+            None,
+            false,
+            None,
+            None,
+            Some("1h 30m"),
+            false,
+        )
+        .unwrap_err();
+        update_task(
+            &db,
+            &[2],
+            None,
+            None,
+            None,
+            None,
+            false,
+            None,
+            false,
+            None,
+            None,
+            false,
+            None,
+            false,
+            None,
+            None,
+            Some("90m"),
+            false,
+        )
+        .unwrap();
+        assert_eq!(db.estimates().unwrap(), [(1, 120), (2, 90)].into());
+
+        plan_budget(&db, "3h", Duration::hours(24)).unwrap();
+        assert!(plan_budget(&db, "half a day", Duration::hours(24)).is_err());
+        let tasks = db.get_tasks(&TaskFilter::default()).unwrap();
+        let picked = crate::schedule::budget(
+            &tasks,
+            &db.estimates().unwrap(),
+            180,
+            Utc::now(),
+            Duration::hours(24),
+        );
+        assert_eq!(picked, vec![(1, 120)]);
+    }
+
+    #[test]
+    fn test_snooze_task() {
+        let (db, _temp_file) = create_test_db();
+        let priority = crate::Priority::Low;
+        add_task(
+            &db,
+            "Undated",
+            None,
+            None,
+            None,
+            Some(&priority),
+            &[],
+            None,
+            None,
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        add_task(
+            &db,
+            "Dated",
+            None,
+            Some("2099-12-31"),
+            None,
+            Some(&priority),
+            &[],
+            None,
+            None,
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+
+        snooze_task(&db, 1, "2d").unwrap();
+        let due = db.get_task_by_id(1).unwrap().unwrap().due_date.unwrap();
+        assert!((due - Utc::now() - Duration::days(2)).num_seconds().abs() < 5);
+
+        snooze_task(&db, 2, "2d").unwrap();
+        let due = db.get_task_by_id(2).unwrap().unwrap().due_date.unwrap();
+        assert_eq!(due.format("%Y-%m-%d").to_string(), "2100-01-02");
+        snooze_task(&db, 2, "1w").unwrap();
+        let due = db.get_task_by_id(2).unwrap().unwrap().due_date.unwrap();
+        assert_eq!(due.format("%Y-%m-%d").to_string(), "2100-01-09");
+        assert_eq!(db.get_snooze_count(2).unwrap(), 2);
+
+        // Dates must be later than the current due date
+        assert!(snooze_task(&db, 2, "tomorrow").is_err());
+        snooze_task(&db, 2, "2100-02-01").unwrap();
+        let due = db.get_task_by_id(2).unwrap().unwrap().due_date.unwrap();
+        assert_eq!(due.format("%Y-%m-%d").to_string(), "2100-02-01");
+
+        assert!(snooze_task(&db, 1, "soonish").is_err());
+        assert!(snooze_task(&db, 99, "1d").is_err());
+
+        add_task(
+            &db,
+            "Never snoozed",
+            None,
+            Some("2099-06-01"),
+            None,
+            Some(&priority),
+            &[],
+            None,
+            None,
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        let snoozed = TaskFilter {
+            snoozed: true,
+            ..TaskFilter::default()
+        };
+        let ids: Vec<Option<i32>> = db
+            .get_tasks(&snoozed)
+            .unwrap()
+            .into_iter()
+            .map(|task| task.id)
+            .collect();
+        assert_eq!(ids, vec![Some(1), Some(2)]);
+    }
+
+    #[test]
+    fn test_review_someday() {
+        let (db, _temp_file) = create_test_db();
+        let priority = crate::Priority::Low;
+        for title in ["Learn Go", "Write a novel", "Visit Japan", "Build a boat"] {
+            add_task(
+                &db,
+                title,
+                None,
+                None,
+                None,
+                Some(&priority),
+                &[],
+                None,
+                None,
+                None,
+                None,
+                None,
+                false,
+            )
+            .unwrap();
+        }
+        for id in 1..=4 {
+            someday(&db, Some(id), false).unwrap();
+        }
+        let before_review = Utc::now();
+        let tasks = db
+            .get_someday_due_for_review(Utc::now() + Duration::days(1))
+            .unwrap();
+
+        // An unknown answer is asked again; quitting leaves the rest alone
+        let mut input = "x\np\nd\nk\nq\n".as_bytes();
+        let mut output = Vec::new();
+        let summary = review_someday(&db, &tasks, &mut input, &mut output).unwrap();
+        assert_eq!(
+            summary,
+            ReviewSummary {
+                promoted: 1,
+                dropped: 1,
+                kept: 1
+            }
+        );
+
+        assert!(!db.get_task_by_id(1).unwrap().unwrap().someday);
+        assert!(db.get_task_by_id(2).unwrap().is_none());
+        // Kept tasks are not due again until the next period
+        let due = db.get_someday_due_for_review(before_review).unwrap();
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].id, Some(4));
+    }
+
+    #[test]
+    fn test_review_decisions_from_flags() {
+        let (db, _temp_file) = create_test_db();
+        for title in ["Learn Go", "Write a novel", "Visit Japan"] {
+            db.add_task(&Task::new(title.to_string(), None, None, 0))
+                .unwrap();
+        }
+        someday(&db, Some(1), false).unwrap();
+        someday(&db, Some(2), false).unwrap();
+
+        // Every ID is checked before anything changes
+        let decisions = ReviewDecisions {
+            promote: vec![1],
+            drop: vec![3],
+            keep: vec![],
+        };
+        assert!(review(&db, false, false, &decisions).is_err());
+        assert!(db.get_task_by_id(1).unwrap().unwrap().someday);
+
+        let decisions = ReviewDecisions {
+            promote: vec![1],
+            drop: vec![2],
+            keep: vec![],
+        };
+        review(&db, false, false, &decisions).unwrap();
+        assert!(!db.get_task_by_id(1).unwrap().unwrap().someday);
+        assert!(db.get_task_by_id(2).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_triage_tasks() {
+        let (db, _temp_file) = create_test_db();
+        let priority = crate::Priority::Medium;
+        for title in ["Taxes", "Gym", "Dentist", "Paint fence", "Read"] {
+            add_task(
+                &db,
+                title,
+                None,
+                None,
+                None,
+                Some(&priority),
+                &[],
+                None,
+                None,
+                None,
+                None,
+                None,
+                false,
+            )
+            .unwrap();
+        }
+        let tasks = db.get_tasks(&TaskFilter::default()).unwrap();
+
+        // Unknown keys are asked again, keeping the same priority is a skip,
+        // and quitting keeps the decisions made so far
+        let mut input = "x
+h
+m
+d
+l
+q
+"
+        .as_bytes();
+        let mut output = Vec::new();
+        let triage = triage_tasks(&tasks, &mut input, &mut output).unwrap();
+        let edits: Vec<(i32, i32)> = triage
+            .edits
+            .iter()
+            .map(|(id, task)| (*id, task.priority))
+            .collect();
+        assert_eq!(edits, vec![(1, 2), (4, 0)]);
+        assert_eq!(triage.deletes, vec![3]);
+        assert_eq!(triage.skipped, 1);
+
+        db.apply_changes(&triage.edits, &triage.deletes).unwrap();
+        assert_eq!(db.get_task_by_id(1).unwrap().unwrap().priority, 2);
+        assert!(db.get_task_by_id(3).unwrap().is_none());
+        assert_eq!(db.get_task_by_id(5).unwrap().unwrap().priority, 1);
+
+        // The whole session is undone at once
+        assert_eq!(db.undo_last().unwrap().len(), 3);
+        assert_eq!(db.get_tasks(&TaskFilter::default()).unwrap().len(), 5);
+    }
+
+    #[test]
+    fn test_reminders() {
+        let (db, _temp_file) = create_test_db();
+        let now = Utc::now();
+        let mut ids = Vec::new();
+        for (title, due) in [
+            ("Pay rent", Some(now - Duration::hours(2))),
+            ("Call back", Some(now + Duration::hours(3))),
+            ("Plan trip", Some(now + Duration::days(5))),
+            ("Undated", None),
+        ] {
+            let mut task = Task::new(title.to_string(), None, None, 1);
+            task.due_date = due;
+            ids.push(db.add_task(&task).unwrap());
+        }
+
+        let due = db.get_unreminded_due(now + Duration::hours(24)).unwrap();
+        let titles: Vec<&str> = due.iter().map(|task| task.title.as_str()).collect();
+        assert_eq!(titles, ["Pay rent", "Call back"]);
+        let notes = reminders(&due);
+        assert_eq!(notes[0].0, "Overdue: Pay rent");
+        assert!(notes[0].1.starts_with("Task 1 was due "));
+        assert_eq!(notes[1].0, "Due soon: Call back");
+
+        // A focus session holds reminders until it ends
+        let session = db
+            .start_focus_session(None, now + Duration::minutes(50))
+            .unwrap();
+        notify_due(&db, Duration::hours(24), Default::default(), true).unwrap();
+        assert_eq!(
+            db.get_unreminded_due(now + Duration::hours(24))
+                .unwrap()
+                .len(),
+            2
+        );
+        db.end_focus_session(session).unwrap();
+
+        // Once reminded, only a new due date reminds again
+        notify_due(&db, Duration::hours(24), Default::default(), true).unwrap();
+        assert!(db
+            .get_unreminded_due(now + Duration::hours(24))
+            .unwrap()
+            .is_empty());
+        let mut task = db.get_task_by_id(ids[1]).unwrap().unwrap();
+        task.due_date = Some(now + Duration::hours(6));
+        db.edit_task(ids[1], &task).unwrap();
+        assert_eq!(
+            db.get_unreminded_due(now + Duration::hours(24))
+                .unwrap()
+                .len(),
+            1
+        );
+
+        let many: Vec<Task> = (0..5)
+            .map(|n| Task::new(format!("Task {}", n), None, Some(now), 1))
+            .collect();
+        let notes = reminders(&many);
+        assert_eq!(notes.len(), 1);
+        assert_eq!(notes[0].0, "5 tasks due");
+        assert!(notes[0].1.ends_with("and 2 more"));
+        let notes = digest(&many, &crate::Priority::Low);
+        assert_eq!(notes[0].0, "5 low priority tasks due");
+        assert_eq!(notes[0].1.lines().count(), 5);
+    }
+
+    #[test]
+    fn test_reminder_digests() {
+        let (db, _temp_file) = create_test_db();
+        let now = Utc::now();
+        let add = |title: &str, priority: i32| {
+            let task = Task::new(title.to_string(), None, Some(now), priority);
+            db.add_task(&task).unwrap()
+        };
+        let digests = crate::notify::DigestWindows {
+            low: 60,
+            ..Default::default()
+        };
+        let pending = || {
+            let due = db.get_unreminded_due(now + Duration::hours(1)).unwrap();
+            due.iter()
+                .map(|task| task.title.clone())
+                .collect::<Vec<_>>()
+        };
+
+        // The first digest goes out right away
+        add("Water plants", 0);
+        add("Sort photos", 0);
+        notify_due(&db, Duration::hours(1), digests, true).unwrap();
+        assert!(pending().is_empty());
+
+        // Later low priority reminders wait for the window; others don't
+        add("Dust shelves", 0);
+        add("Call the bank", 2);
+        notify_due(&db, Duration::hours(1), digests, true).unwrap();
+        assert_eq!(pending(), ["Dust shelves"]);
+
+        let key = format!("{}_low", crate::db::META_LAST_DIGEST);
+        db.set_meta_datetime(&key, now - Duration::minutes(61))
+            .unwrap();
+        notify_due(&db, Duration::hours(1), digests, true).unwrap();
+        assert!(pending().is_empty());
+    }
+
+    #[test]
+    fn test_templates() {
+        let (db, _temp_file) = create_test_db();
+        let mut task = Task::new(
+            "Weekly report".to_string(),
+            Some("Numbers and notes".to_string()),
+            None,
+            2,
+        );
+        task.created_at = Utc::now() - Duration::days(10);
+        task.due_date = Some(task.created_at + Duration::days(4));
+        task.tags = vec!["work".to_string()];
+        let id = db.add_task(&task).unwrap();
+
+        assert!(template_save(&db, "weekly report", id, None).is_err());
+        assert!(template_save(&db, "weekly", 99, None).is_err());
+        assert!(template_save(&db, "weekly", id, Some("someday")).is_err());
+        template_save(&db, "weekly", id, None).unwrap();
+        let template = find_template(&db, "weekly").unwrap();
+        assert_eq!(
+            template,
+            crate::models::Template {
+                name: "weekly".to_string(),
+                title: "Weekly report".to_string(),
+                description: Some("Numbers and notes".to_string()),
+                priority: Some(2),
+                tags: vec!["work".to_string()],
+                project: None,
+                due: Some("in 4 days".to_string()),
+            }
+        );
+
+        template_save(&db, "weekly", id, Some("next friday")).unwrap();
+        assert_eq!(db.get_templates().unwrap().len(), 1);
+        assert_eq!(
+            find_template(&db, "weekly").unwrap().due.as_deref(),
+            Some("next friday")
+        );
+
+        template_delete(&db, "weekly").unwrap();
+        assert!(template_delete(&db, "weekly").is_err());
+        assert!(find_template(&db, "weekly").is_err());
+    }
+
+    #[test]
+    fn test_move_to_column() {
+        use crate::board::Column;
+        let (db, _temp_file) = create_test_db();
+        let id = db
+            .add_task(&Task::new("Draft talk".to_string(), None, None, 1))
+            .unwrap();
+        let limits = WipLimits::default();
+        let column = |db: &Database| Column::of(&db.get_task_by_id(id).unwrap().unwrap());
+
+        move_to_column(&db, id, Column::InProgress, limits, false).unwrap();
+        assert_eq!(column(&db), Column::InProgress);
+        move_to_column(&db, id, Column::Done, limits, false).unwrap();
+        assert_eq!(column(&db), Column::Done);
+        move_to_column(&db, id, Column::Todo, limits, false).unwrap();
+        assert_eq!(column(&db), Column::Todo);
+        assert!(move_to_column(&db, 99, Column::Todo, limits, false).is_err());
+
+        // The move back to To do is undone in one step
+        db.undo_last().unwrap();
+        assert_eq!(column(&db), Column::Done);
+    }
+
+    #[test]
+    fn test_clone_task() {
+        let (db, _temp_file) = create_test_db();
+        let mut task = Task::new(
+            "Water plants".to_string(),
+            Some("Balcony too".to_string()),
+            Some(Utc::now() - Duration::days(2)),
+            2,
+        );
+        task.tags = vec!["home".to_string()];
+        task.project = Some("house".to_string());
+        let id = db.add_task(&task).unwrap();
+        db.complete_task(id).unwrap();
+
+        clone_task(&db, id, None).unwrap();
+        clone_task(&db, id, Some("tomorrow")).unwrap();
+        assert!(clone_task(&db, 99, None).is_err());
+        assert!(clone_task(&db, id, Some("whenever")).is_err());
+
+        let copy = db.get_task_by_id(id + 1).unwrap().unwrap();
+        assert_eq!(copy.title, "Water plants");
+        assert_eq!(copy.description.as_deref(), Some("Balcony too"));
+        assert_eq!(
+            (copy.priority, copy.completed, copy.due_date),
+            (2, false, None)
+        );
+        assert_eq!(copy.tags, vec!["home"]);
+        assert_eq!(copy.project.as_deref(), Some("house"));
+        assert!(db
+            .get_task_by_id(id + 2)
+            .unwrap()
+            .unwrap()
+            .due_date
+            .is_some());
+    }
+
+    #[test]
+    fn test_purge() {
+        let (db, _temp_file) = create_test_db();
+        let done = db
+            .add_task(&Task::new("Done".to_string(), None, None, 1))
+            .unwrap();
+        let open = db
+            .add_task(&Task::new("Open".to_string(), None, None, 1))
+            .unwrap();
+        db.complete_task(done).unwrap();
+
+        assert!(purge(&db, Some("a while"), false, true).is_err());
+        purge(&db, Some("30d"), false, true).unwrap();
+        assert!(db.get_task_by_id(done).unwrap().is_some());
+        purge(&db, None, true, true).unwrap();
+        assert!(db.get_task_by_id(done).unwrap().is_none());
+        assert_eq!(db.get_archived_tasks().unwrap().len(), 1);
+        assert!(db.get_task_by_id(open).unwrap().is_some());
+
+        let mut output = Vec::new();
+        let mut answer = std::io::Cursor::new("Y\n");
+        assert!(confirm("Delete?", &mut answer, &mut output).unwrap());
+        assert_eq!(String::from_utf8(output).unwrap(), "Delete? [y/N] ");
+        let mut empty = std::io::Cursor::new("");
+        assert!(!confirm("Delete?", &mut empty, &mut Vec::new()).unwrap());
+
+        let tasks: Vec<Task> = (0..12)
+            .map(|n| Task::new(format!("Chore {}", n), None, None, 1))
+            .collect();
+        let mut output = Vec::new();
+        let mut answer = std::io::Cursor::new("n\n");
+        assert!(!confirm_tasks_with(&tasks, "Delete 12 tasks?", &mut answer, &mut output).unwrap());
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("Chore 9") && !output.contains("Chore 10"));
+        assert!(output.ends_with("  … and 2 more\nDelete 12 tasks? [y/N] "));
+    }
+
+    #[test]
+    fn test_parse_batch() {
+        let text = "# weekly chores\nadd 'Water plants' --tag home\n\ntodo complete 3\n";
+        assert_eq!(
+            parse_batch(text).unwrap(),
+            vec![
+                (
+                    2,
+                    vec![
+                        "add".to_string(),
+                        "Water plants".to_string(),
+                        "--tag".to_string(),
+                        "home".to_string()
+                    ]
+                ),
+                (4, vec!["complete".to_string(), "3".to_string()]),
+            ]
+        );
+
+        let json = r#"["add \"Buy milk\"", ["delete", "4"]]"#;
+        assert_eq!(
+            parse_batch(json).unwrap(),
+            vec![
+                (1, vec!["add".to_string(), "Buy milk".to_string()]),
+                (2, vec!["delete".to_string(), "4".to_string()]),
+            ]
+        );
+
+        assert!(parse_batch("add 'unterminated").is_err());
+        assert!(parse_batch(r#"[{"op": "add"}]"#).is_err());
+        assert!(parse_batch("todo\n").is_err());
+    }
+
+    #[test]
+    fn test_share() {
+        let (db, _temp_file) = create_test_db();
+        let mut task = Task::new("Plan party".to_string(), None, None, 1);
+        task.project = Some("home".to_string());
+        let id = db.add_task(&task).unwrap();
+        let base = "https://todo.example.com";
+
+        share(&db, Some(id), None, "7d", base).unwrap();
+        share(&db, None, Some("home"), "2w", base).unwrap();
+        assert!(share(&db, Some(99), None, "7d", base).is_err());
+        assert!(share(&db, None, Some("work"), "7d", base).is_err());
+        assert!(share(&db, Some(id), None, "soon", base).is_err());
+        assert!(share(&db, None, None, "7d", base).is_err());
+    }
+
+    #[test]
+    fn test_goal_commands() {
+        let (db, _temp_file) = create_test_db();
+        goal_add(&db, "Run a 10k", Some("2099-06-01")).unwrap();
+        assert!(goal_add(&db, "Too late", Some("2000-01-01")).is_err());
+
+        let priority = crate::Priority::Medium;
+        add_task(
+            &db,
+            "Buy shoes",
+            None,
+            None,
+            None,
+            Some(&priority),
+            &[],
+            None,
+            None,
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        add_task(
+            &db,
+            "Run 5k",
+            None,
+            None,
+            None,
+            Some(&priority),
+            &[],
+            None,
+            None,
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+
+        assert!(goal_link(&db, 99, &[1]).is_err());
+        assert!(goal_link(&db, 1, &[1, 99]).is_err());
+        assert!(db.get_goal_tasks(1).unwrap().is_empty());
+
+        goal_link(&db, 1, &[1, 2]).unwrap();
+        complete_task(&db, &[1], false).unwrap();
+        goal_show(&db, 1).unwrap();
+        let tasks = db.get_goal_tasks(1).unwrap();
+        assert_eq!(tasks.iter().filter(|t| t.completed).count(), 1);
+
+        goal_unlink(&db, &[2]).unwrap();
+        assert_eq!(db.get_goal_tasks(1).unwrap().len(), 1);
+
+        goal_delete(&db, 1).unwrap();
+        assert!(goal_show(&db, 1).is_err());
+    }
+
+    #[test]
+    fn test_project_commands() {
+        let (db, _temp_file) = create_test_db();
+        let priority = crate::Priority::Medium;
+        add_task(
+            &db,
+            "Fix bug",
+            None,
+            None,
+            None,
+            Some(&priority),
+            &[],
+            Some("work"),
+            None,
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        add_task(
+            &db,
+            "Mow lawn",
+            None,
+            None,
+            None,
+            Some(&priority),
+            &[],
+            Some("home"),
+            None,
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        add_task(
+            &db,
+            "Loose end",
+            None,
+            None,
+            None,
+            Some(&priority),
+            &[],
+            None,
+            None,
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        assert!(add_task(
+            &db,
+            "Blank",
+            None,
+            None,
+            None,
+            Some(&priority),
+            &[],
+            Some("  "),
+            None,
+            None,
+            None,
+            None,
+            false
+        )
+        .is_err());
+
+        let groups = group_by_project(db.get_tasks(&TaskFilter::default()).unwrap());
+        let names: Vec<Option<String>> = groups.into_iter().map(|(name, _)| name).collect();
+        assert_eq!(
+            names,
+            vec![Some("home".to_string()), Some("work".to_string()), None]
+        );
+
+        assert!(project_rename(&db, "work", "home").is_err());
+        assert!(project_rename(&db, "nope", "x").is_err());
+        project_rename(&db, "work", "job").unwrap();
+        assert_eq!(
+            db.get_task_by_id(1).unwrap().unwrap().project.as_deref(),
+            Some("job")
+        );
+
+        project_delete(&db, "job").unwrap();
+        assert!(project_delete(&db, "job").is_err());
+        assert_eq!(db.get_task_by_id(1).unwrap().unwrap().project, None);
+    }
+
+    fn import_options(
+        format: Option<crate::import::FileFormat>,
+        path: Option<&std::path::Path>,
+        on_conflict: crate::import::OnConflict,
+    ) -> crate::import::ImportOptions<'_> {
+        crate::import::ImportOptions {
+            format,
+            path,
+            resume: false,
+            on_conflict,
+            dry_run: false,
+            force: false,
+            default_priority: 1,
+            remap: Default::default(),
+            interactive: false,
+        }
+    }
+
+    #[test]
+    fn test_import_file_resumes_after_last_chunk() {
+        use crate::db::META_IMPORT_PROGRESS;
+        use crate::import::{self, FileFormat, OnConflict, Progress};
+
+        let (db, _temp_file) = create_test_db();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tasks.csv");
+        std::fs::write(&path, "title\nOne\nTwo\nThree\n").unwrap();
+
+        // An earlier run committed the first two rows, then died
+        let contents = std::fs::read(&path).unwrap();
+        let mut tasks =
+            import::parse(FileFormat::Csv, std::str::from_utf8(&contents).unwrap(), 1).unwrap();
+        import::assign_ids(
+            &mut tasks,
+            &Default::default(),
+            1,
+            OnConflict::Renumber,
+            |_| false,
+        )
+        .unwrap();
+        let mut progress = Progress {
+            format: FileFormat::Csv,
+            path: path.clone(),
+            checksum: import::checksum(&contents),
+            done: 0,
+            total: 3,
+            on_conflict: OnConflict::Renumber,
+            taken: Vec::new(),
+            next_id: 1,
+            force: false,
+            remap: Default::default(),
+        };
+        let chunk = |done| assert!(done <= 2, "only the first chunk commits");
+        import_chunks(&db, &tasks[..2], &mut progress, 2, chunk).unwrap();
+
+        let options = import_options(Some(FileFormat::Csv), Some(&path), OnConflict::Renumber);
+        let error = import_file(&db, &options).unwrap_err();
+        assert!(error.to_string().contains("--resume"));
+
+        let resume = import::ImportOptions {
+            resume: true,
+            ..import_options(None, None, OnConflict::Renumber)
+        };
+        import_file(&db, &resume).unwrap();
+        let titles: Vec<String> = db
+            .get_tasks(&TaskFilter::any_status())
+            .unwrap()
+            .into_iter()
+            .map(|task| task.title)
+            .collect();
+        assert_eq!(titles, vec!["One", "Two", "Three"]);
+        assert_eq!(db.get_meta(META_IMPORT_PROGRESS).unwrap(), None);
+        assert!(import_file(&db, &resume).is_err());
+    }
+
+    #[test]
+    fn test_import_markdown_checklist() {
+        use crate::import::OnConflict;
+
+        let (db, _temp_file) = create_test_db();
+        add_task(
+            &db,
+            "Existing",
+            None,
+            None,
+            None,
+            None,
+            &[],
+            None,
+            None,
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.md");
+        std::fs::write(
+            &path,
+            "# Week\n\n## work\n\n- [ ] Launch ⏫\n  - [x] Slides ✅ 2030-01-02\n\n## No project\n\n- [ ] Existing\n",
+        )
+        .unwrap();
+
+        import_file(&db, &import_options(None, Some(&path), OnConflict::Merge)).unwrap();
+        let mut tasks: Vec<(i32, String, Option<i32>, Option<String>)> = db
+            .get_tasks(&TaskFilter::any_status())
+            .unwrap()
+            .into_iter()
+            .map(|task| (task.id.unwrap(), task.title, task.parent_id, task.project))
+            .collect();
+        tasks.sort();
+        // The pending "Existing" is a duplicate, and nothing is merged into
+        // task 1: the file's tasks have no IDs of their own
+        assert_eq!(
+            tasks,
+            vec![
+                (1, "Existing".to_string(), None, None),
+                (2, "Launch".to_string(), None, Some("work".to_string())),
+                (3, "Slides".to_string(), Some(2), Some("work".to_string())),
+            ]
+        );
+        assert!(db.get_task_by_id(3).unwrap().unwrap().completed);
+    }
+
+    #[test]
+    fn test_import_remaps_projects_and_tags() {
+        use crate::import::{FileFormat, OnConflict, Remap};
+
+        let (db, _temp_file) = create_test_db();
+        add_task(
+            &db,
+            "Mine",
+            None,
+            None,
+            None,
+            Some(&crate::Priority::Medium),
+            &["home".to_string()],
+            Some("job"),
+            None,
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("theirs.csv");
+        std::fs::write(
+            &path,
+            "title,project,tags\nA,work,home;urgent\nB,hobby,reading\n",
+        )
+        .unwrap();
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let tasks = crate::import::parse(FileFormat::Csv, &contents, 1).unwrap();
+
+        // Known names and mapped ones aren't asked about
+        let mut remap = Remap {
+            tags: [("urgent".to_string(), None)].into(),
+            ..Default::default()
+        };
+        let known = ["job".to_string(), "#home".to_string()].into();
+        let mut output = Vec::new();
+        let mut input = std::io::Cursor::new("\njob\n-\n");
+        ask_remap(&tasks, &known, &mut remap, &mut input, &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("Project hobby (1 task) is new"));
+        assert!(!output.contains("#home") && !output.contains("#urgent"));
+        assert_eq!(
+            remap.projects,
+            [("work".to_string(), Some("job".to_string()))].into()
+        );
+        assert_eq!(
+            remap.tags,
+            [("reading".to_string(), None), ("urgent".to_string(), None)].into()
+        );
+
+        let options = crate::import::ImportOptions {
+            remap,
+            ..import_options(Some(FileFormat::Csv), Some(&path), OnConflict::Renumber)
+        };
+        import_file(&db, &options).unwrap();
+        let imported: Vec<(Option<String>, Vec<String>)> = db
+            .get_tasks(&TaskFilter::any_status())
+            .unwrap()
+            .into_iter()
+            .skip(1)
+            .map(|task| (task.project, task.tags))
+            .collect();
+        assert_eq!(
+            imported,
+            vec![
+                (Some("job".to_string()), vec!["home".to_string()]),
+                (Some("hobby".to_string()), vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_taskwarrior_reimport_skips_linked_tasks() {
+        use crate::import::{FileFormat, OnConflict};
+
+        let (db, _temp_file) = create_test_db();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("export.json");
+        std::fs::write(
+            &path,
+            r#"[{"uuid":"u1","description":"Water plants","status":"pending"}]"#,
+        )
+        .unwrap();
+
+        let options = import_options(
+            Some(FileFormat::Taskwarrior),
+            Some(&path),
+            OnConflict::Renumber,
+        );
+        import_file(&db, &options).unwrap();
+        import_file(&db, &options).unwrap();
+        assert_eq!(db.get_tasks(&TaskFilter::any_status()).unwrap().len(), 1);
+        assert!(db.find_linked_task("taskwarrior", "u1").unwrap().is_some());
+    }
+
+    #[test]
+    fn test_export_import_roundtrip_with_conflicts() {
+        use crate::import::{ImportOptions, OnConflict};
+
+        let (db, _temp_file) = create_test_db();
+        let priority = crate::Priority::High;
+        add_task(
+            &db,
+            "Launch",
+            None,
+            None,
+            None,
+            Some(&priority),
+            &[],
+            None,
+            None,
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        add_task(
+            &db,
+            "Slides",
+            None,
+            None,
+            None,
+            Some(&priority),
+            &[],
+            None,
+            None,
+            Some(1),
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tasks.json");
+        export(&db, crate::ExportFormat::Json, Some(&path)).unwrap();
+
+        // Into an empty database, IDs and nesting are kept
+        let (fresh, _fresh_file) = create_test_db();
+        import_file(
+            &fresh,
+            &import_options(None, Some(&path), OnConflict::Renumber),
+        )
+        .unwrap();
+        let slides = fresh.get_task_by_id(2).unwrap().unwrap();
+        assert_eq!(slides.title, "Slides");
+        assert_eq!(slides.parent_id, Some(1));
+
+        // A dry run writes nothing
+        let dry_run = ImportOptions {
+            dry_run: true,
+            ..import_options(None, Some(&path), OnConflict::Renumber)
+        };
+        import_file(&db, &dry_run).unwrap();
+        assert_eq!(db.get_tasks(&TaskFilter::any_status()).unwrap().len(), 2);
+
+        // Tasks that are already pending are not imported again
+        import_file(
+            &db,
+            &import_options(None, Some(&path), OnConflict::Renumber),
+        )
+        .unwrap();
+        assert_eq!(db.get_tasks(&TaskFilter::any_status()).unwrap().len(), 2);
+
+        // Forced, renumbering gives both tasks new IDs and keeps them nested
+        let forced = ImportOptions {
+            force: true,
+            ..import_options(None, Some(&path), OnConflict::Renumber)
+        };
+        import_file(&db, &forced).unwrap();
+        let copy = db.get_task_by_id(4).unwrap().unwrap();
+        assert_eq!(copy.title, "Slides");
+        assert_eq!(copy.parent_id, Some(3));
+
+        // Merging overwrites the existing tasks in place
+        let mut document: Vec<Task> =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        document[0].title = "Launch v2".to_string();
+        std::fs::write(&path, serde_json::to_string(&document).unwrap()).unwrap();
+        import_file(&db, &import_options(None, Some(&path), OnConflict::Merge)).unwrap();
+        assert_eq!(db.get_tasks(&TaskFilter::any_status()).unwrap().len(), 4);
+        assert_eq!(db.get_task_by_id(1).unwrap().unwrap().title, "Launch v2");
     }
 }
@@ -0,0 +1,1723 @@
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml_edit::{DocumentMut, ImDocument, Item, TableLike};
+
+use crate::calendar;
+use crate::files::{write_atomic, FileLock};
+use crate::keymap::{self, Keymap};
+use crate::logging::LogFile;
+use crate::style::{self, ColorScheme};
+use crate::zone::Zone;
+use crate::Priority;
+
+pub const DEFAULT_LOG_MAX_KB: i64 = 1024;
+pub const DEFAULT_LOG_MAX_DAYS: i64 = 7;
+pub const DEFAULT_WIP_LIMIT_IN_PROGRESS: i64 = 3;
+pub const DEFAULT_WORKDAY_HOURS: i64 = 6;
+pub const DEFAULT_DIGEST_LOW_MINUTES: i64 = 240;
+pub const DEFAULT_DIGEST_MEDIUM_MINUTES: i64 = 60;
+pub const DEFAULT_SYNC_REMOTE: &str = "origin";
+
+/// Value types a config key can hold.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Bool,
+    Integer,
+    Priority,
+    Text,
+    DateFormat,
+    ColorScheme,
+    Formula,
+    Weekday,
+    TimeZone,
+}
+
+impl Kind {
+    fn describe(&self) -> &'static str {
+        match self {
+            Kind::Bool => "a boolean (true or false)",
+            Kind::Integer => "an integer",
+            Kind::Priority => "one of \"low\", \"medium\" or \"high\"",
+            Kind::Text => "a string",
+            Kind::DateFormat => "a strftime date format such as \"%d/%m/%Y\"",
+            Kind::ColorScheme => "one of \"default\", \"colorblind\" or \"mono\"",
+            Kind::Weekday => "a day of the week such as \"monday\" or \"sunday\"",
+            Kind::Formula => "an urgency formula such as \"2*priority + overdue_days\"",
+            Kind::TimeZone => "\"local\", \"utc\" or an offset such as \"+05:30\"",
+        }
+    }
+}
+
+pub struct KeySpec {
+    pub name: &'static str,
+    pub kind: Kind,
+    pub description: &'static str,
+}
+
+/// Every recognised config key. Keys are flat, top-level TOML entries; the
+/// only tables are `[tui.keys]`, read into `Config::tui_keys`, `[colors]`,
+/// read into `Config::colors` with its `tags` and `projects` subtables in
+/// `Config::tag_colors` and `Config::project_colors`, and `[alias]`, read
+/// into `Config::aliases`.
+pub const KEYS: &[KeySpec] = &[
+    KeySpec {
+        name: "default_priority",
+        kind: Kind::Priority,
+        description: "Priority for `todo add` when --priority is not given",
+    },
+    KeySpec {
+        name: "due_soon_hours",
+        kind: Kind::Integer,
+        description: "Size of the \"due soon\" window in hours",
+    },
+    KeySpec {
+        name: "maintenance_interval",
+        kind: Kind::Integer,
+        description: "Minutes between automatic maintenance runs",
+    },
+    KeySpec {
+        name: "stale_tagging",
+        kind: Kind::Bool,
+        description: "Tag tasks untouched for 30 days as stale during maintenance",
+    },
+    KeySpec {
+        name: "priority_decay",
+        kind: Kind::Bool,
+        description: "Decay the priority of snoozed someday/maybe tasks",
+    },
+    KeySpec {
+        name: "usage_metrics",
+        kind: Kind::Bool,
+        description: "Record command usage locally",
+    },
+    KeySpec {
+        name: "command_history",
+        kind: Kind::Bool,
+        description: "Remember recent commands for `todo history` and `todo !!`",
+    },
+    KeySpec {
+        name: "color",
+        kind: Kind::Bool,
+        description: "Use colors in terminal output",
+    },
+    KeySpec {
+        name: "color_scheme",
+        kind: Kind::ColorScheme,
+        description: "Colors for priorities and due dates",
+    },
+    KeySpec {
+        name: "plain",
+        kind: Kind::Bool,
+        description: "Plain output for screen readers and scripts: no color, emoji or box drawing",
+    },
+    KeySpec {
+        name: "week_start",
+        kind: Kind::Weekday,
+        description: "First day of the week in calendars and `todo report week --last`",
+    },
+    KeySpec {
+        name: "date_format",
+        kind: Kind::DateFormat,
+        description: "strftime format for due dates in terminal output",
+    },
+    KeySpec {
+        name: "timezone",
+        kind: Kind::TimeZone,
+        description:
+            "Zone due dates are entered and shown in; \"local\" follows the system or `TZ`",
+    },
+    KeySpec {
+        name: "list_filter",
+        kind: Kind::Text,
+        description: "Flags every `todo list` starts from, e.g. \"--not-tag waiting\"",
+    },
+    KeySpec {
+        name: "log_file",
+        kind: Kind::Text,
+        description: "File that syncs, imports, maintenance and errors are logged to",
+    },
+    KeySpec {
+        name: "log_max_kb",
+        kind: Kind::Integer,
+        description: "Rotate the log file once it grows past this many KiB",
+    },
+    KeySpec {
+        name: "log_max_days",
+        kind: Kind::Integer,
+        description: "Rotate the log file once its oldest entry is this many days old",
+    },
+    KeySpec {
+        name: "wip_limit_todo",
+        kind: Kind::Integer,
+        description: "Most tasks the TUI board's To do column should hold (0 for no limit)",
+    },
+    KeySpec {
+        name: "wip_limit_in_progress",
+        kind: Kind::Integer,
+        description: "Most tasks the TUI board's In progress column should hold (0 for no limit)",
+    },
+    KeySpec {
+        name: "digest_low_minutes",
+        kind: Kind::Integer,
+        description:
+            "Minutes `todo notify` gathers low priority reminders into one (0 to send each)",
+    },
+    KeySpec {
+        name: "digest_medium_minutes",
+        kind: Kind::Integer,
+        description:
+            "Minutes `todo notify` gathers medium priority reminders into one (0 to send each)",
+    },
+    KeySpec {
+        name: "digest_high_minutes",
+        kind: Kind::Integer,
+        description:
+            "Minutes `todo notify` gathers high priority reminders into one (0 to send each)",
+    },
+    KeySpec {
+        name: "tui_mouse",
+        kind: Kind::Bool,
+        description: "Let `todo tui` take mouse clicks and the scroll wheel",
+    },
+    KeySpec {
+        name: "urgency_priority",
+        kind: Kind::Integer,
+        description: "Weight of priority in the urgency `todo next` ranks by",
+    },
+    KeySpec {
+        name: "urgency_due",
+        kind: Kind::Integer,
+        description: "Weight of the due date in the urgency `todo next` ranks by",
+    },
+    KeySpec {
+        name: "urgency_age",
+        kind: Kind::Integer,
+        description: "Weight of a task's age in the urgency `todo next` ranks by",
+    },
+    KeySpec {
+        name: "urgency_energy",
+        kind: Kind::Integer,
+        description: "Weight of matching `todo next --energy` in the urgency it ranks by",
+    },
+    KeySpec {
+        name: "urgency_formula",
+        kind: Kind::Formula,
+        description: "Expression `todo next` ranks by instead of the weighted sum",
+    },
+    KeySpec {
+        name: "adjust_subtask_due",
+        kind: Kind::Bool,
+        description: "Move subtasks' due dates up when their parent's is set earlier",
+    },
+    KeySpec {
+        name: "auto_complete_parents",
+        kind: Kind::Bool,
+        description: "Complete a task once all of its subtasks are completed",
+    },
+    KeySpec {
+        name: "inherit_priority",
+        kind: Kind::Bool,
+        description: "Let subtasks and project tasks added without --priority inherit one",
+    },
+    KeySpec {
+        name: "workday_hours",
+        kind: Kind::Integer,
+        description: "Hours of work a day that `todo schedule` fills",
+    },
+    KeySpec {
+        name: "sqlite_extensions",
+        kind: Kind::Text,
+        description:
+            "SQLite extensions to load, as a path list like $PATH (needs allow_extensions)",
+    },
+    KeySpec {
+        name: "allow_extensions",
+        kind: Kind::Bool,
+        description:
+            "Load sqlite_extensions; this and file keys are ignored in a directory's .todo.toml",
+    },
+    KeySpec {
+        name: "sync_repo",
+        kind: Kind::Text,
+        description: "Git repository that `todo sync` keeps the tasks in",
+    },
+    KeySpec {
+        name: "sync_remote",
+        kind: Kind::Text,
+        description: "Remote of sync_repo that `todo sync` pulls from and pushes to",
+    },
+    KeySpec {
+        name: "db",
+        kind: Kind::Text,
+        description: "Path of the task database (~/.todo.db when unset)",
+    },
+    KeySpec {
+        name: "context",
+        kind: Kind::Text,
+        description: "Tag that `todo list` filters by and `todo add` attaches",
+    },
+    KeySpec {
+        name: "profile",
+        kind: Kind::Text,
+        description: "Profile applied on top of the config files",
+    },
+];
+
+pub fn find_key(name: &str) -> Option<&'static KeySpec> {
+    KEYS.iter().find(|key| key.name == name)
+}
+
+/// Effective settings after merging every config layer.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub default_priority: Priority,
+    pub due_soon_hours: i64,
+    pub maintenance_interval: i64,
+    pub stale_tagging: bool,
+    pub priority_decay: bool,
+    pub usage_metrics: bool,
+    pub command_history: bool,
+    pub color: bool,
+    pub color_scheme: ColorScheme,
+    pub plain: bool,
+    pub date_format: String,
+    pub week_start: chrono::Weekday,
+    pub timezone: Zone,
+    pub list_filter: Option<String>,
+    pub log_file: Option<String>,
+    pub log_max_kb: i64,
+    pub log_max_days: i64,
+    pub wip_limit_todo: i64,
+    pub wip_limit_in_progress: i64,
+    pub digests: crate::notify::DigestWindows,
+    pub tui_mouse: bool,
+    pub urgency: crate::urgency::Weights,
+    pub urgency_formula: Option<String>,
+    pub adjust_subtask_due: bool,
+    pub auto_complete_parents: bool,
+    pub inherit_priority: bool,
+    pub workday_hours: i64,
+    pub sqlite_extensions: Option<String>,
+    pub allow_extensions: bool,
+    pub sync_repo: Option<String>,
+    pub sync_remote: String,
+    pub db: Option<String>,
+    pub context: Option<String>,
+    pub profile: Option<String>,
+    /// Key bindings of `todo tui`, from `[tui.keys]`.
+    pub tui_keys: Keymap,
+    /// Colors of terminal output, from `[colors]`.
+    pub colors: style::Colors,
+    /// Colors of single tags, from `[colors.tags]`.
+    pub tag_colors: style::BadgeColors,
+    /// Colors of single projects, from `[colors.projects]`.
+    pub project_colors: style::BadgeColors,
+    /// Command lines that a name stands for, from `[alias]`.
+    pub aliases: BTreeMap<String, String>,
+    sources: HashMap<&'static str, Source>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            default_priority: Priority::Medium,
+            due_soon_hours: crate::models::DEFAULT_DUE_SOON_HOURS,
+            maintenance_interval: crate::maintenance::DEFAULT_INTERVAL_MINUTES,
+            stale_tagging: false,
+            priority_decay: false,
+            usage_metrics: false,
+            command_history: false,
+            color: true,
+            color_scheme: ColorScheme::Default,
+            plain: false,
+            date_format: style::DEFAULT_DATE_FORMAT.to_string(),
+            week_start: chrono::Weekday::Mon,
+            timezone: Zone::Local,
+            list_filter: None,
+            log_file: None,
+            log_max_kb: DEFAULT_LOG_MAX_KB,
+            log_max_days: DEFAULT_LOG_MAX_DAYS,
+            wip_limit_todo: 0,
+            wip_limit_in_progress: DEFAULT_WIP_LIMIT_IN_PROGRESS,
+            digests: crate::notify::DigestWindows {
+                low: DEFAULT_DIGEST_LOW_MINUTES,
+                medium: DEFAULT_DIGEST_MEDIUM_MINUTES,
+                high: 0,
+            },
+            tui_mouse: true,
+            urgency: crate::urgency::Weights::default(),
+            urgency_formula: None,
+            adjust_subtask_due: false,
+            auto_complete_parents: false,
+            inherit_priority: false,
+            workday_hours: DEFAULT_WORKDAY_HOURS,
+            sqlite_extensions: None,
+            allow_extensions: false,
+            sync_repo: None,
+            sync_remote: DEFAULT_SYNC_REMOTE.to_string(),
+            db: None,
+            context: None,
+            profile: None,
+            tui_keys: Keymap::default(),
+            colors: style::Colors::new(),
+            tag_colors: style::BadgeColors::new(),
+            project_colors: style::BadgeColors::new(),
+            aliases: BTreeMap::new(),
+            sources: HashMap::new(),
+        }
+    }
+}
+
+/// A typed config value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Setting {
+    Bool(bool),
+    Integer(i64),
+    Priority(i32),
+    Text(String),
+    ColorScheme(ColorScheme),
+}
+
+impl std::fmt::Display for Setting {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Setting::Bool(value) => write!(f, "{}", value),
+            Setting::Integer(value) => write!(f, "{}", value),
+            Setting::Priority(value) => {
+                write!(f, "{}", Priority::from_int(*value).name())
+            }
+            Setting::Text(value) => write!(f, "{}", value),
+            Setting::ColorScheme(value) => write!(f, "{}", value.name()),
+        }
+    }
+}
+
+/// Where an effective value came from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    Default,
+    File { origin: String, line: usize },
+    Env(String),
+    Flag(&'static str),
+}
+
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Source::Default => write!(f, "default"),
+            Source::File { origin, line } => write!(f, "{}:{}", origin, line),
+            Source::Env(var) => write!(f, "${}", var),
+            Source::Flag(flag) => write!(f, "{}", flag),
+        }
+    }
+}
+
+impl Config {
+    pub fn source(&self, key: &str) -> Source {
+        self.sources.get(key).cloned().unwrap_or(Source::Default)
+    }
+
+    pub fn get(&self, key: &str) -> Option<Setting> {
+        Some(match key {
+            "default_priority" => Setting::Priority(self.default_priority.to_int()),
+            "due_soon_hours" => Setting::Integer(self.due_soon_hours),
+            "maintenance_interval" => Setting::Integer(self.maintenance_interval),
+            "stale_tagging" => Setting::Bool(self.stale_tagging),
+            "priority_decay" => Setting::Bool(self.priority_decay),
+            "usage_metrics" => Setting::Bool(self.usage_metrics),
+            "command_history" => Setting::Bool(self.command_history),
+            "color" => Setting::Bool(self.color),
+            "color_scheme" => Setting::ColorScheme(self.color_scheme),
+            "plain" => Setting::Bool(self.plain),
+            "date_format" => Setting::Text(self.date_format.clone()),
+            "week_start" => Setting::Text(calendar::weekday_name(self.week_start).to_string()),
+            "timezone" => Setting::Text(self.timezone.name()),
+            "list_filter" => Setting::Text(self.list_filter.clone().unwrap_or_default()),
+            "log_file" => Setting::Text(self.log_file.clone().unwrap_or_default()),
+            "log_max_kb" => Setting::Integer(self.log_max_kb),
+            "log_max_days" => Setting::Integer(self.log_max_days),
+            "wip_limit_todo" => Setting::Integer(self.wip_limit_todo),
+            "wip_limit_in_progress" => Setting::Integer(self.wip_limit_in_progress),
+            "digest_low_minutes" => Setting::Integer(self.digests.low),
+            "digest_medium_minutes" => Setting::Integer(self.digests.medium),
+            "digest_high_minutes" => Setting::Integer(self.digests.high),
+            "tui_mouse" => Setting::Bool(self.tui_mouse),
+            "urgency_priority" => Setting::Integer(self.urgency.priority),
+            "urgency_due" => Setting::Integer(self.urgency.due),
+            "urgency_age" => Setting::Integer(self.urgency.age),
+            "urgency_energy" => Setting::Integer(self.urgency.energy),
+            "urgency_formula" => Setting::Text(self.urgency_formula.clone().unwrap_or_default()),
+            "adjust_subtask_due" => Setting::Bool(self.adjust_subtask_due),
+            "auto_complete_parents" => Setting::Bool(self.auto_complete_parents),
+            "inherit_priority" => Setting::Bool(self.inherit_priority),
+            "workday_hours" => Setting::Integer(self.workday_hours),
+            "sqlite_extensions" => {
+                Setting::Text(self.sqlite_extensions.clone().unwrap_or_default())
+            }
+            "allow_extensions" => Setting::Bool(self.allow_extensions),
+            "sync_repo" => Setting::Text(self.sync_repo.clone().unwrap_or_default()),
+            "sync_remote" => Setting::Text(self.sync_remote.clone()),
+            "db" => Setting::Text(self.db.clone().unwrap_or_default()),
+            "context" => Setting::Text(self.context.clone().unwrap_or_default()),
+            "profile" => Setting::Text(self.profile.clone().unwrap_or_default()),
+            _ => return None,
+        })
+    }
+
+    fn apply(&mut self, key: &str, setting: Setting) {
+        match (key, setting) {
+            ("default_priority", Setting::Priority(value)) => {
+                self.default_priority = Priority::from_int(value)
+            }
+            ("due_soon_hours", Setting::Integer(value)) => self.due_soon_hours = value,
+            ("maintenance_interval", Setting::Integer(value)) => self.maintenance_interval = value,
+            ("stale_tagging", Setting::Bool(value)) => self.stale_tagging = value,
+            ("priority_decay", Setting::Bool(value)) => self.priority_decay = value,
+            ("usage_metrics", Setting::Bool(value)) => self.usage_metrics = value,
+            ("command_history", Setting::Bool(value)) => self.command_history = value,
+            ("color", Setting::Bool(value)) => self.color = value,
+            ("color_scheme", Setting::ColorScheme(value)) => self.color_scheme = value,
+            ("plain", Setting::Bool(value)) => self.plain = value,
+            ("date_format", Setting::Text(value)) => self.date_format = value,
+            ("week_start", Setting::Text(value)) => {
+                self.week_start = calendar::parse_weekday(&value).unwrap_or(self.week_start)
+            }
+            ("timezone", Setting::Text(value)) => {
+                self.timezone = Zone::parse(&value).unwrap_or(self.timezone)
+            }
+            // An empty string unsets a text key, e.g. `TODO_CONTEXT=`.
+            ("db", Setting::Text(value)) => self.db = non_empty(value),
+            ("context", Setting::Text(value)) => self.context = non_empty(value),
+            ("profile", Setting::Text(value)) => self.profile = non_empty(value),
+            ("list_filter", Setting::Text(value)) => self.list_filter = non_empty(value),
+            ("urgency_formula", Setting::Text(value)) => self.urgency_formula = non_empty(value),
+            ("log_file", Setting::Text(value)) => self.log_file = non_empty(value),
+            ("log_max_kb", Setting::Integer(value)) => self.log_max_kb = value,
+            ("log_max_days", Setting::Integer(value)) => self.log_max_days = value,
+            ("wip_limit_todo", Setting::Integer(value)) => self.wip_limit_todo = value,
+            ("wip_limit_in_progress", Setting::Integer(value)) => {
+                self.wip_limit_in_progress = value
+            }
+            ("digest_low_minutes", Setting::Integer(value)) => self.digests.low = value,
+            ("digest_medium_minutes", Setting::Integer(value)) => self.digests.medium = value,
+            ("digest_high_minutes", Setting::Integer(value)) => self.digests.high = value,
+            ("tui_mouse", Setting::Bool(value)) => self.tui_mouse = value,
+            ("urgency_priority", Setting::Integer(value)) => self.urgency.priority = value,
+            ("urgency_due", Setting::Integer(value)) => self.urgency.due = value,
+            ("urgency_age", Setting::Integer(value)) => self.urgency.age = value,
+            ("urgency_energy", Setting::Integer(value)) => self.urgency.energy = value,
+            ("adjust_subtask_due", Setting::Bool(value)) => self.adjust_subtask_due = value,
+            ("auto_complete_parents", Setting::Bool(value)) => self.auto_complete_parents = value,
+            ("inherit_priority", Setting::Bool(value)) => self.inherit_priority = value,
+            ("workday_hours", Setting::Integer(value)) => self.workday_hours = value,
+            ("sqlite_extensions", Setting::Text(value)) => {
+                self.sqlite_extensions = non_empty(value)
+            }
+            ("allow_extensions", Setting::Bool(value)) => self.allow_extensions = value,
+            ("sync_repo", Setting::Text(value)) => self.sync_repo = non_empty(value),
+            ("sync_remote", Setting::Text(value)) => {
+                self.sync_remote = non_empty(value).unwrap_or(DEFAULT_SYNC_REMOTE.to_string())
+            }
+            _ => {}
+        }
+    }
+
+    /// Apply a command-line flag such as `--db`, the highest-priority layer.
+    pub fn apply_flag(&mut self, key: &'static str, setting: Setting, flag: &'static str) {
+        self.apply(key, setting);
+        self.sources.insert(key, Source::Flag(flag));
+    }
+
+    /// The database to open: `db` with `~/` expanded, or `~/.todo.db`.
+    pub fn db_path(&self) -> Result<PathBuf> {
+        expand_home(self.db.as_deref().unwrap_or("~/.todo.db"))
+    }
+
+    /// The SQLite extensions to load, `~/` expanded.
+    pub fn sqlite_extensions(&self) -> Result<Vec<PathBuf>> {
+        let Some(paths) = self.sqlite_extensions.as_deref() else {
+            return Ok(Vec::new());
+        };
+        std::env::split_paths(paths)
+            .filter(|path| !path.as_os_str().is_empty())
+            .map(|path| expand_home(&path.to_string_lossy()))
+            .collect()
+    }
+
+    /// The repository `todo sync` uses, `~/` expanded, if `sync_repo` is set.
+    pub fn sync_repo(&self) -> Result<Option<PathBuf>> {
+        self.sync_repo.as_deref().map(expand_home).transpose()
+    }
+
+    /// Where to log to, if `log_file` is set.
+    pub fn log_file(&self) -> Result<Option<LogFile>> {
+        let Some(path) = self.log_file.as_deref() else {
+            return Ok(None);
+        };
+        Ok(Some(LogFile {
+            path: expand_home(path)?,
+            max_bytes: self.log_max_kb.max(1) as u64 * 1024,
+            max_age: chrono::Duration::days(self.log_max_days.max(1)),
+        }))
+    }
+}
+
+/// `path` with a leading `~/` replaced by the home directory.
+fn expand_home(path: &str) -> Result<PathBuf> {
+    Ok(match path.strip_prefix("~/") {
+        Some(rest) => dirs::home_dir()
+            .ok_or_else(|| anyhow::anyhow!("Could not find home directory"))?
+            .join(rest),
+        None => PathBuf::from(path),
+    })
+}
+
+fn non_empty(value: String) -> Option<String> {
+    let value = value.trim().to_string();
+    (!value.is_empty()).then_some(value)
+}
+
+/// Split a flag string such as `--not-tag waiting --matches "^fix "` into
+/// arguments. Single and double quotes group words; there are no escapes.
+pub fn split_args(text: &str) -> std::result::Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut current: Option<String> = None;
+    let mut quote = None;
+    for c in text.chars() {
+        match (quote, c) {
+            (Some(open), c) if c == open => quote = None,
+            (Some(_), c) => current.get_or_insert_with(String::new).push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                current.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => args.extend(current.take()),
+            (None, c) => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err("unterminated quote".to_string());
+    }
+    args.extend(current);
+    Ok(args)
+}
+
+/// Where a config layer lives.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Layer {
+    System,
+    User,
+    Local,
+}
+
+impl Layer {
+    /// Layers in the order they are applied; later layers win.
+    pub const ALL: [Layer; 3] = [Layer::System, Layer::User, Layer::Local];
+
+    pub fn path(&self) -> Option<PathBuf> {
+        match self {
+            Layer::System => Some(PathBuf::from("/etc/todo/config.toml")),
+            Layer::User => dirs::config_dir().map(|dir| dir.join("todo").join("config.toml")),
+            Layer::Local => Some(PathBuf::from(".todo.toml")),
+        }
+    }
+}
+
+/// Convert a TOML item to the type `spec` expects.
+fn typed_value(spec: &KeySpec, item: &Item) -> Option<Setting> {
+    let value = item.as_value()?;
+    match spec.kind {
+        Kind::Bool => value.as_bool().map(Setting::Bool),
+        Kind::Integer => value.as_integer().map(Setting::Integer),
+        Kind::Priority => value
+            .as_str()
+            .and_then(parse_priority)
+            .map(Setting::Priority),
+        Kind::Text => value.as_str().map(|text| Setting::Text(text.to_string())),
+        Kind::DateFormat => value.as_str().and_then(parse_date_format),
+        Kind::Formula => value.as_str().and_then(parse_formula),
+        Kind::Weekday => value.as_str().and_then(parse_weekday),
+        Kind::TimeZone => value.as_str().and_then(parse_zone),
+        Kind::ColorScheme => value
+            .as_str()
+            .and_then(parse_color_scheme)
+            .map(Setting::ColorScheme),
+    }
+}
+
+fn parse_date_format(text: &str) -> Option<Setting> {
+    style::is_valid_date_format(text).then(|| Setting::Text(text.to_string()))
+}
+
+/// An empty formula is valid and stands for the built-in one.
+fn parse_formula(text: &str) -> Option<Setting> {
+    (text.trim().is_empty() || crate::urgency::Formula::parse(text).is_ok())
+        .then(|| Setting::Text(text.to_string()))
+}
+
+/// A weekday, kept under its full name.
+fn parse_weekday(text: &str) -> Option<Setting> {
+    calendar::parse_weekday(text).map(|day| Setting::Text(calendar::weekday_name(day).to_string()))
+}
+
+/// A time zone, kept under the name `Zone::parse` reads back.
+fn parse_zone(text: &str) -> Option<Setting> {
+    Zone::parse(text).map(|zone| Setting::Text(zone.name()))
+}
+
+fn parse_color_scheme(text: &str) -> Option<ColorScheme> {
+    <ColorScheme as clap::ValueEnum>::from_str(text, true).ok()
+}
+
+fn parse_priority(text: &str) -> Option<i32> {
+    <Priority as clap::ValueEnum>::from_str(text, true)
+        .ok()
+        .map(|p| p.to_int())
+}
+
+fn line_of(source: &str, offset: usize) -> usize {
+    source[..offset.min(source.len())].matches('\n').count() + 1
+}
+
+/// Parse one config file's contents into `config`. Unknown keys produce
+/// warnings; syntax and type errors are fatal and carry the line number.
+pub fn parse_into(config: &mut Config, source: &str, origin: &str) -> Result<Vec<String>> {
+    let document = ImDocument::parse(source)
+        .map_err(|e| anyhow::anyhow!("{}: invalid TOML: {}", origin, e))?;
+
+    let mut warnings = Vec::new();
+    for (key, item) in document.as_table().iter() {
+        let line = document
+            .as_table()
+            .key(key)
+            .and_then(|k| k.span())
+            .map(|span| line_of(source, span.start))
+            .unwrap_or(0);
+
+        if key == "tui" {
+            parse_tui(config, item, source, origin, &mut warnings)?;
+            continue;
+        }
+        if key == "colors" {
+            parse_colors(config, item, source, origin, &mut warnings)?;
+            continue;
+        }
+        if key == "alias" {
+            parse_aliases(config, item, source, origin)?;
+            continue;
+        }
+        let Some(spec) = find_key(key) else {
+            warnings.push(format!(
+                "{} line {}: unknown key `{}` ignored",
+                origin, line, key
+            ));
+            continue;
+        };
+        let setting = typed_value(spec, item).ok_or_else(|| {
+            anyhow::anyhow!(
+                "{} line {}: `{}` must be {}",
+                origin,
+                line,
+                key,
+                spec.kind.describe()
+            )
+        })?;
+        config.apply(key, setting);
+        config.sources.insert(
+            spec.name,
+            Source::File {
+                origin: origin.to_string(),
+                line,
+            },
+        );
+    }
+    Ok(warnings)
+}
+
+/// The `[tui]` table, of which only `[tui.keys]` is read. Each action there
+/// takes a key or a list of keys, replacing its defaults.
+fn parse_tui(
+    config: &mut Config,
+    item: &Item,
+    source: &str,
+    origin: &str,
+    warnings: &mut Vec<String>,
+) -> Result<()> {
+    let line_in = |table: &dyn TableLike, key: &str| {
+        table
+            .key(key)
+            .and_then(|k| k.span())
+            .map(|span| line_of(source, span.start))
+            .unwrap_or(0)
+    };
+    let tui = item
+        .as_table_like()
+        .ok_or_else(|| anyhow::anyhow!("{}: `tui` must be a table", origin))?;
+    for (key, item) in tui.iter() {
+        if key != "keys" {
+            warnings.push(format!(
+                "{} line {}: unknown key `tui.{}` ignored",
+                origin,
+                line_in(tui, key),
+                key
+            ));
+            continue;
+        }
+        let keys = item
+            .as_table_like()
+            .ok_or_else(|| anyhow::anyhow!("{}: `tui.keys` must be a table", origin))?;
+        for (name, value) in keys.iter() {
+            let line = line_in(keys, name);
+            let Some(action) = keymap::Action::from_name(name) else {
+                warnings.push(format!(
+                    "{} line {}: unknown action `tui.keys.{}` ignored",
+                    origin, line, name
+                ));
+                continue;
+            };
+            let texts = match value.as_value() {
+                Some(toml_edit::Value::String(text)) => Some(vec![text.value().as_str()]),
+                Some(toml_edit::Value::Array(array)) => {
+                    array.iter().map(|v| v.as_str()).collect::<Option<Vec<_>>>()
+                }
+                _ => None,
+            }
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "{} line {}: `tui.keys.{}` must be a key or a list of keys",
+                    origin,
+                    line,
+                    name
+                )
+            })?;
+            let bound = texts
+                .into_iter()
+                .map(keymap::Key::parse)
+                .collect::<std::result::Result<Vec<_>, _>>()
+                .map_err(|e| anyhow::anyhow!("{} line {}: {}", origin, line, e))?;
+            config.tui_keys.bind(action, bound);
+        }
+    }
+    Ok(())
+}
+
+/// The `[colors]` table: a color for any of `style::COLOR_ROLES`, on top of
+/// `color_scheme`, and the `tags` and `projects` subtables coloring badges
+/// by name.
+fn parse_colors(
+    config: &mut Config,
+    item: &Item,
+    source: &str,
+    origin: &str,
+    warnings: &mut Vec<String>,
+) -> Result<()> {
+    let colors = item
+        .as_table_like()
+        .ok_or_else(|| anyhow::anyhow!("{}: `colors` must be a table", origin))?;
+    for (role, value) in colors.iter() {
+        let line = colors
+            .key(role)
+            .and_then(|k| k.span())
+            .map(|span| line_of(source, span.start))
+            .unwrap_or(0);
+        if role == "tags" || role == "projects" {
+            let badges = value.as_table_like().ok_or_else(|| {
+                anyhow::anyhow!(
+                    "{} line {}: `colors.{}` must be a table",
+                    origin,
+                    line,
+                    role
+                )
+            })?;
+            for (name, value) in badges.iter() {
+                let line = badges
+                    .key(name)
+                    .and_then(|k| k.span())
+                    .map(|span| line_of(source, span.start))
+                    .unwrap_or(line);
+                let key = format!("{}.{}", role, name);
+                let color = parse_color_value(value, &key, origin, line)?;
+                match role {
+                    "tags" => config.tag_colors.insert(name.to_string(), color),
+                    _ => config.project_colors.insert(name.to_string(), color),
+                };
+            }
+            continue;
+        }
+        let Some(&role) = style::COLOR_ROLES.iter().find(|&&name| name == role) else {
+            warnings.push(format!(
+                "{} line {}: unknown key `colors.{}` ignored",
+                origin, line, role
+            ));
+            continue;
+        };
+        let color = parse_color_value(value, role, origin, line)?;
+        config.colors.insert(role, color);
+    }
+    Ok(())
+}
+
+/// The `[alias]` table: a command line for each name, such as
+/// `urgent = "list --priority high --due week"`.
+fn parse_aliases(config: &mut Config, item: &Item, source: &str, origin: &str) -> Result<()> {
+    let aliases = item
+        .as_table_like()
+        .ok_or_else(|| anyhow::anyhow!("{}: `alias` must be a table", origin))?;
+    for (name, value) in aliases.iter() {
+        let line = aliases
+            .key(name)
+            .and_then(|k| k.span())
+            .map(|span| line_of(source, span.start))
+            .unwrap_or(0);
+        let command = value
+            .as_str()
+            .filter(|command| split_args(command).is_ok_and(|args| !args.is_empty()))
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "{} line {}: `alias.{}` must be a command line such as \"list --priority high\"",
+                    origin,
+                    line,
+                    name
+                )
+            })?;
+        config.aliases.insert(name.to_string(), command.to_string());
+    }
+    Ok(())
+}
+
+/// The color `colors.<key>` is set to.
+fn parse_color_value(item: &Item, key: &str, origin: &str, line: usize) -> Result<colored::Color> {
+    item.as_str().and_then(style::parse_color).ok_or_else(|| {
+        anyhow::anyhow!(
+            "{} line {}: `colors.{}` must be a color such as \"red\", \"bright blue\" or \"#ff8000\"",
+            origin,
+            line,
+            key
+        )
+    })
+}
+
+/// Environment variable overriding `key`, e.g. `TODO_DUE_SOON_HOURS`.
+pub fn env_var(key: &str) -> String {
+    format!("TODO_{}", key.to_uppercase())
+}
+
+/// Apply `TODO_*` overrides on top of the config files. Booleans also
+/// accept 1/0 and yes/no so the older `TODO_PRIORITY_DECAY=1` style keeps
+/// working.
+pub fn apply_env(config: &mut Config, lookup: impl Fn(&str) -> Option<String>) -> Result<()> {
+    for spec in KEYS {
+        let var = env_var(spec.name);
+        let Some(raw) = lookup(&var) else {
+            continue;
+        };
+        let value = raw.trim();
+        let setting = match spec.kind {
+            Kind::Bool => match value.to_lowercase().as_str() {
+                "1" | "true" | "yes" | "on" => Some(Setting::Bool(true)),
+                "0" | "false" | "no" | "off" => Some(Setting::Bool(false)),
+                _ => None,
+            },
+            Kind::Integer => value.parse().ok().map(Setting::Integer),
+            Kind::Priority => parse_priority(value).map(Setting::Priority),
+            Kind::Text => Some(Setting::Text(value.to_string())),
+            Kind::DateFormat => parse_date_format(value),
+            Kind::Formula => parse_formula(value),
+            Kind::Weekday => parse_weekday(value),
+            Kind::TimeZone => parse_zone(value),
+            Kind::ColorScheme => parse_color_scheme(value).map(Setting::ColorScheme),
+        }
+        .ok_or_else(|| anyhow::anyhow!("{} must be {}", var, spec.kind.describe()))?;
+        config.apply(spec.name, setting);
+        config.sources.insert(spec.name, Source::Env(var));
+    }
+    Ok(())
+}
+
+/// Load and merge the system, user and local config files, the selected
+/// profile and then the environment. Command-line flags are applied by the
+/// caller on top. `profile` is the `--profile` flag, which wins over
+/// `TODO_PROFILE` and the sticky `profile` key.
+pub fn load(profile: Option<&str>) -> Result<(Config, Vec<String>)> {
+    let mut config = Config::default();
+    let mut warnings = Vec::new();
+
+    for layer in Layer::ALL {
+        let Some(path) = layer.path() else {
+            continue;
+        };
+        let before = (layer == Layer::Local).then(|| config.clone());
+        load_file(&mut config, &path, &mut warnings)?;
+        if let Some(before) = before {
+            ignore_local_keys(&mut config, &before, &path, &mut warnings);
+        }
+    }
+
+    let selected = profile
+        .map(str::to_string)
+        .or_else(|| std::env::var(env_var("profile")).ok().and_then(non_empty))
+        .or_else(|| config.profile.clone());
+    if let Some(name) = &selected {
+        let path = profile_path(name)?;
+        if !path.exists() {
+            return Err(anyhow::anyhow!(
+                "Profile `{}` does not exist. Create it with `todo profile create {}`",
+                name,
+                name
+            ));
+        }
+        load_file(&mut config, &path, &mut warnings)?;
+    }
+
+    apply_env(&mut config, |var| std::env::var(var).ok())?;
+    if let Some(name) = profile {
+        config.profile = Some(name.to_string());
+        config.sources.insert("profile", Source::Flag("--profile"));
+    } else {
+        config.profile = selected;
+    }
+    Ok((config, warnings))
+}
+
+fn load_file(config: &mut Config, path: &Path, warnings: &mut Vec<String>) -> Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+    let _lock = FileLock::acquire(path)?;
+    let source =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    warnings.extend(parse_into(config, &source, &path.display().to_string())?);
+    Ok(())
+}
+
+/// Directory holding one `<name>.toml` file per profile.
+pub fn profiles_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("todo").join("profiles"))
+}
+
+/// Profile names become file names, so they are kept to a safe alphabet.
+pub fn validate_profile_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!(
+            "Invalid profile name `{}`: use letters, digits, `-` and `_`",
+            name
+        ))
+    }
+}
+
+pub fn profile_path(name: &str) -> Result<PathBuf> {
+    validate_profile_name(name)?;
+    let dir =
+        profiles_dir().ok_or_else(|| anyhow::anyhow!("Could not find the config directory"))?;
+    Ok(dir.join(format!("{}.toml", name)))
+}
+
+/// Names of all profiles, sorted.
+pub fn list_profiles() -> Result<Vec<String>> {
+    let Some(dir) = profiles_dir().filter(|dir| dir.exists()) else {
+        return Ok(Vec::new());
+    };
+    let mut names = Vec::new();
+    for entry in fs::read_dir(&dir).with_context(|| format!("Cannot read {}", dir.display()))? {
+        let path = entry?.path();
+        if path.extension().map(|ext| ext == "toml").unwrap_or(false) {
+            if let Some(stem) = path.file_stem() {
+                names.push(stem.to_string_lossy().to_string());
+            }
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+/// Validate `value` for `key` and write it to the config file at `path`,
+/// keeping the rest of the file (comments included) as it was.
+pub fn set_in_file(path: &Path, key: &str, value: &str) -> Result<()> {
+    let spec = find_key(key).ok_or_else(|| {
+        anyhow::anyhow!(
+            "Unknown config key `{}`. Known keys: {}",
+            key,
+            KEYS.iter().map(|k| k.name).collect::<Vec<_>>().join(", ")
+        )
+    })?;
+
+    if matches!(spec.kind, Kind::Formula) && !value.trim().is_empty() {
+        if let Err(error) = crate::urgency::Formula::parse(value) {
+            return Err(anyhow::anyhow!("Invalid urgency formula: {}", error));
+        }
+    }
+    let item = match spec.kind {
+        Kind::Bool => value.parse::<bool>().ok().map(toml_edit::value),
+        Kind::Integer => value.parse::<i64>().ok().map(toml_edit::value),
+        Kind::Priority => {
+            parse_priority(value).map(|p| toml_edit::value(Priority::from_int(p).name()))
+        }
+        Kind::Text => Some(toml_edit::value(value)),
+        Kind::DateFormat => parse_date_format(value).map(|_| toml_edit::value(value)),
+        Kind::Formula => parse_formula(value).map(|_| toml_edit::value(value)),
+        Kind::Weekday => {
+            calendar::parse_weekday(value).map(|day| toml_edit::value(calendar::weekday_name(day)))
+        }
+        Kind::TimeZone => Zone::parse(value).map(|zone| toml_edit::value(zone.name())),
+        Kind::ColorScheme => parse_color_scheme(value).map(|s| toml_edit::value(s.name())),
+    }
+    .ok_or_else(|| anyhow::anyhow!("`{}` must be {}", key, spec.kind.describe()))?;
+
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    let _lock = FileLock::acquire(path)?;
+    let source = if path.exists() {
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?
+    } else {
+        String::new()
+    };
+    let mut document: DocumentMut = source
+        .parse()
+        .map_err(|e| anyhow::anyhow!("{}: invalid TOML: {}", path.display(), e))?;
+    document[key] = item;
+    write_atomic(path, document.to_string().as_bytes())
+}
+
+/// Remove `key` from the config file at `path`, if present.
+pub fn unset_in_file(path: &Path, key: &str) -> Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+    let _lock = FileLock::acquire(path)?;
+    let source =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let mut document: DocumentMut = source
+        .parse()
+        .map_err(|e| anyhow::anyhow!("{}: invalid TOML: {}", path.display(), e))?;
+    if document.remove(key).is_some() {
+        write_atomic(path, document.to_string().as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Alias names are typed in place of a command, so they are kept to
+/// letters, digits, `-` and `_`.
+pub fn validate_alias_name(name: &str) -> Result<()> {
+    let valid = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphanumeric())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!(
+            "Invalid alias name `{}`: use letters, digits, `-` and `_`",
+            name
+        ))
+    }
+}
+
+/// Write `[alias] name = command` to the config file at `path`, replacing
+/// any alias of that name.
+pub fn set_alias_in_file(path: &Path, name: &str, command: &str) -> Result<()> {
+    validate_alias_name(name)?;
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    let _lock = FileLock::acquire(path)?;
+    let mut document = read_document(path)?;
+    let aliases = document
+        .entry("alias")
+        .or_insert_with(toml_edit::table)
+        .as_table_like_mut()
+        .ok_or_else(|| anyhow::anyhow!("{}: `alias` must be a table", path.display()))?;
+    aliases.insert(name, toml_edit::value(command));
+    write_atomic(path, document.to_string().as_bytes())
+}
+
+/// Remove alias `name` from the config file at `path`. Returns false when
+/// the file doesn't define it.
+pub fn unset_alias_in_file(path: &Path, name: &str) -> Result<bool> {
+    if !path.exists() {
+        return Ok(false);
+    }
+    let _lock = FileLock::acquire(path)?;
+    let mut document = read_document(path)?;
+    let Some(aliases) = document
+        .get_mut("alias")
+        .and_then(|item| item.as_table_like_mut())
+    else {
+        return Ok(false);
+    };
+    if aliases.remove(name).is_none() {
+        return Ok(false);
+    }
+    if aliases.is_empty() {
+        document.remove("alias");
+    }
+    write_atomic(path, document.to_string().as_bytes())?;
+    Ok(true)
+}
+
+/// Keys `todo config export` leaves out: they name files on this machine.
+const MACHINE_KEYS: &[&str] = &["db", "log_file", "sqlite_extensions", "sync_repo"];
+
+/// Undo what a directory's `.todo.toml` at `path` set of `allow_extensions`
+/// and `MACHINE_KEYS`, back to `before`. Extensions run native code and the
+/// other keys point at files to load or write, so a config in, say, a
+/// cloned repository must not choose them.
+fn ignore_local_keys(
+    config: &mut Config,
+    before: &Config,
+    path: &Path,
+    warnings: &mut Vec<String>,
+) {
+    for key in std::iter::once(&"allow_extensions").chain(MACHINE_KEYS) {
+        let source = before.sources.get(key).cloned();
+        if config.sources.get(key) == source.as_ref() {
+            continue;
+        }
+        if let Some(setting) = before.get(key) {
+            config.apply(key, setting);
+        }
+        match source {
+            Some(source) => config.sources.insert(key, source),
+            None => config.sources.remove(key),
+        };
+        warnings.push(format!(
+            "{}: {} is ignored here; set it in your user config",
+            path.display(),
+            key
+        ));
+    }
+}
+
+/// `table` without `MACHINE_KEYS`, as a document of its own.
+fn shareable(table: &toml_edit::Table) -> DocumentMut {
+    let mut document = DocumentMut::new();
+    for (key, item) in table.iter() {
+        if !MACHINE_KEYS.contains(&key) {
+            document[key] = item.clone();
+        }
+    }
+    document
+}
+
+fn read_document(path: &Path) -> Result<DocumentMut> {
+    let source = if path.exists() {
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?
+    } else {
+        String::new()
+    };
+    source
+        .parse()
+        .map_err(|e| anyhow::anyhow!("{}: invalid TOML: {}", path.display(), e))
+}
+
+/// `todo config export`: the user config file at `user` and every profile
+/// in `profiles` as one TOML document, under `[config]` and
+/// `[profiles.<name>]`, without `MACHINE_KEYS`.
+pub fn export_setup(user: &Path, profiles: &Path) -> Result<String> {
+    let mut bundle = DocumentMut::new();
+    let mut config = shareable(read_document(user)?.as_table())
+        .as_table()
+        .clone();
+    config.set_implicit(false);
+    bundle["config"] = Item::Table(config);
+
+    let mut names: Vec<PathBuf> = match fs::read_dir(profiles) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    names.sort();
+    let mut all = toml_edit::Table::new();
+    all.set_implicit(true);
+    for path in names {
+        let name = path.file_stem().unwrap_or_default().to_string_lossy();
+        let mut profile = shareable(read_document(&path)?.as_table())
+            .as_table()
+            .clone();
+        profile.set_implicit(false);
+        all.insert(&name, Item::Table(profile));
+    }
+    if !all.is_empty() {
+        bundle["profiles"] = Item::Table(all);
+    }
+    // Tables keep their place in the file they came from; put each under
+    // its section instead
+    renumber(bundle.as_table_mut(), &mut 0);
+    Ok(format!(
+        "# todo settings from `todo config export`; load them with `todo config import`\n{}",
+        bundle
+    ))
+}
+
+/// Number the tables below `table` in the order they are nested, which is
+/// the order they are written in.
+fn renumber(table: &mut toml_edit::Table, next: &mut usize) {
+    for (_, item) in table.iter_mut() {
+        if let Some(table) = item.as_table_mut() {
+            table.set_position(*next);
+            *next += 1;
+            renumber(table, next);
+        }
+    }
+}
+
+/// Copy the entries of `from` into `into`, merging tables present in both
+/// so that `[colors]` or `[tui.keys]` entries not in `from` are kept.
+fn merge_table(into: &mut dyn TableLike, from: &dyn TableLike) {
+    for (key, item) in from.iter() {
+        match (
+            into.get_mut(key).and_then(Item::as_table_like_mut),
+            item.as_table_like(),
+        ) {
+            (Some(existing), Some(table)) => merge_table(existing, table),
+            _ => {
+                into.insert(key, item.clone());
+            }
+        }
+    }
+}
+
+/// `todo config import`: merge a file written by `export_setup` into the
+/// user config file at `user` and the profiles in `profiles`. Every part
+/// is checked before anything is written; keys it sets replace the ones in
+/// place, and other keys stay. Returns each file written with how many
+/// keys it received; unknown keys produce `warnings`.
+pub fn import_setup(
+    source: &str,
+    origin: &str,
+    user: &Path,
+    profiles: &Path,
+    warnings: &mut Vec<String>,
+) -> Result<Vec<(PathBuf, usize)>> {
+    let bundle = ImDocument::parse(source)
+        .map_err(|e| anyhow::anyhow!("{}: invalid TOML: {}", origin, e))?;
+    let mut parts: Vec<(PathBuf, DocumentMut)> = Vec::new();
+    for (key, item) in bundle.as_table().iter() {
+        let table = item
+            .as_table()
+            .ok_or_else(|| anyhow::anyhow!("{}: `{}` must be a table", origin, key))?;
+        match key {
+            "config" => parts.push((user.to_path_buf(), shareable(table))),
+            "profiles" => {
+                for (name, profile) in table.iter() {
+                    validate_profile_name(name)?;
+                    let profile = profile.as_table().ok_or_else(|| {
+                        anyhow::anyhow!("{}: `profiles.{}` must be a table", origin, name)
+                    })?;
+                    let path = profiles.join(format!("{}.toml", name));
+                    parts.push((path, shareable(profile)));
+                }
+            }
+            _ => warnings.push(format!("{}: unknown section `{}` ignored", origin, key)),
+        }
+    }
+    for (path, document) in &parts {
+        let part = format!("{} ({})", origin, path.display());
+        warnings.extend(parse_into(
+            &mut Config::default(),
+            &document.to_string(),
+            &part,
+        )?);
+    }
+
+    let mut written = Vec::new();
+    for (path, document) in parts {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let _lock = FileLock::acquire(&path)?;
+        let mut target = read_document(&path)?;
+        merge_table(target.as_table_mut(), document.as_table());
+        write_atomic(&path, target.to_string().as_bytes())?;
+        written.push((path, document.as_table().len()));
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse_into() {
+        let mut config = Config::default();
+        let source =
+            "# my settings\ndefault_priority = \"High\"\ndue_soon_hours = 24\ncolour = false\n";
+
+        let warnings = parse_into(&mut config, source, "config.toml").unwrap();
+        assert_eq!(config.default_priority.to_int(), 2);
+        assert_eq!(config.due_soon_hours, 24);
+        assert!(config.color);
+        assert_eq!(
+            warnings,
+            vec!["config.toml line 4: unknown key `colour` ignored"]
+        );
+    }
+
+    #[test]
+    fn test_tui_keys_table() {
+        use crate::keymap::Action;
+        let mut config = Config::default();
+        let source = "color = true\n\n[tui.keys]\ncomplete = \"space\"\ndown = [\"j\", \"ctrl-n\"]\nfly = \"f\"\n";
+
+        let warnings = parse_into(&mut config, source, "config.toml").unwrap();
+        assert_eq!(config.tui_keys.hint(Action::Complete), "space");
+        assert_eq!(config.tui_keys.keys(Action::Down).len(), 2);
+        assert_eq!(config.tui_keys.hint(Action::Quit), "q");
+        assert_eq!(
+            warnings,
+            vec!["config.toml line 6: unknown action `tui.keys.fly` ignored"]
+        );
+
+        let error = parse_into(&mut config, "[tui.keys]\nquit = \"hyper-q\"\n", "x")
+            .unwrap_err()
+            .to_string();
+        assert_eq!(error, "x line 2: unknown key `hyper-q`");
+        assert!(parse_into(&mut config, "[tui.keys]\nquit = 1\n", "x").is_err());
+    }
+
+    #[test]
+    fn test_export_and_import_setup() {
+        let dir = tempdir().unwrap();
+        let user = dir.path().join("config.toml");
+        let profiles = dir.path().join("profiles");
+        fs::create_dir(&profiles).unwrap();
+        fs::write(
+            &user,
+            "db = \"/home/me/tasks.db\"\ndue_soon_hours = 24\n\n[colors]\nhigh = \"red\"\n",
+        )
+        .unwrap();
+        fs::write(profiles.join("work.toml"), "context = \"work\"\n").unwrap();
+
+        let bundle = export_setup(&user, &profiles).unwrap();
+        assert!(!bundle.contains("tasks.db"));
+        assert!(bundle.contains("[config.colors]\nhigh = \"red\""));
+        assert!(bundle.contains("[profiles.work]\ncontext = \"work\""));
+
+        // Another machine with settings of its own
+        let other = tempdir().unwrap();
+        let user = other.path().join("config.toml");
+        let profiles = other.path().join("profiles");
+        fs::write(
+            &user,
+            "db = \"/srv/todo.db\"\ndue_soon_hours = 4\n\n[colors]\nlow = \"blue\"\n",
+        )
+        .unwrap();
+        let mut warnings = Vec::new();
+        let written = import_setup(&bundle, "setup.toml", &user, &profiles, &mut warnings).unwrap();
+        assert!(warnings.is_empty());
+        assert_eq!(
+            written,
+            vec![(user.clone(), 2), (profiles.join("work.toml"), 1)]
+        );
+        let mut config = Config::default();
+        parse_into(&mut config, &fs::read_to_string(&user).unwrap(), "x").unwrap();
+        assert_eq!(config.due_soon_hours, 24);
+        assert_eq!(config.db.as_deref(), Some("/srv/todo.db"));
+        assert_eq!(config.colors.len(), 2);
+        assert!(fs::read_to_string(profiles.join("work.toml"))
+            .unwrap()
+            .contains("context = \"work\""));
+
+        // Nothing is written when a part is invalid
+        let bad = "[config]\ndue_soon_hours = 2\n[profiles.home]\ncolor = \"yes\"\n";
+        assert!(import_setup(bad, "bad.toml", &user, &profiles, &mut warnings).is_err());
+        assert!(fs::read_to_string(&user)
+            .unwrap()
+            .contains("due_soon_hours = 24"));
+        assert!(!profiles.join("home.toml").exists());
+    }
+
+    #[test]
+    fn test_colors_table() {
+        let mut config = Config::default();
+        let source = "[colors]\nhigh = \"bright_red\"\noverdue = \"#ff8000\"\nurgent = \"red\"\n";
+
+        let warnings = parse_into(&mut config, source, "config.toml").unwrap();
+        assert_eq!(config.colors.get("high"), Some(&colored::Color::BrightRed));
+        assert!(config.colors.contains_key("overdue"));
+        assert_eq!(config.colors.len(), 2);
+        assert_eq!(
+            warnings,
+            vec!["config.toml line 4: unknown key `colors.urgent` ignored"]
+        );
+
+        let error = parse_into(&mut config, "[colors]\nlow = \"reddish\"\n", "x")
+            .unwrap_err()
+            .to_string();
+        assert!(error.starts_with("x line 2: `colors.low` must be a color"));
+
+        let source =
+            "[colors.tags]\nurgent = \"red\"\n\n[colors.projects]\n\"home/garden\" = \"#00aa00\"\n";
+        assert!(parse_into(&mut config, source, "x").unwrap().is_empty());
+        assert_eq!(config.tag_colors.get("urgent"), Some(&colored::Color::Red));
+        assert!(config.project_colors.contains_key("home/garden"));
+        assert_eq!(config.colors.len(), 2);
+        let error = parse_into(&mut config, "[colors.tags]\nwork = \"plaid\"\n", "x")
+            .unwrap_err()
+            .to_string();
+        assert!(error.starts_with("x line 2: `colors.tags.work` must be a color"));
+    }
+
+    #[test]
+    fn test_aliases() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("todo").join("config.toml");
+        set_in_file(&path, "due_soon_hours", "24").unwrap();
+        set_alias_in_file(&path, "urgent", "list --priority high").unwrap();
+        set_alias_in_file(&path, "u", "urgent").unwrap();
+        assert!(set_alias_in_file(&path, "-x", "list").is_err());
+
+        let mut config = Config::default();
+        let source = fs::read_to_string(&path).unwrap();
+        assert!(parse_into(&mut config, &source, "x").unwrap().is_empty());
+        assert_eq!(config.due_soon_hours, 24);
+        assert_eq!(
+            config.aliases.get("urgent").map(String::as_str),
+            Some("list --priority high")
+        );
+        assert_eq!(config.aliases.len(), 2);
+
+        assert!(unset_alias_in_file(&path, "urgent").unwrap());
+        assert!(!unset_alias_in_file(&path, "urgent").unwrap());
+        assert!(unset_alias_in_file(&path, "u").unwrap());
+        assert!(!fs::read_to_string(&path).unwrap().contains("alias"));
+
+        let error = parse_into(
+            &mut config,
+            "[alias]
+quick = \"\"\n",
+            "x",
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(error.starts_with("x line 2: `alias.quick` must be a command line"));
+    }
+
+    #[test]
+    fn test_type_errors_carry_line_numbers() {
+        let mut config = Config::default();
+        let error = parse_into(
+            &mut config,
+            "color = true\n\npriority_decay = \"yes\"\n",
+            "x",
+        )
+        .unwrap_err()
+        .to_string();
+        assert_eq!(
+            error,
+            "x line 3: `priority_decay` must be a boolean (true or false)"
+        );
+
+        let error = parse_into(&mut config, "default_priority = \"urgent\"", "x")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("line 1"));
+
+        assert!(parse_into(&mut config, "due_soon_hours = ", "x").is_err());
+    }
+
+    #[test]
+    fn test_later_layers_win() {
+        let mut config = Config::default();
+        parse_into(&mut config, "due_soon_hours = 24\ncolor = false", "system").unwrap();
+        parse_into(&mut config, "due_soon_hours = 12", "user").unwrap();
+        assert_eq!(config.due_soon_hours, 12);
+        assert!(!config.color);
+    }
+
+    #[test]
+    fn test_local_config_cannot_set_machine_keys() {
+        let mut config = Config::default();
+        parse_into(&mut config, "db = \"~/tasks.db\"", "user").unwrap();
+        let before = config.clone();
+        parse_into(
+            &mut config,
+            "allow_extensions = true\nsqlite_extensions = \"./evil.so\"\ndb = \"./x.db\"\nlog_file = \"./log\"\ncolor = false",
+            ".todo.toml",
+        )
+        .unwrap();
+        let mut warnings = Vec::new();
+        ignore_local_keys(&mut config, &before, Path::new(".todo.toml"), &mut warnings);
+
+        assert!(!config.allow_extensions);
+        assert_eq!(config.sqlite_extensions, None);
+        assert_eq!(config.log_file, None);
+        assert_eq!(config.db.as_deref(), Some("~/tasks.db"));
+        assert_eq!(config.source("db"), before.source("db"));
+        assert_eq!(config.source("sqlite_extensions"), Source::Default);
+        assert!(!config.color);
+        assert_eq!(warnings.len(), 4);
+        assert!(warnings[3].contains("sqlite_extensions is ignored"));
+    }
+
+    #[test]
+    fn test_env_overrides_files() {
+        let mut config = Config::default();
+        parse_into(
+            &mut config,
+            "\ncolor = true\ndue_soon_hours = 24",
+            "user.toml",
+        )
+        .unwrap();
+
+        let env: HashMap<&str, &str> = [
+            ("TODO_COLOR", "0"),
+            ("TODO_DEFAULT_PRIORITY", "high"),
+            ("TODO_UNRELATED", "x"),
+        ]
+        .into_iter()
+        .collect();
+        apply_env(&mut config, |var| env.get(var).map(|v| v.to_string())).unwrap();
+
+        assert!(!config.color);
+        assert_eq!(config.default_priority.to_int(), 2);
+        assert_eq!(config.due_soon_hours, 24);
+        assert_eq!(
+            config.source("color"),
+            Source::Env("TODO_COLOR".to_string())
+        );
+        assert_eq!(config.source("due_soon_hours").to_string(), "user.toml:3");
+        assert_eq!(config.source("usage_metrics"), Source::Default);
+
+        let error = apply_env(&mut config, |var| {
+            (var == "TODO_MAINTENANCE_INTERVAL").then(|| "hourly".to_string())
+        })
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "TODO_MAINTENANCE_INTERVAL must be an integer"
+        );
+    }
+
+    #[test]
+    fn test_set_in_file_keeps_comments() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("todo").join("config.toml");
+
+        set_in_file(&path, "due_soon_hours", "24").unwrap();
+        let existing = fs::read_to_string(&path).unwrap();
+        fs::write(&path, format!("# keep me\n{}", existing)).unwrap();
+
+        set_in_file(&path, "default_priority", "HIGH").unwrap();
+        set_in_file(&path, "due_soon_hours", "12").unwrap();
+        let written = fs::read_to_string(&path).unwrap();
+        assert!(written.starts_with("# keep me\n"));
+        assert!(written.contains("due_soon_hours = 12"));
+        assert!(written.contains("default_priority = \"high\""));
+
+        assert!(set_in_file(&path, "due_soon_hours", "soon").is_err());
+        assert!(set_in_file(&path, "nope", "1").is_err());
+    }
+
+    #[test]
+    fn test_text_keys_and_unset() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("work.toml");
+        set_in_file(&path, "db", "~/work.db").unwrap();
+        set_in_file(&path, "context", "work").unwrap();
+
+        let mut config = Config::default();
+        parse_into(&mut config, &fs::read_to_string(&path).unwrap(), "work").unwrap();
+        assert_eq!(config.db.as_deref(), Some("~/work.db"));
+        assert_eq!(config.context.as_deref(), Some("work"));
+
+        // An empty override unsets a text key
+        apply_env(&mut config, |var| (var == "TODO_CONTEXT").then(String::new)).unwrap();
+        assert_eq!(config.context, None);
+
+        unset_in_file(&path, "context").unwrap();
+        let written = fs::read_to_string(&path).unwrap();
+        assert!(!written.contains("context"));
+        assert!(written.contains("db = \"~/work.db\""));
+    }
+
+    #[test]
+    fn test_display_keys() {
+        let mut config = Config::default();
+        parse_into(
+            &mut config,
+            "date_format = \"%d/%m/%Y\"\ncolor_scheme = \"Mono\"\nlist_filter = \"--not-tag waiting\"",
+            "x",
+        )
+        .unwrap();
+        assert_eq!(config.date_format, "%d/%m/%Y");
+        assert_eq!(config.color_scheme, ColorScheme::Mono);
+        assert_eq!(config.list_filter.as_deref(), Some("--not-tag waiting"));
+
+        let error = parse_into(&mut config, "date_format = \"%Q\"", "x").unwrap_err();
+        assert!(error.to_string().contains("strftime"));
+        assert!(parse_into(&mut config, "color_scheme = \"pink\"", "x").is_err());
+
+        assert_eq!(config.timezone, Zone::Local);
+        parse_into(&mut config, "timezone = \"+0530\"", "x").unwrap();
+        assert_eq!(config.timezone, Zone::parse("+05:30").unwrap());
+        assert!(matches!(config.get("timezone"), Some(Setting::Text(name)) if name == "+05:30"));
+        let error = parse_into(&mut config, "timezone = \"Mars/Olympus\"", "x").unwrap_err();
+        assert!(error.to_string().contains("offset"));
+
+        parse_into(
+            &mut config,
+            "urgency_formula = \"urgency + has_tag('work')\"",
+            "x",
+        )
+        .unwrap();
+        assert_eq!(
+            config.urgency_formula.as_deref(),
+            Some("urgency + has_tag('work')")
+        );
+        let error = parse_into(&mut config, "urgency_formula = \"2 *\"", "x").unwrap_err();
+        assert!(error.to_string().contains("urgency formula"));
+    }
+
+    #[test]
+    fn test_split_args() {
+        assert_eq!(
+            split_args("--not-tag waiting  --title-matches '^fix (a|b)' --tag \"\"").unwrap(),
+            [
+                "--not-tag",
+                "waiting",
+                "--title-matches",
+                "^fix (a|b)",
+                "--tag",
+                ""
+            ]
+        );
+        assert!(split_args("--tag \"open").is_err());
+    }
+
+    #[test]
+    fn test_profile_names() {
+        assert!(validate_profile_name("work-2").is_ok());
+        assert!(validate_profile_name("").is_err());
+        assert!(validate_profile_name("../etc").is_err());
+        assert!(validate_profile_name("my profile").is_err());
+    }
+
+    #[test]
+    fn test_get_round_trips() {
+        let config = Config::default();
+        for key in KEYS {
+            assert!(config.get(key.name).is_some(), "{} has no getter", key.name);
+        }
+        assert_eq!(
+            config.get("default_priority").unwrap().to_string(),
+            "medium"
+        );
+        assert!(config.get("nope").is_none());
+    }
+}
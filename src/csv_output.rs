@@ -0,0 +1,83 @@
+use anyhow::Result;
+use chrono::{DateTime, SecondsFormat, Utc};
+
+use crate::models::Task;
+
+const HEADER: [&str; 13] = [
+    "id",
+    "title",
+    "description",
+    "due_date",
+    "priority",
+    "completed",
+    "created_at",
+    "updated_at",
+    "tags",
+    "someday",
+    "project",
+    "recurrence",
+    "parent_id",
+];
+
+/// Render `tasks` as CSV with a header row. Fields mirror the JSON output;
+/// tags are joined with `;` and missing values are left empty.
+pub fn render(tasks: &[Task]) -> Result<String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(HEADER)?;
+    for task in tasks {
+        writer.write_record([
+            task.id.map(|id| id.to_string()).unwrap_or_default(),
+            task.title.clone(),
+            task.description.clone().unwrap_or_default(),
+            task.due_date.map(timestamp).unwrap_or_default(),
+            task.priority.to_string(),
+            task.completed.to_string(),
+            timestamp(task.created_at),
+            timestamp(task.updated_at),
+            task.tags.join(";"),
+            task.someday.to_string(),
+            task.project.clone().unwrap_or_default(),
+            task.recurrence.clone().unwrap_or_default(),
+            task.parent_id.map(|id| id.to_string()).unwrap_or_default(),
+        ])?;
+    }
+    Ok(String::from_utf8(writer.into_inner()?)?)
+}
+
+/// RFC 3339 in UTC with a `Z` suffix, as in the JSON output.
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_quotes_and_joins() {
+        let mut task = Task::new(
+            "Buy milk, eggs".to_string(),
+            Some("Say \"hi\"\nto the baker".to_string()),
+            None,
+            2,
+        );
+        task.id = Some(7);
+        task.tags = vec!["home".to_string(), "errands/shop".to_string()];
+        task.parent_id = Some(3);
+
+        let output = render(&[task]).unwrap();
+        let mut reader = csv::Reader::from_reader(output.as_bytes());
+        assert_eq!(reader.headers().unwrap(), HEADER.as_slice());
+
+        let records: Vec<csv::StringRecord> = reader.records().map(Result::unwrap).collect();
+        assert_eq!(records.len(), 1);
+        let record = &records[0];
+        assert_eq!(&record[0], "7");
+        assert_eq!(&record[1], "Buy milk, eggs");
+        assert_eq!(&record[2], "Say \"hi\"\nto the baker");
+        assert_eq!(&record[3], "");
+        assert_eq!(&record[4], "2");
+        assert_eq!(&record[8], "home;errands/shop");
+        assert_eq!(&record[12], "3");
+    }
+}
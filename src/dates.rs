@@ -0,0 +1,341 @@
+use chrono::{DateTime, Datelike, Duration, Months, NaiveDate, Utc, Weekday};
+
+use crate::diagnostic::{did_you_mean, words, Diagnostic};
+use crate::zone;
+
+const EXPRESSION_HINT: &str =
+    "use YYYY-MM-DD, RFC 3339, or an expression like tomorrow, next friday, in 2 weeks or eod";
+const UNIT_HINT: &str = "use minutes, hours, days, weeks, months or years";
+/// Words that make a date on their own, for suggestions.
+const DAY_WORDS: &[&str] = &[
+    "today",
+    "tonight",
+    "eod",
+    "tomorrow",
+    "monday",
+    "tuesday",
+    "wednesday",
+    "thursday",
+    "friday",
+    "saturday",
+    "sunday",
+];
+const UNITS: &[&str] = &[
+    "minute", "min", "hour", "hr", "day", "week", "month", "year",
+];
+
+/// Parse a relative date expression such as `tomorrow`, `next friday`,
+/// `in 2 weeks` or `eod`, relative to `now`. Day-based expressions, `today`
+/// and `eod` among them, resolve to the last second of the day they name in
+/// the configured zone (see `zone`), like a `YYYY-MM-DD` date; hours and
+/// minutes count from `now` exactly. Anything else is a `Diagnostic`
+/// pointing at the word that could not be understood.
+pub fn parse_relative(text: &str, now: DateTime<Utc>) -> Result<DateTime<Utc>, Diagnostic> {
+    let words = words(text);
+    let names: Vec<&str> = words.iter().map(|(_, word)| word.as_str()).collect();
+    let today = zone::day_of(now);
+    let at = |index: usize, message: String| {
+        let (span, _): &(std::ops::Range<usize>, String) = &words[index];
+        Diagnostic::new("date", text, span.clone(), message)
+    };
+    let end = text.trim_end().len();
+    let out_of_range = || {
+        Diagnostic::new(
+            "date",
+            text,
+            words.first().map(|(span, _)| span.start).unwrap_or(0)..end,
+            "date is out of range".to_string(),
+        )
+    };
+
+    let date = match names.as_slice() {
+        ["today"] | ["eod"] | ["end", "of", "day"] | ["tonight"] => {
+            return Ok(zone::end_of(today));
+        }
+        ["tomorrow"] | ["tmrw"] | ["tmr"] => today.succ_opt().ok_or_else(out_of_range)?,
+        ["next", "week"] => today + Duration::weeks(1),
+        ["next", "month"] => today
+            .checked_add_months(Months::new(1))
+            .ok_or_else(out_of_range)?,
+        ["next", "year"] => today
+            .checked_add_months(Months::new(12))
+            .ok_or_else(out_of_range)?,
+        ["in"] => {
+            return Err(Diagnostic::new(
+                "date",
+                text,
+                end..end,
+                "expected an amount and a unit".to_string(),
+            )
+            .with_hint("e.g. `in 2 weeks` or `in 3 hours`"))
+        }
+        [day] | ["next", day] | ["this", day] | ["on", day] => match parse_weekday(day) {
+            Some(weekday) => next_weekday(today, weekday),
+            None => return Err(unknown_day(&at, names.len() - 1, day)),
+        },
+        ["in", count, unit] => {
+            let count = parse_count(count).ok_or_else(|| {
+                at(1, format!("expected a number, found `{}`", count))
+                    .with_hint("write the amount in digits, e.g. `in 2 weeks`")
+            })?;
+            if !UNITS.contains(&unit.trim_end_matches('s')) {
+                let hint = did_you_mean(
+                    unit,
+                    &["minutes", "hours", "days", "weeks", "months", "years"],
+                );
+                return Err(at(2, format!("unknown unit `{}`", unit))
+                    .with_hint(hint.unwrap_or_else(|| UNIT_HINT.to_string())));
+            }
+            return add(now, count, unit).ok_or_else(out_of_range);
+        }
+        ["in", amount] if parse_duration(amount).is_some() => {
+            let (count, unit) = parse_duration(amount).unwrap();
+            return add(now, count, unit).ok_or_else(out_of_range);
+        }
+        ["in", count] => {
+            return Err(Diagnostic::new(
+                "date",
+                text,
+                end..end,
+                format!("expected a unit after `{}`", count),
+            )
+            .with_hint(UNIT_HINT))
+        }
+        [] => {
+            return Err(
+                Diagnostic::new("date", text, 0..0, "empty date".to_string())
+                    .with_hint(EXPRESSION_HINT),
+            )
+        }
+        ["in" | "next" | "this" | "on", ..] => {
+            let last = words.len() - 1;
+            return Err(Diagnostic::new(
+                "date",
+                text,
+                words[if names[0] == "in" { 3 } else { 2 }].0.start..words[last].0.end,
+                "unexpected words".to_string(),
+            )
+            .with_hint(EXPRESSION_HINT));
+        }
+        _ => {
+            return Err(Diagnostic::new(
+                "date",
+                text,
+                words[0].0.start..end,
+                "not a date".to_string(),
+            )
+            .with_hint(EXPRESSION_HINT))
+        }
+    };
+    Ok(zone::end_of(date))
+}
+
+/// Error for a word that should have named a day.
+fn unknown_day(at: &impl Fn(usize, String) -> Diagnostic, index: usize, word: &str) -> Diagnostic {
+    let looks_numeric = word.chars().all(|c| c.is_ascii_digit() || c == '-') && word.contains('-');
+    if looks_numeric {
+        return at(index, "not a valid calendar date".to_string())
+            .with_hint("use YYYY-MM-DD with a month from 01 to 12 and a day that exists");
+    }
+    let known: &[&str] = if index == 0 {
+        DAY_WORDS
+    } else {
+        &[
+            "week",
+            "month",
+            "year",
+            "monday",
+            "tuesday",
+            "wednesday",
+            "thursday",
+            "friday",
+            "saturday",
+            "sunday",
+        ]
+    };
+    let message = if index == 0 {
+        format!("unknown date `{}`", word)
+    } else {
+        format!("unknown day `{}`", word)
+    };
+    at(index, message)
+        .with_hint(did_you_mean(word, known).unwrap_or_else(|| EXPRESSION_HINT.to_string()))
+}
+
+/// `2`, `a` or `an`.
+fn parse_count(word: &str) -> Option<u32> {
+    match word {
+        "a" | "an" => Some(1),
+        _ => word.parse().ok(),
+    }
+}
+
+/// `now` plus `count` units; whole days and longer land on the end of the
+/// day.
+fn add(now: DateTime<Utc>, count: u32, unit: &str) -> Option<DateTime<Utc>> {
+    let later = shift(now, count, unit)?;
+    match unit.trim_end_matches('s') {
+        "minute" | "min" | "hour" | "hr" => Some(later),
+        _ => Some(zone::end_of(zone::day_of(later))),
+    }
+}
+
+/// `from` moved forward by `count` units. Days and longer keep its time of
+/// day on the clock of the configured zone, across daylight saving changes.
+pub fn shift(from: DateTime<Utc>, count: u32, unit: &str) -> Option<DateTime<Utc>> {
+    let local = from.with_timezone(&zone::current()).naive_local();
+    let moved = match unit.trim_end_matches('s') {
+        "minute" | "min" => return from.checked_add_signed(Duration::minutes(count.into())),
+        "hour" | "hr" => return from.checked_add_signed(Duration::hours(count.into())),
+        "day" => local.checked_add_signed(Duration::days(count.into())),
+        "week" => local.checked_add_signed(Duration::weeks(count.into())),
+        "month" => local.checked_add_months(Months::new(count)),
+        "year" => local.checked_add_months(Months::new(count.checked_mul(12)?)),
+        _ => None,
+    }?;
+    Some(zone::from_local(moved))
+}
+
+/// A length of time written compactly, such as `30m`, `3h`, `2d`, `1w`,
+/// `2mo` or `1y`, or spelled out, such as `2 days`: the count and its unit.
+pub fn parse_duration(text: &str) -> Option<(u32, &'static str)> {
+    let text = text.trim().to_lowercase();
+    let split = text.find(|c: char| !c.is_ascii_digit())?;
+    let count = text[..split].parse().ok()?;
+    let unit = match text[split..].trim().trim_end_matches('s') {
+        "m" | "min" | "minute" => "minute",
+        "h" | "hr" | "hour" => "hour",
+        "d" | "day" => "day",
+        "w" | "wk" | "week" => "week",
+        "mo" | "month" => "month",
+        "y" | "yr" | "year" => "year",
+        _ => return None,
+    };
+    Some((count, unit))
+}
+
+fn parse_weekday(word: &str) -> Option<Weekday> {
+    match word {
+        "monday" | "mon" => Some(Weekday::Mon),
+        "tuesday" | "tue" | "tues" => Some(Weekday::Tue),
+        "wednesday" | "wed" => Some(Weekday::Wed),
+        "thursday" | "thu" | "thur" | "thurs" => Some(Weekday::Thu),
+        "friday" | "fri" => Some(Weekday::Fri),
+        "saturday" | "sat" => Some(Weekday::Sat),
+        "sunday" | "sun" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+/// The first `weekday` strictly after `today`, so `friday` on a Friday means
+/// a week from now.
+fn next_weekday(today: NaiveDate, weekday: Weekday) -> NaiveDate {
+    let ahead = (weekday.num_days_from_monday() + 7 - today.weekday().num_days_from_monday()) % 7;
+    today + Duration::days(if ahead == 0 { 7 } else { ahead.into() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn day(text: &str, now: DateTime<Utc>) -> String {
+        parse_relative(text, now)
+            .unwrap()
+            .format("%Y-%m-%d %H:%M")
+            .to_string()
+    }
+
+    #[test]
+    fn test_day_expressions() {
+        // A Wednesday afternoon
+        let now = Utc.with_ymd_and_hms(2030, 1, 9, 15, 30, 0).unwrap();
+        assert_eq!(day("tomorrow", now), "2030-01-10 23:59");
+        assert_eq!(day("Friday", now), "2030-01-11 23:59");
+        assert_eq!(day("next fri", now), "2030-01-11 23:59");
+        assert_eq!(day("wednesday", now), "2030-01-16 23:59");
+        assert_eq!(day("next week", now), "2030-01-16 23:59");
+        assert_eq!(day("in 2 weeks", now), "2030-01-23 23:59");
+        assert_eq!(day("in a month", now), "2030-02-09 23:59");
+        assert_eq!(day("next year", now), "2031-01-09 23:59");
+        assert_eq!(day("in 3d", now), "2030-01-12 23:59");
+        assert_eq!(day("in 2h", now), "2030-01-09 17:30");
+    }
+
+    #[test]
+    fn test_durations() {
+        assert_eq!(parse_duration("2d"), Some((2, "day")));
+        assert_eq!(parse_duration("1W"), Some((1, "week")));
+        assert_eq!(parse_duration("30m"), Some((30, "minute")));
+        assert_eq!(parse_duration("2mo"), Some((2, "month")));
+        assert_eq!(parse_duration("3 hours"), Some((3, "hour")));
+        assert_eq!(parse_duration("tomorrow"), None);
+        assert_eq!(parse_duration("2"), None);
+        assert_eq!(parse_duration("d"), None);
+
+        let due = Utc.with_ymd_and_hms(2030, 1, 31, 9, 15, 0).unwrap();
+        assert_eq!(
+            shift(due, 1, "month").unwrap().to_rfc3339(),
+            "2030-02-28T09:15:00+00:00"
+        );
+        assert_eq!(
+            shift(due, 2, "day").unwrap().to_rfc3339(),
+            "2030-02-02T09:15:00+00:00"
+        );
+    }
+
+    #[test]
+    fn test_errors_point_at_the_bad_word() {
+        let now = Utc.with_ymd_and_hms(2030, 1, 9, 15, 30, 0).unwrap();
+        let error = |text: &str| {
+            let error = parse_relative(text, now).unwrap_err();
+            let underlined = error.input[error.span.clone()].to_string();
+            (underlined, error.message, error.hint.unwrap_or_default())
+        };
+
+        let (word, message, hint) = error("in 3 wekes");
+        assert_eq!(
+            (word.as_str(), message.as_str()),
+            ("wekes", "unknown unit `wekes`")
+        );
+        assert_eq!(hint, "did you mean `weeks`?");
+
+        let (word, _, hint) = error("next fridy");
+        assert_eq!(word, "fridy");
+        assert_eq!(hint, "did you mean `friday`?");
+
+        let (word, message, _) = error("tomorow");
+        assert_eq!(
+            (word.as_str(), message.as_str()),
+            ("tomorow", "unknown date `tomorow`")
+        );
+
+        let (word, message, _) = error("in two weeks");
+        assert_eq!(
+            (word.as_str(), message.as_str()),
+            ("two", "expected a number, found `two`")
+        );
+
+        let (word, message, _) = error("in 3");
+        assert_eq!(
+            (word.as_str(), message.as_str()),
+            ("", "expected a unit after `3`")
+        );
+
+        let (_, message, _) = error("2030-02-30");
+        assert_eq!(message, "not a valid calendar date");
+    }
+
+    #[test]
+    fn test_time_expressions() {
+        let now = Utc.with_ymd_and_hms(2030, 1, 9, 15, 30, 0).unwrap();
+        assert_eq!(day("eod", now), "2030-01-09 23:59");
+        assert_eq!(day("  End of Day ", now), "2030-01-09 23:59");
+        assert_eq!(day("in 3 hours", now), "2030-01-09 18:30");
+        assert_eq!(day("in 45 mins", now), "2030-01-09 16:15");
+
+        assert!(parse_relative("2030-02-01", now).is_err());
+        assert!(parse_relative("in 3 fortnights", now).is_err());
+        assert!(parse_relative("someday", now).is_err());
+    }
+}
@@ -1,132 +1,1505 @@
-use crate::models::Task;
-use chrono::{DateTime, Utc};
-use rusqlite::{params, Connection, Result as SqliteResult};
+use crate::models::{
+    AgeBucket, AgingReport, Attachment, FocusSession, Goal, HistoryEntry, Note, Operation,
+    SearchField, SearchHit, Task, Template, TimeEntry, UsageStat, MATCH_END, MATCH_START,
+};
+use crate::recurrence::Recurrence;
+use chrono::{DateTime, Duration, Utc};
+use regex::Regex;
+use rusqlite::functions::FunctionFlags;
+use rusqlite::types::{Type, Value};
+use rusqlite::{params, Connection, Result as SqliteResult, Row};
+use std::cell::{Cell, RefCell};
+use std::sync::Arc;
+
+/// The priority a row of `tasks` ends up with. A task without one of its
+/// own takes its project's default, or else the nearest parent task's (its
+/// own or its project's), or else medium.
+macro_rules! effective_priority {
+    () => {
+        "COALESCE(
+             tasks.priority,
+             (SELECT default_priority FROM projects WHERE id = tasks.project_id),
+             (WITH RECURSIVE ancestors(id, priority, depth) AS (
+                  SELECT parent.id,
+                         COALESCE(parent.priority,
+                             (SELECT default_priority FROM projects WHERE id = parent.project_id)),
+                         1
+                  FROM tasks AS parent WHERE parent.id = tasks.parent_id
+                  UNION ALL
+                  SELECT parent.id,
+                         COALESCE(parent.priority,
+                             (SELECT default_priority FROM projects WHERE id = parent.project_id)),
+                         ancestors.depth + 1
+                  FROM ancestors
+                  JOIN tasks AS child ON child.id = ancestors.id
+                  JOIN tasks AS parent ON parent.id = child.parent_id
+                  WHERE ancestors.priority IS NULL AND ancestors.depth < 100
+              )
+              SELECT priority FROM ancestors WHERE priority IS NOT NULL),
+             1
+         )"
+    };
+}
+
+const EFFECTIVE_PRIORITY: &str = effective_priority!();
+
+/// A random (version 4) UUID, made by SQLite for each row it is used on.
+const NEW_UUID: &str = "lower(hex(randomblob(4)) || '-' || hex(randomblob(2)) || '-4' ||
+     substr(hex(randomblob(2)), 2) || '-' || substr('89ab', 1 + (abs(random()) % 4), 1) ||
+     substr(hex(randomblob(2)), 2) || '-' || hex(randomblob(6)))";
+
+/// Columns selected for every task query, in the order `task_from_row` expects.
+/// Tags are folded into a comma-separated list by a correlated subquery.
+const TASK_COLUMNS: &str = concat!(
+    "id, title, description, due_date, ",
+    effective_priority!(),
+    " AS priority, completed, created_at, updated_at,
+     (SELECT group_concat(tag, ',') FROM task_tags WHERE task_id = tasks.id) AS tags,
+     EXISTS (SELECT 1 FROM someday_tasks WHERE task_id = tasks.id) AS someday,
+     (SELECT name FROM projects WHERE id = tasks.project_id) AS project,
+     recurrence, parent_id,
+     EXISTS (SELECT 1 FROM started_tasks WHERE task_id = tasks.id) AS in_progress, energy,
+     tasks.priority IS NULL AS priority_inherited, completed_at, start_date, uuid"
+);
+
+/// Number of columns in `TASK_COLUMNS`; queries selecting more read theirs
+/// from this index on.
+const TASK_COLUMN_COUNT: usize = 19;
+
+/// `TASK_COLUMNS` for rows of `archived_tasks`, where tags and the project
+/// name are stored inline.
+const ARCHIVED_COLUMNS: &str =
+    "id, title, description, due_date, priority, completed, created_at, updated_at,
+     tags, FALSE AS someday, project, recurrence, parent_id, FALSE AS in_progress,
+     NULL AS energy, FALSE AS priority_inherited, completed_at, NULL AS start_date, uuid";
+
+fn task_from_row(row: &Row) -> SqliteResult<Task> {
+    let due_date_str: Option<String> = row.get(3)?;
+    let due_date = due_date_str
+        .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
+        .map(|dt| dt.with_timezone(&Utc));
+
+    let tags_str: Option<String> = row.get(8)?;
+    let mut tags: Vec<String> = tags_str
+        .map(|s| s.split(',').map(|t| t.to_string()).collect())
+        .unwrap_or_default();
+    tags.sort();
+
+    Ok(Task {
+        id: Some(row.get(0)?),
+        title: row.get(1)?,
+        description: row.get(2)?,
+        due_date,
+        priority: row.get(4)?,
+        completed: row.get(5)?,
+        created_at: DateTime::parse_from_rfc3339(&row.get::<_, String>(6)?)
+            .unwrap()
+            .with_timezone(&Utc),
+        updated_at: DateTime::parse_from_rfc3339(&row.get::<_, String>(7)?)
+            .unwrap()
+            .with_timezone(&Utc),
+        tags,
+        someday: row.get(9)?,
+        project: row.get(10)?,
+        recurrence: row.get(11)?,
+        parent_id: row.get(12)?,
+        in_progress: row.get(13)?,
+        energy: row
+            .get::<_, Option<String>>(14)?
+            .and_then(|name| crate::Energy::from_name(&name)),
+        priority_inherited: row.get(15)?,
+        completed_at: row
+            .get::<_, Option<String>>(16)?
+            .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
+            .map(|dt| dt.with_timezone(&Utc)),
+        start_date: row
+            .get::<_, Option<String>>(17)?
+            .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
+            .map(|dt| dt.with_timezone(&Utc)),
+        uuid: row.get(18)?,
+    })
+}
+
+/// Turn free text into an FTS5 expression: every word is quoted, so
+/// punctuation can't be read as query syntax, and matched as a prefix.
+fn fts_expression(query: &str) -> Option<String> {
+    let terms: Vec<String> = query
+        .split_whitespace()
+        .map(|word| word.replace('"', ""))
+        .filter(|word| !word.is_empty())
+        .map(|word| format!("\"{}\"*", word))
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" "))
+}
+
+fn goal_from_row(row: &Row) -> SqliteResult<Goal> {
+    let parse = |s: String| {
+        DateTime::parse_from_rfc3339(&s)
+            .unwrap()
+            .with_timezone(&Utc)
+    };
+    Ok(Goal {
+        id: row.get(0)?,
+        title: row.get(1)?,
+        target_date: row.get::<_, Option<String>>(2)?.map(parse),
+        created_at: parse(row.get(3)?),
+    })
+}
+
+fn template_from_row(row: &Row) -> SqliteResult<Template> {
+    let tags: String = row.get(4)?;
+    Ok(Template {
+        name: row.get(0)?,
+        title: row.get(1)?,
+        description: row.get(2)?,
+        priority: row.get(3)?,
+        tags: serde_json::from_str(&tags).unwrap_or_default(),
+        project: row.get(5)?,
+        due: row.get(6)?,
+    })
+}
+
+/// A `task_history` row selected as `task_id, actor, action, details,
+/// changed_at`.
+fn history_from_row(row: &Row) -> SqliteResult<HistoryEntry> {
+    Ok(HistoryEntry {
+        task_id: row.get(0)?,
+        actor: row.get(1)?,
+        action: row.get(2)?,
+        details: row.get(3)?,
+        changed_at: DateTime::parse_from_rfc3339(&row.get::<_, String>(4)?)
+            .unwrap()
+            .with_timezone(&Utc),
+    })
+}
+
+const TEMPLATE_COLUMNS: &str = "name, title, description, priority, tags, project, due";
+
+/// Version of the schema created by `Database::init`.
+pub const SCHEMA_VERSION: i64 = MIGRATIONS[MIGRATIONS.len() - 1].version;
+
+/// One step in the history of the schema. Steps check what is already
+/// there (`IF NOT EXISTS`, `Database::add_column`), since databases from
+/// before the meta table start at version 0 whatever they contain.
+struct Migration {
+    version: i64,
+    description: &'static str,
+    apply: fn(&Database) -> SqliteResult<()>,
+}
+
+/// Every schema change, oldest first. Append new steps; never edit one
+/// that has shipped.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "tasks, tags and meta",
+        apply: |db| {
+            db.conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS tasks (
+                    id INTEGER PRIMARY KEY,
+                    title TEXT NOT NULL,
+                    description TEXT,
+                    due_date TEXT,
+                    priority INTEGER DEFAULT 1,
+                    completed BOOLEAN DEFAULT FALSE,
+                    created_at TEXT NOT NULL,
+                    updated_at TEXT NOT NULL
+                );
+                CREATE TABLE IF NOT EXISTS task_tags (
+                    task_id INTEGER NOT NULL,
+                    tag TEXT NOT NULL,
+                    PRIMARY KEY (task_id, tag)
+                );
+                CREATE TABLE IF NOT EXISTS meta (
+                    key TEXT PRIMARY KEY,
+                    value TEXT NOT NULL
+                );",
+            )
+        },
+    },
+    Migration {
+        version: 2,
+        description: "projects",
+        apply: |db| {
+            db.conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS projects (
+                    id INTEGER PRIMARY KEY,
+                    name TEXT NOT NULL UNIQUE,
+                    created_at TEXT NOT NULL
+                );",
+            )?;
+            db.add_column("tasks", "project_id", "INTEGER")
+        },
+    },
+    Migration {
+        version: 3,
+        description: "recurring tasks",
+        apply: |db| db.add_column("tasks", "recurrence", "TEXT"),
+    },
+    Migration {
+        version: 4,
+        description: "subtasks",
+        apply: |db| db.add_column("tasks", "parent_id", "INTEGER"),
+    },
+    Migration {
+        version: 5,
+        description: "search index",
+        apply: |db| {
+            if db.has_table("tasks_fts")? {
+                return Ok(());
+            }
+            db.create_search_index()
+        },
+    },
+    Migration {
+        version: 6,
+        description: "trash and undo journal",
+        apply: |db| {
+            db.conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS operations (
+                    id INTEGER PRIMARY KEY,
+                    task_id INTEGER NOT NULL,
+                    kind TEXT NOT NULL,
+                    data TEXT NOT NULL,
+                    created_at TEXT NOT NULL
+                );",
+            )?;
+            db.add_column("tasks", "deleted_at", "TEXT")
+        },
+    },
+    Migration {
+        version: 7,
+        description: "archive",
+        apply: |db| {
+            db.conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS archived_tasks (
+                    id INTEGER PRIMARY KEY,
+                    title TEXT NOT NULL,
+                    description TEXT,
+                    due_date TEXT,
+                    priority INTEGER NOT NULL,
+                    completed BOOLEAN NOT NULL,
+                    created_at TEXT NOT NULL,
+                    updated_at TEXT NOT NULL,
+                    tags TEXT,
+                    project TEXT,
+                    recurrence TEXT,
+                    parent_id INTEGER,
+                    archived_at TEXT NOT NULL
+                );",
+            )
+        },
+    },
+    Migration {
+        version: 8,
+        description: "duplicate detection",
+        apply: |db| {
+            db.add_column("tasks", "content_hash", "TEXT")?;
+            db.conn.execute(
+                "CREATE INDEX IF NOT EXISTS tasks_content_hash ON tasks (content_hash)",
+                [],
+            )?;
+            Ok(())
+        },
+    },
+    Migration {
+        version: 9,
+        description: "notes",
+        apply: |db| {
+            db.conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS task_notes (
+                    id INTEGER PRIMARY KEY,
+                    task_id INTEGER NOT NULL,
+                    text TEXT NOT NULL,
+                    created_at TEXT NOT NULL
+                );",
+            )
+        },
+    },
+    Migration {
+        version: 10,
+        description: "board columns",
+        apply: |db| {
+            db.conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS started_tasks (
+                    task_id INTEGER PRIMARY KEY,
+                    started_at TEXT NOT NULL
+                );",
+            )
+        },
+    },
+    Migration {
+        version: 11,
+        description: "time tracking",
+        apply: |db| {
+            db.conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS time_entries (
+                    id INTEGER PRIMARY KEY,
+                    task_id INTEGER NOT NULL,
+                    started_at TEXT NOT NULL,
+                    stopped_at TEXT
+                );",
+            )
+        },
+    },
+    // Tables and columns that older versions created on every start
+    // without bumping the version, so any database may lack them.
+    Migration {
+        version: 12,
+        description: "links, focus sessions, snoozes, someday, goals, usage and history",
+        apply: |db| {
+            db.conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS task_links (
+                    task_id INTEGER NOT NULL,
+                    source TEXT NOT NULL,
+                    external_id TEXT NOT NULL,
+                    PRIMARY KEY (source, external_id)
+                );
+                CREATE TABLE IF NOT EXISTS focus_sessions (
+                    id INTEGER PRIMARY KEY,
+                    task_id INTEGER,
+                    started_at TEXT NOT NULL,
+                    ends_at TEXT NOT NULL,
+                    ended_at TEXT
+                );
+                CREATE TABLE IF NOT EXISTS task_snoozes (
+                    task_id INTEGER PRIMARY KEY,
+                    count INTEGER NOT NULL DEFAULT 0,
+                    last_snoozed_at TEXT NOT NULL
+                );
+                CREATE TABLE IF NOT EXISTS someday_tasks (
+                    task_id INTEGER PRIMARY KEY,
+                    since TEXT NOT NULL,
+                    reviewed_at TEXT NOT NULL
+                );
+                CREATE TABLE IF NOT EXISTS goals (
+                    id INTEGER PRIMARY KEY,
+                    title TEXT NOT NULL,
+                    target_date TEXT,
+                    created_at TEXT NOT NULL
+                );
+                CREATE TABLE IF NOT EXISTS task_goals (
+                    task_id INTEGER PRIMARY KEY,
+                    goal_id INTEGER NOT NULL
+                );
+                CREATE TABLE IF NOT EXISTS command_usage (
+                    id INTEGER PRIMARY KEY,
+                    command TEXT NOT NULL,
+                    used_at TEXT NOT NULL,
+                    duration_ms INTEGER NOT NULL
+                );
+                CREATE TABLE IF NOT EXISTS task_history (
+                    id INTEGER PRIMARY KEY,
+                    task_id INTEGER NOT NULL,
+                    actor TEXT NOT NULL,
+                    action TEXT NOT NULL,
+                    details TEXT,
+                    changed_at TEXT NOT NULL
+                );",
+            )?;
+            db.add_column("operations", "batch", "INTEGER NOT NULL DEFAULT 0")
+        },
+    },
+    Migration {
+        version: 13,
+        description: "manual order",
+        apply: |db| db.add_column("tasks", "sort_order", "INTEGER"),
+    },
+    Migration {
+        version: 14,
+        description: "energy levels",
+        apply: |db| db.add_column("tasks", "energy", "TEXT"),
+    },
+    Migration {
+        version: 15,
+        description: "time estimates",
+        apply: |db| db.add_column("tasks", "estimate_minutes", "INTEGER"),
+    },
+    Migration {
+        version: 16,
+        description: "task dependencies",
+        apply: |db| {
+            db.conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS task_dependencies (
+                    task_id INTEGER NOT NULL,
+                    depends_on INTEGER NOT NULL,
+                    PRIMARY KEY (task_id, depends_on)
+                );",
+            )
+        },
+    },
+    Migration {
+        version: 17,
+        description: "inherited priorities",
+        apply: |db| db.add_column("projects", "default_priority", "INTEGER"),
+    },
+    Migration {
+        version: 18,
+        description: "task templates",
+        apply: |db| {
+            db.conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS templates (
+                    name TEXT PRIMARY KEY,
+                    title TEXT NOT NULL,
+                    description TEXT,
+                    priority INTEGER,
+                    tags TEXT NOT NULL DEFAULT '[]',
+                    project TEXT,
+                    due TEXT,
+                    created_at TEXT NOT NULL
+                );",
+            )
+        },
+    },
+    Migration {
+        version: 19,
+        description: "sent reminders",
+        apply: |db| {
+            db.conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS task_reminders (
+                    task_id INTEGER NOT NULL,
+                    due_date TEXT NOT NULL,
+                    sent_at TEXT NOT NULL,
+                    PRIMARY KEY (task_id, due_date)
+                );",
+            )
+        },
+    },
+    Migration {
+        version: 20,
+        description: "completion times",
+        apply: |db| {
+            db.add_column("tasks", "completed_at", "TEXT")?;
+            db.add_column("archived_tasks", "completed_at", "TEXT")?;
+            // Tasks completed before this step finished when their history
+            // says, or at their last change when it has nothing
+            for table in ["tasks", "archived_tasks"] {
+                db.conn.execute(
+                    &format!(
+                        "UPDATE {0} SET completed_at = COALESCE(
+                            (SELECT MAX(changed_at) FROM task_history
+                             WHERE task_id = {0}.id AND action = 'completed'),
+                            updated_at)
+                         WHERE completed = TRUE AND completed_at IS NULL",
+                        table
+                    ),
+                    [],
+                )?;
+            }
+            Ok(())
+        },
+    },
+    Migration {
+        version: 21,
+        description: "note search index",
+        apply: |db| {
+            if db.has_table("notes_fts")? {
+                return Ok(());
+            }
+            db.create_note_search_index()
+        },
+    },
+    Migration {
+        version: 22,
+        description: "start dates",
+        apply: |db| db.add_column("tasks", "start_date", "TEXT"),
+    },
+    Migration {
+        version: 23,
+        description: "task uuids",
+        apply: |db| {
+            for table in ["tasks", "archived_tasks"] {
+                db.add_column(table, "uuid", "TEXT")?;
+                db.conn.execute(
+                    &format!(
+                        "UPDATE {} SET uuid = {} WHERE uuid IS NULL",
+                        table, NEW_UUID
+                    ),
+                    [],
+                )?;
+            }
+            db.conn.execute(
+                "CREATE UNIQUE INDEX IF NOT EXISTS idx_tasks_uuid ON tasks(uuid)",
+                [],
+            )?;
+            Ok(())
+        },
+    },
+    Migration {
+        version: 24,
+        description: "attachments",
+        apply: |db| {
+            db.conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS task_attachments (
+                    id INTEGER PRIMARY KEY,
+                    task_id INTEGER NOT NULL,
+                    target TEXT NOT NULL,
+                    created_at TEXT NOT NULL
+                );",
+            )
+        },
+    },
+    Migration {
+        version: 25,
+        description: "contexts",
+        apply: |db| {
+            db.conn
+                .execute_batch("CREATE TABLE IF NOT EXISTS contexts (name TEXT PRIMARY KEY);")
+        },
+    },
+];
+
+/// A database whose schema version is past `SCHEMA_VERSION`, written by a
+/// newer `todo`.
+#[derive(Debug)]
+pub struct SchemaTooNew(pub i64);
+
+impl std::fmt::Display for SchemaTooNew {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "the database has schema version {}, but this todo only knows up to {}; upgrade todo to open it",
+            self.0, SCHEMA_VERSION
+        )
+    }
+}
+
+impl std::error::Error for SchemaTooNew {}
+
+/// Well-known keys in the `meta` table.
+pub const META_SCHEMA_VERSION: &str = "schema_version";
+pub const META_INSTALL_ID: &str = "install_id";
+pub const META_LAST_MAINTENANCE: &str = "last_maintenance_at";
+pub const META_LINK_SECRET: &str = "link_signing_secret";
+pub const META_IMPORT_PROGRESS: &str = "import_progress";
+pub const META_COMMAND_HISTORY: &str = "command_history";
+/// Prefix of the keys holding when `todo notify` last sent each priority's digest.
+pub const META_LAST_DIGEST: &str = "last_digest_at";
+
+/// Matches a tag pattern and everything nested beneath it. The pattern is
+/// bound to both placeholders.
+const TAG_SUBTREE_MATCH: &str = "(tag GLOB ? OR tag GLOB ? || '/*')";
+
+/// Which tasks a filter selects by completion.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum StatusFilter {
+    #[default]
+    Pending,
+    Completed,
+    Any,
+}
+
+/// The order `Database::get_tasks` returns tasks in.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum TaskOrder {
+    /// Highest priority first, then oldest first.
+    #[default]
+    Priority,
+    /// The order set with `Database::move_task`; tasks never moved follow
+    /// in priority order.
+    Manual,
+}
+
+impl TaskOrder {
+    fn to_sql(self) -> &'static str {
+        match self {
+            TaskOrder::Priority => "priority DESC, created_at ASC",
+            TaskOrder::Manual => "sort_order IS NULL, sort_order, priority DESC, created_at ASC",
+        }
+    }
+}
+
+/// Where `Database::move_task` puts a task, relative to another one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Placement {
+    Before(i32),
+    After(i32),
+}
+
+/// Which side of the someday/maybe bucket a filter selects.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum SomedayFilter {
+    /// Normal views: someday tasks are left out.
+    #[default]
+    Exclude,
+    Only,
+    Include,
+}
+
+/// Which tasks whose start date is still ahead a filter selects.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum WaitingFilter {
+    #[default]
+    Include,
+    /// Normal lists: waiting tasks are left out until they start.
+    Exclude,
+    Only,
+}
+
+/// Commands whose operations are kept for `todo undo`; older ones are
+/// dropped.
+pub const UNDO_HISTORY: i64 = 100;
+
+/// Tasks in the trash keep their row, with `deleted_at` set, until the trash
+/// is emptied. Every query for live tasks filters on this.
+const NOT_DELETED: &str = "tasks.deleted_at IS NULL";
+
+const IN_SOMEDAY: &str = "EXISTS (SELECT 1 FROM someday_tasks WHERE task_id = tasks.id)";
+
+/// Due dates from `from` (inclusive, or the beginning of time) up to `until`
+/// (exclusive).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DueRange {
+    pub from: Option<DateTime<Utc>>,
+    pub until: DateTime<Utc>,
+}
+
+/// A slice of a task list, taken in SQL: at most `limit` tasks (all of
+/// them when `None`) after skipping the first `offset`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Page {
+    pub limit: Option<usize>,
+    pub offset: usize,
+}
+
+/// Criteria for selecting tasks, compiled to a parameterized `WHERE` clause.
+#[derive(Debug, Clone, Default)]
+pub struct TaskFilter {
+    /// Due-date criteria only ever select pending tasks, whatever this is.
+    pub status: StatusFilter,
+    pub priority: Option<i32>,
+    /// Only pending tasks due between now and `now + window`.
+    pub due_within: Option<Duration>,
+    /// Only pending tasks due within this range.
+    pub due_range: Option<DueRange>,
+    /// Keep only tasks carrying every one of these tags. Each entry is a
+    /// glob, and also matches the levels nested beneath it, so `work`
+    /// matches `work/clients/acme`.
+    pub tags: Vec<String>,
+    /// Drop tasks carrying any of these tags or tags nested beneath them.
+    pub exclude_tags: Vec<String>,
+    /// Regular expression the title must match.
+    pub title_pattern: Option<String>,
+    /// Drop tasks without a due date.
+    pub exclude_undated: bool,
+    /// Keep only tasks in this project.
+    pub project: Option<String>,
+    /// Drop tasks in any of these projects.
+    pub exclude_projects: Vec<String>,
+    pub someday: SomedayFilter,
+    pub waiting: WaitingFilter,
+    /// Only pending tasks that were snoozed and aren't due yet.
+    pub snoozed: bool,
+    /// Words that must all match, as prefixes, in the title and description
+    /// or in one note; the same search as `Database::search_tasks`. Blank
+    /// text is ignored.
+    pub text: Option<String>,
+    /// Not a criterion: the order matching tasks come back in.
+    pub order: TaskOrder,
+}
+
+impl TaskFilter {
+    /// Pending and completed tasks, outside the someday/maybe bucket.
+    pub fn any_status() -> Self {
+        Self {
+            status: StatusFilter::Any,
+            ..Self::default()
+        }
+    }
+
+    fn to_sql(&self) -> (String, Vec<Value>) {
+        let mut conditions = vec![NOT_DELETED.to_string()];
+        let mut values = Vec::new();
+
+        if self.due_within.is_some() || self.due_range.is_some() {
+            conditions.push("completed = FALSE".to_string());
+        }
+        match self.status {
+            StatusFilter::Pending => conditions.push("completed = FALSE".to_string()),
+            StatusFilter::Completed => conditions.push("completed = TRUE".to_string()),
+            StatusFilter::Any => {}
+        }
+        if let Some(priority) = self.priority {
+            conditions.push(format!("{} = ?", EFFECTIVE_PRIORITY));
+            values.push(Value::from(priority));
+        }
+        if let Some(window) = self.due_within {
+            let now = Utc::now();
+            conditions.push("due_date >= ? AND due_date <= ?".to_string());
+            values.push(Value::from(now.to_rfc3339()));
+            values.push(Value::from((now + window).to_rfc3339()));
+        }
+        if let Some(range) = self.due_range {
+            if let Some(from) = range.from {
+                conditions.push("due_date >= ?".to_string());
+                values.push(Value::from(from.to_rfc3339()));
+            }
+            conditions.push("due_date < ?".to_string());
+            values.push(Value::from(range.until.to_rfc3339()));
+        }
+        for tag in &self.tags {
+            conditions.push(format!(
+                "EXISTS (SELECT 1 FROM task_tags WHERE task_id = tasks.id AND {})",
+                TAG_SUBTREE_MATCH
+            ));
+            values.push(Value::from(tag.clone()));
+            values.push(Value::from(tag.clone()));
+        }
+        if let Some(pattern) = &self.title_pattern {
+            conditions.push("title REGEXP ?".to_string());
+            values.push(Value::from(pattern.clone()));
+        }
+        for tag in &self.exclude_tags {
+            conditions.push(format!(
+                "NOT EXISTS (SELECT 1 FROM task_tags WHERE task_id = tasks.id AND {})",
+                TAG_SUBTREE_MATCH
+            ));
+            values.push(Value::from(tag.clone()));
+            values.push(Value::from(tag.clone()));
+        }
+        if self.exclude_undated {
+            conditions.push("due_date IS NOT NULL".to_string());
+        }
+        if let Some(project) = &self.project {
+            conditions.push("project_id = (SELECT id FROM projects WHERE name = ?)".to_string());
+            values.push(Value::from(project.clone()));
+        }
+        for project in &self.exclude_projects {
+            conditions
+                .push("project_id IS NOT (SELECT id FROM projects WHERE name = ?)".to_string());
+            values.push(Value::from(project.clone()));
+        }
+        if self.snoozed {
+            conditions.push(
+                "completed = FALSE AND due_date > ?
+                 AND EXISTS (SELECT 1 FROM task_snoozes WHERE task_id = tasks.id)"
+                    .to_string(),
+            );
+            values.push(Value::from(Utc::now().to_rfc3339()));
+        }
+        match self.someday {
+            SomedayFilter::Exclude => conditions.push(format!("NOT {}", IN_SOMEDAY)),
+            SomedayFilter::Only => conditions.push(IN_SOMEDAY.to_string()),
+            SomedayFilter::Include => {}
+        }
+        match self.waiting {
+            WaitingFilter::Include => {}
+            WaitingFilter::Exclude => {
+                conditions.push(
+                    "(completed = TRUE OR start_date IS NULL OR julianday(start_date) <= julianday(?))"
+                        .to_string(),
+                );
+                values.push(Value::from(Utc::now().to_rfc3339()));
+            }
+            WaitingFilter::Only => {
+                conditions
+                    .push("completed = FALSE AND julianday(start_date) > julianday(?)".to_string());
+                values.push(Value::from(Utc::now().to_rfc3339()));
+            }
+        }
+        if let Some(expression) = self.text.as_deref().and_then(fts_expression) {
+            conditions.push(
+                "(id IN (SELECT rowid FROM tasks_fts WHERE tasks_fts MATCH ?)
+                  OR id IN (SELECT task_id FROM task_notes WHERE id IN
+                      (SELECT rowid FROM notes_fts WHERE notes_fts MATCH ?)))"
+                    .to_string(),
+            );
+            values.push(Value::from(expression.clone()));
+            values.push(Value::from(expression));
+        }
+
+        (conditions.join(" AND "), values)
+    }
+}
+
+/// Open a connection to the database at `path` that waits out other
+/// processes' locks and has todo's SQL functions.
+fn connect(path: &std::path::Path) -> SqliteResult<Connection> {
+    let conn = Connection::open(path)?;
+    conn.busy_timeout(BUSY_TIMEOUT)?;
+    register_functions(&conn)?;
+    Ok(conn)
+}
+
+/// Register `REGEXP` so queries can use `column REGEXP pattern`. Compiled
+/// patterns are cached per statement by SQLite's auxiliary data.
+fn register_functions(conn: &Connection) -> SqliteResult<()> {
+    conn.create_scalar_function(
+        "regexp",
+        2,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        |ctx| {
+            let regex: Arc<Regex> = ctx.get_or_create_aux(0, |pattern| {
+                Regex::new(pattern.as_str()?)
+                    .map_err(|e| rusqlite::Error::UserFunctionError(Box::new(e)))
+            })?;
+            let text = ctx.get::<Option<String>>(1)?;
+            Ok(text.map(|t| regex.is_match(&t)).unwrap_or(false))
+        },
+    )
+}
+
+/// Actor recorded in the task history for changes made from the command
+/// line; see `Database::with_actor`.
+pub const ACTOR_CLI: &str = "cli";
+
+/// How long a statement waits for another process to release its lock on
+/// the database, retrying with backoff, before failing with `SQLITE_BUSY`.
+pub const BUSY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Whether `error` is SQLite giving up on a lock another process held for
+/// longer than `BUSY_TIMEOUT`.
+pub fn is_locked(error: &rusqlite::Error) -> bool {
+    matches!(
+        error.sqlite_error_code(),
+        Some(rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked)
+    )
+}
+
+/// A transaction that nests: `BEGIN IMMEDIATE` starts one at the top level
+/// and `SAVEPOINT` one inside another, so `todo batch` can wrap many
+/// commands in one transaction and still roll back a single failed command.
+/// Rolled back when dropped without `commit`.
+///
+/// The top level takes the write lock up front: a transaction that reads
+/// first and writes later can't wait for a lock taken in between, and would
+/// fail with `SQLITE_BUSY` straight away.
+pub struct Transaction<'a> {
+    conn: &'a Connection,
+    outermost: bool,
+    committed: bool,
+}
+
+impl Transaction<'_> {
+    pub fn commit(mut self) -> SqliteResult<()> {
+        self.committed = true;
+        self.conn.execute_batch(if self.outermost {
+            "COMMIT"
+        } else {
+            "RELEASE todo"
+        })
+    }
+}
+
+impl std::ops::Deref for Transaction<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        self.conn
+    }
+}
+
+impl Drop for Transaction<'_> {
+    fn drop(&mut self) {
+        if !self.committed {
+            // Fails only when SQLite already rolled everything back
+            let _ = self.conn.execute_batch(if self.outermost {
+                "ROLLBACK"
+            } else {
+                "ROLLBACK TO todo; RELEASE todo"
+            });
+        }
+    }
+}
 
 pub struct Database {
     conn: Connection,
+    actor: RefCell<String>,
+    /// SQL functions added by `load_extension`.
+    extension_functions: RefCell<Vec<String>>,
+    /// What `reopen` needs to set the new connection up the same way: the
+    /// passphrase given to `unlock` and the extensions loaded.
+    passphrase: RefCell<Option<String>>,
+    extensions: RefCell<Vec<std::path::PathBuf>>,
+    /// The schema version `init` left, so a migration by another process
+    /// can be noticed.
+    seen_schema: Cell<i64>,
 }
 
 impl Database {
+    /// Start a transaction, or a savepoint inside the one already open.
+    pub fn transaction(&self) -> SqliteResult<Transaction<'_>> {
+        let outermost = self.conn.is_autocommit();
+        self.conn.execute_batch(if outermost {
+            "BEGIN IMMEDIATE"
+        } else {
+            "SAVEPOINT todo"
+        })?;
+        Ok(Transaction {
+            conn: &self.conn,
+            outermost,
+            committed: false,
+        })
+    }
+
     pub fn new(path: &std::path::Path) -> SqliteResult<Self> {
-        let conn = Connection::open(path)?;
-        Ok(Self { conn })
+        let conn = connect(path)?;
+        Ok(Self {
+            conn,
+            actor: RefCell::new(ACTOR_CLI.to_string()),
+            extension_functions: RefCell::new(Vec::new()),
+            passphrase: RefCell::new(None),
+            extensions: RefCell::new(Vec::new()),
+            seen_schema: Cell::new(0),
+        })
+    }
+
+    /// A number that changes whenever another connection commits to the
+    /// database, for noticing changes without reading any tasks.
+    pub fn data_version(&self) -> SqliteResult<i64> {
+        self.conn
+            .pragma_query_value(None, "data_version", |row| row.get(0))
+    }
+
+    /// Whether todo was built with SQLCipher, so databases can be encrypted.
+    pub fn cipher_available(&self) -> bool {
+        self.conn
+            .query_row("PRAGMA cipher_version", [], |row| row.get::<_, String>(0))
+            .is_ok()
+    }
+
+    /// Whether the file can be read: false for an encrypted database until
+    /// `unlock` is given the right passphrase.
+    pub fn is_readable(&self) -> bool {
+        self.conn
+            .query_row("SELECT COUNT(*) FROM sqlite_master", [], |_| Ok(()))
+            .is_ok()
+    }
+
+    /// Give SQLCipher the passphrase of an encrypted database. Must come
+    /// before anything reads the file.
+    pub fn unlock(&self, passphrase: &str) -> SqliteResult<()> {
+        self.conn.pragma_update(None, "key", passphrase)?;
+        *self.passphrase.borrow_mut() = Some(passphrase.to_string());
+        Ok(())
     }
 
+    /// Load the SQLite extension at `path` (a shared library) into this
+    /// connection, remembering the SQL functions it adds.
+    pub fn load_extension(&self, path: &std::path::Path) -> SqliteResult<()> {
+        let before = self.function_names()?;
+        // SAFETY: loading is only enabled while the guard lives, and nothing
+        // but this one load runs meanwhile, so no SQL can load a library
+        unsafe {
+            let _guard = rusqlite::LoadExtensionGuard::new(&self.conn)?;
+            self.conn.load_extension(path, None::<&str>)?;
+        }
+        let mut added = self.extension_functions.borrow_mut();
+        for name in self.function_names()? {
+            if !before.contains(&name) && !added.contains(&name) {
+                added.push(name);
+            }
+        }
+        let mut extensions = self.extensions.borrow_mut();
+        if !extensions.iter().any(|loaded| loaded == path) {
+            extensions.push(path.to_path_buf());
+        }
+        Ok(())
+    }
+
+    /// SQL functions the loaded extensions added, for `sql_expr::validate`.
+    pub fn extension_functions(&self) -> Vec<String> {
+        self.extension_functions.borrow().clone()
+    }
+
+    fn function_names(&self) -> SqliteResult<std::collections::HashSet<String>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT DISTINCT name FROM pragma_function_list")?;
+        let names = stmt.query_map([], |row| row.get(0))?;
+        names.collect()
+    }
+
+    /// Write an encrypted copy of the database to `target`.
+    pub fn export_encrypted(&self, target: &std::path::Path, passphrase: &str) -> SqliteResult<()> {
+        self.conn.execute(
+            "ATTACH DATABASE ?1 AS encrypted KEY ?2",
+            params![target.to_string_lossy(), passphrase],
+        )?;
+        let exported = self
+            .conn
+            .query_row("SELECT sqlcipher_export('encrypted')", [], |_| Ok(()));
+        self.conn.execute("DETACH DATABASE encrypted", [])?;
+        exported
+    }
+
+    /// Create the schema, or bring a database made by an older `todo` up
+    /// to `SCHEMA_VERSION`. Databases from a newer `todo` are refused.
     pub fn init(&self) -> SqliteResult<()> {
+        // Readers and a writer can then work at the same time, which a CLI
+        // run from several terminals and scripts at once needs. The mode is
+        // stored in the file; it needs the passphrase first, so it is set
+        // here rather than in `new`.
+        self.conn
+            .pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))?;
+        self.migrate()?;
         self.conn.execute(
-            "CREATE TABLE IF NOT EXISTS tasks (
-                id INTEGER PRIMARY KEY,
-                title TEXT NOT NULL,
-                description TEXT,
-                due_date TEXT,
-                priority INTEGER DEFAULT 1,
-                completed BOOLEAN DEFAULT FALSE,
-                created_at TEXT NOT NULL,
-                updated_at TEXT NOT NULL
-            )",
-            [],
+            "INSERT OR IGNORE INTO meta (key, value) VALUES
+                (?1, lower(hex(randomblob(16)))), (?2, lower(hex(randomblob(32))))",
+            params![META_INSTALL_ID, META_LINK_SECRET],
         )?;
+        self.seen_schema.set(self.schema_version()?);
+        Ok(())
+    }
+
+    /// For long-running processes such as `todo serve`: when another
+    /// process changed the schema version in `meta` since `init` or the
+    /// last call, open a fresh connection so every statement is prepared
+    /// against the new schema, and return the new version. An older
+    /// version than ours is migrated up again; a newer one, from a newer
+    /// `todo`, is used as it is, since migrations only add to the schema.
+    pub fn reopen_if_migrated(&mut self) -> SqliteResult<Option<i64>> {
+        let version = self.schema_version()?;
+        if version == self.seen_schema.get() {
+            return Ok(None);
+        }
+        // Nothing else can reach an in-memory database
+        if let Some(path) = self.conn.path().filter(|path| !path.is_empty()) {
+            let conn = connect(std::path::Path::new(path))?;
+            if let Some(passphrase) = self.passphrase.borrow().as_deref() {
+                conn.pragma_update(None, "key", passphrase)?;
+            }
+            self.conn = conn;
+            self.extension_functions.borrow_mut().clear();
+            for extension in self.extensions.take() {
+                self.load_extension(&extension)?;
+            }
+        }
+        if version <= SCHEMA_VERSION {
+            self.init()?;
+        }
+        let version = self.schema_version()?;
+        self.seen_schema.set(version);
+        Ok(Some(version))
+    }
+
+    /// Run the steps of `MIGRATIONS` past the stored version, each in its
+    /// own transaction together with the version bump, so a failed step
+    /// leaves the database at the last version that completed.
+    fn migrate(&self) -> SqliteResult<()> {
+        let current = self.schema_version()?;
+        if current > SCHEMA_VERSION {
+            return Err(rusqlite::Error::UserFunctionError(Box::new(SchemaTooNew(
+                current,
+            ))));
+        }
+        for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+            let tx = self.transaction()?;
+            (migration.apply)(self).map_err(|error| {
+                rusqlite::Error::UserFunctionError(
+                    format!(
+                        "schema migration {} ({}) failed: {}",
+                        migration.version, migration.description, error
+                    )
+                    .into(),
+                )
+            })?;
+            self.set_meta(META_SCHEMA_VERSION, &migration.version.to_string())?;
+            tx.commit()?;
+        }
+        // Hashing reads tasks through `TASK_COLUMNS`, which needs tables
+        // from later steps, so it waits until every step has run.
+        if current < 8 {
+            let tx = self.transaction()?;
+            self.rehash_tasks(&self.task_ids()?)?;
+            tx.commit()?;
+        }
+        Ok(())
+    }
+
+    /// The version recorded in `meta`; 0 for new databases and for those
+    /// from before the meta table.
+    pub fn schema_version(&self) -> SqliteResult<i64> {
+        if !self.has_table("meta")? {
+            return Ok(0);
+        }
+        Ok(self
+            .get_meta(META_SCHEMA_VERSION)?
+            .and_then(|version| version.parse().ok())
+            .unwrap_or(0))
+    }
+
+    fn add_column(&self, table: &str, column: &str, definition: &str) -> SqliteResult<()> {
+        if !self.has_column(table, column)? {
+            self.conn.execute(
+                &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+                [],
+            )?;
+        }
         Ok(())
     }
 
+    fn has_table(&self, table: &str) -> SqliteResult<bool> {
+        self.conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE name = ?)",
+            [table],
+            |row| row.get(0),
+        )
+    }
+
+    /// FTS5 index over titles and descriptions, kept in sync by triggers and
+    /// filled from any tasks that already exist.
+    fn create_search_index(&self) -> SqliteResult<()> {
+        self.conn.execute_batch(
+            "CREATE VIRTUAL TABLE tasks_fts USING fts5(
+                title, description,
+                content = 'tasks', content_rowid = 'id',
+                tokenize = 'unicode61 remove_diacritics 2'
+            );
+            CREATE TRIGGER tasks_fts_insert AFTER INSERT ON tasks BEGIN
+                INSERT INTO tasks_fts (rowid, title, description)
+                VALUES (new.id, new.title, new.description);
+            END;
+            CREATE TRIGGER tasks_fts_delete AFTER DELETE ON tasks BEGIN
+                INSERT INTO tasks_fts (tasks_fts, rowid, title, description)
+                VALUES ('delete', old.id, old.title, old.description);
+            END;
+            CREATE TRIGGER tasks_fts_update AFTER UPDATE OF title, description ON tasks BEGIN
+                INSERT INTO tasks_fts (tasks_fts, rowid, title, description)
+                VALUES ('delete', old.id, old.title, old.description);
+                INSERT INTO tasks_fts (rowid, title, description)
+                VALUES (new.id, new.title, new.description);
+            END;
+            INSERT INTO tasks_fts (tasks_fts) VALUES ('rebuild');",
+        )
+    }
+
+    /// FTS5 index over task notes, like `create_search_index` for tasks.
+    fn create_note_search_index(&self) -> SqliteResult<()> {
+        self.conn.execute_batch(
+            "CREATE VIRTUAL TABLE notes_fts USING fts5(
+                text,
+                content = 'task_notes', content_rowid = 'id',
+                tokenize = 'unicode61 remove_diacritics 2'
+            );
+            CREATE TRIGGER notes_fts_insert AFTER INSERT ON task_notes BEGIN
+                INSERT INTO notes_fts (rowid, text) VALUES (new.id, new.text);
+            END;
+            CREATE TRIGGER notes_fts_delete AFTER DELETE ON task_notes BEGIN
+                INSERT INTO notes_fts (notes_fts, rowid, text) VALUES ('delete', old.id, old.text);
+            END;
+            CREATE TRIGGER notes_fts_update AFTER UPDATE OF text ON task_notes BEGIN
+                INSERT INTO notes_fts (notes_fts, rowid, text) VALUES ('delete', old.id, old.text);
+                INSERT INTO notes_fts (rowid, text) VALUES (new.id, new.text);
+            END;
+            INSERT INTO notes_fts (notes_fts) VALUES ('rebuild');",
+        )
+    }
+
+    fn has_column(&self, table: &str, column: &str) -> SqliteResult<bool> {
+        let mut stmt = self
+            .conn
+            .prepare(&format!("SELECT name FROM pragma_table_info('{}')", table))?;
+        let names = stmt.query_map([], |row| row.get::<_, String>(0))?;
+        for name in names {
+            if name? == column {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
     pub fn add_task(&self, task: &Task) -> SqliteResult<i32> {
-        let due_date_str = task.due_date.map(|d| d.to_rfc3339());
+        // One transaction for the row and its tags keeps quick capture to a
+        // single fsync.
+        let tx = self.transaction()?;
+        let id = self.insert_task(None, task)?;
+        tx.commit()?;
+        Ok(id)
+    }
+
+    /// Add many tasks in one transaction, returning their IDs in order.
+    /// Nothing is added if one of them fails.
+    pub fn add_tasks_batch(&self, tasks: &[Task]) -> SqliteResult<Vec<i32>> {
+        let tx = self.transaction()?;
+        let ids = tasks
+            .iter()
+            .map(|task| self.insert_task(None, task))
+            .collect::<SqliteResult<_>>()?;
+        tx.commit()?;
+        Ok(ids)
+    }
+
+    /// Like `add_tasks_batch`, with each task's dependencies: the existing
+    /// tasks it comes after, and those that come after it.
+    pub fn add_tasks_with_dependencies(
+        &self,
+        tasks: &[(Task, Vec<i32>, Vec<i32>)],
+    ) -> SqliteResult<Vec<i32>> {
+        let tx = self.transaction()?;
+        let mut ids = Vec::new();
+        for (task, after, before) in tasks {
+            let id = self.insert_task(None, task)?;
+            for &other in after {
+                self.add_dependency(id, other)?;
+            }
+            for &other in before {
+                self.add_dependency(other, id)?;
+            }
+            ids.push(id);
+        }
+        tx.commit()?;
+        Ok(ids)
+    }
+
+    /// Record that task `id` can only start once `depends_on` is done.
+    pub fn add_dependency(&self, id: i32, depends_on: i32) -> SqliteResult<()> {
+        let tx = self.transaction()?;
+        let added = self.conn.execute(
+            "INSERT OR IGNORE INTO task_dependencies (task_id, depends_on) VALUES (?1, ?2)",
+            params![id, depends_on],
+        )?;
+        if added > 0 {
+            let details = format!("after task {}", depends_on);
+            self.record_history(id, "dependency added", Some(&details))?;
+        }
+        tx.commit()
+    }
+
+    /// Whether task `id` waits on `other`, directly or through other
+    /// dependencies.
+    pub fn depends_on(&self, id: i32, other: i32) -> SqliteResult<bool> {
+        self.conn.query_row(
+            "WITH RECURSIVE upstream(id) AS (
+                 SELECT depends_on FROM task_dependencies WHERE task_id = ?1
+                 UNION
+                 SELECT depends_on FROM task_dependencies JOIN upstream
+                     ON task_dependencies.task_id = upstream.id
+             )
+             SELECT EXISTS (SELECT 1 FROM upstream WHERE id = ?2)",
+            params![id, other],
+            |row| row.get(0),
+        )
+    }
+
+    /// Every dependency as `(task, the task it waits on)`, trash included.
+    pub fn get_dependency_pairs(&self) -> SqliteResult<Vec<(i32, i32)>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT task_id, depends_on FROM task_dependencies ORDER BY task_id")?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect()
+    }
+
+    /// The tasks task `id` waits on, lowest ID first.
+    pub fn get_dependencies(&self, id: i32) -> SqliteResult<Vec<Task>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM tasks
+             JOIN task_dependencies ON task_dependencies.depends_on = tasks.id
+             WHERE task_dependencies.task_id = ? AND {}
+             ORDER BY tasks.id",
+            TASK_COLUMNS, NOT_DELETED
+        ))?;
+        let rows = stmt.query_map([id], task_from_row)?;
+        rows.collect()
+    }
 
+    /// Insert a task and its tags under `id`, or the next free ID when
+    /// `None`; callers provide the transaction.
+    fn insert_task(&self, id: Option<i32>, task: &Task) -> SqliteResult<i32> {
+        let id = match id {
+            Some(id) => Some(id),
+            None => self.id_after_archive()?,
+        };
+        let due_date_str = task.due_date.map(|d| d.to_rfc3339());
+        let project_id = self.resolve_project(task.project.as_deref())?;
+        // A task keeps its UUID, such as when undoing a delete, unless
+        // another task already has it
         self.conn.execute(
-            "INSERT INTO tasks (title, description, due_date, priority, completed, created_at, updated_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            &format!(
+                "INSERT INTO tasks (id, title, description, due_date, priority, completed, created_at, updated_at, project_id, recurrence, parent_id, content_hash, energy, completed_at, start_date, uuid)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15,
+                         CASE WHEN ?16 IS NULL OR EXISTS (SELECT 1 FROM tasks WHERE uuid = ?16)
+                              THEN {} ELSE ?16 END)",
+                NEW_UUID
+            ),
             params![
+                id,
                 task.title,
                 task.description,
                 due_date_str,
-                task.priority,
+                task.own_priority(),
                 task.completed,
                 task.created_at.to_rfc3339(),
                 task.updated_at.to_rfc3339(),
+                project_id,
+                task.recurrence,
+                task.parent_id,
+                task.content_hash(),
+                task.energy.map(|energy| energy.name()),
+                task.completed
+                    .then(|| task.completed_at.unwrap_or(task.updated_at).to_rfc3339()),
+                task.start_date.map(|d| d.to_rfc3339()),
+                task.uuid,
             ],
         )?;
 
-        Ok(self.conn.last_insert_rowid() as i32)
+        let id = self.conn.last_insert_rowid() as i32;
+        for tag in &task.tags {
+            self.insert_tag(id, tag)?;
+        }
+        self.record_history(id, "created", None)?;
+        Ok(id)
     }
 
-    pub fn get_all_tasks(
-        &self,
-        include_completed: bool,
-        priority_filter: Option<i32>,
-    ) -> SqliteResult<Vec<Task>> {
-        let mut query = String::from(
-            "SELECT id, title, description, due_date, priority, completed, created_at, updated_at 
-             FROM tasks",
-        );
+    /// New tasks must not reuse the ID of an archived one. SQLite picks
+    /// `MAX(id) + 1` from `tasks` on its own, so an explicit ID is only needed
+    /// while the newest task ID lives in the archive.
+    fn id_after_archive(&self) -> SqliteResult<Option<i32>> {
+        self.conn.query_row(
+            "SELECT MAX(id) + 1 FROM archived_tasks
+             WHERE id >= (SELECT COALESCE(MAX(id), 0) FROM tasks)",
+            [],
+            |row| row.get(0),
+        )
+    }
 
-        let mut conditions = Vec::new();
-        if !include_completed {
-            conditions.push("completed = FALSE".to_string());
-        }
-        if let Some(priority) = priority_filter {
-            conditions.push(format!("priority = {}", priority));
+    /// Insert one chunk of an import and record `progress` under
+    /// `META_IMPORT_PROGRESS` in the same transaction, so an interrupted
+    /// import resumes right after the last chunk that was committed. A task
+    /// whose ID is already taken replaces that task, even one in the trash; tasks without an ID get
+    /// the next free one. Tasks with an external ID already linked to
+    /// `source` are skipped. With `dedupe`, a new pending task that is
+    /// already pending under another ID (see `find_pending_duplicate`) is
+    /// not inserted; its external ID is linked to the existing task instead.
+    /// Returns how many tasks were written.
+    pub fn import_chunk(
+        &self,
+        tasks: &[(Task, Option<String>)],
+        source: &str,
+        progress: &str,
+        dedupe: bool,
+    ) -> SqliteResult<usize> {
+        let tx = self.transaction()?;
+        let mut written = 0;
+        for (task, external_id) in tasks {
+            if let Some(external_id) = external_id {
+                if self.find_linked_task(source, external_id)?.is_some() {
+                    continue;
+                }
+            }
+            let id = match task.id {
+                Some(id) if self.task_ids_contain(id)? => {
+                    // A trashed task holding the ID comes back replaced
+                    self.conn
+                        .execute("UPDATE tasks SET deleted_at = NULL WHERE id = ?", [id])?;
+                    let before = self.get_task_by_id(id)?;
+                    self.update_task(id, task)?;
+                    self.replace_tags(id, &task.tags)?;
+                    self.record_edit(id, before.as_ref())?;
+                    id
+                }
+                id => {
+                    let duplicate = if dedupe && !task.completed {
+                        self.find_pending_duplicate(task)?
+                    } else {
+                        None
+                    };
+                    if let Some(duplicate) = duplicate {
+                        if let Some(external_id) = external_id {
+                            self.add_task_link(duplicate.id.unwrap(), source, external_id)?;
+                        }
+                        continue;
+                    }
+                    self.insert_task(id, task)?
+                }
+            };
+            self.set_someday(id, task.someday)?;
+            self.set_in_progress(id, task.in_progress && !task.completed)?;
+            if let Some(external_id) = external_id {
+                self.add_task_link(id, source, external_id)?;
+            }
+            written += 1;
         }
+        self.set_meta(META_IMPORT_PROGRESS, progress)?;
+        tx.commit()?;
+        Ok(written)
+    }
+
+    pub fn get_tasks(&self, filter: &TaskFilter) -> SqliteResult<Vec<Task>> {
+        self.get_tasks_page(filter, Page::default())
+    }
+
+    /// The `page` of the tasks `get_tasks` would return.
+    pub fn get_tasks_page(&self, filter: &TaskFilter, page: Page) -> SqliteResult<Vec<Task>> {
+        let (conditions, mut values) = filter.to_sql();
+        let mut query = format!("SELECT {} FROM tasks", TASK_COLUMNS);
 
         if !conditions.is_empty() {
             query.push_str(" WHERE ");
-            query.push_str(&conditions.join(" AND "));
+            query.push_str(&conditions);
         }
 
-        query.push_str(" ORDER BY priority DESC, created_at ASC");
+        query.push_str(" ORDER BY ");
+        query.push_str(filter.order.to_sql());
+        if page != Page::default() {
+            // A negative limit means no limit to SQLite
+            query.push_str(" LIMIT ? OFFSET ?");
+            values.push(Value::from(page.limit.map_or(-1, |limit| limit as i64)));
+            values.push(Value::from(page.offset as i64));
+        }
 
         let mut stmt = self.conn.prepare(&query)?;
-        let task_iter = stmt.query_map([], |row| {
-            let due_date_str: Option<String> = row.get(3)?;
-            let due_date = due_date_str
-                .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
-                .map(|dt| dt.with_timezone(&Utc));
-
-            Ok(Task {
-                id: Some(row.get(0)?),
-                title: row.get(1)?,
-                description: row.get(2)?,
-                due_date,
-                priority: row.get(4)?,
-                completed: row.get(5)?,
-                created_at: DateTime::parse_from_rfc3339(&row.get::<_, String>(6)?)
-                    .unwrap()
-                    .with_timezone(&Utc),
-                updated_at: DateTime::parse_from_rfc3339(&row.get::<_, String>(7)?)
-                    .unwrap()
-                    .with_timezone(&Utc),
-            })
-        })?;
+        let task_iter = stmt.query_map(rusqlite::params_from_iter(values), task_from_row)?;
 
         task_iter.collect()
     }
 
-    pub fn get_task_by_id(&self, id: i32) -> SqliteResult<Option<Task>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT id, title, description, due_date, priority, completed, created_at, updated_at 
-             FROM tasks WHERE id = ?",
-        )?;
+    /// How many tasks `get_tasks` would return, without reading them.
+    pub fn count_tasks(&self, filter: &TaskFilter) -> SqliteResult<usize> {
+        let (conditions, values) = filter.to_sql();
+        self.conn.query_row(
+            &format!("SELECT COUNT(*) FROM tasks WHERE {}", conditions),
+            rusqlite::params_from_iter(values),
+            |row| row.get(0),
+        )
+    }
 
-        let mut task_iter = stmt.query_map([id], |row| {
-            let due_date_str: Option<String> = row.get(3)?;
-            let due_date = due_date_str
-                .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
-                .map(|dt| dt.with_timezone(&Utc));
+    /// Put task `id` just before or after another one in the manual order,
+    /// renumbering every task outside the trash. Returns false when either
+    /// task is missing or in the trash.
+    pub fn move_task(&self, id: i32, placement: Placement) -> SqliteResult<bool> {
+        let tx = self.transaction()?;
+        let mut ids: Vec<i32> = {
+            let mut stmt = self.conn.prepare(&format!(
+                "SELECT id FROM tasks WHERE {} ORDER BY {}",
+                NOT_DELETED,
+                TaskOrder::Manual.to_sql()
+            ))?;
+            let rows = stmt.query_map([], |row| row.get(0))?;
+            rows.collect::<SqliteResult<_>>()?
+        };
+        let (Placement::Before(target) | Placement::After(target)) = placement;
+        if !ids.contains(&id) {
+            return Ok(false);
+        }
+        ids.retain(|&other| other != id);
+        let Some(mut index) = ids.iter().position(|&other| other == target) else {
+            return Ok(false);
+        };
+        if let Placement::After(_) = placement {
+            index += 1;
+        }
+        ids.insert(index, id);
 
-            Ok(Task {
-                id: Some(row.get(0)?),
-                title: row.get(1)?,
-                description: row.get(2)?,
-                due_date,
-                priority: row.get(4)?,
-                completed: row.get(5)?,
-                created_at: DateTime::parse_from_rfc3339(&row.get::<_, String>(6)?)
-                    .unwrap()
-                    .with_timezone(&Utc),
-                updated_at: DateTime::parse_from_rfc3339(&row.get::<_, String>(7)?)
-                    .unwrap()
-                    .with_timezone(&Utc),
-            })
-        })?;
+        let mut update = self
+            .conn
+            .prepare("UPDATE tasks SET sort_order = ?1 WHERE id = ?2")?;
+        for (position, id) in ids.iter().enumerate() {
+            update.execute(params![position as i64, id])?;
+        }
+        drop(update);
+        tx.commit()?;
+        Ok(true)
+    }
+
+    pub fn get_task_by_id(&self, id: i32) -> SqliteResult<Option<Task>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM tasks WHERE id = ? AND {}",
+            TASK_COLUMNS, NOT_DELETED
+        ))?;
+
+        let mut task_iter = stmt.query_map([id], task_from_row)?;
 
         task_iter.next().transpose()
     }
@@ -134,145 +1507,2737 @@ impl Database {
     pub fn update_task(&self, id: i32, task: &Task) -> SqliteResult<()> {
         let due_date_str = task.due_date.map(|d| d.to_rfc3339());
 
+        let project_id = self.resolve_project(task.project.as_deref())?;
+
         self.conn.execute(
             "UPDATE tasks 
              SET title = ?1, description = ?2, due_date = ?3, priority = ?4, 
-                 completed = ?5, updated_at = ?6
-             WHERE id = ?7",
+                 completed = ?5, updated_at = ?6, project_id = ?7, recurrence = ?8,
+                 parent_id = ?9, content_hash = ?10, energy = ?11,
+                 completed_at = CASE WHEN ?5 THEN COALESCE(completed_at, ?6) END,
+                 start_date = ?12
+             WHERE id = ?13",
             params![
                 task.title,
                 task.description,
                 due_date_str,
-                task.priority,
+                task.own_priority(),
                 task.completed,
                 Utc::now().to_rfc3339(),
+                project_id,
+                task.recurrence,
+                task.parent_id,
+                task.content_hash(),
+                task.energy.map(|energy| energy.name()),
+                task.start_date.map(|d| d.to_rfc3339()),
                 id,
             ],
         )?;
         Ok(())
     }
 
+    /// Move a task to the trash. Its subtasks move up to its parent; undoing
+    /// the delete or restoring the task nests them again.
     pub fn delete_task(&self, id: i32) -> SqliteResult<()> {
-        self.conn.execute("DELETE FROM tasks WHERE id = ?", [id])?;
-        Ok(())
+        self.delete_tasks(&[id])
+    }
+
+    /// Move several tasks to the trash in one transaction, undone together.
+    pub fn delete_tasks(&self, ids: &[i32]) -> SqliteResult<()> {
+        let tx = self.transaction()?;
+        let batch = self.next_batch()?;
+        for &id in ids {
+            self.trash_task(id, batch)?;
+        }
+        tx.commit()
     }
 
-    pub fn complete_task(&self, id: i32) -> SqliteResult<()> {
+    fn trash_task(&self, id: i32, batch: i64) -> SqliteResult<()> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id FROM tasks WHERE parent_id = ? ORDER BY id")?;
+        let subtasks = stmt
+            .query_map([id], |row| row.get(0))?
+            .collect::<SqliteResult<Vec<i32>>>()?;
         self.conn.execute(
-            "UPDATE tasks SET completed = TRUE, updated_at = ? WHERE id = ?",
+            "UPDATE tasks SET parent_id = (SELECT parent_id FROM tasks WHERE id = ?1)
+             WHERE parent_id = ?1",
+            [id],
+        )?;
+        self.conn.execute(
+            "UPDATE tasks SET deleted_at = ?1 WHERE id = ?2",
             params![Utc::now().to_rfc3339(), id],
         )?;
+        self.log_operation(
+            &Operation::Delete {
+                task_id: id,
+                subtasks,
+            },
+            batch,
+        )?;
+        self.record_history(id, "deleted", None)
+    }
+
+    /// Remove a task and everything attached to it for good.
+    fn purge_task(&self, id: i32) -> SqliteResult<()> {
+        self.conn.execute(
+            "UPDATE tasks SET parent_id = NULL WHERE parent_id = ?",
+            [id],
+        )?;
+        self.conn
+            .execute("DELETE FROM task_tags WHERE task_id = ?", [id])?;
+        self.conn
+            .execute("DELETE FROM task_links WHERE task_id = ?", [id])?;
+        self.conn
+            .execute("DELETE FROM task_snoozes WHERE task_id = ?", [id])?;
+        self.conn
+            .execute("DELETE FROM task_reminders WHERE task_id = ?", [id])?;
+        self.conn.execute(
+            "DELETE FROM task_dependencies WHERE task_id = ?1 OR depends_on = ?1",
+            [id],
+        )?;
+        self.conn
+            .execute("DELETE FROM someday_tasks WHERE task_id = ?", [id])?;
+        self.conn
+            .execute("DELETE FROM started_tasks WHERE task_id = ?", [id])?;
+        self.conn
+            .execute("DELETE FROM task_goals WHERE task_id = ?", [id])?;
+        self.conn
+            .execute("DELETE FROM operations WHERE task_id = ?", [id])?;
+        self.conn
+            .execute("DELETE FROM task_history WHERE task_id = ?", [id])?;
+        self.conn
+            .execute("DELETE FROM task_notes WHERE task_id = ?", [id])?;
+        self.conn
+            .execute("DELETE FROM task_attachments WHERE task_id = ?", [id])?;
+        self.conn
+            .execute("DELETE FROM time_entries WHERE task_id = ?", [id])?;
+        self.conn.execute("DELETE FROM tasks WHERE id = ?", [id])?;
         Ok(())
     }
 
-    pub fn task_exists(&self, id: i32) -> SqliteResult<bool> {
-        let count: i32 =
-            self.conn
-                .query_row("SELECT COUNT(*) FROM tasks WHERE id = ?", [id], |row| {
-                    row.get(0)
-                })?;
-        Ok(count > 0)
+    /// A task by ID, whether or not it is in the trash.
+    pub fn get_task_with_trashed(&self, id: i32) -> SqliteResult<Option<Task>> {
+        let mut stmt = self
+            .conn
+            .prepare(&format!("SELECT {} FROM tasks WHERE id = ?", TASK_COLUMNS))?;
+        let mut task_iter = stmt.query_map([id], task_from_row)?;
+        task_iter.next().transpose()
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use tempfile::NamedTempFile;
+    /// IDs of the tasks, trashed ones included, whose UUID starts with
+    /// `prefix`.
+    pub fn find_tasks_by_uuid(&self, prefix: &str) -> SqliteResult<Vec<i32>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id FROM tasks WHERE substr(uuid, 1, length(?1)) = ?1 ORDER BY id")?;
+        let ids = stmt.query_map([prefix.to_lowercase()], |row| row.get(0))?;
+        ids.collect()
+    }
 
-    fn create_test_db() -> (Database, NamedTempFile) {
-        let temp_file = NamedTempFile::new().unwrap();
-        let db = Database::new(temp_file.path()).unwrap();
-        db.init().unwrap();
-        (db, temp_file)
+    /// Tasks in the trash with the time they were deleted, most recent first.
+    pub fn get_trashed_tasks(&self) -> SqliteResult<Vec<(Task, DateTime<Utc>)>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {}, deleted_at FROM tasks WHERE deleted_at IS NOT NULL
+             ORDER BY deleted_at DESC, id DESC",
+            TASK_COLUMNS
+        ))?;
+        let rows = stmt.query_map([], |row| {
+            let deleted_at =
+                DateTime::parse_from_rfc3339(&row.get::<_, String>(TASK_COLUMN_COUNT)?)
+                    .unwrap()
+                    .with_timezone(&Utc);
+            Ok((task_from_row(row)?, deleted_at))
+        })?;
+        rows.collect()
+    }
+
+    /// Take a task back out of the trash. Returns false if it isn't there.
+    pub fn restore_task(&self, id: i32) -> SqliteResult<bool> {
+        let tx = self.transaction()?;
+        let trashed: bool = self.conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM tasks WHERE id = ? AND deleted_at IS NOT NULL)",
+            [id],
+            |row| row.get(0),
+        )?;
+        if !trashed {
+            return Ok(false);
+        }
+        let mut stmt = self.conn.prepare(
+            "SELECT id, data FROM operations WHERE task_id = ? AND kind = 'delete'
+             ORDER BY id DESC LIMIT 1",
+        )?;
+        let logged = stmt
+            .query_map([id], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+            })?
+            .next()
+            .transpose()?;
+        let subtasks = match logged {
+            Some((op_id, data)) => {
+                self.conn
+                    .execute("DELETE FROM operations WHERE id = ?", [op_id])?;
+                match serde_json::from_str(&data) {
+                    Ok(Operation::Delete { subtasks, .. }) => subtasks,
+                    _ => Vec::new(),
+                }
+            }
+            None => Vec::new(),
+        };
+        self.undelete(id, &subtasks)?;
+        tx.commit()?;
+        Ok(true)
+    }
+
+    /// Clear `deleted_at` and nest `subtasks` under the task again, unless
+    /// they were moved somewhere else in the meantime.
+    fn undelete(&self, id: i32, subtasks: &[i32]) -> SqliteResult<()> {
+        self.conn
+            .execute("UPDATE tasks SET deleted_at = NULL WHERE id = ?", [id])?;
+        self.record_history(id, "restored", None)?;
+        for &subtask in subtasks {
+            self.conn.execute(
+                "UPDATE tasks SET parent_id = ?1
+                 WHERE id = ?2 AND parent_id IS (SELECT parent_id FROM tasks WHERE id = ?1)",
+                params![id, subtask],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Permanently delete every task in the trash. Returns how many.
+    pub fn empty_trash(&self) -> SqliteResult<usize> {
+        let tx = self.transaction()?;
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id FROM tasks WHERE deleted_at IS NOT NULL")?;
+        let ids = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<SqliteResult<Vec<i32>>>()?;
+        for &id in &ids {
+            self.purge_task(id)?;
+        }
+        tx.commit()?;
+        Ok(ids.len())
+    }
+
+    /// Permanently delete tasks `ids`, wherever they are, as emptying the
+    /// trash does.
+    pub fn purge_tasks(&self, ids: &[i32]) -> SqliteResult<()> {
+        let tx = self.transaction()?;
+        for &id in ids {
+            self.purge_task(id)?;
+        }
+        tx.commit()
+    }
+
+    /// Every task outside the archive, trashed ones included, with the time
+    /// it was deleted; in ID order.
+    pub fn get_tasks_for_sync(&self) -> SqliteResult<Vec<(Task, Option<DateTime<Utc>>)>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {}, deleted_at FROM tasks ORDER BY id",
+            TASK_COLUMNS
+        ))?;
+        let rows = stmt.query_map([], |row| {
+            let deleted_at = row
+                .get::<_, Option<String>>(TASK_COLUMN_COUNT)?
+                .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
+                .map(|dt| dt.with_timezone(&Utc));
+            Ok((task_from_row(row)?, deleted_at))
+        })?;
+        rows.collect()
+    }
+
+    /// Write a task received from another database over task `id`, or as a
+    /// new task when `None`, keeping its timestamps and putting it in or
+    /// out of the trash as `deleted_at` says. Returns its ID.
+    pub fn save_synced_task(
+        &self,
+        id: Option<i32>,
+        task: &Task,
+        deleted_at: Option<DateTime<Utc>>,
+    ) -> SqliteResult<i32> {
+        let tx = self.transaction()?;
+        let id = match id {
+            Some(id) => {
+                let before = self.get_task_with_trashed(id)?;
+                self.update_task(id, task)?;
+                self.replace_tags(id, &task.tags)?;
+                self.record_edit(id, before.as_ref())?;
+                id
+            }
+            None => self.insert_task(None, task)?,
+        };
+        self.set_someday(id, task.someday)?;
+        self.conn.execute(
+            "UPDATE tasks SET updated_at = ?1, completed_at = ?2, deleted_at = ?3 WHERE id = ?4",
+            params![
+                task.updated_at.to_rfc3339(),
+                task.completed
+                    .then(|| task.completed_at.unwrap_or(task.updated_at).to_rfc3339()),
+                deleted_at.map(|at| at.to_rfc3339()),
+                id,
+            ],
+        )?;
+        tx.commit()?;
+        Ok(id)
+    }
+
+    /// IDs of completed tasks outside the trash, only those last changed
+    /// before `cutoff` when one is given.
+    pub fn completed_task_ids(&self, cutoff: Option<DateTime<Utc>>) -> SqliteResult<Vec<i32>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT id FROM tasks
+             WHERE completed = TRUE AND (?1 IS NULL OR updated_at < ?1) AND {}
+             ORDER BY id",
+            NOT_DELETED
+        ))?;
+        let ids = stmt.query_map([cutoff.map(|cutoff| cutoff.to_rfc3339())], |row| row.get(0))?;
+        ids.collect()
+    }
+
+    /// Permanently delete the completed tasks `completed_task_ids` lists,
+    /// in one transaction. Returns how many.
+    pub fn purge_completed(&self, cutoff: Option<DateTime<Utc>>) -> SqliteResult<usize> {
+        let tx = self.transaction()?;
+        let ids = self.completed_task_ids(cutoff)?;
+        for &id in &ids {
+            self.purge_task(id)?;
+        }
+        tx.commit()?;
+        Ok(ids.len())
+    }
+
+    /// Move tasks completed before `cutoff` out of `tasks` into
+    /// `archived_tasks`. Open subtasks move up to the archived task's parent.
+    /// History, notes and external links are kept, so `todo log` still works
+    /// and syncs don't re-import archived items. Returns how many were moved.
+    pub fn archive_completed(&self, cutoff: DateTime<Utc>) -> SqliteResult<usize> {
+        let tx = self.transaction()?;
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT id FROM tasks WHERE completed = TRUE AND updated_at < ? AND {}
+             ORDER BY id",
+            NOT_DELETED
+        ))?;
+        let ids = stmt
+            .query_map([cutoff.to_rfc3339()], |row| row.get(0))?
+            .collect::<SqliteResult<Vec<i32>>>()?;
+
+        let now = Utc::now().to_rfc3339();
+        for &id in &ids {
+            self.conn.execute(
+                concat!(
+                    "INSERT OR REPLACE INTO archived_tasks
+                    (id, title, description, due_date, priority, completed, created_at,
+                     updated_at, tags, project, recurrence, parent_id, archived_at, completed_at,
+                     uuid)
+                 SELECT id, title, description, due_date, ",
+                    effective_priority!(),
+                    ", completed, created_at,
+                     updated_at,
+                     (SELECT group_concat(tag, ',') FROM task_tags WHERE task_id = tasks.id),
+                     (SELECT name FROM projects WHERE id = tasks.project_id),
+                     recurrence, parent_id, ?2, completed_at, uuid
+                 FROM tasks WHERE id = ?1"
+                ),
+                params![id, now],
+            )?;
+            self.conn.execute(
+                "UPDATE tasks SET parent_id = (SELECT parent_id FROM tasks WHERE id = ?1)
+                 WHERE parent_id = ?1",
+                [id],
+            )?;
+            for table in [
+                "task_tags",
+                "task_snoozes",
+                "task_reminders",
+                "task_dependencies",
+                "someday_tasks",
+                "started_tasks",
+                "task_goals",
+                "operations",
+            ] {
+                self.conn
+                    .execute(&format!("DELETE FROM {} WHERE task_id = ?", table), [id])?;
+            }
+            self.conn
+                .execute("DELETE FROM task_dependencies WHERE depends_on = ?", [id])?;
+            self.record_history(id, "archived", None)?;
+            self.conn.execute("DELETE FROM tasks WHERE id = ?", [id])?;
+        }
+        tx.commit()?;
+        Ok(ids.len())
+    }
+
+    /// Archived tasks, most recently completed first.
+    pub fn get_archived_tasks(&self) -> SqliteResult<Vec<Task>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM archived_tasks ORDER BY updated_at DESC, id DESC",
+            ARCHIVED_COLUMNS
+        ))?;
+        let tasks = stmt.query_map([], task_from_row)?;
+        tasks.collect()
+    }
+
+    /// Replace a task's fields, tags and someday state, logging the previous
+    /// version so the edit can be undone.
+    pub fn edit_task(&self, id: i32, task: &Task) -> SqliteResult<()> {
+        self.edit_tasks(&[(id, task.clone())])
+    }
+
+    /// `edit_task` for several tasks in one transaction, undone together.
+    pub fn edit_tasks(&self, edits: &[(i32, Task)]) -> SqliteResult<()> {
+        self.apply_changes(edits, &[])
+    }
+
+    /// Save `edits` and move `deletes` to the trash in one transaction,
+    /// undone together, e.g. the outcome of a triage session.
+    pub fn apply_changes(&self, edits: &[(i32, Task)], deletes: &[i32]) -> SqliteResult<()> {
+        let tx = self.transaction()?;
+        let batch = self.next_batch()?;
+        for (id, task) in edits {
+            self.edit_in_batch(*id, task, batch)?;
+        }
+        for &id in deletes {
+            self.trash_task(id, batch)?;
+        }
+        tx.commit()
+    }
+
+    fn edit_in_batch(&self, id: i32, task: &Task, batch: i64) -> SqliteResult<()> {
+        let before = self.get_task_by_id(id)?;
+        if let Some(before) = &before {
+            self.log_operation(
+                &Operation::Update {
+                    before: Box::new(before.clone()),
+                },
+                batch,
+            )?;
+        }
+        self.update_task(id, task)?;
+        self.replace_tags(id, &task.tags)?;
+        self.set_someday(id, task.someday)?;
+        self.set_in_progress(id, task.in_progress && !task.completed)?;
+        self.record_edit(id, before.as_ref())
+    }
+
+    /// Number for the operations of the next command; `todo undo` reverses
+    /// one batch at a time.
+    fn next_batch(&self) -> SqliteResult<i64> {
+        self.conn.query_row(
+            "SELECT COALESCE(MAX(batch), 0) + 1 FROM operations",
+            [],
+            |row| row.get(0),
+        )
+    }
+
+    /// Append to the undo log, keeping the last `UNDO_HISTORY` batches.
+    /// Callers provide the transaction.
+    fn log_operation(&self, operation: &Operation, batch: i64) -> SqliteResult<()> {
+        let data = serde_json::to_string(operation)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        self.conn.execute(
+            "INSERT INTO operations (task_id, kind, data, created_at, batch)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                operation.task_id(),
+                operation.kind(),
+                data,
+                Utc::now().to_rfc3339(),
+                batch
+            ],
+        )?;
+        self.conn.execute(
+            "DELETE FROM operations WHERE batch <= ?",
+            [batch - UNDO_HISTORY],
+        )?;
+        Ok(())
+    }
+
+    /// Run `f` with changes attributed to `actor` in the task history, e.g.
+    /// `sync:obsidian` or `maintenance`.
+    pub fn with_actor<T>(&self, actor: &str, f: impl FnOnce() -> T) -> T {
+        let previous = self.actor.replace(actor.to_string());
+        let result = f();
+        *self.actor.borrow_mut() = previous;
+        result
+    }
+
+    fn record_history(&self, id: i32, action: &str, details: Option<&str>) -> SqliteResult<()> {
+        self.conn.execute(
+            "INSERT INTO task_history (task_id, actor, action, details, changed_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                id,
+                *self.actor.borrow(),
+                action,
+                details,
+                Utc::now().to_rfc3339()
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Record how a task differs from `before`, if it does.
+    fn record_edit(&self, id: i32, before: Option<&Task>) -> SqliteResult<()> {
+        let (Some(before), Some(after)) = (before, self.get_task_by_id(id)?) else {
+            return Ok(());
+        };
+        let changes = after.changes_from(before);
+        if changes.is_empty() {
+            return Ok(());
+        }
+        self.record_history(id, "updated", Some(&changes.join("; ")))
+    }
+
+    pub fn add_note(&self, id: i32, text: &str) -> SqliteResult<()> {
+        self.conn.execute(
+            "INSERT INTO task_notes (task_id, text, created_at) VALUES (?1, ?2, ?3)",
+            params![id, text, Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    /// Notes on a task, oldest first.
+    pub fn get_notes(&self, id: i32) -> SqliteResult<Vec<Note>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT task_id, text, created_at FROM task_notes
+             WHERE task_id = ? ORDER BY created_at, id",
+        )?;
+        let rows = stmt.query_map([id], |row| {
+            Ok(Note {
+                task_id: row.get(0)?,
+                text: row.get(1)?,
+                created_at: DateTime::parse_from_rfc3339(&row.get::<_, String>(2)?)
+                    .unwrap()
+                    .with_timezone(&Utc),
+            })
+        })?;
+        rows.collect()
+    }
+
+    pub fn add_attachment(&self, id: i32, target: &str) -> SqliteResult<()> {
+        self.conn.execute(
+            "INSERT INTO task_attachments (task_id, target, created_at) VALUES (?1, ?2, ?3)",
+            params![id, target, Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    /// Links and files attached to a task, in the order they were added.
+    pub fn get_attachments(&self, id: i32) -> SqliteResult<Vec<Attachment>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT task_id, target, created_at FROM task_attachments
+             WHERE task_id = ? ORDER BY id",
+        )?;
+        let rows = stmt.query_map([id], |row| {
+            Ok(Attachment {
+                task_id: row.get(0)?,
+                target: row.get(1)?,
+                created_at: DateTime::parse_from_rfc3339(&row.get::<_, String>(2)?)
+                    .unwrap()
+                    .with_timezone(&Utc),
+            })
+        })?;
+        rows.collect()
+    }
+
+    /// Remember the tag `name` as a context, so setting another context on
+    /// a task can tell it apart from the task's other tags.
+    pub fn add_context(&self, name: &str) -> SqliteResult<()> {
+        self.conn
+            .execute("INSERT OR IGNORE INTO contexts (name) VALUES (?)", [name])?;
+        Ok(())
+    }
+
+    /// Every tag that has been used as a context, by name.
+    pub fn get_contexts(&self) -> SqliteResult<Vec<String>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT name FROM contexts ORDER BY name")?;
+        let rows = stmt.query_map([], |row| row.get(0))?;
+        rows.collect()
+    }
+
+    /// Start timing task `id`. Callers make sure no other timer is running.
+    pub fn start_timer(&self, id: i32) -> SqliteResult<()> {
+        self.conn.execute(
+            "INSERT INTO time_entries (task_id, started_at) VALUES (?1, ?2)",
+            params![id, Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    /// Stop the running timer, returning its finished entry.
+    pub fn stop_timer(&self) -> SqliteResult<Option<TimeEntry>> {
+        let Some(mut entry) = self.get_running_timer()? else {
+            return Ok(None);
+        };
+        let now = Utc::now();
+        self.conn.execute(
+            "UPDATE time_entries SET stopped_at = ? WHERE stopped_at IS NULL",
+            [now.to_rfc3339()],
+        )?;
+        entry.stopped_at = Some(now);
+        Ok(Some(entry))
+    }
+
+    pub fn get_running_timer(&self) -> SqliteResult<Option<TimeEntry>> {
+        let mut entries = self.query_time_entries("stopped_at IS NULL", params![])?;
+        Ok(entries.pop())
+    }
+
+    /// A task's time entries, oldest first.
+    pub fn get_time_entries(&self, id: i32) -> SqliteResult<Vec<TimeEntry>> {
+        self.query_time_entries("task_id = ?", params![id])
+    }
+
+    /// Entries started at or after `since`, oldest first, with the title of
+    /// their task. Archived tasks keep their titles.
+    pub fn get_time_entries_since(
+        &self,
+        since: DateTime<Utc>,
+    ) -> SqliteResult<Vec<(TimeEntry, String)>> {
+        let entries = self.query_time_entries("started_at >= ?", params![since.to_rfc3339()])?;
+        let mut stmt = self.conn.prepare(
+            "SELECT title FROM tasks WHERE id = ?1
+             UNION ALL SELECT title FROM archived_tasks WHERE id = ?1",
+        )?;
+        entries
+            .into_iter()
+            .map(|entry| {
+                let title = stmt
+                    .query_map([entry.task_id], |row| row.get(0))?
+                    .next()
+                    .transpose()?
+                    .unwrap_or_else(|| "(deleted)".to_string());
+                Ok((entry, title))
+            })
+            .collect()
+    }
+
+    fn query_time_entries(
+        &self,
+        condition: &str,
+        params: &[&dyn rusqlite::ToSql],
+    ) -> SqliteResult<Vec<TimeEntry>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT task_id, started_at, stopped_at FROM time_entries
+             WHERE {} ORDER BY started_at, id",
+            condition
+        ))?;
+        let rows = stmt.query_map(params, |row| {
+            let parse = |s: String| {
+                DateTime::parse_from_rfc3339(&s)
+                    .unwrap()
+                    .with_timezone(&Utc)
+            };
+            Ok(TimeEntry {
+                task_id: row.get(0)?,
+                started_at: parse(row.get(1)?),
+                stopped_at: row.get::<_, Option<String>>(2)?.map(parse),
+            })
+        })?;
+        rows.collect()
+    }
+
+    /// A task's history, oldest first. Kept for tasks in the trash too.
+    pub fn get_task_history(&self, id: i32) -> SqliteResult<Vec<HistoryEntry>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT task_id, actor, action, details, changed_at FROM task_history
+             WHERE task_id = ? ORDER BY id",
+        )?;
+        let rows = stmt.query_map([id], history_from_row)?;
+        rows.collect()
+    }
+
+    /// The last `limit` history entries of every task, oldest first.
+    pub fn get_recent_history(&self, limit: usize) -> SqliteResult<Vec<HistoryEntry>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT task_id, actor, action, details, changed_at FROM
+                 (SELECT * FROM task_history ORDER BY id DESC LIMIT ?)
+             ORDER BY id",
+        )?;
+        let rows = stmt.query_map([limit as i64], history_from_row)?;
+        rows.collect()
+    }
+
+    /// The highest task ID in use, trash included, or 0 when there are no
+    /// tasks.
+    pub fn max_task_id(&self) -> SqliteResult<i32> {
+        self.conn
+            .query_row("SELECT COALESCE(MAX(id), 0) FROM tasks", [], |row| {
+                row.get(0)
+            })
+    }
+
+    /// ID of the newest task history entry, or 0 when there is none.
+    pub fn last_history_id(&self) -> SqliteResult<i64> {
+        self.conn
+            .query_row("SELECT COALESCE(MAX(id), 0) FROM task_history", [], |row| {
+                row.get(0)
+            })
+    }
+
+    /// History entries of every task written after entry `id`, oldest first.
+    pub fn get_history_since(&self, id: i64) -> SqliteResult<Vec<HistoryEntry>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT task_id, actor, action, details, changed_at FROM task_history
+             WHERE id > ? ORDER BY id",
+        )?;
+        let rows = stmt.query_map([id], history_from_row)?;
+        rows.collect()
+    }
+
+    /// Reverse the operations logged by the most recent command, newest
+    /// first, and drop them from the log. Returns them, or nothing when
+    /// there is nothing left to undo.
+    pub fn undo_last(&self) -> SqliteResult<Vec<Operation>> {
+        match self.last_batch()? {
+            Some(batch) => self.undo_batch(batch),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// The batch of the most recent command that can still be undone.
+    pub fn last_batch(&self) -> SqliteResult<Option<i64>> {
+        self.conn
+            .query_row("SELECT MAX(batch) FROM operations", [], |row| row.get(0))
+    }
+
+    /// Reverse one batch, which need not be the last. Returns nothing when
+    /// it was already undone.
+    pub fn undo_batch(&self, batch: i64) -> SqliteResult<Vec<Operation>> {
+        let tx = self.transaction()?;
+        let mut stmt = self
+            .conn
+            .prepare("SELECT data FROM operations WHERE batch = ? ORDER BY id DESC")?;
+        let operations = stmt
+            .query_map([batch], |row| row.get::<_, String>(0))?
+            .map(|data| {
+                serde_json::from_str::<Operation>(&data?).map_err(|e| {
+                    rusqlite::Error::FromSqlConversionFailure(0, Type::Text, Box::new(e))
+                })
+            })
+            .collect::<SqliteResult<Vec<_>>>()?;
+
+        for operation in &operations {
+            self.reverse(operation)?;
+        }
+        self.conn
+            .execute("DELETE FROM operations WHERE batch = ?", [batch])?;
+        tx.commit()?;
+        Ok(operations)
+    }
+
+    fn reverse(&self, operation: &Operation) -> SqliteResult<()> {
+        match operation {
+            Operation::Delete { task_id, subtasks } => self.undelete(*task_id, subtasks)?,
+            Operation::Update { before } => {
+                let id = operation.task_id();
+                let edited = self.get_task_by_id(id)?;
+                self.update_task(id, before)?;
+                self.replace_tags(id, &before.tags)?;
+                self.set_someday(id, before.someday)?;
+                self.set_in_progress(id, before.in_progress && !before.completed)?;
+                self.record_edit(id, edited.as_ref())?;
+            }
+            Operation::Complete { task_id, next_id } => {
+                self.conn.execute(
+                    "UPDATE tasks SET completed = FALSE, completed_at = NULL, updated_at = ?1
+                     WHERE id = ?2",
+                    params![Utc::now().to_rfc3339(), task_id],
+                )?;
+                self.record_history(*task_id, "reopened", None)?;
+                if let Some(next_id) = next_id {
+                    self.purge_task(*next_id)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Full-text search over titles, descriptions and notes, best matches
+    /// first. Every word of `query` must match, as a prefix, in the title
+    /// and description together or in a single note, and the task must pass
+    /// `filter`. Tasks matching on their own text come before those matching
+    /// only in a note, and each task is listed once.
+    pub fn search_tasks(&self, query: &str, filter: &TaskFilter) -> SqliteResult<Vec<SearchHit>> {
+        let Some(expression) = fts_expression(query) else {
+            return Ok(Vec::new());
+        };
+        let mut hits = self.search_task_text(&expression, filter)?;
+        for hit in self.search_notes(&expression, filter)? {
+            if !hits.iter().any(|seen| seen.task.id == hit.task.id) {
+                hits.push(hit);
+            }
+        }
+        Ok(hits)
+    }
+
+    /// The title and description part of `search_tasks`.
+    fn search_task_text(
+        &self,
+        expression: &str,
+        filter: &TaskFilter,
+    ) -> SqliteResult<Vec<SearchHit>> {
+        let (conditions, values) = filter.to_sql();
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {}, hits.title_hl, hits.excerpt FROM tasks
+             JOIN (
+                 SELECT rowid AS hit_id, rank,
+                        highlight(tasks_fts, 0, ?2, ?3) AS title_hl,
+                        snippet(tasks_fts, 1, ?2, ?3, '…', 12) AS excerpt
+                 FROM tasks_fts WHERE tasks_fts MATCH ?1
+             ) hits ON hits.hit_id = tasks.id
+             WHERE {}
+             ORDER BY hits.rank",
+            TASK_COLUMNS, conditions
+        ))?;
+        let mut params = vec![
+            Value::from(expression.to_string()),
+            Value::from(MATCH_START.to_string()),
+            Value::from(MATCH_END.to_string()),
+        ];
+        params.extend(values);
+        let hits = stmt.query_map(rusqlite::params_from_iter(params), |row| {
+            let excerpt: Option<String> = row.get(TASK_COLUMN_COUNT + 1)?;
+            let excerpt = excerpt.filter(|text| text.contains(MATCH_START));
+            Ok(SearchHit {
+                task: task_from_row(row)?,
+                title: row.get(TASK_COLUMN_COUNT)?,
+                field: match excerpt {
+                    Some(_) => SearchField::Description,
+                    None => SearchField::Title,
+                },
+                excerpt,
+            })
+        })?;
+        hits.collect()
+    }
+
+    /// The note part of `search_tasks`: tasks with a note matching
+    /// `expression`, each with its best note.
+    fn search_notes(&self, expression: &str, filter: &TaskFilter) -> SqliteResult<Vec<SearchHit>> {
+        let (conditions, values) = filter.to_sql();
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {}, hits.excerpt FROM tasks
+             JOIN (
+                 SELECT (SELECT task_id FROM task_notes WHERE id = notes_fts.rowid) AS hit_id,
+                        rank,
+                        snippet(notes_fts, 0, ?2, ?3, '…', 12) AS excerpt
+                 FROM notes_fts WHERE notes_fts MATCH ?1
+             ) hits ON hits.hit_id = tasks.id
+             WHERE {}
+             ORDER BY hits.rank",
+            TASK_COLUMNS, conditions
+        ))?;
+        let mut params = vec![
+            Value::from(expression.to_string()),
+            Value::from(MATCH_START.to_string()),
+            Value::from(MATCH_END.to_string()),
+        ];
+        params.extend(values);
+        let rows = stmt.query_map(rusqlite::params_from_iter(params), |row| {
+            let task = task_from_row(row)?;
+            Ok(SearchHit {
+                title: task.title.clone(),
+                task,
+                field: SearchField::Note,
+                excerpt: row.get(TASK_COLUMN_COUNT)?,
+            })
+        })?;
+        let mut hits: Vec<SearchHit> = Vec::new();
+        for hit in rows {
+            let hit = hit?;
+            if !hits.iter().any(|seen| seen.task.id == hit.task.id) {
+                hits.push(hit);
+            }
+        }
+        Ok(hits)
+    }
+
+    /// The value of `expr` for every live task, as text; `None` for NULL.
+    /// `expr` is pasted into the query, so it must have passed
+    /// `sql_expr::validate`.
+    pub fn expression_values(
+        &self,
+        expr: &str,
+    ) -> SqliteResult<std::collections::HashMap<i32, Option<String>>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT id, CAST(({}) AS TEXT) FROM (SELECT {} FROM tasks WHERE {})",
+            expr, TASK_COLUMNS, NOT_DELETED
+        ))?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect()
+    }
+
+    /// Every task nested below `id`, depth first. Siblings keep the usual
+    /// list order (priority, then age).
+    pub fn get_subtasks(&self, id: i32) -> SqliteResult<Vec<Task>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "WITH RECURSIVE subtree(id, path) AS (
+                 SELECT id, printf('%03d.%s.%010d', 2 - {priority}, created_at, id)
+                 FROM tasks WHERE parent_id = ?1 AND deleted_at IS NULL
+                 UNION ALL
+                 SELECT tasks.id,
+                        s.path || '/' || printf('%03d.%s.%010d', 2 - {priority}, tasks.created_at, tasks.id)
+                 FROM tasks JOIN subtree s ON tasks.parent_id = s.id
+                 WHERE tasks.deleted_at IS NULL
+             )
+             SELECT {columns} FROM tasks JOIN subtree USING (id)
+             ORDER BY subtree.path",
+            priority = EFFECTIVE_PRIORITY,
+            columns = TASK_COLUMNS
+        ))?;
+        let rows = stmt.query_map([id], task_from_row)?;
+        rows.collect()
+    }
+
+    /// Whether `ancestor` is `id` itself or one of its parents, grandparents
+    /// and so on. Used to keep the hierarchy free of cycles.
+    pub fn is_ancestor(&self, ancestor: i32, id: i32) -> SqliteResult<bool> {
+        self.conn.query_row(
+            "WITH RECURSIVE chain(id) AS (
+                 SELECT ?2
+                 UNION
+                 SELECT tasks.parent_id FROM tasks JOIN chain ON tasks.id = chain.id
+                 WHERE tasks.parent_id IS NOT NULL
+             )
+             SELECT EXISTS (SELECT 1 FROM chain WHERE id = ?1)",
+            params![ancestor, id],
+            |row| row.get(0),
+        )
+    }
+
+    /// Attach a tag to a task. Tagging is not considered an edit, so
+    /// `updated_at` is left alone.
+    pub fn add_tag(&self, id: i32, tag: &str) -> SqliteResult<()> {
+        let tx = self.transaction()?;
+        if self.insert_tag(id, tag)? {
+            self.record_history(id, "tagged", Some(&format!("#{}", tag)))?;
+        }
+        tx.commit()
+    }
+
+    /// Returns whether the tag was new.
+    fn insert_tag(&self, id: i32, tag: &str) -> SqliteResult<bool> {
+        let inserted = self.conn.execute(
+            "INSERT OR IGNORE INTO task_tags (task_id, tag) VALUES (?1, ?2)",
+            params![id, tag],
+        )?;
+        Ok(inserted > 0)
+    }
+
+    /// Replace all tags on a task in one transaction.
+    pub fn set_tags(&self, id: i32, tags: &[String]) -> SqliteResult<()> {
+        let tx = self.transaction()?;
+        self.replace_tags(id, tags)?;
+        tx.commit()
+    }
+
+    /// `set_tags` for callers that provide the transaction.
+    fn replace_tags(&self, id: i32, tags: &[String]) -> SqliteResult<()> {
+        self.conn
+            .execute("DELETE FROM task_tags WHERE task_id = ?", [id])?;
+        for tag in tags {
+            self.insert_tag(id, tag)?;
+        }
+        Ok(())
+    }
+
+    /// Every tag in use, by name.
+    pub fn tag_names(&self) -> SqliteResult<Vec<String>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT DISTINCT tag FROM task_tags ORDER BY tag")?;
+        let rows = stmt.query_map([], |row| row.get(0))?;
+        rows.collect()
+    }
+
+    /// `(tag, task_id)` pairs for every tag on a pending task.
+    pub fn get_pending_tag_pairs(&self) -> SqliteResult<Vec<(String, i32)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT tag, task_id FROM task_tags
+             JOIN tasks ON tasks.id = task_tags.task_id
+             WHERE tasks.completed = FALSE AND tasks.deleted_at IS NULL
+             ORDER BY tag",
+        )?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect()
+    }
+
+    /// Remember that a task mirrors an item in an external source.
+    pub fn add_task_link(&self, id: i32, source: &str, external_id: &str) -> SqliteResult<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO task_links (task_id, source, external_id) VALUES (?1, ?2, ?3)",
+            params![id, source, external_id],
+        )?;
+        Ok(())
+    }
+
+    pub fn remove_task_link(&self, source: &str, external_id: &str) -> SqliteResult<()> {
+        self.conn.execute(
+            "DELETE FROM task_links WHERE source = ?1 AND external_id = ?2",
+            params![source, external_id],
+        )?;
+        Ok(())
+    }
+
+    /// All `(task_id, external_id)` links for a source.
+    pub fn get_task_links(&self, source: &str) -> SqliteResult<Vec<(i32, String)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT task_id, external_id FROM task_links WHERE source = ? ORDER BY task_id",
+        )?;
+        let rows = stmt.query_map([source], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect()
+    }
+
+    pub fn find_linked_task(&self, source: &str, external_id: &str) -> SqliteResult<Option<i32>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT task_id FROM task_links WHERE source = ?1 AND external_id = ?2")?;
+        let mut rows = stmt.query_map(params![source, external_id], |row| row.get(0))?;
+        rows.next().transpose()
+    }
+
+    pub fn start_focus_session(
+        &self,
+        task_id: Option<i32>,
+        ends_at: DateTime<Utc>,
+    ) -> SqliteResult<i32> {
+        self.conn.execute(
+            "INSERT INTO focus_sessions (task_id, started_at, ends_at) VALUES (?1, ?2, ?3)",
+            params![task_id, Utc::now().to_rfc3339(), ends_at.to_rfc3339()],
+        )?;
+        Ok(self.conn.last_insert_rowid() as i32)
+    }
+
+    /// The running focus session, if any. Sessions past their end time are
+    /// not active even if they were never stopped explicitly.
+    pub fn get_active_focus_session(&self) -> SqliteResult<Option<FocusSession>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, task_id, started_at, ends_at, ended_at FROM focus_sessions
+             WHERE ended_at IS NULL AND ends_at > ?
+             ORDER BY started_at DESC LIMIT 1",
+        )?;
+        let mut rows = stmt.query_map([Utc::now().to_rfc3339()], |row| {
+            let parse = |s: String| {
+                DateTime::parse_from_rfc3339(&s)
+                    .unwrap()
+                    .with_timezone(&Utc)
+            };
+            Ok(FocusSession {
+                id: row.get(0)?,
+                task_id: row.get(1)?,
+                started_at: parse(row.get(2)?),
+                ends_at: parse(row.get(3)?),
+                ended_at: row.get::<_, Option<String>>(4)?.map(parse),
+            })
+        })?;
+        rows.next().transpose()
+    }
+
+    pub fn end_focus_session(&self, id: i32) -> SqliteResult<()> {
+        self.conn.execute(
+            "UPDATE focus_sessions SET ended_at = ?1 WHERE id = ?2",
+            params![Utc::now().to_rfc3339(), id],
+        )?;
+        Ok(())
+    }
+
+    /// Id of the named project, creating it on first use.
+    fn resolve_project(&self, name: Option<&str>) -> SqliteResult<Option<i32>> {
+        let Some(name) = name else {
+            return Ok(None);
+        };
+        self.conn.execute(
+            "INSERT OR IGNORE INTO projects (name, created_at) VALUES (?1, ?2)",
+            params![name, Utc::now().to_rfc3339()],
+        )?;
+        self.conn
+            .query_row("SELECT id FROM projects WHERE name = ?", [name], |row| {
+                row.get(0)
+            })
+            .map(Some)
+    }
+
+    /// `(name, pending, total)` for every project, by name.
+    pub fn get_projects(&self) -> SqliteResult<Vec<(String, i64, i64)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT projects.name,
+                    COUNT(tasks.id) FILTER (WHERE tasks.completed = FALSE),
+                    COUNT(tasks.id)
+             FROM projects
+             LEFT JOIN tasks ON tasks.project_id = projects.id AND tasks.deleted_at IS NULL
+             GROUP BY projects.id
+             ORDER BY projects.name",
+        )?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+        rows.collect()
+    }
+
+    /// Default priorities of the projects that have one, by name.
+    pub fn project_priorities(&self) -> SqliteResult<std::collections::HashMap<String, i32>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT name, default_priority FROM projects WHERE default_priority IS NOT NULL",
+        )?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect()
+    }
+
+    /// Set or clear the priority inherited by the project's tasks that have
+    /// none of their own.
+    pub fn set_project_priority(&self, name: &str, priority: Option<i32>) -> SqliteResult<()> {
+        self.conn.execute(
+            "UPDATE projects SET default_priority = ?1 WHERE name = ?2",
+            params![priority, name],
+        )?;
+        Ok(())
+    }
+
+    pub fn project_exists(&self, name: &str) -> SqliteResult<bool> {
+        let count: i32 = self.conn.query_row(
+            "SELECT COUNT(*) FROM projects WHERE name = ?",
+            [name],
+            |row| row.get(0),
+        )?;
+        Ok(count > 0)
+    }
+
+    pub fn rename_project(&self, old: &str, new: &str) -> SqliteResult<()> {
+        let tx = self.transaction()?;
+        let ids = self.project_task_ids(old)?;
+        self.conn.execute(
+            "UPDATE projects SET name = ?1 WHERE name = ?2",
+            params![new, old],
+        )?;
+        self.rehash_tasks(&ids)?;
+        tx.commit()
+    }
+
+    /// Delete a project. Its tasks are kept and left without a project.
+    pub fn delete_project(&self, name: &str) -> SqliteResult<()> {
+        let tx = self.transaction()?;
+        let ids = self.project_task_ids(name)?;
+        tx.execute(
+            "UPDATE tasks SET project_id = NULL
+             WHERE project_id = (SELECT id FROM projects WHERE name = ?)",
+            [name],
+        )?;
+        tx.execute("DELETE FROM projects WHERE name = ?", [name])?;
+        self.rehash_tasks(&ids)?;
+        tx.commit()
+    }
+
+    fn project_task_ids(&self, name: &str) -> SqliteResult<Vec<i32>> {
+        self.column_ids(
+            "SELECT id FROM tasks WHERE project_id = (SELECT id FROM projects WHERE name = ?)",
+            [name],
+        )
+    }
+
+    fn column_ids(&self, query: &str, params: impl rusqlite::Params) -> SqliteResult<Vec<i32>> {
+        let mut stmt = self.conn.prepare(query)?;
+        let ids = stmt.query_map(params, |row| row.get(0))?;
+        ids.collect()
+    }
+
+    /// Recompute the stored `Task::content_hash` of tasks changed without
+    /// going through `insert_task` or `update_task`.
+    fn rehash_tasks(&self, ids: &[i32]) -> SqliteResult<()> {
+        let mut select = self
+            .conn
+            .prepare(&format!("SELECT {} FROM tasks WHERE id = ?", TASK_COLUMNS))?;
+        for &id in ids {
+            let task = select.query_row([id], task_from_row)?;
+            self.conn.execute(
+                "UPDATE tasks SET content_hash = ?1 WHERE id = ?2",
+                params![task.content_hash(), id],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// A pending task outside the trash with the same `Task::content_hash`
+    /// as `task`, other than `task` itself.
+    pub fn find_pending_duplicate(&self, task: &Task) -> SqliteResult<Option<Task>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM tasks
+             WHERE content_hash = ?1 AND completed = FALSE AND id IS NOT ?2 AND {}
+             ORDER BY id LIMIT 1",
+            TASK_COLUMNS, NOT_DELETED
+        ))?;
+        let mut rows = stmt.query_map(params![task.content_hash(), task.id], task_from_row)?;
+        rows.next().transpose()
+    }
+
+    pub fn add_goal(&self, title: &str, target_date: Option<DateTime<Utc>>) -> SqliteResult<i32> {
+        self.conn.execute(
+            "INSERT INTO goals (title, target_date, created_at) VALUES (?1, ?2, ?3)",
+            params![
+                title,
+                target_date.map(|d| d.to_rfc3339()),
+                Utc::now().to_rfc3339()
+            ],
+        )?;
+        Ok(self.conn.last_insert_rowid() as i32)
+    }
+
+    /// All goals, soonest target date first and undated ones last.
+    pub fn get_goals(&self) -> SqliteResult<Vec<Goal>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, title, target_date, created_at FROM goals
+             ORDER BY target_date IS NULL, target_date, id",
+        )?;
+        let rows = stmt.query_map([], goal_from_row)?;
+        rows.collect()
+    }
+
+    pub fn get_goal(&self, id: i32) -> SqliteResult<Option<Goal>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, title, target_date, created_at FROM goals WHERE id = ?")?;
+        let mut rows = stmt.query_map([id], goal_from_row)?;
+        rows.next().transpose()
+    }
+
+    /// Delete a goal. Its tasks are kept and simply unlinked.
+    pub fn delete_goal(&self, id: i32) -> SqliteResult<()> {
+        let tx = self.transaction()?;
+        tx.execute("DELETE FROM task_goals WHERE goal_id = ?", [id])?;
+        tx.execute("DELETE FROM goals WHERE id = ?", [id])?;
+        tx.commit()
+    }
+
+    /// Pending tasks due before `cutoff`, outside someday, that haven't
+    /// been reminded of for their current due date, soonest first.
+    pub fn get_unreminded_due(&self, cutoff: DateTime<Utc>) -> SqliteResult<Vec<Task>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM tasks
+             WHERE {} AND NOT {} AND NOT completed
+               AND due_date IS NOT NULL AND due_date <= ?1
+               AND NOT EXISTS (SELECT 1 FROM task_reminders
+                               WHERE task_id = tasks.id AND due_date = tasks.due_date)
+             ORDER BY due_date, id",
+            TASK_COLUMNS, NOT_DELETED, IN_SOMEDAY
+        ))?;
+        let tasks = stmt.query_map([cutoff.to_rfc3339()], task_from_row)?;
+        tasks.collect()
+    }
+
+    /// Note that `task` was reminded of for its current due date.
+    pub fn mark_reminded(&self, task: &Task) -> SqliteResult<()> {
+        self.conn.execute(
+            "INSERT OR IGNORE INTO task_reminders (task_id, due_date, sent_at)
+             SELECT id, due_date, ?2 FROM tasks WHERE id = ?1 AND due_date IS NOT NULL",
+            params![task.id, Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    /// Save `template`, replacing any template of the same name.
+    pub fn save_template(&self, template: &Template) -> SqliteResult<()> {
+        self.conn.execute(
+            &format!(
+                "INSERT OR REPLACE INTO templates ({}, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                TEMPLATE_COLUMNS
+            ),
+            params![
+                template.name,
+                template.title,
+                template.description,
+                template.priority,
+                serde_json::to_string(&template.tags).unwrap_or_else(|_| "[]".to_string()),
+                template.project,
+                template.due,
+                Utc::now().to_rfc3339()
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_template(&self, name: &str) -> SqliteResult<Option<Template>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM templates WHERE name = ?",
+            TEMPLATE_COLUMNS
+        ))?;
+        let mut rows = stmt.query_map([name], template_from_row)?;
+        rows.next().transpose()
+    }
+
+    /// All templates, by name.
+    pub fn get_templates(&self) -> SqliteResult<Vec<Template>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM templates ORDER BY name",
+            TEMPLATE_COLUMNS
+        ))?;
+        let rows = stmt.query_map([], template_from_row)?;
+        rows.collect()
+    }
+
+    /// Delete a template; false when there was none of that name.
+    pub fn delete_template(&self, name: &str) -> SqliteResult<bool> {
+        Ok(self
+            .conn
+            .execute("DELETE FROM templates WHERE name = ?", [name])?
+            > 0)
+    }
+
+    /// Link a task to a goal, replacing any previous goal of that task.
+    pub fn link_task_to_goal(&self, task_id: i32, goal_id: i32) -> SqliteResult<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO task_goals (task_id, goal_id) VALUES (?1, ?2)",
+            params![task_id, goal_id],
+        )?;
+        Ok(())
+    }
+
+    pub fn unlink_task_from_goal(&self, task_id: i32) -> SqliteResult<()> {
+        self.conn
+            .execute("DELETE FROM task_goals WHERE task_id = ?", [task_id])?;
+        Ok(())
+    }
+
+    /// Every task linked to a goal, completed ones included.
+    pub fn get_goal_tasks(&self, goal_id: i32) -> SqliteResult<Vec<Task>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM tasks
+             WHERE id IN (SELECT task_id FROM task_goals WHERE goal_id = ?) AND {}
+             ORDER BY completed, priority DESC, created_at",
+            TASK_COLUMNS, NOT_DELETED
+        ))?;
+        let task_iter = stmt.query_map([goal_id], task_from_row)?;
+        task_iter.collect()
+    }
+
+    pub fn record_command_usage(&self, command: &str, duration: Duration) -> SqliteResult<()> {
+        self.conn.execute(
+            "INSERT INTO command_usage (command, used_at, duration_ms) VALUES (?1, ?2, ?3)",
+            params![
+                command,
+                Utc::now().to_rfc3339(),
+                duration.num_milliseconds()
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Per-command usage, most used first.
+    pub fn get_usage_stats(&self) -> SqliteResult<Vec<UsageStat>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT command, COUNT(*), AVG(duration_ms), MAX(duration_ms), MAX(used_at)
+             FROM command_usage
+             GROUP BY command
+             ORDER BY COUNT(*) DESC, command",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(UsageStat {
+                command: row.get(0)?,
+                count: row.get(1)?,
+                avg_ms: row.get(2)?,
+                max_ms: row.get(3)?,
+                last_used: DateTime::parse_from_rfc3339(&row.get::<_, String>(4)?)
+                    .unwrap()
+                    .with_timezone(&Utc),
+            })
+        })?;
+        rows.collect()
+    }
+
+    pub fn clear_command_usage(&self) -> SqliteResult<()> {
+        self.conn.execute("DELETE FROM command_usage", [])?;
+        Ok(())
+    }
+
+    pub fn get_meta(&self, key: &str) -> SqliteResult<Option<String>> {
+        let mut stmt = self.conn.prepare("SELECT value FROM meta WHERE key = ?")?;
+        let mut rows = stmt.query_map([key], |row| row.get(0))?;
+        rows.next().transpose()
+    }
+
+    pub fn set_meta(&self, key: &str, value: &str) -> SqliteResult<()> {
+        self.conn.execute(
+            "INSERT INTO meta (key, value) VALUES (?1, ?2)
+             ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+            params![key, value],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_meta(&self, key: &str) -> SqliteResult<()> {
+        self.conn.execute("DELETE FROM meta WHERE key = ?", [key])?;
+        Ok(())
+    }
+
+    /// Read a meta value stored as an RFC 3339 timestamp. Unparseable values
+    /// are treated as missing.
+    pub fn get_meta_datetime(&self, key: &str) -> SqliteResult<Option<DateTime<Utc>>> {
+        Ok(self
+            .get_meta(key)?
+            .and_then(|value| DateTime::parse_from_rfc3339(&value).ok())
+            .map(|dt| dt.with_timezone(&Utc)))
+    }
+
+    pub fn set_meta_datetime(&self, key: &str, value: DateTime<Utc>) -> SqliteResult<()> {
+        self.set_meta(key, &value.to_rfc3339())
+    }
+
+    /// Pending tasks that have not been updated since `cutoff`, oldest first.
+    /// Someday tasks are expected to sit untouched and are left out.
+    pub fn get_stale_tasks(&self, cutoff: DateTime<Utc>) -> SqliteResult<Vec<Task>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM tasks
+             WHERE completed = FALSE AND updated_at < ? AND NOT {} AND {}
+             ORDER BY updated_at ASC",
+            TASK_COLUMNS, IN_SOMEDAY, NOT_DELETED
+        ))?;
+        let task_iter = stmt.query_map([cutoff.to_rfc3339()], task_from_row)?;
+        task_iter.collect()
+    }
+
+    /// Open tasks bucketed by age as of `now`, with every bucket present,
+    /// and the `oldest` tasks that have been open the longest.
+    pub fn get_aging_report(&self, now: DateTime<Utc>, oldest: usize) -> SqliteResult<AgingReport> {
+        let open = format!(
+            "completed = FALSE AND NOT {} AND {}",
+            IN_SOMEDAY, NOT_DELETED
+        );
+        let cases: String = AgeBucket::ALL
+            .iter()
+            .enumerate()
+            .filter_map(|(index, bucket)| {
+                bucket
+                    .max_days()
+                    .map(|days| format!("WHEN age < {} THEN {} ", days, index))
+            })
+            .collect();
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT CASE {}ELSE {} END AS bucket, COUNT(*)
+             FROM (SELECT julianday(?1) - julianday(created_at) AS age FROM tasks WHERE {})
+             GROUP BY bucket",
+            cases,
+            AgeBucket::ALL.len() - 1,
+            open
+        ))?;
+        let mut buckets: Vec<(AgeBucket, usize)> =
+            AgeBucket::ALL.iter().map(|bucket| (*bucket, 0)).collect();
+        let rows = stmt.query_map([now.to_rfc3339()], |row| {
+            Ok((row.get::<_, usize>(0)?, row.get::<_, usize>(1)?))
+        })?;
+        for row in rows {
+            let (index, count) = row?;
+            buckets[index].1 = count;
+        }
+
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM tasks WHERE {} ORDER BY julianday(created_at), id LIMIT ?",
+            TASK_COLUMNS, open
+        ))?;
+        let oldest = stmt
+            .query_map([oldest as i64], task_from_row)?
+            .collect::<SqliteResult<Vec<_>>>()?;
+        Ok(AgingReport { buckets, oldest })
+    }
+
+    /// Tasks completed at or after `since`, in the order they were completed.
+    pub fn get_completed_since(&self, since: DateTime<Utc>) -> SqliteResult<Vec<Task>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM tasks
+             WHERE completed = TRUE AND julianday(completed_at) >= julianday(?) AND {}
+             ORDER BY julianday(completed_at), id",
+            TASK_COLUMNS, NOT_DELETED
+        ))?;
+        let tasks = stmt.query_map([since.to_rfc3339()], task_from_row)?;
+        tasks.collect()
+    }
+
+    /// Push a task's due date to `until` and count the snooze.
+    pub fn snooze_task(&self, id: i32, until: DateTime<Utc>) -> SqliteResult<()> {
+        let now = Utc::now().to_rfc3339();
+        let tx = self.transaction()?;
+        tx.execute(
+            "UPDATE tasks SET due_date = ?1, updated_at = ?2 WHERE id = ?3",
+            params![until.to_rfc3339(), now, id],
+        )?;
+        self.rehash_tasks(&[id])?;
+        tx.execute(
+            "INSERT INTO task_snoozes (task_id, count, last_snoozed_at) VALUES (?1, 1, ?2)
+             ON CONFLICT(task_id) DO UPDATE SET
+                count = count + 1, last_snoozed_at = excluded.last_snoozed_at",
+            params![id, now],
+        )?;
+        self.record_history(
+            id,
+            "snoozed",
+            Some(&format!("until {}", until.format("%Y-%m-%d"))),
+        )?;
+        tx.commit()
+    }
+
+    /// Snoozes since the counter was last reset.
+    pub fn get_snooze_count(&self, id: i32) -> SqliteResult<i32> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT count FROM task_snoozes WHERE task_id = ?")?;
+        let mut rows = stmt.query_map([id], |row| row.get(0))?;
+        Ok(rows.next().transpose()?.unwrap_or(0))
+    }
+
+    pub fn reset_snooze_count(&self, id: i32) -> SqliteResult<()> {
+        self.conn
+            .execute("UPDATE task_snoozes SET count = 0 WHERE task_id = ?", [id])?;
+        Ok(())
+    }
+
+    /// Move a task into or out of the someday/maybe bucket.
+    pub fn set_someday(&self, id: i32, someday: bool) -> SqliteResult<()> {
+        let tx = self.transaction()?;
+        if someday {
+            let now = Utc::now().to_rfc3339();
+            let moved = self.conn.execute(
+                "INSERT OR IGNORE INTO someday_tasks (task_id, since, reviewed_at)
+                 VALUES (?1, ?2, ?2)",
+                params![id, now],
+            )?;
+            if moved > 0 {
+                self.record_history(id, "moved to someday", None)?;
+            }
+        } else {
+            let moved = self
+                .conn
+                .execute("DELETE FROM someday_tasks WHERE task_id = ?", [id])?;
+            if moved > 0 {
+                self.record_history(id, "promoted from someday", None)?;
+            }
+        }
+        tx.commit()
+    }
+
+    /// Set or clear how many minutes a task is expected to take.
+    pub fn set_estimate(&self, id: i32, minutes: Option<i64>) -> SqliteResult<()> {
+        let tx = self.transaction()?;
+        self.conn.execute(
+            "UPDATE tasks SET estimate_minutes = ?1 WHERE id = ?2",
+            params![minutes, id],
+        )?;
+        let details = minutes.map(|minutes| format!("{} minutes", minutes));
+        self.record_history(id, "estimated", details.as_deref())?;
+        tx.commit()
+    }
+
+    /// Estimates in minutes of the tasks that have one, by task ID.
+    pub fn estimates(&self) -> SqliteResult<std::collections::HashMap<i32, i64>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT id, estimate_minutes FROM tasks
+             WHERE estimate_minutes IS NOT NULL AND {}",
+            NOT_DELETED
+        ))?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect()
+    }
+
+    /// Mark a task as being worked on, or move it back to the to-do column.
+    pub fn set_in_progress(&self, id: i32, in_progress: bool) -> SqliteResult<()> {
+        let tx = self.transaction()?;
+        let (changed, action) = if in_progress {
+            let changed = self.conn.execute(
+                "INSERT OR IGNORE INTO started_tasks (task_id, started_at) VALUES (?1, ?2)",
+                params![id, Utc::now().to_rfc3339()],
+            )?;
+            (changed, "started")
+        } else {
+            let changed = self
+                .conn
+                .execute("DELETE FROM started_tasks WHERE task_id = ?", [id])?;
+            (changed, "stopped")
+        };
+        if changed > 0 {
+            self.record_history(id, action, None)?;
+        }
+        tx.commit()
+    }
+
+    /// Pending someday tasks not reviewed since `cutoff`, least recently
+    /// reviewed first.
+    pub fn get_someday_due_for_review(&self, cutoff: DateTime<Utc>) -> SqliteResult<Vec<Task>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM tasks
+             JOIN someday_tasks ON someday_tasks.task_id = tasks.id
+             WHERE completed = FALSE AND someday_tasks.reviewed_at < ? AND {}
+             ORDER BY someday_tasks.reviewed_at ASC",
+            TASK_COLUMNS, NOT_DELETED
+        ))?;
+        let task_iter = stmt.query_map([cutoff.to_rfc3339()], task_from_row)?;
+        task_iter.collect()
+    }
+
+    pub fn mark_someday_reviewed(&self, id: i32) -> SqliteResult<()> {
+        self.conn.execute(
+            "UPDATE someday_tasks SET reviewed_at = ?1 WHERE task_id = ?2",
+            params![Utc::now().to_rfc3339(), id],
+        )?;
+        Ok(())
+    }
+
+    /// Change only the priority. Like tagging, automatic re-prioritisation
+    /// is not an edit and leaves `updated_at` alone.
+    pub fn set_priority(&self, id: i32, priority: i32) -> SqliteResult<()> {
+        let tx = self.transaction()?;
+        let before = self.get_task_by_id(id)?;
+        self.conn.execute(
+            "UPDATE tasks SET priority = ?1 WHERE id = ?2",
+            params![priority, id],
+        )?;
+        self.record_edit(id, before.as_ref())?;
+        tx.commit()
+    }
+
+    /// Mark a task as completed. Completing a pending recurring task also
+    /// creates its next occurrence, whose ID is returned. Completing a
+    /// pending task is logged for `todo undo`.
+    pub fn complete_task(&self, id: i32) -> SqliteResult<Option<i32>> {
+        Ok(self.complete_tasks(&[id])?.remove(0))
+    }
+
+    /// `complete_task` for several tasks in one transaction, undone
+    /// together. Returns the next occurrence of each, in order.
+    pub fn complete_tasks(&self, ids: &[i32]) -> SqliteResult<Vec<Option<i32>>> {
+        let tx = self.transaction()?;
+        let batch = self.next_batch()?;
+        let next_ids = ids
+            .iter()
+            .map(|&id| self.complete_and_repeat(id, batch))
+            .collect::<SqliteResult<Vec<_>>>()?;
+        tx.commit()?;
+        Ok(next_ids)
+    }
+
+    fn complete_and_repeat(&self, id: i32, batch: i64) -> SqliteResult<Option<i32>> {
+        let task = self.get_task_by_id(id)?;
+        let now = Utc::now();
+        self.conn.execute(
+            "UPDATE tasks SET completed = TRUE, updated_at = ?1,
+                 completed_at = COALESCE(completed_at, ?1)
+             WHERE id = ?2",
+            params![now.to_rfc3339(), id],
+        )?;
+        self.conn
+            .execute("DELETE FROM started_tasks WHERE task_id = ?", [id])?;
+        self.conn.execute(
+            "UPDATE time_entries SET stopped_at = ?1 WHERE task_id = ?2 AND stopped_at IS NULL",
+            params![now.to_rfc3339(), id],
+        )?;
+
+        let Some(task) = task.filter(|task| !task.completed) else {
+            return Ok(None);
+        };
+        self.record_history(id, "completed", None)?;
+        let next_id = self.spawn_next_occurrence(task, now)?;
+        self.log_operation(
+            &Operation::Complete {
+                task_id: id,
+                next_id,
+            },
+            batch,
+        )?;
+        Ok(next_id)
+    }
+
+    /// Create the next occurrence of a recurring task; callers provide the
+    /// transaction.
+    fn spawn_next_occurrence(&self, task: Task, now: DateTime<Utc>) -> SqliteResult<Option<i32>> {
+        let Some(rule) = task
+            .recurrence
+            .as_deref()
+            .and_then(|rule| Recurrence::parse(rule).ok())
+        else {
+            return Ok(None);
+        };
+
+        let mut next = Task::new(
+            task.title,
+            task.description,
+            Some(rule.next_due(task.due_date, now)),
+            task.priority,
+        );
+        next.tags = task
+            .tags
+            .into_iter()
+            .filter(|tag| tag != crate::commands::STALE_TAG)
+            .collect();
+        next.project = task.project;
+        next.recurrence = task.recurrence;
+        self.insert_task(None, &next).map(Some)
+    }
+
+    /// Whether a live task has this ID; tasks in the trash don't count.
+    pub fn task_exists(&self, id: i32) -> SqliteResult<bool> {
+        self.conn.query_row(
+            &format!(
+                "SELECT EXISTS (SELECT 1 FROM tasks WHERE id = ? AND {})",
+                NOT_DELETED
+            ),
+            [id],
+            |row| row.get(0),
+        )
+    }
+
+    /// Whether any task, including one in the trash, holds this ID.
+    fn task_ids_contain(&self, id: i32) -> SqliteResult<bool> {
+        self.conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM tasks WHERE id = ?)",
+            [id],
+            |row| row.get(0),
+        )
+    }
+
+    /// IDs of every task, including completed, someday and trashed ones.
+    pub fn task_ids(&self) -> SqliteResult<Vec<i32>> {
+        let mut stmt = self.conn.prepare("SELECT id FROM tasks ORDER BY id")?;
+        let rows = stmt.query_map([], |row| row.get(0))?;
+        rows.collect()
+    }
+}
+
+/// A fresh, initialized database in a temporary file, for tests. Keep the
+/// file alive as long as the database.
+#[cfg(test)]
+pub(crate) fn create_test_db() -> (Database, tempfile::NamedTempFile) {
+    let temp_file = tempfile::NamedTempFile::new().unwrap();
+    let db = Database::new(temp_file.path()).unwrap();
+    db.init().unwrap();
+    (db, temp_file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    fn create_test_task() -> Task {
+        Task::new(
+            "Test task".to_string(),
+            Some("Test description".to_string()),
+            Some(Utc::now()),
+            1,
+        )
+    }
+
+    #[test]
+    fn test_database_initialization() {
+        let (db, _temp_file) = create_test_db();
+
+        // Test that we can add a task (table exists)
+        let task = create_test_task();
+        let id = db.add_task(&task).unwrap();
+        assert_eq!(id, 1);
+    }
+
+    #[test]
+    fn test_add_and_get_task() {
+        let (db, _temp_file) = create_test_db();
+
+        let task = create_test_task();
+        let id = db.add_task(&task).unwrap();
+
+        let retrieved_task = db.get_task_by_id(id).unwrap().unwrap();
+        assert_eq!(retrieved_task.title, "Test task");
+        assert_eq!(
+            retrieved_task.description,
+            Some("Test description".to_string())
+        );
+        assert_eq!(retrieved_task.priority, 1);
+        assert!(!retrieved_task.completed);
+    }
+
+    #[test]
+    fn test_get_all_tasks() {
+        let (db, _temp_file) = create_test_db();
+
+        // Add multiple tasks
+        let task1 = Task::new("Task 1".to_string(), None, None, 0);
+        let task2 = Task::new("Task 2".to_string(), None, None, 2);
+
+        db.add_task(&task1).unwrap();
+        db.add_task(&task2).unwrap();
+
+        let tasks = db.get_tasks(&TaskFilter::any_status()).unwrap();
+        assert_eq!(tasks.len(), 2);
+
+        // Test priority filtering
+        let high_priority_tasks = db
+            .get_tasks(&TaskFilter {
+                priority: Some(2),
+                ..TaskFilter::any_status()
+            })
+            .unwrap();
+        assert_eq!(high_priority_tasks.len(), 1);
+        assert_eq!(high_priority_tasks[0].title, "Task 2");
+    }
+
+    #[test]
+    fn test_get_due_soon_tasks() {
+        let (db, _temp_file) = create_test_db();
+
+        let soon = Task::new(
+            "Soon".to_string(),
+            None,
+            Some(Utc::now() + Duration::hours(6)),
+            1,
+        );
+        let later = Task::new(
+            "Later".to_string(),
+            None,
+            Some(Utc::now() + Duration::days(10)),
+            1,
+        );
+        let undated = Task::new("Undated".to_string(), None, None, 1);
+
+        db.add_task(&soon).unwrap();
+        db.add_task(&later).unwrap();
+        db.add_task(&undated).unwrap();
+
+        let tasks = db
+            .get_tasks(&TaskFilter {
+                due_within: Some(Duration::hours(48)),
+                ..TaskFilter::default()
+            })
+            .unwrap();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].title, "Soon");
+    }
+
+    #[test]
+    fn test_get_tasks_exclusions() {
+        let (db, _temp_file) = create_test_db();
+
+        let mut chore = Task::new(
+            "Chore".to_string(),
+            None,
+            Some(Utc::now() + Duration::days(1)),
+            1,
+        );
+        chore.tags = vec!["chores".to_string()];
+        let mut dated = Task::new(
+            "Dated".to_string(),
+            None,
+            Some(Utc::now() + Duration::days(2)),
+            1,
+        );
+        dated.tags = vec!["work".to_string()];
+        let undated = Task::new("Undated".to_string(), None, None, 1);
+
+        db.add_task(&chore).unwrap();
+        db.add_task(&dated).unwrap();
+        db.add_task(&undated).unwrap();
+
+        let filter = TaskFilter {
+            exclude_tags: vec!["chores".to_string()],
+            ..TaskFilter::default()
+        };
+        let titles: Vec<String> = db
+            .get_tasks(&filter)
+            .unwrap()
+            .into_iter()
+            .map(|t| t.title)
+            .collect();
+        assert_eq!(titles, vec!["Dated", "Undated"]);
+
+        let filter = TaskFilter {
+            exclude_tags: vec!["chores".to_string()],
+            exclude_undated: true,
+            ..TaskFilter::default()
+        };
+        let tasks = db.get_tasks(&filter).unwrap();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].title, "Dated");
+    }
+
+    #[test]
+    fn test_find_pending_duplicate() {
+        let (db, _temp_file) = create_test_db();
+        let mut task = Task::new("Call  the bank".to_string(), None, None, 1);
+        task.project = Some("home".to_string());
+        let id = db.add_task(&task).unwrap();
+
+        // Case and spacing don't matter; the project and due day do
+        let mut same = Task::new("call the Bank".to_string(), None, None, 2);
+        same.project = Some("home".to_string());
+        assert_eq!(
+            db.find_pending_duplicate(&same).unwrap().unwrap().id,
+            Some(id)
+        );
+        same.project = None;
+        assert!(db.find_pending_duplicate(&same).unwrap().is_none());
+        same.project = Some("home".to_string());
+        same.due_date = Some(Utc::now());
+        assert!(db.find_pending_duplicate(&same).unwrap().is_none());
+
+        // Renaming the project updates the stored hash
+        db.rename_project("home", "house").unwrap();
+        same.due_date = None;
+        assert!(db.find_pending_duplicate(&same).unwrap().is_none());
+        same.project = Some("house".to_string());
+        assert!(db.find_pending_duplicate(&same).unwrap().is_some());
+
+        // Completed tasks are not duplicates
+        db.complete_task(id).unwrap();
+        assert!(db.find_pending_duplicate(&same).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_get_tasks_due_range() {
+        let (db, _temp_file) = create_test_db();
+        let now = Utc::now();
+        for (title, due) in [
+            ("Late", Some(now - Duration::days(3))),
+            ("Soon", Some(now + Duration::days(2))),
+            ("Later", Some(now + Duration::days(20))),
+            ("Undated", None),
+        ] {
+            db.add_task(&Task::new(title.to_string(), None, due, 1))
+                .unwrap();
+        }
+        let mut done = Task::new(
+            "Done late".to_string(),
+            None,
+            Some(now - Duration::days(1)),
+            1,
+        );
+        done.completed = true;
+        db.add_task(&done).unwrap();
+
+        let titles = |range: DueRange| -> Vec<String> {
+            let filter = TaskFilter {
+                status: StatusFilter::Any,
+                due_range: Some(range),
+                ..TaskFilter::default()
+            };
+            db.get_tasks(&filter)
+                .unwrap()
+                .into_iter()
+                .map(|t| t.title)
+                .collect()
+        };
+        assert_eq!(
+            titles(DueRange {
+                from: None,
+                until: now
+            }),
+            vec!["Late"]
+        );
+        assert_eq!(
+            titles(DueRange {
+                from: Some(now),
+                until: now + Duration::days(7),
+            }),
+            vec!["Soon"]
+        );
+    }
+
+    #[test]
+    fn test_get_tasks_title_regex_and_tag_glob() {
+        let (db, _temp_file) = create_test_db();
+
+        let mut rent = Task::new("Pay rent".to_string(), None, None, 1);
+        rent.tags = vec!["home/bills".to_string()];
+        let mut acme = Task::new("Pay Acme invoice".to_string(), None, None, 1);
+        acme.tags = vec!["work/acme".to_string()];
+        let call = Task::new("Call mom, then pay back".to_string(), None, None, 1);
+
+        db.add_task(&rent).unwrap();
+        db.add_task(&acme).unwrap();
+        db.add_task(&call).unwrap();
+
+        let filter = TaskFilter {
+            title_pattern: Some("^Pay .*".to_string()),
+            ..TaskFilter::default()
+        };
+        assert_eq!(db.get_tasks(&filter).unwrap().len(), 2);
+
+        let filter = TaskFilter {
+            title_pattern: Some("^Pay .*".to_string()),
+            tags: vec!["work/*".to_string()],
+            ..TaskFilter::default()
+        };
+        let tasks = db.get_tasks(&filter).unwrap();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].title, "Pay Acme invoice");
+
+        // A parent tag matches everything nested beneath it
+        let filter = TaskFilter {
+            tags: vec!["work".to_string()],
+            ..TaskFilter::default()
+        };
+        assert_eq!(db.get_tasks(&filter).unwrap().len(), 1);
+        let filter = TaskFilter {
+            exclude_tags: vec!["home".to_string()],
+            ..TaskFilter::default()
+        };
+        assert_eq!(db.get_tasks(&filter).unwrap().len(), 2);
+        // ...but not unrelated tags sharing a prefix
+        let filter = TaskFilter {
+            tags: vec!["wor".to_string()],
+            ..TaskFilter::default()
+        };
+        assert!(db.get_tasks(&filter).unwrap().is_empty());
+
+        let filter = TaskFilter {
+            title_pattern: Some("(unclosed".to_string()),
+            ..TaskFilter::default()
+        };
+        assert!(db.get_tasks(&filter).is_err());
+    }
+
+    #[test]
+    fn test_complete_task() {
+        let (db, _temp_file) = create_test_db();
+
+        let task = create_test_task();
+        let id = db.add_task(&task).unwrap();
+
+        // Complete the task
+        db.complete_task(id).unwrap();
+
+        let completed_task = db.get_task_by_id(id).unwrap().unwrap();
+        assert!(completed_task.completed);
+    }
+
+    #[test]
+    fn test_search_tasks() {
+        let (db, _temp_file) = create_test_db();
+
+        let mut invoice = Task::new(
+            "Send invoice".to_string(),
+            Some("Acme wants the Q3 invoice before Friday".to_string()),
+            None,
+            1,
+        );
+        let id = db.add_task(&invoice).unwrap();
+        db.add_task(&Task::new("Café visit".to_string(), None, None, 1))
+            .unwrap();
+        let done = db
+            .add_task(&Task::new("Invoice archive".to_string(), None, None, 1))
+            .unwrap();
+        db.complete_task(done).unwrap();
+
+        let hits = db.search_tasks("acme INV", &TaskFilter::default()).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].title, "Send \u{2}invoice\u{3}");
+        assert!(hits[0].excerpt.as_ref().unwrap().contains("\u{2}Acme\u{3}"));
+
+        assert_eq!(
+            db.search_tasks("invoice", &TaskFilter::any_status())
+                .unwrap()
+                .len(),
+            2
+        );
+        assert_eq!(
+            db.search_tasks("cafe", &TaskFilter::default())
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(db
+            .search_tasks("\"-(", &TaskFilter::default())
+            .unwrap()
+            .is_empty());
+
+        // The index follows edits and deletions
+        invoice.title = "Pay rent".to_string();
+        invoice.description = None;
+        db.update_task(id, &invoice).unwrap();
+        assert!(db
+            .search_tasks("acme", &TaskFilter::default())
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            db.search_tasks("rent", &TaskFilter::default())
+                .unwrap()
+                .len(),
+            1
+        );
+        db.delete_task(id).unwrap();
+        assert!(db
+            .search_tasks("rent", &TaskFilter::default())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_search_notes() {
+        let (db, _temp_file) = create_test_db();
+        let call = db
+            .add_task(&Task::new("Call the bank".to_string(), None, None, 1))
+            .unwrap();
+        let visit = db
+            .add_task(&Task::new(
+                "Visit".to_string(),
+                Some("Ask about the mortgage".to_string()),
+                None,
+                1,
+            ))
+            .unwrap();
+        db.add_note(call, "They asked for the mortgage papers")
+            .unwrap();
+        db.add_note(call, "Mortgage rate is fixed").unwrap();
+
+        let hits = db.search_tasks("mortgage", &TaskFilter::default()).unwrap();
+        let found: Vec<(Option<i32>, SearchField)> =
+            hits.iter().map(|hit| (hit.task.id, hit.field)).collect();
+        assert_eq!(
+            found,
+            [
+                (Some(visit), SearchField::Description),
+                (Some(call), SearchField::Note)
+            ]
+        );
+        assert!(hits[1].excerpt.as_ref().unwrap().contains("\u{2}"));
+        let hits = db.search_tasks("bank", &TaskFilter::default()).unwrap();
+        assert_eq!(
+            (hits[0].field, hits[0].excerpt.clone()),
+            (SearchField::Title, None)
+        );
+
+        // Every word has to be in the same note
+        assert!(db
+            .search_tasks("papers fixed", &TaskFilter::default())
+            .unwrap()
+            .is_empty());
+        let filter = TaskFilter {
+            text: Some("papers".to_string()),
+            ..TaskFilter::default()
+        };
+        assert_eq!(db.get_tasks(&filter).unwrap()[0].id, Some(call));
+    }
+
+    #[test]
+    fn test_filter_by_status_and_text() {
+        let (db, _temp_file) = create_test_db();
+        db.add_task(&Task::new("Buy milk".to_string(), None, None, 1))
+            .unwrap();
+        let bread = db
+            .add_task(&Task::new("Buy bread".to_string(), None, None, 1))
+            .unwrap();
+        db.complete_task(bread).unwrap();
+        db.add_task(&Task::new("Call the bakery".to_string(), None, None, 2))
+            .unwrap();
+
+        let titles = |filter: &TaskFilter| -> Vec<String> {
+            let tasks = db.get_tasks(filter).unwrap();
+            tasks.into_iter().map(|task| task.title).collect()
+        };
+        let text = Some("buy".to_string());
+        assert_eq!(
+            titles(&TaskFilter {
+                text: text.clone(),
+                ..TaskFilter::default()
+            }),
+            ["Buy milk"]
+        );
+        assert_eq!(
+            titles(&TaskFilter {
+                text: text.clone(),
+                status: StatusFilter::Completed,
+                ..TaskFilter::default()
+            }),
+            ["Buy bread"]
+        );
+        assert_eq!(
+            titles(&TaskFilter {
+                text,
+                ..TaskFilter::any_status()
+            })
+            .len(),
+            2
+        );
+
+        let urgent = TaskFilter {
+            priority: Some(2),
+            ..TaskFilter::default()
+        };
+        assert!(db.search_tasks("buy", &urgent).unwrap().is_empty());
+        assert_eq!(db.search_tasks("bak", &urgent).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_move_task_sets_manual_order() {
+        let (db, _temp_file) = create_test_db();
+        for (title, priority) in [("A", 2), ("B", 1), ("C", 0)] {
+            db.add_task(&Task::new(title.to_string(), None, None, priority))
+                .unwrap();
+        }
+        let manual = TaskFilter {
+            order: TaskOrder::Manual,
+            ..TaskFilter::default()
+        };
+        let titles = |filter: &TaskFilter| -> String {
+            let tasks = db.get_tasks(filter).unwrap();
+            tasks.into_iter().map(|task| task.title).collect()
+        };
+        assert_eq!(titles(&manual), "ABC");
+
+        assert!(db.move_task(3, Placement::Before(1)).unwrap());
+        assert_eq!(titles(&manual), "CAB");
+        assert!(db.move_task(1, Placement::After(2)).unwrap());
+        assert_eq!(titles(&manual), "CBA");
+        assert_eq!(titles(&TaskFilter::default()), "ABC");
+
+        // Tasks added later follow the ones already placed
+        db.add_task(&Task::new("D".to_string(), None, None, 2))
+            .unwrap();
+        assert_eq!(titles(&manual), "CBAD");
+        assert!(!db.move_task(9, Placement::Before(1)).unwrap());
+        assert!(!db.move_task(1, Placement::After(9)).unwrap());
+    }
+
+    #[test]
+    fn test_subtasks() {
+        let (db, _temp_file) = create_test_db();
+
+        let add = |title: &str, parent_id: Option<i32>, priority: i32| {
+            let mut task = Task::new(title.to_string(), None, None, priority);
+            task.parent_id = parent_id;
+            db.add_task(&task).unwrap()
+        };
+        let release = add("Release", None, 1);
+        let tests = add("Write tests", Some(release), 1);
+        let unit = add("Unit tests", Some(tests), 1);
+        let notes = add("Release notes", Some(release), 2);
+
+        let titles: Vec<String> = db
+            .get_subtasks(release)
+            .unwrap()
+            .into_iter()
+            .map(|task| task.title)
+            .collect();
+        assert_eq!(titles, vec!["Release notes", "Write tests", "Unit tests"]);
+
+        assert!(db.is_ancestor(release, unit).unwrap());
+        assert!(db.is_ancestor(unit, unit).unwrap());
+        assert!(!db.is_ancestor(unit, release).unwrap());
+        assert!(!db.is_ancestor(notes, unit).unwrap());
+
+        // Deleting a task moves its subtasks up a level
+        db.delete_task(tests).unwrap();
+        let unit_task = db.get_task_by_id(unit).unwrap().unwrap();
+        assert_eq!(unit_task.parent_id, Some(release));
+    }
+
+    #[test]
+    fn test_complete_recurring_task() {
+        let (db, _temp_file) = create_test_db();
+
+        let due = Utc::now() + Duration::days(2);
+        let mut task = Task::new("Water plants".to_string(), None, Some(due), 2);
+        task.tags = vec!["home".to_string(), "stale".to_string()];
+        task.project = Some("garden".to_string());
+        task.recurrence = Some("weekly".to_string());
+        let id = db.add_task(&task).unwrap();
+
+        let next_id = db.complete_task(id).unwrap().unwrap();
+        let next = db.get_task_by_id(next_id).unwrap().unwrap();
+        assert!(!next.completed);
+        assert_eq!(next.title, "Water plants");
+        assert_eq!(next.priority, 2);
+        assert_eq!(next.due_date.unwrap(), due + Duration::weeks(1));
+        assert_eq!(next.tags, vec!["home"]);
+        assert_eq!(next.project.as_deref(), Some("garden"));
+        assert_eq!(next.recurrence.as_deref(), Some("weekly"));
+
+        // Completing an already completed occurrence does not spawn another
+        assert_eq!(db.complete_task(id).unwrap(), None);
+        assert_eq!(db.get_tasks(&TaskFilter::any_status()).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_delete_task() {
+        let (db, _temp_file) = create_test_db();
+
+        let task = create_test_task();
+        let id = db.add_task(&task).unwrap();
+
+        // Verify task exists
+        assert!(db.task_exists(id).unwrap());
+
+        // Delete the task
+        db.delete_task(id).unwrap();
+
+        // Verify task is deleted
+        assert!(!db.task_exists(id).unwrap());
+        assert!(db.get_task_by_id(id).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_trash_and_restore() {
+        let (db, _temp_file) = create_test_db();
+
+        let parent = db.add_task(&create_test_task()).unwrap();
+        let mut child = create_test_task();
+        child.parent_id = Some(parent);
+        let child = db.add_task(&child).unwrap();
+
+        db.delete_task(parent).unwrap();
+        assert_eq!(db.get_tasks(&TaskFilter::any_status()).unwrap().len(), 1);
+        assert_eq!(
+            db.search_tasks("test", &TaskFilter::any_status())
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(db.get_task_by_id(child).unwrap().unwrap().parent_id, None);
+        let trashed = db.get_trashed_tasks().unwrap();
+        assert_eq!(trashed.len(), 1);
+        assert_eq!(trashed[0].0.id, Some(parent));
+
+        assert!(db.restore_task(parent).unwrap());
+        assert!(!db.restore_task(parent).unwrap());
+        assert_eq!(
+            db.get_task_by_id(child).unwrap().unwrap().parent_id,
+            Some(parent)
+        );
+        // Restoring consumed the delete, so there is nothing left to undo
+        assert!(db.undo_last().unwrap().is_empty());
+
+        db.delete_task(child).unwrap();
+        assert_eq!(db.empty_trash().unwrap(), 1);
+        assert!(db.get_trashed_tasks().unwrap().is_empty());
+        assert_eq!(db.task_ids().unwrap(), vec![parent]);
     }
 
-    fn create_test_task() -> Task {
-        Task::new(
-            "Test task".to_string(),
-            Some("Test description".to_string()),
-            Some(Utc::now()),
-            1,
-        )
+    #[test]
+    fn test_task_history_records_actor() {
+        let (db, _temp_file) = create_test_db();
+
+        let id = db.add_task(&create_test_task()).unwrap();
+        db.with_actor("sync:obsidian", || db.complete_task(id))
+            .unwrap();
+        let mut edited = db.get_task_by_id(id).unwrap().unwrap();
+        edited.priority = 2;
+        edited.tags = vec!["home".to_string()];
+        db.edit_task(id, &edited).unwrap();
+        // Saving an unchanged task adds nothing
+        db.edit_task(id, &edited).unwrap();
+
+        let history: Vec<(String, String, Option<String>)> = db
+            .get_task_history(id)
+            .unwrap()
+            .into_iter()
+            .map(|entry| (entry.actor, entry.action, entry.details))
+            .collect();
+        assert_eq!(
+            history,
+            vec![
+                ("cli".to_string(), "created".to_string(), None),
+                ("sync:obsidian".to_string(), "completed".to_string(), None),
+                (
+                    "cli".to_string(),
+                    "updated".to_string(),
+                    Some("priority medium → high; tags +#home".to_string())
+                ),
+            ]
+        );
     }
 
     #[test]
-    fn test_database_initialization() {
+    fn test_undo_operations() {
         let (db, _temp_file) = create_test_db();
 
-        // Test that we can add a task (table exists)
-        let task = create_test_task();
+        let mut task = create_test_task();
+        task.recurrence = Some("daily".to_string());
+        task.tags = vec!["work".to_string()];
         let id = db.add_task(&task).unwrap();
-        assert_eq!(id, 1);
+
+        let mut edited = db.get_task_by_id(id).unwrap().unwrap();
+        edited.title = "Renamed".to_string();
+        edited.tags = vec!["home".to_string()];
+        edited.someday = true;
+        db.edit_task(id, &edited).unwrap();
+        let next_id = db.complete_task(id).unwrap().unwrap();
+        db.delete_task(id).unwrap();
+
+        // Undo walks back through the log, most recent first
+        let undone = db.undo_last().unwrap().remove(0);
+        assert!(matches!(undone, Operation::Delete { .. }));
+        assert!(db.task_exists(id).unwrap());
+
+        let undone = db.undo_last().unwrap().remove(0);
+        assert!(matches!(undone, Operation::Complete { next_id: Some(n), .. } if n == next_id));
+        assert!(!db.get_task_by_id(id).unwrap().unwrap().completed);
+        assert!(!db.task_ids().unwrap().contains(&next_id));
+
+        db.undo_last().unwrap();
+        let restored = db.get_task_by_id(id).unwrap().unwrap();
+        assert_eq!(restored.title, "Test task");
+        assert_eq!(restored.tags, vec!["work"]);
+        assert!(!restored.someday);
+
+        assert!(db.undo_last().unwrap().is_empty());
     }
 
     #[test]
-    fn test_add_and_get_task() {
+    fn test_tags_roundtrip() {
         let (db, _temp_file) = create_test_db();
 
-        let task = create_test_task();
+        let mut task = create_test_task();
+        task.tags = vec!["work".to_string(), "errands".to_string()];
         let id = db.add_task(&task).unwrap();
+        db.add_tag(id, "work").unwrap(); // duplicate is ignored
 
         let retrieved_task = db.get_task_by_id(id).unwrap().unwrap();
-        assert_eq!(retrieved_task.title, "Test task");
+        assert_eq!(retrieved_task.tags, vec!["errands", "work"]);
+
+        db.delete_task(id).unwrap();
+        db.empty_trash().unwrap();
+        let count: i32 = db
+            .conn
+            .query_row("SELECT COUNT(*) FROM task_tags", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 0);
+    }
+
+    #[test]
+    fn test_get_stale_tasks() {
+        let (db, _temp_file) = create_test_db();
+
+        let mut old_task = Task::new("Old".to_string(), None, None, 1);
+        old_task.created_at = Utc::now() - Duration::days(60);
+        old_task.updated_at = old_task.created_at;
+        let mut old_done = old_task.clone();
+        old_done.title = "Old but done".to_string();
+        old_done.completed = true;
+        let fresh = Task::new("Fresh".to_string(), None, None, 1);
+
+        db.add_task(&old_task).unwrap();
+        db.add_task(&old_done).unwrap();
+        db.add_task(&fresh).unwrap();
+
+        let stale = db.get_stale_tasks(Utc::now() - Duration::days(30)).unwrap();
+        assert_eq!(stale.len(), 1);
+        assert_eq!(stale[0].title, "Old");
+    }
+
+    #[test]
+    fn test_aging_report() {
+        let (db, _temp_file) = create_test_db();
+        let now = Utc::now();
+
+        for (title, days) in [
+            ("Fresh", 1),
+            ("Weeks", 10),
+            ("Months", 40),
+            ("Ancient", 200),
+        ] {
+            let mut task = Task::new(title.to_string(), None, None, 1);
+            task.created_at = now - Duration::days(days);
+            db.add_task(&task).unwrap();
+        }
+        let mut done = Task::new("Done".to_string(), None, None, 1);
+        done.created_at = now - Duration::days(300);
+        done.completed = true;
+        db.add_task(&done).unwrap();
+        let mut parked = Task::new("Parked".to_string(), None, None, 1);
+        parked.created_at = now - Duration::days(300);
+        let parked_id = db.add_task(&parked).unwrap();
+        db.set_someday(parked_id, true).unwrap();
+        let mut also_fresh = Task::new("Also fresh".to_string(), None, None, 1);
+        also_fresh.created_at = now - Duration::hours(2);
+        db.add_task(&also_fresh).unwrap();
+
+        let report = db.get_aging_report(now, 2).unwrap();
+        let counts: Vec<(&str, usize)> = report
+            .buckets
+            .iter()
+            .map(|(bucket, count)| (bucket.label(), *count))
+            .collect();
         assert_eq!(
-            retrieved_task.description,
-            Some("Test description".to_string())
+            counts,
+            vec![("<1w", 2), ("1–4w", 1), ("1–3m", 1), (">3m", 1)]
         );
-        assert_eq!(retrieved_task.priority, 1);
-        assert_eq!(retrieved_task.completed, false);
+        let oldest: Vec<&str> = report.oldest.iter().map(|t| t.title.as_str()).collect();
+        assert_eq!(oldest, vec!["Ancient", "Months"]);
     }
 
     #[test]
-    fn test_get_all_tasks() {
+    fn test_archive_completed() {
         let (db, _temp_file) = create_test_db();
+        let old = Utc::now() - Duration::days(60);
 
-        // Add multiple tasks
-        let task1 = Task::new("Task 1".to_string(), None, None, 0);
-        let task2 = Task::new("Task 2".to_string(), None, None, 2);
+        let mut parent = Task::new("Old parent".to_string(), None, None, 1);
+        parent.tags = vec!["home".to_string()];
+        parent.project = Some("house".to_string());
+        let parent_id = db.add_task(&parent).unwrap();
+        let mut child = Task::new("Open child".to_string(), None, None, 1);
+        child.parent_id = Some(parent_id);
+        let child_id = db.add_task(&child).unwrap();
+        let recent_id = db
+            .add_task(&Task::new("Recent".to_string(), None, None, 1))
+            .unwrap();
+        db.complete_task(recent_id).unwrap();
 
-        db.add_task(&task1).unwrap();
-        db.add_task(&task2).unwrap();
+        let mut done = db.get_task_by_id(parent_id).unwrap().unwrap();
+        done.completed = true;
+        db.update_task(parent_id, &done).unwrap();
+        db.conn
+            .execute(
+                "UPDATE tasks SET updated_at = ? WHERE id = ?",
+                params![old.to_rfc3339(), parent_id],
+            )
+            .unwrap();
 
-        let tasks = db.get_all_tasks(true, None).unwrap();
-        assert_eq!(tasks.len(), 2);
+        assert_eq!(
+            db.archive_completed(Utc::now() - Duration::days(30))
+                .unwrap(),
+            1
+        );
+        assert!(db.get_task_by_id(parent_id).unwrap().is_none());
+        assert!(db.get_task_by_id(recent_id).unwrap().is_some());
+        let child = db.get_task_by_id(child_id).unwrap().unwrap();
+        assert_eq!(child.parent_id, None);
 
-        // Test priority filtering
-        let high_priority_tasks = db.get_all_tasks(true, Some(2)).unwrap();
-        assert_eq!(high_priority_tasks.len(), 1);
-        assert_eq!(high_priority_tasks[0].title, "Task 2");
+        let archived = db.get_archived_tasks().unwrap();
+        assert_eq!(archived.len(), 1);
+        assert_eq!(archived[0].id, Some(parent_id));
+        assert_eq!(archived[0].tags, vec!["home"]);
+        assert_eq!(archived[0].project.as_deref(), Some("house"));
+        let history = db.get_task_history(parent_id).unwrap();
+        assert_eq!(history.last().unwrap().action, "archived");
+
+        // Archived IDs are never handed out again
+        db.purge_task(recent_id).unwrap();
+        db.purge_task(child_id).unwrap();
+        let new_id = db
+            .add_task(&Task::new("New".to_string(), None, None, 1))
+            .unwrap();
+        assert!(new_id > parent_id);
     }
 
     #[test]
-    fn test_complete_task() {
+    fn test_completed_at() {
         let (db, _temp_file) = create_test_db();
+        let id = db.add_task(&create_test_task()).unwrap();
+        assert_eq!(db.get_task_by_id(id).unwrap().unwrap().completed_at, None);
 
-        let task = create_test_task();
-        let id = db.add_task(&task).unwrap();
-
-        // Complete the task
+        let before = Utc::now();
         db.complete_task(id).unwrap();
+        let completed_at = db
+            .get_task_by_id(id)
+            .unwrap()
+            .unwrap()
+            .completed_at
+            .unwrap();
+        assert!(completed_at >= before);
+        // Completing again or editing keeps the first time
+        db.complete_task(id).unwrap();
+        let mut task = db.get_task_by_id(id).unwrap().unwrap();
+        task.title = "Renamed".to_string();
+        db.update_task(id, &task).unwrap();
+        let task = db.get_task_by_id(id).unwrap().unwrap();
+        assert_eq!(task.completed_at, Some(completed_at));
+        assert_eq!(db.get_completed_since(before).unwrap()[0].id, Some(id));
+        assert!(db
+            .get_completed_since(Utc::now() + Duration::hours(1))
+            .unwrap()
+            .is_empty());
 
-        let completed_task = db.get_task_by_id(id).unwrap().unwrap();
-        assert!(completed_task.completed);
+        db.undo_last().unwrap();
+        assert_eq!(db.get_task_by_id(id).unwrap().unwrap().completed_at, None);
     }
 
     #[test]
-    fn test_delete_task() {
+    fn test_start_date() {
         let (db, _temp_file) = create_test_db();
+        let now = Utc::now();
+        let mut later = create_test_task();
+        later.start_date = Some(now + Duration::days(3));
+        let later = db.add_task(&later).unwrap();
+        let mut started = create_test_task();
+        started.start_date = Some(now - Duration::days(1));
+        let started = db.add_task(&started).unwrap();
+        let plain = db.add_task(&create_test_task()).unwrap();
 
-        let task = create_test_task();
+        let ids = |waiting| -> Vec<i32> {
+            let filter = TaskFilter {
+                waiting,
+                ..TaskFilter::default()
+            };
+            db.get_tasks(&filter)
+                .unwrap()
+                .iter()
+                .map(|task| task.id.unwrap())
+                .collect()
+        };
+        assert_eq!(ids(WaitingFilter::Exclude), [started, plain]);
+        assert_eq!(ids(WaitingFilter::Only), [later]);
+        assert_eq!(ids(WaitingFilter::Include).len(), 3);
+        assert!(db.get_task_by_id(later).unwrap().unwrap().is_waiting());
+
+        let mut task = db.get_task_by_id(later).unwrap().unwrap();
+        task.start_date = None;
+        db.edit_task(later, &task).unwrap();
+        assert!(ids(WaitingFilter::Only).is_empty());
+        db.undo_last().unwrap();
+        assert_eq!(ids(WaitingFilter::Only), [later]);
+    }
+
+    #[test]
+    fn test_recent_history() {
+        let (db, _temp_file) = create_test_db();
+        let first = db.add_task(&create_test_task()).unwrap();
+        let second = db.add_task(&create_test_task()).unwrap();
+        db.complete_task(first).unwrap();
+
+        let recent = db.get_recent_history(2).unwrap();
+        let entries: Vec<(i32, &str)> = recent
+            .iter()
+            .map(|entry| (entry.task_id, entry.action.as_str()))
+            .collect();
+        assert_eq!(entries, [(second, "created"), (first, "completed")]);
+        assert_eq!(db.get_recent_history(10).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_nested_transactions() {
+        let (db, _temp_file) = create_test_db();
+        let outer = db.transaction().unwrap();
+        let kept = db
+            .add_task(&Task::new("Kept".to_string(), None, None, 1))
+            .unwrap();
+        {
+            let _inner = db.transaction().unwrap();
+            db.add_task(&Task::new("Rolled back".to_string(), None, None, 1))
+                .unwrap();
+        }
+        let inner = db.transaction().unwrap();
+        db.complete_task(kept).unwrap();
+        inner.commit().unwrap();
+        outer.commit().unwrap();
+
+        let tasks = db
+            .get_tasks(&TaskFilter::builder().include_completed().build().unwrap())
+            .unwrap();
+        assert_eq!(tasks.len(), 1);
+        assert!(tasks[0].completed);
+    }
+
+    #[test]
+    fn test_purge_completed() {
+        let (db, _temp_file) = create_test_db();
+        let old = db
+            .add_task(&Task::new("Old".to_string(), None, None, 1))
+            .unwrap();
+        let recent = db
+            .add_task(&Task::new("Recent".to_string(), None, None, 1))
+            .unwrap();
+        let open = db
+            .add_task(&Task::new("Open".to_string(), None, None, 1))
+            .unwrap();
+        db.complete_tasks(&[old, recent]).unwrap();
+        db.conn
+            .execute(
+                "UPDATE tasks SET updated_at = ? WHERE id = ?",
+                params![(Utc::now() - Duration::days(60)).to_rfc3339(), old],
+            )
+            .unwrap();
+
+        let cutoff = Some(Utc::now() - Duration::days(30));
+        assert_eq!(db.completed_task_ids(cutoff).unwrap(), vec![old]);
+        assert_eq!(db.completed_task_ids(None).unwrap(), vec![old, recent]);
+        assert_eq!(db.purge_completed(cutoff).unwrap(), 1);
+        assert!(db.get_task_with_trashed(old).unwrap().is_none());
+        assert!(db.get_task_history(old).unwrap().is_empty());
+        assert_eq!(db.purge_completed(None).unwrap(), 1);
+        assert!(db.get_task_by_id(open).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_set_tags() {
+        let (db, _temp_file) = create_test_db();
+
+        let mut task = create_test_task();
+        task.tags = vec!["old".to_string()];
         let id = db.add_task(&task).unwrap();
 
-        // Verify task exists
-        assert!(db.task_exists(id).unwrap());
+        db.set_tags(id, &["a".to_string(), "b".to_string()])
+            .unwrap();
+        assert_eq!(db.get_task_by_id(id).unwrap().unwrap().tags, vec!["a", "b"]);
 
-        // Delete the task
+        db.set_tags(id, &[]).unwrap();
+        assert!(db.get_task_by_id(id).unwrap().unwrap().tags.is_empty());
+    }
+
+    #[test]
+    fn test_task_links() {
+        let (db, _temp_file) = create_test_db();
+
+        let id = db.add_task(&create_test_task()).unwrap();
+        assert_eq!(db.find_linked_task("habitica", "abc").unwrap(), None);
+
+        db.add_task_link(id, "habitica", "abc").unwrap();
+        assert_eq!(db.find_linked_task("habitica", "abc").unwrap(), Some(id));
+        assert_eq!(db.find_linked_task("other", "abc").unwrap(), None);
+
+        // Links survive the trash, so syncs don't import the task again
         db.delete_task(id).unwrap();
+        assert_eq!(db.find_linked_task("habitica", "abc").unwrap(), Some(id));
+        db.empty_trash().unwrap();
+        assert_eq!(db.find_linked_task("habitica", "abc").unwrap(), None);
+    }
 
-        // Verify task is deleted
-        assert!(!db.task_exists(id).unwrap());
-        assert!(db.get_task_by_id(id).unwrap().is_none());
+    #[test]
+    fn test_focus_sessions() {
+        let (db, _temp_file) = create_test_db();
+        assert!(db.get_active_focus_session().unwrap().is_none());
+
+        let id = db
+            .start_focus_session(Some(3), Utc::now() + Duration::minutes(50))
+            .unwrap();
+        let session = db.get_active_focus_session().unwrap().unwrap();
+        assert_eq!(session.id, id);
+        assert_eq!(session.task_id, Some(3));
+
+        db.end_focus_session(id).unwrap();
+        assert!(db.get_active_focus_session().unwrap().is_none());
+
+        // Expired sessions are not active
+        db.start_focus_session(None, Utc::now() - Duration::minutes(1))
+            .unwrap();
+        assert!(db.get_active_focus_session().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_meta_roundtrip() {
+        let (db, _temp_file) = create_test_db();
+
+        assert_eq!(db.get_meta("missing").unwrap(), None);
+        db.set_meta("key", "one").unwrap();
+        db.set_meta("key", "two").unwrap();
+        assert_eq!(db.get_meta("key").unwrap(), Some("two".to_string()));
+
+        let now = Utc::now();
+        db.set_meta_datetime("when", now).unwrap();
+        assert_eq!(db.get_meta_datetime("when").unwrap(), Some(now));
+        db.set_meta("when", "garbage").unwrap();
+        assert_eq!(db.get_meta_datetime("when").unwrap(), None);
+    }
+
+    #[test]
+    fn test_init_seeds_meta() {
+        let (db, _temp_file) = create_test_db();
+
+        assert_eq!(
+            db.get_meta(META_SCHEMA_VERSION).unwrap(),
+            Some(SCHEMA_VERSION.to_string())
+        );
+        let install_id = db.get_meta(META_INSTALL_ID).unwrap().unwrap();
+        assert_eq!(install_id.len(), 32);
+        assert_eq!(db.get_meta(META_LINK_SECRET).unwrap().unwrap().len(), 64);
+
+        // Re-running init keeps the existing install id
+        db.init().unwrap();
+        assert_eq!(db.get_meta(META_INSTALL_ID).unwrap(), Some(install_id));
     }
 
     #[test]
@@ -293,4 +4258,437 @@ mod tests {
         assert_eq!(retrieved_task.title, "Updated task");
         assert_eq!(retrieved_task.priority, 2);
     }
+
+    #[test]
+    fn test_load_missing_extension() {
+        let db = Database::new(std::path::Path::new(":memory:")).unwrap();
+        assert!(!db.function_names().unwrap().is_empty());
+        assert!(db
+            .load_extension(std::path::Path::new("/nonexistent/ext.so"))
+            .is_err());
+        assert!(db.extension_functions().is_empty());
+    }
+
+    #[test]
+    fn test_inherited_priority() {
+        let (db, _temp_file) = create_test_db();
+        let add = |title: &str, priority: Option<i32>, project: Option<&str>, parent_id| {
+            let mut task = Task::new(title.to_string(), None, None, priority.unwrap_or(1));
+            task.priority_inherited = priority.is_none();
+            task.project = project.map(str::to_string);
+            task.parent_id = parent_id;
+            db.add_task(&task).unwrap()
+        };
+        let launch = add("Launch", Some(2), None, None);
+        let docs = add("Write docs", None, Some("site"), Some(launch));
+        let typos = add("Fix typos", None, None, Some(docs));
+        let chores = add("Chores", None, Some("home"), None);
+        let priority = |id| {
+            let task = db.get_task_by_id(id).unwrap().unwrap();
+            (task.priority, task.priority_inherited)
+        };
+
+        // Without project defaults the nearest parent with a priority wins
+        assert_eq!(priority(typos), (2, true));
+        assert_eq!(priority(chores), (1, true));
+        db.set_project_priority("site", Some(0)).unwrap();
+        assert_eq!(priority(docs), (0, true));
+        assert_eq!(priority(typos), (0, true));
+        let low = db
+            .get_tasks(&TaskFilter {
+                priority: Some(0),
+                ..TaskFilter::default()
+            })
+            .unwrap();
+        assert_eq!(low.len(), 2);
+
+        // Saving an inherited task keeps it inherited; a set priority sticks
+        let mut task = db.get_task_by_id(typos).unwrap().unwrap();
+        task.title = "Fix all typos".to_string();
+        db.update_task(typos, &task).unwrap();
+        db.set_project_priority("site", None).unwrap();
+        assert_eq!(priority(typos), (2, true));
+        task.priority = 1;
+        task.priority_inherited = false;
+        db.update_task(typos, &task).unwrap();
+        assert_eq!(priority(typos), (1, false));
+        assert_eq!(db.get_subtasks(launch).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_snooze_task() {
+        let (db, _temp_file) = create_test_db();
+        let id = db.add_task(&create_test_task()).unwrap();
+        assert_eq!(db.get_snooze_count(id).unwrap(), 0);
+
+        let until = Utc::now() + Duration::days(3);
+        db.snooze_task(id, until).unwrap();
+        db.snooze_task(id, until).unwrap();
+        assert_eq!(db.get_snooze_count(id).unwrap(), 2);
+        let task = db.get_task_by_id(id).unwrap().unwrap();
+        assert_eq!(task.due_date.unwrap().timestamp(), until.timestamp());
+
+        db.reset_snooze_count(id).unwrap();
+        assert_eq!(db.get_snooze_count(id).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_someday_bucket() {
+        let (db, _temp_file) = create_test_db();
+        let id = db.add_task(&create_test_task()).unwrap();
+        let other = db.add_task(&create_test_task()).unwrap();
+
+        db.set_someday(id, true).unwrap();
+        assert!(db.get_task_by_id(id).unwrap().unwrap().someday);
+
+        let ids = |filter: &TaskFilter| -> Vec<i32> {
+            db.get_tasks(filter)
+                .unwrap()
+                .iter()
+                .map(|t| t.id.unwrap())
+                .collect()
+        };
+        assert_eq!(ids(&TaskFilter::default()), vec![other]);
+        let only = TaskFilter {
+            someday: SomedayFilter::Only,
+            ..TaskFilter::default()
+        };
+        assert_eq!(ids(&only), vec![id]);
+        let all = TaskFilter {
+            someday: SomedayFilter::Include,
+            ..TaskFilter::default()
+        };
+        assert_eq!(ids(&all).len(), 2);
+
+        // Freshly parked tasks are not due for review yet
+        let cutoff = Utc::now() - Duration::days(1);
+        assert!(db.get_someday_due_for_review(cutoff).unwrap().is_empty());
+        let cutoff = Utc::now() + Duration::days(1);
+        assert_eq!(db.get_someday_due_for_review(cutoff).unwrap().len(), 1);
+        db.mark_someday_reviewed(id).unwrap();
+
+        db.set_someday(id, false).unwrap();
+        assert!(!db.get_task_by_id(id).unwrap().unwrap().someday);
+    }
+
+    #[test]
+    fn test_goals() {
+        let (db, _temp_file) = create_test_db();
+        let later = db
+            .add_goal("Run a 10k", Some(Utc::now() + Duration::days(90)))
+            .unwrap();
+        let undated = db.add_goal("Learn Spanish", None).unwrap();
+        let sooner = db
+            .add_goal("Ship v2", Some(Utc::now() + Duration::days(10)))
+            .unwrap();
+
+        let order: Vec<i32> = db.get_goals().unwrap().iter().map(|g| g.id).collect();
+        assert_eq!(order, vec![sooner, later, undated]);
+
+        let a = db.add_task(&create_test_task()).unwrap();
+        let b = db.add_task(&create_test_task()).unwrap();
+        db.link_task_to_goal(a, later).unwrap();
+        db.link_task_to_goal(b, later).unwrap();
+        db.complete_task(b).unwrap();
+        assert_eq!(db.get_goal_tasks(later).unwrap().len(), 2);
+
+        // Linking again moves the task to the other goal
+        db.link_task_to_goal(a, sooner).unwrap();
+        assert_eq!(db.get_goal_tasks(later).unwrap().len(), 1);
+
+        db.unlink_task_from_goal(a).unwrap();
+        assert!(db.get_goal_tasks(sooner).unwrap().is_empty());
+
+        db.delete_goal(later).unwrap();
+        assert!(db.get_goal(later).unwrap().is_none());
+        assert!(db.get_task_by_id(b).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_command_usage() {
+        let (db, _temp_file) = create_test_db();
+        db.record_command_usage("list", Duration::milliseconds(10))
+            .unwrap();
+        db.record_command_usage("list", Duration::milliseconds(30))
+            .unwrap();
+        db.record_command_usage("add", Duration::milliseconds(5))
+            .unwrap();
+
+        let stats = db.get_usage_stats().unwrap();
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[0].command, "list");
+        assert_eq!(stats[0].count, 2);
+        assert_eq!(stats[0].avg_ms, 20.0);
+        assert_eq!(stats[0].max_ms, 30);
+
+        db.clear_command_usage().unwrap();
+        assert!(db.get_usage_stats().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_projects() {
+        let (db, _temp_file) = create_test_db();
+        let mut task = create_test_task();
+        task.project = Some("work".to_string());
+        let a = db.add_task(&task).unwrap();
+        let b = db.add_task(&task).unwrap();
+        let loose = db.add_task(&create_test_task()).unwrap();
+        db.complete_task(b).unwrap();
+
+        assert_eq!(
+            db.get_task_by_id(a).unwrap().unwrap().project.as_deref(),
+            Some("work")
+        );
+        assert_eq!(db.get_projects().unwrap(), vec![("work".to_string(), 1, 2)]);
+
+        let in_work = TaskFilter {
+            project: Some("work".to_string()),
+            ..TaskFilter::default()
+        };
+        assert_eq!(db.get_tasks(&in_work).unwrap().len(), 1);
+        let not_work = TaskFilter {
+            exclude_projects: vec!["work".to_string()],
+            ..TaskFilter::default()
+        };
+        let ids: Vec<i32> = db
+            .get_tasks(&not_work)
+            .unwrap()
+            .iter()
+            .map(|t| t.id.unwrap())
+            .collect();
+        assert_eq!(ids, vec![loose]);
+
+        db.rename_project("work", "job").unwrap();
+        assert!(db.project_exists("job").unwrap());
+        assert_eq!(
+            db.get_task_by_id(a).unwrap().unwrap().project.as_deref(),
+            Some("job")
+        );
+
+        db.delete_project("job").unwrap();
+        assert!(db.get_projects().unwrap().is_empty());
+        assert_eq!(db.get_task_by_id(a).unwrap().unwrap().project, None);
+    }
+
+    #[test]
+    fn test_migrate_adds_task_columns() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let conn = Connection::open(temp_file.path()).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE tasks (
+                id INTEGER PRIMARY KEY,
+                title TEXT NOT NULL,
+                description TEXT,
+                due_date TEXT,
+                priority INTEGER DEFAULT 1,
+                completed BOOLEAN DEFAULT FALSE,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            );
+            INSERT INTO tasks (title, created_at, updated_at)
+            VALUES ('Old task', '2024-01-01T00:00:00+00:00', '2024-01-01T00:00:00+00:00');",
+        )
+        .unwrap();
+        drop(conn);
+
+        let db = Database::new(temp_file.path()).unwrap();
+        db.init().unwrap();
+        let task = db.get_task_by_id(1).unwrap().unwrap();
+        assert_eq!(task.title, "Old task");
+        assert_eq!(task.project, None);
+        assert_eq!(task.recurrence, None);
+        assert_eq!(task.parent_id, None);
+        assert_eq!(
+            db.search_tasks("old", &TaskFilter::default())
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(
+            db.get_meta(META_SCHEMA_VERSION).unwrap(),
+            Some(SCHEMA_VERSION.to_string())
+        );
+    }
+
+    #[test]
+    fn test_migrations_are_consecutive() {
+        for (index, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.version, index as i64 + 1);
+        }
+        assert_eq!(SCHEMA_VERSION, MIGRATIONS.len() as i64);
+    }
+
+    #[test]
+    fn test_init_runs_pending_migrations_only() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let conn = Connection::open(temp_file.path()).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE tasks (
+                id INTEGER PRIMARY KEY,
+                title TEXT NOT NULL,
+                description TEXT,
+                due_date TEXT,
+                priority INTEGER DEFAULT 1,
+                completed BOOLEAN DEFAULT FALSE,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL,
+                project_id INTEGER
+            );
+            CREATE TABLE task_tags (task_id INTEGER NOT NULL, tag TEXT NOT NULL);
+            CREATE TABLE projects (id INTEGER PRIMARY KEY, name TEXT NOT NULL UNIQUE, created_at TEXT NOT NULL);
+            CREATE TABLE meta (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+            INSERT INTO meta VALUES ('schema_version', '2');
+            INSERT INTO tasks (title, created_at, updated_at)
+            VALUES ('Old task', '2024-01-01T00:00:00+00:00', '2024-01-01T00:00:00+00:00');",
+        )
+        .unwrap();
+        drop(conn);
+
+        let db = Database::new(temp_file.path()).unwrap();
+        assert_eq!(db.schema_version().unwrap(), 2);
+        db.init().unwrap();
+        assert_eq!(db.schema_version().unwrap(), SCHEMA_VERSION);
+        let mut task = db.get_task_by_id(1).unwrap().unwrap();
+        task.id = None;
+        let duplicate = db.find_pending_duplicate(&task).unwrap().unwrap();
+        assert_eq!(duplicate.id, Some(1));
+        db.delete_task(1).unwrap();
+        assert_eq!(db.undo_last().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_init_refuses_newer_schema() {
+        let (db, temp_file) = create_test_db();
+        db.set_meta(META_SCHEMA_VERSION, &(SCHEMA_VERSION + 1).to_string())
+            .unwrap();
+        drop(db);
+
+        let db = Database::new(temp_file.path()).unwrap();
+        let error = db.init().unwrap_err().to_string();
+        assert!(error.contains("upgrade todo"), "{}", error);
+        assert_eq!(db.schema_version().unwrap(), SCHEMA_VERSION + 1);
+    }
+
+    #[test]
+    fn test_get_tasks_page() {
+        let (db, _temp_file) = create_test_db();
+        for i in 1..=5 {
+            db.add_task(&Task::new(format!("Task {}", i), None, None, 1))
+                .unwrap();
+        }
+        let filter = TaskFilter::default();
+        let titles = |page: Page| -> Vec<String> {
+            db.get_tasks_page(&filter, page)
+                .unwrap()
+                .into_iter()
+                .map(|task| task.title)
+                .collect()
+        };
+
+        assert_eq!(titles(Page::default()).len(), 5);
+        let second = Page {
+            limit: Some(2),
+            offset: 2,
+        };
+        assert_eq!(titles(second), ["Task 3", "Task 4"]);
+        // An offset without a limit runs to the end
+        let rest = Page {
+            limit: None,
+            offset: 3,
+        };
+        assert_eq!(titles(rest), ["Task 4", "Task 5"]);
+        assert_eq!(db.count_tasks(&filter).unwrap(), 5);
+    }
+
+    #[test]
+    fn test_writers_wait_for_each_other() {
+        let (db, temp_file) = create_test_db();
+        let mode: String = db
+            .conn
+            .pragma_query_value(None, "journal_mode", |row| row.get(0))
+            .unwrap();
+        assert_eq!(mode, "wal");
+
+        // Another process holds the write lock for a moment
+        let (locked, wait) = std::sync::mpsc::channel();
+        let path = temp_file.path().to_path_buf();
+        let writer = std::thread::spawn(move || {
+            let other = Database::new(&path).unwrap();
+            let tx = other.transaction().unwrap();
+            other
+                .add_task(&Task::new("Theirs".to_string(), None, None, 1))
+                .unwrap();
+            locked.send(()).unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(300));
+            tx.commit().unwrap();
+        });
+        wait.recv().unwrap();
+
+        // Reading doesn't wait; writing waits for the lock, then succeeds
+        assert!(db.get_tasks(&TaskFilter::default()).unwrap().is_empty());
+        let impatient = Database::new(temp_file.path()).unwrap();
+        impatient
+            .conn
+            .busy_timeout(std::time::Duration::ZERO)
+            .unwrap();
+        let error = impatient
+            .add_task(&Task::new("Impatient".to_string(), None, None, 1))
+            .unwrap_err();
+        assert!(is_locked(&error));
+        db.add_task(&Task::new("Mine".to_string(), None, None, 1))
+            .unwrap();
+        writer.join().unwrap();
+        assert_eq!(db.get_tasks(&TaskFilter::default()).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_data_version_sees_other_connections() {
+        let (watcher, temp_file) = create_test_db();
+        let before = watcher.data_version().unwrap();
+
+        // Its own writes don't count, only other connections' commits
+        watcher
+            .add_task(&Task::new("Mine".to_string(), None, None, 1))
+            .unwrap();
+        assert_eq!(watcher.data_version().unwrap(), before);
+
+        let other = Database::new(temp_file.path()).unwrap();
+        other
+            .add_task(&Task::new("Theirs".to_string(), None, None, 1))
+            .unwrap();
+        assert_ne!(watcher.data_version().unwrap(), before);
+    }
+
+    #[test]
+    fn test_reopen_after_another_process_migrates() {
+        let (mut server, temp_file) = create_test_db();
+        assert_eq!(server.reopen_if_migrated().unwrap(), None);
+
+        // A newer todo adds a column and bumps the version
+        let cli = Database::new(temp_file.path()).unwrap();
+        cli.conn
+            .execute("ALTER TABLE tasks ADD COLUMN context TEXT", [])
+            .unwrap();
+        cli.set_meta(META_SCHEMA_VERSION, &(SCHEMA_VERSION + 1).to_string())
+            .unwrap();
+
+        assert_eq!(
+            server.reopen_if_migrated().unwrap(),
+            Some(SCHEMA_VERSION + 1)
+        );
+        assert_eq!(server.reopen_if_migrated().unwrap(), None);
+        let id = server
+            .add_task(&Task::new("Still served".to_string(), None, None, 1))
+            .unwrap();
+        assert_eq!(
+            server.get_task_by_id(id).unwrap().unwrap().title,
+            "Still served"
+        );
+
+        // Back at an older version, such as from a restored backup, the
+        // server migrates it up again
+        cli.set_meta(META_SCHEMA_VERSION, &(SCHEMA_VERSION - 1).to_string())
+            .unwrap();
+        assert_eq!(server.reopen_if_migrated().unwrap(), Some(SCHEMA_VERSION));
+    }
 }
@@ -0,0 +1,147 @@
+//! Parse errors that point at the offending part of the input, rendered
+//! like compiler diagnostics:
+//!
+//! ```text
+//! Invalid date 'in 3 fortnights'
+//!   |
+//!   | in 3 fortnights
+//!   |      ^^^^^^^^^^ unknown unit `fortnights`
+//!   = hint: use minutes, hours, days, weeks, months or years
+//! ```
+
+use colored::*;
+use std::ops::Range;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    /// What was being parsed, e.g. `date` or `repeat rule`.
+    pub what: &'static str,
+    pub input: String,
+    /// Byte range of `input` to underline. An empty range points just past
+    /// the end, for something missing.
+    pub span: Range<usize>,
+    pub message: String,
+    pub hint: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn new(what: &'static str, input: &str, span: Range<usize>, message: String) -> Self {
+        Self {
+            what,
+            input: input.to_string(),
+            span,
+            message,
+            hint: None,
+        }
+    }
+
+    pub fn with_hint(mut self, hint: impl Into<String>) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
+
+    pub fn render(&self, color: bool) -> String {
+        let paint = |text: String, style: fn(ColoredString) -> ColoredString| {
+            if color {
+                style(text.normal()).to_string()
+            } else {
+                text
+            }
+        };
+        let gutter = paint("  |".to_string(), |s| s.blue().bold());
+        let column = self.input[..self.span.start].chars().count();
+        let width = self.input[self.span.clone()].chars().count().max(1);
+
+        let mut out = format!("Invalid {} '{}'\n", self.what, self.input);
+        out.push_str(&format!("{}\n", gutter));
+        out.push_str(&format!("{} {}\n", gutter, self.input));
+        out.push_str(&format!(
+            "{} {}{}",
+            gutter,
+            " ".repeat(column),
+            paint(format!("{} {}", "^".repeat(width), self.message), |s| s
+                .red()
+                .bold())
+        ));
+        if let Some(hint) = &self.hint {
+            out.push_str(&format!(
+                "\n  {} {}",
+                paint("= hint:".to_string(), |s| s.cyan().bold()),
+                hint
+            ));
+        }
+        out
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let color = colored::control::SHOULD_COLORIZE.should_colorize();
+        write!(f, "{}", self.render(color))
+    }
+}
+
+impl std::error::Error for Diagnostic {}
+
+/// Whitespace-separated words of `text`, lowercased, with their byte ranges
+/// in `text`.
+pub fn words(text: &str) -> Vec<(Range<usize>, String)> {
+    let mut words = Vec::new();
+    let mut start = None;
+    for (index, c) in text.char_indices().chain([(text.len(), ' ')]) {
+        match (start, c.is_whitespace()) {
+            (None, false) => start = Some(index),
+            (Some(from), true) => {
+                words.push((from..index, text[from..index].to_lowercase()));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    words
+}
+
+/// "did you mean" for `word` among `known`, if anything is close.
+pub fn did_you_mean(word: &str, known: &[&str]) -> Option<String> {
+    let known: Vec<String> = known.iter().map(|k| k.to_string()).collect();
+    let matches = crate::suggest::closest(word, &known);
+    (!matches.is_empty()).then(|| {
+        let names: Vec<String> = matches.iter().map(|m| format!("`{}`", m)).collect();
+        format!("did you mean {}?", names.join(" or "))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_points_at_span() {
+        let diagnostic = Diagnostic::new(
+            "date",
+            "in 3 fortnights",
+            5..15,
+            "unknown unit `fortnights`".to_string(),
+        )
+        .with_hint("use days or weeks");
+        assert_eq!(
+            diagnostic.render(false),
+            "Invalid date 'in 3 fortnights'\n  |\n  | in 3 fortnights\n  |      ^^^^^^^^^^ unknown unit `fortnights`\n  = hint: use days or weeks"
+        );
+
+        // A missing word is pointed at just past the end
+        let missing = Diagnostic::new("date", "in 3", 4..4, "expected a unit".to_string());
+        assert!(missing
+            .render(false)
+            .ends_with("  | in 3\n  |     ^ expected a unit"));
+    }
+
+    #[test]
+    fn test_words_keep_byte_ranges() {
+        let words = words("  Next  Fri");
+        assert_eq!(
+            words,
+            vec![(2..6, "next".to_string()), (8..11, "fri".to_string())]
+        );
+    }
+}
@@ -0,0 +1,83 @@
+use chrono::Duration;
+use std::collections::{HashMap, HashSet};
+
+use crate::models::Task;
+
+/// Render `tasks` as summary lines, nesting subtasks under their parent with
+/// tree connectors. Tasks whose parent is not in the list are shown at the
+/// top level, so filtered lists still show every task exactly once.
+pub fn render_tree(tasks: &[Task], due_soon_window: Duration) -> Vec<String> {
+    tree_rows(tasks)
+        .into_iter()
+        .map(|(prefix, task)| format!("{}{}", prefix, task.display_summary(due_soon_window)))
+        .collect()
+}
+
+/// `tasks` in tree order, each with the connectors to draw in front of it.
+pub fn tree_rows(tasks: &[Task]) -> Vec<(String, &Task)> {
+    let ids: HashSet<i32> = tasks.iter().filter_map(|task| task.id).collect();
+    let mut children: HashMap<i32, Vec<&Task>> = HashMap::new();
+    let mut roots = Vec::new();
+    for task in tasks {
+        match task.parent_id.filter(|parent| ids.contains(parent)) {
+            Some(parent) => children.entry(parent).or_default().push(task),
+            None => roots.push(task),
+        }
+    }
+
+    let mut rows = Vec::new();
+    for root in roots {
+        rows.push((String::new(), root));
+        push_children(root, "", &children, &mut rows);
+    }
+    rows
+}
+
+fn push_children<'a>(
+    parent: &Task,
+    indent: &str,
+    children: &HashMap<i32, Vec<&'a Task>>,
+    rows: &mut Vec<(String, &'a Task)>,
+) {
+    let Some(kids) = parent.id.and_then(|id| children.get(&id)) else {
+        return;
+    };
+    for (index, child) in kids.iter().enumerate() {
+        let last = index + 1 == kids.len();
+        let connector = if last { "└─ " } else { "├─ " };
+        rows.push((format!("{}{}", indent, connector), child));
+        let nested = format!("{}{}", indent, if last { "   " } else { "│  " });
+        push_children(child, &nested, children, rows);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(id: i32, parent_id: Option<i32>) -> Task {
+        let mut task = Task::new(format!("T{}", id), None, None, 1);
+        task.id = Some(id);
+        task.parent_id = parent_id;
+        task
+    }
+
+    #[test]
+    fn test_render_tree() {
+        let tasks = vec![
+            task(1, None),
+            task(2, Some(1)),
+            task(3, Some(2)),
+            task(4, Some(1)),
+            task(5, Some(99)),
+        ];
+        let lines = render_tree(&tasks, Duration::hours(48));
+        let prefixes: Vec<&str> = lines
+            .iter()
+            .map(|line| &line[..line.find('[').unwrap()])
+            .collect();
+        assert_eq!(prefixes, vec!["", "├─ ", "│  └─ ", "└─ ", ""]);
+        assert!(lines[2].contains("[3] T3"));
+        assert!(lines[4].contains("[5] T5"));
+    }
+}
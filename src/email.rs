@@ -0,0 +1,170 @@
+use chrono::{DateTime, Duration, Utc};
+
+use crate::html::escape;
+use crate::models::Task;
+use crate::signing::{action_url, LinkAction};
+
+/// How long one-click links in a weekly plan stay valid.
+pub const LINK_LIFETIME_DAYS: i64 = 7;
+
+pub struct PlanEmail<'a> {
+    pub to: Option<&'a str>,
+    pub base_url: &'a str,
+    pub secret: &'a str,
+    pub now: DateTime<Utc>,
+}
+
+/// Tasks worth planning this week: everything overdue or due within seven
+/// days, plus undated high-priority work.
+pub fn plan_tasks(tasks: Vec<Task>, now: DateTime<Utc>) -> Vec<Task> {
+    let horizon = now + Duration::days(7);
+    let mut planned: Vec<Task> = tasks
+        .into_iter()
+        .filter(|t| !t.completed)
+        .filter(|t| match t.due_date {
+            Some(due) => due <= horizon,
+            None => t.priority == 2,
+        })
+        .collect();
+    planned.sort_by_key(|t| (t.due_date.is_none(), t.due_date, -t.priority));
+    planned
+}
+
+/// Render a complete RFC 5322 message with an HTML body, ready to pipe into
+/// `sendmail -t`. Headers are plain ASCII; a recipient with a line break in
+/// it, which would add headers of its own, is refused.
+pub fn render_weekly_plan(tasks: &[Task], email: &PlanEmail) -> anyhow::Result<String> {
+    if let Some(to) = email.to.filter(|to| to.contains(['\r', '\n'])) {
+        return Err(anyhow::anyhow!(
+            "Invalid recipient {:?}: it cannot contain a line break",
+            to
+        ));
+    }
+    let expires = email.now + Duration::days(LINK_LIFETIME_DAYS);
+    let subject = format!("Weekly plan - week of {}", email.now.format("%Y-%m-%d"));
+
+    let rows: String = tasks
+        .iter()
+        .map(|task| {
+            let id = task.id.unwrap_or(0);
+            let due_style = if task.is_overdue() {
+                "color:#d62e2e"
+            } else {
+                "color:#555"
+            };
+            format!(
+                r#"<tr><td style="padding:6px 8px">{title}<br><span style="{due_style};font-size:12px">{priority} · {due}</span></td><td style="padding:6px 8px;white-space:nowrap"><a href="{complete}">Done</a> · <a href="{snooze}">Snooze</a></td></tr>
+"#,
+                title = escape(&task.title),
+                due_style = due_style,
+                priority = task.priority_text(),
+                due = match task.due_date {
+                    Some(_) => format!("due {}", escape(&task.due_date_text())),
+                    None => "no due date".to_string(),
+                },
+                complete = escape(&action_url(
+                    email.base_url,
+                    email.secret,
+                    id,
+                    LinkAction::Complete,
+                    expires
+                )),
+                snooze = escape(&action_url(
+                    email.base_url,
+                    email.secret,
+                    id,
+                    LinkAction::Snooze,
+                    expires
+                )),
+            )
+        })
+        .collect();
+
+    let body = if tasks.is_empty() {
+        "<p>Nothing due this week. Enjoy!</p>".to_string()
+    } else {
+        format!(
+            "<table style=\"border-collapse:collapse;font-family:sans-serif\">\n{}</table>",
+            rows
+        )
+    };
+
+    let mut message = String::new();
+    if let Some(to) = email.to {
+        message.push_str(&format!("To: {}\r\n", to));
+    }
+    message.push_str(&format!("Subject: {}\r\n", subject));
+    message.push_str("MIME-Version: 1.0\r\n");
+    message.push_str("Content-Type: text/html; charset=utf-8\r\n\r\n");
+    message.push_str(&format!(
+        "<html><body>\n<h2 style=\"font-family:sans-serif\">{}</h2>\n{}\n<p style=\"color:#888;font-size:12px\">Links expire on {}.</p>\n</body></html>\n",
+        escape(&subject),
+        body,
+        expires.format("%Y-%m-%d")
+    ));
+    Ok(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_tasks_selection_and_order() {
+        let now = Utc::now();
+        let mut late = Task::new("Late".to_string(), None, Some(now - Duration::days(1)), 1);
+        late.id = Some(1);
+        let soon = Task::new("Soon".to_string(), None, Some(now + Duration::days(2)), 0);
+        let far = Task::new("Far".to_string(), None, Some(now + Duration::days(30)), 2);
+        let urgent = Task::new("Urgent undated".to_string(), None, None, 2);
+        let meh = Task::new("Meh undated".to_string(), None, None, 1);
+        let mut done = Task::new("Done".to_string(), None, Some(now), 2);
+        done.completed = true;
+
+        let titles: Vec<String> = plan_tasks(vec![far, urgent, soon, meh, late, done], now)
+            .into_iter()
+            .map(|t| t.title)
+            .collect();
+        assert_eq!(titles, vec!["Late", "Soon", "Urgent undated"]);
+    }
+
+    #[test]
+    fn test_render_weekly_plan() {
+        let mut task = Task::new("Pay <rent>".to_string(), None, None, 2);
+        task.id = Some(4);
+        let email = PlanEmail {
+            to: Some("me@example.com"),
+            base_url: "https://todo.example",
+            secret: "secret",
+            now: Utc::now(),
+        };
+
+        let message = render_weekly_plan(&[task.clone()], &email).unwrap();
+        assert!(message.starts_with("To: me@example.com\r\nSubject: Weekly plan"));
+        let headers = message.split("\r\n\r\n").next().unwrap();
+        assert!(headers.is_ascii());
+        assert!(message.contains("Content-Type: text/html"));
+        assert!(message.contains("Pay &lt;rent&gt;"));
+        assert!(message.contains("https://todo.example/actions/4/complete?expires="));
+        assert!(message.contains("/actions/4/snooze?expires="));
+
+        let injected = PlanEmail {
+            to: Some("me@example.com\r\nBcc: them@example.com"),
+            ..email
+        };
+        assert!(render_weekly_plan(&[task], &injected).is_err());
+    }
+
+    #[test]
+    fn test_render_empty_plan() {
+        let email = PlanEmail {
+            to: None,
+            base_url: "https://todo.example",
+            secret: "secret",
+            now: Utc::now(),
+        };
+        let message = render_weekly_plan(&[], &email).unwrap();
+        assert!(message.starts_with("Subject:"));
+        assert!(message.contains("Nothing due this week"));
+    }
+}
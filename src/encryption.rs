@@ -0,0 +1,165 @@
+//! Encrypted task databases. Built with the `encryption` feature, todo uses
+//! SQLCipher, which encrypts every page of the database file with a key
+//! derived from a passphrase. `todo init --encrypted` encrypts the task
+//! database; afterwards the passphrase comes from `TODO_PASSPHRASE`, the
+//! system keyring (after `--keyring`), or a prompt.
+
+use anyhow::{Context, Result};
+use std::path::Path;
+
+use crate::db::Database;
+
+const KEYRING_SERVICE: &str = "todo-database";
+pub const PASSPHRASE_ENV: &str = "TODO_PASSPHRASE";
+
+/// The keyring entry holding the passphrase of the database at `path`.
+fn keyring_entry(path: &Path) -> Result<keyring::Entry> {
+    let path = std::path::absolute(path)?;
+    Ok(keyring::Entry::new(
+        KEYRING_SERVICE,
+        &path.display().to_string(),
+    )?)
+}
+
+pub fn save_to_keyring(path: &Path, passphrase: &str) -> Result<()> {
+    keyring_entry(path)?
+        .set_password(passphrase)
+        .context("Cannot store the passphrase in the system keyring")
+}
+
+/// The passphrase of the database at `path`: from `TODO_PASSPHRASE`, the
+/// keyring, or asked for when stdin is a terminal.
+pub fn passphrase(path: &Path) -> Result<String> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        return Ok(passphrase);
+    }
+    if let Ok(passphrase) = keyring_entry(path).and_then(|entry| Ok(entry.get_password()?)) {
+        return Ok(passphrase);
+    }
+    use std::io::IsTerminal;
+    if !std::io::stdin().is_terminal() {
+        return Err(anyhow::anyhow!(
+            "{} is encrypted. Set {} or store the passphrase with `todo init --encrypted --keyring`",
+            path.display(),
+            PASSPHRASE_ENV
+        ));
+    }
+    read_passphrase(&format!("Passphrase for {}: ", path.display()))
+}
+
+/// A new passphrase, from `TODO_PASSPHRASE` or typed twice.
+pub fn new_passphrase() -> Result<String> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        return Ok(passphrase);
+    }
+    let passphrase = read_passphrase("New passphrase: ")?;
+    if passphrase.is_empty() {
+        return Err(anyhow::anyhow!("The passphrase cannot be empty"));
+    }
+    if read_passphrase("Repeat it: ")? != passphrase {
+        return Err(anyhow::anyhow!("The passphrases do not match"));
+    }
+    Ok(passphrase)
+}
+
+/// Read a line from the terminal without echoing it.
+pub fn read_passphrase(prompt: &str) -> Result<String> {
+    use std::io::Write;
+    eprint!("{}", prompt);
+    std::io::stderr().flush()?;
+    let mut line = String::new();
+    with_echo_off(|| std::io::stdin().read_line(&mut line))?;
+    eprintln!();
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+#[cfg(unix)]
+fn with_echo_off<T>(read: impl FnOnce() -> std::io::Result<T>) -> std::io::Result<T> {
+    use std::os::fd::AsRawFd;
+    let fd = std::io::stdin().as_raw_fd();
+    // SAFETY: `termios` is plain data filled in by `tcgetattr`, and the
+    // settings are restored before returning.
+    unsafe {
+        let mut original: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(fd, &mut original) != 0 {
+            return read();
+        }
+        let mut silent = original;
+        silent.c_lflag &= !libc::ECHO;
+        libc::tcsetattr(fd, libc::TCSANOW, &silent);
+        let result = read();
+        libc::tcsetattr(fd, libc::TCSANOW, &original);
+        result
+    }
+}
+
+#[cfg(not(unix))]
+fn with_echo_off<T>(read: impl FnOnce() -> std::io::Result<T>) -> std::io::Result<T> {
+    read()
+}
+
+/// Encrypt the database at `path` with `passphrase`, in place. An empty or
+/// missing file becomes an empty encrypted database.
+pub fn encrypt(path: &Path, passphrase: &str) -> Result<()> {
+    let db = Database::new(path)?;
+    if !db.cipher_available() {
+        return Err(anyhow::anyhow!(
+            "This build of todo cannot encrypt databases. Rebuild it with `--features encryption`"
+        ));
+    }
+    if !db.is_readable() {
+        return Err(anyhow::anyhow!("{} is already encrypted", path.display()));
+    }
+
+    let encrypted = crate::files::sibling(path, "encrypted");
+    let _ = std::fs::remove_file(&encrypted);
+    db.export_encrypted(&encrypted, passphrase)
+        .with_context(|| format!("Cannot encrypt {}", path.display()))?;
+    drop(db);
+    std::fs::set_permissions(&encrypted, std::fs::metadata(path)?.permissions())?;
+    std::fs::rename(&encrypted, path)
+        .with_context(|| format!("Cannot replace {}", path.display()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tasks.db");
+        let db = Database::new(&path).unwrap();
+        db.init().unwrap();
+        let task = crate::models::Task::new("Secret plan".to_string(), None, None, 1);
+        db.add_task(&task).unwrap();
+        assert!(db.is_readable());
+        let available = db.cipher_available();
+        drop(db);
+
+        let result = encrypt(&path, "correct horse");
+        if !available {
+            assert!(result
+                .unwrap_err()
+                .to_string()
+                .contains("--features encryption"));
+            return;
+        }
+        result.unwrap();
+        assert!(encrypt(&path, "again").is_err());
+
+        let db = Database::new(&path).unwrap();
+        assert!(!db.is_readable());
+        db.unlock("wrong").unwrap();
+        assert!(!db.is_readable());
+
+        let db = Database::new(&path).unwrap();
+        db.unlock("correct horse").unwrap();
+        assert!(db.is_readable());
+        db.init().unwrap();
+        assert_eq!(db.get_task_by_id(1).unwrap().unwrap().title, "Secret plan");
+        let raw = std::fs::read(&path).unwrap();
+        assert!(!raw.windows(6).any(|window| window == b"Secret"));
+    }
+}
@@ -0,0 +1,122 @@
+//! Errors callers may want to tell apart, both library users matching on
+//! them and shell scripts branching on `todo`'s exit status. Anything else
+//! stays an `anyhow` message and exits with 1, and 2 is left to clap for a
+//! command line it can't parse.
+
+use crate::diagnostic::Diagnostic;
+
+/// Why a command or store call failed.
+#[derive(Debug)]
+pub enum TodoError {
+    /// No task has this ID, or it is in the trash.
+    NotFound(i32),
+    /// No project, goal or other named thing goes by this; the message says
+    /// which.
+    Missing(String),
+    /// A date that could not be understood, or that is not allowed where it
+    /// was given.
+    InvalidDate(Diagnostic),
+    /// Input refused, such as an empty title or a malformed tag.
+    Invalid(String),
+    /// Another process kept the database locked for longer than
+    /// `db::BUSY_TIMEOUT`.
+    DbLocked,
+    /// The database could not be read or written.
+    Storage(rusqlite::Error),
+}
+
+impl TodoError {
+    /// The process exit status `todo` ends with for this error.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            TodoError::NotFound(_) | TodoError::Missing(_) => 3,
+            TodoError::InvalidDate(_) | TodoError::Invalid(_) => 4,
+            TodoError::DbLocked => 5,
+            TodoError::Storage(_) => 6,
+        }
+    }
+
+    /// The exit status for `error`: that of the `TodoError` in its chain,
+    /// or of what it would have been for a bare diagnostic or SQLite error,
+    /// and 1 for anything else.
+    pub fn exit_code_of(error: &anyhow::Error) -> i32 {
+        if let Some(error) = error.downcast_ref::<TodoError>() {
+            return error.exit_code();
+        }
+        for cause in error.chain() {
+            if let Some(error) = cause.downcast_ref::<TodoError>() {
+                return error.exit_code();
+            }
+            if cause.is::<Diagnostic>() {
+                return 4;
+            }
+            if let Some(error) = cause.downcast_ref::<rusqlite::Error>() {
+                return if crate::db::is_locked(error) {
+                    TodoError::DbLocked.exit_code()
+                } else {
+                    6
+                };
+            }
+        }
+        1
+    }
+}
+
+impl std::fmt::Display for TodoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TodoError::NotFound(id) => write!(f, "Task with ID {} not found", id),
+            TodoError::Missing(what) => write!(f, "{}", what),
+            TodoError::InvalidDate(diagnostic) => write!(f, "{}", diagnostic),
+            TodoError::Invalid(reason) => write!(f, "{}", reason),
+            TodoError::DbLocked => write!(
+                f,
+                "Another todo process kept the task database locked for over {} seconds; try again once it has finished",
+                crate::db::BUSY_TIMEOUT.as_secs()
+            ),
+            TodoError::Storage(error) => write!(f, "Database error: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for TodoError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TodoError::Storage(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<rusqlite::Error> for TodoError {
+    fn from(error: rusqlite::Error) -> Self {
+        if crate::db::is_locked(&error) {
+            TodoError::DbLocked
+        } else {
+            TodoError::Storage(error)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_codes() {
+        let code = |error: anyhow::Error| TodoError::exit_code_of(&error);
+        assert_eq!(code(TodoError::NotFound(7).into()), 3);
+        assert_eq!(
+            code(anyhow::Error::from(TodoError::NotFound(7)).context("Cannot show task 7")),
+            3
+        );
+        assert_eq!(code(anyhow::anyhow!("x").context(TodoError::DbLocked)), 5);
+        let diagnostic = Diagnostic::new("repeat rule", "every", 5..5, "expected a unit".into());
+        assert_eq!(code(diagnostic.into()), 4);
+        let busy = rusqlite::Error::SqliteFailure(rusqlite::ffi::Error::new(5), None);
+        assert!(matches!(TodoError::from(busy), TodoError::DbLocked));
+        let corrupt = rusqlite::Error::SqliteFailure(rusqlite::ffi::Error::new(11), None);
+        assert_eq!(code(anyhow::Error::from(corrupt).context("Cannot list")), 6);
+        assert_eq!(code(anyhow::anyhow!("Something else")), 1);
+    }
+}
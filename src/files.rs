@@ -0,0 +1,123 @@
+use anyhow::{Context, Result};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// An exclusive advisory lock on a file, held until dropped.
+///
+/// The lock is taken on a hidden sibling (`.<name>.lock`) rather than the
+/// file itself, because `write_atomic` replaces the file's inode. Lock files
+/// are left in place; removing them would race with other waiters.
+pub struct FileLock {
+    _file: File,
+}
+
+impl FileLock {
+    /// Block until no other todo process holds the lock for `path`.
+    pub fn acquire(path: &Path) -> Result<Self> {
+        let lock_path = sibling(path, "lock");
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)
+            .with_context(|| format!("Cannot create lock file {}", lock_path.display()))?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::io::AsRawFd;
+            // SAFETY: the descriptor is owned by `file` and stays open for
+            // the lifetime of the lock.
+            if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+                return Err(std::io::Error::last_os_error())
+                    .with_context(|| format!("Cannot lock {}", path.display()));
+            }
+        }
+
+        Ok(Self { _file: file })
+    }
+}
+
+/// `.<name>.<suffix>` next to `path`.
+pub(crate) fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.{}", name, suffix))
+}
+
+/// Replace `path` with `contents` via a temporary file and a rename, so
+/// readers see either the old or the new file, never a partial one. The
+/// existing file's permissions are kept.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let staged = sibling(path, &format!("tmp-{}", std::process::id()));
+    let result = (|| {
+        let mut file = File::create(&staged)?;
+        if let Ok(metadata) = fs::metadata(path) {
+            file.set_permissions(metadata.permissions())?;
+        }
+        file.write_all(contents)?;
+        file.sync_all()?;
+        fs::rename(&staged, path)
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&staged);
+    }
+    result.with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// `write_atomic` under the file's advisory lock.
+pub fn write_locked(path: &Path, contents: &[u8]) -> Result<()> {
+    let _lock = FileLock::acquire(path)?;
+    write_atomic(path, contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_write_atomic_replaces_and_cleans_up() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("board.svg");
+        fs::write(&path, "old").unwrap();
+
+        write_locked(&path, b"new").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+
+        let mut names: Vec<String> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        assert_eq!(names, vec![".board.svg.lock", "board.svg"]);
+    }
+
+    #[test]
+    fn test_concurrent_writers_never_interleave() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("export.txt");
+
+        let writers: Vec<_> = (0..8u8)
+            .map(|n| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    let contents = vec![b'a' + n; 64 * 1024];
+                    // Threads share a process id, so each one stages under
+                    // the lock, as separate invocations would.
+                    write_locked(&path, &contents).unwrap();
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        let contents = fs::read(&path).unwrap();
+        assert_eq!(contents.len(), 64 * 1024);
+        assert!(contents.iter().all(|byte| *byte == contents[0]));
+    }
+}
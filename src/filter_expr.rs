@@ -0,0 +1,219 @@
+//! Filter expressions such as `priority=high due<today tag!=waiting`,
+//! which `--filter` on the bulk commands turns into a [`TaskFilter`].
+//! Every condition must hold:
+//!
+//! - `priority=LEVEL`
+//! - `tag=TAG` and `tag!=TAG`, with globs and nested tags as in `--tag`
+//! - `project=NAME` and `project!=NAME`
+//! - `due<WHEN`, `due<=WHEN`, `due>WHEN`, `due>=WHEN` and `due=WHEN`,
+//!   comparing whole days, and `due!=none` for tasks with a due date
+//! - `title~REGEX`
+//!
+//! Conditions are separated by spaces. A value with spaces in it goes in
+//! double quotes, as in `project="side work"`; in dates `_` stands for a
+//! space too, as in `due<next_friday`. `WHEN` is `YYYY-MM-DD` or a date
+//! such as `today`, `tomorrow` or `in_2_weeks`.
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+
+use crate::db::TaskFilter;
+use crate::store::TaskFilterBuilder;
+
+const OPERATORS: [&str; 7] = ["<=", ">=", "!=", "=", "<", ">", "~"];
+
+/// Parse `text` into a filter builder, so other criteria can be added.
+pub fn parse(text: &str, now: DateTime<Utc>) -> Result<TaskFilterBuilder> {
+    let mut builder = TaskFilter::builder();
+    // Due-date conditions narrow a single range
+    let mut from: Option<NaiveDate> = None;
+    let mut until: Option<NaiveDate> = None;
+    let mut narrow = |low: Option<NaiveDate>, high: Option<NaiveDate>| {
+        from = from.max(low);
+        until = match (until, high) {
+            (Some(until), Some(high)) => Some(until.min(high)),
+            (until, high) => until.or(high),
+        };
+    };
+
+    for word in words(text)? {
+        let word = word.as_str();
+        let (key, operator, value) = split(word)?;
+        let value = if key == "due" {
+            value.replace('_', " ")
+        } else {
+            value.to_string()
+        };
+        match (key, operator) {
+            ("priority", "=") => {
+                let priority = <crate::Priority as clap::ValueEnum>::from_str(&value, true)
+                    .map_err(|_| {
+                        anyhow::anyhow!("Invalid priority '{}': use low, medium or high", value)
+                    })?;
+                builder = builder.priority(priority);
+            }
+            ("tag", "=") => builder = builder.tag(&value),
+            ("tag", "!=") => builder = builder.exclude_tag(&value),
+            ("project", "=") => builder = builder.project(&value),
+            ("project", "!=") => builder = builder.exclude_project(&value),
+            ("title", "~") => builder = builder.title_matches(&value),
+            ("due", "!=") if value == "none" => builder = builder.exclude_undated(),
+            ("due", _) => {
+                let day = day(&value, now)?;
+                let next = day.succ_opt();
+                match operator {
+                    "<" => narrow(None, Some(day)),
+                    "<=" => narrow(None, next),
+                    ">" => narrow(next, None),
+                    ">=" => narrow(Some(day), None),
+                    "=" => narrow(Some(day), next),
+                    _ => return Err(unsupported(word)),
+                }
+            }
+            _ => return Err(unsupported(word)),
+        }
+    }
+
+    if from.is_some() || until.is_some() {
+        let start = crate::zone::start_of;
+        // Without an upper bound the range runs past any due date
+        let until = until.unwrap_or(NaiveDate::from_ymd_opt(9999, 12, 31).unwrap());
+        builder = builder.due_between(from.map(start), start(until));
+    }
+    Ok(builder)
+}
+
+/// The conditions in `text`: split at spaces outside double quotes, with the
+/// quotes removed.
+fn words(text: &str) -> Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut quoted = false;
+    for c in text.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+            }
+            c => word.push(c),
+        }
+    }
+    if quoted {
+        return Err(anyhow::anyhow!(
+            "Invalid filter '{}': a double quote is not closed",
+            text
+        ));
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+/// `word` as key, operator and value.
+fn split(word: &str) -> Result<(&str, &str, &str)> {
+    let invalid = || {
+        anyhow::anyhow!(
+            "Invalid condition '{}': write e.g. priority=high or due<today",
+            word
+        )
+    };
+    let at = word.find(['<', '>', '=', '!', '~']).ok_or_else(invalid)?;
+    let (key, rest) = word.split_at(at);
+    let operator = OPERATORS
+        .iter()
+        .find(|operator| rest.starts_with(**operator))
+        .ok_or_else(invalid)?;
+    let value = &rest[operator.len()..];
+    if key.is_empty() || value.is_empty() {
+        return Err(invalid());
+    }
+    Ok((key, operator, value))
+}
+
+fn unsupported(word: &str) -> anyhow::Error {
+    anyhow::anyhow!(
+        "Unsupported condition '{}': filter on priority, tag, project, due or title",
+        word
+    )
+}
+
+/// The day `text` names.
+fn day(text: &str, now: DateTime<Utc>) -> Result<NaiveDate> {
+    if let Ok(date) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        return Ok(date);
+    }
+    if text == "yesterday" {
+        return Ok(crate::zone::day_of(now) - Duration::days(1));
+    }
+    Ok(crate::zone::day_of(crate::dates::parse_relative(
+        text, now,
+    )?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::DueRange;
+
+    #[test]
+    fn test_parse_filter_expression() {
+        let now = DateTime::parse_from_rfc3339("2030-01-10T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let at = |date: &str| {
+            DateTime::parse_from_rfc3339(&format!("{}T00:00:00Z", date))
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+
+        let filter = parse("priority=HIGH due<today tag=work tag!=waiting", now)
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(filter.priority, Some(2));
+        assert_eq!(
+            filter.due_range,
+            Some(DueRange {
+                from: None,
+                until: at("2030-01-10"),
+            })
+        );
+        assert_eq!(
+            (filter.tags, filter.exclude_tags),
+            (vec!["work".to_string()], vec!["waiting".to_string()])
+        );
+
+        let filter = parse(r#"due>=2030-01-01 due<=tomorrow project!="side work""#, now)
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(
+            filter.due_range,
+            Some(DueRange {
+                from: Some(at("2030-01-01")),
+                until: at("2030-01-12"),
+            })
+        );
+        assert_eq!(filter.exclude_projects, ["side work"]);
+        let filter = parse("due=tomorrow", now).unwrap().build().unwrap();
+        assert_eq!(filter.due_range.unwrap().from, Some(at("2030-01-11")));
+
+        // `_` is only a space in dates
+        let filter = parse("tag=work_items project=side_work title~^to_do", now)
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(filter.tags, ["work_items"]);
+        assert_eq!(filter.project.as_deref(), Some("side_work"));
+        assert!(parse(r#"project="side work"#, now).is_err());
+
+        assert!(parse("priority=urgent", now).is_err());
+        assert!(parse("priority", now).is_err());
+        assert!(parse("due<someday", now).is_err());
+        assert!(parse("size>3", now).is_err());
+        assert!(parse("tag<work", now).is_err());
+    }
+}
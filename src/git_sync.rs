@@ -0,0 +1,693 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::db::Database;
+use crate::models::Task;
+use crate::progress::Progress;
+
+/// Actor recorded in the task history for changes made by a sync.
+pub const ACTOR: &str = "sync:git";
+/// Directory of the repository holding one file per task.
+pub const TASKS_DIR: &str = "tasks";
+/// Meta key holding the UUIDs of the tasks the last sync saw, one per line,
+/// so a missing file or task can be told apart from a new one.
+const META_SYNCED_UUIDS: &str = "git_sync_uuids";
+
+/// A task as written to `tasks/<uuid>.json`. Tasks refer to each other by
+/// UUID, since IDs differ from one database to the next. Notes, time
+/// estimates, attachments and time entries are not part of it and stay in
+/// the database they were added to.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SyncedTask {
+    pub uuid: String,
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub due_date: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_date: Option<DateTime<Utc>>,
+    /// `None` when the priority is inherited.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<i32>,
+    #[serde(default)]
+    pub completed: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// When the task was moved to the trash.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recurrence: Option<String>,
+    /// UUID of the parent task.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub energy: Option<crate::Energy>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub someday: bool,
+    /// UUIDs of the tasks this one waits for (`after:`), sorted.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub after: Vec<String>,
+}
+
+impl SyncedTask {
+    /// `task` as it is written out; `uuids` maps task IDs to UUIDs, and
+    /// `dependencies` task IDs to the IDs of the tasks they wait for.
+    pub fn from_task(
+        task: &Task,
+        deleted_at: Option<DateTime<Utc>>,
+        uuids: &HashMap<i32, String>,
+        dependencies: &HashMap<i32, Vec<i32>>,
+    ) -> Option<Self> {
+        let mut tags = task.tags.clone();
+        tags.sort();
+        let mut after: Vec<String> = task
+            .id
+            .and_then(|id| dependencies.get(&id))
+            .into_iter()
+            .flatten()
+            .filter_map(|other| uuids.get(other).cloned())
+            .collect();
+        after.sort();
+        Some(SyncedTask {
+            uuid: task.uuid.clone()?,
+            title: task.title.clone(),
+            description: task.description.clone(),
+            due_date: task.due_date,
+            start_date: task.start_date,
+            priority: task.own_priority(),
+            completed: task.completed,
+            completed_at: task.completed_at.filter(|_| task.completed),
+            created_at: task.created_at,
+            updated_at: task.updated_at,
+            deleted_at,
+            tags,
+            project: task.project.clone(),
+            recurrence: task.recurrence.clone(),
+            parent: task.parent_id.and_then(|id| uuids.get(&id).cloned()),
+            energy: task.energy,
+            someday: task.someday,
+            after,
+        })
+    }
+
+    /// The task to save, with `parent_id` looked up in `ids`, which maps
+    /// UUIDs to task IDs.
+    pub fn to_task(&self, ids: &HashMap<String, i32>) -> Task {
+        let mut task = Task::new(
+            self.title.clone(),
+            self.description.clone(),
+            self.due_date,
+            self.priority.unwrap_or(1),
+        );
+        task.uuid = Some(self.uuid.clone());
+        task.start_date = self.start_date;
+        task.priority_inherited = self.priority.is_none();
+        task.completed = self.completed;
+        task.completed_at = self.completed_at;
+        task.created_at = self.created_at;
+        task.updated_at = self.updated_at;
+        task.tags = self.tags.clone();
+        task.project = self.project.clone();
+        task.recurrence = self.recurrence.clone();
+        task.parent_id = self.parent.as_ref().and_then(|uuid| ids.get(uuid).copied());
+        task.energy = self.energy;
+        task.someday = self.someday;
+        task
+    }
+
+    /// When the task last changed, counting a move to the trash; the newer
+    /// side wins a conflict.
+    pub fn changed_at(&self) -> DateTime<Utc> {
+        self.deleted_at
+            .map_or(self.updated_at, |deleted| deleted.max(self.updated_at))
+    }
+
+    /// The file contents: pretty JSON with a trailing newline, the same
+    /// for the same task on any machine so unchanged tasks make no diff.
+    pub fn to_file(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)? + "\n")
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SyncReport {
+    /// Tasks added here from the repository.
+    pub imported: usize,
+    /// Tasks changed here because the repository had a newer version.
+    pub updated: usize,
+    /// Tasks deleted here because they were deleted for good elsewhere.
+    pub removed: usize,
+    /// Task files written or removed in the repository.
+    pub written: usize,
+    /// Whether the remote was pulled from and pushed to.
+    pub pushed: bool,
+    /// Why task files in the repository were left alone.
+    pub skipped: Vec<String>,
+}
+
+/// Two-way sync between the database and the git repository `repo`:
+/// local changes are written out and committed, `remote` is merged in when
+/// it exists and `online` is set, tasks changed on the other side are saved
+/// here when their copy is newer, and the result is committed and pushed.
+pub fn sync(
+    db: &Database,
+    repo: &Path,
+    remote: &str,
+    online: bool,
+    progress: &Progress,
+) -> Result<SyncReport> {
+    db.with_actor(ACTOR, || sync_repo(db, repo, remote, online, progress))
+}
+
+fn sync_repo(
+    db: &Database,
+    repo: &Path,
+    remote: &str,
+    online: bool,
+    progress: &Progress,
+) -> Result<SyncReport> {
+    git(repo, &["rev-parse", "--git-dir"]).map_err(|_| {
+        anyhow::anyhow!(
+            "{} is not a git repository; create it with `git init` or `git clone` first",
+            repo.display()
+        )
+    })?;
+    let dir = repo.join(TASKS_DIR);
+    fs::create_dir_all(&dir).with_context(|| format!("Cannot create {}", dir.display()))?;
+    let synced = synced_uuids(db)?;
+    let mut report = SyncReport::default();
+
+    progress.set_label("Writing tasks");
+    report.written += write_files(db, &dir, &synced)?;
+    commit(repo, "Update tasks")?;
+
+    let remote = (online && git(repo, &["remote", "get-url", remote]).is_ok()).then_some(remote);
+    let branch = git(repo, &["symbolic-ref", "--short", "HEAD"])?;
+    if let Some(remote) = remote {
+        crate::shutdown::check()?;
+        progress.set_label("Pulling");
+        git(repo, &["fetch", "--quiet", remote])?;
+        let tracking = format!("refs/remotes/{}/{}", remote, branch);
+        if git(repo, &["rev-parse", "--verify", "--quiet", &tracking]).is_ok() {
+            merge(repo, &tracking)?;
+        }
+    }
+
+    progress.set_label("Merging tasks");
+    let files = read_files(&dir, &mut report.skipped)?;
+    merge_into(db, &files, &synced, &mut report, progress)?;
+    report.written += write_files(db, &dir, &synced)?;
+    commit(repo, "Merge tasks")?;
+
+    if let Some(remote) = remote {
+        if git(repo, &["rev-parse", "--verify", "--quiet", "HEAD"]).is_ok() {
+            crate::shutdown::check()?;
+            progress.set_label("Pushing");
+            git(
+                repo,
+                &[
+                    "push",
+                    "--quiet",
+                    remote,
+                    &format!("HEAD:refs/heads/{}", branch),
+                ],
+            )?;
+        }
+        report.pushed = true;
+    }
+
+    let uuids: Vec<String> = db
+        .get_tasks_for_sync()?
+        .into_iter()
+        .filter_map(|(task, _)| task.uuid)
+        .collect();
+    db.set_meta(META_SYNCED_UUIDS, &uuids.join("\n"))?;
+    Ok(report)
+}
+
+fn synced_uuids(db: &Database) -> Result<HashSet<String>> {
+    Ok(db
+        .get_meta(META_SYNCED_UUIDS)?
+        .unwrap_or_default()
+        .lines()
+        .map(str::to_string)
+        .collect())
+}
+
+/// Whether `text` is a UUID such as `0123abcd-0000-4000-8000-000000000001`,
+/// which is all a task file may be named, so a file cannot send a write
+/// outside the repository.
+fn is_uuid(text: &str) -> bool {
+    text.len() == 36
+        && text.char_indices().all(|(index, c)| match index {
+            8 | 13 | 18 | 23 => c == '-',
+            _ => c.is_ascii_hexdigit(),
+        })
+}
+
+/// Every task file in `dir`, by UUID. Files whose name is not the UUID
+/// they hold are left out, with the reason added to `skipped`.
+pub fn read_files(dir: &Path, skipped: &mut Vec<String>) -> Result<HashMap<String, SyncedTask>> {
+    let mut files = HashMap::new();
+    for entry in fs::read_dir(dir).with_context(|| format!("Cannot read {}", dir.display()))? {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+        let text =
+            fs::read_to_string(&path).with_context(|| format!("Cannot read {}", path.display()))?;
+        let task: SyncedTask = serde_json::from_str(&text)
+            .with_context(|| format!("{} is not a task file", path.display()))?;
+        let stem = path.file_stem().and_then(|stem| stem.to_str());
+        if !is_uuid(&task.uuid) || stem != Some(task.uuid.as_str()) {
+            skipped.push(format!(
+                "{} skipped: its uuid {:?} is not a UUID matching the file name",
+                path.display(),
+                task.uuid
+            ));
+            continue;
+        }
+        files.insert(task.uuid.clone(), task);
+    }
+    Ok(files)
+}
+
+/// Write the file of every task in the database whose contents changed,
+/// and remove the files of tasks deleted for good since the last sync.
+/// Returns how many files were written or removed.
+fn write_files(db: &Database, dir: &Path, synced: &HashSet<String>) -> Result<usize> {
+    let tasks = db.get_tasks_for_sync()?;
+    let uuids: HashMap<i32, String> = tasks
+        .iter()
+        .filter_map(|(task, _)| Some((task.id?, task.uuid.clone()?)))
+        .collect();
+    let mut dependencies: HashMap<i32, Vec<i32>> = HashMap::new();
+    for (id, other) in db.get_dependency_pairs()? {
+        dependencies.entry(id).or_default().push(other);
+    }
+    let mut written = 0;
+    for (task, deleted_at) in &tasks {
+        crate::shutdown::check()?;
+        let Some(synced_task) = SyncedTask::from_task(task, *deleted_at, &uuids, &dependencies)
+            .filter(|synced_task| is_uuid(&synced_task.uuid))
+        else {
+            continue;
+        };
+        let path = task_file(dir, &synced_task.uuid);
+        let contents = synced_task.to_file()?;
+        if fs::read_to_string(&path).ok().as_deref() != Some(contents.as_str()) {
+            crate::files::write_atomic(&path, contents.as_bytes())?;
+            written += 1;
+        }
+    }
+    let here: HashSet<&String> = uuids.values().collect();
+    // The merge reads them again and reports the skipped ones
+    for uuid in read_files(dir, &mut Vec::new())?.keys() {
+        if synced.contains(uuid) && !here.contains(uuid) {
+            let path = task_file(dir, uuid);
+            fs::remove_file(&path).with_context(|| format!("Cannot remove {}", path.display()))?;
+            written += 1;
+        }
+    }
+    Ok(written)
+}
+
+fn task_file(dir: &Path, uuid: &str) -> PathBuf {
+    dir.join(format!("{}.json", uuid))
+}
+
+/// Save the tasks of `files` that are new or newer than the database's
+/// copy, and delete for good the tasks whose file was removed elsewhere.
+/// Tasks deleted for good here since the last sync are not brought back.
+/// Dependencies are only ever added, as they are when a task is, and
+/// follow once both tasks are here.
+pub fn merge_into(
+    db: &Database,
+    files: &HashMap<String, SyncedTask>,
+    synced: &HashSet<String>,
+    report: &mut SyncReport,
+    progress: &Progress,
+) -> Result<()> {
+    let local = db.get_tasks_for_sync()?;
+    let mut ids: HashMap<String, i32> = local
+        .iter()
+        .filter_map(|(task, _)| Some((task.uuid.clone()?, task.id?)))
+        .collect();
+    let changed_at: HashMap<String, DateTime<Utc>> = local
+        .iter()
+        .filter_map(|(task, deleted_at)| {
+            let changed = deleted_at.map_or(task.updated_at, |d| d.max(task.updated_at));
+            Some((task.uuid.clone()?, changed))
+        })
+        .collect();
+
+    let mut uuids: Vec<&String> = files.keys().collect();
+    uuids.sort();
+    let mut saved = Vec::new();
+    for uuid in uuids {
+        crate::shutdown::check()?;
+        progress.inc(1);
+        let file = &files[uuid];
+        match ids.get(uuid) {
+            Some(&id) if file.changed_at() > changed_at[uuid] => {
+                db.save_synced_task(Some(id), &file.to_task(&ids), file.deleted_at)?;
+                report.updated += 1;
+                saved.push(file);
+            }
+            None if !synced.contains(uuid) => {
+                let id = db.save_synced_task(None, &file.to_task(&ids), file.deleted_at)?;
+                ids.insert(uuid.clone(), id);
+                report.imported += 1;
+                saved.push(file);
+            }
+            _ => {}
+        }
+    }
+    // Parents saved after their subtasks have an ID by now
+    for file in saved {
+        if let Some(parent) = file.parent.as_ref().filter(|p| ids.contains_key(*p)) {
+            let task = db.get_task_with_trashed(ids[&file.uuid])?;
+            if task.is_some_and(|task| task.parent_id != Some(ids[parent])) {
+                db.save_synced_task(Some(ids[&file.uuid]), &file.to_task(&ids), file.deleted_at)?;
+            }
+        }
+    }
+
+    let existing: HashSet<(i32, i32)> = db.get_dependency_pairs()?.into_iter().collect();
+    for file in files.values() {
+        let Some(&id) = ids.get(&file.uuid) else {
+            continue;
+        };
+        for other in file.after.iter().filter_map(|uuid| ids.get(uuid)) {
+            // A dependency the other way round would make both wait forever
+            if !existing.contains(&(id, *other)) && id != *other && !db.depends_on(*other, id)? {
+                db.add_dependency(id, *other)?;
+            }
+        }
+    }
+
+    let removed: Vec<i32> = local
+        .iter()
+        .filter_map(|(task, _)| {
+            let uuid = task.uuid.as_ref()?;
+            (synced.contains(uuid) && !files.contains_key(uuid)).then_some(task.id?)
+        })
+        .collect();
+    if !removed.is_empty() {
+        db.purge_tasks(&removed)?;
+        report.removed += removed.len();
+    }
+    Ok(())
+}
+
+/// Merge `tracking` into the checked-out branch. Files both sides changed
+/// take the remote's version; the newer task wins afterwards anyway, when
+/// the database is merged in.
+fn merge(repo: &Path, tracking: &str) -> Result<()> {
+    if git(
+        repo,
+        &[
+            "merge",
+            "--quiet",
+            "--no-edit",
+            "--allow-unrelated-histories",
+            "-X",
+            "theirs",
+            tracking,
+        ],
+    )
+    .is_ok()
+    {
+        return Ok(());
+    }
+    // Changed on one side and deleted on the other: the remote wins here too
+    let conflicts = git(repo, &["diff", "--name-only", "--diff-filter=U"])?;
+    for file in conflicts.lines() {
+        if git(repo, &["checkout", "--theirs", "--", file]).is_ok() {
+            git(repo, &["add", "--", file])?;
+        } else {
+            git(repo, &["rm", "--quiet", "--", file])?;
+        }
+    }
+    if let Err(error) = git(repo, &["commit", "--quiet", "--no-edit"]) {
+        let _ = git(repo, &["merge", "--abort"]);
+        return Err(error.context(format!("Cannot merge {}", tracking)));
+    }
+    Ok(())
+}
+
+/// Commit the task files, if anything changed.
+fn commit(repo: &Path, message: &str) -> Result<()> {
+    git(repo, &["add", "--all", "--", TASKS_DIR])?;
+    let status = git(repo, &["status", "--porcelain", "--", TASKS_DIR])?;
+    if !status.is_empty() {
+        git(repo, &["commit", "--quiet", "-m", message, "--", TASKS_DIR])?;
+    }
+    Ok(())
+}
+
+/// Run git in `repo`, returning its trimmed output.
+fn git(repo: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(args)
+        .output()
+        .context("Cannot run git; is it installed?")?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "`git {}` failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::create_test_db;
+    use tempfile::TempDir;
+
+    fn run_git(dir: &Path, args: &[&str]) {
+        git(dir, args).unwrap();
+    }
+
+    /// A bare repository and two clones of it, one per machine.
+    fn create_repos() -> (TempDir, PathBuf, PathBuf) {
+        let root = TempDir::new().unwrap();
+        let remote = root.path().join("remote.git");
+        run_git(root.path(), &["init", "--quiet", "--bare", "remote.git"]);
+        let clones: Vec<PathBuf> = ["one", "two"]
+            .iter()
+            .map(|name| {
+                run_git(
+                    root.path(),
+                    &["clone", "--quiet", remote.to_str().unwrap(), name],
+                );
+                let clone = root.path().join(name);
+                run_git(&clone, &["config", "user.name", "Test"]);
+                run_git(&clone, &["config", "user.email", "test@example.com"]);
+                run_git(&clone, &["symbolic-ref", "HEAD", "refs/heads/main"]);
+                clone
+            })
+            .collect();
+        (root, clones[0].clone(), clones[1].clone())
+    }
+
+    fn sync_now(db: &Database, repo: &Path) -> SyncReport {
+        sync(db, repo, "origin", true, &Progress::hidden()).unwrap()
+    }
+
+    fn titles(db: &Database) -> Vec<String> {
+        let mut titles: Vec<String> = db
+            .get_tasks(&crate::db::TaskFilter::any_status())
+            .unwrap()
+            .into_iter()
+            .map(|task| task.title)
+            .collect();
+        titles.sort();
+        titles
+    }
+
+    #[test]
+    fn test_synced_task_round_trip() {
+        let mut task = Task::new("Write report".to_string(), None, None, 2);
+        task.uuid = Some("0123abcd-0000-4000-8000-000000000001".to_string());
+        task.tags = vec!["work".to_string(), "alpha".to_string()];
+        let synced = SyncedTask::from_task(&task, None, &HashMap::new(), &HashMap::new()).unwrap();
+        assert_eq!(synced.tags, vec!["alpha", "work"]);
+
+        let file = synced.to_file().unwrap();
+        assert!(!file.contains("deleted_at"));
+        let read: SyncedTask = serde_json::from_str(&file).unwrap();
+        assert_eq!(read, synced);
+        assert_eq!(read.to_task(&HashMap::new()).priority, 2);
+    }
+
+    #[test]
+    fn test_sync_between_two_databases() {
+        let (_root, one, two) = create_repos();
+        let (db_one, _file_one) = create_test_db();
+        let (db_two, _file_two) = create_test_db();
+
+        let parent = db_one
+            .add_task(&Task::new("Plan trip".to_string(), None, None, 1))
+            .unwrap();
+        let mut subtask = Task::new("Book flights".to_string(), None, None, 2);
+        subtask.parent_id = Some(parent);
+        db_one.add_task(&subtask).unwrap();
+        let report = sync_now(&db_one, &one);
+        assert_eq!(report.written, 2);
+        assert!(report.pushed);
+
+        let report = sync_now(&db_two, &two);
+        assert_eq!(report.imported, 2);
+        assert_eq!(titles(&db_two), vec!["Book flights", "Plan trip"]);
+        let flights = db_two
+            .get_tasks(&crate::db::TaskFilter::any_status())
+            .unwrap()
+            .into_iter()
+            .find(|task| task.title == "Book flights")
+            .unwrap();
+        let parent_two = db_two.get_task_by_id(flights.parent_id.unwrap()).unwrap();
+        assert_eq!(parent_two.unwrap().title, "Plan trip");
+
+        // A change on one side reaches the other, and syncing again is quiet
+        let id = flights.id.unwrap();
+        let mut edited = flights.clone();
+        edited.title = "Book trains".to_string();
+        db_two.edit_task(id, &edited).unwrap();
+        sync_now(&db_two, &two);
+        let report = sync_now(&db_one, &one);
+        assert_eq!(report.updated, 1);
+        assert_eq!(titles(&db_one), vec!["Book trains", "Plan trip"]);
+        let report = sync_now(&db_one, &one);
+        assert_eq!((report.updated, report.written), (0, 0));
+
+        // Emptying the trash on one side deletes the task on the other
+        db_one.delete_task(parent).unwrap();
+        db_one.empty_trash().unwrap();
+        sync_now(&db_one, &one);
+        let report = sync_now(&db_two, &two);
+        assert_eq!(report.removed, 1);
+        assert_eq!(titles(&db_two), vec!["Book trains"]);
+    }
+
+    #[test]
+    fn test_sync_keeps_dependencies() {
+        let (_root, one, two) = create_repos();
+        let (db_one, _file_one) = create_test_db();
+        let (db_two, _file_two) = create_test_db();
+        let build = db_one
+            .add_task(&Task::new("Build app".to_string(), None, None, 1))
+            .unwrap();
+        let deploy = db_one
+            .add_task(&Task::new("Deploy app".to_string(), None, None, 1))
+            .unwrap();
+        db_one.add_dependency(deploy, build).unwrap();
+        sync_now(&db_one, &one);
+        sync_now(&db_two, &two);
+
+        let find = |title: &str| {
+            db_two
+                .get_tasks(&crate::db::TaskFilter::any_status())
+                .unwrap()
+                .into_iter()
+                .find(|task| task.title == title)
+                .unwrap()
+                .id
+                .unwrap()
+        };
+        let waits_for: Vec<String> = db_two
+            .get_dependencies(find("Deploy app"))
+            .unwrap()
+            .into_iter()
+            .map(|task| task.title)
+            .collect();
+        assert_eq!(waits_for, ["Build app"]);
+        assert!(db_two
+            .get_dependencies(find("Build app"))
+            .unwrap()
+            .is_empty());
+
+        // Syncing back changes nothing
+        let report = sync_now(&db_one, &one);
+        assert_eq!((report.updated, report.written), (0, 0));
+        assert_eq!(db_one.get_dependency_pairs().unwrap(), [(deploy, build)]);
+    }
+
+    #[test]
+    fn test_newer_edit_wins_conflict() {
+        let (_root, one, two) = create_repos();
+        let (db_one, _file_one) = create_test_db();
+        let (db_two, _file_two) = create_test_db();
+        let id = db_one
+            .add_task(&Task::new("Draft".to_string(), None, None, 1))
+            .unwrap();
+        sync_now(&db_one, &one);
+        sync_now(&db_two, &two);
+        let id_two = db_two.task_ids().unwrap()[0];
+
+        let mut task = db_one.get_task_by_id(id).unwrap().unwrap();
+        task.title = "Older edit".to_string();
+        db_one.edit_task(id, &task).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        let mut task = db_two.get_task_by_id(id_two).unwrap().unwrap();
+        task.title = "Newer edit".to_string();
+        db_two.edit_task(id_two, &task).unwrap();
+
+        sync_now(&db_two, &two);
+        sync_now(&db_one, &one);
+        sync_now(&db_two, &two);
+        assert_eq!(titles(&db_one), vec!["Newer edit"]);
+        assert_eq!(titles(&db_two), vec!["Newer edit"]);
+    }
+
+    #[test]
+    fn test_task_files_must_be_named_by_uuid() {
+        let (_root, one, _two) = create_repos();
+        let (db, _file) = create_test_db();
+        let dir = one.join(TASKS_DIR);
+        fs::create_dir_all(&dir).unwrap();
+        let mut task = Task::new("Escape".to_string(), None, None, 1);
+        task.uuid = Some("../../x".to_string());
+        let file = SyncedTask::from_task(&task, None, &HashMap::new(), &HashMap::new())
+            .unwrap()
+            .to_file()
+            .unwrap();
+        fs::write(dir.join("evil.json"), &file).unwrap();
+        let uuid = "0123abcd-0000-4000-8000-000000000001";
+        let renamed = file.replace("../../x", uuid);
+        fs::write(dir.join("other.json"), renamed).unwrap();
+
+        let report = sync_now(&db, &one);
+        assert_eq!(report.imported, 0);
+        assert_eq!(report.skipped.len(), 2);
+        assert!(report.skipped[0].contains("is not a UUID"));
+        assert!(titles(&db).is_empty());
+        assert!(!one.parent().unwrap().join("x.json").exists());
+    }
+
+    #[test]
+    fn test_sync_needs_a_repository() {
+        let dir = TempDir::new().unwrap();
+        let (db, _file) = create_test_db();
+        let error = sync(&db, dir.path(), "origin", true, &Progress::hidden()).unwrap_err();
+        assert!(error.to_string().contains("not a git repository"));
+    }
+}
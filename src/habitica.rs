@@ -0,0 +1,196 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use crate::models::Task;
+
+const API_BASE: &str = "https://habitica.com/api/v3";
+const KEYRING_SERVICE: &str = "todo-habitica";
+
+/// Source name used when linking imported tasks back to Habitica.
+pub const LINK_SOURCE: &str = "habitica";
+/// Actor recorded in the task history for imported tasks.
+pub const ACTOR: &str = "sync:habitica";
+/// Environment variable the API token can be given in, instead of typing it.
+pub const TOKEN_ENV: &str = "TODO_HABITICA_TOKEN";
+
+pub struct Credentials {
+    pub user_id: String,
+    pub api_token: String,
+}
+
+impl Credentials {
+    /// Load credentials previously stored with `save`.
+    pub fn load() -> Result<Self> {
+        let read = |key: &str| -> Result<String> {
+            keyring::Entry::new(KEYRING_SERVICE, key)?
+                .get_password()
+                .with_context(|| {
+                    format!(
+                        "No Habitica {} in the keyring. Pass --habitica-user once to store your credentials",
+                        key
+                    )
+                })
+        };
+
+        Ok(Self {
+            user_id: read("user_id")?,
+            api_token: read("api_token")?,
+        })
+    }
+
+    /// The API token, from `TODO_HABITICA_TOKEN` or typed without echo, so
+    /// it shows up in neither `ps` nor the shell history.
+    pub fn read_token() -> Result<String> {
+        let token = match std::env::var(TOKEN_ENV) {
+            Ok(token) => token,
+            Err(_) => crate::encryption::read_passphrase("Habitica API token: ")?,
+        };
+        let token = token.trim().to_string();
+        if token.is_empty() {
+            return Err(anyhow::anyhow!("The Habitica API token cannot be empty"));
+        }
+        Ok(token)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        keyring::Entry::new(KEYRING_SERVICE, "user_id")?.set_password(&self.user_id)?;
+        keyring::Entry::new(KEYRING_SERVICE, "api_token")?.set_password(&self.api_token)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiResponse {
+    success: bool,
+    #[serde(default)]
+    data: Vec<HabiticaTask>,
+    message: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HabiticaTask {
+    pub id: String,
+    pub text: String,
+    #[serde(default)]
+    pub notes: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// Difficulty: 0.1 trivial, 1 easy, 1.5 medium, 2 hard.
+    #[serde(default = "default_difficulty")]
+    pub priority: f64,
+    /// Due date, only set on to-dos.
+    pub date: Option<String>,
+    #[serde(default)]
+    pub completed: bool,
+}
+
+fn default_difficulty() -> f64 {
+    1.0
+}
+
+impl HabiticaTask {
+    pub fn is_daily(&self) -> bool {
+        self.kind == "daily"
+    }
+
+    pub fn to_task(&self) -> Task {
+        let priority = if self.priority >= 2.0 {
+            2
+        } else if self.priority >= 1.5 {
+            1
+        } else {
+            0
+        };
+
+        let due_date = self
+            .date
+            .as_deref()
+            .and_then(|date| DateTime::parse_from_rfc3339(date).ok())
+            .map(|dt| dt.with_timezone(&Utc));
+
+        let description = if self.notes.trim().is_empty() {
+            None
+        } else {
+            Some(self.notes.clone())
+        };
+
+        let mut task = Task::new(self.text.clone(), description, due_date, priority);
+        task.tags.push(LINK_SOURCE.to_string());
+        if self.is_daily() {
+            task.tags.push("habitica-daily".to_string());
+            task.recurrence = Some("daily".to_string());
+        }
+        task
+    }
+}
+
+/// Fetch the user's to-dos and dailies.
+pub fn fetch_tasks(credentials: &Credentials) -> Result<Vec<HabiticaTask>> {
+    let mut tasks = Vec::new();
+    for kind in ["todos", "dailys"] {
+        let response: ApiResponse = ureq::get(&format!("{}/tasks/user", API_BASE))
+            .query("type", kind)
+            .set("x-api-user", &credentials.user_id)
+            .set("x-api-key", &credentials.api_token)
+            .set("x-client", &format!("{}-todo-cli", credentials.user_id))
+            .call()
+            .context("Failed to reach the Habitica API")?
+            .into_json()
+            .context("Unexpected response from the Habitica API")?;
+
+        if !response.success {
+            return Err(anyhow::anyhow!(
+                "Habitica API error: {}",
+                response.message.unwrap_or_default()
+            ));
+        }
+        tasks.extend(response.data);
+    }
+    Ok(tasks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = r#"{
+        "success": true,
+        "data": [
+            {"id": "a1", "text": "File taxes", "notes": "Before April", "type": "todo",
+             "priority": 2, "date": "2099-04-15T00:00:00.000Z", "completed": false},
+            {"id": "b2", "text": "Stretch", "notes": "", "type": "daily", "priority": 0.1}
+        ]
+    }"#;
+
+    #[test]
+    fn test_parse_response() {
+        let response: ApiResponse = serde_json::from_str(FIXTURE).unwrap();
+        assert!(response.success);
+        assert_eq!(response.data.len(), 2);
+        assert!(response.data[1].is_daily());
+    }
+
+    #[test]
+    fn test_todo_mapping() {
+        let response: ApiResponse = serde_json::from_str(FIXTURE).unwrap();
+        let task = response.data[0].to_task();
+
+        assert_eq!(task.title, "File taxes");
+        assert_eq!(task.description, Some("Before April".to_string()));
+        assert_eq!(task.priority, 2);
+        assert_eq!(task.due_date_text(), "2099-04-15");
+        assert_eq!(task.tags, vec![LINK_SOURCE]);
+    }
+
+    #[test]
+    fn test_daily_mapping() {
+        let response: ApiResponse = serde_json::from_str(FIXTURE).unwrap();
+        let task = response.data[1].to_task();
+
+        assert_eq!(task.priority, 0); // trivial
+        assert_eq!(task.description, None);
+        assert!(task.tags.contains(&"habitica-daily".to_string()));
+        assert_eq!(task.recurrence.as_deref(), Some("daily"));
+    }
+}
@@ -0,0 +1,251 @@
+use anyhow::Result;
+use clap::CommandFactory;
+
+/// A help topic: name, one-line summary and body.
+pub struct Topic {
+    pub name: &'static str,
+    pub summary: &'static str,
+    pub body: &'static str,
+}
+
+/// Topics that span several commands. Extend this as the filter, date and
+/// config syntaxes grow.
+pub const TOPICS: &[Topic] = &[
+    Topic {
+        name: "filters",
+        summary: "Narrowing down `todo list`",
+        body: "\
+Filters on `todo list` combine with AND.
+
+  --priority LEVEL       only low, medium or high tasks
+  --due-soon             pending tasks due within --due-soon-hours (default 48)
+  --tag TAG              tasks carrying TAG or a tag nested beneath it;
+                         globs are allowed (`work/*`); repeat to require several
+  --not-tag TAG          hide tasks carrying TAG or anything beneath it
+  --title-matches REGEX  title must match the regular expression
+  --no-due               hide tasks without a due date
+  --project NAME         only tasks in project NAME
+  --not-project NAME     hide tasks in project NAME; repeatable
+  --completed            include completed tasks
+  --someday              show the someday/maybe bucket instead
+  --group-by project     group the plain output by project
+
+Example:
+  todo list --tag work --not-tag work/clients --title-matches '^Fix'
+
+`complete`, `update` and `delete` pick pending tasks with --filter EXPR, a
+list of conditions that must all hold:
+
+  priority=LEVEL               low, medium or high
+  tag=TAG, tag!=TAG            as --tag and --not-tag
+  project=NAME, project!=NAME  as --project and --not-project
+  due<WHEN, due<=WHEN          due before, or on or before, that day
+  due>WHEN, due>=WHEN          due after, or on or after, that day
+  due=WHEN                     due that day
+  due!=none                    has a due date
+  title~REGEX                  as --title-matches
+
+WHEN is a date as in `todo help dates`, or yesterday; `_` stands for a
+space there, as in due<next_friday. Other values with spaces go in double
+quotes, as in project=\"side work\". At a terminal, the tasks are listed
+for confirmation first; --yes skips that.
+
+Example:
+  todo complete --filter \"priority=low due<today tag=errands\"",
+    },
+    Topic {
+        name: "dates",
+        summary: "Date formats accepted by --due and --by",
+        body: "\
+Dates are given as YYYY-MM-DD (the end of that day), as a full RFC 3339
+timestamp such as 2099-03-01T09:30:00+01:00, or as an expression:
+
+  tomorrow                  the last second of tomorrow
+  friday, next fri          the next Friday after today
+  next week/month/year      the same day a week/month/year from now
+  in 3 days, in 2 weeks     counted in whole days from today
+  in 2 hours, in 30 mins    counted exactly from now
+  eod, today                the last second of today
+
+Days are those of the `timezone` zone.
+
+Due dates and goal target dates must lie in the future.
+
+Examples:
+  todo add \"File taxes\" --due 2099-04-15
+  todo add \"Call the plumber\" --due \"next tuesday\"
+  todo update 3 --due eod
+  todo goal add \"Run a 10k\" --by \"in 3 months\"",
+    },
+    Topic {
+        name: "tagging",
+        summary: "Hierarchical tags",
+        body: "\
+Tags are attached with --tag and may be nested with `/`, e.g.
+work/clients/acme. Filtering by a tag also matches everything nested
+beneath it, and `todo tags` shows the tree with pending counts.
+
+Tags may not contain whitespace or commas, and levels may not be empty.
+Maintenance uses a few tags itself: #stale for untouched tasks, and
+#someday / #maybe mark candidates for priority decay.",
+    },
+    Topic {
+        name: "environment",
+        summary: "Environment variable overrides",
+        body: "\
+Every config key can be overridden with TODO_ and the key in upper case.
+The environment wins over the config files; command-line flags win over
+both. Booleans accept true/false, 1/0, yes/no and on/off.
+
+TODO_DEFAULT_PRIORITY      priority for `todo add` (medium)
+TODO_DUE_SOON_HOURS        size of the \"due soon\" window in hours (48)
+TODO_MAINTENANCE_INTERVAL  minutes between automatic maintenance runs (60)
+TODO_STALE_TAGGING         tag tasks untouched for 30 days as stale (false)
+TODO_PRIORITY_DECAY        decay snoozed someday/maybe tasks (false)
+TODO_USAGE_METRICS         record command usage locally (false)
+TODO_COMMAND_HISTORY       remember commands for `todo history` (false)
+TODO_COLOR                 use colors in terminal output (true)
+TODO_COLOR_SCHEME          default, colorblind or mono (default)
+TODO_PLAIN                 plain output: no color, emoji or box drawing (false)
+TODO_DATE_FORMAT           strftime format for due dates (%Y-%m-%d)
+TODO_WEEK_START            first day of the week in calendars and reports (monday)
+TODO_TIMEZONE              zone for due dates: local, utc or +05:30 (local)
+TODO_LIST_FILTER           flags every `todo list` starts from
+TODO_LOG_FILE              activity log shown by `todo logs` (off)
+TODO_LOG_MAX_KB            rotate the log past this size in KiB (1024)
+TODO_LOG_MAX_DAYS          rotate the log once it is this many days old (7)
+TODO_WIP_LIMIT_TODO        most tasks in the board's To do column (0, none)
+TODO_WIP_LIMIT_IN_PROGRESS most tasks in the board's In progress column (3)
+TODO_DIGEST_LOW_MINUTES    minutes `todo notify` gathers low reminders (240)
+TODO_DIGEST_MEDIUM_MINUTES minutes `todo notify` gathers medium reminders (60)
+TODO_DIGEST_HIGH_MINUTES   minutes `todo notify` gathers high reminders (0, none)
+TODO_TUI_MOUSE             mouse clicks and scrolling in `todo tui` (true)
+TODO_URGENCY_PRIORITY      weight of priority in `todo next` urgency (6)
+TODO_URGENCY_DUE           weight of the due date in `todo next` urgency (12)
+TODO_URGENCY_AGE           weight of task age in `todo next` urgency (2)
+TODO_URGENCY_ENERGY        weight of an energy match in `todo next` urgency (3)
+TODO_URGENCY_FORMULA       expression `todo next` ranks by instead (unset)
+TODO_ADJUST_SUBTASK_DUE    move subtask due dates up to an earlier parent's (false)
+TODO_AUTO_COMPLETE_PARENTS complete a task when its last subtask is (false)
+TODO_INHERIT_PRIORITY      new subtasks and project tasks inherit priority (false)
+TODO_WORKDAY_HOURS         hours of work a day for `todo schedule` (6)
+TODO_SQLITE_EXTENSIONS     SQLite extensions to load, `:`-separated
+TODO_ALLOW_EXTENSIONS      load sqlite_extensions (false)
+TODO_SYNC_REPO             git repository `todo sync` keeps tasks in
+TODO_SYNC_REMOTE           remote of the sync repository (origin)
+TODO_DB                    path of the task database (~/.todo.db)
+TODO_CONTEXT               tag that `list` filters by and `add` attaches
+TODO_PROFILE               profile to apply; --profile wins over it
+TODO_PASSPHRASE            passphrase of an encrypted database
+TODO_HABITICA_TOKEN        Habitica API token for `todo import --from habitica`
+
+`todo config sources` shows where each effective value came from.",
+    },
+];
+
+pub fn find_topic(name: &str) -> Option<&'static Topic> {
+    TOPICS.iter().find(|topic| topic.name == name)
+}
+
+/// `todo help [TOPIC|COMMAND]`: topics first, then per-command help.
+pub fn print_help(topic: Option<&str>) -> Result<()> {
+    let mut cli = crate::Cli::command();
+    cli.build();
+
+    let Some(name) = topic else {
+        cli.print_help()?;
+        println!("\nHelp topics (todo help <topic>):");
+        for topic in TOPICS {
+            println!("  {:<12} {}", topic.name, topic.summary);
+        }
+        return Ok(());
+    };
+
+    if let Some(topic) = find_topic(name) {
+        println!("{}", topic.body);
+        return Ok(());
+    }
+    match cli.find_subcommand_mut(name) {
+        Some(command) => {
+            command.print_long_help()?;
+            Ok(())
+        }
+        None => Err(anyhow::anyhow!(
+            "No help for '{}'. Topics: {}",
+            name,
+            TOPICS
+                .iter()
+                .map(|topic| topic.name)
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+    }
+}
+
+/// Render the `todo(1)` man page, with the help topics appended as extra
+/// sections.
+pub fn render_man_page(out: &mut impl std::io::Write) -> Result<()> {
+    let man = clap_mangen::Man::new(crate::Cli::command());
+    man.render(out)?;
+
+    for topic in TOPICS {
+        writeln!(out, ".SH \"{}\"", topic.name.to_uppercase())?;
+        writeln!(out, ".nf")?;
+        for line in topic.body.lines() {
+            // Lines starting with a dot or quote would be read as requests.
+            let escaped = line.replace('\\', "\\e");
+            if escaped.starts_with('.') || escaped.starts_with('\'') {
+                writeln!(out, "\\&{}", escaped)?;
+            } else {
+                writeln!(out, "{}", escaped)?;
+            }
+        }
+        writeln!(out, ".fi")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_topics_are_unique_and_not_commands() {
+        let cli = crate::Cli::command();
+        for (index, topic) in TOPICS.iter().enumerate() {
+            assert!(find_topic(topic.name).is_some());
+            assert!(TOPICS[index + 1..].iter().all(|t| t.name != topic.name));
+            assert!(
+                cli.find_subcommand(topic.name).is_none(),
+                "topic '{}' shadows a command",
+                topic.name
+            );
+        }
+    }
+
+    #[test]
+    fn test_environment_topic_lists_every_key() {
+        let body = find_topic("environment").unwrap().body;
+        for key in crate::config::KEYS {
+            assert!(body.contains(&crate::config::env_var(key.name)));
+        }
+    }
+
+    #[test]
+    fn test_render_man_page() {
+        let mut out = Vec::new();
+        render_man_page(&mut out).unwrap();
+        let page = String::from_utf8(out).unwrap();
+
+        assert!(page.contains(".TH todo"));
+        assert!(page.contains("status\\-line"));
+        assert!(page.contains(".SH \"FILTERS\""));
+        assert!(page.contains("TODO_MAINTENANCE_INTERVAL"));
+    }
+
+    #[test]
+    fn test_unknown_topic() {
+        assert!(print_help(Some("nonsense")).is_err());
+        assert!(print_help(Some("dates")).is_ok());
+    }
+}
@@ -0,0 +1,201 @@
+//! Lifecycle hooks: executables in `~/.config/todo/hooks/` that run after a
+//! command adds, completes, deletes or updates tasks. A hook is named after
+//! its event, `on-add`, `on-complete`, `on-delete` or `on-update`, optionally
+//! followed by `.` or `-` and anything else (`on-complete-journal.sh`), so
+//! several hooks can share an event; they run in name order. Each run gets
+//! one task as JSON on stdin, the same document `todo get --format json`
+//! prints, and the event name in `TODO_HOOK_EVENT`.
+//!
+//! Events come from the task history the command wrote, so tasks changed
+//! through the TUI, `pick` or a batch trigger hooks like the plain commands.
+
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::db::Database;
+use crate::models::Task;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    Add,
+    Complete,
+    Delete,
+    Update,
+}
+
+impl Event {
+    pub fn name(self) -> &'static str {
+        match self {
+            Event::Add => "add",
+            Event::Complete => "complete",
+            Event::Delete => "delete",
+            Event::Update => "update",
+        }
+    }
+
+    /// The event a task history action stands for.
+    fn from_action(action: &str) -> Option<Self> {
+        match action {
+            "created" => Some(Event::Add),
+            "completed" => Some(Event::Complete),
+            "deleted" => Some(Event::Delete),
+            "updated" => Some(Event::Update),
+            _ => None,
+        }
+    }
+}
+
+pub fn hooks_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("todo").join("hooks"))
+}
+
+/// The hooks in `dir` for `event`, in the order they run.
+pub fn scripts(dir: &Path, event: Event) -> Vec<PathBuf> {
+    let prefix = format!("on-{}", event.name());
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut scripts: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let matches = name
+                .strip_prefix(&prefix)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with(['.', '-']));
+            matches && is_executable(&entry.path())
+        })
+        .map(|entry| entry.path())
+        .collect();
+    scripts.sort();
+    scripts
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(path)
+        .is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Whether `dir` holds any hook, so commands can skip looking for events.
+pub fn any(dir: &Path) -> bool {
+    [Event::Add, Event::Complete, Event::Delete, Event::Update]
+        .iter()
+        .any(|event| !scripts(dir, *event).is_empty())
+}
+
+/// Run one hook with `task` on stdin.
+fn run_script(script: &Path, event: Event, task: &Task) -> Result<()> {
+    let mut child = Command::new(script)
+        .env("TODO_HOOK_EVENT", event.name())
+        .stdin(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Cannot run {}", script.display()))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // A hook that doesn't read its input closes the pipe early
+        let _ = stdin.write_all(serde_json::to_string(task)?.as_bytes());
+    }
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(anyhow::anyhow!(
+            "{} exited with {}",
+            script.display(),
+            status
+        ));
+    }
+    Ok(())
+}
+
+/// Run the hooks in `dir` for every task event recorded after history entry
+/// `since`. A task gets each event once per command, however many changes
+/// it saw. Failing hooks are reported but don't fail the command, whose
+/// changes are already saved.
+pub fn run_since(db: &Database, dir: &Path, since: i64) -> Result<()> {
+    let mut events: Vec<(i32, Event)> = Vec::new();
+    for entry in db.get_history_since(since)? {
+        if let Some(event) = Event::from_action(&entry.action) {
+            if !events.contains(&(entry.task_id, event)) {
+                events.push((entry.task_id, event));
+            }
+        }
+    }
+    for (id, event) in events {
+        let scripts = scripts(dir, event);
+        if scripts.is_empty() {
+            continue;
+        }
+        // Tasks purged since, such as by `trash --empty`, have nothing to send
+        let Some(task) = db.get_task_with_trashed(id)? else {
+            continue;
+        };
+        for script in scripts {
+            if let Err(error) = run_script(&script, event, &task) {
+                errln!("⚠️  Hook failed: {:#}", error);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn write_hook(dir: &Path, name: &str, body: &str) {
+        let path = dir.join(name);
+        std::fs::write(&path, format!("#!/bin/sh\n{}\n", body)).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[test]
+    fn test_hooks_run_for_recorded_events() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("log");
+        let hooks = dir.path().join("hooks");
+        std::fs::create_dir(&hooks).unwrap();
+        write_hook(
+            &hooks,
+            "on-complete-journal.sh",
+            &format!(
+                "cat >> {0}; echo \" $TODO_HOOK_EVENT\" >> {0}",
+                log.display()
+            ),
+        );
+        write_hook(&hooks, "on-completed", "exit 1");
+        std::fs::write(hooks.join("on-add"), "not executable").unwrap();
+        assert_eq!(scripts(&hooks, Event::Complete).len(), 1);
+        assert!(scripts(&hooks, Event::Add).is_empty());
+        assert!(any(&hooks));
+
+        let db = Database::new(&dir.path().join("tasks.db")).unwrap();
+        db.init().unwrap();
+        let id = db
+            .add_task(&Task::new("Write report".to_string(), None, None, 1))
+            .unwrap();
+        let since = db.last_history_id().unwrap();
+        db.complete_tasks(&[id]).unwrap();
+        run_since(&db, &hooks, since).unwrap();
+
+        let logged = std::fs::read_to_string(&log).unwrap();
+        let (json, event) = logged.rsplit_once(' ').unwrap();
+        let task: Task = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            (task.title.as_str(), task.completed),
+            ("Write report", true)
+        );
+        assert_eq!(event, "complete\n");
+
+        // Nothing new happened since
+        std::fs::remove_file(&log).unwrap();
+        run_since(&db, &hooks, db.last_history_id().unwrap()).unwrap();
+        assert!(!log.exists());
+    }
+}
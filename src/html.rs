@@ -0,0 +1,173 @@
+use chrono::Duration;
+
+use crate::models::Task;
+
+const STYLE: &str = "
+  body { font-family: system-ui, sans-serif; margin: 2rem; }
+  .todo-list { list-style: none; padding: 0; }
+  .task { padding: 0.4rem 0; border-bottom: 1px solid #eee; }
+  .task-id { color: #888; margin-right: 0.5rem; }
+  .priority-low { color: #1e6bd6; }
+  .priority-medium { color: #b58900; }
+  .priority-high { color: #d62e2e; font-weight: bold; }
+  .status-completed { color: #2e9d44; }
+  .status-completed .task-title { text-decoration: line-through; }
+  .status-pending { color: inherit; }
+  .due-overdue { color: #d62e2e; }
+  .due-soon { color: #b58900; }
+  .age { color: #999; }
+  .tag { color: #1b9aaa; margin-left: 0.3rem; }
+  .project { color: #a0439b; }
+";
+
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// One `<li>` mirroring `Task::display_summary`, with CSS classes in place
+/// of ANSI colors.
+pub fn task_item(task: &Task, due_soon_window: Duration) -> String {
+    let status = if task.completed {
+        "completed"
+    } else {
+        "pending"
+    };
+    let priority = task.priority_text().to_lowercase();
+    let due_class = if task.is_overdue() {
+        " due-overdue"
+    } else if task.is_due_soon(due_soon_window) {
+        " due-soon"
+    } else {
+        ""
+    };
+    let project = task
+        .project
+        .as_ref()
+        .map(|name| format!(r#" <span class="project">@{}</span>"#, escape(name)))
+        .unwrap_or_default();
+    let tags: String = task
+        .tags
+        .iter()
+        .map(|tag| format!(r#"<span class="tag">#{}</span>"#, escape(tag)))
+        .collect();
+
+    format!(
+        r#"<li class="task status-{status}"><span class="task-id">[{id}]</span><span class="task-title">{title}</span> <span class="priority priority-{priority}">{priority_text}</span> <span class="status">{status_text}</span> <span class="due{due_class}">{due}</span> <span class="age">{age}</span>{project}{tags}</li>"#,
+        id = task.id.unwrap_or(0),
+        title = escape(&task.title),
+        priority_text = task.priority_text(),
+        status_text = status.to_uppercase(),
+        due = escape(&task.due_date_text()),
+        age = task.age_text(),
+    )
+}
+
+/// A standalone page; the `<ul class="todo-list">` element can also be
+/// lifted out and embedded on its own.
+pub fn render_page(title: &str, tasks: &[Task], due_soon_window: Duration) -> String {
+    let items: Vec<String> = tasks
+        .iter()
+        .map(|task| format!("    {}", task_item(task, due_soon_window)))
+        .collect();
+
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n<style>{style}</style>\n</head>\n<body>\n<h1>{title}</h1>\n<ul class=\"todo-list\">\n{items}\n</ul>\n<p class=\"total\">Total: {count} tasks</p>\n</body>\n</html>\n",
+        title = escape(title),
+        style = STYLE,
+        items = items.join("\n"),
+        count = tasks.len(),
+    )
+}
+
+/// The read-only page behind a `todo share` link: the shared task with its
+/// subtasks, or a project's tasks, and how many of them are done.
+pub fn render_shared(
+    title: &str,
+    description: Option<&str>,
+    tasks: &[Task],
+    due_soon_window: Duration,
+) -> String {
+    let items: Vec<String> = tasks
+        .iter()
+        .map(|task| format!("    {}", task_item(task, due_soon_window)))
+        .collect();
+    let description = description
+        .map(|text| format!("<p class=\"description\">{}</p>\n", escape(text)))
+        .unwrap_or_default();
+    let done = tasks.iter().filter(|task| task.completed).count();
+
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<meta name=\"viewport\" content=\"width=device-width\">\n<title>{title}</title>\n<style>{style}</style>\n</head>\n<body>\n<h1>{title}</h1>\n{description}<ul class=\"todo-list\">\n{items}\n</ul>\n<p class=\"total\">{done} of {count} done</p>\n</body>\n</html>\n",
+        title = escape(title),
+        style = STYLE,
+        items = items.join("\n"),
+        count = tasks.len(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(
+            escape(r#"<b>"Tom" & 'Jerry'</b>"#),
+            "&lt;b&gt;&quot;Tom&quot; &amp; &#39;Jerry&#39;&lt;/b&gt;"
+        );
+    }
+
+    #[test]
+    fn test_task_item_classes() {
+        let mut task = Task::new(
+            "Pay <rent>".to_string(),
+            None,
+            Some(Utc::now() - Duration::days(1)),
+            2,
+        );
+        task.id = Some(7);
+        task.tags = vec!["home".to_string()];
+
+        let item = task_item(&task, Duration::hours(48));
+        assert!(item.contains(r#"class="task status-pending""#));
+        assert!(item.contains("priority-high"));
+        assert!(item.contains("due-overdue"));
+        assert!(item.contains("Pay &lt;rent&gt;"));
+        assert!(item.contains(r##"<span class="tag">#home</span>"##));
+        assert!(!item.contains('\x1b'));
+    }
+
+    #[test]
+    fn test_render_page() {
+        let task = Task::new("Write report".to_string(), None, None, 1);
+        let page = render_page("Your tasks", &[task], Duration::hours(48));
+
+        assert!(page.starts_with("<!DOCTYPE html>"));
+        assert!(page.contains("<ul class=\"todo-list\">"));
+        assert!(page.contains("Write report"));
+        assert!(page.contains("Total: 1 tasks"));
+    }
+
+    #[test]
+    fn test_render_shared() {
+        let mut done = Task::new("Book venue".to_string(), None, None, 1);
+        done.completed = true;
+        let open = Task::new("Send <invites>".to_string(), None, None, 2);
+        let page = render_shared(
+            "Party",
+            Some("Saturday & Sunday"),
+            &[done, open],
+            Duration::hours(48),
+        );
+
+        assert!(page.contains("<h1>Party</h1>"));
+        assert!(page.contains("Saturday &amp; Sunday"));
+        assert!(page.contains("Send &lt;invites&gt;"));
+        assert!(page.contains("1 of 2 done"));
+    }
+}
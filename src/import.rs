@@ -0,0 +1,686 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::PathBuf;
+
+use crate::models::Task;
+
+/// Tasks committed per transaction. An interrupted import loses at most
+/// one chunk of work.
+pub const CHUNK_SIZE: usize = 500;
+
+/// A parsed task and, when the file has one, its ID in the source tool.
+pub type ImportedTask = (Task, Option<String>);
+
+/// File formats `todo import` can read.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileFormat {
+    /// The output of `todo export` or `todo list --format json`.
+    Json,
+    /// The output of `task export`.
+    Taskwarrior,
+    /// The output of `todo export --format csv` or `todo list --format csv`.
+    Csv,
+    /// The output of `todo export --format markdown`, or any checklist in
+    /// a Markdown note.
+    Markdown,
+}
+
+impl FileFormat {
+    pub fn name(&self) -> &'static str {
+        match self {
+            FileFormat::Json => "JSON",
+            FileFormat::Taskwarrior => "Taskwarrior",
+            FileFormat::Csv => "CSV",
+            FileFormat::Markdown => "Markdown",
+        }
+    }
+
+    /// Source name used to link imported tasks to their external IDs.
+    pub fn link_source(&self) -> &'static str {
+        match self {
+            FileFormat::Json => "json",
+            FileFormat::Taskwarrior => "taskwarrior",
+            FileFormat::Csv => "csv",
+            FileFormat::Markdown => "markdown",
+        }
+    }
+
+    /// Guess the format of todo's own exports from the file extension.
+    pub fn from_extension(path: &std::path::Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_lowercase().as_str() {
+            "json" => Some(FileFormat::Json),
+            "csv" => Some(FileFormat::Csv),
+            "md" | "markdown" => Some(FileFormat::Markdown),
+            _ => None,
+        }
+    }
+}
+
+/// What happens to an imported task whose ID is already taken.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum OnConflict {
+    /// Overwrite the existing task with the imported one
+    Merge,
+    /// Give the imported task the next free ID
+    Renumber,
+}
+
+/// Options for `commands::import_file`.
+pub struct ImportOptions<'a> {
+    /// Inferred from the file extension when not given.
+    pub format: Option<FileFormat>,
+    pub path: Option<&'a std::path::Path>,
+    pub resume: bool,
+    pub on_conflict: OnConflict,
+    pub dry_run: bool,
+    /// Import tasks that are already pending instead of skipping them.
+    pub force: bool,
+    pub default_priority: i32,
+    pub remap: Remap,
+    /// Ask what to do with projects and tags that don't exist yet.
+    pub interactive: bool,
+}
+
+/// How the projects and tags of someone else's tasks are fitted into mine:
+/// renamed, dropped, or replaced by one project for the whole import.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Remap {
+    /// Their project name and mine; `None` imports the tasks without one.
+    pub projects: BTreeMap<String, Option<String>>,
+    /// Their tag and mine; `None` drops it. Nested tags follow their
+    /// parent, so mapping `errands` also renames `errands/shop`.
+    pub tags: BTreeMap<String, Option<String>>,
+    /// Put every imported task in this project, whatever `projects` says.
+    pub project: Option<String>,
+}
+
+impl Remap {
+    pub fn is_empty(&self) -> bool {
+        self.projects.is_empty() && self.tags.is_empty() && self.project.is_none()
+    }
+
+    /// What `tag` becomes, or `None` when it is dropped.
+    pub fn apply_tag(&self, tag: &str) -> Option<String> {
+        if let Some(mapped) = self.tags.get(tag) {
+            return mapped.clone();
+        }
+        match tag.rsplit_once('/') {
+            Some((parent, child)) => self.apply_tag(parent).map(|p| format!("{}/{}", p, child)),
+            None => Some(tag.to_string()),
+        }
+    }
+
+    pub fn apply(&self, tasks: &mut [ImportedTask]) {
+        for (task, _) in tasks.iter_mut() {
+            if self.project.is_some() {
+                task.project = self.project.clone();
+            } else if let Some(mapped) = task.project.as_ref().and_then(|p| self.projects.get(p)) {
+                task.project = mapped.clone();
+            }
+            let mut tags: Vec<String> = Vec::new();
+            for tag in task.tags.iter().filter_map(|tag| self.apply_tag(tag)) {
+                if !tags.contains(&tag) {
+                    tags.push(tag);
+                }
+            }
+            task.tags = tags;
+        }
+    }
+}
+
+/// Parse a `--map-project` or `--map-tag` value: `THEIRS=MINE`, or
+/// `THEIRS=` to drop it.
+pub fn parse_mapping(text: &str) -> Result<(String, Option<String>), String> {
+    let (theirs, mine) = text
+        .split_once('=')
+        .ok_or_else(|| format!("expected THEIRS=MINE, got `{}`", text))?;
+    let (theirs, mine) = (theirs.trim(), mine.trim());
+    if theirs.is_empty() {
+        return Err(format!("expected THEIRS=MINE, got `{}`", text));
+    }
+    Ok((
+        theirs.to_string(),
+        (!mine.is_empty()).then(|| mine.to_string()),
+    ))
+}
+
+/// How far a file import got, stored in the meta table after each chunk.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Progress {
+    pub format: FileFormat,
+    pub path: PathBuf,
+    /// SHA-256 of the file, so `--resume` refuses a file that changed.
+    pub checksum: String,
+    pub done: usize,
+    pub total: usize,
+    pub on_conflict: OnConflict,
+    /// IDs in the file that were taken when the import started, and the
+    /// first free ID at that point, so a resumed import assigns the same IDs.
+    pub taken: Vec<i32>,
+    pub next_id: i32,
+    #[serde(default)]
+    pub force: bool,
+    #[serde(default)]
+    pub remap: Remap,
+}
+
+/// What `assign_ids` did to the IDs in the file.
+#[derive(Debug, Default, PartialEq)]
+pub struct IdChanges {
+    /// Existing tasks that are replaced by the imported ones.
+    pub merged: Vec<i32>,
+    /// `(ID in the file, new ID)` for tasks that were given a new ID.
+    pub renumbered: Vec<(i32, i32)>,
+}
+
+/// Give every task the ID it will be written under. IDs in `taken` are
+/// merged or renumbered according to `on_conflict`, and repeated IDs in the
+/// file are always renumbered. New IDs, and IDs for tasks that have none,
+/// count up from `next_id`. Parent references follow their task when it is
+/// in the file, and are dropped when they point at neither the file nor an
+/// existing task. Formats without IDs refer to a parent by its position in
+/// the file instead, as `-1` for the first task, `-2` for the second...
+pub fn assign_ids(
+    tasks: &mut [ImportedTask],
+    taken: &HashSet<i32>,
+    next_id: i32,
+    on_conflict: OnConflict,
+    task_exists: impl Fn(i32) -> bool,
+) -> Result<IdChanges> {
+    let mut changes = IdChanges::default();
+    let mut next_id = next_id;
+    let mut seen = HashSet::new();
+    let mut new_ids = HashMap::new();
+
+    for (task, _) in tasks.iter_mut() {
+        let id = match task.id {
+            Some(id) if !seen.insert(id) => None,
+            Some(id) if taken.contains(&id) && on_conflict == OnConflict::Merge => {
+                changes.merged.push(id);
+                Some(id)
+            }
+            Some(id) if taken.contains(&id) => None,
+            id => id,
+        };
+        let id = id.unwrap_or_else(|| {
+            next_id += 1;
+            next_id - 1
+        });
+        if let Some(old) = task.id {
+            if old != id {
+                changes.renumbered.push((old, id));
+            }
+            new_ids.entry(old).or_insert(id);
+        }
+        task.id = Some(id);
+    }
+
+    let positions: Vec<Option<i32>> = tasks.iter().map(|(task, _)| task.id).collect();
+    for (task, _) in tasks.iter_mut() {
+        task.parent_id = match task.parent_id {
+            Some(parent) if parent < 0 => positions
+                .get(parent.unsigned_abs() as usize - 1)
+                .copied()
+                .flatten(),
+            Some(parent) if new_ids.contains_key(&parent) => Some(new_ids[&parent]),
+            Some(parent) if task_exists(parent) => Some(parent),
+            _ => None,
+        };
+    }
+
+    let parents: HashMap<i32, i32> = tasks
+        .iter()
+        .filter_map(|(task, _)| Some((task.id?, task.parent_id?)))
+        .collect();
+    for &start in parents.keys() {
+        let mut current = start;
+        for _ in 0..parents.len() {
+            match parents.get(&current) {
+                Some(&parent) if parent == start => {
+                    return Err(anyhow::anyhow!(
+                        "Task #{} is nested under itself in the imported file",
+                        start
+                    ));
+                }
+                Some(&parent) => current = parent,
+                None => break,
+            }
+        }
+    }
+    Ok(changes)
+}
+
+pub fn checksum(contents: &[u8]) -> String {
+    hex::encode(Sha256::digest(contents))
+}
+
+pub fn parse(
+    format: FileFormat,
+    contents: &str,
+    default_priority: i32,
+) -> Result<Vec<ImportedTask>> {
+    match format {
+        FileFormat::Json => parse_json(contents),
+        FileFormat::Taskwarrior => parse_taskwarrior(contents, default_priority),
+        FileFormat::Csv => parse_csv(contents, default_priority),
+        FileFormat::Markdown => crate::markdown::parse(contents, default_priority),
+    }
+}
+
+/// An array of tasks as written by `todo export`. Values are checked the
+/// same way as for `todo set`.
+fn parse_json(contents: &str) -> Result<Vec<ImportedTask>> {
+    let tasks: Vec<Task> = serde_json::from_str(contents).context("Invalid task JSON")?;
+    tasks
+        .into_iter()
+        .enumerate()
+        .map(|(index, mut task)| {
+            let check = || -> Result<()> {
+                if task.title.trim().is_empty() {
+                    return Err(anyhow::anyhow!("Task title cannot be empty"));
+                }
+                if !(0..=2).contains(&task.priority) {
+                    return Err(anyhow::anyhow!("Invalid priority {}", task.priority));
+                }
+                Ok(())
+            };
+            check().with_context(|| format!("Invalid task at index {}", index))?;
+            task.tags = task
+                .tags
+                .iter()
+                .map(|tag| crate::tags::normalize(tag))
+                .collect::<Result<_>>()?;
+            task.project = task
+                .project
+                .map(|project| project.trim().to_string())
+                .filter(|project| !project.is_empty());
+            task.recurrence = task
+                .recurrence
+                .as_deref()
+                .map(crate::recurrence::normalize)
+                .transpose()?;
+            Ok((task, None))
+        })
+        .collect()
+}
+
+#[derive(Debug, Deserialize)]
+struct TaskwarriorTask {
+    uuid: String,
+    description: String,
+    status: String,
+    entry: Option<String>,
+    modified: Option<String>,
+    end: Option<String>,
+    start: Option<String>,
+    wait: Option<String>,
+    scheduled: Option<String>,
+    due: Option<String>,
+    priority: Option<String>,
+    project: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    annotations: Vec<Annotation>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Annotation {
+    description: String,
+}
+
+/// Taskwarrior timestamps look like `20240131T170000Z`.
+fn taskwarrior_date(text: &str) -> Result<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(text, "%Y%m%dT%H%M%SZ")
+        .map(|naive| naive.and_utc())
+        .with_context(|| format!("Invalid Taskwarrior date '{}'", text))
+}
+
+/// Accepts both a JSON array and the older one-object-per-line export.
+/// Deleted tasks and recurrence templates are left out. Started tasks are
+/// in progress, and `wait` (or else `scheduled`) becomes the start date.
+fn parse_taskwarrior(contents: &str, default_priority: i32) -> Result<Vec<ImportedTask>> {
+    let records: Vec<TaskwarriorTask> = if contents.trim_start().starts_with('[') {
+        serde_json::from_str(contents).context("Invalid Taskwarrior export")?
+    } else {
+        contents
+            .lines()
+            .map(|line| line.trim().trim_end_matches(','))
+            .filter(|line| !line.is_empty())
+            .enumerate()
+            .map(|(index, line)| {
+                serde_json::from_str(line)
+                    .with_context(|| format!("Invalid Taskwarrior export on line {}", index + 1))
+            })
+            .collect::<Result<_>>()?
+    };
+
+    let mut tasks = Vec::new();
+    for record in records {
+        if record.status == "deleted" || record.status == "recurring" {
+            continue;
+        }
+        let due = record.due.as_deref().map(taskwarrior_date).transpose()?;
+        let priority = match record.priority.as_deref() {
+            Some("H") => 2,
+            Some("M") => 1,
+            Some("L") => 0,
+            _ => default_priority,
+        };
+        let notes: Vec<&str> = record
+            .annotations
+            .iter()
+            .map(|annotation| annotation.description.as_str())
+            .collect();
+        let description = (!notes.is_empty()).then(|| notes.join("\n"));
+
+        let mut task = Task::new(record.description, description, due, priority);
+        task.completed = record.status == "completed";
+        if let Some(entry) = record.entry.as_deref() {
+            task.created_at = taskwarrior_date(entry)?;
+        }
+        task.updated_at = match record.modified.as_deref() {
+            Some(modified) => taskwarrior_date(modified)?,
+            None => task.created_at,
+        };
+        if task.completed {
+            task.completed_at = record.end.as_deref().map(taskwarrior_date).transpose()?;
+        }
+        task.in_progress = !task.completed && record.start.is_some();
+        task.start_date = record
+            .wait
+            .or(record.scheduled)
+            .as_deref()
+            .map(taskwarrior_date)
+            .transpose()?;
+        task.tags = record
+            .tags
+            .iter()
+            .filter_map(|tag| crate::tags::normalize(tag).ok())
+            .collect();
+        task.project = record.project;
+        tasks.push((task, Some(record.uuid)));
+    }
+    Ok(tasks)
+}
+
+/// Columns of `todo list --format csv`; only `title` is required.
+#[derive(Debug, Deserialize)]
+struct CsvRow {
+    #[serde(default)]
+    id: Option<i32>,
+    title: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    due_date: String,
+    #[serde(default)]
+    priority: String,
+    #[serde(default)]
+    completed: String,
+    #[serde(default)]
+    created_at: String,
+    #[serde(default)]
+    updated_at: String,
+    #[serde(default)]
+    tags: String,
+    #[serde(default)]
+    project: String,
+    #[serde(default)]
+    someday: String,
+    #[serde(default)]
+    recurrence: String,
+    #[serde(default)]
+    parent_id: Option<i32>,
+}
+
+fn csv_date(text: &str) -> Result<DateTime<Utc>> {
+    if let Ok(date) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        return Ok(crate::zone::start_of(date));
+    }
+    DateTime::parse_from_rfc3339(text)
+        .map(|date| date.with_timezone(&Utc))
+        .with_context(|| format!("Invalid date '{}'. Use YYYY-MM-DD or RFC 3339", text))
+}
+
+fn csv_priority(text: &str) -> Result<i32> {
+    match text.parse::<i32>() {
+        Ok(value @ 0..=2) => Ok(value),
+        _ => <crate::Priority as clap::ValueEnum>::from_str(text, true)
+            .map(|priority| priority.to_int())
+            .map_err(|_| anyhow::anyhow!("Invalid priority '{}'", text)),
+    }
+}
+
+fn is_true(text: &str) -> bool {
+    matches!(
+        text.trim().to_lowercase().as_str(),
+        "true" | "yes" | "1" | "x"
+    )
+}
+
+fn parse_csv(contents: &str, default_priority: i32) -> Result<Vec<ImportedTask>> {
+    let non_empty = |text: String| (!text.trim().is_empty()).then(|| text.trim().to_string());
+
+    let mut reader = csv::Reader::from_reader(contents.as_bytes());
+    let mut tasks = Vec::new();
+    for (index, row) in reader.deserialize::<CsvRow>().enumerate() {
+        // Row 1 is the header
+        let row_number = index + 2;
+        let row = row.with_context(|| format!("Invalid CSV on row {}", row_number))?;
+        let parsed = (|| -> Result<Task> {
+            let due = non_empty(row.due_date)
+                .as_deref()
+                .map(csv_date)
+                .transpose()?;
+            let priority = match non_empty(row.priority) {
+                Some(priority) => csv_priority(&priority)?,
+                None => default_priority,
+            };
+            let mut task = Task::new(row.title, non_empty(row.description), due, priority);
+            task.id = row.id;
+            task.parent_id = row.parent_id;
+            task.completed = is_true(&row.completed);
+            task.someday = is_true(&row.someday);
+            if let Some(created) = non_empty(row.created_at) {
+                task.created_at = csv_date(&created)?;
+            }
+            task.updated_at = match non_empty(row.updated_at) {
+                Some(updated) => csv_date(&updated)?,
+                None => task.created_at,
+            };
+            task.tags = row
+                .tags
+                .split(';')
+                .filter(|tag| !tag.trim().is_empty())
+                .map(crate::tags::normalize)
+                .collect::<Result<_>>()?;
+            task.project = non_empty(row.project);
+            task.recurrence = non_empty(row.recurrence)
+                .as_deref()
+                .map(crate::recurrence::normalize)
+                .transpose()?;
+            Ok(task)
+        })()
+        .with_context(|| format!("Invalid CSV on row {}", row_number))?;
+        if parsed.title.trim().is_empty() {
+            return Err(anyhow::anyhow!("Row {} has no title", row_number));
+        }
+        tasks.push((parsed, None));
+    }
+    Ok(tasks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_taskwarrior_export() {
+        let export = r#"[
+            {"uuid":"a1","description":"Pay rent","status":"pending","entry":"20300101T090000Z",
+             "due":"20300201T000000Z","priority":"H","project":"home","tags":["bills"],
+             "annotations":[{"entry":"20300101T090000Z","description":"Landlord's IBAN"}]},
+            {"uuid":"b2","description":"Old","status":"completed","entry":"20290101T090000Z",
+             "end":"20290301T120000Z"},
+            {"uuid":"e5","description":"Call back","status":"pending","start":"20300102T080000Z",
+             "wait":"20300110T000000Z","scheduled":"20300105T000000Z"},
+            {"uuid":"c3","description":"Gone","status":"deleted"},
+            {"uuid":"d4","description":"Template","status":"recurring"}
+        ]"#;
+        let tasks = parse(FileFormat::Taskwarrior, export, 1).unwrap();
+        assert_eq!(tasks.len(), 3);
+
+        let (rent, uuid) = &tasks[0];
+        assert_eq!(uuid.as_deref(), Some("a1"));
+        assert_eq!(rent.title, "Pay rent");
+        assert_eq!(rent.description.as_deref(), Some("Landlord's IBAN"));
+        assert_eq!(rent.priority, 2);
+        assert_eq!(rent.project.as_deref(), Some("home"));
+        assert_eq!(rent.tags, vec!["bills"]);
+        assert_eq!(
+            rent.due_date.unwrap().to_rfc3339(),
+            "2030-02-01T00:00:00+00:00"
+        );
+        assert!(tasks[1].0.completed);
+        assert_eq!(tasks[1].0.priority, 1);
+        assert_eq!(
+            tasks[1].0.completed_at.unwrap().to_rfc3339(),
+            "2029-03-01T12:00:00+00:00"
+        );
+        let call = &tasks[2].0;
+        assert!(call.in_progress);
+        assert_eq!(
+            call.start_date.unwrap().to_rfc3339(),
+            "2030-01-10T00:00:00+00:00"
+        );
+
+        // Older exports have one object per line
+        let lines = "{\"uuid\":\"x\",\"description\":\"A\",\"status\":\"pending\"},\n\
+                     {\"uuid\":\"y\",\"description\":\"B\",\"status\":\"waiting\"}\n";
+        assert_eq!(parse(FileFormat::Taskwarrior, lines, 1).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_parse_csv_roundtrip() {
+        let mut task = Task::new(
+            "Buy milk, eggs".to_string(),
+            Some("Two \"big\" ones".to_string()),
+            None,
+            0,
+        );
+        task.id = Some(4);
+        task.tags = vec!["home".to_string(), "errands/shop".to_string()];
+        task.recurrence = Some("weekly".to_string());
+        let csv = crate::csv_output::render(&[task.clone()]).unwrap();
+
+        let tasks = parse(FileFormat::Csv, &csv, 1).unwrap();
+        assert_eq!(tasks.len(), 1);
+        let (parsed, external_id) = &tasks[0];
+        assert_eq!(external_id, &None);
+        assert_eq!(parsed.title, task.title);
+        assert_eq!(parsed.description, task.description);
+        assert_eq!(parsed.priority, 0);
+        assert_eq!(parsed.tags, task.tags);
+        assert_eq!(parsed.recurrence, task.recurrence);
+        assert_eq!(parsed.created_at, task.created_at);
+
+        // Hand-written files only need a title
+        let tasks = parse(FileFormat::Csv, "title,priority\nCall mum,high\n", 1).unwrap();
+        assert_eq!(tasks[0].0.priority, 2);
+        let error = parse(FileFormat::Csv, "title,due_date\nX,soon\n", 1).unwrap_err();
+        assert!(format!("{:#}", error).contains("row 2"));
+    }
+
+    #[test]
+    fn test_assign_ids() {
+        let task = |id: Option<i32>, parent_id: Option<i32>| {
+            let mut task = Task::new("T".to_string(), None, None, 1);
+            task.id = id;
+            task.parent_id = parent_id;
+            (task, None)
+        };
+        let taken: HashSet<i32> = [1, 2].into_iter().collect();
+        let ids = |tasks: &[ImportedTask]| -> Vec<(Option<i32>, Option<i32>)> {
+            tasks.iter().map(|(t, _)| (t.id, t.parent_id)).collect()
+        };
+
+        let mut tasks = vec![
+            task(Some(1), None),
+            task(Some(2), Some(1)),
+            task(Some(5), Some(2)),
+            task(Some(5), None),
+            task(None, Some(40)),
+            task(None, Some(30)),
+        ];
+        let changes = assign_ids(&mut tasks.clone(), &taken, 10, OnConflict::Merge, |id| {
+            id == 30
+        })
+        .unwrap();
+        assert_eq!(changes.merged, vec![1, 2]);
+        assert_eq!(changes.renumbered, vec![(5, 10)]);
+
+        let changes =
+            assign_ids(&mut tasks, &taken, 10, OnConflict::Renumber, |id| id == 30).unwrap();
+        assert_eq!(changes.merged, Vec::<i32>::new());
+        assert_eq!(changes.renumbered, vec![(1, 10), (2, 11), (5, 12)]);
+        assert_eq!(
+            ids(&tasks),
+            vec![
+                (Some(10), None),
+                (Some(11), Some(10)),
+                (Some(5), Some(11)),
+                (Some(12), None),
+                (Some(13), None),
+                (Some(14), Some(30)),
+            ]
+        );
+
+        let mut cycle = vec![task(Some(1), Some(2)), task(Some(2), Some(1))];
+        let error = assign_ids(&mut cycle, &HashSet::new(), 3, OnConflict::Merge, |_| false);
+        assert!(error
+            .unwrap_err()
+            .to_string()
+            .contains("nested under itself"));
+    }
+
+    #[test]
+    fn test_remap() {
+        assert_eq!(
+            parse_mapping("work = job"),
+            Ok(("work".to_string(), Some("job".to_string())))
+        );
+        assert_eq!(parse_mapping("misc="), Ok(("misc".to_string(), None)));
+        assert!(parse_mapping("job").is_err());
+        assert!(parse_mapping("=job").is_err());
+
+        let mut task = Task::new("T".to_string(), None, None, 1);
+        task.project = Some("work".to_string());
+        task.tags = ["errands/shop", "chores", "urgent", "home"]
+            .map(String::from)
+            .to_vec();
+        let mut remap = Remap {
+            projects: [("work".to_string(), Some("job".to_string()))].into(),
+            tags: [
+                ("errands".to_string(), Some("chores".to_string())),
+                ("urgent".to_string(), None),
+            ]
+            .into(),
+            project: None,
+        };
+        let mut tasks = vec![(task, None)];
+        remap.apply(&mut tasks.clone());
+        remap.apply(&mut tasks[..]);
+        assert_eq!(tasks[0].0.project.as_deref(), Some("job"));
+        assert_eq!(tasks[0].0.tags, vec!["chores/shop", "chores", "home"]);
+
+        remap.project = Some("shared".to_string());
+        remap.apply(&mut tasks);
+        assert_eq!(tasks[0].0.project.as_deref(), Some("shared"));
+    }
+}
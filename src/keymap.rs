@@ -0,0 +1,350 @@
+//! Key bindings of `todo tui`. Every action has vim-style defaults, and the
+//! `[tui.keys]` config table rebinds them, one key or a list per action:
+//!
+//! ```toml
+//! [tui.keys]
+//! complete = "space"
+//! down = ["j", "down", "ctrl-n"]
+//! ```
+//!
+//! Keys are a character (case matters, so `G` is shift-g) or one of the
+//! names below, optionally prefixed with `ctrl-` and/or `alt-`. Ctrl-C
+//! always quits, and Enter/Esc keep their meaning while typing.
+
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// Named keys, in the spelling used by the config file.
+const NAMED_KEYS: &[(&str, KeyCode)] = &[
+    ("enter", KeyCode::Enter),
+    ("esc", KeyCode::Esc),
+    ("tab", KeyCode::Tab),
+    ("space", KeyCode::Char(' ')),
+    ("backspace", KeyCode::Backspace),
+    ("delete", KeyCode::Delete),
+    ("up", KeyCode::Up),
+    ("down", KeyCode::Down),
+    ("left", KeyCode::Left),
+    ("right", KeyCode::Right),
+    ("home", KeyCode::Home),
+    ("end", KeyCode::End),
+    ("pageup", KeyCode::PageUp),
+    ("pagedown", KeyCode::PageDown),
+];
+
+/// Something a key can do in the TUI's normal mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    Down,
+    Up,
+    First,
+    Last,
+    Filter,
+    Reload,
+    Complete,
+    Snooze,
+    Delete,
+    Undo,
+    Redo,
+    EditTitle,
+    EditDue,
+    EditPriority,
+    AddNote,
+    Board,
+    Calendar,
+    Left,
+    Right,
+    MoveLeft,
+    MoveRight,
+    PreviousMonth,
+    NextMonth,
+    Today,
+}
+
+impl Action {
+    pub const ALL: [Action; 25] = [
+        Action::Quit,
+        Action::Down,
+        Action::Up,
+        Action::First,
+        Action::Last,
+        Action::Filter,
+        Action::Reload,
+        Action::Complete,
+        Action::Snooze,
+        Action::Delete,
+        Action::Undo,
+        Action::Redo,
+        Action::EditTitle,
+        Action::EditDue,
+        Action::EditPriority,
+        Action::AddNote,
+        Action::Board,
+        Action::Calendar,
+        Action::Left,
+        Action::Right,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::PreviousMonth,
+        Action::NextMonth,
+        Action::Today,
+    ];
+
+    /// The action's name in `[tui.keys]`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::Down => "down",
+            Action::Up => "up",
+            Action::First => "first",
+            Action::Last => "last",
+            Action::Filter => "filter",
+            Action::Reload => "reload",
+            Action::Complete => "complete",
+            Action::Snooze => "snooze",
+            Action::Delete => "delete",
+            Action::Undo => "undo",
+            Action::Redo => "redo",
+            Action::EditTitle => "edit",
+            Action::EditDue => "edit_due",
+            Action::EditPriority => "edit_priority",
+            Action::AddNote => "note",
+            Action::Board => "board",
+            Action::Calendar => "calendar",
+            Action::Left => "left",
+            Action::Right => "right",
+            Action::MoveLeft => "move_left",
+            Action::MoveRight => "move_right",
+            Action::PreviousMonth => "previous_month",
+            Action::NextMonth => "next_month",
+            Action::Today => "today",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Action::Quit => "Quit",
+            Action::Down => "Select the next task",
+            Action::Up => "Select the previous task",
+            Action::First => "Jump to the first task",
+            Action::Last => "Jump to the last task",
+            Action::Filter => "Type a filter",
+            Action::Reload => "Reload the tasks",
+            Action::Complete => "Complete the task",
+            Action::Snooze => "Push the due date back a day",
+            Action::Delete => "Move the task to the trash",
+            Action::Undo => "Undo the last change made in this session",
+            Action::Redo => "Redo the last undone change",
+            Action::EditTitle => "Edit the title",
+            Action::EditDue => "Edit the due date",
+            Action::EditPriority => "Edit the priority",
+            Action::AddNote => "Add a note",
+            Action::Board => "Switch between the list and the board",
+            Action::Calendar => "Show or hide the calendar",
+            Action::Left => "Board: column to the left; calendar: previous day",
+            Action::Right => "Board: column to the right; calendar: next day",
+            Action::MoveLeft => "Board: move the task left; calendar: previous week",
+            Action::MoveRight => "Board: move the task right; calendar: next week",
+            Action::PreviousMonth => "Calendar: previous month",
+            Action::NextMonth => "Calendar: next month",
+            Action::Today => "Calendar: back to today",
+        }
+    }
+
+    fn default_keys(&self) -> &'static [&'static str] {
+        match self {
+            Action::Quit => &["q"],
+            Action::Down => &["j", "down"],
+            Action::Up => &["k", "up"],
+            Action::First => &["g", "home"],
+            Action::Last => &["G", "end"],
+            Action::Filter => &["/"],
+            Action::Reload => &["r"],
+            Action::Complete => &["x"],
+            Action::Snooze => &["s"],
+            Action::Delete => &["D"],
+            Action::Undo => &["u"],
+            Action::Redo => &["ctrl-r"],
+            Action::EditTitle => &["e"],
+            Action::EditDue => &["d"],
+            Action::EditPriority => &["p"],
+            Action::AddNote => &["n"],
+            Action::Board => &["b"],
+            Action::Calendar => &["c"],
+            Action::Left => &["h", "left"],
+            Action::Right => &["l", "right"],
+            Action::MoveLeft => &["H"],
+            Action::MoveRight => &["L"],
+            Action::PreviousMonth => &["["],
+            Action::NextMonth => &["]"],
+            Action::Today => &["t"],
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.into_iter().find(|action| action.name() == name)
+    }
+}
+
+/// One key, possibly with modifiers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Key {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl Key {
+    /// Parse a key such as `x`, `G`, `space` or `ctrl-n`.
+    pub fn parse(text: &str) -> Result<Key, String> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = text;
+        loop {
+            let lower = rest.to_lowercase();
+            if lower.starts_with("ctrl-") && rest.len() > 5 {
+                modifiers |= KeyModifiers::CONTROL;
+                rest = &rest[5..];
+            } else if lower.starts_with("alt-") && rest.len() > 4 {
+                modifiers |= KeyModifiers::ALT;
+                rest = &rest[4..];
+            } else {
+                break;
+            }
+        }
+
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => NAMED_KEYS
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(rest))
+                .map(|(_, code)| *code)
+                .ok_or_else(|| format!("unknown key `{}`", text))?,
+        };
+        Ok(Key { code, modifiers })
+    }
+
+    /// Whether a key press is this key. Shift is part of the character
+    /// for letters and symbols, so it is ignored there.
+    pub fn matches(&self, event: &KeyEvent) -> bool {
+        let mut modifiers = event.modifiers;
+        if matches!(event.code, KeyCode::Char(_)) {
+            modifiers.remove(KeyModifiers::SHIFT);
+        }
+        event.code == self.code && modifiers == self.modifiers
+    }
+}
+
+impl std::fmt::Display for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "ctrl-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "alt-")?;
+        }
+        match NAMED_KEYS.iter().find(|(_, code)| *code == self.code) {
+            Some((name, _)) => write!(f, "{}", name),
+            None => match self.code {
+                KeyCode::Char(c) => write!(f, "{}", c),
+                code => write!(f, "{:?}", code),
+            },
+        }
+    }
+}
+
+/// The keys bound to each action.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keymap {
+    /// One entry per action, in `Action::ALL` order.
+    bindings: Vec<Vec<Key>>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self {
+            bindings: Action::ALL
+                .iter()
+                .map(|action| {
+                    action
+                        .default_keys()
+                        .iter()
+                        .map(|key| Key::parse(key).expect("default keys parse"))
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Keymap {
+    /// Replace the keys of `action`.
+    pub fn bind(&mut self, action: Action, keys: Vec<Key>) {
+        self.bindings[action as usize] = keys;
+    }
+
+    pub fn keys(&self, action: Action) -> &[Key] {
+        &self.bindings[action as usize]
+    }
+
+    /// The first key of `action`, for hints; `-` when it is unbound.
+    pub fn hint(&self, action: Action) -> String {
+        self.keys(action)
+            .first()
+            .map(Key::to_string)
+            .unwrap_or_else(|| "-".to_string())
+    }
+
+    /// The action a key press triggers.
+    pub fn action(&self, event: &KeyEvent) -> Option<Action> {
+        Action::ALL
+            .into_iter()
+            .find(|&action| self.keys(action).iter().any(|key| key.matches(event)))
+    }
+
+    /// An error naming the first key bound to two actions.
+    pub fn check_conflicts(&self) -> Result<(), String> {
+        for (index, action) in Action::ALL.iter().enumerate() {
+            for key in self.keys(*action) {
+                if let Some(other) = Action::ALL[index + 1..]
+                    .iter()
+                    .find(|other| self.keys(**other).contains(key))
+                {
+                    return Err(format!(
+                        "key `{}` is bound to both `{}` and `{}` in [tui.keys]",
+                        key,
+                        action.name(),
+                        other.name()
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_rebind_keys() {
+        assert_eq!(Key::parse("ctrl-n").unwrap().to_string(), "ctrl-n");
+        assert_eq!(Key::parse("Space").unwrap().to_string(), "space");
+        assert_eq!(Key::parse("-").unwrap().to_string(), "-");
+        assert!(Key::parse("hyper-x").is_err());
+
+        let mut keymap = Keymap::default();
+        let shift_g = KeyEvent::new(KeyCode::Char('G'), KeyModifiers::SHIFT);
+        assert_eq!(keymap.action(&shift_g), Some(Action::Last));
+        assert!(keymap.check_conflicts().is_ok());
+
+        keymap.bind(Action::Complete, vec![Key::parse("space").unwrap()]);
+        let space = KeyEvent::from(KeyCode::Char(' '));
+        assert_eq!(keymap.action(&space), Some(Action::Complete));
+        assert_eq!(keymap.action(&KeyEvent::from(KeyCode::Char('x'))), None);
+
+        keymap.bind(Action::Complete, vec![Key::parse("d").unwrap()]);
+        let error = keymap.check_conflicts().unwrap_err();
+        assert!(error.contains("`complete` and `edit_due`"));
+    }
+}
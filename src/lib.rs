@@ -0,0 +1,448 @@
+//! The task logic behind the `todo` command line, as a library.
+//!
+//! [`TodoStore`] is the entry point for other tools: it opens a task
+//! database and adds, lists, edits, completes and deletes tasks, reporting
+//! failures as [`Error`]. Lists are narrowed with a [`TaskFilter`], most
+//! easily built with [`TaskFilter::builder`].
+//!
+//! ```
+//! use todo::{TaskFilter, TodoStore};
+//! use todo::models::Task;
+//!
+//! let store = TodoStore::open(":memory:")?;
+//! let mut task = Task::new("Write report".to_string(), None, None, 2);
+//! task.tags = vec!["work".to_string()];
+//! let id = store.add(&task)?;
+//!
+//! let filter = TaskFilter::builder().tag("work").build()?;
+//! assert_eq!(store.list(&filter)?[0].id, Some(id));
+//!
+//! store.complete(id)?;
+//! assert!(store.list(&filter)?.is_empty());
+//! # Ok::<(), todo::Error>(())
+//! ```
+//!
+//! The remaining modules are what the CLI is built from: [`db`] for direct
+//! database access, [`commands`] for the subcommands that print their
+//! results, and [`tui`] for the full-screen interface.
+
+/// `println!` for messages meant for people. In plain mode emoji, symbols
+/// and box drawing are dropped; see [`style::plain_text`]. Machine-readable
+/// output (JSON, CSV, HTML) goes through `println!` untouched. Inside
+/// [`style::capture`] the lines are collected instead of printed.
+#[macro_export]
+macro_rules! outln {
+    () => {
+        $crate::style::print_line("")
+    };
+    ($($arg:tt)*) => {
+        if let Some(text) = $crate::style::plain_text(&format!($($arg)*)) {
+            $crate::style::print_line(&text);
+        }
+    };
+}
+
+/// `eprintln!` with the same plain-mode treatment as [`outln!`].
+#[macro_export]
+macro_rules! errln {
+    ($($arg:tt)*) => {
+        if let Some(text) = $crate::style::plain_text(&format!($($arg)*)) {
+            eprintln!("{}", text);
+        }
+    };
+}
+
+pub mod board;
+pub mod calendar;
+pub mod capture;
+pub mod commands;
+pub mod config;
+pub mod csv_output;
+pub mod dates;
+pub mod db;
+pub mod diagnostic;
+pub mod display;
+pub mod email;
+pub mod encryption;
+pub mod error;
+pub mod files;
+pub mod filter_expr;
+pub mod git_sync;
+pub mod habitica;
+pub mod hooks;
+pub mod html;
+pub mod import;
+pub mod keymap;
+pub mod logging;
+pub mod maintenance;
+pub mod markdown;
+pub mod models;
+pub mod notify;
+pub mod obsidian;
+pub mod opener;
+pub mod permissions;
+pub mod picker;
+pub mod progress;
+pub mod quick_add;
+pub mod recurrence;
+pub mod report;
+pub mod schedule;
+pub mod server;
+pub mod shutdown;
+pub mod signing;
+pub mod snapshot;
+pub mod sql_expr;
+pub mod store;
+pub mod style;
+pub mod suggest;
+pub mod table;
+pub mod tags;
+pub mod tui;
+pub mod update;
+pub mod urgency;
+pub mod zone;
+
+pub use db::TaskFilter;
+pub use error::TodoError;
+pub use store::{Error, TaskFilterBuilder, TodoStore};
+
+#[derive(clap::ValueEnum, Clone, Debug)]
+pub enum OutputFormat {
+    Plain,
+    Json,
+    Csv,
+    Html,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum ExportFormat {
+    Json,
+    Csv,
+    Markdown,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum ListGroupBy {
+    Project,
+}
+
+/// Columns of the `todo list` table.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum ListColumn {
+    Id,
+    Title,
+    Priority,
+    Due,
+    Start,
+    Status,
+    Tags,
+    Project,
+    Age,
+    Repeat,
+    Energy,
+}
+
+impl ListColumn {
+    /// What `todo list` shows without `--columns`.
+    pub const DEFAULT: &'static [ListColumn] = &[
+        ListColumn::Id,
+        ListColumn::Title,
+        ListColumn::Priority,
+        ListColumn::Due,
+        ListColumn::Status,
+        ListColumn::Tags,
+    ];
+
+    pub fn header(&self) -> &'static str {
+        match self {
+            ListColumn::Id => "ID",
+            ListColumn::Title => "TITLE",
+            ListColumn::Priority => "PRIORITY",
+            ListColumn::Due => "DUE",
+            ListColumn::Start => "START",
+            ListColumn::Status => "STATUS",
+            ListColumn::Tags => "TAGS",
+            ListColumn::Project => "PROJECT",
+            ListColumn::Age => "AGE",
+            ListColumn::Repeat => "REPEAT",
+            ListColumn::Energy => "ENERGY",
+        }
+    }
+}
+
+/// Orders for `todo list --sort`.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum ListSort {
+    /// Highest priority first, then oldest first
+    #[default]
+    Priority,
+    /// The order set with `todo move`
+    Manual,
+}
+
+impl ListSort {
+    pub fn order(&self) -> db::TaskOrder {
+        match self {
+            ListSort::Priority => db::TaskOrder::Priority,
+            ListSort::Manual => db::TaskOrder::Manual,
+        }
+    }
+}
+
+/// Due-date ranges for `todo list --due`, in UTC days like `--due tomorrow`.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum DueFilter {
+    /// Due today
+    Today,
+    /// Past their due date
+    Overdue,
+    /// Due today or in the next six days
+    Week,
+}
+
+impl DueFilter {
+    pub fn range(&self, now: chrono::DateTime<chrono::Utc>) -> db::DueRange {
+        let day = zone::day_of(now);
+        let today = zone::start_of(day);
+        match self {
+            DueFilter::Today => db::DueRange {
+                from: Some(today),
+                until: zone::start_of(day + chrono::Duration::days(1)),
+            },
+            DueFilter::Overdue => db::DueRange {
+                from: None,
+                until: now,
+            },
+            DueFilter::Week => db::DueRange {
+                from: Some(today),
+                until: zone::start_of(day + chrono::Duration::days(7)),
+            },
+        }
+    }
+}
+
+/// Which pending tasks `todo reprioritize` steps through.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum TriageFilter {
+    /// Past their due date
+    Overdue,
+    /// Due within the due-soon window
+    DueSoon,
+    /// Not updated in a month
+    Stale,
+    /// Without a due date
+    NoDue,
+    /// Every pending task
+    All,
+}
+
+/// What `todo pick` does with the picked task.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum PickAction {
+    /// Show its details
+    Show,
+    /// Mark it completed
+    Complete,
+    /// Move it to the trash
+    Delete,
+    /// Start tracking time on it
+    Start,
+}
+
+/// A task named on the command line: its ID, or the start of its UUID.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TaskRef {
+    Id(i32),
+    Uuid(String),
+}
+
+impl TaskRef {
+    /// Fewest UUID characters taken as a prefix.
+    pub const MIN_PREFIX: usize = 4;
+}
+
+impl From<i32> for TaskRef {
+    fn from(id: i32) -> Self {
+        TaskRef::Id(id)
+    }
+}
+
+impl std::str::FromStr for TaskRef {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        if let Ok(id) = text.parse() {
+            return Ok(TaskRef::Id(id));
+        }
+        if text.len() >= TaskRef::MIN_PREFIX
+            && text.chars().all(|c| c.is_ascii_hexdigit() || c == '-')
+        {
+            return Ok(TaskRef::Uuid(text.to_ascii_lowercase()));
+        }
+        Err(format!(
+            "'{}' is not a task ID or the start of a task UUID (at least {} characters)",
+            text,
+            TaskRef::MIN_PREFIX
+        ))
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Debug)]
+pub enum ImportSource {
+    Habitica,
+    Json,
+    Taskwarrior,
+    Csv,
+    Markdown,
+}
+
+impl ImportSource {
+    pub fn file_format(&self) -> Option<import::FileFormat> {
+        match self {
+            ImportSource::Habitica => None,
+            ImportSource::Json => Some(import::FileFormat::Json),
+            ImportSource::Taskwarrior => Some(import::FileFormat::Taskwarrior),
+            ImportSource::Csv => Some(import::FileFormat::Csv),
+            ImportSource::Markdown => Some(import::FileFormat::Markdown),
+        }
+    }
+}
+
+/// How much energy a task takes, for `todo next --energy`. Tasks without
+/// one count as medium.
+#[derive(
+    clap::ValueEnum,
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Energy {
+    Low,
+    Medium,
+    High,
+}
+
+impl Energy {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Energy::Low => "low",
+            Energy::Medium => "medium",
+            Energy::High => "high",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        <Energy as clap::ValueEnum>::from_str(name, true).ok()
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Debug, PartialEq)]
+pub enum Priority {
+    Low,
+    Medium,
+    High,
+}
+
+impl Priority {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Priority::Low => "low",
+            Priority::Medium => "medium",
+            Priority::High => "high",
+        }
+    }
+
+    pub fn to_int(&self) -> i32 {
+        match self {
+            Priority::Low => 0,
+            Priority::Medium => 1,
+            Priority::High => 2,
+        }
+    }
+
+    pub fn from_int(value: i32) -> Self {
+        match value {
+            0 => Priority::Low,
+            1 => Priority::Medium,
+            2 => Priority::High,
+            _ => Priority::Medium,
+        }
+    }
+
+    pub fn color(&self) -> colored::ColoredString {
+        style::priority(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_priority_to_int() {
+        assert_eq!(Priority::Low.to_int(), 0);
+        assert_eq!(Priority::Medium.to_int(), 1);
+        assert_eq!(Priority::High.to_int(), 2);
+    }
+
+    #[test]
+    fn test_priority_from_int() {
+        assert!(matches!(Priority::from_int(0), Priority::Low));
+        assert!(matches!(Priority::from_int(1), Priority::Medium));
+        assert!(matches!(Priority::from_int(2), Priority::High));
+        assert!(matches!(Priority::from_int(99), Priority::Medium)); // Default case
+    }
+
+    #[test]
+    fn test_priority_color() {
+        let low_color = Priority::Low.color();
+        let medium_color = Priority::Medium.color();
+        let high_color = Priority::High.color();
+
+        assert!(low_color.to_string().contains("LOW"));
+        assert!(medium_color.to_string().contains("MEDIUM"));
+        assert!(high_color.to_string().contains("HIGH"));
+    }
+
+    #[test]
+    fn test_priority_enum_variants() {
+        // Test that all priority variants exist
+        let _low = Priority::Low;
+        let _medium = Priority::Medium;
+        let _high = Priority::High;
+    }
+
+    #[test]
+    fn test_priority_ordering() {
+        // Test that priorities are ordered correctly
+        let priorities = [Priority::Low, Priority::Medium, Priority::High];
+        let int_values: Vec<i32> = priorities.iter().map(|p| p.to_int()).collect();
+
+        assert_eq!(int_values, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_priority_roundtrip() {
+        // Test that priority conversion is reversible
+        let original = Priority::High;
+        let int_value = original.to_int();
+        let converted = Priority::from_int(int_value);
+
+        assert!(matches!(converted, Priority::High));
+    }
+
+    #[test]
+    fn test_invalid_priority_handling() {
+        // Test that invalid priority values default to Medium
+        let invalid_priority = Priority::from_int(999);
+        assert!(matches!(invalid_priority, Priority::Medium));
+    }
+}
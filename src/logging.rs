@@ -0,0 +1,236 @@
+//! Optional activity log. When `log_file` is set, automatic maintenance,
+//! syncs, imports and failed commands append one JSON object per line to
+//! it. The file is rotated once it grows past `log_max_kb` or its first
+//! entry is older than `log_max_days`; `ROTATED_FILES` old generations are
+//! kept as `<file>.1` (newest) to `<file>.3`.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use crate::files::FileLock;
+
+/// Old log files kept after rotation.
+pub const ROTATED_FILES: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Info,
+    Warn,
+    Error,
+}
+
+impl Level {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Level::Info => "INFO",
+            Level::Warn => "WARN",
+            Level::Error => "ERROR",
+        }
+    }
+}
+
+/// One line of the log.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    pub time: DateTime<Utc>,
+    pub level: Level,
+    /// What did the work, using the task history actor names, e.g.
+    /// `maintenance` or `sync:obsidian`.
+    pub component: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct LogFile {
+    pub path: PathBuf,
+    pub max_bytes: u64,
+    pub max_age: Duration,
+}
+
+static LOG: OnceLock<LogFile> = OnceLock::new();
+
+/// Start logging to `log` for the rest of the process.
+pub fn init(log: LogFile) {
+    let _ = LOG.set(log);
+}
+
+pub fn info(component: &str, message: impl Into<String>) {
+    record(Level::Info, component, message.into());
+}
+
+pub fn warn(component: &str, message: impl Into<String>) {
+    record(Level::Warn, component, message.into());
+}
+
+pub fn error(component: &str, message: impl Into<String>) {
+    record(Level::Error, component, message.into());
+}
+
+fn record(level: Level, component: &str, message: String) {
+    if let Some(log) = LOG.get() {
+        let entry = Entry {
+            time: Utc::now(),
+            level,
+            component: component.to_string(),
+            message,
+        };
+        // A log that can't be written must not fail the work it describes
+        let _ = log.append(&entry);
+    }
+}
+
+impl LogFile {
+    pub fn append(&self, entry: &Entry) -> Result<()> {
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let line = format!("{}\n", serde_json::to_string(entry)?);
+        let _lock = FileLock::acquire(&self.path)?;
+        if self.needs_rotation(entry.time, line.len() as u64) {
+            self.rotate()?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("Cannot open {}", self.path.display()))?;
+        file.write_all(line.as_bytes())?;
+        Ok(())
+    }
+
+    /// Whether the current file must be rotated before appending `incoming`
+    /// bytes at `now`.
+    fn needs_rotation(&self, now: DateTime<Utc>, incoming: u64) -> bool {
+        let Ok(metadata) = fs::metadata(&self.path) else {
+            return false;
+        };
+        if metadata.len() == 0 {
+            return false;
+        }
+        if metadata.len() + incoming > self.max_bytes {
+            return true;
+        }
+        read_entries(&self.path)
+            .ok()
+            .and_then(|entries| {
+                entries
+                    .first()
+                    .map(|first| now - first.time >= self.max_age)
+            })
+            .unwrap_or(false)
+    }
+
+    fn rotate(&self) -> Result<()> {
+        for generation in (1..ROTATED_FILES).rev() {
+            let from = self.generation(generation);
+            if from.exists() {
+                fs::rename(&from, self.generation(generation + 1))?;
+            }
+        }
+        fs::rename(&self.path, self.generation(1))
+            .with_context(|| format!("Cannot rotate {}", self.path.display()))
+    }
+
+    /// `<file>.N`, or the current file for 0.
+    fn generation(&self, generation: usize) -> PathBuf {
+        if generation == 0 {
+            return self.path.clone();
+        }
+        let mut name = self.path.as_os_str().to_owned();
+        name.push(format!(".{}", generation));
+        PathBuf::from(name)
+    }
+
+    /// The last `count` entries, oldest first, reaching back into rotated
+    /// files when the current one is shorter.
+    pub fn tail(&self, count: usize) -> Result<Vec<Entry>> {
+        let mut entries = Vec::new();
+        for generation in 0..=ROTATED_FILES {
+            if entries.len() >= count {
+                break;
+            }
+            let path = self.generation(generation);
+            if path.exists() {
+                let mut older = read_entries(&path)?;
+                older.append(&mut entries);
+                entries = older;
+            }
+        }
+        let skip = entries.len().saturating_sub(count);
+        Ok(entries.split_off(skip))
+    }
+}
+
+/// Entries of one log file; lines that aren't entries are skipped.
+fn read_entries(path: &std::path::Path) -> Result<Vec<Entry>> {
+    let file = File::open(path).with_context(|| format!("Cannot read {}", path.display()))?;
+    Ok(BufReader::new(file)
+        .lines()
+        .map_while(|line| line.ok())
+        .filter_map(|line| serde_json::from_str(&line).ok())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn entry(time: DateTime<Utc>, message: &str) -> Entry {
+        Entry {
+            time,
+            level: Level::Info,
+            component: "test".to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_rotates_by_size_and_tails_across_files() {
+        let dir = tempdir().unwrap();
+        let log = LogFile {
+            path: dir.path().join("logs").join("todo.log"),
+            max_bytes: 300,
+            max_age: Duration::days(7),
+        };
+        let now = Utc::now();
+        for n in 0..20 {
+            log.append(&entry(now, &format!("entry {}", n))).unwrap();
+        }
+
+        assert!(fs::metadata(&log.path).unwrap().len() <= 300);
+        assert!(log.generation(1).exists());
+        assert!(log.generation(ROTATED_FILES).exists());
+        assert!(!log.generation(ROTATED_FILES + 1).exists());
+
+        let messages: Vec<String> = log
+            .tail(4)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.message)
+            .collect();
+        assert_eq!(messages, ["entry 16", "entry 17", "entry 18", "entry 19"]);
+    }
+
+    #[test]
+    fn test_rotates_by_age() {
+        let dir = tempdir().unwrap();
+        let log = LogFile {
+            path: dir.path().join("todo.log"),
+            max_bytes: 1 << 20,
+            max_age: Duration::days(7),
+        };
+        let now = Utc::now();
+        log.append(&entry(now - Duration::days(8), "old")).unwrap();
+        log.append(&entry(now, "new")).unwrap();
+
+        assert_eq!(read_entries(&log.path).unwrap()[0].message, "new");
+        assert_eq!(read_entries(&log.generation(1)).unwrap()[0].message, "old");
+    }
+}
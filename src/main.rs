@@ -1,19 +1,57 @@
-use clap::{Parser, Subcommand};
+use anyhow::Context;
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
 use colored::*;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-pub mod commands;
-pub mod db;
-pub mod models;
+mod help;
+mod shell;
 
-use commands::{add_task, complete_task, delete_task, list_tasks, show_task, update_task};
+use commands::{
+    add_note, add_task, add_tasks_from, agenda, archive, attach, board, clone_task, command_line,
+    complete_ids, complete_projects, complete_tags, complete_task, confirm_tasks, delete_task,
+    doctor, estimate_task, export, find_template, focus_start, focus_status, focus_stop, get_task,
+    git_sync, goal_add, goal_delete, goal_link, goal_list, goal_show, goal_unlink, history,
+    history_entry, import_file, import_habitica, list_archived, list_tag_tree, list_tasks, logs,
+    maintain, move_task, move_to_column, next_tasks, notify_due, obsidian_sync, open_attachments,
+    parse_batch, parse_ids, pick, plan_budget, plan_email, project_delete, project_list,
+    project_priority, project_rename, purge, recent_log, record_command, report_aging, report_week,
+    reprioritize, resolve_task, resolve_tasks, restore_task, review, schedule_week, search,
+    self_update, set_task_from_json, share, show_task, snapshot, snooze_task, someday, stale_tasks,
+    start_timer, status_line, stop_timer, task_log, template_delete, template_list, template_save,
+    timesheet, trash, undo, update_task, usage, watch_list, why_task, ReviewDecisions,
+};
 use db::Database;
+use todo::{
+    board, commands, config, db, encryption, errln, filter_expr, hooks, import, logging,
+    maintenance, outln, permissions, quick_add, server, shutdown, snapshot, style, suggest, table,
+    tags, tui, urgency, zone, DueFilter, Energy, ExportFormat, ImportSource, ListColumn,
+    ListGroupBy, ListSort, OutputFormat, PickAction, Priority, TaskRef, TodoError, TriageFilter,
+};
 
 #[derive(Parser)]
 #[command(name = "todo")]
 #[command(about = "A simple todo CLI tool with SQLite storage")]
 #[command(version)]
-struct Cli {
+#[command(disable_help_subcommand = true)]
+pub(crate) struct Cli {
+    /// Use this profile for this invocation only
+    #[arg(long, global = true, value_name = "NAME")]
+    profile: Option<String>,
+    /// Use this task database instead of the configured one
+    #[arg(long, global = true, value_name = "PATH")]
+    db: Option<PathBuf>,
+    /// Line-oriented output without color, emoji or box drawing, for screen
+    /// readers and scripts
+    #[arg(long, global = true)]
+    plain: bool,
+    /// Never color output; `NO_COLOR` and output that is not a terminal
+    /// leave out color too
+    #[arg(long, global = true)]
+    no_color: bool,
+    /// Don't ask before deleting, purging, scheduling or updating several
+    /// tasks; prompts only appear when stdin is a terminal
+    #[arg(short, long, global = true)]
+    yes: bool,
     #[command(subcommand)]
     command: Commands,
 }
@@ -21,142 +59,2598 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     /// Add a new task
+    #[command(visible_alias = "a")]
     Add {
-        /// Task title
-        title: String,
+        /// Task title; `!high`, `#tag`, `@context` and a date at the end,
+        /// as in "Pay rent tomorrow !high #finance", are taken out of it
+        #[arg(required_unless_present_any = ["from_file", "template"])]
+        title: Option<String>,
+        /// Add one task per line of this file (`-` for stdin); lines may carry
+        /// #tag, +project, due:WHEN, start:WHEN, p:PRIORITY, repeat:RULE, after:ID and before:ID
+        #[arg(long, value_name = "PATH", conflicts_with_all = ["title", "description", "due", "start", "repeat", "template"])]
+        from_file: Option<PathBuf>,
+        /// Start from a saved template; other flags and the title override it
+        #[arg(long, value_name = "NAME")]
+        template: Option<String>,
         /// Task description
         #[arg(long)]
         description: Option<String>,
-        /// Due date (YYYY-MM-DD format)
+        /// Due date: YYYY-MM-DD, or e.g. tomorrow, next friday, in 2 weeks, eod
+        #[arg(short, long)]
+        due: Option<String>,
+        /// Keep the task out of `todo list` until this date, written like --due
+        #[arg(long, value_name = "WHEN")]
+        start: Option<String>,
+        /// Priority level (low, medium, high); defaults to `default_priority`
+        #[arg(short, long, value_enum)]
+        priority: Option<Priority>,
+        /// Tag to attach (can be repeated)
+        #[arg(long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+        /// Project to file the task under (created on first use)
+        #[arg(long)]
+        project: Option<String>,
+        /// Context the task can be done in, such as @home; instead of the `context` key
+        #[arg(long, value_name = "CONTEXT")]
+        context: Option<String>,
+        /// How long the task is expected to take, e.g. 45m or 2h
+        #[arg(long, value_name = "DURATION", conflicts_with = "from_file")]
+        estimate: Option<String>,
+        /// Repeat rule: daily, weekly, monthly, yearly or e.g. "every 3 days"
+        #[arg(long, value_name = "RULE")]
+        repeat: Option<String>,
+        /// Make this a subtask of another task
+        #[arg(long, value_name = "ID")]
+        parent: Option<TaskRef>,
+        /// How much energy the task takes, for `todo next --energy`
+        #[arg(long, value_enum)]
+        energy: Option<Energy>,
+        /// Add the task even if the same one is already pending
+        #[arg(long)]
+        force: bool,
+        /// Keep the title exactly as written, without quick-add parsing
+        #[arg(long)]
+        literal: bool,
+    },
+    /// Add a new pending task with the title, description, priority, tags and project of another
+    Clone {
+        /// ID of the task to copy
+        id: TaskRef,
+        /// Due date for the copy: YYYY-MM-DD, or e.g. tomorrow, next friday, in 2 weeks, eod
         #[arg(short, long)]
         due: Option<String>,
-        /// Priority level (low, medium, high)
-        #[arg(short, long, value_enum, default_value = "medium")]
-        priority: Priority,
     },
     /// List all tasks
+    #[command(args_override_self = true)]
     List {
         /// Show completed tasks
         #[arg(short, long)]
         completed: bool,
+        /// Show only completed tasks
+        #[arg(long, conflicts_with = "completed")]
+        done: bool,
         /// Filter by priority
         #[arg(short, long, value_enum)]
         priority: Option<Priority>,
+        /// Only show pending tasks due within the due-soon window
+        #[arg(long)]
+        due_soon: bool,
+        /// Size of the due-soon window in hours; defaults to `due_soon_hours`
+        #[arg(long, value_name = "HOURS")]
+        due_soon_hours: Option<i64>,
+        /// Only show pending tasks due today, overdue, or due this week
+        #[arg(long, value_enum, value_name = "WHEN")]
+        due: Option<DueFilter>,
+        /// Only show tasks with this tag or tags nested under it; globs like `work/*` are allowed (can be repeated)
+        #[arg(long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+        /// Only show tasks whose title matches this regular expression
+        #[arg(long, value_name = "REGEX")]
+        title_matches: Option<String>,
+        /// Only show tasks whose title and description or one of whose notes contain these words, as `todo search` matches them
+        #[arg(long, value_name = "WORDS")]
+        matching: Option<String>,
+        /// Hide tasks with this tag or tags nested under it (can be repeated)
+        #[arg(long = "not-tag", value_name = "TAG")]
+        not_tags: Vec<String>,
+        /// Hide tasks that have no due date
+        #[arg(long)]
+        no_due: bool,
+        /// Show only tasks in the someday/maybe bucket
+        #[arg(long)]
+        someday: bool,
+        /// Show only snoozed tasks that aren't due yet
+        #[arg(long)]
+        snoozed: bool,
+        /// Show only tasks whose start date hasn't come yet
+        #[arg(long)]
+        waiting: bool,
+        /// Also show tasks whose start date hasn't come yet
+        #[arg(long, conflicts_with = "waiting")]
+        all: bool,
+        /// Show only tasks in this project
+        #[arg(long)]
+        project: Option<String>,
+        /// Hide tasks in this project (can be repeated)
+        #[arg(
+            long = "not-project",
+            alias = "exclude-project",
+            value_name = "PROJECT"
+        )]
+        not_projects: Vec<String>,
+        /// Group plain output
+        #[arg(long, value_enum)]
+        group_by: Option<ListGroupBy>,
+        /// Group plain output by the value of a SQL expression over task
+        /// columns, e.g. "strftime('%Y-%W', due_date)"
+        #[arg(long, value_name = "EXPR", conflicts_with = "group_by")]
+        group_by_expr: Option<String>,
+        /// Order of the tasks
+        #[arg(long, value_enum, default_value = "priority")]
+        sort: ListSort,
+        /// Columns of the table, comma-separated
+        #[arg(
+            long,
+            value_enum,
+            value_delimiter = ',',
+            default_value = "id,title,priority,due,status,tags",
+            overrides_with = "columns"
+        )]
+        columns: Vec<ListColumn>,
+        /// Wrap long titles onto extra lines instead of cutting them off
+        #[arg(long)]
+        wrap: bool,
+        /// Show at most this many tasks
+        #[arg(long, value_name = "N")]
+        limit: Option<usize>,
+        /// Skip this many tasks first
+        #[arg(long, value_name = "N")]
+        offset: Option<usize>,
+        /// Show this page of --limit tasks, counting from 1
+        #[arg(
+            long,
+            value_name = "N",
+            requires = "limit",
+            conflicts_with = "offset",
+            value_parser = clap::value_parser!(u32).range(1..)
+        )]
+        page: Option<u32>,
+        /// Keep the list on screen and redraw it whenever the tasks change
+        #[arg(long)]
+        watch: bool,
+        /// Browse archived tasks instead (no filters apply)
+        #[arg(long, conflicts_with_all = [
+            "completed", "done", "priority", "due_soon", "due", "tags", "title_matches",
+            "matching", "not_tags", "no_due", "someday", "snoozed", "waiting", "all", "project",
+            "not_projects",
+            "sort", "group_by_expr", "limit", "offset", "page", "watch",
+        ])]
+        archived: bool,
+        /// Output format
+        #[arg(long, value_enum, default_value = "plain")]
+        format: OutputFormat,
+    },
+    /// Full-screen browser: filter bar, task list and details, vim keys
+    #[command(visible_alias = "ui")]
+    Tui {
+        /// Print the key bindings instead of starting
+        #[arg(long)]
+        show_keys: bool,
+    },
+    /// Tasks in columns side by side: To do, In progress and Done, or by priority or project
+    Board {
+        /// How to split tasks into columns
+        #[arg(long, value_enum, default_value = "status")]
+        group_by: board::GroupBy,
+    },
+    /// Agenda: overdue tasks and tasks due today
+    Today,
+    /// The most urgent tasks you can act on now
+    Next {
+        /// Only tasks that take at most this much energy, favoring ones that fit it
+        #[arg(long, value_enum)]
+        energy: Option<Energy>,
+        /// How many tasks to show
+        #[arg(short = 'n', long, default_value_t = 5)]
+        limit: usize,
+    },
+    /// Show or set how long a task is expected to take
+    Estimate {
+        /// Task ID
+        id: TaskRef,
+        /// Expected time, such as 45m or 2h
+        #[arg(conflicts_with = "clear")]
+        duration: Option<String>,
+        /// Remove the estimate
+        #[arg(long)]
+        clear: bool,
+    },
+    /// Propose due dates for tasks that have none
+    Schedule {
+        /// Fill the working days of the next week, up to `workday_hours` a day
+        #[arg(long, required = true)]
+        fill_week: bool,
+        /// Hours of work a day, instead of `workday_hours`
+        #[arg(long, value_name = "HOURS")]
+        hours: Option<i64>,
+    },
+    /// Pick the most pressing tasks that fit in a time budget
+    Plan {
+        /// Time available, such as 90m or 4h
+        budget: String,
+    },
+    /// Move tasks completed a while ago out of the task list into the archive
+    Archive {
+        /// Archive tasks completed more than this many days ago
+        #[arg(short, long, default_value_t = 30)]
+        days: i64,
+    },
+    /// Permanently delete completed tasks, or archive them with --archive
+    Purge {
+        /// Only tasks completed longer ago than this, such as 30d or 6w
+        #[arg(long, value_name = "AGE")]
+        older_than: Option<String>,
+        /// Move them to the archive instead of deleting them
+        #[arg(long)]
+        archive: bool,
     },
-    /// Mark a task as completed
+    /// Mark tasks as completed
     Complete {
+        /// Task IDs or ranges, e.g. 3 5 7-10
+        #[arg(value_name = "ID", required_unless_present_any = SELECTION_ARGS, conflicts_with_all = SELECTION_ARGS)]
+        ids: Vec<String>,
+        #[command(flatten)]
+        selection: Selection,
+    },
+    /// Move a task to a board column, or before or after another in the manual order (`todo list --sort manual`)
+    #[command(group(clap::ArgGroup::new("placement").required(true)))]
+    Move {
         /// Task ID
-        id: i32,
+        id: TaskRef,
+        /// Board column to move it to; done completes it
+        #[arg(value_enum, group = "placement")]
+        status: Option<board::Column>,
+        /// Put it just before this task
+        #[arg(long, value_name = "ID", group = "placement")]
+        before: Option<TaskRef>,
+        /// Put it just after this task
+        #[arg(long, value_name = "ID", group = "placement")]
+        after: Option<TaskRef>,
     },
-    /// Delete a task
+    /// Move tasks to the trash
     Delete {
+        /// Task IDs or ranges, e.g. 3 5 7-10
+        #[arg(value_name = "ID", required_unless_present_any = SELECTION_ARGS, conflicts_with_all = SELECTION_ARGS)]
+        ids: Vec<String>,
+        #[command(flatten)]
+        selection: Selection,
+    },
+    /// Append a timestamped note to a task
+    Note {
         /// Task ID
-        id: i32,
+        id: TaskRef,
+        /// Note text
+        text: String,
     },
-    /// Update a task
-    Update {
+    /// Attach a link or a file to a task
+    Attach {
         /// Task ID
-        id: i32,
+        id: TaskRef,
+        /// A URL such as https://example.com/spec, or a file path such as ./design.pdf
+        #[arg(value_name = "URL_OR_PATH")]
+        target: String,
+    },
+    /// Open a task's attachments in their default applications
+    Open {
+        /// Task ID
+        id: TaskRef,
+        /// Open only this attachment, numbered as `todo show` lists them
+        #[arg(value_name = "N")]
+        number: Option<usize>,
+    },
+    /// Start tracking time on a task; one timer runs at a time
+    Start {
+        /// Task ID
+        id: TaskRef,
+    },
+    /// Stop the running timer
+    Stop,
+    /// Tracked time per day and task
+    Timesheet {
+        /// Days to cover, counting today
+        #[arg(short, long, default_value_t = 7)]
+        days: i64,
+    },
+    /// Show who or what changed a task, and when; without an ID, the latest changes to any task
+    Log {
+        /// Task ID
+        id: Option<TaskRef>,
+        /// Number of changes to show when no ID is given
+        #[arg(short = 'n', long, default_value_t = 20, conflicts_with = "id")]
+        limit: usize,
+    },
+    /// List deleted tasks
+    Trash {
+        /// Permanently delete everything in the trash
+        #[arg(long)]
+        empty: bool,
+    },
+    /// Bring a deleted task back from the trash
+    Restore {
+        /// Task ID
+        id: TaskRef,
+    },
+    /// Reverse the last delete, update or complete
+    Undo,
+    /// Update one or more tasks
+    Update {
+        /// Task IDs or ranges, e.g. 3 5 7-10
+        #[arg(value_name = "ID", required_unless_present_any = SELECTION_ARGS, conflicts_with_all = SELECTION_ARGS)]
+        ids: Vec<String>,
+        #[command(flatten)]
+        selection: Selection,
         /// New title
         #[arg(short, long)]
         title: Option<String>,
         /// New description
         #[arg(long)]
         description: Option<String>,
-        /// New due date (YYYY-MM-DD format)
+        /// New due date: YYYY-MM-DD, or e.g. tomorrow, next friday, in 2 weeks, eod
         #[arg(short, long)]
         due: Option<String>,
+        /// New start date; the task stays out of `todo list` until then
+        #[arg(long, value_name = "WHEN")]
+        start: Option<String>,
+        /// Remove the start date
+        #[arg(long, conflicts_with = "start")]
+        no_start: bool,
         /// New priority level
         #[arg(short, long, value_enum)]
         priority: Option<Priority>,
+        /// Take the priority from the project or parent task from now on
+        #[arg(long, conflicts_with = "priority")]
+        inherit_priority: bool,
+        /// Move the task to this project
+        #[arg(long)]
+        project: Option<String>,
+        /// New repeat rule: daily, weekly, monthly, yearly or e.g. "every 3 days"
+        #[arg(long, value_name = "RULE")]
+        repeat: Option<String>,
+        /// Stop the task from repeating
+        #[arg(long, conflicts_with = "repeat")]
+        no_repeat: bool,
+        /// Move the task under another task
+        #[arg(long, value_name = "ID")]
+        parent: Option<TaskRef>,
+        /// Move a subtask back to the top level
+        #[arg(long, conflicts_with = "parent")]
+        no_parent: bool,
+        /// How much energy the task takes
+        #[arg(long, value_enum)]
+        energy: Option<Energy>,
+        /// Move the task to a context, such as @errands, replacing its previous one
+        #[arg(long, value_name = "CONTEXT")]
+        context: Option<String>,
+        /// How long the tasks are expected to take, e.g. 45m or 2h
+        #[arg(long, value_name = "DURATION")]
+        estimate: Option<String>,
+    },
+    /// Search task titles, descriptions and notes
+    Search {
+        /// Words to look for; each must match the start of a word
+        #[arg(required = true)]
+        query: Vec<String>,
+        /// Include completed tasks
+        #[arg(short, long)]
+        completed: bool,
+        /// Only search tasks with this tag or tags nested under it (can be repeated)
+        #[arg(long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+        /// Only search tasks in this project
+        #[arg(long)]
+        project: Option<String>,
+        /// Only search tasks with this priority
+        #[arg(short, long, value_enum)]
+        priority: Option<Priority>,
     },
     /// Show details of a specific task
     Show {
         /// Task ID
+        id: TaskRef,
+        /// Output format
+        #[arg(long, value_enum, default_value = "plain")]
+        format: OutputFormat,
+    },
+    /// Summaries of the backlog
+    Report {
+        #[command(subcommand)]
+        command: ReportCommands,
+    },
+    /// List pending tasks that have not been touched in a while
+    Stale {
+        /// Minimum number of days since the task was last updated
+        #[arg(short, long, default_value_t = 30)]
+        days: i64,
+        /// Tag the listed tasks as "stale"
+        #[arg(long)]
+        tag: bool,
+    },
+    /// Explain what is preventing a task from being actionable
+    Why {
+        /// Task ID
+        id: TaskRef,
+    },
+    /// Check the task hierarchy for inconsistencies
+    Doctor,
+    /// Find an open task with a fuzzy search and show, complete, delete or start it
+    Pick {
+        /// What to do with the picked task
+        #[arg(value_enum, default_value = "show")]
+        action: PickAction,
+    },
+    /// Run the enabled housekeeping jobs (stale tagging, priority decay) now
+    Maintain,
+    /// Import tasks from another service or a file
+    Import {
+        /// Service or file format to import from; todo's own JSON, CSV and
+        /// Markdown files are recognized by their extension
+        #[arg(long, visible_alias = "format", value_enum, required_unless_present_any = ["file", "resume"])]
+        from: Option<ImportSource>,
+        /// File to import
+        file: Option<PathBuf>,
+        /// What to do with imported tasks whose ID is already taken
+        #[arg(long, value_enum, default_value = "renumber")]
+        on_conflict: import::OnConflict,
+        /// Show what would be imported without writing anything
+        #[arg(long, conflicts_with = "resume")]
+        dry_run: bool,
+        /// Finish an interrupted file import
+        #[arg(long, conflicts_with_all = ["from", "file", "on_conflict", "force"])]
+        resume: bool,
+        /// Import tasks even if the same ones are already pending
+        #[arg(long)]
+        force: bool,
+        /// Put every imported task in this project
+        #[arg(long, value_name = "NAME", conflicts_with = "resume")]
+        project: Option<String>,
+        /// Rename one of the file's projects: THEIRS=MINE, or THEIRS= to drop it
+        #[arg(long, value_name = "THEIRS=MINE", value_parser = import::parse_mapping, conflicts_with = "resume")]
+        map_project: Vec<(String, Option<String>)>,
+        /// Rename one of the file's tags, with its nested tags: THEIRS=MINE,
+        /// or THEIRS= to drop it
+        #[arg(long, value_name = "THEIRS=MINE", value_parser = import::parse_mapping, conflicts_with = "resume")]
+        map_tag: Vec<(String, Option<String>)>,
+        /// Ask what to do with each project and tag you don't have yet
+        #[arg(long, conflicts_with = "resume")]
+        interactive: bool,
+        /// Habitica user ID; the API token is then asked for, or read from
+        /// TODO_HABITICA_TOKEN (both saved to the system keyring)
+        #[arg(long)]
+        habitica_user: Option<String>,
+    },
+    /// Write every task to a file or stdout for backups and migration
+    Export {
+        /// Output format
+        #[arg(long, value_enum, default_value = "json")]
+        format: ExportFormat,
+        /// Write to this file instead of stdout
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Sync checklist items with an Obsidian vault
+    Obsidian {
+        #[command(subcommand)]
+        command: ObsidianCommands,
+    },
+    /// Sync tasks with other machines through a git repository
+    Sync {
+        /// Repository to keep the tasks in, instead of `sync_repo`
+        #[arg(long, value_name = "PATH")]
+        repo: Option<PathBuf>,
+        /// Only update the repository, without pulling or pushing
+        #[arg(long)]
+        offline: bool,
+    },
+    /// Print a single task in a structured format
+    Get {
+        /// Task ID
+        id: TaskRef,
+        /// Output format
+        #[arg(long, value_enum, default_value = "json")]
+        format: OutputFormat,
+    },
+    /// Replace a task with a full task document
+    Set {
+        /// Task ID
+        id: TaskRef,
+        /// Read the task JSON from this file, or `-` for stdin
+        #[arg(long, value_name = "FILE")]
+        from_json: String,
+    },
+    /// Run many commands in one process and one transaction, printing a JSON
+    /// line per command
+    Batch {
+        /// File of commands, one per line (or a JSON array), or `-` for stdin
+        file: PathBuf,
+    },
+    /// Set up the task database
+    Init {
+        /// Encrypt the database with a passphrase (needs a build with the
+        /// `encryption` feature)
+        #[arg(long, required = true)]
+        encrypted: bool,
+        /// Remember the passphrase in the system keyring
+        #[arg(long)]
+        keyring: bool,
+    },
+    /// Print shell integration (quick-capture widget and prompt helper)
+    ShellInit {
+        /// Shell to generate the integration for
+        #[arg(value_enum)]
+        shell: shell::Shell,
+    },
+    /// Print a shell completion script
+    Completions {
+        /// Shell to generate completions for
+        #[arg(value_enum)]
+        shell: shell::Completions,
+    },
+    /// Print a one-line summary of open tasks for prompts and status bars
+    StatusLine,
+    /// Send desktop reminders for tasks due soon, once per due date; run it
+    /// every few minutes from cron or a systemd timer
+    Notify {
+        /// Remind of tasks due within this many hours; defaults to `due_soon_hours`
+        #[arg(long, value_name = "HOURS")]
+        hours: Option<i64>,
+        /// Print the reminders instead of showing notifications
+        #[arg(long)]
+        print: bool,
+    },
+    /// Show the tag hierarchy with pending task counts
+    Tags,
+    /// Render a board image of your tasks
+    Snapshot {
+        /// Image format
+        #[arg(long, value_enum, default_value = "svg")]
+        format: snapshot::SnapshotFormat,
+        /// How to split tasks into columns
+        #[arg(long, value_enum, default_value = "status")]
+        group_by: snapshot::GroupBy,
+        /// Write to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Push a task's due date back
+    Snooze {
+        /// Task ID
+        id: TaskRef,
+        /// How long to snooze for (30m, 3h, 2d, 1w, 2mo) or until when
+        /// (tomorrow, next monday, YYYY-MM-DD); one day by default
+        #[arg(value_name = "DURATION|DATE")]
+        when: Option<String>,
+        /// Number of days to push the due date by
+        #[arg(short, long, conflicts_with = "when")]
+        days: Option<i64>,
+    },
+    /// Move a task to someday/maybe, or list the bucket when no ID is given
+    Someday {
+        /// Task ID
+        id: Option<TaskRef>,
+        /// Move the task back to the active list instead
+        #[arg(long, requires = "id")]
+        promote: bool,
+    },
+    /// Walk through items that need a decision
+    Review {
+        /// Include someday items that are not due for review yet
+        #[arg(long)]
+        all: bool,
+        /// Print the items due for review instead of asking about them
+        #[arg(long)]
+        list: bool,
+        /// Promote this someday task without asking (can be repeated)
+        #[arg(long, value_name = "ID")]
+        promote: Vec<TaskRef>,
+        /// Drop this someday task to the trash without asking (can be repeated)
+        #[arg(long, value_name = "ID")]
+        drop: Vec<TaskRef>,
+        /// Keep this someday task until the next review without asking (can be repeated)
+        #[arg(long, value_name = "ID")]
+        keep: Vec<TaskRef>,
+    },
+    /// Step through tasks and set each one's priority with a single key
+    Reprioritize {
+        /// Which pending tasks to go through
+        #[arg(long, value_enum, default_value = "all")]
+        filter: TriageFilter,
+        /// Print the matching tasks instead of asking about them
+        #[arg(long)]
+        list: bool,
+    },
+    /// Print this week's plan as an HTML email with one-click action links
+    PlanEmail {
+        /// Public URL of the todo server the links should point at
+        #[arg(long)]
+        base_url: String,
+        /// Recipient address for the To: header
+        #[arg(long)]
+        to: Option<String>,
+    },
+    /// Print a signed link to a read-only page of a task or project
+    Share {
+        /// ID of the task to share, with its subtasks
+        #[arg(required_unless_present = "project", conflicts_with = "project")]
+        id: Option<TaskRef>,
+        /// Share every task of this project instead
+        #[arg(long)]
+        project: Option<String>,
+        /// Confirm that anyone holding the link can see the page, no token needed
+        #[arg(long, required = true)]
+        public: bool,
+        /// How long the link works, such as 7d or 2w
+        #[arg(long, default_value = "7d")]
+        expires: String,
+        /// Public URL of the todo server the link should point at
+        #[arg(long)]
+        base_url: String,
+    },
+    /// Serve a JSON API over the task database, and the plan-email and share links
+    Serve {
+        /// Port to listen on
+        #[arg(long, default_value_t = 8080)]
+        port: u16,
+        /// Address to listen on; anything but loopback needs --token
+        #[arg(long, default_value = "127.0.0.1")]
+        host: String,
+        /// Bearer token that API requests must send
+        #[arg(long)]
+        token: Option<String>,
+    },
+    /// Timed do-not-disturb focus sessions
+    Focus {
+        #[command(subcommand)]
+        command: FocusCommands,
+    },
+    /// Read and change settings
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+    /// Show help for a command or topic (filters, dates, tagging, environment)
+    Help {
+        /// Command or topic name
+        topic: Option<String>,
+        /// Print the full man page in roff format
+        #[arg(long, conflicts_with = "topic")]
+        man: bool,
+    },
+    /// Download and install the latest release
+    SelfUpdate {
+        /// Only report whether an update is available
+        #[arg(long)]
+        check: bool,
+    },
+    /// Show the last entries of the activity log (see `log_file`)
+    Logs {
+        /// Number of entries to show
+        #[arg(long, default_value_t = 50)]
+        tail: usize,
+    },
+    /// Show the commands you ran recently (see `command_history`)
+    History {
+        /// Number of commands to show
+        #[arg(short = 'n', long, default_value_t = 20)]
+        limit: usize,
+        /// Forget every recorded command
+        #[arg(long)]
+        clear: bool,
+    },
+    /// Run the last command again, or the one numbered in `todo history`
+    #[command(name = "!!")]
+    Again {
+        /// Command number from `todo history`
+        number: Option<usize>,
+    },
+    /// Show locally recorded command usage
+    Usage {
+        /// Delete the recorded history
+        #[arg(long)]
+        reset: bool,
+    },
+    /// Show or switch the context: the tag `todo list` and `todo next` filter by and `todo add` attaches
+    Context {
+        /// Context to switch to, such as @home
+        name: Option<String>,
+        /// Stop filtering by a context
+        #[arg(long, conflicts_with = "name")]
+        clear: bool,
+    },
+    /// Define shortcuts for command lines, such as `todo urgent` for a long `todo list`
+    Alias {
+        #[command(subcommand)]
+        command: AliasCommands,
+    },
+    /// Switch between bundles of config and database
+    Profile {
+        #[command(subcommand)]
+        command: ProfileCommands,
+    },
+    /// Manage projects
+    Project {
+        #[command(subcommand)]
+        command: ProjectCommands,
+    },
+    /// Longer-term goals that tasks contribute to
+    Goal {
+        #[command(subcommand)]
+        command: GoalCommands,
+    },
+    /// Save tasks as templates for `todo add --template`
+    Template {
+        #[command(subcommand)]
+        command: TemplateCommands,
+    },
+}
+
+/// Picks the pending tasks a bulk command acts on instead of listing IDs;
+/// every criterion given must hold.
+#[derive(clap::Args, Debug, Default)]
+struct Selection {
+    /// Act on pending tasks with this tag or tags nested under it (can be repeated)
+    #[arg(long, value_name = "TAG")]
+    tagged: Vec<String>,
+    /// Act on pending tasks in this project
+    #[arg(long, value_name = "PROJECT")]
+    in_project: Option<String>,
+    /// Act on pending tasks with this priority
+    #[arg(long, value_enum, value_name = "PRIORITY")]
+    with_priority: Option<Priority>,
+    /// Act on pending tasks whose title and description or one of whose notes contain these words
+    #[arg(long, value_name = "WORDS")]
+    matching: Option<String>,
+    /// Act on pending tasks matching a filter expression, e.g. "priority=high due<today"
+    /// (see `todo help filters`)
+    #[arg(long, value_name = "EXPR")]
+    filter: Option<String>,
+}
+
+const SELECTION_ARGS: [&str; 5] = [
+    "tagged",
+    "in_project",
+    "with_priority",
+    "matching",
+    "filter",
+];
+
+impl Selection {
+    fn is_empty(&self) -> bool {
+        self.tagged.is_empty()
+            && self.in_project.is_none()
+            && self.with_priority.is_none()
+            && self.matching.is_none()
+            && self.filter.is_none()
+    }
+
+    /// The filter for the criteria given, or `None` when there are none.
+    fn filter(&self) -> anyhow::Result<Option<db::TaskFilter>> {
+        if self.is_empty() {
+            return Ok(None);
+        }
+        let mut builder = match &self.filter {
+            Some(expression) => filter_expr::parse(expression, chrono::Utc::now())?,
+            None => db::TaskFilter::builder(),
+        };
+        for tag in &self.tagged {
+            builder = builder.tag(tag);
+        }
+        if let Some(project) = &self.in_project {
+            builder = builder.project(project);
+        }
+        if let Some(priority) = &self.with_priority {
+            builder = builder.priority(priority.clone());
+        }
+        if let Some(words) = &self.matching {
+            builder = builder.text(words);
+        }
+        Ok(Some(builder.build()?))
+    }
+}
+
+#[derive(Subcommand)]
+enum GoalCommands {
+    /// Add a goal
+    Add {
+        /// Goal title
+        title: String,
+        /// Target date: YYYY-MM-DD, or e.g. next month, in 6 weeks
+        #[arg(long)]
+        by: Option<String>,
+    },
+    /// List goals with their progress
+    List,
+    /// Show a goal's progress, time left and linked tasks
+    Show {
+        /// Goal ID
         id: i32,
     },
+    /// Link tasks to a goal
+    Link {
+        /// Goal ID
+        goal: i32,
+        /// Task IDs
+        #[arg(required = true)]
+        tasks: Vec<TaskRef>,
+    },
+    /// Unlink tasks from their goal
+    Unlink {
+        /// Task IDs
+        #[arg(required = true)]
+        tasks: Vec<TaskRef>,
+    },
+    /// Delete a goal, keeping its tasks
+    Delete {
+        /// Goal ID
+        id: i32,
+    },
+}
+
+#[derive(Subcommand)]
+enum FocusCommands {
+    /// Start a focus session
+    Start {
+        /// Session length in minutes
+        #[arg(short, long, default_value_t = 25)]
+        minutes: i64,
+        /// Task to pin and log the session against
+        #[arg(short, long)]
+        task: Option<TaskRef>,
+    },
+    /// End the running focus session early
+    Stop,
+    /// Show the running focus session
+    Status,
+}
+
+#[derive(Subcommand)]
+enum ConfigCommands {
+    /// Print the effective value of a key
+    Get {
+        /// Config key
+        key: String,
+    },
+    /// Show where each effective value comes from (default, file or environment)
+    Sources,
+    /// Write a key to a config file (the user file, or the profile given with --profile)
+    Set {
+        /// Config key
+        key: String,
+        /// New value
+        #[arg(allow_hyphen_values = true)]
+        value: String,
+        /// Write to ./.todo.toml instead
+        #[arg(long, conflicts_with = "system")]
+        local: bool,
+        /// Write to /etc/todo/config.toml instead
+        #[arg(long)]
+        system: bool,
+    },
+    /// Print every key with its effective value
+    List,
+    /// Open a config file in $VISUAL / $EDITOR and validate it afterwards
+    Edit {
+        /// Edit ./.todo.toml instead
+        #[arg(long, conflicts_with = "system")]
+        local: bool,
+        /// Edit /etc/todo/config.toml instead
+        #[arg(long)]
+        system: bool,
+    },
+    /// Bundle the user config file and every profile into one file to share
+    Export {
+        /// Write to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Merge a file from `todo config export` into the user config and profiles
+    Import {
+        /// The exported file, or `-` for stdin
+        file: PathBuf,
+    },
+}
+
+impl ConfigCommands {
+    /// File that `set` and `edit` write to.
+    fn target(local: bool, system: bool, profile: Option<&str>) -> anyhow::Result<PathBuf> {
+        let layer = if local {
+            config::Layer::Local
+        } else if system {
+            config::Layer::System
+        } else if let Some(name) = profile {
+            return config::profile_path(name);
+        } else {
+            config::Layer::User
+        };
+        layer
+            .path()
+            .ok_or_else(|| anyhow::anyhow!("Could not find the config directory"))
+    }
+}
+
+#[derive(Subcommand)]
+enum AliasCommands {
+    /// List aliases and the command lines they stand for
+    List,
+    /// Define an alias (in the user config, or the profile given with --profile)
+    Add {
+        /// Alias name, typed in place of a command
+        name: String,
+        /// Command line it stands for, such as list --priority high --due week
+        #[arg(required = true, num_args = 1.., allow_hyphen_values = true, trailing_var_arg = true)]
+        command: Vec<String>,
+    },
+    /// Remove an alias
+    #[command(alias = "rm")]
+    Remove {
+        /// Alias name
+        name: String,
+    },
 }
 
-#[derive(clap::ValueEnum, Clone, Debug)]
-pub enum Priority {
-    Low,
-    Medium,
-    High,
+#[derive(Subcommand)]
+enum ProfileCommands {
+    /// Create a profile
+    #[command(alias = "add")]
+    Create {
+        /// Profile name
+        name: String,
+        /// Task database for this profile (~/.todo-NAME.db by default)
+        #[arg(long, value_name = "PATH")]
+        db: Option<String>,
+        /// Tag that `list` and `next` filter by and `add` attaches
+        #[arg(long, value_name = "TAG")]
+        context: Option<String>,
+    },
+    /// List profiles, marking the active one
+    List,
+    /// Make a profile the default for every invocation
+    #[command(alias = "switch")]
+    Use {
+        /// Profile name
+        name: String,
+    },
+    /// Stop using a sticky profile
+    Clear,
+    /// Delete a profile (its database is left alone)
+    Delete {
+        /// Profile name
+        name: String,
+    },
 }
 
-impl Priority {
-    fn to_int(&self) -> i32 {
-        match self {
-            Priority::Low => 0,
-            Priority::Medium => 1,
-            Priority::High => 2,
+#[derive(Subcommand)]
+enum ReportCommands {
+    /// Open tasks bucketed by age (<1w, 1–4w, 1–3m, >3m) and the oldest ones
+    Aging {
+        /// How many of the oldest open tasks to list
+        #[arg(long, value_name = "N", default_value_t = 5)]
+        oldest: usize,
+    },
+    /// Tasks completed on each of the last seven days
+    Week {
+        /// Cover the previous calendar week instead, starting on `week_start`
+        #[arg(long)]
+        last: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum TemplateCommands {
+    /// Save a task's title, description, priority, tags, project and due date
+    Save {
+        /// Template name, e.g. weekly-report
+        name: String,
+        /// Task to copy
+        id: TaskRef,
+        /// When tasks from the template are due, e.g. "in 3 days"; defaults to
+        /// the task's due date relative to when it was added
+        #[arg(long, value_name = "WHEN")]
+        due: Option<String>,
+    },
+    /// List templates
+    List,
+    /// Delete a template
+    Delete {
+        /// Template name
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum ProjectCommands {
+    /// List projects with their task counts
+    List,
+    /// Rename a project
+    Rename {
+        /// Current name
+        old: String,
+        /// New name
+        new: String,
+    },
+    /// Delete a project, keeping its tasks
+    Delete {
+        /// Project name
+        name: String,
+    },
+    /// Set the priority that the project's tasks without one of their own inherit
+    Priority {
+        /// Project name
+        name: String,
+        /// Default priority; shows the current one when left out
+        #[arg(value_enum, conflicts_with = "clear")]
+        priority: Option<Priority>,
+        /// Remove the project's default priority
+        #[arg(long)]
+        clear: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ObsidianCommands {
+    /// Import open checklist items from a vault and check off completed ones
+    Sync {
+        /// Path to the Obsidian vault
+        #[arg(long)]
+        vault: PathBuf,
+        /// List the changes the sync would make, then ask before making them
+        #[arg(long)]
+        plan: bool,
+        /// Make the planned changes without asking
+        #[arg(long, requires = "plan")]
+        apply: bool,
+    },
+}
+
+fn main() -> anyhow::Result<()> {
+    let result = run().map_err(explain_lock);
+    if let Err(error) = &result {
+        if let Some(interrupted) = error.downcast_ref::<shutdown::Interrupted>() {
+            logging::warn("cli", format!("{:#}", error));
+            errln!("⏹️  {:#}", error);
+            std::process::exit(interrupted.exit_code());
+        }
+        logging::error("cli", format!("{:#}", error));
+        // Returning the error would exit with 1 whatever it was
+        let code = TodoError::exit_code_of(error);
+        if code != 1 {
+            eprintln!("Error: {:?}", error);
+            std::process::exit(code);
         }
     }
+    result
+}
 
-    fn from_int(value: i32) -> Self {
-        match value {
-            0 => Priority::Low,
-            1 => Priority::Medium,
-            2 => Priority::High,
-            _ => Priority::Medium,
+/// Say what happened when SQLite gave up waiting for another process's
+/// lock, rather than only "database is locked".
+fn explain_lock(error: anyhow::Error) -> anyhow::Error {
+    let locked = error
+        .chain()
+        .filter_map(|cause| cause.downcast_ref::<rusqlite::Error>())
+        .any(db::is_locked);
+    if !locked {
+        return error;
+    }
+    error.context(TodoError::DbLocked)
+}
+
+fn run() -> anyhow::Result<()> {
+    run_with(std::env::args_os().collect())
+}
+
+/// Run the command line `args`, starting with the program name.
+fn run_with(mut args: Vec<std::ffi::OsString>) -> anyhow::Result<()> {
+    let mut expanded = Vec::new();
+    let matches = loop {
+        match Cli::command().try_get_matches_from(&args) {
+            Ok(matches) => break matches,
+            Err(error) => match expand_alias(&error, &args, &mut expanded)? {
+                Some(with_alias) => args = with_alias,
+                None => args = correct_typo(error, &args),
+            },
+        }
+    };
+    let command_name = matches.subcommand_name().unwrap_or_default().to_string();
+    let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let started = std::time::Instant::now();
+
+    match &cli.command {
+        Commands::ShellInit { shell } => {
+            print!("{}", shell::init_script(*shell));
+            return Ok(());
+        }
+        Commands::Completions { shell } => {
+            if let Some(shell) = shell.shell() {
+                print!("{}", shell::completion_script(shell));
+                return Ok(());
+            }
+        }
+        Commands::SelfUpdate { check } => return self_update(*check),
+        Commands::Help { topic, man } => {
+            if *man {
+                help::render_man_page(&mut std::io::stdout())?;
+            } else {
+                help::print_help(topic.as_deref())?;
+            }
+            return Ok(());
+        }
+        Commands::Config { command } => return run_config_command(command, &cli),
+        Commands::Profile { command } => return run_profile_command(command, &cli),
+        Commands::Context { name, clear } => {
+            return run_context_command(name.as_deref(), *clear, &cli)
         }
+        Commands::Alias { command } => return run_alias_command(command, &cli),
+        _ => {}
+    }
+
+    let (config, warnings) = load_config(&cli)?;
+    for warning in warnings {
+        errln!("⚠️  {}", warning);
+    }
+    if !config.color || config.plain {
+        colored::control::set_override(false);
+    }
+    style::init(style::Style {
+        date_format: config.date_format.clone(),
+        scheme: config.color_scheme,
+        colors: config.colors.clone(),
+        tag_colors: config.tag_colors.clone(),
+        project_colors: config.project_colors.clone(),
+        plain: config.plain,
+    });
+    zone::init(config.timezone);
+    let log_file = config.log_file()?;
+    if let Commands::Logs { tail } = &cli.command {
+        return logs(log_file.as_ref(), *tail);
+    }
+    if let Some(log_file) = log_file {
+        logging::init(log_file);
+    }
+    if let (
+        Commands::List {
+            archived: false, ..
+        },
+        Some(defaults),
+    ) = (&cli.command, &config.list_filter)
+    {
+        cli = with_list_defaults(&args, defaults, &config)?;
     }
 
-    fn color(&self) -> colored::ColoredString {
-        match self {
-            Priority::Low => "LOW".blue(),
-            Priority::Medium => "MEDIUM".yellow(),
-            Priority::High => "HIGH".red(),
+    if let Commands::Init { keyring, .. } = &cli.command {
+        return init_encrypted(&config, *keyring);
+    }
+
+    // Quick capture (`add`) is latency sensitive: it opens the database and
+    // inserts, nothing else. Anything slower (listing, housekeeping) belongs
+    // in the other arms.
+    let db = open_database(&config)?;
+    // Hooks run for the task history this command writes
+    let hooks = hooks::hooks_dir().filter(|dir| hooks::any(dir));
+    let history_start = match hooks {
+        Some(_) => db.last_history_id()?,
+        None => 0,
+    };
+
+    // Commands that write in many steps stop between steps on Ctrl-C or
+    // SIGTERM instead of dying mid-way
+    let _shutdown = matches!(
+        cli.command,
+        Commands::Import { .. }
+            | Commands::Export { .. }
+            | Commands::Obsidian { .. }
+            | Commands::Sync { .. }
+            | Commands::Maintain
+    )
+    .then(shutdown::listen);
+
+    let skip_maintenance = matches!(
+        cli.command,
+        Commands::Add { .. }
+            | Commands::Maintain
+            | Commands::StatusLine
+            | Commands::Completions { .. }
+    );
+    if !skip_maintenance {
+        maintenance::run_if_due(
+            &db,
+            chrono::Duration::minutes(config.maintenance_interval),
+            &maintenance_options(&config),
+        )?;
+    }
+
+    match &cli.command {
+        Commands::Serve { port, host, token } => {
+            let loopback = host == "localhost"
+                || host
+                    .parse::<std::net::IpAddr>()
+                    .is_ok_and(|address| address.is_loopback());
+            if !loopback && token.is_none() {
+                anyhow::bail!(
+                    "Refusing to listen on {} without --token: anyone who can reach it could change your tasks",
+                    host
+                );
+            }
+            let listener = std::net::TcpListener::bind((host.as_str(), *port))
+                .with_context(|| format!("Cannot listen on {}:{}", host, port))?;
+            outln!(
+                "🌐 Serving the task API on http://{}",
+                listener.local_addr()?
+            );
+            let options = server::ServerOptions {
+                token: token.as_deref(),
+                link_secret: db.get_meta(db::META_LINK_SECRET)?,
+                due_soon_window: chrono::Duration::hours(config.due_soon_hours),
+                hooks: hooks.as_deref(),
+                port: listener.local_addr()?.port(),
+            };
+            let mut store = todo::store::TodoStore::from_database(db);
+            return server::serve(&mut store, listener, &options);
+        }
+        Commands::Again { number } => {
+            let again = history_entry(&db, *number)?;
+            errln!("↻ {}", command_line(&again));
+            drop(db);
+            let program = args.into_iter().take(1);
+            return run_with(program.chain(again.into_iter().map(Into::into)).collect());
         }
+        Commands::Batch { file } => run_batch(&db, &config, file)?,
+        _ => execute(&cli, &db, &config)?,
     }
-}
 
-fn main() -> anyhow::Result<()> {
-    let cli = Cli::parse();
+    if let Some(dir) = &hooks {
+        hooks::run_since(&db, dir, history_start)?;
+    }
 
-    // Initialize database
-    let db_path = get_db_path()?;
-    let db = Database::new(&db_path)?;
-    db.init()?;
+    if config.command_history
+        && !matches!(
+            cli.command,
+            Commands::History { .. } | Commands::Completions { .. } | Commands::StatusLine
+        )
+    {
+        let args: Vec<String> = args
+            .iter()
+            .skip(1)
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect();
+        record_command(&db, &args)?;
+    }
 
+    // Completion lookups run on every Tab and are not commands you ran
+    if config.usage_metrics && !matches!(cli.command, Commands::Completions { .. }) {
+        let elapsed = chrono::Duration::from_std(started.elapsed())?;
+        db.record_command_usage(&command_name, elapsed)?;
+    }
+
+    Ok(())
+}
+
+/// The board's work-in-progress limits from the `wip_limit_*` keys.
+fn wip_limits(config: &config::Config) -> board::WipLimits {
+    board::WipLimits {
+        todo: config.wip_limit_todo.max(0) as usize,
+        in_progress: config.wip_limit_in_progress.max(0) as usize,
+    }
+}
+
+/// Run one command that works on the open database. `run_with` calls this
+/// for most commands, and `todo batch` once per line.
+fn execute(cli: &Cli, db: &Database, config: &config::Config) -> anyhow::Result<()> {
     match &cli.command {
+        Commands::Add {
+            from_file: Some(path),
+            priority,
+            tags,
+            project,
+            context,
+            parent,
+            force,
+            ..
+        } => {
+            let text = if path == Path::new("-") {
+                std::io::read_to_string(std::io::stdin()).context("Cannot read tasks from stdin")?
+            } else {
+                std::fs::read_to_string(path)
+                    .with_context(|| format!("Cannot read {}", path.display()))?
+            };
+            add_tasks_from(
+                db,
+                &text,
+                priority.as_ref().unwrap_or(&config.default_priority),
+                &with_context(db, tags, context.as_deref(), config)?,
+                project.as_deref(),
+                parent
+                    .as_ref()
+                    .map(|parent| resolve_task(db, parent))
+                    .transpose()?,
+                *force,
+            )?
+        }
         Commands::Add {
             title,
             description,
             due,
+            start,
             priority,
-        } => add_task(&db, title, description.as_deref(), due.as_deref(), priority)?,
+            tags,
+            project,
+            context,
+            estimate,
+            repeat,
+            parent,
+            energy,
+            force,
+            literal,
+            template,
+            ..
+        } => {
+            let template = template
+                .as_deref()
+                .map(|name| find_template(db, name))
+                .transpose()?;
+            let mut quick = match title {
+                Some(title) if !*literal => quick_add::parse(title, chrono::Utc::now()),
+                _ => quick_add::QuickAdd {
+                    title: title
+                        .clone()
+                        .or(template.as_ref().map(|template| template.title.clone()))
+                        .unwrap_or_default(),
+                    ..Default::default()
+                },
+            };
+            // Flags win; with --due, a date at the end stays in the title
+            if let Some(words) = quick.due.take_if(|_| due.is_some()) {
+                quick.title = format!("{} {}", quick.title, words);
+            }
+            // The title and flags fill in over the template
+            let template = template.unwrap_or_default();
+            let mut all_tags = template.tags;
+            for tag in tags.iter().cloned().chain(quick.tags) {
+                if !all_tags.contains(&tag) {
+                    all_tags.push(tag);
+                }
+            }
+            let project = project.clone().or(template.project);
+            let template_priority = template.priority.map(Priority::from_int);
+            add_task(
+                db,
+                &quick.title,
+                description.as_deref().or(template.description.as_deref()),
+                due.as_deref()
+                    .or(quick.due.as_deref())
+                    .or(template.due.as_deref()),
+                start.as_deref(),
+                priority
+                    .as_ref()
+                    .or(quick.priority.as_ref())
+                    .or(template_priority.as_ref())
+                    .or_else(|| {
+                        let inherit =
+                            config.inherit_priority && (parent.is_some() || project.is_some());
+                        (!inherit).then_some(&config.default_priority)
+                    }),
+                &with_context(
+                    db,
+                    &all_tags,
+                    context.as_deref().or(quick.context.as_deref()),
+                    config,
+                )?,
+                project.as_deref(),
+                repeat.as_deref(),
+                parent
+                    .as_ref()
+                    .map(|parent| resolve_task(db, parent))
+                    .transpose()?,
+                *energy,
+                estimate.as_deref(),
+                *force,
+            )?
+        }
         Commands::List {
             completed,
+            done,
             priority,
-        } => list_tasks(&db, *completed, priority.as_ref())?,
-        Commands::Complete { id } => complete_task(&db, *id)?,
-        Commands::Delete { id } => delete_task(&db, *id)?,
-        Commands::Update {
+            due_soon,
+            due_soon_hours,
+            due,
+            tags,
+            title_matches,
+            matching,
+            not_tags,
+            no_due,
+            someday,
+            snoozed,
+            waiting,
+            all,
+            project,
+            not_projects,
+            group_by,
+            group_by_expr,
+            sort,
+            columns,
+            wrap,
+            limit,
+            offset,
+            page,
+            watch,
+            archived,
+            format,
+        } => {
+            let table = table::TableOptions {
+                columns: columns.clone(),
+                wrap: *wrap,
+            };
+            let window = chrono::Duration::hours(due_soon_hours.unwrap_or(config.due_soon_hours));
+            let filter = db::TaskFilter {
+                status: match (*completed, *done) {
+                    (_, true) => db::StatusFilter::Completed,
+                    (true, false) => db::StatusFilter::Any,
+                    (false, false) => db::StatusFilter::Pending,
+                },
+                priority: priority.as_ref().map(|p| p.to_int()),
+                due_within: due_soon.then_some(window),
+                due_range: due.map(|due| due.range(chrono::Utc::now())),
+                tags: if tags.is_empty() {
+                    config.context.iter().cloned().collect()
+                } else {
+                    tags.clone()
+                },
+                exclude_tags: not_tags.clone(),
+                title_pattern: title_matches.clone(),
+                exclude_undated: *no_due,
+                someday: if *someday {
+                    db::SomedayFilter::Only
+                } else {
+                    db::SomedayFilter::Exclude
+                },
+                waiting: match (*waiting, *all) {
+                    (true, _) => db::WaitingFilter::Only,
+                    (false, true) => db::WaitingFilter::Include,
+                    (false, false) => db::WaitingFilter::Exclude,
+                },
+                snoozed: *snoozed,
+                project: project.clone(),
+                exclude_projects: not_projects.clone(),
+                text: matching.clone(),
+                order: sort.order(),
+            };
+            let page = db::Page {
+                limit: *limit,
+                offset: match page {
+                    Some(page) => (*page as usize - 1) * limit.unwrap_or_default(),
+                    None => offset.unwrap_or_default(),
+                },
+            };
+            if *archived {
+                list_archived(db, window, format, *group_by, &table)?
+            } else if *watch {
+                if !matches!(format, OutputFormat::Plain) {
+                    anyhow::bail!("--watch only works with the plain format");
+                }
+                watch_list(db, || {
+                    // Relative due filters move with the clock between redraws
+                    let filter = db::TaskFilter {
+                        due_range: due.map(|due| due.range(chrono::Utc::now())),
+                        ..filter.clone()
+                    };
+                    list_tasks(
+                        db,
+                        &filter,
+                        window,
+                        format,
+                        *group_by,
+                        group_by_expr.as_deref(),
+                        &table,
+                        page,
+                    )
+                })?
+            } else {
+                style::paged(|| {
+                    list_tasks(
+                        db,
+                        &filter,
+                        window,
+                        format,
+                        *group_by,
+                        group_by_expr.as_deref(),
+                        &table,
+                        page,
+                    )
+                })?
+            }
+        }
+        Commands::Tui { show_keys: true } => tui::print_keys(&config.tui_keys),
+        Commands::Tui { show_keys: false } => tui::run(
+            db,
+            tui::Options {
+                filter: config
+                    .context
+                    .as_ref()
+                    .map(|tag| format!("#{}", tag))
+                    .unwrap_or_default(),
+                limits: wip_limits(config),
+                keys: config.tui_keys.clone(),
+                mouse: config.tui_mouse,
+                week_start: config.week_start,
+            },
+        )?,
+        Commands::Next { energy, limit } => {
+            // Config loading has already checked the formula
+            let formula = config
+                .urgency_formula
+                .as_deref()
+                .map(urgency::Formula::parse)
+                .transpose()
+                .map_err(|error| anyhow::anyhow!("Invalid urgency formula: {}", error))?;
+            next_tasks(
+                db,
+                *energy,
+                *limit,
+                &config.urgency,
+                formula.as_ref(),
+                &config.context.iter().cloned().collect::<Vec<_>>(),
+                chrono::Duration::hours(config.due_soon_hours),
+            )?
+        }
+        Commands::Estimate {
+            id,
+            duration,
+            clear,
+        } => estimate_task(db, resolve_task(db, id)?, duration.as_deref(), *clear)?,
+        Commands::Schedule {
+            fill_week: _,
+            hours,
+        } => schedule_week(db, hours.unwrap_or(config.workday_hours), cli.yes)?,
+        Commands::Plan { budget } => {
+            plan_budget(db, budget, chrono::Duration::hours(config.due_soon_hours))?
+        }
+        Commands::Today => agenda(
+            db,
+            &config.context.iter().cloned().collect::<Vec<_>>(),
+            chrono::Duration::hours(config.due_soon_hours),
+        )?,
+        Commands::Clone { id, due } => clone_task(db, resolve_task(db, id)?, due.as_deref())?,
+        Commands::Archive { days } => archive(db, *days)?,
+        Commands::Purge {
+            older_than,
+            archive,
+        } => purge(db, older_than.as_deref(), *archive, cli.yes)?,
+        Commands::Complete { ids, selection } => {
+            let ids = selected_ids(db, ids, selection)?;
+            let question = format!("Complete {}?", these_tasks(ids.len()));
+            if selection.is_empty() || confirm_tasks(db, &ids, &question, cli.yes)? {
+                complete_task(db, &ids, config.auto_complete_parents)?
+            }
+        }
+        Commands::Move {
             id,
+            status: Some(column),
+            ..
+        } => move_to_column(
+            db,
+            resolve_task(db, id)?,
+            *column,
+            wip_limits(config),
+            config.auto_complete_parents,
+        )?,
+        Commands::Move {
+            id, before, after, ..
+        } => {
+            let placement = match (before, after) {
+                (Some(target), _) => db::Placement::Before(resolve_task(db, target)?),
+                (None, Some(target)) => db::Placement::After(resolve_task(db, target)?),
+                (None, None) => unreachable!("clap requires a status, --before or --after"),
+            };
+            move_task(db, resolve_task(db, id)?, placement)?
+        }
+        Commands::Board { group_by } => board(db, *group_by, wip_limits(config))?,
+        Commands::Delete { ids, selection } => {
+            let ids = selected_ids(db, ids, selection)?;
+            let question = format!("Move {} to the trash?", these_tasks(ids.len()));
+            if confirm_tasks(db, &ids, &question, cli.yes)? {
+                delete_task(db, &ids)?
+            }
+        }
+        Commands::Note { id, text } => add_note(db, resolve_task(db, id)?, text)?,
+        Commands::Attach { id, target } => attach(db, resolve_task(db, id)?, target)?,
+        Commands::Open { id, number } => open_attachments(db, resolve_task(db, id)?, *number)?,
+        Commands::Start { id } => start_timer(db, resolve_task(db, id)?)?,
+        Commands::Stop => stop_timer(db)?,
+        Commands::Timesheet { days } => timesheet(db, *days)?,
+        Commands::Log { id: Some(id), .. } => task_log(db, resolve_task(db, id)?)?,
+        Commands::Log { id: None, limit } => recent_log(db, *limit)?,
+        Commands::Trash { empty } => trash(db, *empty)?,
+        Commands::Restore { id } => restore_task(db, resolve_task(db, id)?)?,
+        Commands::Undo => undo(db)?,
+        Commands::Update {
+            ids,
+            selection,
             title,
             description,
             due,
+            start,
+            no_start,
             priority,
-        } => update_task(
-            &db,
-            *id,
-            title.as_deref(),
-            description.as_deref(),
-            due.as_deref(),
-            priority.as_ref(),
+            inherit_priority,
+            project,
+            repeat,
+            no_repeat,
+            parent,
+            no_parent,
+            energy,
+            context,
+            estimate,
+        } => {
+            let ids = selected_ids(db, ids, selection)?;
+            let question = format!("Update {}?", these_tasks(ids.len()));
+            let bulk = ids.len() > 1 || !selection.is_empty();
+            if !bulk || confirm_tasks(db, &ids, &question, cli.yes)? {
+                update_task(
+                    db,
+                    &ids,
+                    title.as_deref(),
+                    description.as_deref(),
+                    due.as_deref(),
+                    start.as_deref(),
+                    *no_start,
+                    priority.as_ref(),
+                    *inherit_priority,
+                    project.as_deref(),
+                    repeat.as_deref(),
+                    *no_repeat,
+                    parent
+                        .as_ref()
+                        .map(|parent| resolve_task(db, parent))
+                        .transpose()?,
+                    *no_parent,
+                    *energy,
+                    context.as_deref(),
+                    estimate.as_deref(),
+                    config.adjust_subtask_due,
+                )?
+            }
+        }
+        Commands::Search {
+            query,
+            completed,
+            tags,
+            project,
+            priority,
+        } => {
+            let filter = db::TaskFilter {
+                status: if *completed {
+                    db::StatusFilter::Any
+                } else {
+                    db::StatusFilter::Pending
+                },
+                priority: priority.as_ref().map(|p| p.to_int()),
+                tags: tags.clone(),
+                project: project.clone(),
+                someday: db::SomedayFilter::Include,
+                ..db::TaskFilter::default()
+            };
+            search(db, &query.join(" "), &filter)?
+        }
+        Commands::Show { id, format } => show_task(db, resolve_task(db, id)?, format)?,
+        Commands::Report { command } => match command {
+            ReportCommands::Aging { oldest } => report_aging(db, *oldest)?,
+            ReportCommands::Week { last } => report_week(db, *last, config.week_start)?,
+        },
+        Commands::Stale { days, tag } => stale_tasks(db, *days, *tag)?,
+        Commands::Why { id } => why_task(db, resolve_task(db, id)?, config.context.as_deref())?,
+        Commands::Doctor => doctor(db)?,
+        Commands::Pick { action } => pick(db, *action, config.auto_complete_parents)?,
+        Commands::Maintain => maintain(db, &maintenance_options(config))?,
+        Commands::Import {
+            from,
+            file,
+            on_conflict,
+            dry_run,
+            resume,
+            force,
+            project,
+            map_project,
+            map_tag,
+            interactive,
+            habitica_user,
+        } => match (from, file) {
+            (Some(ImportSource::Habitica), Some(_)) => {
+                return Err(anyhow::anyhow!(
+                    "Habitica tasks are fetched from its API; don't pass a file"
+                ));
+            }
+            (Some(ImportSource::Habitica), None)
+                if project.is_some()
+                    || !map_project.is_empty()
+                    || !map_tag.is_empty()
+                    || *interactive =>
+            {
+                return Err(anyhow::anyhow!(
+                    "Projects and tags can only be remapped when importing a file"
+                ));
+            }
+            (Some(ImportSource::Habitica), None) => {
+                import_habitica(db, habitica_user.as_deref(), *force)?
+            }
+            (from, file) => import_file(
+                db,
+                &import::ImportOptions {
+                    format: from.as_ref().and_then(ImportSource::file_format),
+                    path: file.as_deref(),
+                    resume: *resume,
+                    on_conflict: *on_conflict,
+                    dry_run: *dry_run,
+                    force: *force,
+                    default_priority: config.default_priority.to_int(),
+                    remap: import::Remap {
+                        projects: map_project.iter().cloned().collect(),
+                        tags: map_tag.iter().cloned().collect(),
+                        project: project.clone(),
+                    },
+                    interactive: *interactive,
+                },
+            )?,
+        },
+        Commands::Export { format, output } => export(db, *format, output.as_deref())?,
+        Commands::Sync { repo, offline } => {
+            let repo = match repo {
+                Some(repo) => repo.clone(),
+                None => config.sync_repo()?.ok_or_else(|| {
+                    anyhow::anyhow!("No repository to sync with: set sync_repo or pass --repo")
+                })?,
+            };
+            git_sync(db, &repo, &config.sync_remote, *offline)?
+        }
+        Commands::Obsidian { command } => match command {
+            ObsidianCommands::Sync { vault, plan, apply } => {
+                obsidian_sync(db, vault, *plan, *apply || cli.yes)?
+            }
+        },
+        Commands::Get { id, format } => get_task(db, resolve_task(db, id)?, format)?,
+        Commands::Set { id, from_json } => {
+            set_task_from_json(db, resolve_task(db, id)?, from_json)?
+        }
+        Commands::ShellInit { .. }
+        | Commands::Init { .. }
+        | Commands::SelfUpdate { .. }
+        | Commands::Help { .. }
+        | Commands::Config { .. }
+        | Commands::Context { .. }
+        | Commands::Alias { .. }
+        | Commands::Profile { .. } => {
+            unreachable!("handled before opening the database")
+        }
+        Commands::StatusLine => status_line(db)?,
+        Commands::Notify { hours, print } => notify_due(
+            db,
+            chrono::Duration::hours(hours.unwrap_or(config.due_soon_hours)),
+            config.digests,
+            *print,
+        )?,
+        // Scripts are printed before opening the database; this is `ids`
+        Commands::Completions { shell } => match shell {
+            shell::Completions::Tags => complete_tags(db)?,
+            shell::Completions::Projects => complete_projects(db)?,
+            _ => complete_ids(db)?,
+        },
+        Commands::Tags => list_tag_tree(db)?,
+        Commands::Snapshot {
+            format,
+            group_by,
+            output,
+        } => snapshot(db, *group_by, *format, output.as_deref())?,
+        Commands::Snooze { id, when, days } => {
+            let when = match (when, days) {
+                (Some(when), _) => when.clone(),
+                (None, days) => format!("{}d", days.unwrap_or(1)),
+            };
+            snooze_task(db, resolve_task(db, id)?, &when)?
+        }
+        Commands::Someday { id, promote } => someday(
+            db,
+            id.as_ref().map(|id| resolve_task(db, id)).transpose()?,
+            *promote,
+        )?,
+        Commands::Review {
+            all,
+            list,
+            promote,
+            drop,
+            keep,
+        } => review(
+            db,
+            *all,
+            *list,
+            &ReviewDecisions {
+                promote: resolve_tasks(db, promote)?,
+                drop: resolve_tasks(db, drop)?,
+                keep: resolve_tasks(db, keep)?,
+            },
+        )?,
+        Commands::Reprioritize { filter, list } => reprioritize(
+            db,
+            *filter,
+            chrono::Duration::hours(config.due_soon_hours),
+            *list,
         )?,
-        Commands::Show { id } => show_task(&db, *id)?,
+        Commands::PlanEmail { base_url, to } => plan_email(db, base_url, to.as_deref())?,
+        Commands::Share {
+            id,
+            project,
+            public: _,
+            expires,
+            base_url,
+        } => share(
+            db,
+            id.as_ref().map(|id| resolve_task(db, id)).transpose()?,
+            project.as_deref(),
+            expires,
+            base_url,
+        )?,
+        Commands::Focus { command } => match command {
+            FocusCommands::Start { minutes, task } => focus_start(
+                db,
+                *minutes,
+                task.as_ref()
+                    .map(|task| resolve_task(db, task))
+                    .transpose()?,
+            )?,
+            FocusCommands::Stop => focus_stop(db)?,
+            FocusCommands::Status => focus_status(db)?,
+        },
+        Commands::Project { command } => match command {
+            ProjectCommands::List => project_list(db)?,
+            ProjectCommands::Rename { old, new } => project_rename(db, old, new)?,
+            ProjectCommands::Delete { name } => project_delete(db, name)?,
+            ProjectCommands::Priority {
+                name,
+                priority,
+                clear,
+            } => project_priority(db, name, priority.as_ref(), *clear)?,
+        },
+        Commands::Goal { command } => match command {
+            GoalCommands::Add { title, by } => goal_add(db, title, by.as_deref())?,
+            GoalCommands::List => goal_list(db)?,
+            GoalCommands::Show { id } => goal_show(db, *id)?,
+            GoalCommands::Link { goal, tasks } => goal_link(db, *goal, &resolve_tasks(db, tasks)?)?,
+            GoalCommands::Unlink { tasks } => goal_unlink(db, &resolve_tasks(db, tasks)?)?,
+            GoalCommands::Delete { id } => goal_delete(db, *id)?,
+        },
+        Commands::Template { command } => match command {
+            TemplateCommands::Save { name, id, due } => {
+                template_save(db, name, resolve_task(db, id)?, due.as_deref())?
+            }
+            TemplateCommands::List => template_list(db)?,
+            TemplateCommands::Delete { name } => template_delete(db, name)?,
+        },
+        Commands::Logs { .. } => unreachable!("handled before opening the database"),
+        Commands::Usage { reset } => usage(db, config.usage_metrics, *reset)?,
+        Commands::History { limit, clear } => history(db, config.command_history, *limit, *clear)?,
+        Commands::Serve { .. } | Commands::Again { .. } | Commands::Batch { .. } => {
+            unreachable!("handled by run_with")
+        }
+    }
+    Ok(())
+}
+
+/// `todo batch`: run every command of the input in this process and in one
+/// transaction, printing a JSON line per command. A failed command is
+/// rolled back on its own and the others still run.
+fn run_batch(db: &Database, config: &config::Config, file: &Path) -> anyhow::Result<()> {
+    let text = if file == Path::new("-") {
+        std::io::read_to_string(std::io::stdin()).context("Cannot read commands from stdin")?
+    } else {
+        std::fs::read_to_string(file).with_context(|| format!("Cannot read {}", file.display()))?
+    };
+    let commands = parse_batch(&text)?;
+    colored::control::set_override(false);
+
+    let tx = db.transaction()?;
+    let mut failed = 0;
+    for (index, args) in &commands {
+        let (result, output) = style::capture(|| run_batch_command(db, config, args));
+        let line = match result {
+            Ok(()) => {
+                serde_json::json!({ "index": index, "ok": true, "output": output.trim_end() })
+            }
+            Err(error) => {
+                failed += 1;
+                serde_json::json!({ "index": index, "ok": false, "error": format!("{:#}", error) })
+            }
+        };
+        println!("{}", line);
+    }
+    tx.commit()?;
+    if failed > 0 {
+        anyhow::bail!("{} of {} commands failed", failed, commands.len());
+    }
+    Ok(())
+}
+
+/// One command of a batch, given as its arguments, in a savepoint of the
+/// batch's transaction.
+fn run_batch_command(
+    db: &Database,
+    config: &config::Config,
+    args: &[String],
+) -> anyhow::Result<()> {
+    let tx = db.transaction()?;
+    let args: Vec<std::ffi::OsString> = std::iter::once("todo".into())
+        .chain(args.iter().map(Into::into))
+        .collect();
+    let mut cli = Cli::try_parse_from(&args).map_err(|e| {
+        let message = e.to_string();
+        anyhow::anyhow!(
+            "{}",
+            message
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .trim_start_matches("error: ")
+        )
+    })?;
+    if cli.profile.is_some() || cli.db.is_some() {
+        anyhow::bail!("--profile and --db can only be given to `todo batch` itself");
+    }
+    if let Some(reason) = batch_refusal(&cli.command, cli.yes) {
+        anyhow::bail!("`{}` {}", args[1].to_string_lossy(), reason);
+    }
+    // The batch is the confirmation for the rest
+    cli.yes = true;
+    if let (
+        Commands::List {
+            archived: false, ..
+        },
+        Some(defaults),
+    ) = (&cli.command, &config.list_filter)
+    {
+        cli = with_list_defaults(&args, defaults, config)?;
+    }
+    execute(&cli, db, config)?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Why `command` can't be part of a batch, if it can't: it doesn't use the
+/// task database, needs the process to itself, or reads answers from stdin.
+fn batch_refusal(command: &Commands, yes: bool) -> Option<&'static str> {
+    match command {
+        Commands::ShellInit { .. }
+        | Commands::Init { .. }
+        | Commands::SelfUpdate { .. }
+        | Commands::Help { .. }
+        | Commands::Config { .. }
+        | Commands::Context { .. }
+        | Commands::Alias { .. }
+        | Commands::Profile { .. }
+        | Commands::Logs { .. }
+        | Commands::Completions { .. } => Some("doesn't work on the task database"),
+        Commands::Serve { .. } | Commands::Again { .. } | Commands::Batch { .. } => {
+            Some("can't run inside a batch")
+        }
+        Commands::Tui { .. }
+        | Commands::Pick { .. }
+        | Commands::Reprioritize { list: false, .. }
+        | Commands::Import {
+            interactive: true, ..
+        } => Some("is interactive"),
+        Commands::Review {
+            list: false,
+            promote,
+            drop,
+            keep,
+            ..
+        } if promote.is_empty() && drop.is_empty() && keep.is_empty() => Some("is interactive"),
+        Commands::Schedule { .. } | Commands::Purge { .. } if !yes => {
+            Some("asks for confirmation; add --yes")
+        }
+        _ => None,
+    }
+}
+
+/// The IDs a bulk command acts on: those given, or the pending tasks its
+/// selection flags pick.
+/// `this task` or `these N tasks`, for confirmation prompts.
+fn these_tasks(count: usize) -> String {
+    match count {
+        1 => "this task".to_string(),
+        count => format!("these {} tasks", count),
+    }
+}
+
+fn selected_ids(db: &Database, ids: &[String], selection: &Selection) -> anyhow::Result<Vec<i32>> {
+    let Some(filter) = selection.filter()? else {
+        return parse_ids(db, ids);
+    };
+    let ids: Vec<i32> = db
+        .get_tasks(&filter)?
+        .into_iter()
+        .filter_map(|task| task.id)
+        .collect();
+    if ids.is_empty() {
+        return Err(TodoError::Missing("No pending tasks match the selection".to_string()).into());
+    }
+    Ok(ids)
+}
+
+fn open_database(config: &config::Config) -> anyhow::Result<Database> {
+    let db_path = config.db_path()?;
+    permissions::prepare_database(&db_path)?;
+    let context = || format!("Cannot open the task database at {}", db_path.display());
+    let db = Database::new(&db_path).with_context(context)?;
+    if !db.is_readable() {
+        if !db.cipher_available() {
+            return Err(anyhow::anyhow!(
+                "{} is encrypted or not a task database; this build of todo cannot open encrypted databases",
+                db_path.display()
+            ));
+        }
+        db.unlock(&encryption::passphrase(&db_path)?)?;
+        if !db.is_readable() {
+            return Err(anyhow::anyhow!(
+                "Wrong passphrase for {}",
+                db_path.display()
+            ));
+        }
+    }
+    let extensions = config.sqlite_extensions()?;
+    if !extensions.is_empty() && !config.allow_extensions {
+        errln!("⚠️  Not loading sqlite_extensions: set allow_extensions = true to load them");
+    } else {
+        for path in &extensions {
+            db.load_extension(path)
+                .with_context(|| format!("Cannot load SQLite extension {}", path.display()))?;
+        }
+    }
+    db.init()
+        .map_err(|error| match error {
+            // Migration errors carry their own message; unwrapped so it is
+            // not repeated as its own cause
+            rusqlite::Error::UserFunctionError(error) => anyhow::anyhow!(error),
+            error => error.into(),
+        })
+        .with_context(context)?;
+    Ok(db)
+}
+
+/// `todo init --encrypted`: encrypt the task database in place, creating it
+/// if needed.
+fn init_encrypted(config: &config::Config, keyring: bool) -> anyhow::Result<()> {
+    let db_path = config.db_path()?;
+    permissions::prepare_database(&db_path)?;
+    let passphrase = encryption::new_passphrase()?;
+    encryption::encrypt(&db_path, &passphrase)?;
+    outln!("🔒 {} is encrypted", db_path.display());
+    if keyring {
+        encryption::save_to_keyring(&db_path, &passphrase)?;
+        outln!("   The passphrase is in the system keyring");
+    } else {
+        outln!(
+            "   todo will ask for the passphrase, or read it from {}",
+            encryption::PASSPHRASE_ENV
+        );
+    }
+    Ok(())
+}
+
+/// Tags for a new task: the ones given plus its context. That is the one
+/// given with --context or `@context`, or else the profile's, unless a tag
+/// given is already a context.
+fn with_context(
+    db: &Database,
+    tags: &[String],
+    context: Option<&str>,
+    config: &config::Config,
+) -> anyhow::Result<Vec<String>> {
+    let mut tags = tags.to_vec();
+    let context = match context {
+        Some(context) => Some(tags::normalize_context(context)?),
+        None => {
+            let contexts = db.get_contexts()?;
+            config
+                .context
+                .clone()
+                .filter(|_| !tags.iter().any(|tag| contexts.contains(tag)))
+        }
+    };
+    if let Some(context) = context {
+        commands::set_context(db, &mut tags, &context)?;
+    }
+    Ok(tags)
+}
+
+fn maintenance_options(config: &config::Config) -> maintenance::MaintenanceOptions {
+    maintenance::MaintenanceOptions {
+        stale_tagging: config.stale_tagging,
+        priority_decay: config.priority_decay,
+    }
+}
+
+/// `args` with the alias in place of a command replaced by the command line
+/// it stands for, when `error` is about an unknown command that names an
+/// alias. `expanded` holds the aliases already replaced, so an alias that
+/// leads back to itself is an error rather than a loop.
+fn expand_alias(
+    error: &clap::Error,
+    args: &[std::ffi::OsString],
+    expanded: &mut Vec<String>,
+) -> anyhow::Result<Option<Vec<std::ffi::OsString>>> {
+    use clap::error::{ContextKind, ContextValue, ErrorKind};
+
+    let (ErrorKind::InvalidSubcommand, Some(ContextValue::String(name))) =
+        (error.kind(), error.get(ContextKind::InvalidSubcommand))
+    else {
+        return Ok(None);
+    };
+    let Some(position) = args.iter().skip(1).position(|arg| arg == name.as_str()) else {
+        return Ok(None);
+    };
+    let position = position + 1;
+    // Only in place of the command itself, not of a subcommand such as
+    // `todo project <name>`
+    let root = Cli::command();
+    if args[1..position].iter().any(|arg| {
+        let arg = arg.to_string_lossy();
+        root.find_subcommand(arg.as_ref()).is_some()
+    }) {
+        return Ok(None);
+    }
+
+    // `--profile` picks the config the aliases come from, so it is looked
+    // for by hand before the command line can be parsed
+    let profile = args[1..position]
+        .iter()
+        .map(|arg| arg.to_string_lossy())
+        .collect::<Vec<_>>()
+        .windows(2)
+        .find(|pair| pair[0] == "--profile")
+        .map(|pair| pair[1].to_string())
+        .or_else(|| {
+            args[1..position].iter().find_map(|arg| {
+                let arg = arg.to_string_lossy();
+                arg.strip_prefix("--profile=").map(str::to_string)
+            })
+        });
+    let (config, _) = config::load(profile.as_deref())?;
+    let Some(command) = config.aliases.get(name) else {
+        return Ok(None);
+    };
+    if expanded.contains(name) {
+        return Err(TodoError::Invalid(format!(
+            "Alias `{}` leads back to itself: {}",
+            name,
+            expanded.join(" → ")
+        ))
+        .into());
+    }
+    expanded.push(name.clone());
+    let words = config::split_args(command)
+        .map_err(|e| TodoError::Invalid(format!("Invalid alias `{}`: {}", name, e)))?;
+    let mut args = args.to_vec();
+    args.splice(position..=position, words.into_iter().map(Into::into));
+    Ok(Some(args))
+}
+
+/// Report a mistyped command or flag with a "did you mean" hint. With a
+/// single close match on a terminal, offer to run the corrected command
+/// line and return it; otherwise exit like clap would.
+fn correct_typo(error: clap::Error, args: &[std::ffi::OsString]) -> Vec<std::ffi::OsString> {
+    use std::io::{IsTerminal, Write};
+
+    let Some(suggestion) = suggest::suggest(&Cli::command(), &error, args) else {
+        error.exit()
+    };
+    let kind = if suggestion.typed.starts_with("--") {
+        "flag"
+    } else {
+        "command"
+    };
+    errln!(
+        "{} unknown {} `{}`, {}",
+        "error:".red().bold(),
+        kind,
+        suggestion.typed,
+        suggestion.hint()
+    );
+
+    if let Some(corrected) = suggestion.apply(args) {
+        if std::io::stdin().is_terminal() && std::io::stderr().is_terminal() {
+            let line: Vec<String> = corrected[1..]
+                .iter()
+                .map(|arg| arg.to_string_lossy().to_string())
+                .collect();
+            eprint!("Run `todo {}` instead? [y/N] ", line.join(" "));
+            let _ = std::io::stderr().flush();
+            let mut answer = String::new();
+            if std::io::stdin().read_line(&mut answer).is_ok()
+                && matches!(answer.trim(), "y" | "Y" | "yes")
+            {
+                return corrected;
+            }
+        }
+    }
+    errln!("\nFor more information, try '--help'.");
+    std::process::exit(2)
+}
+
+/// Re-parse the command line with the `list_filter` flags inserted right
+/// after `list`, so flags given on the command line come later and win.
+fn with_list_defaults(
+    args: &[std::ffi::OsString],
+    defaults: &str,
+    config: &config::Config,
+) -> anyhow::Result<Cli> {
+    let invalid = |detail: String| {
+        anyhow::anyhow!(
+            "Invalid `list_filter` ({}): {}",
+            config.source("list_filter"),
+            detail
+        )
+    };
+    let defaults = config::split_args(defaults).map_err(invalid)?;
+    let args = insert_after_subcommand(args.to_vec(), "list", &defaults);
+    Cli::try_parse_from(args).map_err(|e| {
+        let message = e.to_string();
+        invalid(
+            message
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .trim_start_matches("error: ")
+                .to_string(),
+        )
+    })
+}
+
+fn insert_after_subcommand(
+    mut args: Vec<std::ffi::OsString>,
+    subcommand: &str,
+    extra: &[String],
+) -> Vec<std::ffi::OsString> {
+    let position = args
+        .iter()
+        .skip(1)
+        .position(|arg| arg == subcommand)
+        .map(|index| index + 2)
+        .unwrap_or(args.len());
+    args.splice(position..position, extra.iter().map(Into::into));
+    args
+}
+
+/// `todo config ...` works without the database and even when the config
+/// files themselves are broken, so they can be fixed.
+/// Config files, profile and environment, then the global flags on top.
+fn load_config(cli: &Cli) -> anyhow::Result<(config::Config, Vec<String>)> {
+    let (mut config, warnings) = config::load(cli.profile.as_deref())?;
+    if let Some(db) = &cli.db {
+        let db = std::path::absolute(db)?.display().to_string();
+        config.apply_flag("db", config::Setting::Text(db), "--db");
+    }
+    if cli.plain {
+        config.apply_flag("plain", config::Setting::Bool(true), "--plain");
+    }
+    if cli.no_color {
+        config.apply_flag("color", config::Setting::Bool(false), "--no-color");
+    }
+    Ok((config, warnings))
+}
+
+fn run_config_command(command: &ConfigCommands, cli: &Cli) -> anyhow::Result<()> {
+    let profile = cli.profile.as_deref();
+    match command {
+        ConfigCommands::Get { key } => {
+            let (config, _) = load_config(cli)?;
+            let value = config
+                .get(key)
+                .ok_or_else(|| TodoError::Invalid(format!("Unknown config key `{}`", key)))?;
+            outln!("{}", value);
+        }
+        ConfigCommands::List => {
+            let (config, warnings) = load_config(cli)?;
+            for warning in warnings {
+                errln!("⚠️  {}", warning);
+            }
+            for key in config::KEYS {
+                outln!(
+                    "{} = {}  {}",
+                    key.name,
+                    config.get(key.name).unwrap(),
+                    format!("# {}", key.description).dimmed()
+                );
+            }
+        }
+        ConfigCommands::Sources => {
+            let (config, _) = load_config(cli)?;
+            for key in config::KEYS {
+                let entry = format!("{} = {}", key.name, config.get(key.name).unwrap());
+                outln!(
+                    "{:<32} {}",
+                    entry,
+                    config.source(key.name).to_string().dimmed()
+                );
+            }
+        }
+        ConfigCommands::Set {
+            key,
+            value,
+            local,
+            system,
+        } => {
+            let path = ConfigCommands::target(*local, *system, profile)?;
+            config::set_in_file(&path, key, value)?;
+            outln!("✅ {} set in {}", key, path.display());
+        }
+        ConfigCommands::Edit { local, system } => {
+            let path = ConfigCommands::target(*local, *system, profile)?;
+            if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+                std::fs::create_dir_all(dir)?;
+            }
+            let editor = std::env::var("VISUAL")
+                .or_else(|_| std::env::var("EDITOR"))
+                .unwrap_or_else(|_| "vi".to_string());
+            let status = std::process::Command::new(&editor).arg(&path).status()?;
+            if !status.success() {
+                return Err(anyhow::anyhow!("{} exited with {}", editor, status));
+            }
+
+            let source = std::fs::read_to_string(&path).unwrap_or_default();
+            let origin = path.display().to_string();
+            for warning in config::parse_into(&mut config::Config::default(), &source, &origin)? {
+                errln!("⚠️  {}", warning);
+            }
+            outln!("✅ {} is valid", origin);
+        }
+        ConfigCommands::Export { output } => {
+            let (user, profiles) = setup_paths()?;
+            let bundle = config::export_setup(&user, &profiles)?;
+            match output {
+                Some(path) => {
+                    std::fs::write(path, bundle)?;
+                    outln!("✅ Settings exported to {}", path.display());
+                }
+                None => print!("{}", bundle),
+            }
+        }
+        ConfigCommands::Import { file } => {
+            let (user, profiles) = setup_paths()?;
+            let source = if file.as_os_str() == "-" {
+                let mut buffer = String::new();
+                std::io::Read::read_to_string(&mut std::io::stdin(), &mut buffer)?;
+                buffer
+            } else {
+                std::fs::read_to_string(file)
+                    .with_context(|| format!("Cannot read {}", file.display()))?
+            };
+            let origin = file.display().to_string();
+            let mut warnings = Vec::new();
+            let written = config::import_setup(&source, &origin, &user, &profiles, &mut warnings)?;
+            for warning in warnings {
+                errln!("⚠️  {}", warning);
+            }
+            for (path, keys) in written {
+                outln!("✅ {} setting(s) merged into {}", keys, path.display());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The user config file and the profiles directory, which `todo config
+/// export` and `import` cover.
+fn setup_paths() -> anyhow::Result<(PathBuf, PathBuf)> {
+    let missing = || anyhow::anyhow!("Could not find the config directory");
+    Ok((
+        config::Layer::User.path().ok_or_else(missing)?,
+        config::profiles_dir().ok_or_else(missing)?,
+    ))
+}
+
+/// `todo context`: print the context, or set it in the user config file
+/// (the profile's, with --profile).
+fn run_context_command(name: Option<&str>, clear: bool, cli: &Cli) -> anyhow::Result<()> {
+    let path = ConfigCommands::target(false, false, cli.profile.as_deref())?;
+    if clear {
+        config::unset_in_file(&path, "context")?;
+        outln!("📍 No context; `todo list` shows tasks from everywhere");
+    } else if let Some(name) = name {
+        let context = tags::normalize_context(name)?;
+        config::set_in_file(&path, "context", &context)?;
+        outln!("📍 Context is now @{}", context);
+    } else {
+        let (config, _) = load_config(cli)?;
+        match &config.context {
+            Some(context) => outln!("📍 @{}", context),
+            None => outln!("📍 No context"),
+        }
+        return Ok(());
+    }
+    // The environment wins over the config files
+    let (config, _) = load_config(cli)?;
+    if let config::Source::Env(variable) = config.source("context") {
+        errln!(
+            "⚠️  {} is set, so the context stays {} until it is unset",
+            variable,
+            config
+                .context
+                .as_ref()
+                .map_or("empty".to_string(), |context| format!("@{}", context))
+        );
     }
+    Ok(())
+}
 
+fn run_alias_command(command: &AliasCommands, cli: &Cli) -> anyhow::Result<()> {
+    let path = ConfigCommands::target(false, false, cli.profile.as_deref())?;
+    match command {
+        AliasCommands::List => {
+            let (config, _) = load_config(cli)?;
+            if config.aliases.is_empty() {
+                outln!("No aliases. Define one with `todo alias add NAME COMMAND...`");
+            }
+            for (name, command) in &config.aliases {
+                outln!("{} = {}", name, command);
+            }
+        }
+        AliasCommands::Add { name, command } => {
+            config::validate_alias_name(name)?;
+            let root = Cli::command();
+            if root.find_subcommand(name).is_some() {
+                return Err(TodoError::Invalid(format!(
+                    "`{}` is already a todo command, so an alias of that name would never run",
+                    name
+                ))
+                .into());
+            }
+            // Either one quoted command line, or its words, quoted again
+            // where they had spaces
+            let command = match command.as_slice() {
+                [line] => line.clone(),
+                words => words
+                    .iter()
+                    .map(|word| match word.contains(char::is_whitespace) {
+                        true => format!("\"{}\"", word),
+                        false => word.clone(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            };
+            let words = config::split_args(&command)
+                .map_err(|e| TodoError::Invalid(format!("Invalid command line: {}", e)))?;
+            let (config, _) = load_config(cli)?;
+            let first = words.first().map(String::as_str).unwrap_or_default();
+            if root.find_subcommand(first).is_none() && !config.aliases.contains_key(first) {
+                return Err(TodoError::Invalid(format!(
+                    "`{}` is neither a todo command nor an alias",
+                    first
+                ))
+                .into());
+            }
+            config::set_alias_in_file(&path, name, &command)?;
+            outln!("✅ `todo {}` now runs `todo {}`", name, command);
+        }
+        AliasCommands::Remove { name } => {
+            if !config::unset_alias_in_file(&path, name)? {
+                return Err(TodoError::Missing(format!(
+                    "{} defines no alias `{}`",
+                    path.display(),
+                    name
+                ))
+                .into());
+            }
+            outln!("🗑️  Removed alias `{}`", name);
+        }
+    }
     Ok(())
 }
 
-fn get_db_path() -> anyhow::Result<PathBuf> {
-    let mut path =
-        dirs::home_dir().ok_or_else(|| anyhow::anyhow!("Could not find home directory"))?;
-    path.push(".todo.db");
-    Ok(path)
+/// `todo profile ...`. A profile is a config file under
+/// `<config dir>/todo/profiles/` applied on top of the regular layers.
+fn run_profile_command(command: &ProfileCommands, cli: &Cli) -> anyhow::Result<()> {
+    let user_config = || {
+        config::Layer::User
+            .path()
+            .ok_or_else(|| anyhow::anyhow!("Could not find the config directory"))
+    };
+
+    match command {
+        ProfileCommands::Create { name, db, context } => {
+            let path = config::profile_path(name)?;
+            if path.exists() {
+                return Err(anyhow::anyhow!("Profile `{}` already exists", name));
+            }
+            // A relative path would resolve against whatever directory the
+            // profile is later used from. Without one, the profile gets a
+            // database of its own rather than sharing the default.
+            let db = match db.as_deref() {
+                Some(db) if !db.starts_with("~/") && Path::new(db).is_relative() => {
+                    std::env::current_dir()?.join(db).display().to_string()
+                }
+                Some(db) => db.to_string(),
+                None => format!("~/.todo-{}.db", name),
+            };
+            let context = context.as_deref().map(tags::normalize).transpose()?;
+
+            std::fs::create_dir_all(path.parent().unwrap())?;
+            std::fs::write(&path, format!("# todo profile `{}`\n", name))?;
+            config::set_in_file(&path, "db", &db)?;
+            if let Some(context) = &context {
+                config::set_in_file(&path, "context", context)?;
+            }
+            outln!("✅ Profile `{}` created in {}", name, path.display());
+            outln!(
+                "   Use it with `todo --profile {} ...` or `todo profile use {}`",
+                name,
+                name
+            );
+        }
+        ProfileCommands::List => {
+            let names = config::list_profiles()?;
+            if names.is_empty() {
+                outln!("👤 No profiles yet. Use `todo profile create NAME` to add one.");
+                return Ok(());
+            }
+            let (active, _) = load_config(cli)?;
+            for name in names {
+                let mut settings = config::Config::default();
+                let path = config::profile_path(&name)?;
+                let source = std::fs::read_to_string(&path)?;
+                config::parse_into(&mut settings, &source, &path.display().to_string())?;
+
+                let marker = if active.profile.as_deref() == Some(name.as_str()) {
+                    "*"
+                } else {
+                    " "
+                };
+                let mut details = vec![format!("db: {}", settings.db_path()?.display())];
+                if let Some(context) = &settings.context {
+                    details.push(format!("context: #{}", context));
+                }
+                outln!("{} {:<16} {}", marker, name, details.join(", ").dimmed());
+            }
+        }
+        ProfileCommands::Use { name } => {
+            if !config::profile_path(name)?.exists() {
+                return Err(
+                    TodoError::Missing(format!("Profile `{}` does not exist", name)).into(),
+                );
+            }
+            config::set_in_file(&user_config()?, "profile", name)?;
+            outln!("✅ Now using profile `{}`", name);
+        }
+        ProfileCommands::Clear => {
+            config::unset_in_file(&user_config()?, "profile")?;
+            outln!("✅ Back to the default profile");
+        }
+        ProfileCommands::Delete { name } => {
+            let path = config::profile_path(name)?;
+            if !path.exists() {
+                return Err(
+                    TodoError::Missing(format!("Profile `{}` does not exist", name)).into(),
+                );
+            }
+            std::fs::remove_file(&path)?;
+
+            // Don't leave the user config pointing at a profile that is gone.
+            let user_config = user_config()?;
+            let mut sticky = config::Config::default();
+            if let Ok(source) = std::fs::read_to_string(&user_config) {
+                config::parse_into(&mut sticky, &source, &user_config.display().to_string())?;
+            }
+            if sticky.profile.as_deref() == Some(name.as_str()) {
+                config::unset_in_file(&user_config, "profile")?;
+            }
+            outln!("🗑️  Profile `{}` deleted", name);
+        }
+    }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -172,121 +2666,553 @@ mod tests {
     }
 
     #[test]
-    fn test_priority_to_int() {
-        assert_eq!(Priority::Low.to_int(), 0);
-        assert_eq!(Priority::Medium.to_int(), 1);
-        assert_eq!(Priority::High.to_int(), 2);
+    fn test_get_db_path() {
+        let mut config = config::Config::default();
+        let path = config.db_path().unwrap();
+        assert!(path.to_string_lossy().contains(".todo.db"));
+
+        config.db = Some("~/work.db".to_string());
+        let path = config.db_path().unwrap();
+        assert_eq!(path, dirs::home_dir().unwrap().join("work.db"));
     }
 
     #[test]
-    fn test_priority_from_int() {
-        assert!(matches!(Priority::from_int(0), Priority::Low));
-        assert!(matches!(Priority::from_int(1), Priority::Medium));
-        assert!(matches!(Priority::from_int(2), Priority::High));
-        assert!(matches!(Priority::from_int(99), Priority::Medium)); // Default case
+    fn test_database_initialization() {
+        let (db, _temp_file) = create_test_db();
+
+        // Test that database is properly initialized
+        let task = todo::models::Task::new("Test task".to_string(), None, None, 1);
+
+        let id = db.add_task(&task).unwrap();
+        assert_eq!(id, 1);
     }
 
     #[test]
-    fn test_priority_color() {
-        let low_color = Priority::Low.color();
-        let medium_color = Priority::Medium.color();
-        let high_color = Priority::High.color();
-
-        assert!(low_color.to_string().contains("LOW"));
-        assert!(medium_color.to_string().contains("MEDIUM"));
-        assert!(high_color.to_string().contains("HIGH"));
+    fn test_add_short_alias() {
+        let cli = Cli::try_parse_from(["todo", "a", "Quick capture"]).unwrap();
+        assert!(
+            matches!(cli.command, Commands::Add { ref title, .. } if title.as_deref() == Some("Quick capture"))
+        );
     }
 
     #[test]
-    fn test_get_db_path() {
-        let result = get_db_path();
-        assert!(result.is_ok());
+    fn test_import_format_alias() {
+        let cli = Cli::try_parse_from(["todo", "import", "--format", "taskwarrior", "export.json"])
+            .unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Import {
+                from: Some(ImportSource::Taskwarrior),
+                file: Some(_),
+                ..
+            }
+        ));
+    }
 
-        let path = result.unwrap();
-        assert!(path.to_string_lossy().contains(".todo.db"));
+    #[test]
+    fn test_exclude_project_alias() {
+        let cli = Cli::try_parse_from([
+            "todo",
+            "list",
+            "--exclude-project",
+            "work",
+            "--not-project",
+            "home",
+        ])
+        .unwrap();
+        assert!(
+            matches!(cli.command, Commands::List { ref not_projects, .. } if not_projects == &["work", "home"])
+        );
     }
 
     #[test]
-    fn test_database_initialization() {
-        let (db, _temp_file) = create_test_db();
+    fn test_alias_add_takes_flags_as_the_command() {
+        let cli = Cli::try_parse_from([
+            "todo",
+            "alias",
+            "add",
+            "urgent",
+            "list",
+            "--priority",
+            "high",
+        ])
+        .unwrap();
+        let Commands::Alias {
+            command: AliasCommands::Add { name, command },
+        } = cli.command
+        else {
+            panic!("expected `alias add`");
+        };
+        assert_eq!(name, "urgent");
+        assert_eq!(command, ["list", "--priority", "high"]);
+    }
 
-        // Test that database is properly initialized
-        let task = models::Task::new("Test task".to_string(), None, None, 1);
+    #[test]
+    fn test_list_defaults_come_before_command_line_flags() {
+        let args = ["todo", "--profile", "work", "list", "--priority", "high"]
+            .map(std::ffi::OsString::from)
+            .to_vec();
+        let defaults = ["--priority".to_string(), "low".to_string()];
+        let args = insert_after_subcommand(args, "list", &defaults);
+        assert_eq!(
+            args,
+            [
+                "todo",
+                "--profile",
+                "work",
+                "list",
+                "--priority",
+                "low",
+                "--priority",
+                "high"
+            ]
+        );
 
-        let id = db.add_task(&task).unwrap();
-        assert_eq!(id, 1);
+        let cli = Cli::try_parse_from(args).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::List {
+                priority: Some(Priority::High),
+                ..
+            }
+        ));
     }
 
     #[test]
     fn test_cli_commands_enum() {
         // Test that all command variants exist
         let _add = Commands::Add {
-            title: "Test".to_string(),
+            title: Some("Test".to_string()),
+            from_file: None,
             description: None,
             due: None,
-            priority: Priority::Medium,
+            start: Some("monday".to_string()),
+            priority: Some(Priority::Medium),
+            tags: vec![],
+            project: Some("work".to_string()),
+            repeat: Some("weekly".to_string()),
+            parent: Some(1.into()),
+            energy: None,
+            context: Some("@home".to_string()),
+            estimate: Some("45m".to_string()),
+            force: false,
+            literal: false,
+            template: None,
         };
 
+        let _clone = Commands::Clone {
+            id: 1.into(),
+            due: Some("tomorrow".to_string()),
+        };
         let _list = Commands::List {
             completed: false,
+            done: false,
             priority: None,
+            due_soon: false,
+            due_soon_hours: None,
+            due: None,
+            tags: vec![],
+            title_matches: None,
+            matching: None,
+            not_tags: vec![],
+            no_due: false,
+            someday: false,
+            snoozed: false,
+            waiting: false,
+            all: true,
+            project: None,
+            not_projects: vec![],
+            group_by: None,
+            group_by_expr: None,
+            columns: vec![ListColumn::Id, ListColumn::Title],
+            wrap: true,
+            sort: ListSort::Priority,
+            limit: Some(20),
+            offset: None,
+            page: Some(2),
+            watch: false,
+            archived: false,
+            format: OutputFormat::Plain,
+        };
+        let _tui = Commands::Tui { show_keys: true };
+        let _board = Commands::Board {
+            group_by: board::GroupBy::Project,
+        };
+        let _today = Commands::Today;
+        let _next = Commands::Next {
+            energy: Some(Energy::Low),
+            limit: 5,
+        };
+        let _archive = Commands::Archive { days: 30 };
+        let _batch = Commands::Batch {
+            file: PathBuf::from("-"),
+        };
+        let _purge = Commands::Purge {
+            older_than: Some("30d".to_string()),
+            archive: false,
+        };
+        let _estimate = Commands::Estimate {
+            id: 1.into(),
+            duration: Some("2h".to_string()),
+            clear: false,
+        };
+        let _schedule = Commands::Schedule {
+            fill_week: true,
+            hours: Some(4),
+        };
+        let _plan = Commands::Plan {
+            budget: "4h".to_string(),
         };
 
-        let _complete = Commands::Complete { id: 1 };
-        let _delete = Commands::Delete { id: 1 };
-        let _show = Commands::Show { id: 1 };
+        let _complete = Commands::Complete {
+            ids: vec!["1".to_string()],
+            selection: Selection::default(),
+        };
+        let _move = Commands::Move {
+            id: 12.into(),
+            status: None,
+            before: Some(7.into()),
+            after: None,
+        };
+        let _delete = Commands::Delete {
+            ids: vec!["1".to_string(), "3-5".to_string()],
+            selection: Selection::default(),
+        };
+        let _note = Commands::Note {
+            id: 1.into(),
+            text: "Waiting on a quote".to_string(),
+        };
+        let _attach = Commands::Attach {
+            id: 1.into(),
+            target: "https://example.com/spec".to_string(),
+        };
+        let _open = Commands::Open {
+            id: 1.into(),
+            number: Some(2),
+        };
+        let _start = Commands::Start { id: 1.into() };
+        let _stop = Commands::Stop;
+        let _timesheet = Commands::Timesheet { days: 7 };
+        let _log = Commands::Log {
+            id: Some(1.into()),
+            limit: 20,
+        };
+        let _trash = Commands::Trash { empty: false };
+        let _restore = Commands::Restore { id: 1.into() };
+        let _undo = Commands::Undo;
+        let _show = Commands::Show {
+            id: 1.into(),
+            format: OutputFormat::Csv,
+        };
+        let _report = Commands::Report {
+            command: ReportCommands::Aging { oldest: 5 },
+        };
+        let _stale = Commands::Stale {
+            days: 30,
+            tag: false,
+        };
+        let _why = Commands::Why { id: 1.into() };
+        let _doctor = Commands::Doctor;
+        let _pick = Commands::Pick {
+            action: PickAction::Complete,
+        };
+        let _maintain = Commands::Maintain;
+        let _import = Commands::Import {
+            from: Some(ImportSource::Habitica),
+            file: None,
+            on_conflict: import::OnConflict::Renumber,
+            dry_run: false,
+            resume: false,
+            force: false,
+            project: None,
+            map_project: Vec::new(),
+            map_tag: Vec::new(),
+            interactive: false,
+            habitica_user: None,
+        };
+        let _export = Commands::Export {
+            format: ExportFormat::Markdown,
+            output: None,
+        };
+        let _obsidian = Commands::Obsidian {
+            command: ObsidianCommands::Sync {
+                vault: PathBuf::from("/tmp/vault"),
+                plan: true,
+                apply: false,
+            },
+        };
+        let _sync = Commands::Sync {
+            repo: Some(PathBuf::from("/tmp/tasks")),
+            offline: true,
+        };
+        let _get = Commands::Get {
+            id: 1.into(),
+            format: OutputFormat::Json,
+        };
+        let _set = Commands::Set {
+            id: 1.into(),
+            from_json: "-".to_string(),
+        };
+        let _init = Commands::Init {
+            encrypted: true,
+            keyring: false,
+        };
+        let _shell_init = Commands::ShellInit {
+            shell: shell::Shell::Zsh,
+        };
+        let _status_line = Commands::StatusLine;
+        let _completions = Commands::Completions {
+            shell: shell::Completions::Fish,
+        };
+        let _tags = Commands::Tags;
+        let _snapshot = Commands::Snapshot {
+            format: snapshot::SnapshotFormat::Svg,
+            group_by: snapshot::GroupBy::Status,
+            output: None,
+        };
+        let _snooze = Commands::Snooze {
+            id: 1.into(),
+            when: Some("2d".to_string()),
+            days: None,
+        };
+        let _someday = Commands::Someday {
+            id: Some(1.into()),
+            promote: false,
+        };
+        let _review = Commands::Review {
+            all: false,
+            list: false,
+            promote: vec![3.into()],
+            drop: vec![],
+            keep: vec![4.into()],
+        };
+        let _reprioritize = Commands::Reprioritize {
+            filter: TriageFilter::Overdue,
+            list: true,
+        };
+        let _plan_email = Commands::PlanEmail {
+            base_url: "http://localhost:8080".to_string(),
+            to: None,
+        };
+        let _share = Commands::Share {
+            id: Some(1.into()),
+            project: None,
+            public: true,
+            expires: "7d".to_string(),
+            base_url: "http://localhost:8080".to_string(),
+        };
+        let _serve = Commands::Serve {
+            port: 8080,
+            host: "127.0.0.1".to_string(),
+            token: Some("secret".to_string()),
+        };
+        let _focus = Commands::Focus {
+            command: FocusCommands::Start {
+                minutes: 50,
+                task: Some(1.into()),
+            },
+        };
+        let _logs = Commands::Logs { tail: 50 };
+        let _usage = Commands::Usage { reset: false };
+        let _history = Commands::History {
+            limit: 20,
+            clear: false,
+        };
+        let _again = Commands::Again { number: Some(3) };
+        let _self_update = Commands::SelfUpdate { check: true };
+        let _help = Commands::Help {
+            topic: Some("filters".to_string()),
+            man: false,
+        };
+        let _goal = Commands::Goal {
+            command: GoalCommands::Link {
+                goal: 1,
+                tasks: vec![1.into(), 2.into()],
+            },
+        };
 
         let _update = Commands::Update {
-            id: 1,
+            ids: vec![],
+            selection: Selection {
+                tagged: vec!["errands".to_string()],
+                ..Selection::default()
+            },
             title: None,
             description: None,
             due: None,
+            start: None,
+            no_start: true,
             priority: None,
+            inherit_priority: true,
+            project: None,
+            repeat: None,
+            no_repeat: true,
+            parent: None,
+            no_parent: true,
+            energy: Some(Energy::High),
+            context: None,
+            estimate: None,
+        };
+        let _search = Commands::Search {
+            query: vec!["invoice".to_string()],
+            completed: false,
+            tags: vec![],
+            project: None,
+            priority: None,
+        };
+        let _config = Commands::Config {
+            command: ConfigCommands::Set {
+                key: "due_soon_hours".to_string(),
+                value: "24".to_string(),
+                local: true,
+                system: false,
+            },
+        };
+        let _context = Commands::Context {
+            name: Some("@office".to_string()),
+            clear: false,
+        };
+        let _alias = Commands::Alias {
+            command: AliasCommands::Add {
+                name: "urgent".to_string(),
+                command: vec![
+                    "list".to_string(),
+                    "--priority".to_string(),
+                    "high".to_string(),
+                ],
+            },
+        };
+        let _profile = Commands::Profile {
+            command: ProfileCommands::Create {
+                name: "work".to_string(),
+                db: Some("~/work.db".to_string()),
+                context: Some("work".to_string()),
+            },
+        };
+        let _project = Commands::Project {
+            command: ProjectCommands::Rename {
+                old: "work".to_string(),
+                new: "job".to_string(),
+            },
+        };
+        let _project_priority = Commands::Project {
+            command: ProjectCommands::Priority {
+                name: "work".to_string(),
+                priority: Some(Priority::High),
+                clear: false,
+            },
+        };
+        let _notify = Commands::Notify {
+            hours: Some(2),
+            print: true,
+        };
+        let _template = Commands::Template {
+            command: TemplateCommands::Save {
+                name: "weekly-report".to_string(),
+                id: 1.into(),
+                due: Some("in 3 days".to_string()),
+            },
         };
-    }
-
-    #[test]
-    fn test_priority_enum_variants() {
-        // Test that all priority variants exist
-        let _low = Priority::Low;
-        let _medium = Priority::Medium;
-        let _high = Priority::High;
     }
 
     #[test]
     fn test_cli_struct() {
         // Test that CLI struct can be created
         let _cli = Cli {
+            profile: Some("work".to_string()),
+            db: Some(PathBuf::from("/tmp/work.db")),
+            plain: false,
+            no_color: false,
+            yes: true,
             command: Commands::List {
                 completed: false,
+                done: false,
                 priority: None,
+                due_soon: false,
+                due_soon_hours: None,
+                due: None,
+                tags: vec![],
+                title_matches: None,
+                matching: None,
+                not_tags: vec![],
+                no_due: false,
+                someday: false,
+                snoozed: false,
+                waiting: true,
+                all: false,
+                project: None,
+                not_projects: vec![],
+                group_by: Some(ListGroupBy::Project),
+                group_by_expr: None,
+                columns: ListColumn::DEFAULT.to_vec(),
+                wrap: false,
+                sort: ListSort::Manual,
+                limit: None,
+                offset: None,
+                page: None,
+                watch: false,
+                archived: false,
+                format: OutputFormat::Plain,
             },
         };
     }
 
-    #[test]
-    fn test_priority_ordering() {
-        // Test that priorities are ordered correctly
-        let priorities = vec![Priority::Low, Priority::Medium, Priority::High];
-        let int_values: Vec<i32> = priorities.iter().map(|p| p.to_int()).collect();
-
-        assert_eq!(int_values, vec![0, 1, 2]);
+    fn args(line: &str) -> Vec<std::ffi::OsString> {
+        line.split_whitespace().map(Into::into).collect()
     }
 
     #[test]
-    fn test_priority_roundtrip() {
-        // Test that priority conversion is reversible
-        let original = Priority::High;
-        let int_value = original.to_int();
-        let converted = Priority::from_int(int_value);
+    fn test_bulk_commands_take_ids_or_a_selection() {
+        let cli = Cli::try_parse_from(args("todo update --tagged errands --priority low")).unwrap();
+        let Commands::Update { ids, selection, .. } = cli.command else {
+            panic!("expected update");
+        };
+        assert!(ids.is_empty());
+        let filter = selection.filter().unwrap().unwrap();
+        assert_eq!(filter.tags, ["errands"]);
+        assert_eq!(filter.priority, None);
 
-        assert!(matches!(converted, Priority::High));
+        assert!(Cli::try_parse_from(args("todo complete")).is_err());
+        assert!(Cli::try_parse_from(args("todo complete 3 --in-project home")).is_err());
+        let cli = Cli::try_parse_from(args("todo delete 3-5")).unwrap();
+        let Commands::Delete { selection, .. } = cli.command else {
+            panic!("expected delete");
+        };
+        assert!(selection.filter().unwrap().is_none());
     }
 
     #[test]
-    fn test_invalid_priority_handling() {
-        // Test that invalid priority values default to Medium
-        let invalid_priority = Priority::from_int(999);
-        assert!(matches!(invalid_priority, Priority::Medium));
+    fn test_suggests_commands_and_flags() {
+        let root = Cli::command();
+
+        let line = args("todo lst --tag work");
+        let error = root.clone().try_get_matches_from(&line).unwrap_err();
+        let suggestion = suggest::suggest(&root, &error, &line).unwrap();
+        assert_eq!(suggestion.hint(), "did you mean `list`?");
+        assert_eq!(
+            suggestion.apply(&line).unwrap(),
+            args("todo list --tag work")
+        );
+
+        let line = args("todo list --priorty=high");
+        let error = root.clone().try_get_matches_from(&line).unwrap_err();
+        let suggestion = suggest::suggest(&root, &error, &line).unwrap();
+        assert_eq!(suggestion.candidates, vec!["--priority"]);
+        assert_eq!(
+            suggestion.apply(&line).unwrap(),
+            args("todo list --priority=high")
+        );
+
+        // Names come from the subcommand that was reached
+        let line = args("todo project lsit");
+        let error = root.clone().try_get_matches_from(&line).unwrap_err();
+        assert_eq!(
+            suggest::suggest(&root, &error, &line).unwrap().candidates,
+            vec!["list"]
+        );
+
+        let line = args("todo frobnicate");
+        let error = root.clone().try_get_matches_from(&line).unwrap_err();
+        assert_eq!(suggest::suggest(&root, &error, &line), None);
     }
 }
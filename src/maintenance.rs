@@ -0,0 +1,241 @@
+use anyhow::Result;
+use chrono::{Duration, Utc};
+
+use crate::commands::STALE_TAG;
+use crate::db::{Database, TaskFilter, META_LAST_MAINTENANCE};
+use crate::progress::Progress;
+
+/// Minimum time between two automatic maintenance runs.
+pub const DEFAULT_INTERVAL_MINUTES: i64 = 60;
+
+/// Pending tasks untouched for this many days get tagged as stale, when
+/// stale tagging is on.
+pub const STALE_AFTER_DAYS: i64 = 30;
+
+/// Tags marking tasks as someday/maybe material, eligible for priority decay.
+pub const SOMEDAY_TAGS: [&str; 2] = ["someday", "maybe"];
+
+/// Snoozes of a someday task that trigger one step of priority decay.
+pub const SNOOZES_PER_DECAY: i32 = 3;
+
+/// Optional housekeeping rules.
+#[derive(Debug, Clone, Default)]
+pub struct MaintenanceOptions {
+    /// Tag tasks untouched for `STALE_AFTER_DAYS` as stale.
+    pub stale_tagging: bool,
+    /// Lower the priority of someday/maybe tasks that keep getting snoozed.
+    pub priority_decay: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct MaintenanceReport {
+    pub stale_tagged: usize,
+    pub decayed: usize,
+    /// Tasks moved into the someday bucket by decay.
+    pub parked: usize,
+}
+
+/// Actor recorded in the task history for housekeeping changes.
+pub const ACTOR: &str = "maintenance";
+
+/// Run the enabled housekeeping jobs unconditionally and record the run time.
+pub fn run(
+    db: &Database,
+    options: &MaintenanceOptions,
+    progress: &Progress,
+) -> Result<MaintenanceReport> {
+    let report = db.with_actor(ACTOR, || run_jobs(db, options, progress))?;
+    crate::logging::info(
+        ACTOR,
+        format!(
+            "tagged {} stale, decayed {}, parked {}",
+            report.stale_tagged, report.decayed, report.parked
+        ),
+    );
+    Ok(report)
+}
+
+fn run_jobs(
+    db: &Database,
+    options: &MaintenanceOptions,
+    progress: &Progress,
+) -> Result<MaintenanceReport> {
+    let mut report = MaintenanceReport::default();
+
+    if options.stale_tagging {
+        progress.set_label("Tagging stale tasks");
+        let cutoff = Utc::now() - Duration::days(STALE_AFTER_DAYS);
+        for task in db.get_stale_tasks(cutoff)? {
+            crate::shutdown::check()?;
+            progress.inc(1);
+            if !task.tags.iter().any(|tag| tag == STALE_TAG) {
+                db.add_tag(task.id.unwrap(), STALE_TAG)?;
+                report.stale_tagged += 1;
+            }
+        }
+    }
+
+    if options.priority_decay {
+        progress.set_label("Decaying someday priorities");
+        decay_someday_tasks(db, &mut report, progress)?;
+    }
+
+    db.set_meta_datetime(META_LAST_MAINTENANCE, Utc::now())?;
+    Ok(report)
+}
+
+/// Every `SNOOZES_PER_DECAY` snoozes, a someday/maybe task drops one priority
+/// level. Once it is already low it is moved into the someday bucket.
+fn decay_someday_tasks(
+    db: &Database,
+    report: &mut MaintenanceReport,
+    progress: &Progress,
+) -> Result<()> {
+    let filter = TaskFilter::default();
+    for task in db.get_tasks(&filter)? {
+        crate::shutdown::check()?;
+        progress.inc(1);
+        if !is_someday(&task.tags) {
+            continue;
+        }
+        let id = task.id.unwrap();
+        if db.get_snooze_count(id)? < SNOOZES_PER_DECAY {
+            continue;
+        }
+
+        if task.priority > 0 {
+            db.set_priority(id, task.priority - 1)?;
+            report.decayed += 1;
+        } else {
+            db.set_someday(id, true)?;
+            report.parked += 1;
+        }
+        db.reset_snooze_count(id)?;
+    }
+    Ok(())
+}
+
+/// Whether any tag is, or is nested beneath, one of the someday tags.
+pub fn is_someday(tags: &[String]) -> bool {
+    tags.iter().any(|tag| {
+        SOMEDAY_TAGS
+            .iter()
+            .any(|root| crate::tags::ancestors(tag).iter().any(|a| a == root))
+    })
+}
+
+/// Run maintenance only if the last run is older than `interval`.
+pub fn run_if_due(
+    db: &Database,
+    interval: Duration,
+    options: &MaintenanceOptions,
+) -> Result<Option<MaintenanceReport>> {
+    match db.get_meta_datetime(META_LAST_MAINTENANCE)? {
+        Some(last) if Utc::now() - last < interval => Ok(None),
+        _ => run(db, options, &Progress::hidden()).map(Some),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::create_test_db;
+    use crate::models::Task;
+
+    fn add_old_task(db: &Database) -> i32 {
+        let mut task = Task::new("Old".to_string(), None, None, 1);
+        task.created_at = Utc::now() - Duration::days(STALE_AFTER_DAYS + 5);
+        task.updated_at = task.created_at;
+        db.add_task(&task).unwrap()
+    }
+
+    #[test]
+    fn test_run_tags_stale_tasks() {
+        let (db, _temp_file) = create_test_db();
+        let id = add_old_task(&db);
+        let options = MaintenanceOptions {
+            stale_tagging: true,
+            ..MaintenanceOptions::default()
+        };
+
+        // Disabled by default
+        let report = run(&db, &MaintenanceOptions::default(), &Progress::hidden()).unwrap();
+        assert_eq!(report.stale_tagged, 0);
+        assert!(db.get_task_by_id(id).unwrap().unwrap().tags.is_empty());
+
+        let report = run(&db, &options, &Progress::hidden()).unwrap();
+        assert_eq!(report.stale_tagged, 1);
+        assert_eq!(
+            db.get_task_by_id(id).unwrap().unwrap().tags,
+            vec![STALE_TAG]
+        );
+
+        // Already tagged tasks are not counted twice
+        let report = run(&db, &options, &Progress::hidden()).unwrap();
+        assert_eq!(report.stale_tagged, 0);
+    }
+
+    #[test]
+    fn test_run_if_due_is_throttled() {
+        let (db, _temp_file) = create_test_db();
+
+        let interval = Duration::minutes(DEFAULT_INTERVAL_MINUTES);
+        assert!(run_if_due(&db, interval, &MaintenanceOptions::default())
+            .unwrap()
+            .is_some());
+        assert!(run_if_due(&db, interval, &MaintenanceOptions::default())
+            .unwrap()
+            .is_none());
+
+        // A zero interval always runs
+        assert!(
+            run_if_due(&db, Duration::zero(), &MaintenanceOptions::default())
+                .unwrap()
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn test_priority_decay_of_snoozed_someday_tasks() {
+        let (db, _temp_file) = create_test_db();
+        let mut task = Task::new("Learn the banjo".to_string(), None, None, 1);
+        task.tags = vec!["someday/hobbies".to_string()];
+        let id = db.add_task(&task).unwrap();
+        let plain = db
+            .add_task(&Task::new("Pay rent".to_string(), None, None, 1))
+            .unwrap();
+
+        let options = MaintenanceOptions {
+            priority_decay: true,
+            ..MaintenanceOptions::default()
+        };
+        let snooze = |id| {
+            for _ in 0..SNOOZES_PER_DECAY {
+                db.snooze_task(id, Utc::now() + Duration::days(1)).unwrap();
+            }
+        };
+
+        // Disabled by default
+        snooze(id);
+        snooze(plain);
+        run(&db, &MaintenanceOptions::default(), &Progress::hidden()).unwrap();
+        assert_eq!(db.get_task_by_id(id).unwrap().unwrap().priority, 1);
+
+        let report = run(&db, &options, &Progress::hidden()).unwrap();
+        assert_eq!(report.decayed, 1);
+        assert_eq!(db.get_task_by_id(id).unwrap().unwrap().priority, 0);
+        assert_eq!(db.get_task_by_id(plain).unwrap().unwrap().priority, 1);
+
+        // Counter was reset, so nothing happens until it is snoozed again
+        assert_eq!(run(&db, &options, &Progress::hidden()).unwrap().decayed, 0);
+
+        snooze(id);
+        let report = run(&db, &options, &Progress::hidden()).unwrap();
+        assert_eq!(report.parked, 1);
+        assert!(db.get_task_by_id(id).unwrap().unwrap().someday);
+
+        // Parked tasks are out of the normal views and are not decayed again
+        snooze(id);
+        assert_eq!(run(&db, &options, &Progress::hidden()).unwrap().parked, 0);
+    }
+}
@@ -0,0 +1,300 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+use std::collections::{HashMap, HashSet};
+
+use crate::import::ImportedTask;
+use crate::models::Task;
+
+/// Heading `render` gives tasks without a project.
+const NO_PROJECT: &str = "No project";
+
+/// Render `tasks` as a Markdown checklist in the Obsidian Tasks format
+/// (`📅` due dates, `⏫`/`🔽` priorities, `✅` done dates), with a heading per
+/// project and subtasks indented under their parent.
+pub fn render(tasks: &[Task]) -> String {
+    let ids: HashSet<i32> = tasks.iter().filter_map(|task| task.id).collect();
+    let mut children: HashMap<i32, Vec<&Task>> = HashMap::new();
+    let mut roots: Vec<&Task> = Vec::new();
+    for task in tasks {
+        match task.parent_id.filter(|parent| ids.contains(parent)) {
+            Some(parent) => children.entry(parent).or_default().push(task),
+            None => roots.push(task),
+        }
+    }
+    // Named projects first, alphabetically; tasks without one last
+    roots.sort_by_key(|task| (task.project.is_none(), task.project.clone()));
+
+    let mut output = String::from("# Tasks\n");
+    let mut current: Option<Option<&str>> = None;
+    for root in roots {
+        let project = root.project.as_deref();
+        if current != Some(project) {
+            output.push_str(&format!("\n## {}\n\n", project.unwrap_or(NO_PROJECT)));
+            current = Some(project);
+        }
+        push_item(root, 0, &children, &mut output);
+    }
+    output
+}
+
+fn push_item(task: &Task, depth: usize, children: &HashMap<i32, Vec<&Task>>, output: &mut String) {
+    let mut line = format!(
+        "{}- [{}] {}",
+        "  ".repeat(depth),
+        if task.completed { "x" } else { " " },
+        task.title
+    );
+    for tag in &task.tags {
+        line.push_str(&format!(" #{}", tag));
+    }
+    match task.priority {
+        2 => line.push_str(" ⏫"),
+        0 => line.push_str(" 🔽"),
+        _ => {}
+    }
+    if let Some(recurrence) = &task.recurrence {
+        line.push_str(&format!(" 🔁 {}", recurrence));
+    }
+    if let Some(due) = task.due_date {
+        line.push_str(&format!(
+            " 📅 {}",
+            crate::zone::day_of(due).format("%Y-%m-%d")
+        ));
+    }
+    if task.completed {
+        let done = crate::zone::day_of(task.updated_at);
+        line.push_str(&format!(" ✅ {}", done.format("%Y-%m-%d")));
+    }
+    output.push_str(&line);
+    output.push('\n');
+
+    if let Some(description) = &task.description {
+        for text in description.lines() {
+            output.push_str(&format!("{}  {}\n", "  ".repeat(depth), text));
+        }
+    }
+    if let Some(kids) = task.id.and_then(|id| children.get(&id)) {
+        for child in kids {
+            push_item(child, depth + 1, children, output);
+        }
+    }
+}
+
+/// Read tasks back from a Markdown checklist, as `render` writes it or as
+/// found in any note. A `##` heading names the project of the items under
+/// it (other headings, and `No project`, leave them without one), indented
+/// items are subtasks of the item above, and indented text under an item is
+/// its description. Items without a priority emoji get `default_priority`.
+pub fn parse(contents: &str, default_priority: i32) -> Result<Vec<ImportedTask>> {
+    let mut tasks: Vec<ImportedTask> = Vec::new();
+    let mut project: Option<String> = None;
+    // Indent and file position of the items later ones may be nested under
+    let mut open: Vec<(usize, usize)> = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        let text = line.trim_start();
+        let indent = line.len() - text.len();
+        if let Some((done, body)) = checklist_item(text) {
+            while open.last().is_some_and(|&(depth, _)| depth >= indent) {
+                open.pop();
+            }
+            let mut task = parse_item(body, done, default_priority)
+                .with_context(|| format!("Invalid Markdown on line {}", index + 1))?;
+            task.project = project.clone();
+            task.parent_id = open.last().map(|&(_, position)| -(position as i32 + 1));
+            open.push((indent, tasks.len()));
+            tasks.push((task, None));
+            continue;
+        }
+
+        // Text indented under the last item belongs to its description
+        if let Some(&(depth, position)) = open.last().filter(|&&(depth, _)| indent > depth) {
+            let task = &mut tasks[position].0;
+            let text = &line[(depth + 2).min(indent)..];
+            let description = task.description.get_or_insert_with(String::new);
+            if !description.is_empty() {
+                description.push('\n');
+            }
+            description.push_str(text.trim_end());
+            continue;
+        }
+        if text.is_empty() {
+            continue;
+        }
+        open.clear();
+        if let Some(heading) = text.strip_prefix('#') {
+            let level = 1 + heading.len() - heading.trim_start_matches('#').len();
+            if let Some(title) = heading.trim_start_matches('#').strip_prefix(' ') {
+                let title = title.trim();
+                project = (level == 2 && title != NO_PROJECT).then(|| title.to_string());
+            }
+        }
+    }
+
+    for (task, _) in &mut tasks {
+        task.description = task
+            .description
+            .take()
+            .map(|description| description.trim_end().to_string())
+            .filter(|description| !description.is_empty());
+    }
+    Ok(tasks)
+}
+
+/// Whether the item is checked, and what follows its checkbox.
+fn checklist_item(text: &str) -> Option<(bool, &str)> {
+    let rest = text
+        .strip_prefix("- ")
+        .or_else(|| text.strip_prefix("* "))?;
+    if let Some(body) = rest.strip_prefix("[ ]") {
+        return Some((false, body));
+    }
+    let body = rest
+        .strip_prefix("[x]")
+        .or_else(|| rest.strip_prefix("[X]"))?;
+    Some((true, body))
+}
+
+/// A task from the text of a checklist item, reading the emoji fields of
+/// the Obsidian Tasks format and `#tags`.
+fn parse_item(body: &str, done: bool, default_priority: i32) -> Result<Task> {
+    let date = |word: Option<&str>| -> Result<DateTime<Utc>> {
+        let word = word.unwrap_or_default();
+        NaiveDate::parse_from_str(word, "%Y-%m-%d")
+            .map(crate::zone::start_of)
+            .with_context(|| format!("Invalid date '{}'. Use YYYY-MM-DD", word))
+    };
+    let is_field = |word: &str| {
+        matches!(
+            word,
+            "📅" | "✅" | "⏳" | "🛫" | "➕" | "🔁" | "🔺" | "⏫" | "🔼" | "🔽" | "⏬"
+        )
+    };
+
+    let mut title = Vec::new();
+    let mut tags = Vec::new();
+    let mut due = None;
+    let mut done_on = None;
+    let mut recurrence = None;
+    let mut priority = default_priority;
+    let mut words = body.split_whitespace().peekable();
+    while let Some(word) = words.next() {
+        match word {
+            "📅" => due = Some(date(words.next())?),
+            "✅" => done_on = Some(date(words.next())?),
+            // Scheduled, start and created dates
+            "⏳" | "🛫" | "➕" => {
+                words.next();
+            }
+            "🔁" => {
+                let mut rule = Vec::new();
+                while let Some(word) = words.next_if(|word| !is_field(word)) {
+                    rule.push(word);
+                }
+                recurrence = Some(crate::recurrence::normalize(&rule.join(" "))?);
+            }
+            "🔺" | "⏫" => priority = 2,
+            "🔼" => priority = 1,
+            "🔽" | "⏬" => priority = 0,
+            _ => match word
+                .strip_prefix('#')
+                .and_then(|tag| crate::tags::normalize(tag).ok())
+            {
+                Some(tag) => tags.push(tag),
+                None => title.push(word),
+            },
+        }
+    }
+    if title.is_empty() {
+        return Err(anyhow::anyhow!("The item has no title"));
+    }
+
+    let mut task = Task::new(title.join(" "), None, due, priority);
+    task.tags = tags;
+    task.recurrence = recurrence;
+    task.completed = done;
+    if let Some(done_on) = done_on.filter(|_| done) {
+        task.updated_at = done_on;
+    }
+    Ok(task)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn test_render_nests_and_groups() {
+        let mut launch = Task::new("Launch".to_string(), None, None, 2);
+        launch.id = Some(1);
+        launch.project = Some("work".to_string());
+        launch.due_date = Some(Utc.with_ymd_and_hms(2030, 5, 1, 0, 0, 0).unwrap());
+        let mut slides = Task::new("Slides".to_string(), Some("Ten max".to_string()), None, 1);
+        slides.id = Some(2);
+        slides.parent_id = Some(1);
+        slides.tags = vec!["deck".to_string()];
+        let mut milk = Task::new("Milk".to_string(), None, None, 0);
+        milk.id = Some(3);
+        milk.completed = true;
+
+        let output = render(&[milk, slides, launch]);
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0], "# Tasks");
+        assert_eq!(lines[2], "## work");
+        assert_eq!(lines[4], "- [ ] Launch ⏫ 📅 2030-05-01");
+        assert_eq!(lines[5], "  - [ ] Slides #deck");
+        assert_eq!(lines[6], "    Ten max");
+        assert_eq!(lines[8], "## No project");
+        assert!(lines[10].starts_with("- [x] Milk 🔽 ✅ "));
+    }
+
+    #[test]
+    fn test_parse_reads_render_back() {
+        let mut launch = Task::new("Launch".to_string(), None, None, 2);
+        launch.id = Some(1);
+        launch.project = Some("work".to_string());
+        launch.recurrence = Some("weekly".to_string());
+        launch.due_date = Some(Utc.with_ymd_and_hms(2030, 5, 1, 0, 0, 0).unwrap());
+        let mut slides = Task::new(
+            "Slides".to_string(),
+            Some("Ten max\n\n  Keep it short".to_string()),
+            None,
+            1,
+        );
+        slides.id = Some(2);
+        slides.parent_id = Some(1);
+        slides.project = Some("work".to_string());
+        slides.tags = vec!["deck".to_string(), "work/q2".to_string()];
+        let mut milk = Task::new("Milk".to_string(), None, None, 0);
+        milk.id = Some(3);
+        milk.completed = true;
+        milk.updated_at = Utc.with_ymd_and_hms(2030, 4, 2, 0, 0, 0).unwrap();
+
+        let tasks = parse(&render(&[milk, slides, launch]), 1).unwrap();
+        let tasks: Vec<Task> = tasks.into_iter().map(|(task, _)| task).collect();
+        assert_eq!(tasks.len(), 3);
+        assert_eq!(tasks[0].title, "Launch");
+        assert_eq!((tasks[0].priority, tasks[0].parent_id), (2, None));
+        assert_eq!(tasks[0].recurrence.as_deref(), Some("weekly"));
+        assert_eq!(
+            tasks[0].due_date,
+            Some(Utc.with_ymd_and_hms(2030, 5, 1, 0, 0, 0).unwrap())
+        );
+        assert_eq!(tasks[1].parent_id, Some(-1));
+        assert_eq!(tasks[1].project.as_deref(), Some("work"));
+        assert_eq!(tasks[1].tags, vec!["deck", "work/q2"]);
+        assert_eq!(
+            tasks[1].description.as_deref(),
+            Some("Ten max\n\n  Keep it short")
+        );
+        assert_eq!((tasks[2].project.as_deref(), tasks[2].priority), (None, 0));
+        assert!(tasks[2].completed);
+        assert_eq!(
+            tasks[2].updated_at,
+            Utc.with_ymd_and_hms(2030, 4, 2, 0, 0, 0).unwrap()
+        );
+
+        let error = parse("- [ ] Pay rent 📅 soon\n", 1).unwrap_err();
+        assert!(format!("{:#}", error).contains("line 1"));
+    }
+}
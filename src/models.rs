@@ -1,7 +1,10 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
 use colored::*;
 use serde::{Deserialize, Serialize};
 
+/// Default size of the "due soon" window, in hours.
+pub const DEFAULT_DUE_SOON_HOURS: i64 = 48;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Task {
     pub id: Option<i32>,
@@ -12,6 +15,38 @@ pub struct Task {
     pub completed: bool,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Parked in the someday/maybe bucket, out of normal lists and counts.
+    #[serde(default)]
+    pub someday: bool,
+    /// Being worked on: the middle column of the TUI board.
+    #[serde(default)]
+    pub in_progress: bool,
+    #[serde(default)]
+    pub project: Option<String>,
+    /// Repeat rule in canonical form, e.g. `weekly` or `every 3 days`.
+    #[serde(default)]
+    pub recurrence: Option<String>,
+    /// The task this one is a subtask of.
+    #[serde(default)]
+    pub parent_id: Option<i32>,
+    #[serde(default)]
+    pub energy: Option<crate::Energy>,
+    /// `priority` comes from the project or a parent task, not the task
+    /// itself, and follows them when they change.
+    #[serde(default)]
+    pub priority_inherited: bool,
+    /// When the task was completed; `None` while it is pending.
+    #[serde(default)]
+    pub completed_at: Option<DateTime<Utc>>,
+    /// Kept out of normal lists until then, like a deferred task.
+    #[serde(default)]
+    pub start_date: Option<DateTime<Utc>>,
+    /// Identifies the task across databases, exports and syncs, unlike
+    /// its ID; set when it is first saved.
+    #[serde(default)]
+    pub uuid: Option<String>,
 }
 
 impl Task {
@@ -31,9 +66,38 @@ impl Task {
             completed: false,
             created_at: now,
             updated_at: now,
+            tags: Vec::new(),
+            someday: false,
+            in_progress: false,
+            project: None,
+            recurrence: None,
+            parent_id: None,
+            energy: None,
+            priority_inherited: false,
+            completed_at: None,
+            start_date: None,
+            uuid: None,
         }
     }
 
+    /// Fingerprint of what the task says, for spotting duplicates: the title
+    /// ignoring case and runs of whitespace, the due day and the project.
+    pub fn content_hash(&self) -> String {
+        use sha2::{Digest, Sha256};
+        let title = self
+            .title
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_lowercase();
+        let due = self
+            .due_date
+            .map(|due| due.format("%Y-%m-%d").to_string())
+            .unwrap_or_default();
+        let project = self.project.as_deref().unwrap_or_default().to_lowercase();
+        hex::encode(Sha256::digest(format!("{}\0{}\0{}", title, due, project)))
+    }
+
     pub fn priority_text(&self) -> &'static str {
         match self.priority {
             0 => "LOW",
@@ -43,29 +107,55 @@ impl Task {
         }
     }
 
+    /// The priority set on the task itself, `None` when it is inherited.
+    pub fn own_priority(&self) -> Option<i32> {
+        (!self.priority_inherited).then_some(self.priority)
+    }
+
+    /// The priority in words for plain output, e.g. `high (inherited)`.
+    fn plain_priority(&self) -> String {
+        let text = self.priority_text().to_lowercase();
+        if self.priority_inherited {
+            format!("{} (inherited)", text)
+        } else {
+            text
+        }
+    }
+
+    /// The priority label, with `*` marking an inherited priority.
     pub fn priority_color(&self) -> ColoredString {
-        match self.priority {
-            0 => "LOW".blue(),
-            1 => "MEDIUM".yellow(),
-            2 => "HIGH".red(),
-            _ => "MEDIUM".yellow(),
+        let mut label = crate::Priority::from_int(self.priority).color();
+        if self.priority_inherited {
+            label.input.push('*');
         }
+        label
     }
 
     pub fn status_text(&self) -> ColoredString {
         if self.completed {
-            "✓ COMPLETED".green()
+            crate::style::completed("✓ COMPLETED")
+        } else if self.someday {
+            crate::style::someday("◌ SOMEDAY")
+        } else if self.is_waiting() {
+            crate::style::waiting("⏳ WAITING")
+        } else if self.in_progress {
+            crate::style::in_progress("▶ IN PROGRESS")
         } else {
-            "○ PENDING".white()
+            crate::style::pending("○ PENDING")
         }
     }
 
     pub fn due_date_text(&self) -> String {
         self.due_date
-            .map(|date| date.format("%Y-%m-%d").to_string())
+            .map(crate::style::date)
             .unwrap_or_else(|| "No due date".to_string())
     }
 
+    /// A pending task is waiting until its start date arrives.
+    pub fn is_waiting(&self) -> bool {
+        !self.completed && self.start_date.is_some_and(|start| start > Utc::now())
+    }
+
     pub fn is_overdue(&self) -> bool {
         if self.completed {
             return false;
@@ -74,53 +164,625 @@ impl Task {
         self.due_date.map(|due| Utc::now() > due).unwrap_or(false)
     }
 
-    pub fn display_summary(&self) -> String {
+    /// A pending task is due soon when its due date falls between now and
+    /// `now + window`. Overdue tasks are not considered due soon.
+    pub fn is_due_soon(&self, window: Duration) -> bool {
+        if self.completed {
+            return false;
+        }
+
+        let now = Utc::now();
+        self.due_date
+            .map(|due| due >= now && due <= now + window)
+            .unwrap_or(false)
+    }
+
+    pub fn due_date_colored(&self, due_soon_window: Duration) -> ColoredString {
+        if self.is_overdue() {
+            crate::style::overdue(&self.due_date_text())
+        } else if self.is_due_soon(due_soon_window) {
+            crate::style::due_soon(&self.due_date_text())
+        } else {
+            self.due_date_text().white()
+        }
+    }
+
+    /// Compact age since creation, e.g. `5h` or `12d`.
+    pub fn age_text(&self) -> String {
+        let age = Utc::now() - self.created_at;
+        if age.num_days() < 1 {
+            format!("{}h", age.num_hours().max(0))
+        } else {
+            format!("{}d", age.num_days())
+        }
+    }
+
+    pub fn tags_text(&self) -> String {
+        self.tags
+            .iter()
+            .map(|tag| format!("#{}", tag))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// What differs from `before`, one short phrase per field, e.g.
+    /// `due 2030-01-10 → 2030-01-12`. Someday membership is tracked
+    /// separately and not compared.
+    pub fn changes_from(&self, before: &Task) -> Vec<String> {
+        fn due(task: &Task) -> String {
+            let zone = crate::zone::current();
+            match task.due_date.map(|date| date.with_timezone(&zone)) {
+                Some(date) if date.format("%H:%M:%S").to_string() != "00:00:00" => {
+                    date.format("%Y-%m-%d %H:%M").to_string()
+                }
+                Some(date) => date.format("%Y-%m-%d").to_string(),
+                None => "none".to_string(),
+            }
+        }
+        fn or_none<T: ToString>(value: &Option<T>) -> String {
+            value
+                .as_ref()
+                .map(|value| value.to_string())
+                .unwrap_or_else(|| "none".to_string())
+        }
+
+        let mut changes = Vec::new();
+        if self.title != before.title {
+            changes.push(format!("title \"{}\" → \"{}\"", before.title, self.title));
+        }
+        if self.description != before.description {
+            changes.push("description changed".to_string());
+        }
+        if self.due_date != before.due_date {
+            changes.push(format!("due {} → {}", due(before), due(self)));
+        }
+        if self.start_date != before.start_date {
+            let start = |task: &Task| {
+                task.start_date
+                    .map(|date| crate::zone::day_of(date).format("%Y-%m-%d").to_string())
+                    .unwrap_or_else(|| "none".to_string())
+            };
+            changes.push(format!("start {} → {}", start(before), start(self)));
+        }
+        if self.priority_inherited && !before.priority_inherited {
+            changes.push("priority inherited".to_string());
+        } else if self.priority != before.priority
+            || self.priority_inherited != before.priority_inherited
+        {
+            changes.push(format!(
+                "priority {} → {}",
+                before.priority_text().to_lowercase(),
+                self.priority_text().to_lowercase()
+            ));
+        }
+        if self.completed != before.completed {
+            changes.push(
+                if self.completed {
+                    "completed"
+                } else {
+                    "reopened"
+                }
+                .to_string(),
+            );
+        }
+        if self.project != before.project {
+            changes.push(format!(
+                "project {} → {}",
+                or_none(&before.project),
+                or_none(&self.project)
+            ));
+        }
+        if self.recurrence != before.recurrence {
+            changes.push(format!(
+                "repeat {} → {}",
+                or_none(&before.recurrence),
+                or_none(&self.recurrence)
+            ));
+        }
+        if self.parent_id != before.parent_id {
+            changes.push(format!(
+                "parent {} → {}",
+                or_none(&before.parent_id),
+                or_none(&self.parent_id)
+            ));
+        }
+        let added: Vec<String> = self
+            .tags
+            .iter()
+            .filter(|tag| !before.tags.contains(tag))
+            .map(|tag| format!("+#{}", tag))
+            .collect();
+        let removed: Vec<String> = before
+            .tags
+            .iter()
+            .filter(|tag| !self.tags.contains(tag))
+            .map(|tag| format!("-#{}", tag))
+            .collect();
+        if !added.is_empty() || !removed.is_empty() {
+            changes.push(format!("tags {}", [added, removed].concat().join(" ")));
+        }
+        changes
+    }
+
+    pub fn display_summary(&self, due_soon_window: Duration) -> String {
+        if crate::style::plain() {
+            return self.plain_summary(due_soon_window);
+        }
         let id = self.id.unwrap_or(0);
         let priority = self.priority_color();
         let status = self.status_text();
-        let due = if self.is_overdue() {
-            self.due_date_text().red()
+        let due = self.due_date_colored(due_soon_window);
+        let age = self.age_text().dimmed();
+        let start = self
+            .start_date
+            .filter(|_| self.is_waiting())
+            .map(|start| {
+                format!(
+                    " {}",
+                    format!("starts {}", crate::style::date(start)).dimmed()
+                )
+            })
+            .unwrap_or_default();
+        let repeat = self
+            .recurrence
+            .as_ref()
+            .map(|rule| format!(" {}", format!("↻ {}", rule).blue()))
+            .unwrap_or_default();
+        let project = self
+            .project
+            .as_ref()
+            .map(|name| format!(" {}", crate::style::project(name)))
+            .unwrap_or_default();
+        let tags = if self.tags.is_empty() {
+            String::new()
         } else {
-            self.due_date_text().white()
+            format!(" {}", crate::style::tags(&self.tags))
         };
 
-        format!("[{}] {} {} {} {}", id, self.title, priority, status, due)
+        format!(
+            "[{}] {} {} {} {} {}{}{}{}{}",
+            id, self.title, priority, status, due, age, start, repeat, project, tags
+        )
+    }
+
+    /// The summary as labelled fields, for plain mode: what colors and
+    /// symbols convey elsewhere is spelled out.
+    fn plain_summary(&self, due_soon_window: Duration) -> String {
+        let mut fields = vec![
+            format!("Task {}: {}", self.id.unwrap_or(0), self.title),
+            format!("priority: {}", self.plain_priority()),
+            format!("status: {}", self.status_word()),
+            format!("due: {}", self.plain_due(due_soon_window)),
+            format!("age: {}", self.age_text()),
+        ];
+        if let Some(start) = self.start_date.filter(|_| self.is_waiting()) {
+            fields.push(format!("starts: {}", crate::style::date(start)));
+        }
+        if let Some(rule) = &self.recurrence {
+            fields.push(format!("repeats: {}", rule));
+        }
+        if let Some(project) = &self.project {
+            fields.push(format!("project: {}", project));
+        }
+        if let Some(energy) = self.energy {
+            fields.push(format!("energy: {}", energy.name()));
+        }
+        if !self.tags.is_empty() {
+            fields.push(format!("tags: {}", self.tags.join(", ")));
+        }
+        fields.join("; ")
+    }
+
+    fn status_word(&self) -> &'static str {
+        if self.completed {
+            "completed"
+        } else if self.someday {
+            "someday"
+        } else if self.is_waiting() {
+            "waiting"
+        } else if self.in_progress {
+            "in progress"
+        } else {
+            "pending"
+        }
+    }
+
+    /// The due date with its urgency in words rather than color.
+    fn plain_due(&self, due_soon_window: Duration) -> String {
+        match self.due_date {
+            None => "none".to_string(),
+            Some(due) if self.is_overdue() => format!("{} (overdue)", crate::style::date(due)),
+            Some(due) if self.is_due_soon(due_soon_window) => {
+                format!("{} (due soon)", crate::style::date(due))
+            }
+            Some(due) => crate::style::date(due),
+        }
     }
 
-    pub fn display_detailed(&self) -> String {
+    /// Every field of the task, followed by `notes` oldest first.
+    pub fn display_detailed(&self, notes: &[Note]) -> String {
         let id = self.id.unwrap_or(0);
-        let priority = self.priority_color();
-        let status = self.status_text();
-        let due = if self.is_overdue() {
-            self.due_date_text().red()
+        let window = Duration::hours(DEFAULT_DUE_SOON_HOURS);
+        let (priority, status, due) = if crate::style::plain() {
+            (
+                self.plain_priority(),
+                self.status_word().to_string(),
+                self.plain_due(window),
+            )
         } else {
-            self.due_date_text().white()
+            (
+                self.priority_color().to_string(),
+                self.status_text().to_string(),
+                self.due_date_colored(window).to_string(),
+            )
         };
 
         let description = self
             .description
             .as_ref()
             .map(|desc| format!("\nDescription: {}", desc))
-            .unwrap_or_else(|| "".to_string());
+            .unwrap_or_default();
+
+        let project = self
+            .project
+            .as_ref()
+            .map(|name| format!("\nProject: {}", name))
+            .unwrap_or_default();
+
+        let repeat = self
+            .recurrence
+            .as_ref()
+            .map(|rule| format!("\nRepeats: {}", rule))
+            .unwrap_or_default();
+
+        let start = self
+            .start_date
+            .map(|start| format!("\nStarts: {}", crate::style::date(start)))
+            .unwrap_or_default();
+
+        let parent = self
+            .parent_id
+            .map(|parent| format!("\nSubtask of: #{}", parent))
+            .unwrap_or_default();
+
+        let energy = self
+            .energy
+            .map(|energy| format!("\nEnergy: {}", energy.name()))
+            .unwrap_or_default();
+
+        let tags = if self.tags.is_empty() {
+            String::new()
+        } else {
+            format!("\nTags: {}", self.tags_text())
+        };
+
+        let notes: String = notes
+            .iter()
+            .map(|note| {
+                format!(
+                    "\n  {}  {}",
+                    note.created_at.format("%Y-%m-%d %H:%M"),
+                    note.text
+                )
+            })
+            .collect();
+        let notes = if notes.is_empty() {
+            notes
+        } else {
+            format!("\nNotes:{}", notes)
+        };
+
+        let completed = self
+            .completed_at
+            .map(|at| format!("\nCompleted: {}", at.format("%Y-%m-%d %H:%M")))
+            .unwrap_or_default();
+
+        let uuid = self
+            .uuid
+            .as_ref()
+            .map(|uuid| format!("\nUUID: {}", uuid))
+            .unwrap_or_default();
 
         format!(
-            "Task #{}: {}\nPriority: {}\nStatus: {}\nDue: {}{}\nCreated: {}\nUpdated: {}",
+            "Task #{}: {}\nPriority: {}\nStatus: {}\nDue: {}{}{}{}{}{}{}{}\nCreated: {}\nUpdated: {}{}{}{}",
             id,
             self.title,
             priority,
             status,
             due,
+            start,
+            repeat,
+            parent,
             description,
+            project,
+            energy,
+            tags,
             self.created_at.format("%Y-%m-%d %H:%M"),
-            self.updated_at.format("%Y-%m-%d %H:%M")
+            self.updated_at.format("%Y-%m-%d %H:%M"),
+            completed,
+            uuid,
+            notes
         )
     }
 }
 
+/// A timed do-not-disturb session, optionally logged against a task.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FocusSession {
+    pub id: i32,
+    pub task_id: Option<i32>,
+    pub started_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    pub ended_at: Option<DateTime<Utc>>,
+}
+
+impl FocusSession {
+    pub fn is_active(&self) -> bool {
+        self.ended_at.is_none() && Utc::now() < self.ends_at
+    }
+
+    pub fn remaining(&self) -> Duration {
+        (self.ends_at - Utc::now()).max(Duration::zero())
+    }
+
+    /// Remaining time rounded up to whole minutes, e.g. `23m`.
+    pub fn remaining_text(&self) -> String {
+        let seconds = self.remaining().num_seconds();
+        format!("{}m", (seconds + 59) / 60)
+    }
+}
+
+/// Time spent on a task, from `todo start` to `todo stop`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimeEntry {
+    pub task_id: i32,
+    pub started_at: DateTime<Utc>,
+    /// `None` while the timer is running.
+    pub stopped_at: Option<DateTime<Utc>>,
+}
+
+impl TimeEntry {
+    /// Time covered so far; a running timer counts up to now.
+    pub fn duration(&self) -> Duration {
+        self.stopped_at.unwrap_or_else(Utc::now) - self.started_at
+    }
+}
+
+/// A saved starting point for tasks, from `todo template save`; `todo add
+/// --template` fills in what the command line leaves out from it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Template {
+    pub name: String,
+    pub title: String,
+    pub description: Option<String>,
+    /// `None` leaves the priority to `default_priority` or inheritance.
+    pub priority: Option<i32>,
+    pub tags: Vec<String>,
+    pub project: Option<String>,
+    /// When tasks from the template are due, as a date expression relative
+    /// to the day they are added, such as `in 3 days`.
+    pub due: Option<String>,
+}
+
+/// A longer-term outcome that tasks contribute to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Goal {
+    pub id: i32,
+    pub title: String,
+    pub target_date: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Goal {
+    /// Whole days until the target date; negative once it has passed.
+    pub fn days_left(&self) -> Option<i64> {
+        self.target_date
+            .map(|target| (target - Utc::now()).num_days())
+    }
+
+    pub fn time_left_text(&self) -> String {
+        match self.days_left() {
+            None => "no target date".to_string(),
+            Some(days) if days < 0 => format!("{} days overdue", -days),
+            Some(days) => format!("{} days left", days),
+        }
+    }
+}
+
+/// A link or file path attached to a task with `todo attach`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Attachment {
+    pub task_id: i32,
+    /// A URL, or the absolute path of a file.
+    pub target: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Attachment {
+    /// Whether `target` is a URL such as `https://...` or `mailto:...`
+    /// rather than a file path.
+    pub fn is_link(target: &str) -> bool {
+        let Some((scheme, _)) = target.split_once(':') else {
+            return false;
+        };
+        // A single letter is a Windows drive, as in `C:\design.pdf`
+        scheme.len() > 1
+            && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+    }
+}
+
+/// A timestamped comment appended to a task with `todo note`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Note {
+    pub task_id: i32,
+    pub text: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// One entry of a task's change history: what happened, and which part of
+/// the tool did it (`cli`, `maintenance`, `sync:obsidian`, `import:csv`...).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub task_id: i32,
+    pub actor: String,
+    pub action: String,
+    pub details: Option<String>,
+    pub changed_at: DateTime<Utc>,
+}
+
+/// A change to a task that `todo undo` can reverse, as stored in the
+/// operations log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Operation {
+    /// Moved to the trash; `subtasks` were moved up to its parent.
+    Delete { task_id: i32, subtasks: Vec<i32> },
+    /// Edited; `before` is the task as it was.
+    Update { before: Box<Task> },
+    /// Completed, creating `next_id` if the task repeats.
+    Complete { task_id: i32, next_id: Option<i32> },
+}
+
+impl Operation {
+    pub fn task_id(&self) -> i32 {
+        match self {
+            Operation::Delete { task_id, .. } | Operation::Complete { task_id, .. } => *task_id,
+            Operation::Update { before } => before.id.unwrap_or(0),
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Operation::Delete { .. } => "delete",
+            Operation::Update { .. } => "update",
+            Operation::Complete { .. } => "complete",
+        }
+    }
+}
+
+/// Aggregated local usage of one subcommand.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageStat {
+    pub command: String,
+    pub count: i64,
+    pub avg_ms: f64,
+    pub max_ms: i64,
+    pub last_used: DateTime<Utc>,
+}
+
+/// Marks the start of a matched term in `SearchHit` text.
+pub const MATCH_START: char = '\u{2}';
+/// Marks the end of a matched term in `SearchHit` text.
+pub const MATCH_END: char = '\u{3}';
+
+/// The part of a task a search matched in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SearchField {
+    Title,
+    Description,
+    Note,
+}
+
+impl SearchField {
+    pub fn name(self) -> &'static str {
+        match self {
+            SearchField::Title => "title",
+            SearchField::Description => "description",
+            SearchField::Note => "note",
+        }
+    }
+}
+
+/// A full-text search result. `title` and `excerpt` wrap matched terms in
+/// `MATCH_START` / `MATCH_END`; `excerpt` is a window of the description or
+/// note `field` names, and `None` when the match is in the title alone.
+#[derive(Debug, Clone)]
+pub struct SearchHit {
+    pub task: Task,
+    pub title: String,
+    pub field: SearchField,
+    pub excerpt: Option<String>,
+}
+
+/// Age cohorts of open tasks for `todo report aging`, youngest first.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AgeBucket {
+    Week,
+    Month,
+    Quarter,
+    Older,
+}
+
+impl AgeBucket {
+    pub const ALL: [AgeBucket; 4] = [
+        AgeBucket::Week,
+        AgeBucket::Month,
+        AgeBucket::Quarter,
+        AgeBucket::Older,
+    ];
+
+    /// Exclusive upper bound of the bucket in days since creation.
+    pub fn max_days(&self) -> Option<i64> {
+        match self {
+            AgeBucket::Week => Some(7),
+            AgeBucket::Month => Some(28),
+            AgeBucket::Quarter => Some(90),
+            AgeBucket::Older => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            AgeBucket::Week => "<1w",
+            AgeBucket::Month => "1–4w",
+            AgeBucket::Quarter => "1–3m",
+            AgeBucket::Older => ">3m",
+        }
+    }
+}
+
+/// Open tasks counted per `AgeBucket`, plus the longest-open ones.
+#[derive(Debug, Clone)]
+pub struct AgingReport {
+    pub buckets: Vec<(AgeBucket, usize)>,
+    pub oldest: Vec<Task>,
+}
+
+/// Completion ratio of a goal's linked tasks, rendered as a bar; in plain
+/// mode as words.
+pub fn progress_bar(done: usize, total: usize, width: usize) -> String {
+    let filled = (done * width).checked_div(total).unwrap_or(0);
+    let percent = (done * 100).checked_div(total).unwrap_or(0);
+    if crate::style::plain() {
+        return format!("{} of {} done ({}%)", done, total, percent);
+    }
+    format!(
+        "[{}{}] {}/{} ({}%)",
+        "#".repeat(filled),
+        "-".repeat(width - filled),
+        done,
+        total,
+        percent
+    )
+}
+
+/// Tracked time in whole minutes, e.g. `45m` or `2h 05m`.
+pub fn duration_text(duration: Duration) -> String {
+    let minutes = duration.num_minutes().max(0);
+    if minutes < 60 {
+        format!("{}m", minutes)
+    } else {
+        format!("{}h {:02}m", minutes / 60, minutes % 60)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use chrono::{Duration, Utc};
+    use chrono::{Duration, TimeZone, Utc};
 
     fn create_test_task() -> Task {
         Task::new(
@@ -134,27 +796,27 @@ mod tests {
     #[test]
     fn test_task_creation() {
         let task = create_test_task();
-        
+
         assert_eq!(task.title, "Test task");
         assert_eq!(task.description, Some("Test description".to_string()));
         assert_eq!(task.priority, 1);
-        assert_eq!(task.completed, false);
+        assert!(!task.completed);
         assert!(task.id.is_none());
     }
 
     #[test]
     fn test_priority_text() {
         let mut task = create_test_task();
-        
+
         task.priority = 0;
         assert_eq!(task.priority_text(), "LOW");
-        
+
         task.priority = 1;
         assert_eq!(task.priority_text(), "MEDIUM");
-        
+
         task.priority = 2;
         assert_eq!(task.priority_text(), "HIGH");
-        
+
         task.priority = 99;
         assert_eq!(task.priority_text(), "MEDIUM"); // Default case
     }
@@ -162,13 +824,13 @@ mod tests {
     #[test]
     fn test_due_date_text() {
         let mut task = create_test_task();
-        
+
         // With due date
         let due_date = Utc::now() + Duration::days(1);
         task.due_date = Some(due_date);
         let due_text = task.due_date_text();
         assert!(due_text.contains(&due_date.format("%Y-%m-%d").to_string()));
-        
+
         // Without due date
         task.due_date = None;
         assert_eq!(task.due_date_text(), "No due date");
@@ -177,65 +839,153 @@ mod tests {
     #[test]
     fn test_is_overdue() {
         let mut task = create_test_task();
-        
+
         // Future date - not overdue
         task.due_date = Some(Utc::now() + Duration::days(1));
         assert!(!task.is_overdue());
-        
+
         // Past date - overdue
         task.due_date = Some(Utc::now() - Duration::days(1));
         assert!(task.is_overdue());
-        
+
         // Completed task - not overdue even if past due
         task.completed = true;
         assert!(!task.is_overdue());
-        
+
         // No due date - not overdue
         task.due_date = None;
         task.completed = false;
         assert!(!task.is_overdue());
     }
 
+    #[test]
+    fn test_is_due_soon() {
+        let mut task = create_test_task();
+        let window = Duration::hours(48);
+
+        // Inside the window
+        task.due_date = Some(Utc::now() + Duration::hours(12));
+        assert!(task.is_due_soon(window));
+
+        // Beyond the window
+        task.due_date = Some(Utc::now() + Duration::days(5));
+        assert!(!task.is_due_soon(window));
+
+        // Overdue tasks are not "due soon"
+        task.due_date = Some(Utc::now() - Duration::hours(1));
+        assert!(!task.is_due_soon(window));
+
+        // Completed tasks are never due soon
+        task.due_date = Some(Utc::now() + Duration::hours(12));
+        task.completed = true;
+        assert!(!task.is_due_soon(window));
+
+        // No due date
+        task.completed = false;
+        task.due_date = None;
+        assert!(!task.is_due_soon(window));
+    }
+
     #[test]
     fn test_display_summary() {
         let mut task = create_test_task();
         task.id = Some(42);
-        
-        let summary = task.display_summary();
+
+        let summary = task.display_summary(Duration::hours(DEFAULT_DUE_SOON_HOURS));
         assert!(summary.contains("[42]"));
         assert!(summary.contains("Test task"));
         assert!(summary.contains("MEDIUM"));
+        assert!(!summary.contains('@'));
+
+        task.project = Some("work".to_string());
+        let summary = task.display_summary(Duration::hours(DEFAULT_DUE_SOON_HOURS));
+        assert!(summary.contains("@work"));
+        assert!(task.display_detailed(&[]).contains("Project: work"));
+    }
+
+    #[test]
+    fn test_age_text() {
+        let mut task = create_test_task();
+
+        task.created_at = Utc::now() - Duration::hours(5);
+        assert_eq!(task.age_text(), "5h");
+
+        task.created_at = Utc::now() - Duration::days(12);
+        assert_eq!(task.age_text(), "12d");
+    }
+
+    #[test]
+    fn test_tags_text() {
+        let mut task = create_test_task();
+        assert_eq!(task.tags_text(), "");
+
+        task.tags = vec!["home".to_string(), "stale".to_string()];
+        assert_eq!(task.tags_text(), "#home #stale");
+        assert!(task.display_detailed(&[]).contains("Tags: #home #stale"));
     }
 
     #[test]
     fn test_display_detailed() {
         let mut task = create_test_task();
         task.id = Some(42);
-        
-        let detailed = task.display_detailed();
+
+        let detailed = task.display_detailed(&[]);
         assert!(detailed.contains("Task #42:"));
         assert!(detailed.contains("Test task"));
         assert!(detailed.contains("Test description"));
         assert!(detailed.contains("MEDIUM"));
+        assert!(!detailed.contains("Notes:"));
+        assert!(!detailed.contains("Completed:"));
+
+        let note = Note {
+            task_id: 42,
+            text: "Spoke to vendor".to_string(),
+            created_at: Utc::now(),
+        };
+        let detailed = task.display_detailed(&[note]);
+        assert!(detailed.contains("\nNotes:\n  "));
+        assert!(detailed.ends_with("  Spoke to vendor"));
+    }
+
+    #[test]
+    fn test_changes_from() {
+        let before = Task::new("Pay rent".to_string(), None, None, 1);
+        let mut after = before.clone();
+        assert!(after.changes_from(&before).is_empty());
+
+        after.title = "Pay rent now".to_string();
+        after.due_date = Some(Utc.with_ymd_and_hms(2030, 1, 10, 0, 0, 0).unwrap());
+        after.priority = 2;
+        after.tags = vec!["home".to_string()];
+        after.someday = true;
+        assert_eq!(
+            after.changes_from(&before),
+            vec![
+                "title \"Pay rent\" → \"Pay rent now\"",
+                "due none → 2030-01-10",
+                "priority medium → high",
+                "tags +#home",
+            ]
+        );
     }
 
     #[test]
     fn test_task_with_id() {
         let mut task = create_test_task();
         task.id = Some(123);
-        
+
         assert_eq!(task.id, Some(123));
     }
 
     #[test]
     fn test_task_serialization() {
         let task = create_test_task();
-        
+
         // Test serialization
         let json = serde_json::to_string(&task).unwrap();
         assert!(json.contains("Test task"));
         assert!(json.contains("Test description"));
-        
+
         // Test deserialization
         let deserialized_task: Task = serde_json::from_str(&json).unwrap();
         assert_eq!(deserialized_task.title, task.title);
@@ -245,17 +995,12 @@ mod tests {
 
     #[test]
     fn test_task_without_description() {
-        let task = Task::new(
-            "Simple task".to_string(),
-            None,
-            None,
-            0,
-        );
-        
+        let task = Task::new("Simple task".to_string(), None, None, 0);
+
         assert_eq!(task.title, "Simple task");
         assert_eq!(task.description, None);
         assert_eq!(task.priority, 0);
-        assert_eq!(task.completed, false);
+        assert!(!task.completed);
     }
 
     #[test]
@@ -266,7 +1011,7 @@ mod tests {
             Some(Utc::now() + Duration::hours(1)),
             2,
         );
-        
+
         assert_eq!(task.priority_text(), "HIGH");
         assert_eq!(task.title, "Urgent task");
         assert!(task.due_date.is_some());
@@ -276,16 +1021,80 @@ mod tests {
     fn test_completed_task_status() {
         let mut task = create_test_task();
         task.completed = true;
-        
+
         let status = task.status_text();
         assert!(status.to_string().contains("COMPLETED"));
     }
 
+    #[test]
+    fn test_focus_session_remaining() {
+        let mut session = FocusSession {
+            id: 1,
+            task_id: None,
+            started_at: Utc::now(),
+            ends_at: Utc::now() + Duration::minutes(25),
+            ended_at: None,
+        };
+        assert!(session.is_active());
+        assert_eq!(session.remaining_text(), "25m");
+
+        session.ended_at = Some(Utc::now());
+        assert!(!session.is_active());
+
+        session.ended_at = None;
+        session.ends_at = Utc::now() - Duration::minutes(1);
+        assert!(!session.is_active());
+        assert_eq!(session.remaining_text(), "0m");
+    }
+
     #[test]
     fn test_pending_task_status() {
         let task = create_test_task();
-        
+
         let status = task.status_text();
         assert!(status.to_string().contains("PENDING"));
     }
+
+    #[test]
+    fn test_progress_bar() {
+        assert_eq!(progress_bar(0, 0, 10), "[----------] 0/0 (0%)");
+        assert_eq!(progress_bar(3, 6, 10), "[#####-----] 3/6 (50%)");
+        assert_eq!(progress_bar(4, 4, 4), "[####] 4/4 (100%)");
+    }
+
+    #[test]
+    fn test_goal_time_left() {
+        let mut goal = Goal {
+            id: 1,
+            title: "Run a 10k".to_string(),
+            target_date: None,
+            created_at: Utc::now(),
+        };
+        assert_eq!(goal.time_left_text(), "no target date");
+
+        goal.target_date = Some(Utc::now() + Duration::days(10) + Duration::hours(1));
+        assert_eq!(goal.time_left_text(), "10 days left");
+
+        goal.target_date = Some(Utc::now() - Duration::days(3) - Duration::hours(1));
+        assert_eq!(goal.time_left_text(), "3 days overdue");
+    }
+
+    #[test]
+    fn test_attachment_is_link() {
+        for link in [
+            "https://example.com/spec",
+            "mailto:ana@example.com",
+            "obsidian://open",
+        ] {
+            assert!(Attachment::is_link(link), "{}", link);
+        }
+        for path in [
+            "./design.pdf",
+            "/home/me/notes.md",
+            "C:\\design.pdf",
+            "a b:c",
+        ] {
+            assert!(!Attachment::is_link(path), "{}", path);
+        }
+    }
 }
@@ -0,0 +1,77 @@
+//! Desktop notifications for `todo notify`, sent through the platform's
+//! own tool: `notify-send` (libnotify) on Linux and the BSDs, `osascript`
+//! on macOS. There is no daemon; `todo notify` is meant to run every few
+//! minutes from cron, a systemd timer or launchd.
+
+use anyhow::{Context, Result};
+use chrono::Duration;
+use std::process::Command;
+
+use crate::Priority;
+
+/// Minutes between digests of low, medium and high priority reminders,
+/// from the `digest_*_minutes` keys; 0 sends each reminder as it comes.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DigestWindows {
+    pub low: i64,
+    pub medium: i64,
+    pub high: i64,
+}
+
+impl DigestWindows {
+    /// The digest window of `priority`, if its reminders are batched.
+    pub fn of(&self, priority: &Priority) -> Option<Duration> {
+        let minutes = match priority {
+            Priority::Low => self.low,
+            Priority::Medium => self.medium,
+            Priority::High => self.high,
+        };
+        (minutes > 0).then(|| Duration::minutes(minutes))
+    }
+}
+
+/// Show a notification with `summary` as its heading.
+pub fn send(summary: &str, body: &str) -> Result<()> {
+    let mut command = command(summary, body)?;
+    let status = command
+        .status()
+        .with_context(|| format!("Cannot run {:?}", command.get_program()))?;
+    if !status.success() {
+        return Err(anyhow::anyhow!(
+            "{:?} exited with {}",
+            command.get_program(),
+            status
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+fn command(summary: &str, body: &str) -> Result<Command> {
+    let mut command = Command::new("osascript");
+    command.arg("-e").arg(format!(
+        "display notification {} with title {}",
+        applescript_string(body),
+        applescript_string(summary)
+    ));
+    Ok(command)
+}
+
+#[cfg(target_os = "macos")]
+fn applescript_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn command(summary: &str, body: &str) -> Result<Command> {
+    let mut command = Command::new("notify-send");
+    command.args(["--app-name", "todo", summary, body]);
+    Ok(command)
+}
+
+#[cfg(not(unix))]
+fn command(_summary: &str, _body: &str) -> Result<Command> {
+    Err(anyhow::anyhow!(
+        "Desktop notifications are not supported on this platform; use `todo notify --print`"
+    ))
+}
@@ -0,0 +1,624 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::db::Database;
+use crate::models::Task;
+use crate::progress::Progress;
+
+/// Source name used when linking tasks to vault lines.
+pub const LINK_SOURCE: &str = "obsidian";
+/// Actor recorded in the task history for changes made by a sync.
+pub const ACTOR: &str = "sync:obsidian";
+
+/// A `- [ ]` checklist item found in a vault note.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VaultTask {
+    /// Path relative to the vault root, with `/` separators.
+    pub file: String,
+    /// 1-based line number.
+    pub line: usize,
+    pub title: String,
+    pub done: bool,
+    pub due_date: Option<DateTime<Utc>>,
+    pub priority: i32,
+}
+
+impl VaultTask {
+    pub fn link_id(&self) -> String {
+        format!("{}:{}", self.file, self.line)
+    }
+
+    pub fn to_task(&self) -> Task {
+        let mut task = Task::new(self.title.clone(), None, self.due_date, self.priority);
+        task.tags.push(LINK_SOURCE.to_string());
+        task
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SyncReport {
+    pub imported: usize,
+    /// New checklist items linked to a task that was already pending.
+    pub linked: usize,
+    pub completed_locally: usize,
+    pub checked_off: usize,
+}
+
+/// Parse a single Markdown line using the Tasks plugin emoji conventions.
+/// Returns `(title, done, due_date, priority)`.
+pub fn parse_line(line: &str) -> Option<(String, bool, Option<DateTime<Utc>>, i32)> {
+    let trimmed = line.trim_start();
+    let rest = trimmed
+        .strip_prefix("- ")
+        .or_else(|| trimmed.strip_prefix("* "))?;
+    let (done, body) = if let Some(body) = rest.strip_prefix("[ ] ") {
+        (false, body)
+    } else if let Some(body) = rest
+        .strip_prefix("[x] ")
+        .or_else(|| rest.strip_prefix("[X] "))
+    {
+        (true, body)
+    } else {
+        return None;
+    };
+
+    let mut title_words = Vec::new();
+    let mut due_date = None;
+    let mut priority = 1;
+    let mut words = body.split_whitespace();
+
+    while let Some(word) = words.next() {
+        match word {
+            "📅" => {
+                due_date = words
+                    .next()
+                    .and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok())
+                    .map(crate::zone::start_of);
+            }
+            // Other dated fields: done, scheduled, start, created
+            "✅" | "⏳" | "🛫" | "➕" => {
+                words.next();
+            }
+            "🔺" | "⏫" => priority = 2,
+            "🔼" => priority = 1,
+            "🔽" | "⏬" => priority = 0,
+            _ => title_words.push(word),
+        }
+    }
+
+    let title = title_words.join(" ");
+    if title.is_empty() {
+        return None;
+    }
+    Some((title, done, due_date, priority))
+}
+
+/// Find every checklist item in the Markdown files under `vault`.
+pub fn scan_vault(vault: &Path, progress: &Progress) -> Result<Vec<VaultTask>> {
+    let mut files = Vec::new();
+    collect_markdown_files(vault, &mut files)?;
+    files.sort();
+
+    let mut tasks = Vec::new();
+    for path in files {
+        progress.inc(1);
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let file = relative_name(vault, &path);
+
+        for (index, line) in content.lines().enumerate() {
+            if let Some((title, done, due_date, priority)) = parse_line(line) {
+                tasks.push(VaultTask {
+                    file: file.clone(),
+                    line: index + 1,
+                    title,
+                    done,
+                    due_date,
+                    priority,
+                });
+            }
+        }
+    }
+    Ok(tasks)
+}
+
+fn collect_markdown_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir).with_context(|| format!("Cannot read {}", dir.display()))? {
+        let path = entry?.path();
+        let hidden = path
+            .file_name()
+            .map(|name| name.to_string_lossy().starts_with('.'))
+            .unwrap_or(false);
+        if hidden {
+            continue;
+        }
+        if path.is_dir() {
+            collect_markdown_files(&path, files)?;
+        } else if path.extension().map(|ext| ext == "md").unwrap_or(false) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn relative_name(vault: &Path, path: &Path) -> String {
+    path.strip_prefix(vault)
+        .unwrap_or(path)
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Mark the item at `file:line` as done. If the note changed and the line no
+/// longer holds the task, the first unchecked item with the same title is
+/// used instead. Returns false when the item cannot be found.
+pub fn check_off(vault: &Path, file: &str, line: usize, title: &str) -> Result<bool> {
+    let path = vault.join(file);
+    if !path.is_file() {
+        return Ok(false);
+    }
+    // Held across the read and the write so a concurrent sync can't undo
+    // this check-off
+    let _lock = crate::files::FileLock::acquire(&path)?;
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(_) => return Ok(false),
+    };
+    // Each line keeps its own ending, so a CRLF note stays CRLF
+    let mut lines: Vec<(String, &str)> = content
+        .split_inclusive('\n')
+        .map(|l| {
+            let text = l.trim_end_matches('\n').trim_end_matches('\r');
+            (text.to_string(), &l[text.len()..])
+        })
+        .collect();
+
+    let is_match =
+        |candidate: &str| matches!(parse_line(candidate), Some((ref t, false, _, _)) if t == title);
+    let index = if line > 0 && line <= lines.len() && is_match(&lines[line - 1].0) {
+        Some(line - 1)
+    } else {
+        lines.iter().position(|(l, _)| is_match(l))
+    };
+
+    let Some(index) = index else {
+        return Ok(false);
+    };
+
+    let checked = lines[index].0.replacen("[ ]", "[x]", 1);
+    lines[index].0 = format!("{} ✅ {}", checked, Utc::now().format("%Y-%m-%d"));
+
+    let output: String = lines
+        .iter()
+        .flat_map(|(text, ending)| [text.as_str(), ending])
+        .collect();
+    crate::files::write_atomic(&path, output.as_bytes())?;
+    Ok(true)
+}
+
+/// One change a sync makes, found by `plan` and made by `apply`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    /// Add an open checklist item as a new task, or link it to the same
+    /// task when one is pending by then.
+    Import(VaultTask),
+    /// Link an open checklist item to the same task, already pending.
+    Link { task_id: i32, item: VaultTask },
+    /// Follow an item that moved to another line of its note.
+    Follow {
+        task_id: i32,
+        from: String,
+        to: String,
+    },
+    /// Complete the task of an item checked off in the vault.
+    Complete { task_id: i32, item: VaultTask },
+    /// Check off the item of a task completed here.
+    CheckOff {
+        task_id: i32,
+        link_id: String,
+        title: String,
+    },
+}
+
+impl Change {
+    /// One line of a plan, `+` for what is added and `~` for what changes.
+    pub fn describe(&self) -> String {
+        match self {
+            Change::Import(item) => {
+                format!("+ import     \"{}\" from {}", item.title, item.link_id())
+            }
+            Change::Link { task_id, item } => format!(
+                "~ link       task {} to \"{}\" in {}",
+                task_id,
+                item.title,
+                item.link_id()
+            ),
+            Change::Follow { task_id, from, to } => {
+                format!("~ follow     task {} from {} to {}", task_id, from, to)
+            }
+            Change::Complete { task_id, item } => format!(
+                "~ complete   task {} \"{}\", checked off in {}",
+                task_id,
+                item.title,
+                item.link_id()
+            ),
+            Change::CheckOff {
+                task_id,
+                link_id,
+                title,
+            } => format!(
+                "~ check off  \"{}\" in {}, task {} is completed",
+                title, link_id, task_id
+            ),
+        }
+    }
+}
+
+/// The counts of a plan, e.g. `2 to import, 1 to check off in the vault`.
+pub fn summarize(changes: &[Change]) -> String {
+    let count = |wanted: fn(&Change) -> bool| changes.iter().filter(|c| wanted(c)).count();
+    let counts = [
+        (count(|c| matches!(c, Change::Import(_))), "to import"),
+        (count(|c| matches!(c, Change::Link { .. })), "to link"),
+        (count(|c| matches!(c, Change::Follow { .. })), "moved"),
+        (
+            count(|c| matches!(c, Change::Complete { .. })),
+            "to complete here",
+        ),
+        (
+            count(|c| matches!(c, Change::CheckOff { .. })),
+            "to check off in the vault",
+        ),
+    ];
+    let parts: Vec<String> = counts
+        .iter()
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, what)| format!("{} {}", count, what))
+        .collect();
+    if parts.is_empty() {
+        "no changes".to_string()
+    } else {
+        parts.join(", ")
+    }
+}
+
+/// Two-way sync between the vault and the database:
+/// locally completed tasks are checked off in their notes, items checked
+/// off in the vault are completed locally, and new open items are imported.
+pub fn sync(db: &Database, vault: &Path, progress: &Progress) -> Result<SyncReport> {
+    let changes = plan(db, vault, progress)?;
+    apply(db, vault, &changes, progress)
+}
+
+/// What `sync` would change, worked out without changing anything.
+pub fn plan(db: &Database, vault: &Path, progress: &Progress) -> Result<Vec<Change>> {
+    progress.set_label("Scanning notes");
+    let items = scan_vault(vault, progress)?;
+    let mut links = db.get_task_links(LINK_SOURCE)?;
+    let mut changes = Vec::new();
+
+    progress.set_label("Checking off completed tasks");
+    for (task_id, link_id) in &links {
+        progress.inc(1);
+        let Some(task) = db.get_task_by_id(*task_id)? else {
+            continue;
+        };
+        let Some((file, _)) = split_link_id(link_id) else {
+            continue;
+        };
+        // `check_off` falls back to another open item with the same title
+        let open = items
+            .iter()
+            .any(|item| !item.done && item.file == file && item.title == task.title);
+        if task.completed && open {
+            changes.push(Change::CheckOff {
+                task_id: *task_id,
+                link_id: link_id.clone(),
+                title: task.title.clone(),
+            });
+        }
+    }
+
+    progress.set_label("Comparing checklist items");
+    for item in &items {
+        crate::shutdown::check()?;
+        progress.inc(1);
+        let linked = match links.iter().find(|(_, link_id)| *link_id == item.link_id()) {
+            Some((id, _)) => Some(*id),
+            None => match moved_item(db, item, &items, &links)? {
+                Some((id, from)) => {
+                    for link in links.iter_mut().filter(|(_, link_id)| *link_id == from) {
+                        link.1 = item.link_id();
+                    }
+                    changes.push(Change::Follow {
+                        task_id: id,
+                        from,
+                        to: item.link_id(),
+                    });
+                    Some(id)
+                }
+                None => None,
+            },
+        };
+
+        match linked {
+            Some(id) if item.done => {
+                let pending = db
+                    .get_task_by_id(id)?
+                    .map(|task| !task.completed)
+                    .unwrap_or(false);
+                if pending {
+                    changes.push(Change::Complete {
+                        task_id: id,
+                        item: item.clone(),
+                    });
+                }
+            }
+            // The same task already pending is linked, not duplicated
+            None if !item.done => match db.find_pending_duplicate(&item.to_task())? {
+                Some(duplicate) => changes.push(Change::Link {
+                    task_id: duplicate.id.unwrap(),
+                    item: item.clone(),
+                }),
+                None => changes.push(Change::Import(item.clone())),
+            },
+            _ => {}
+        }
+    }
+
+    Ok(changes)
+}
+
+/// Make the `changes` of a plan.
+pub fn apply(
+    db: &Database,
+    vault: &Path,
+    changes: &[Change],
+    progress: &Progress,
+) -> Result<SyncReport> {
+    db.with_actor(ACTOR, || apply_changes(db, vault, changes, progress))
+}
+
+fn apply_changes(
+    db: &Database,
+    vault: &Path,
+    changes: &[Change],
+    progress: &Progress,
+) -> Result<SyncReport> {
+    let mut report = SyncReport::default();
+    progress.set_label("Applying changes");
+    for change in changes {
+        crate::shutdown::check()?;
+        progress.inc(1);
+        match change {
+            Change::CheckOff { link_id, title, .. } => {
+                let Some((file, line)) = split_link_id(link_id) else {
+                    continue;
+                };
+                if check_off(vault, file, line, title)? {
+                    report.checked_off += 1;
+                }
+            }
+            Change::Follow { task_id, from, to } => {
+                db.add_task_link(*task_id, LINK_SOURCE, to)?;
+                db.remove_task_link(LINK_SOURCE, from)?;
+            }
+            Change::Complete { task_id, .. } => {
+                db.complete_task(*task_id)?;
+                report.completed_locally += 1;
+            }
+            Change::Link { task_id, item } => {
+                db.add_task_link(*task_id, LINK_SOURCE, &item.link_id())?;
+                report.linked += 1;
+            }
+            // An item imported earlier in the plan may be pending by now
+            Change::Import(item) => {
+                let task = item.to_task();
+                match db.find_pending_duplicate(&task)? {
+                    Some(duplicate) => {
+                        db.add_task_link(duplicate.id.unwrap(), LINK_SOURCE, &item.link_id())?;
+                        report.linked += 1;
+                    }
+                    None => {
+                        let id = db.add_task(&task)?;
+                        db.add_task_link(id, LINK_SOURCE, &item.link_id())?;
+                        report.imported += 1;
+                    }
+                }
+            }
+        }
+    }
+    Ok(report)
+}
+
+/// Items keep their link when lines above them are inserted or removed: a
+/// linked task from the same file with the same title, whose old line no
+/// longer holds it, moves to the item's new line. Returns the task and the
+/// link it had.
+fn moved_item(
+    db: &Database,
+    item: &VaultTask,
+    items: &[VaultTask],
+    links: &[(i32, String)],
+) -> Result<Option<(i32, String)>> {
+    for (task_id, link_id) in links {
+        let same_file = split_link_id(link_id)
+            .map(|(file, _)| file == item.file)
+            .unwrap_or(false);
+        if !same_file {
+            continue;
+        }
+        let Some(task) = db.get_task_by_id(*task_id)? else {
+            continue;
+        };
+        let still_in_place = items
+            .iter()
+            .any(|other| other.link_id() == *link_id && other.title == task.title);
+        if task.title == item.title && !still_in_place {
+            return Ok(Some((*task_id, link_id.clone())));
+        }
+    }
+    Ok(None)
+}
+
+fn split_link_id(link_id: &str) -> Option<(&str, usize)> {
+    let (file, line) = link_id.rsplit_once(':')?;
+    Some((file, line.parse().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::create_test_db;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse_line() {
+        let (title, done, due, priority) =
+            parse_line("- [ ] Renew passport ⏫ 📅 2099-03-01").unwrap();
+        assert_eq!(title, "Renew passport");
+        assert!(!done);
+        assert_eq!(priority, 2);
+        assert_eq!(due.unwrap().format("%Y-%m-%d").to_string(), "2099-03-01");
+
+        let (title, done, _, priority) =
+            parse_line("  * [x] Water plants 🔽 ✅ 2024-01-02").unwrap();
+        assert_eq!(title, "Water plants");
+        assert!(done);
+        assert_eq!(priority, 0);
+
+        assert!(parse_line("Just a sentence").is_none());
+        assert!(parse_line("- plain bullet").is_none());
+        assert!(parse_line("- [ ] ").is_none());
+    }
+
+    #[test]
+    fn test_scan_vault_skips_hidden_dirs() {
+        let vault = tempdir().unwrap();
+        fs::create_dir(vault.path().join("projects")).unwrap();
+        fs::create_dir(vault.path().join(".obsidian")).unwrap();
+        fs::write(
+            vault.path().join("projects/home.md"),
+            "# Home\n- [ ] Fix sink\n",
+        )
+        .unwrap();
+        fs::write(vault.path().join(".obsidian/x.md"), "- [ ] Hidden\n").unwrap();
+        fs::write(vault.path().join("notes.txt"), "- [ ] Not markdown\n").unwrap();
+
+        let tasks = scan_vault(vault.path(), &Progress::hidden()).unwrap();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].link_id(), "projects/home.md:2");
+    }
+
+    #[test]
+    fn test_sync_round_trip() {
+        let (db, _temp_file) = create_test_db();
+        let vault = tempdir().unwrap();
+        let note = vault.path().join("todo.md");
+        fs::write(&note, "- [ ] Call bank\n- [ ] Buy stamps 🔼\n").unwrap();
+
+        let report = sync(&db, vault.path(), &Progress::hidden()).unwrap();
+        assert_eq!(report.imported, 2);
+
+        // Running again imports nothing new
+        let report = sync(&db, vault.path(), &Progress::hidden()).unwrap();
+        assert_eq!(report.imported, 0);
+
+        // Completing locally checks the item off in the note
+        let id = db
+            .find_linked_task(LINK_SOURCE, "todo.md:1")
+            .unwrap()
+            .unwrap();
+        db.complete_task(id).unwrap();
+        let report = sync(&db, vault.path(), &Progress::hidden()).unwrap();
+        assert_eq!(report.checked_off, 1);
+        let content = fs::read_to_string(&note).unwrap();
+        assert!(content.starts_with("- [x] Call bank ✅"));
+        assert!(content.contains("- [ ] Buy stamps"));
+    }
+
+    #[test]
+    fn test_plan_changes_nothing_until_applied() {
+        let (db, _temp_file) = create_test_db();
+        let vault = tempdir().unwrap();
+        let note = vault.path().join("todo.md");
+        fs::write(&note, "- [ ] Call bank\n- [ ] Buy stamps\n").unwrap();
+        sync(&db, vault.path(), &Progress::hidden()).unwrap();
+        let id = db
+            .find_linked_task(LINK_SOURCE, "todo.md:1")
+            .unwrap()
+            .unwrap();
+        db.complete_task(id).unwrap();
+        fs::write(
+            &note,
+            "- [ ] Call bank\n\n- [x] Buy stamps\n- [ ] Post card\n",
+        )
+        .unwrap();
+
+        let changes = plan(&db, vault.path(), &Progress::hidden()).unwrap();
+        assert_eq!(
+            summarize(&changes),
+            "1 to import, 1 moved, 1 to complete here, 1 to check off in the vault"
+        );
+        assert!(changes[0]
+            .describe()
+            .starts_with("~ check off  \"Call bank\""));
+        assert_eq!(db.get_tasks(&Default::default()).unwrap().len(), 1);
+        assert!(!fs::read_to_string(&note).unwrap().contains("[x] Call bank"));
+
+        let report = apply(&db, vault.path(), &changes, &Progress::hidden()).unwrap();
+        assert_eq!(
+            (
+                report.imported,
+                report.completed_locally,
+                report.checked_off
+            ),
+            (1, 1, 1)
+        );
+        assert!(fs::read_to_string(&note).unwrap().contains("[x] Call bank"));
+        let changes = plan(&db, vault.path(), &Progress::hidden()).unwrap();
+        assert_eq!(summarize(&changes), "no changes");
+    }
+
+    #[test]
+    fn test_sync_completes_checked_items_and_follows_moves() {
+        let (db, _temp_file) = create_test_db();
+        let vault = tempdir().unwrap();
+        let note = vault.path().join("todo.md");
+        fs::write(&note, "- [ ] Buy stamps\n").unwrap();
+        sync(&db, vault.path(), &Progress::hidden()).unwrap();
+
+        // A heading is inserted above and the item is checked off in Obsidian
+        fs::write(&note, "# Errands\n- [x] Buy stamps\n").unwrap();
+        let report = sync(&db, vault.path(), &Progress::hidden()).unwrap();
+        assert_eq!(report.imported, 0);
+        assert_eq!(report.completed_locally, 1);
+
+        let id = db
+            .find_linked_task(LINK_SOURCE, "todo.md:2")
+            .unwrap()
+            .unwrap();
+        assert!(db.get_task_by_id(id).unwrap().unwrap().completed);
+    }
+
+    #[test]
+    fn test_check_off_keeps_line_endings() {
+        let vault = tempdir().unwrap();
+        let note = vault.path().join("todo.md");
+        fs::write(&note, "# Errands\r\n- [ ] Buy stamps\r\n- [ ] Post letter").unwrap();
+
+        assert!(check_off(vault.path(), "todo.md", 2, "Buy stamps").unwrap());
+        let today = Utc::now().format("%Y-%m-%d");
+        assert_eq!(
+            fs::read_to_string(&note).unwrap(),
+            format!(
+                "# Errands\r\n- [x] Buy stamps ✅ {}\r\n- [ ] Post letter",
+                today
+            )
+        );
+        assert!(!check_off(vault.path(), "todo.md", 2, "Walk dog").unwrap());
+    }
+}
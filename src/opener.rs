@@ -0,0 +1,53 @@
+//! Opening a task's attachments for `todo open`, through the platform's
+//! own tool: `xdg-open` on Linux and the BSDs, `open` on macOS and the
+//! shell's URL handler on Windows. Each picks the application the desktop
+//! has set for the link or file type.
+
+use anyhow::{Context, Result};
+use std::process::Command;
+
+/// Open `target`, a URL or a file path, in its default application.
+pub fn open(target: &str) -> Result<()> {
+    let mut command = command(target)?;
+    let status = command
+        .status()
+        .with_context(|| format!("Cannot run {:?}", command.get_program()))?;
+    if !status.success() {
+        return Err(anyhow::anyhow!(
+            "{:?} exited with {} opening {}",
+            command.get_program(),
+            status,
+            target
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+fn command(target: &str) -> Result<Command> {
+    let mut command = Command::new("open");
+    command.arg(target);
+    Ok(command)
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn command(target: &str) -> Result<Command> {
+    let mut command = Command::new("xdg-open");
+    command.arg(target);
+    Ok(command)
+}
+
+#[cfg(windows)]
+fn command(target: &str) -> Result<Command> {
+    // What `start` does, without cmd's quoting rules
+    let mut command = Command::new("rundll32");
+    command.arg("url.dll,FileProtocolHandler").arg(target);
+    Ok(command)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn command(_target: &str) -> Result<Command> {
+    Err(anyhow::anyhow!(
+        "Opening attachments is not supported on this platform; `todo show` lists them"
+    ))
+}
@@ -0,0 +1,144 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::Path;
+
+/// Hint appended to every access error.
+const OVERRIDE_HINT: &str = "Pass --db PATH (or set `db` in the config) to use another database.";
+
+/// Check that the database at `path` can be used by the current user before
+/// SQLite gets a chance to fail with a less helpful message, and create it
+/// readable by its owner only if it doesn't exist yet.
+pub fn prepare_database(path: &Path) -> Result<()> {
+    match fs::metadata(path) {
+        Ok(metadata) => check_existing(path, &metadata),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => create_private(path),
+        Err(e) => {
+            Err(e).with_context(|| format!("Cannot access {}. {}", path.display(), OVERRIDE_HINT))
+        }
+    }
+}
+
+fn check_existing(path: &Path, metadata: &fs::Metadata) -> Result<()> {
+    if metadata.is_dir() {
+        return Err(anyhow::anyhow!(
+            "{} is a directory, not a task database. {}",
+            path.display(),
+            OVERRIDE_HINT
+        ));
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        // Also catches `sudo todo ...`, which would otherwise leave root-owned
+        // journal files next to the user's database.
+        let euid = current_uid();
+        if metadata.uid() != euid {
+            return Err(anyhow::anyhow!(
+                "{} is owned by uid {}, but todo is running as uid {}{}. Run todo as the owner, or {}",
+                path.display(),
+                metadata.uid(),
+                euid,
+                if euid == 0 { " (root)" } else { "" },
+                lowercase_first(OVERRIDE_HINT)
+            ));
+        }
+    }
+
+    if metadata.permissions().readonly() {
+        return Err(anyhow::anyhow!(
+            "{} is read-only. Make it writable with `chmod u+w {}`, or {}",
+            path.display(),
+            path.display(),
+            lowercase_first(OVERRIDE_HINT)
+        ));
+    }
+    Ok(())
+}
+
+/// Create an empty database file (a valid SQLite database) with 0600
+/// permissions, so task titles aren't readable by other local users.
+fn create_private(path: &Path) -> Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    match options.open(path) {
+        Ok(_) => Ok(()),
+        // Another invocation created it first
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => Ok(()),
+        Err(e) => Err(e).with_context(|| {
+            let dir = path
+                .parent()
+                .map(|dir| dir.display().to_string())
+                .unwrap_or_default();
+            format!(
+                "Cannot create the task database in '{}'. {}",
+                dir, OVERRIDE_HINT
+            )
+        }),
+    }
+}
+
+#[cfg(unix)]
+fn current_uid() -> u32 {
+    // SAFETY: geteuid has no preconditions and cannot fail.
+    unsafe { libc::geteuid() }
+}
+
+fn lowercase_first(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_lowercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_new_database_is_private() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("tasks.db");
+        prepare_database(&path).unwrap();
+        assert!(path.exists());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        // Existing databases pass the checks
+        prepare_database(&path).unwrap();
+    }
+
+    #[test]
+    fn test_unusable_paths() {
+        let dir = tempdir().unwrap();
+        let error = prepare_database(dir.path()).unwrap_err().to_string();
+        assert!(error.contains("is a directory"));
+        assert!(error.contains("--db PATH"));
+
+        let missing = dir.path().join("nope").join("tasks.db");
+        let error = prepare_database(&missing).unwrap_err().to_string();
+        assert!(error.contains("Cannot create the task database"));
+
+        let path = dir.path().join("locked.db");
+        fs::write(&path, b"").unwrap();
+        let mut permissions = fs::metadata(&path).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&path, permissions).unwrap();
+        let error = prepare_database(&path).unwrap_err().to_string();
+        assert!(error.contains("is read-only"));
+    }
+}
@@ -0,0 +1,242 @@
+//! `todo pick`: a fuzzy finder over tasks, so commands can be run without
+//! remembering IDs. Typing narrows the list to tasks whose ID, title,
+//! project and tags contain the typed characters in order; the arrow keys
+//! (or Ctrl-P/Ctrl-N) move, Enter picks and Esc cancels.
+
+use anyhow::Result;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+
+use crate::models::Task;
+
+/// How well `query` fuzzy-matches `text`, or `None` when it doesn't: every
+/// character of the query must appear in the text in order, ignoring case
+/// and spaces. Characters that follow the previous match or start a word
+/// score extra, so "wr" prefers "Write report" over "draw graph".
+pub fn fuzzy_score(query: &str, text: &str) -> Option<i64> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut next = 0;
+    let mut previous = None;
+    for wanted in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = (next..text.len()).find(|&i| text[i] == wanted)?;
+        score += 1;
+        if found == 0 || !text[found - 1].is_alphanumeric() {
+            score += 3;
+        }
+        if previous == Some(found.wrapping_sub(1)) {
+            score += 2;
+        }
+        previous = Some(found);
+        next = found + 1;
+    }
+    Some(score)
+}
+
+/// What the query is matched against: the ID, title, project and tags.
+fn haystack(task: &Task) -> String {
+    let mut text = format!("{} {}", task.id.unwrap_or(0), task.title);
+    if let Some(project) = &task.project {
+        text.push_str(&format!(" +{}", project));
+    }
+    for tag in &task.tags {
+        text.push_str(&format!(" #{}", tag));
+    }
+    text
+}
+
+pub struct Picker {
+    prompt: String,
+    tasks: Vec<Task>,
+    query: String,
+    /// Indexes into `tasks` of the tasks matching `query`, best first.
+    matches: Vec<usize>,
+    list: ListState,
+    done: bool,
+    picked: Option<i32>,
+}
+
+impl Picker {
+    pub fn new(prompt: &str, tasks: Vec<Task>) -> Self {
+        let mut picker = Self {
+            prompt: prompt.to_string(),
+            tasks,
+            query: String::new(),
+            matches: Vec::new(),
+            list: ListState::default(),
+            done: false,
+            picked: None,
+        };
+        picker.refilter();
+        picker
+    }
+
+    fn refilter(&mut self) {
+        let mut scored: Vec<(i64, usize)> = self
+            .tasks
+            .iter()
+            .enumerate()
+            .filter_map(|(index, task)| Some((fuzzy_score(&self.query, &haystack(task))?, index)))
+            .collect();
+        // Stable, so equally good matches keep the list order
+        scored.sort_by_key(|&(score, _)| std::cmp::Reverse(score));
+        self.matches = scored.into_iter().map(|(_, index)| index).collect();
+        self.list.select((!self.matches.is_empty()).then_some(0));
+    }
+
+    fn selected(&self) -> Option<&Task> {
+        let index = *self.matches.get(self.list.selected()?)?;
+        self.tasks.get(index)
+    }
+
+    pub fn on_key(&mut self, key: KeyEvent) {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => self.done = true,
+            KeyCode::Char('c' | 'g') if ctrl => self.done = true,
+            KeyCode::Enter => {
+                self.picked = self.selected().and_then(|task| task.id);
+                self.done = self.picked.is_some();
+            }
+            KeyCode::Up => self.list.select_previous(),
+            KeyCode::Char('p' | 'k') if ctrl => self.list.select_previous(),
+            KeyCode::Down => self.list.select_next(),
+            KeyCode::Char('n' | 'j') if ctrl => self.list.select_next(),
+            KeyCode::Char('u') if ctrl => {
+                self.query.clear();
+                self.refilter();
+            }
+            KeyCode::Backspace => {
+                self.query.pop();
+                self.refilter();
+            }
+            KeyCode::Char(c) if !ctrl => {
+                self.query.push(c);
+                self.refilter();
+            }
+            _ => {}
+        }
+        // `select_next` runs past the end until the list is drawn
+        let last = self.matches.len().checked_sub(1);
+        self.list.select(
+            self.list
+                .selected()
+                .zip(last)
+                .map(|(index, last)| index.min(last)),
+        );
+    }
+
+    pub fn render(&mut self, frame: &mut Frame) {
+        let [query_area, list_area, status_area] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let block = Block::bordered()
+            .title(format!(" {} ", self.prompt))
+            .border_style(Style::new().fg(Color::Cyan));
+        frame.render_widget(Paragraph::new(self.query.as_str()).block(block), query_area);
+        let column = self.query.chars().count() as u16;
+        frame.set_cursor_position((query_area.x + 1 + column, query_area.y + 1));
+
+        let items: Vec<ListItem> = self
+            .matches
+            .iter()
+            .map(|&index| crate::tui::task_item(&self.tasks[index]))
+            .collect();
+        let list = List::new(items)
+            .block(Block::bordered().title(format!(
+                " Tasks ({}/{}) ",
+                self.matches.len(),
+                self.tasks.len()
+            )))
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+            .highlight_symbol("› ");
+        frame.render_stateful_widget(list, list_area, &mut self.list);
+
+        frame.render_widget(
+            Line::from("↑/↓ move  Enter pick  Esc cancel".dim()),
+            status_area,
+        );
+    }
+}
+
+/// Let the user pick one of `tasks`; `None` when they cancel.
+pub fn pick(prompt: &str, tasks: Vec<Task>) -> Result<Option<i32>> {
+    use std::io::IsTerminal;
+    if !std::io::stdout().is_terminal() || !std::io::stdin().is_terminal() {
+        return Err(anyhow::anyhow!(
+            "todo pick needs an interactive terminal; pass task IDs instead"
+        ));
+    }
+    let mut picker = Picker::new(prompt, tasks);
+    let mut terminal = ratatui::init();
+    let result = event_loop(&mut picker, &mut terminal);
+    ratatui::restore();
+    result.map(|()| picker.picked)
+}
+
+fn event_loop(picker: &mut Picker, terminal: &mut DefaultTerminal) -> Result<()> {
+    while !picker.done {
+        terminal.draw(|frame| picker.render(frame))?;
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press {
+                picker.on_key(key);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn type_keys(picker: &mut Picker, keys: &str) {
+        for c in keys.chars() {
+            let code = match c {
+                '\n' => KeyCode::Enter,
+                '\x08' => KeyCode::Backspace,
+                '↓' => KeyCode::Down,
+                c => KeyCode::Char(c),
+            };
+            picker.on_key(KeyEvent::from(code));
+        }
+    }
+
+    #[test]
+    fn test_fuzzy_picking() {
+        assert!(fuzzy_score("wr", "Write report") > fuzzy_score("wr", "draw graph"));
+        assert_eq!(fuzzy_score("rw", "Write"), None);
+        assert!(fuzzy_score("", "anything").is_some());
+
+        let tasks = ["Write report", "Draw graph", "Water plants"]
+            .iter()
+            .enumerate()
+            .map(|(i, title)| {
+                let mut task = Task::new(title.to_string(), None, None, 1);
+                task.id = Some(i as i32 + 1);
+                task
+            })
+            .collect();
+        let mut picker = Picker::new("Show which task?", tasks);
+        type_keys(&mut picker, "wr");
+        assert_eq!(picker.matches, vec![0, 2, 1]);
+        type_keys(&mut picker, "↓↓↓\n");
+        assert!(picker.done);
+        assert_eq!(picker.picked, Some(2));
+
+        // Enter with nothing matching keeps the picker open
+        let mut picker = Picker::new("Show which task?", Vec::new());
+        type_keys(&mut picker, "x\n");
+        assert!(!picker.done);
+        picker.on_key(KeyEvent::from(KeyCode::Esc));
+        assert!(picker.done && picker.picked.is_none());
+    }
+}
@@ -0,0 +1,174 @@
+use indicatif::{ProgressBar, ProgressStyle};
+use std::cell::{Cell, RefCell};
+use std::io::IsTerminal;
+use std::time::{Duration, Instant};
+
+/// How often progress is logged when stderr is not a terminal.
+const LOG_INTERVAL: Duration = Duration::from_secs(5);
+
+enum Output {
+    Bar(ProgressBar),
+    Log,
+    Hidden,
+}
+
+/// Progress of a long-running operation, reported on stderr: an indicatif
+/// bar (or a spinner when the total is unknown) on a terminal, a log line
+/// every `LOG_INTERVAL` otherwise, so redirected output and cron logs still
+/// show signs of life without filling up with redraws. Plain mode always
+/// logs lines, since screen readers cannot follow a redrawn bar.
+pub struct Progress {
+    label: RefCell<String>,
+    total: Option<u64>,
+    output: Output,
+    position: Cell<u64>,
+    started: Instant,
+    last_log: Cell<Instant>,
+    logged: Cell<bool>,
+}
+
+impl Progress {
+    /// A bar counting `total` items.
+    pub fn bar(label: &str, total: u64) -> Self {
+        let style =
+            ProgressStyle::with_template("{msg} [{bar:30}] {pos}/{len} ({per_sec}, {eta} left)")
+                .unwrap()
+                .progress_chars("=> ");
+        Self::new(label, Some(total), style)
+    }
+
+    /// A spinner for work whose size isn't known up front.
+    pub fn spinner(label: &str) -> Self {
+        let style = ProgressStyle::with_template("{spinner} {msg} {pos} ({elapsed})").unwrap();
+        Self::new(label, None, style)
+    }
+
+    /// Reports nothing, for work that runs in the background of another
+    /// command.
+    pub fn hidden() -> Self {
+        Self::with_output(String::new(), None, Output::Hidden)
+    }
+
+    fn new(label: &str, total: Option<u64>, style: ProgressStyle) -> Self {
+        let output = if std::io::stderr().is_terminal() && !crate::style::plain() {
+            let bar = match total {
+                Some(total) => ProgressBar::new(total),
+                None => ProgressBar::new_spinner(),
+            };
+            bar.set_style(style);
+            bar.set_message(label.to_string());
+            bar.enable_steady_tick(Duration::from_millis(120));
+            Output::Bar(bar)
+        } else {
+            Output::Log
+        };
+        Self::with_output(label.to_string(), total, output)
+    }
+
+    fn with_output(label: String, total: Option<u64>, output: Output) -> Self {
+        let now = Instant::now();
+        Self {
+            label: RefCell::new(label),
+            total,
+            output,
+            position: Cell::new(0),
+            started: now,
+            last_log: Cell::new(now),
+            logged: Cell::new(false),
+        }
+    }
+
+    pub fn set_position(&self, position: u64) {
+        self.position.set(position);
+        match &self.output {
+            Output::Bar(bar) => bar.set_position(position),
+            Output::Log if self.last_log.get().elapsed() >= LOG_INTERVAL => {
+                self.last_log.set(Instant::now());
+                self.logged.set(true);
+                eprintln!("{}", self.log_line());
+            }
+            _ => {}
+        }
+    }
+
+    pub fn inc(&self, delta: u64) {
+        self.set_position(self.position.get() + delta);
+    }
+
+    /// Replace the label, e.g. when moving on to the next phase.
+    pub fn set_label(&self, label: &str) {
+        *self.label.borrow_mut() = label.to_string();
+        if let Output::Bar(bar) = &self.output {
+            bar.set_message(label.to_string());
+        }
+    }
+
+    /// Remove the bar. In log mode a final line is printed if progress was
+    /// logged at all, so the log doesn't end mid-way.
+    pub fn finish(&self) {
+        match &self.output {
+            Output::Bar(bar) => bar.finish_and_clear(),
+            Output::Log if self.logged.get() => eprintln!(
+                "{}: done, {} in {}s",
+                self.label.borrow(),
+                self.position.get(),
+                self.started.elapsed().as_secs()
+            ),
+            _ => {}
+        }
+    }
+
+    fn log_line(&self) -> String {
+        let position = self.position.get();
+        match self.total {
+            Some(total) if total > 0 => {
+                let elapsed = self.started.elapsed().as_secs_f64();
+                let remaining = if position > 0 {
+                    elapsed * (total.saturating_sub(position)) as f64 / position as f64
+                } else {
+                    0.0
+                };
+                format!(
+                    "{}: {}/{} ({}%, about {}s left)",
+                    self.label.borrow(),
+                    position,
+                    total,
+                    position * 100 / total,
+                    remaining.round()
+                )
+            }
+            _ => format!("{}: {}", self.label.borrow(), position),
+        }
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        // An early return with `?` must not leave a stale bar above the
+        // error message
+        if let Output::Bar(bar) = &self.output {
+            if !bar.is_finished() {
+                bar.finish_and_clear();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_line() {
+        let progress = Progress::with_output("Importing".to_string(), Some(200), Output::Hidden);
+        progress.set_position(50);
+        assert!(progress
+            .log_line()
+            .starts_with("Importing: 50/200 (25%, about "));
+
+        let spinner = Progress::with_output("Scanning".to_string(), None, Output::Hidden);
+        spinner.inc(3);
+        spinner.inc(4);
+        assert_eq!(spinner.log_line(), "Scanning: 7");
+    }
+}
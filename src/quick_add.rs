@@ -0,0 +1,158 @@
+//! Quick-add syntax for `todo add`: one string with the task's details
+//! written into it.
+//!
+//! ```text
+//! todo add "Pay rent tomorrow !high #finance @home"
+//! ```
+//!
+//! `!low`, `!medium` and `!high` set the priority, `#tag` adds a tag and
+//! `@context` sets the context (contexts are tags, as with the `context`
+//! config key, but a task has one; the last given wins), wherever they
+//! appear. A date expression at the
+//! end of what remains, such as `tomorrow`, `next friday`, `in 2 weeks` or
+//! `2030-04-01`, becomes the due date. The other words, in order, are the
+//! title. `#42`, `@3` and unknown `!words` stay in the title, and the
+//! title always keeps at least one word, so `todo add "Tomorrow"` is just
+//! a title.
+
+use chrono::{DateTime, NaiveDate, Utc};
+
+use crate::capture::is_name;
+use crate::Priority;
+
+/// Longest date expression looked for at the end, e.g. `end of day`.
+const MAX_DATE_WORDS: usize = 3;
+
+#[derive(Debug, Default, PartialEq)]
+pub struct QuickAdd {
+    pub title: String,
+    pub priority: Option<Priority>,
+    /// `#tag`s, in the order written.
+    pub tags: Vec<String>,
+    /// The last `@context`.
+    pub context: Option<String>,
+    /// The trailing date expression, as written.
+    pub due: Option<String>,
+}
+
+/// Split `text` into a title and the details written into it, reading
+/// dates relative to `now`.
+pub fn parse(text: &str, now: DateTime<Utc>) -> QuickAdd {
+    let mut quick = QuickAdd::default();
+    let mut words = Vec::new();
+    for word in text.split_whitespace() {
+        if let Some(priority) = word.strip_prefix('!').and_then(parse_priority) {
+            quick.priority = Some(priority);
+        } else if let Some(tag) = word.strip_prefix('#').filter(|rest| is_name(rest)) {
+            if !quick.tags.iter().any(|known| known == tag) {
+                quick.tags.push(tag.to_string());
+            }
+        } else if let Some(context) = word.strip_prefix('@').filter(|rest| is_name(rest)) {
+            quick.context = Some(context.to_string());
+        } else {
+            words.push(word);
+        }
+    }
+
+    // The longest date at the end wins: `next friday` over `friday`
+    let longest = MAX_DATE_WORDS.min(words.len().saturating_sub(1));
+    if let Some(count) = (1..=longest)
+        .rev()
+        .find(|&count| is_date(&words[words.len() - count..].join(" "), now))
+    {
+        let start = words.len() - count;
+        quick.due = Some(words.split_off(start).join(" "));
+    }
+    quick.title = words.join(" ");
+    quick
+}
+
+fn parse_priority(text: &str) -> Option<Priority> {
+    <Priority as clap::ValueEnum>::from_str(text, true).ok()
+}
+
+/// Whether `text` is a date `--due` would take, past or not.
+fn is_date(text: &str, now: DateTime<Utc>) -> bool {
+    NaiveDate::parse_from_str(text, "%Y-%m-%d").is_ok()
+        || crate::dates::parse_relative(&text.to_lowercase(), now).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_now(text: &str) -> QuickAdd {
+        parse(text, Utc::now())
+    }
+
+    #[test]
+    fn test_parse_quick_add() {
+        assert_eq!(
+            parse_now("Pay rent tomorrow !high #finance @office @home"),
+            QuickAdd {
+                title: "Pay rent".to_string(),
+                priority: Some(Priority::High),
+                tags: vec!["finance".to_string()],
+                context: Some("home".to_string()),
+                due: Some("tomorrow".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_trailing_dates() {
+        let cases = [
+            (
+                "Call the bank next Friday",
+                "Call the bank",
+                Some("next Friday"),
+            ),
+            (
+                "Renew passport in 2 weeks",
+                "Renew passport",
+                Some("in 2 weeks"),
+            ),
+            ("Ship it end of day", "Ship it", Some("end of day")),
+            (
+                "File taxes 2030-04-01 #admin",
+                "File taxes",
+                Some("2030-04-01"),
+            ),
+            ("Prepare for monday", "Prepare for", Some("monday")),
+            // Only at the end, and never the whole title
+            ("Tomorrow never dies", "Tomorrow never dies", None),
+            ("Tomorrow", "Tomorrow", None),
+            ("Read chapter 3", "Read chapter 3", None),
+            ("Plan the week ahead", "Plan the week ahead", None),
+        ];
+        for (text, title, due) in cases {
+            let quick = parse_now(text);
+            assert_eq!(
+                (quick.title.as_str(), quick.due.as_deref()),
+                (title, due),
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn test_plain_words_stay_in_title() {
+        let quick = parse_now("Fix bug #42 for ann@example.com !urgent @3 wow!");
+        assert_eq!(
+            quick.title,
+            "Fix bug #42 for ann@example.com !urgent @3 wow!"
+        );
+        assert_eq!(
+            quick,
+            QuickAdd {
+                title: quick.title.clone(),
+                ..QuickAdd::default()
+            }
+        );
+
+        let quick = parse_now("Water plants !LOW !medium #garden #garden");
+        assert_eq!(quick.priority, Some(Priority::Medium));
+        assert_eq!(quick.tags, ["garden"]);
+    }
+}
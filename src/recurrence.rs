@@ -0,0 +1,227 @@
+use anyhow::Result;
+use chrono::{DateTime, Duration, Months, Utc};
+
+use crate::diagnostic::{did_you_mean, words, Diagnostic};
+
+const RULE_HINT: &str = "use daily, weekly, monthly, yearly or e.g. `every 3 days`";
+
+/// Calendar step of a recurrence rule.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Unit {
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+impl Unit {
+    fn parse(word: &str) -> Option<Self> {
+        match word {
+            "day" | "days" => Some(Unit::Day),
+            "week" | "weeks" => Some(Unit::Week),
+            "month" | "months" => Some(Unit::Month),
+            "year" | "years" => Some(Unit::Year),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Unit::Day => "day",
+            Unit::Week => "week",
+            Unit::Month => "month",
+            Unit::Year => "year",
+        }
+    }
+}
+
+/// How often a task repeats, e.g. `weekly` or `every 3 days`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Recurrence {
+    pub every: u32,
+    pub unit: Unit,
+}
+
+impl Recurrence {
+    /// Parse `daily`, `weekly`, `monthly`, `yearly` or `every [N] UNIT(S)`.
+    /// Errors are `Diagnostic`s pointing at the word that was not understood.
+    pub fn parse(text: &str) -> Result<Self> {
+        let words = words(text);
+        let names: Vec<&str> = words.iter().map(|(_, word)| word.as_str()).collect();
+        let at = |index: usize, message: String| {
+            Diagnostic::new("repeat rule", text, words[index].0.clone(), message)
+        };
+        let unit = |index: usize, word: &str| {
+            Unit::parse(word).ok_or_else(|| {
+                let hint = did_you_mean(word, &["days", "weeks", "months", "years"]);
+                at(index, format!("unknown unit `{}`", word)).with_hint(
+                    hint.unwrap_or_else(|| "use days, weeks, months or years".to_string()),
+                )
+            })
+        };
+        let end = text.trim_end().len();
+
+        let (every, unit) = match names.as_slice() {
+            ["daily"] => (1, Unit::Day),
+            ["weekly"] => (1, Unit::Week),
+            ["monthly"] => (1, Unit::Month),
+            ["yearly"] | ["annually"] => (1, Unit::Year),
+            ["every"] => {
+                return Err(Diagnostic::new(
+                    "repeat rule",
+                    text,
+                    end..end,
+                    "expected a unit after `every`".to_string(),
+                )
+                .with_hint("e.g. `every week` or `every 3 days`")
+                .into())
+            }
+            ["every", word] => (1, unit(1, word)?),
+            ["every", count, word] => {
+                let every = count
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|count| *count > 0)
+                    .ok_or_else(|| {
+                        at(1, format!("expected a positive number, found `{}`", count))
+                    })?;
+                (every, unit(2, word)?)
+            }
+            [word] => {
+                let hint = did_you_mean(word, &["daily", "weekly", "monthly", "yearly"]);
+                return Err(at(0, format!("unknown repeat rule `{}`", word))
+                    .with_hint(hint.unwrap_or_else(|| RULE_HINT.to_string()))
+                    .into());
+            }
+            _ => {
+                let start = words.first().map(|(span, _)| span.start).unwrap_or(0);
+                return Err(Diagnostic::new(
+                    "repeat rule",
+                    text,
+                    start..end,
+                    "not a repeat rule".to_string(),
+                )
+                .with_hint(RULE_HINT)
+                .into());
+            }
+        };
+        Ok(Recurrence { every, unit })
+    }
+
+    /// The `n`th occurrence after `start`. Months and years are counted from
+    /// `start` itself, so the 31st stays on the last day of shorter months
+    /// without drifting.
+    fn nth_after(&self, start: DateTime<Utc>, n: u32) -> Option<DateTime<Utc>> {
+        let steps = self.every.checked_mul(n)?;
+        match self.unit {
+            Unit::Day => start.checked_add_signed(Duration::days(steps.into())),
+            Unit::Week => start.checked_add_signed(Duration::weeks(steps.into())),
+            Unit::Month => start.checked_add_months(Months::new(steps)),
+            Unit::Year => start.checked_add_months(Months::new(steps.checked_mul(12)?)),
+        }
+    }
+
+    /// Due date of the next occurrence: one step after the current due date
+    /// (or after `now` for undated tasks), skipping any occurrences that
+    /// would already be overdue.
+    pub fn next_due(&self, due: Option<DateTime<Utc>>, now: DateTime<Utc>) -> DateTime<Utc> {
+        let start = due.unwrap_or(now);
+        (1..)
+            .map_while(|n| self.nth_after(start, n))
+            .find(|next| *next > now)
+            .unwrap_or(DateTime::<Utc>::MAX_UTC)
+    }
+}
+
+impl std::fmt::Display for Recurrence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.every, self.unit) {
+            (1, Unit::Day) => write!(f, "daily"),
+            (1, Unit::Week) => write!(f, "weekly"),
+            (1, Unit::Month) => write!(f, "monthly"),
+            (1, Unit::Year) => write!(f, "yearly"),
+            (every, unit) => write!(f, "every {} {}s", every, unit.name()),
+        }
+    }
+}
+
+/// Validate a user-supplied rule and return its canonical spelling.
+pub fn normalize(text: &str) -> Result<String> {
+    Ok(Recurrence::parse(text)?.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn date(y: i32, m: u32, d: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, m, d, 0, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn test_parse_and_display() {
+        assert_eq!(normalize("Weekly").unwrap(), "weekly");
+        assert_eq!(normalize("every day").unwrap(), "daily");
+        assert_eq!(normalize(" every  3 days ").unwrap(), "every 3 days");
+        assert_eq!(normalize("every 1 month").unwrap(), "monthly");
+        assert_eq!(normalize("every 2 years").unwrap(), "every 2 years");
+
+        assert!(normalize("every 0 days").is_err());
+        assert!(normalize("every fortnight").is_err());
+        assert!(normalize("sometimes").is_err());
+    }
+
+    #[test]
+    fn test_parse_errors_point_at_the_bad_word() {
+        let error = Recurrence::parse("every 2 wekes").unwrap_err();
+        let diagnostic = error.downcast_ref::<Diagnostic>().unwrap();
+        assert_eq!(&diagnostic.input[diagnostic.span.clone()], "wekes");
+        assert_eq!(diagnostic.hint.as_deref(), Some("did you mean `weeks`?"));
+
+        let error = Recurrence::parse("wekly").unwrap_err();
+        let diagnostic = error.downcast_ref::<Diagnostic>().unwrap();
+        assert_eq!(diagnostic.message, "unknown repeat rule `wekly`");
+        assert_eq!(diagnostic.hint.as_deref(), Some("did you mean `weekly`?"));
+    }
+
+    #[test]
+    fn test_next_due_advances_from_due_date() {
+        let now = date(2030, 1, 10);
+        let weekly = Recurrence::parse("weekly").unwrap();
+        assert_eq!(
+            weekly.next_due(Some(date(2030, 1, 12)), now),
+            date(2030, 1, 19)
+        );
+
+        // Undated tasks repeat from the moment they are completed
+        let every_3_days = Recurrence::parse("every 3 days").unwrap();
+        assert_eq!(every_3_days.next_due(None, now), date(2030, 1, 13));
+    }
+
+    #[test]
+    fn test_next_due_skips_missed_occurrences() {
+        let now = date(2030, 1, 10);
+        let daily = Recurrence::parse("daily").unwrap();
+        assert_eq!(
+            daily.next_due(Some(date(2030, 1, 1)), now),
+            date(2030, 1, 11)
+        );
+    }
+
+    #[test]
+    fn test_monthly_clamps_without_drifting() {
+        let monthly = Recurrence::parse("monthly").unwrap();
+        let now = date(2030, 2, 1);
+        assert_eq!(
+            monthly.next_due(Some(date(2030, 1, 31)), now),
+            date(2030, 2, 28)
+        );
+
+        let now = date(2030, 3, 1);
+        assert_eq!(
+            monthly.next_due(Some(date(2030, 1, 31)), now),
+            date(2030, 3, 31)
+        );
+    }
+}
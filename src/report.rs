@@ -0,0 +1,163 @@
+//! Plain-text reports on the shape of the backlog.
+
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use std::fmt::Write;
+
+use crate::calendar::{local_day, Period};
+use crate::models::{AgingReport, Task};
+
+const BAR_WIDTH: usize = 40;
+
+/// Render `todo report aging`: a bar per age bucket scaled to the largest
+/// one, then the tasks that have been open the longest.
+pub fn render_aging(report: &AgingReport, now: DateTime<Utc>) -> String {
+    let total: usize = report.buckets.iter().map(|(_, count)| count).sum();
+    let largest = report
+        .buckets
+        .iter()
+        .map(|(_, count)| *count)
+        .max()
+        .unwrap_or(0);
+
+    let mut out = String::new();
+    let rule = "─".repeat(80);
+    writeln!(out, "⏳ Open tasks by age:").unwrap();
+    writeln!(out, "{}", rule).unwrap();
+    for (bucket, count) in &report.buckets {
+        let width = (count * BAR_WIDTH).div_ceil(largest.max(1));
+        let line = format!("{:<5} {:>5}  {}", bucket.label(), count, "█".repeat(width));
+        writeln!(out, "{}", line.trim_end()).unwrap();
+    }
+    writeln!(out, "{}", rule).unwrap();
+    writeln!(out, "Total: {} open tasks", total).unwrap();
+
+    if !report.oldest.is_empty() {
+        writeln!(out, "\n🦴 Oldest open tasks:").unwrap();
+        for task in &report.oldest {
+            writeln!(
+                out,
+                "[{}] {} — open {} days, since {}",
+                task.id.unwrap_or(0),
+                task.title,
+                (now - task.created_at).num_days(),
+                crate::style::date(task.created_at)
+            )
+            .unwrap();
+        }
+    }
+    out
+}
+
+/// Render `todo report week`: for each day of `period`, a bar of how many
+/// `completed` tasks were finished that day in `zone` and the tasks
+/// themselves, under `heading`.
+pub fn render_week<Tz: TimeZone>(
+    completed: &[Task],
+    period: &Period,
+    heading: &str,
+    zone: &Tz,
+) -> String {
+    let days: Vec<(NaiveDate, Vec<&Task>)> = period
+        .days()
+        .map(|day| {
+            let tasks = completed
+                .iter()
+                .filter(|task| task.completed_at.map(|at| local_day(at, zone)) == Some(day))
+                .collect();
+            (day, tasks)
+        })
+        .collect();
+    let largest = days.iter().map(|(_, tasks)| tasks.len()).max().unwrap_or(0);
+
+    let mut out = String::new();
+    let rule = "─".repeat(80);
+    writeln!(out, "✅ {}:", heading).unwrap();
+    writeln!(out, "{}", rule).unwrap();
+    for (day, tasks) in &days {
+        let width = (tasks.len() * BAR_WIDTH).div_ceil(largest.max(1));
+        let line = format!(
+            "{} {:>5}  {}",
+            day.format("%a %Y-%m-%d"),
+            tasks.len(),
+            "█".repeat(width)
+        );
+        writeln!(out, "{}", line.trim_end()).unwrap();
+        for task in tasks {
+            writeln!(out, "    [{}] {}", task.id.unwrap_or(0), task.title).unwrap();
+        }
+    }
+    writeln!(out, "{}", rule).unwrap();
+    let total: usize = days.iter().map(|(_, tasks)| tasks.len()).sum();
+    writeln!(out, "Total: {} completed tasks", total).unwrap();
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{AgeBucket, Task};
+    use chrono::Duration;
+
+    #[test]
+    fn test_render_aging() {
+        let now = Utc::now();
+        let mut task = Task::new("Rotting".to_string(), None, None, 1);
+        task.id = Some(7);
+        task.created_at = now - Duration::days(120);
+        let report = AgingReport {
+            buckets: vec![
+                (AgeBucket::Week, 4),
+                (AgeBucket::Month, 1),
+                (AgeBucket::Quarter, 0),
+                (AgeBucket::Older, 2),
+            ],
+            oldest: vec![task],
+        };
+
+        let text = render_aging(&report, now);
+        assert!(text.contains(&format!("<1w       4  {}\n", "█".repeat(40))));
+        assert!(text.contains(&format!("1–4w      1  {}\n", "█".repeat(10))));
+        assert!(text.contains("1–3m      0\n"));
+        assert!(text.contains("Total: 7 open tasks"));
+        assert!(text.contains("[7] Rotting — open 120 days, since "));
+    }
+
+    #[test]
+    fn test_render_week() {
+        let today = NaiveDate::from_ymd_opt(2030, 1, 10).unwrap();
+        let done = |id: i32, days_ago: i64| {
+            let mut task = Task::new(format!("Task {}", id), None, None, 1);
+            task.id = Some(id);
+            task.completed = true;
+            task.completed_at = Some(
+                (today - Duration::days(days_ago))
+                    .and_hms_opt(15, 0, 0)
+                    .unwrap()
+                    .and_utc(),
+            );
+            task
+        };
+        let completed = vec![done(1, 6), done(2, 0), done(3, 0), done(4, 9)];
+
+        let period = Period::last_days(today, 7);
+        let text = render_week(&completed, &period, "Completed in the last 7 days", &Utc);
+        assert!(text.contains(&format!(
+            "Fri 2030-01-04     1  {}\n    [1] Task 1\n",
+            "█".repeat(20)
+        )));
+        assert!(text.contains("Sat 2030-01-05     0\n"));
+        assert!(text.contains(&format!(
+            "Thu 2030-01-10     2  {}\n    [2] Task 2\n    [3] Task 3\n",
+            "█".repeat(40)
+        )));
+        assert!(!text.contains("Task 4"));
+        assert!(text.contains("Total: 3 completed tasks"));
+
+        // 15:00 UTC is already the next day ten hours east
+        let east = chrono::FixedOffset::east_opt(10 * 3600).unwrap();
+        let text = render_week(&completed, &period, "Completed", &east);
+        assert!(text.contains("Fri 2030-01-04     0\n"));
+        assert!(text.contains("Sat 2030-01-05     1"));
+        assert!(text.contains("Total: 1 completed tasks"));
+    }
+}
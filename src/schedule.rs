@@ -0,0 +1,298 @@
+//! `todo schedule --fill-week`: due dates proposed for tasks that have
+//! none. Each working day (Monday to Friday) of the next seven days has
+//! room for a number of hours of work. Tasks already due that day, or
+//! overdue on the first day, take up their estimate; undated tasks are then
+//! placed highest priority first, oldest first among equals, on the
+//! earliest day they still fit. Tasks without an estimate count as
+//! `DEFAULT_ESTIMATE_MINUTES`.
+//!
+//! `todo plan` works from the same estimates: `budget` picks the tasks
+//! to work on now that fit within a number of minutes.
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc, Weekday};
+use std::collections::HashMap;
+
+use crate::models::{duration_text, Task};
+
+pub const DEFAULT_ESTIMATE_MINUTES: i64 = 30;
+/// How far ahead `--fill-week` looks, today included.
+const DAYS_AHEAD: i64 = 7;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Day {
+    pub date: NaiveDate,
+    /// Minutes taken by tasks that were already due.
+    pub booked: i64,
+    /// Undated tasks placed on this day, with their estimates.
+    pub planned: Vec<(i32, i64)>,
+}
+
+impl Day {
+    fn load(&self) -> i64 {
+        self.booked + self.planned.iter().map(|(_, minutes)| minutes).sum::<i64>()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Plan {
+    pub days: Vec<Day>,
+    /// Minutes of work each day has room for.
+    pub capacity: i64,
+    /// Tasks that fit on no day, with their estimates.
+    pub unplaced: Vec<(i32, i64)>,
+}
+
+fn estimate(task: &Task, estimates: &HashMap<i32, i64>) -> i64 {
+    task.id
+        .and_then(|id| estimates.get(&id).copied())
+        .unwrap_or(DEFAULT_ESTIMATE_MINUTES)
+}
+
+/// Plan the week starting `today` for the pending `tasks`, with `capacity`
+/// minutes of work a day and `estimates` in minutes by task ID.
+pub fn plan(
+    today: NaiveDate,
+    capacity: i64,
+    tasks: &[Task],
+    estimates: &HashMap<i32, i64>,
+) -> Plan {
+    let mut days: Vec<Day> = (0..DAYS_AHEAD)
+        .map(|offset| today + Duration::days(offset))
+        .filter(|date| !matches!(date.weekday(), Weekday::Sat | Weekday::Sun))
+        .map(|date| Day {
+            date,
+            booked: 0,
+            planned: Vec::new(),
+        })
+        .collect();
+
+    let mut undated: Vec<&Task> = Vec::new();
+    for task in tasks.iter().filter(|task| !task.completed) {
+        let Some(due) = task.due_date else {
+            undated.push(task);
+            continue;
+        };
+        let due = crate::zone::day_of(due).max(today);
+        if let Some(day) = days.iter_mut().find(|day| day.date == due) {
+            day.booked += estimate(task, estimates);
+        }
+    }
+    undated.sort_by_key(|task| (std::cmp::Reverse(task.priority), task.created_at));
+
+    let mut unplaced = Vec::new();
+    for task in undated {
+        let Some(id) = task.id else { continue };
+        let minutes = estimate(task, estimates);
+        match days.iter_mut().find(|day| day.load() + minutes <= capacity) {
+            Some(day) => day.planned.push((id, minutes)),
+            None => unplaced.push((id, minutes)),
+        }
+    }
+    Plan {
+        days,
+        capacity,
+        unplaced,
+    }
+}
+
+/// The tasks to work on within `minutes`, with their estimates. Overdue
+/// tasks and those due within `window` of `now` come first, then the rest
+/// by priority, earliest due and oldest first among equals; each is taken
+/// if it still fits what is left of the budget.
+pub fn budget(
+    tasks: &[Task],
+    estimates: &HashMap<i32, i64>,
+    minutes: i64,
+    now: DateTime<Utc>,
+    window: Duration,
+) -> Vec<(i32, i64)> {
+    let mut pending: Vec<&Task> = tasks.iter().filter(|task| !task.completed).collect();
+    pending.sort_by_key(|task| {
+        let pressing = task.due_date.is_some_and(|due| due <= now + window);
+        (
+            !pressing,
+            std::cmp::Reverse(task.priority),
+            task.due_date.is_none(),
+            task.due_date,
+            task.created_at,
+        )
+    });
+
+    let mut left = minutes;
+    let mut picked = Vec::new();
+    for task in pending {
+        let Some(id) = task.id else { continue };
+        let minutes = estimate(task, estimates);
+        if minutes <= left {
+            left -= minutes;
+            picked.push((id, minutes));
+        }
+    }
+    picked
+}
+
+impl Plan {
+    /// `(task ID, day)` for every task the plan gives a due date.
+    pub fn placements(&self) -> Vec<(i32, NaiveDate)> {
+        self.days
+            .iter()
+            .flat_map(|day| day.planned.iter().map(|&(id, _)| (id, day.date)))
+            .collect()
+    }
+
+    /// The day `text` names: a weekday (`wed`, `Wednesday`) or a date in
+    /// the plan.
+    pub fn find_day(&self, text: &str) -> Option<NaiveDate> {
+        let text = text.trim().to_lowercase();
+        self.days.iter().map(|day| day.date).find(|date| {
+            text == date.format("%Y-%m-%d").to_string()
+                || text == date.format("%a").to_string().to_lowercase()
+                || text == date.format("%A").to_string().to_lowercase()
+        })
+    }
+
+    /// Move a planned or unplaced task to `date`, or leave it unplaced with
+    /// `None`. Returns false when the task isn't part of the plan.
+    pub fn move_task(&mut self, id: i32, date: Option<NaiveDate>) -> bool {
+        let taken = self
+            .days
+            .iter_mut()
+            .flat_map(|day| {
+                let index = day.planned.iter().position(|&(task, _)| task == id);
+                index.map(|index| day.planned.remove(index))
+            })
+            .next()
+            .or_else(|| {
+                let index = self.unplaced.iter().position(|&(task, _)| task == id)?;
+                Some(self.unplaced.remove(index))
+            });
+        let Some(entry) = taken else {
+            return false;
+        };
+        match date.and_then(|date| self.days.iter_mut().find(|day| day.date == date)) {
+            Some(day) => day.planned.push(entry),
+            None => self.unplaced.push(entry),
+        }
+        true
+    }
+
+    /// The plan, one block per day, naming tasks by `title`.
+    pub fn render(&self, title: impl Fn(i32) -> String) -> String {
+        let minutes = |minutes: i64| duration_text(Duration::minutes(minutes));
+        let mut lines = Vec::new();
+        for day in &self.days {
+            let mut heading = format!(
+                "{}  {} of {}",
+                day.date.format("%a %Y-%m-%d"),
+                minutes(day.load()),
+                minutes(self.capacity)
+            );
+            if day.booked > 0 {
+                heading.push_str(&format!(" ({} already due)", minutes(day.booked)));
+            }
+            lines.push(heading);
+            for &(id, estimate) in &day.planned {
+                lines.push(format!(
+                    "  {:>4}  {} ({})",
+                    id,
+                    title(id),
+                    minutes(estimate)
+                ));
+            }
+        }
+        if !self.unplaced.is_empty() {
+            lines.push("Not scheduled:".to_string());
+            for &(id, estimate) in &self.unplaced {
+                lines.push(format!(
+                    "  {:>4}  {} ({})",
+                    id,
+                    title(id),
+                    minutes(estimate)
+                ));
+            }
+        }
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn task(id: i32, priority: i32, due: Option<NaiveDate>) -> Task {
+        let mut task = Task::new(format!("Task {}", id), None, None, priority);
+        task.id = Some(id);
+        task.due_date = due.map(|date| date.and_hms_opt(0, 0, 0).unwrap().and_utc());
+        task.created_at = chrono::Utc
+            .with_ymd_and_hms(2030, 1, 1, 0, 0, id as u32)
+            .unwrap();
+        task
+    }
+
+    #[test]
+    fn test_fill_week() {
+        // A Thursday: Thu, Fri, then Mon to Wed of the next week
+        let today = NaiveDate::from_ymd_opt(2030, 1, 10).unwrap();
+        let friday = NaiveDate::from_ymd_opt(2030, 1, 11).unwrap();
+        let tasks = vec![
+            task(1, 0, None),
+            task(2, 2, None),
+            task(3, 1, Some(today - Duration::days(3))),
+            task(4, 1, Some(friday)),
+            task(5, 2, None),
+            task(6, 1, None),
+        ];
+        let estimates: HashMap<i32, i64> = [(2, 120), (3, 60), (4, 240), (5, 600), (6, 180)].into();
+
+        let plan = plan(today, 240, &tasks, &estimates);
+        let dates: Vec<String> = plan
+            .days
+            .iter()
+            .map(|day| day.date.format("%a").to_string())
+            .collect();
+        assert_eq!(dates, ["Thu", "Fri", "Mon", "Tue", "Wed"]);
+        assert_eq!(plan.days[0].booked, 60);
+        assert_eq!(plan.days[1].booked, 240);
+        // High priority first; the overdue hour leaves room for task 2 today
+        assert_eq!(plan.days[0].planned, vec![(2, 120), (1, 30)]);
+        assert_eq!(plan.days[2].planned, vec![(6, 180)]);
+        assert_eq!(plan.unplaced, vec![(5, 600)]);
+
+        let mut plan = plan;
+        assert_eq!(plan.find_day("monday"), plan.days.get(2).map(|d| d.date));
+        assert_eq!(plan.find_day("2030-01-11"), Some(friday));
+        assert_eq!(plan.find_day("sat"), None);
+        assert!(plan.move_task(5, Some(friday)));
+        assert!(plan.move_task(1, None));
+        assert!(!plan.move_task(4, None));
+        assert_eq!(plan.unplaced, vec![(1, 30)]);
+        assert_eq!(
+            plan.placements(),
+            vec![(2, today), (5, friday), (6, plan.days[2].date)]
+        );
+        assert!(plan.render(|id| format!("T{}", id)).contains(
+            "Fri 2030-01-11  14h 00m of 4h 00m (4h 00m already due)\n     5  T5 (10h 00m)"
+        ));
+    }
+
+    #[test]
+    fn test_budget() {
+        let today = NaiveDate::from_ymd_opt(2030, 1, 10).unwrap();
+        let now = today.and_hms_opt(9, 0, 0).unwrap().and_utc();
+        let tasks = vec![
+            task(1, 2, None),
+            task(2, 0, Some(today - Duration::days(1))),
+            task(3, 1, Some(today + Duration::days(1))),
+            task(4, 2, Some(today + Duration::days(30))),
+            task(5, 1, None),
+        ];
+        let estimates: HashMap<i32, i64> = [(1, 90), (2, 60), (3, 120), (4, 30)].into();
+
+        // Overdue and due-soon tasks first, then by priority; task 1 no
+        // longer fits, the shorter task 5 after it still does
+        let picked = budget(&tasks, &estimates, 240, now, Duration::hours(48));
+        assert_eq!(picked, vec![(3, 120), (2, 60), (4, 30), (5, 30)]);
+        assert!(budget(&tasks, &estimates, 20, now, Duration::hours(48)).is_empty());
+    }
+}
@@ -0,0 +1,1032 @@
+//! `todo serve`: a small JSON API over the task database, for phone
+//! shortcuts and dashboards.
+//!
+//! ```text
+//! GET    /                      a small page to list, add and complete tasks
+//! GET    /tasks                 pending tasks; ?status=completed|all, ?tag=, ?project=
+//! POST   /tasks                 add {"title", "description", "due", "priority", "tags",
+//!                               "project", "parent_id", "repeat"}; only the title is required
+//! GET    /tasks/ID              one task
+//! PATCH  /tasks/ID              change some of "title", "description", "due", "priority",
+//!                               "tags", "project" and "completed"
+//! PUT    /tasks/ID              replace the task with a full task document
+//! POST   /tasks/ID/complete     complete it
+//! DELETE /tasks/ID              move it to the trash
+//! GET    /actions/ID/ACTION     the signed links of `todo plan-email`: a page to confirm
+//! POST   /actions/ID/ACTION     what that page's button sends
+//! GET    /share/task/ID         the read-only pages of `todo share`
+//! GET    /share/project/NAME
+//! ```
+//!
+//! Tasks are sent as the document `todo get --format json` prints. Errors
+//! come back as `{"error": "..."}`. With a token, every `/tasks` request
+//! needs an `Authorization: Bearer TOKEN` header; the action and share links
+//! carry their own signature. The page at `/` holds no tasks itself and asks
+//! for the token before calling `/tasks`.
+//!
+//! Without a token, any web page the user opens could send requests to a
+//! server on loopback, so changes are refused when their `Origin` is not the
+//! server itself, and request bodies must be sent as `application/json`,
+//! which a cross-origin page cannot do without the server's consent. A page
+//! could also point its own domain at 127.0.0.1 (DNS rebinding) and so be
+//! the server's origin; requests whose `Host` is not a loopback name on the
+//! server's port are refused for that.
+//!
+//! Each connection is read on a thread of its own, so a slow client holds
+//! up no one else; the requests then take turns on the one database
+//! connection, over the same checks and undo journal as the CLI.
+//!
+//! The server keeps running when a newer `todo` migrates the database: it
+//! notices the schema version change in `meta` before the next request and
+//! reopens its connection. A request the old code can no longer answer gets
+//! a 503 asking for a restart instead of a bare database error.
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use crate::db::{StatusFilter, TaskFilter};
+use crate::models::Task;
+use crate::signing::{LinkAction, Shared};
+use crate::store::{self, TodoStore};
+
+/// Bodies larger than this are refused.
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+/// How long a client may take to send its request.
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+/// The web page served at `/`, built into the binary.
+const PAGE: &str = include_str!("web/index.html");
+
+#[derive(Debug, Default)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub query: Vec<(String, String)>,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn query(&self, name: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+#[derive(Debug)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: String,
+}
+
+impl Response {
+    fn json(status: u16, value: &impl serde::Serialize) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            body: serde_json::to_string(value).expect("tasks serialize to JSON"),
+        }
+    }
+
+    fn empty() -> Self {
+        Self {
+            status: 204,
+            content_type: "application/json",
+            body: String::new(),
+        }
+    }
+
+    fn html(status: u16, message: &str) -> Self {
+        Self::page(status, &format!("<p>{}</p>", crate::html::escape(message)))
+    }
+
+    /// A page of `body`, which is already HTML.
+    fn page(status: u16, body: &str) -> Self {
+        Self {
+            status,
+            content_type: "text/html; charset=utf-8",
+            body: format!(
+                "<!doctype html><meta name=\"viewport\" content=\"width=device-width\">{}",
+                body
+            ),
+        }
+    }
+}
+
+/// A failed request: its status and the message sent back.
+#[derive(Debug)]
+struct ApiError(u16, String);
+
+impl From<store::Error> for ApiError {
+    fn from(error: store::Error) -> Self {
+        let status = match &error {
+            store::Error::NotFound(_) | store::Error::Missing(_) => 404,
+            store::Error::Invalid(_) => 400,
+            store::Error::InvalidDate(diagnostic) => {
+                return ApiError(400, diagnostic.render(false))
+            }
+            // Another process is writing; worth trying again shortly
+            store::Error::DbLocked => 503,
+            store::Error::Storage(error) if schema_mismatch(error) => {
+                return ApiError(
+                    503,
+                    "The database was upgraded by a newer todo; restart `todo serve`".to_string(),
+                )
+            }
+            store::Error::Storage(_) => 500,
+        };
+        ApiError(status, error.to_string())
+    }
+}
+
+/// Whether `error` comes from a schema this server's queries don't fit,
+/// after a newer `todo` migrated the database.
+fn schema_mismatch(error: &rusqlite::Error) -> bool {
+    let message = error.to_string();
+    message.contains("no such column") || message.contains("no such table")
+}
+
+impl From<rusqlite::Error> for ApiError {
+    fn from(error: rusqlite::Error) -> Self {
+        store::Error::from(error).into()
+    }
+}
+
+fn bad_request(error: impl std::fmt::Display) -> ApiError {
+    ApiError(400, format!("{:#}", error))
+}
+
+pub struct ServerOptions<'a> {
+    /// Bearer token `/tasks` requests must present.
+    pub token: Option<&'a str>,
+    /// Secret the `plan-email` and `share` links are signed with.
+    pub link_secret: Option<String>,
+    /// How close a due date is highlighted on shared pages.
+    pub due_soon_window: chrono::Duration,
+    /// Directory of lifecycle hooks to run after each change.
+    pub hooks: Option<&'a Path>,
+    /// Port the server listens on, which the `Host` of requests must name
+    /// when there is no token.
+    pub port: u16,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NewTask {
+    title: String,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    due: Option<String>,
+    #[serde(default)]
+    priority: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    project: Option<String>,
+    #[serde(default)]
+    parent_id: Option<i32>,
+    #[serde(default)]
+    repeat: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TaskPatch {
+    #[serde(default)]
+    title: Option<String>,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    due: Option<String>,
+    #[serde(default)]
+    priority: Option<String>,
+    #[serde(default)]
+    tags: Option<Vec<String>>,
+    #[serde(default)]
+    project: Option<String>,
+    #[serde(default)]
+    completed: Option<bool>,
+}
+
+fn parse_priority(text: &str) -> Result<i32, ApiError> {
+    <crate::Priority as clap::ValueEnum>::from_str(text, true)
+        .map(|priority| priority.to_int())
+        .map_err(|_| bad_request("Priority must be low, medium or high"))
+}
+
+fn parse_body<T: serde::de::DeserializeOwned>(request: &Request) -> Result<T, ApiError> {
+    let json = request
+        .header("content-type")
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|value| value.trim().eq_ignore_ascii_case("application/json"));
+    if !json {
+        return Err(ApiError(
+            415,
+            "Send the body with Content-Type: application/json".to_string(),
+        ));
+    }
+    serde_json::from_slice(&request.body)
+        .map_err(|error| bad_request(format!("Invalid JSON: {}", error)))
+}
+
+/// Compare without stopping at the first difference, so response times
+/// don't reveal how much of a guessed token was right.
+fn same_token(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// The `Origin` of a request that is not the server's own, as a page on
+/// another site sends along with anything it makes the browser do.
+/// Clients such as curl send no `Origin` at all.
+fn foreign_origin(request: &Request) -> Option<&str> {
+    let origin = request.header("origin")?;
+    let host = origin
+        .strip_prefix("http://")
+        .or_else(|| origin.strip_prefix("https://"));
+    match (host, request.header("host")) {
+        (Some(host), Some(own)) if host.eq_ignore_ascii_case(own) => None,
+        _ => Some(origin),
+    }
+}
+
+/// Whether the `Host` of a request is `127.0.0.1`, `localhost` or `[::1]`
+/// on `port`. A browser led to the server by a rebound domain sends that
+/// domain instead.
+fn loopback_host(request: &Request, port: u16) -> bool {
+    let Some(host) = request.header("host") else {
+        return false;
+    };
+    let (name, given_port) = match host.rsplit_once(':') {
+        Some((name, given)) if !given.ends_with(']') => (name, given.parse().ok()),
+        _ => (host, Some(80)),
+    };
+    ["127.0.0.1", "localhost", "[::1]"]
+        .iter()
+        .any(|loopback| name.eq_ignore_ascii_case(loopback))
+        && given_port == Some(port)
+}
+
+/// Answer one request.
+pub fn handle(store: &TodoStore, options: &ServerOptions, request: &Request) -> Response {
+    let changes = !matches!(request.method.as_str(), "GET" | "HEAD");
+    let result = if options.token.is_none() && !loopback_host(request, options.port) {
+        Err(ApiError(
+            403,
+            format!(
+                "Requests for {} are not allowed without a token",
+                request.header("host").unwrap_or("no host")
+            ),
+        ))
+    } else if let Some(origin) = foreign_origin(request).filter(|_| changes) {
+        Err(ApiError(
+            403,
+            format!("Requests from {} are not allowed", origin),
+        ))
+    } else if request.method == "GET" && matches!(request.path.as_str(), "/" | "/index.html") {
+        Ok(Response {
+            status: 200,
+            content_type: "text/html; charset=utf-8",
+            body: PAGE.to_string(),
+        })
+    } else if request.path.starts_with("/actions/") {
+        action(store, options, request)
+    } else if request.path.starts_with("/share/") {
+        share(store, options, request)
+    } else {
+        let authorized = options.token.is_none_or(|token| {
+            request
+                .header("authorization")
+                .and_then(|value| value.strip_prefix("Bearer "))
+                .is_some_and(|given| same_token(given.trim(), token))
+        });
+        if authorized {
+            route(store, request)
+        } else {
+            Err(ApiError(401, "Missing or wrong bearer token".to_string()))
+        }
+    };
+    result.unwrap_or_else(|ApiError(status, message)| {
+        Response::json(status, &serde_json::json!({ "error": message }))
+    })
+}
+
+fn route(store: &TodoStore, request: &Request) -> Result<Response, ApiError> {
+    let segments: Vec<&str> = request.path.trim_matches('/').split('/').collect();
+    let id = |text: &str| -> Result<i32, ApiError> {
+        text.parse()
+            .map_err(|_| ApiError(404, format!("No such resource: {}", request.path)))
+    };
+    match (request.method.as_str(), segments.as_slice()) {
+        ("GET", ["tasks"]) => list(store, request),
+        ("POST", ["tasks"]) => add(store, request),
+        ("GET", ["tasks", task]) => Ok(Response::json(200, &store.get(id(task)?)?)),
+        ("PATCH", ["tasks", task]) => patch(store, id(task)?, request),
+        ("PUT", ["tasks", task]) => {
+            let id = id(task)?;
+            let task: Task = parse_body(request)?;
+            store.update(id, &task)?;
+            Ok(Response::json(200, &store.get(id)?))
+        }
+        ("POST", ["tasks", task, "complete"]) => {
+            let id = id(task)?;
+            store.complete(id)?;
+            Ok(Response::json(200, &store.database().get_task_by_id(id)?))
+        }
+        ("DELETE", ["tasks", task]) => {
+            store.delete(id(task)?)?;
+            Ok(Response::empty())
+        }
+        (_, ["tasks"]) | (_, ["tasks", _]) | (_, ["tasks", _, "complete"]) => Err(ApiError(
+            405,
+            format!("{} is not allowed on {}", request.method, request.path),
+        )),
+        _ => Err(ApiError(404, format!("No such resource: {}", request.path))),
+    }
+}
+
+fn list(store: &TodoStore, request: &Request) -> Result<Response, ApiError> {
+    let mut builder = TaskFilter::builder();
+    builder = match request.query("status") {
+        None | Some("pending") => builder,
+        Some("completed") => builder.status(StatusFilter::Completed),
+        Some("all") => builder.include_completed(),
+        Some(other) => {
+            return Err(bad_request(format!(
+                "Unknown status '{}': use pending, completed or all",
+                other
+            )))
+        }
+    };
+    if let Some(tag) = request.query("tag") {
+        builder = builder.tag(tag);
+    }
+    if let Some(project) = request.query("project") {
+        builder = builder.project(project);
+    }
+    let filter = builder.build()?;
+    Ok(Response::json(200, &store.list(&filter)?))
+}
+
+fn add(store: &TodoStore, request: &Request) -> Result<Response, ApiError> {
+    let new: NewTask = parse_body(request)?;
+    let due = new
+        .due
+        .as_deref()
+        .map(crate::commands::parse_due_date)
+        .transpose()
+        .map_err(bad_request)?;
+    let priority = new.priority.as_deref().map(parse_priority).transpose()?;
+    let mut task = Task::new(new.title, new.description, due, priority.unwrap_or(1));
+    task.tags = new.tags;
+    task.project = new.project;
+    task.parent_id = new.parent_id;
+    task.recurrence = new.repeat;
+    let id = store.add(&task)?;
+    Ok(Response::json(201, &store.get(id)?))
+}
+
+fn patch(store: &TodoStore, id: i32, request: &Request) -> Result<Response, ApiError> {
+    let patch: TaskPatch = parse_body(request)?;
+    let mut task = store.get(id)?;
+    let before = task.clone();
+    if let Some(title) = patch.title {
+        task.title = title;
+    }
+    if let Some(description) = patch.description {
+        task.description = Some(description).filter(|text| !text.is_empty());
+    }
+    if let Some(due) = patch.due {
+        task.due_date = crate::commands::parse_due_date(&due)
+            .map(Some)
+            .map_err(bad_request)?;
+    }
+    if let Some(priority) = patch.priority {
+        task.priority = parse_priority(&priority)?;
+        task.priority_inherited = false;
+    }
+    if let Some(tags) = patch.tags {
+        task.tags = tags;
+    }
+    if let Some(project) = patch.project {
+        task.project = Some(project).filter(|name| !name.is_empty());
+    }
+    let complete = patch.completed == Some(true) && !task.completed;
+    if patch.completed == Some(false) {
+        task.completed = false;
+    }
+    if !task.changes_from(&before).is_empty() {
+        store.update(id, &task)?;
+    }
+    if complete {
+        store.complete(id)?;
+    }
+    Ok(Response::json(200, &store.get(id)?))
+}
+
+/// Follow a signed `plan-email` link. The answer is a page, since these
+/// are opened in a browser. A GET only asks to confirm: mail scanners and
+/// link previews open links too, so the change waits for the POST the
+/// page's button sends to the same signed URL.
+fn action(
+    store: &TodoStore,
+    options: &ServerOptions,
+    request: &Request,
+) -> Result<Response, ApiError> {
+    let invalid = || Response::html(403, "This link is invalid or has expired.");
+    let segments: Vec<&str> = request.path.trim_matches('/').split('/').collect();
+    let (Some(secret), ["actions", id, action]) = (&options.link_secret, segments.as_slice())
+    else {
+        return Ok(invalid());
+    };
+    let expires = request
+        .query("expires")
+        .and_then(|value| value.parse().ok())
+        .and_then(|timestamp| DateTime::<Utc>::from_timestamp(timestamp, 0));
+    let (Ok(id), Some(action), Some(expires), Some(signature)) = (
+        id.parse::<i32>(),
+        LinkAction::parse(action),
+        expires,
+        request.query("sig"),
+    ) else {
+        return Ok(invalid());
+    };
+    if !crate::signing::verify(secret, id, action, expires, signature) {
+        return Ok(invalid());
+    }
+
+    let task = store.get(id)?;
+    if action == LinkAction::Complete && task.completed {
+        return Ok(Response::html(
+            200,
+            &format!("“{}” was already done.", task.title),
+        ));
+    }
+    if request.method != "POST" {
+        let (question, button) = match action {
+            LinkAction::Complete => (format!("Mark “{}” as done?", task.title), "Done"),
+            LinkAction::Snooze => (format!("Snooze “{}” for a day?", task.title), "Snooze"),
+        };
+        // No `action`, so the form posts back to this URL, signature and all
+        return Ok(Response::page(
+            200,
+            &format!(
+                "<p>{}</p><form method=\"post\"><button>{}</button></form>",
+                crate::html::escape(&question),
+                button
+            ),
+        ));
+    }
+    let message = match action {
+        LinkAction::Complete => {
+            store.complete(id)?;
+            format!("Done: “{}”.", task.title)
+        }
+        LinkAction::Snooze => {
+            let until = crate::commands::snooze_target(&task, 1);
+            store.database().snooze_task(id, until)?;
+            format!(
+                "“{}” snoozed until {}.",
+                task.title,
+                crate::style::date(until)
+            )
+        }
+    };
+    Ok(Response::html(200, &message))
+}
+
+/// Show the page of a signed `todo share` link: a read-only view of a task
+/// or project for anyone holding the link, without the API token.
+fn share(
+    store: &TodoStore,
+    options: &ServerOptions,
+    request: &Request,
+) -> Result<Response, ApiError> {
+    let invalid = || Response::html(403, "This link is invalid or has expired.");
+    // A project name may itself contain slashes
+    let segments: Vec<&str> = request
+        .path
+        .trim_start_matches('/')
+        .splitn(3, '/')
+        .collect();
+    let (Some(secret), ["share", kind, value]) = (&options.link_secret, segments.as_slice()) else {
+        return Ok(invalid());
+    };
+    let expires = request
+        .query("expires")
+        .and_then(|value| value.parse().ok())
+        .and_then(|timestamp| DateTime::<Utc>::from_timestamp(timestamp, 0));
+    let (Some(shared), Some(expires), Some(signature)) =
+        (Shared::parse(kind, value), expires, request.query("sig"))
+    else {
+        return Ok(invalid());
+    };
+    if !crate::signing::verify_share(secret, &shared, expires, signature) {
+        return Ok(invalid());
+    }
+
+    let db = store.database();
+    let page = match shared {
+        Shared::Task(id) => {
+            let Some(task) = db.get_task_by_id(id)? else {
+                return Ok(Response::html(404, "This task no longer exists."));
+            };
+            let mut tasks = vec![task.clone()];
+            tasks.extend(db.get_subtasks(id)?);
+            crate::html::render_shared(
+                &task.title,
+                task.description.as_deref(),
+                &tasks,
+                options.due_soon_window,
+            )
+        }
+        Shared::Project(name) => {
+            let filter = TaskFilter::builder()
+                .project(&name)
+                .include_completed()
+                .build()?;
+            crate::html::render_shared(&name, None, &store.list(&filter)?, options.due_soon_window)
+        }
+    };
+    Ok(Response {
+        status: 200,
+        content_type: "text/html; charset=utf-8",
+        body: page,
+    })
+}
+
+/// `%XX` escapes and `+` for spaces, as in query strings.
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'+' => decoded.push(b' '),
+            b'%' => {
+                let hex = text.get(index + 1..index + 3);
+                match hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                    Some(byte) => {
+                        decoded.push(byte);
+                        index += 2;
+                    }
+                    None => decoded.push(b'%'),
+                }
+            }
+            byte => decoded.push(byte),
+        }
+        index += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Read one HTTP/1.1 request: the request line, headers and a body of
+/// `Content-Length` bytes.
+pub fn read_request(reader: &mut impl BufRead) -> anyhow::Result<Request> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err(anyhow::anyhow!("Malformed request line"));
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let mut request = Request {
+        method: method.to_string(),
+        path: percent_decode(path),
+        query: query
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+                (percent_decode(key), percent_decode(value))
+            })
+            .collect(),
+        ..Request::default()
+    };
+
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            request
+                .headers
+                .push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+
+    let length: usize = request
+        .header("content-length")
+        .map(|value| value.parse())
+        .transpose()
+        .map_err(|_| anyhow::anyhow!("Malformed Content-Length"))?
+        .unwrap_or(0);
+    if length > MAX_BODY_BYTES {
+        return Err(anyhow::anyhow!("Request body is too large"));
+    }
+    request.body = vec![0; length];
+    reader.read_exact(&mut request.body)?;
+    Ok(request)
+}
+
+fn write_response(stream: &mut impl Write, response: &Response) -> std::io::Result<()> {
+    let reason = match response.status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        415 => "Unsupported Media Type",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        reason,
+        response.content_type,
+        response.body.len(),
+        response.body
+    )?;
+    stream.flush()
+}
+
+fn serve_connection(
+    store: &Mutex<&mut TodoStore>,
+    options: &ServerOptions,
+    mut stream: TcpStream,
+) -> anyhow::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let request = match read_request(&mut BufReader::new(&mut stream)) {
+        Ok(request) => request,
+        Err(error) => {
+            let body = serde_json::json!({ "error": format!("{:#}", error) });
+            write_response(&mut stream, &Response::json(400, &body))?;
+            return Ok(());
+        }
+    };
+    // Held through the hooks too, so they see only this request's changes
+    let mut store = store.lock().unwrap_or_else(PoisonError::into_inner);
+    match store.reopen_if_migrated() {
+        Ok(Some(version)) if version > crate::db::SCHEMA_VERSION => errln!(
+            "⚠️  A newer todo upgraded the database to schema version {}; still serving, restart `todo serve` to use it fully",
+            version
+        ),
+        Ok(Some(version)) => errln!("↻ Reopened the database at schema version {}", version),
+        Ok(None) => {}
+        Err(error) => errln!("⚠️  Cannot reopen the database: {}", error),
+    }
+    let since = match options.hooks {
+        Some(_) => store.database().last_history_id()?,
+        None => 0,
+    };
+    let response = handle(&store, options, &request);
+    errln!("{} {} {}", request.method, request.path, response.status);
+    write_response(&mut stream, &response)?;
+    if let Some(dir) = options.hooks {
+        crate::hooks::run_since(store.database(), dir, since)?;
+    }
+    Ok(())
+}
+
+/// Answer requests on `listener` until the process is stopped, each
+/// connection on a thread of its own. A broken connection only ends that
+/// request.
+pub fn serve(
+    store: &mut TodoStore,
+    listener: TcpListener,
+    options: &ServerOptions,
+) -> anyhow::Result<()> {
+    let store = Mutex::new(store);
+    std::thread::scope(|scope| {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(error) => {
+                    errln!("⚠️  {:#}", error);
+                    continue;
+                }
+            };
+            let store = &store;
+            scope.spawn(move || {
+                if let Err(error) = serve_connection(store, options, stream) {
+                    errln!("⚠️  {:#}", error);
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A request as a client on the same machine sends it, with a JSON body
+    /// when there is one.
+    fn request(method: &str, path: &str, body: &str) -> Request {
+        let (path, query) = path.split_once('?').unwrap_or((path, ""));
+        let mut headers = vec![("Host".to_string(), "127.0.0.1:8080".to_string())];
+        if !body.is_empty() {
+            headers.push(("Content-Type".to_string(), "application/json".to_string()));
+        }
+        Request {
+            method: method.to_string(),
+            path: percent_decode(path),
+            query: query
+                .split('&')
+                .filter_map(|pair| pair.split_once('='))
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            headers,
+            body: body.as_bytes().to_vec(),
+        }
+    }
+
+    fn json(response: &Response) -> serde_json::Value {
+        serde_json::from_str(&response.body).unwrap()
+    }
+
+    #[test]
+    fn test_task_routes() {
+        let store = TodoStore::open(":memory:").unwrap();
+        let options = ServerOptions {
+            token: None,
+            link_secret: None,
+            due_soon_window: chrono::Duration::hours(48),
+            hooks: None,
+            port: 8080,
+        };
+        let call = |method, path, body| handle(&store, &options, &request(method, path, body));
+
+        let created = call(
+            "POST",
+            "/tasks",
+            r#"{"title": "Buy milk", "priority": "high", "tags": ["errands"]}"#,
+        );
+        assert_eq!(created.status, 201);
+        assert_eq!(json(&created)["priority"], 2);
+        call("POST", "/tasks", r#"{"title": "Call mom"}"#);
+
+        let listed = call("GET", "/tasks?tag=errands", "");
+        assert_eq!(json(&listed).as_array().unwrap().len(), 1);
+
+        let patched = call(
+            "PATCH",
+            "/tasks/2",
+            r#"{"title": "Call dad", "completed": true}"#,
+        );
+        assert_eq!(json(&patched)["title"], "Call dad");
+        assert_eq!(json(&patched)["completed"], true);
+        assert_eq!(
+            json(&call("GET", "/tasks", "")).as_array().unwrap().len(),
+            1
+        );
+        assert_eq!(
+            json(&call("GET", "/tasks?status=all", ""))
+                .as_array()
+                .unwrap()
+                .len(),
+            2
+        );
+
+        assert_eq!(call("DELETE", "/tasks/1", "").status, 204);
+        assert_eq!(call("GET", "/tasks/1", "").status, 404);
+        assert_eq!(call("POST", "/tasks", r#"{"title": " "}"#).status, 400);
+        assert_eq!(call("POST", "/tasks", r#"{"name": "x"}"#).status, 400);
+        assert_eq!(call("DELETE", "/tasks", "").status, 405);
+        assert_eq!(call("GET", "/nothing", "").status, 404);
+        assert_eq!(
+            json(&call("PATCH", "/tasks/2", r#"{"priority": "urgent"}"#))["error"],
+            "Priority must be low, medium or high"
+        );
+    }
+
+    #[test]
+    fn test_cross_origin_changes_are_refused() {
+        let store = TodoStore::open(":memory:").unwrap();
+        let options = ServerOptions {
+            token: None,
+            link_secret: None,
+            due_soon_window: chrono::Duration::hours(48),
+            hooks: None,
+            port: 8080,
+        };
+        let from = |origin: &str, mut request: Request| {
+            request
+                .headers
+                .push(("Origin".to_string(), origin.to_string()));
+            handle(&store, &options, &request)
+        };
+        let add = || request("POST", "/tasks", r#"{"title": "Buy milk"}"#);
+
+        assert_eq!(from("https://evil.example", add()).status, 403);
+        assert_eq!(from("null", add()).status, 403);
+        assert!(store.list(&TaskFilter::default()).unwrap().is_empty());
+        assert_eq!(from("http://127.0.0.1:8080", add()).status, 201);
+        assert_eq!(
+            from("https://evil.example", request("GET", "/tasks", "")).status,
+            200
+        );
+
+        // A form or `fetch` without consent can only send text/plain and
+        // the like
+        let mut form = add();
+        form.headers[1].1 = "text/plain".to_string();
+        assert_eq!(handle(&store, &options, &form).status, 415);
+        form.headers.pop();
+        assert_eq!(handle(&store, &options, &form).status, 415);
+    }
+
+    #[test]
+    fn test_foreign_hosts_are_refused_without_a_token() {
+        let store = TodoStore::open(":memory:").unwrap();
+        let mut options = ServerOptions {
+            token: None,
+            link_secret: None,
+            due_soon_window: chrono::Duration::hours(48),
+            hooks: None,
+            port: 8080,
+        };
+        let at = |options: &ServerOptions, host: &str, mut request: Request| {
+            request.headers[0].1 = host.to_string();
+            request
+                .headers
+                .push(("Origin".to_string(), format!("http://{}", host)));
+            handle(&store, options, &request).status
+        };
+        let add = || request("POST", "/tasks", r#"{"title": "Buy milk"}"#);
+
+        // A rebound domain is its own origin, but not a loopback host
+        assert_eq!(at(&options, "evil.example:8080", add()), 403);
+        assert_eq!(
+            at(&options, "evil.example:8080", request("GET", "/tasks", "")),
+            403
+        );
+        assert_eq!(at(&options, "127.0.0.1:9999", add()), 403);
+        assert!(store.list(&TaskFilter::default()).unwrap().is_empty());
+        assert_eq!(at(&options, "localhost:8080", add()), 201);
+        assert_eq!(at(&options, "[::1]:8080", add()), 201);
+        let mut no_host = request("GET", "/tasks", "");
+        no_host.headers.clear();
+        assert_eq!(handle(&store, &options, &no_host).status, 403);
+
+        // With a token, the token is what keeps others out
+        options.token = Some("s3cret");
+        let mut listed = request("GET", "/tasks", "");
+        listed
+            .headers
+            .push(("Authorization".to_string(), "Bearer s3cret".to_string()));
+        assert_eq!(at(&options, "todo.example:8080", listed), 200);
+    }
+
+    #[test]
+    fn test_token_and_action_links() {
+        let store = TodoStore::open(":memory:").unwrap();
+        let id = store
+            .add(&Task::new("Pay rent".to_string(), None, None, 1))
+            .unwrap();
+        let options = ServerOptions {
+            token: Some("s3cret"),
+            link_secret: Some("link-secret".to_string()),
+            due_soon_window: chrono::Duration::hours(48),
+            hooks: None,
+            port: 8080,
+        };
+
+        // The page needs no token; the requests it makes do
+        let page = handle(&store, &options, &request("GET", "/", ""));
+        assert_eq!(page.status, 200);
+        assert!(page.body.contains("Authorization"));
+        assert!(!page.body.contains("Pay rent"));
+        let mut get = request("GET", "/tasks", "");
+        assert_eq!(handle(&store, &options, &get).status, 401);
+        get.headers
+            .push(("Authorization".to_string(), "Bearer s3cret".to_string()));
+        assert_eq!(handle(&store, &options, &get).status, 200);
+
+        let expires = Utc::now() + chrono::Duration::days(1);
+        let url = crate::signing::action_url("", "link-secret", id, LinkAction::Complete, expires);
+        // Opening the link only asks; the page's button does it
+        let asked = handle(&store, &options, &request("GET", &url, ""));
+        assert_eq!(asked.status, 200);
+        assert!(asked.body.contains("Mark “Pay rent” as done?"));
+        assert!(asked.body.contains("<form method=\"post\">"));
+        assert!(!store.get(id).unwrap().completed);
+        let done = handle(&store, &options, &request("POST", &url, ""));
+        assert_eq!(done.status, 200);
+        assert!(done.body.contains("Done: “Pay rent”"));
+        assert!(store.get(id).unwrap().completed);
+
+        let forged = url.replace("sig=", "sig=00");
+        assert_eq!(
+            handle(&store, &options, &request("GET", &forged, "")).status,
+            403
+        );
+    }
+
+    #[test]
+    fn test_share_links() {
+        let store = TodoStore::open(":memory:").unwrap();
+        let mut task = Task::new("Plan party".to_string(), None, None, 1);
+        task.project = Some("Home/Events".to_string());
+        let id = store.add(&task).unwrap();
+        let mut sub = Task::new("Book venue".to_string(), None, None, 1);
+        sub.parent_id = Some(id);
+        let sub = store.add(&sub).unwrap();
+        store.complete(sub).unwrap();
+        let options = ServerOptions {
+            token: Some("s3cret"),
+            link_secret: Some("link-secret".to_string()),
+            due_soon_window: chrono::Duration::hours(48),
+            hooks: None,
+            port: 8080,
+        };
+        let expires = Utc::now() + chrono::Duration::days(7);
+        let open = |shared: &Shared| {
+            let url = crate::signing::share_url("", "link-secret", shared, expires);
+            handle(&store, &options, &request("GET", &url, ""))
+        };
+
+        let page = open(&Shared::Task(id));
+        assert_eq!(page.status, 200);
+        assert!(page.body.contains("<h1>Plan party</h1>"));
+        assert!(page.body.contains("Book venue"));
+        assert!(page.body.contains("1 of 2 done"));
+
+        let page = open(&Shared::Project("Home/Events".to_string()));
+        assert_eq!(page.status, 200);
+        assert!(page.body.contains("<h1>Home/Events</h1>"));
+
+        // The signature covers what is shared and stays read-only
+        let url = crate::signing::share_url("", "link-secret", &Shared::Task(id), expires);
+        let other = url.replace(&format!("/task/{}?", id), &format!("/task/{}?", sub));
+        assert_eq!(
+            handle(&store, &options, &request("GET", &other, "")).status,
+            403
+        );
+        assert_eq!(
+            handle(&store, &options, &request("GET", "/share/task/1", "")).status,
+            403
+        );
+
+        store.delete(id).unwrap();
+        assert_eq!(open(&Shared::Task(id)).status, 404);
+    }
+
+    #[test]
+    fn test_schema_mismatch_asks_for_restart() {
+        let stale = rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(1),
+            Some("no such column: tasks.context".to_string()),
+        );
+        assert_eq!(ApiError::from(stale).0, 503);
+        let corrupt = rusqlite::Error::SqliteFailure(rusqlite::ffi::Error::new(11), None);
+        assert_eq!(ApiError::from(corrupt).0, 500);
+        let locked = rusqlite::Error::SqliteFailure(rusqlite::ffi::Error::new(5), None);
+        assert_eq!(ApiError::from(locked).0, 503);
+    }
+
+    #[test]
+    fn test_read_request() {
+        let raw = "POST /tasks?tag=home%2Fgarden&q=a+b HTTP/1.1\r\nHost: x\r\nContent-Length: 17\r\n\r\n{\"title\": \"Mow\"}\n";
+        let request = read_request(&mut std::io::Cursor::new(raw)).unwrap();
+        assert_eq!(
+            (request.method.as_str(), request.path.as_str()),
+            ("POST", "/tasks")
+        );
+        assert_eq!(request.query("tag"), Some("home/garden"));
+        assert_eq!(request.query("q"), Some("a b"));
+        assert_eq!(request.header("content-length"), Some("17"));
+        assert_eq!(request.body, b"{\"title\": \"Mow\"}\n");
+    }
+}
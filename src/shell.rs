@@ -0,0 +1,260 @@
+use clap::CommandFactory;
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum Shell {
+    Bash,
+    Zsh,
+}
+
+/// Build the shell integration script. The binary name and the short alias
+/// of `add` are read from the CLI definition; `status-line` is used as is.
+pub fn init_script(shell: Shell) -> String {
+    let cli = crate::Cli::command();
+    let bin = cli.get_name().to_string();
+    let capture = cli
+        .find_subcommand("add")
+        .and_then(|add| add.get_visible_aliases().next())
+        .unwrap_or("add")
+        .to_string();
+    let status = "status-line";
+
+    match shell {
+        Shell::Zsh => format!(
+            r#"# {bin} zsh integration. Add to ~/.zshrc:
+#   eval "$({bin} shell-init zsh)"
+
+# Ctrl-T: capture a task without leaving the command line
+__todo_capture() {{
+  local REPLY
+  autoload -Uz read-from-minibuffer
+  read-from-minibuffer '{bin}> '
+  if [[ -n $REPLY ]]; then
+    zle -I
+    command {bin} {capture} "$REPLY"
+  fi
+  zle reset-prompt
+}}
+zle -N __todo_capture
+bindkey '^T' __todo_capture
+
+# Prompt segment with open/overdue counts, e.g.:
+#   setopt prompt_subst
+#   RPROMPT='$(todo_prompt_info)'
+todo_prompt_info() {{
+  command {bin} {status} 2>/dev/null
+}}
+"#
+        ),
+        Shell::Bash => format!(
+            r#"# {bin} bash integration. Add to ~/.bashrc:
+#   eval "$({bin} shell-init bash)"
+
+# Ctrl-T: capture a task without leaving the command line
+__todo_capture() {{
+  local task
+  read -r -e -p '{bin}> ' task
+  if [[ -n $task ]]; then
+    command {bin} {capture} "$task"
+  fi
+}}
+bind -x '"\C-t": __todo_capture'
+
+# Prompt segment with open/overdue counts, e.g.:
+#   PS1='$(todo_prompt_info) '"$PS1"
+todo_prompt_info() {{
+  command {bin} {status} 2>/dev/null
+}}
+"#
+        ),
+    }
+}
+
+/// Commands whose arguments are task IDs, completed from the database.
+const TASK_ID_COMMANDS: [&str; 3] = ["complete", "delete", "show"];
+
+/// What `todo completions` prints.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Completions {
+    Bash,
+    Zsh,
+    Fish,
+    #[value(name = "powershell")]
+    PowerShell,
+    Elvish,
+    /// Pending tasks as `ID<tab>title`, which the scripts call on Tab.
+    #[value(hide = true)]
+    Ids,
+    /// Tags in use, one per line, for `--tag` and `--not-tag`.
+    #[value(hide = true)]
+    Tags,
+    /// Project names, one per line, for `--project` and `--not-project`.
+    #[value(hide = true)]
+    Projects,
+}
+
+impl Completions {
+    /// The shell to generate a script for; `None` for the lists the
+    /// scripts ask for.
+    pub fn shell(&self) -> Option<clap_complete::Shell> {
+        Some(match self {
+            Completions::Bash => clap_complete::Shell::Bash,
+            Completions::Zsh => clap_complete::Shell::Zsh,
+            Completions::Fish => clap_complete::Shell::Fish,
+            Completions::PowerShell => clap_complete::Shell::PowerShell,
+            Completions::Elvish => clap_complete::Shell::Elvish,
+            Completions::Ids | Completions::Tags | Completions::Projects => return None,
+        })
+    }
+}
+
+/// Completion script for `shell`, generated from the CLI definition. For
+/// bash, zsh and fish it also completes the task IDs of `complete`,
+/// `delete` and `show` by asking `todo completions ids`, and the values of
+/// `--tag` and `--project` (and their `--not-` forms) from `todo
+/// completions tags` and `projects`; the other shells only get the static
+/// script.
+pub fn completion_script(shell: clap_complete::Shell) -> String {
+    let mut cli = crate::Cli::command();
+    let bin = cli.get_name().to_string();
+    let mut out = Vec::new();
+    clap_complete::generate(shell, &mut cli, bin.clone(), &mut out);
+    let mut script = String::from_utf8_lossy(&out).into_owned();
+
+    // Aliases complete task IDs too
+    let commands: Vec<String> = TASK_ID_COMMANDS
+        .iter()
+        .filter_map(|name| cli.find_subcommand(name))
+        .flat_map(|cmd| std::iter::once(cmd.get_name()).chain(cmd.get_visible_aliases()))
+        .map(str::to_string)
+        .collect();
+    let function = format!("_{}", bin.replace('-', "__"));
+    let ids = "completions ids";
+    let dynamic = match shell {
+        clap_complete::Shell::Bash => format!(
+            r#"
+# Task IDs for {list}, and tag and project names
+{function}_task_ids() {{
+  local cur="${{COMP_WORDS[COMP_CWORD]}}" prev="${{COMP_WORDS[COMP_CWORD-1]}}"
+  case "$prev" in
+    --tag|--not-tag)
+      COMPREPLY=($(compgen -W "$(command {bin} completions tags 2>/dev/null)" -- "$cur"))
+      return 0;;
+    --project|--not-project)
+      COMPREPLY=($(compgen -W "$(command {bin} completions projects 2>/dev/null)" -- "$cur"))
+      return 0;;
+  esac
+  if [[ $COMP_CWORD -ge 2 && $cur != -* && $prev != -* ]]; then
+    case "${{COMP_WORDS[1]}}" in
+      {cases})
+        COMPREPLY=($(compgen -W "$(command {bin} {ids} 2>/dev/null | cut -f1)" -- "$cur"))
+        return 0;;
+    esac
+  fi
+  {function} "$@"
+}}
+complete -F {function}_task_ids -o bashdefault -o default {bin}
+"#,
+            list = commands.join(", "),
+            cases = commands.join("|"),
+        ),
+        clap_complete::Shell::Zsh => format!(
+            r#"
+# Task IDs, with their titles, for {list}, and tag and project names
+{function}_task_ids() {{
+  local -a tasks
+  tasks=(${{(f)"$(command {bin} {ids} 2>/dev/null)"}})
+  tasks=(${{tasks/$'	'/:}})
+  _describe -t tasks 'task' tasks
+}}
+{function}_names() {{
+  local -a names
+  names=(${{(f)"$(command {bin} completions $1 2>/dev/null)"}})
+  _describe -t $1 ${{1%s}} names
+}}
+{function}_with_task_ids() {{
+  case $words[CURRENT-1] in
+    --tag|--not-tag)
+      {function}_names tags
+      return;;
+    --project|--not-project)
+      {function}_names projects
+      return;;
+  esac
+  if (( CURRENT > 2 )) && [[ $words[CURRENT] != -* && $words[CURRENT-1] != -* ]]; then
+    case $words[2] in
+      {cases})
+        {function}_task_ids
+        return;;
+    esac
+  fi
+  {function} "$@"
+}}
+compdef {function}_with_task_ids {bin}
+"#,
+            list = commands.join(", "),
+            cases = commands.join("|"),
+        ),
+        clap_complete::Shell::Fish => format!(
+            r#"
+# Task IDs, with their titles, for {list}, and tag and project names
+complete -c {bin} -n "__fish_seen_subcommand_from {names}" -f -a "(command {bin} {ids} 2>/dev/null)"
+complete -c {bin} -l tag -l not-tag -f -r -a "(command {bin} completions tags 2>/dev/null)"
+complete -c {bin} -l project -l not-project -f -r -a "(command {bin} completions projects 2>/dev/null)"
+"#,
+            list = commands.join(", "),
+            names = commands.join(" "),
+        ),
+        _ => String::new(),
+    };
+    script.push_str(&dynamic);
+    script
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zsh_script_uses_cli_names() {
+        let script = init_script(Shell::Zsh);
+        assert!(script.contains("command todo a \"$REPLY\""));
+        assert!(script.contains("bindkey '^T' __todo_capture"));
+        assert!(script.contains("command todo status-line"));
+    }
+
+    #[test]
+    fn test_bash_script_binds_widget() {
+        let script = init_script(Shell::Bash);
+        assert!(script.contains(r#"bind -x '"\C-t": __todo_capture'"#));
+        assert!(script.contains("command todo a \"$task\""));
+    }
+
+    #[test]
+    fn test_completion_scripts_complete_task_ids() {
+        let cli = crate::Cli::command();
+        for name in TASK_ID_COMMANDS {
+            assert!(cli.find_subcommand(name).is_some(), "no `{}` command", name);
+        }
+
+        let bash = completion_script(clap_complete::Shell::Bash);
+        assert!(bash.contains("_todo()"));
+        assert!(bash.contains("complete|delete|show)"));
+        assert!(bash.contains("complete -F _todo_task_ids"));
+
+        let zsh = completion_script(clap_complete::Shell::Zsh);
+        assert!(zsh.contains("#compdef todo"));
+        assert!(zsh.contains("compdef _todo_with_task_ids todo"));
+
+        assert!(bash.contains("--tag|--not-tag)"));
+        assert!(zsh.contains("_todo_names projects"));
+
+        let fish = completion_script(clap_complete::Shell::Fish);
+        assert!(fish.contains("__fish_seen_subcommand_from complete delete show"));
+        assert!(fish
+            .contains("-l project -l not-project -f -r -a \"(command todo completions projects"));
+
+        let powershell = completion_script(clap_complete::Shell::PowerShell);
+        assert!(powershell.contains("Register-ArgumentCompleter"));
+        assert!(!powershell.contains("completions ids"));
+    }
+}
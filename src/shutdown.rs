@@ -0,0 +1,142 @@
+//! Graceful handling of SIGINT and SIGTERM for long-running commands.
+//!
+//! While a `Listener` is alive, the first signal only sets a flag: the
+//! command finishes its current step (e.g. the import chunk being committed)
+//! and stops at its next `check`. A second signal exits immediately. When the
+//! listener is dropped the previous handlers come back, and a signal that no
+//! `check` reported is re-raised so the process still ends the way the user
+//! asked, after the in-flight work is done.
+
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+
+/// The signal received while listening, or 0.
+static RECEIVED: AtomicI32 = AtomicI32::new(0);
+/// Whether `check` already turned the signal into an error.
+static REPORTED: AtomicBool = AtomicBool::new(false);
+
+/// Returned by `check` once a shutdown was requested.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Interrupted {
+    pub signal: i32,
+}
+
+impl Interrupted {
+    /// The conventional shell exit code for death by `signal`.
+    pub fn exit_code(&self) -> i32 {
+        128 + self.signal
+    }
+}
+
+impl std::fmt::Display for Interrupted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.signal {
+            #[cfg(unix)]
+            libc::SIGINT => write!(f, "Interrupted"),
+            #[cfg(unix)]
+            libc::SIGTERM => write!(f, "Terminated"),
+            signal => write!(f, "Stopped by signal {}", signal),
+        }
+    }
+}
+
+impl std::error::Error for Interrupted {}
+
+/// Fails once SIGINT or SIGTERM arrived while a `Listener` was alive. Long
+/// loops call this between steps that are safe to stop after.
+pub fn check() -> Result<(), Interrupted> {
+    match RECEIVED.load(Ordering::SeqCst) {
+        0 => Ok(()),
+        signal => {
+            REPORTED.store(true, Ordering::SeqCst);
+            Err(Interrupted { signal })
+        }
+    }
+}
+
+/// Defers SIGINT and SIGTERM until dropped; see the module docs.
+pub struct Listener {
+    #[cfg(unix)]
+    previous: Vec<(libc::c_int, libc::sigaction)>,
+}
+
+#[cfg(unix)]
+const SIGNALS: [libc::c_int; 2] = [libc::SIGINT, libc::SIGTERM];
+
+#[cfg(unix)]
+extern "C" fn on_signal(signal: libc::c_int) {
+    if RECEIVED.swap(signal, Ordering::SeqCst) != 0 {
+        // SAFETY: _exit is async-signal-safe
+        unsafe { libc::_exit(128 + signal) };
+    }
+    let message = b"\nStopping after the current step (press Ctrl-C again to quit now)\n";
+    // SAFETY: write is async-signal-safe and the buffer outlives the call
+    unsafe { libc::write(libc::STDERR_FILENO, message.as_ptr().cast(), message.len()) };
+}
+
+/// Start deferring SIGINT and SIGTERM.
+pub fn listen() -> Listener {
+    RECEIVED.store(0, Ordering::SeqCst);
+    REPORTED.store(false, Ordering::SeqCst);
+
+    #[cfg(unix)]
+    {
+        let mut previous = Vec::new();
+        for signal in SIGNALS {
+            // SAFETY: both structs are fully initialized before use, and the
+            // handler only touches atomics and async-signal-safe calls.
+            unsafe {
+                let mut action: libc::sigaction = std::mem::zeroed();
+                action.sa_sigaction = on_signal as extern "C" fn(libc::c_int) as usize;
+                action.sa_flags = libc::SA_RESTART;
+                libc::sigemptyset(&mut action.sa_mask);
+                let mut old: libc::sigaction = std::mem::zeroed();
+                if libc::sigaction(signal, &action, &mut old) == 0 {
+                    previous.push((signal, old));
+                }
+            }
+        }
+        Listener { previous }
+    }
+    #[cfg(not(unix))]
+    Listener {}
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        {
+            for (signal, old) in &self.previous {
+                // SAFETY: restores the action saved by `listen`
+                unsafe { libc::sigaction(*signal, old, std::ptr::null_mut()) };
+            }
+            let signal = RECEIVED.swap(0, Ordering::SeqCst);
+            if signal != 0 && !REPORTED.load(Ordering::SeqCst) {
+                // SAFETY: raise has no preconditions; the previous (usually
+                // default) action now handles the signal.
+                unsafe { libc::raise(signal) };
+            }
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signal_is_deferred_until_checked() {
+        let listener = listen();
+        assert_eq!(check(), Ok(()));
+
+        // SAFETY: the listener's handler is installed
+        unsafe { libc::raise(libc::SIGTERM) };
+        let interrupted = check().unwrap_err();
+        assert_eq!(interrupted.signal, libc::SIGTERM);
+        assert_eq!(interrupted.exit_code(), 143);
+        assert_eq!(interrupted.to_string(), "Terminated");
+
+        // Reported, so dropping the listener doesn't re-raise it
+        drop(listener);
+        assert_eq!(check(), Ok(()));
+    }
+}
@@ -0,0 +1,244 @@
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Actions that can be triggered from a signed one-click link.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LinkAction {
+    Complete,
+    Snooze,
+}
+
+impl LinkAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LinkAction::Complete => "complete",
+            LinkAction::Snooze => "snooze",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "complete" => Some(LinkAction::Complete),
+            "snooze" => Some(LinkAction::Snooze),
+            _ => None,
+        }
+    }
+}
+
+fn payload(task_id: i32, action: LinkAction, expires: DateTime<Utc>) -> String {
+    format!("{}:{}:{}", task_id, action.as_str(), expires.timestamp())
+}
+
+fn mac(secret: &str, payload: &str) -> HmacSha256 {
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(payload.as_bytes());
+    mac
+}
+
+/// Check a signature over `payload` in constant time, rejecting it once
+/// `expires` has passed.
+fn verify_payload(secret: &str, payload: &str, expires: DateTime<Utc>, signature: &str) -> bool {
+    if Utc::now() > expires {
+        return false;
+    }
+    let Ok(signature) = hex::decode(signature) else {
+        return false;
+    };
+    mac(secret, payload).verify_slice(&signature).is_ok()
+}
+
+/// Hex HMAC-SHA256 over the link payload.
+pub fn sign(secret: &str, task_id: i32, action: LinkAction, expires: DateTime<Utc>) -> String {
+    hex::encode(
+        mac(secret, &payload(task_id, action, expires))
+            .finalize()
+            .into_bytes(),
+    )
+}
+
+/// Check a signature in constant time and reject expired links.
+pub fn verify(
+    secret: &str,
+    task_id: i32,
+    action: LinkAction,
+    expires: DateTime<Utc>,
+    signature: &str,
+) -> bool {
+    verify_payload(
+        secret,
+        &payload(task_id, action, expires),
+        expires,
+        signature,
+    )
+}
+
+/// Build `{base}/actions/{id}/{action}?expires=..&sig=..`.
+pub fn action_url(
+    base_url: &str,
+    secret: &str,
+    task_id: i32,
+    action: LinkAction,
+    expires: DateTime<Utc>,
+) -> String {
+    format!(
+        "{}/actions/{}/{}?expires={}&sig={}",
+        base_url.trim_end_matches('/'),
+        task_id,
+        action.as_str(),
+        expires.timestamp(),
+        sign(secret, task_id, action, expires)
+    )
+}
+
+/// What a read-only `todo share` link shows.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Shared {
+    Task(i32),
+    Project(String),
+}
+
+impl Shared {
+    /// The two path segments after `/share/`, such as `task/12`.
+    pub fn parse(kind: &str, value: &str) -> Option<Self> {
+        match kind {
+            "task" => value.parse().ok().map(Shared::Task),
+            "project" if !value.is_empty() => Some(Shared::Project(value.to_string())),
+            _ => None,
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            Shared::Task(_) => "task",
+            Shared::Project(_) => "project",
+        }
+    }
+
+    fn value(&self) -> String {
+        match self {
+            Shared::Task(id) => id.to_string(),
+            Shared::Project(name) => name.clone(),
+        }
+    }
+
+    /// Prefixed so a share signature can never pass for an action link's.
+    fn payload(&self, expires: DateTime<Utc>) -> String {
+        format!(
+            "share:{}:{}:{}",
+            self.kind(),
+            self.value(),
+            expires.timestamp()
+        )
+    }
+}
+
+pub fn sign_share(secret: &str, shared: &Shared, expires: DateTime<Utc>) -> String {
+    hex::encode(
+        mac(secret, &shared.payload(expires))
+            .finalize()
+            .into_bytes(),
+    )
+}
+
+pub fn verify_share(
+    secret: &str,
+    shared: &Shared,
+    expires: DateTime<Utc>,
+    signature: &str,
+) -> bool {
+    verify_payload(secret, &shared.payload(expires), expires, signature)
+}
+
+/// `%XX` for every byte but letters, digits and `-._~`, so a project name
+/// fits in one path segment.
+fn percent_encode(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Build `{base}/share/{task|project}/{id or name}?expires=..&sig=..`.
+pub fn share_url(base_url: &str, secret: &str, shared: &Shared, expires: DateTime<Utc>) -> String {
+    format!(
+        "{}/share/{}/{}?expires={}&sig={}",
+        base_url.trim_end_matches('/'),
+        shared.kind(),
+        percent_encode(&shared.value()),
+        expires.timestamp(),
+        sign_share(secret, shared, expires)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_sign_and_verify() {
+        let expires = Utc::now() + Duration::days(7);
+        let sig = sign("secret", 12, LinkAction::Complete, expires);
+
+        assert!(verify("secret", 12, LinkAction::Complete, expires, &sig));
+        assert!(!verify("other", 12, LinkAction::Complete, expires, &sig));
+        assert!(!verify("secret", 13, LinkAction::Complete, expires, &sig));
+        assert!(!verify("secret", 12, LinkAction::Snooze, expires, &sig));
+        assert!(!verify("secret", 12, LinkAction::Complete, expires, "zz"));
+    }
+
+    #[test]
+    fn test_expired_links_are_rejected() {
+        let expires = Utc::now() - Duration::minutes(1);
+        let sig = sign("secret", 1, LinkAction::Snooze, expires);
+        assert!(!verify("secret", 1, LinkAction::Snooze, expires, &sig));
+    }
+
+    #[test]
+    fn test_action_url() {
+        let expires = DateTime::from_timestamp(4_000_000_000, 0).unwrap();
+        let url = action_url(
+            "http://nas:8080/",
+            "secret",
+            5,
+            LinkAction::Complete,
+            expires,
+        );
+        assert!(url.starts_with("http://nas:8080/actions/5/complete?expires=4000000000&sig="));
+        assert_eq!(LinkAction::parse("snooze"), Some(LinkAction::Snooze));
+        assert_eq!(LinkAction::parse("delete"), None);
+    }
+
+    #[test]
+    fn test_share_links() {
+        let expires = Utc::now() + Duration::days(7);
+        let project = Shared::Project("Home & garden".to_string());
+        let sig = sign_share("secret", &project, expires);
+        assert!(verify_share("secret", &project, expires, &sig));
+        assert!(!verify_share(
+            "secret",
+            &Shared::Project("Home".to_string()),
+            expires,
+            &sig
+        ));
+        assert!(!verify_share("secret", &Shared::Task(3), expires, &sig));
+
+        // An action link's signature doesn't open a share page, or the reverse
+        let action = sign("secret", 3, LinkAction::Complete, expires);
+        assert!(!verify_share("secret", &Shared::Task(3), expires, &action));
+
+        let url = share_url("http://nas:8080/", "secret", &project, expires);
+        assert!(url.starts_with("http://nas:8080/share/project/Home%20%26%20garden?expires="));
+        assert_eq!(Shared::parse("task", "12"), Some(Shared::Task(12)));
+        assert_eq!(Shared::parse("task", "twelve"), None);
+        assert_eq!(Shared::parse("goal", "1"), None);
+    }
+}
@@ -0,0 +1,171 @@
+use crate::html::escape;
+use crate::models::Task;
+
+const COLUMN_WIDTH: usize = 240;
+const COLUMN_GAP: usize = 16;
+const HEADER_HEIGHT: usize = 40;
+const CARD_HEIGHT: usize = 48;
+const CARD_GAP: usize = 8;
+const MARGIN: usize = 16;
+const TITLE_CHARS: usize = 30;
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum GroupBy {
+    Status,
+    Priority,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum SnapshotFormat {
+    Svg,
+}
+
+/// Split tasks into named board columns, keeping empty columns so boards
+/// have a stable shape.
+pub fn group(tasks: &[Task], group_by: GroupBy) -> Vec<(String, Vec<&Task>)> {
+    match group_by {
+        GroupBy::Status => vec![
+            (
+                "Pending".to_string(),
+                tasks.iter().filter(|t| !t.completed).collect(),
+            ),
+            (
+                "Completed".to_string(),
+                tasks.iter().filter(|t| t.completed).collect(),
+            ),
+        ],
+        GroupBy::Priority => [(2, "High"), (1, "Medium"), (0, "Low")]
+            .into_iter()
+            .map(|(priority, name)| {
+                (
+                    name.to_string(),
+                    tasks.iter().filter(|t| t.priority == priority).collect(),
+                )
+            })
+            .collect(),
+    }
+}
+
+fn priority_fill(priority: i32) -> &'static str {
+    match priority {
+        0 => "#1e6bd6",
+        2 => "#d62e2e",
+        _ => "#b58900",
+    }
+}
+
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        text.to_string()
+    } else {
+        let cut: String = text.chars().take(max - 1).collect();
+        format!("{}…", cut)
+    }
+}
+
+/// Render a board as a self-contained SVG document.
+pub fn render_svg(tasks: &[Task], group_by: GroupBy) -> String {
+    let columns = group(tasks, group_by);
+    let tallest = columns.iter().map(|(_, c)| c.len()).max().unwrap_or(0);
+    let width = MARGIN * 2 + columns.len() * COLUMN_WIDTH + (columns.len() - 1) * COLUMN_GAP;
+    let height = MARGIN * 2 + HEADER_HEIGHT + tallest.max(1) * (CARD_HEIGHT + CARD_GAP);
+
+    let mut svg = format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="sans-serif">
+<rect width="100%" height="100%" fill="#ffffff"/>
+"##,
+        w = width,
+        h = height
+    );
+
+    for (index, (name, cards)) in columns.iter().enumerate() {
+        let x = MARGIN + index * (COLUMN_WIDTH + COLUMN_GAP);
+        svg.push_str(&format!(
+            r##"<rect x="{x}" y="{y}" width="{cw}" height="{ch}" rx="6" fill="#f4f5f7"/>
+<text x="{tx}" y="{ty}" font-size="14" font-weight="bold" fill="#333">{name} ({count})</text>
+"##,
+            x = x,
+            y = MARGIN,
+            cw = COLUMN_WIDTH,
+            ch = height - MARGIN * 2,
+            tx = x + 10,
+            ty = MARGIN + 24,
+            name = escape(name),
+            count = cards.len()
+        ));
+
+        for (row, task) in cards.iter().enumerate() {
+            let y = MARGIN + HEADER_HEIGHT + row * (CARD_HEIGHT + CARD_GAP);
+            svg.push_str(&format!(
+                r##"<rect x="{x}" y="{y}" width="{w}" height="{h}" rx="4" fill="#ffffff" stroke="#dddddd"/>
+<rect x="{x}" y="{y}" width="4" height="{h}" fill="{fill}"/>
+<text x="{tx}" y="{ty}" font-size="12" fill="#222">{title}</text>
+<text x="{tx}" y="{dy}" font-size="10" fill="#888">#{id} · {due}</text>
+"##,
+                x = x + 8,
+                y = y,
+                w = COLUMN_WIDTH - 16,
+                h = CARD_HEIGHT,
+                fill = priority_fill(task.priority),
+                tx = x + 18,
+                ty = y + 20,
+                dy = y + 37,
+                title = escape(&truncate(&task.title, TITLE_CHARS)),
+                id = task.id.unwrap_or(0),
+                due = escape(&task.due_date_text())
+            ));
+        }
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_tasks() -> Vec<Task> {
+        let mut done = Task::new("Done & dusted".to_string(), None, None, 0);
+        done.completed = true;
+        vec![
+            Task::new("Write <report>".to_string(), None, None, 2),
+            Task::new("Buy milk".to_string(), None, None, 1),
+            done,
+        ]
+    }
+
+    #[test]
+    fn test_group_by_status() {
+        let tasks = sample_tasks();
+        let columns = group(&tasks, GroupBy::Status);
+        assert_eq!(columns[0].0, "Pending");
+        assert_eq!(columns[0].1.len(), 2);
+        assert_eq!(columns[1].1.len(), 1);
+    }
+
+    #[test]
+    fn test_group_by_priority_keeps_empty_columns() {
+        let tasks = vec![Task::new("Only high".to_string(), None, None, 2)];
+        let columns = group(&tasks, GroupBy::Priority);
+        let names: Vec<&str> = columns.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, vec!["High", "Medium", "Low"]);
+        assert_eq!(columns[2].1.len(), 0);
+    }
+
+    #[test]
+    fn test_render_svg() {
+        let svg = render_svg(&sample_tasks(), GroupBy::Status);
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        assert!(svg.contains("Pending (2)"));
+        assert!(svg.contains("Write &lt;report&gt;"));
+        assert!(svg.contains("Done &amp; dusted"));
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("short", 10), "short");
+        assert_eq!(truncate("a very long title", 6), "a ver…");
+    }
+}
@@ -0,0 +1,190 @@
+//! User-written SQL expressions, such as the one behind
+//! `todo list --group-by-expr "strftime('%Y-%W', due_date)"`. They are
+//! pasted into a query, so they are checked token by token first: only
+//! task columns, the functions in `FUNCTIONS`, the keywords in `KEYWORDS`,
+//! literals and operators may appear, plus the functions of any SQLite
+//! extensions loaded through `sqlite_extensions`. Statements, subqueries,
+//! comments, parameters and quoted identifiers can't be written.
+
+use anyhow::Result;
+
+/// Columns an expression can read; the same names as in JSON output.
+pub const COLUMNS: &[&str] = &[
+    "id",
+    "title",
+    "description",
+    "due_date",
+    "priority",
+    "completed",
+    "created_at",
+    "updated_at",
+    "tags",
+    "someday",
+    "project",
+    "recurrence",
+    "parent_id",
+    "in_progress",
+    "energy",
+];
+
+/// Scalar SQLite functions without side effects.
+pub const FUNCTIONS: &[&str] = &[
+    "abs",
+    "coalesce",
+    "date",
+    "datetime",
+    "ifnull",
+    "iif",
+    "instr",
+    "julianday",
+    "length",
+    "lower",
+    "ltrim",
+    "max",
+    "min",
+    "nullif",
+    "printf",
+    "replace",
+    "round",
+    "rtrim",
+    "strftime",
+    "substr",
+    "time",
+    "trim",
+    "unixepoch",
+    "upper",
+];
+
+const KEYWORDS: &[&str] = &[
+    "and", "as", "between", "case", "cast", "else", "end", "glob", "in", "integer", "is", "like",
+    "not", "null", "or", "real", "text", "then", "when",
+];
+
+const MAX_LENGTH: usize = 500;
+
+/// Check that `expr` only uses what the module docs allow;
+/// `extra_functions` are those added by extensions.
+pub fn validate(expr: &str, extra_functions: &[String]) -> Result<()> {
+    let fail = |reason: String| Err(anyhow::anyhow!("Invalid expression: {}", reason));
+    if expr.trim().is_empty() {
+        return fail("it is empty".to_string());
+    }
+    if expr.len() > MAX_LENGTH {
+        return fail(format!("it is longer than {} characters", MAX_LENGTH));
+    }
+
+    let chars: Vec<char> = expr.chars().collect();
+    let mut depth = 0usize;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            _ if c.is_whitespace() => i += 1,
+            '\'' => {
+                // A string literal; '' is an escaped quote
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None => return fail("a string is not closed".to_string()),
+                        Some('\'') if chars.get(i + 1) == Some(&'\'') => i += 2,
+                        Some('\'') => break,
+                        Some(_) => i += 1,
+                    }
+                }
+                i += 1;
+            }
+            '0'..='9' | '.' => {
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '.') {
+                    i += 1;
+                }
+            }
+            _ if c.is_ascii_alphabetic() || c == '_' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                let word = chars[start..i].iter().collect::<String>().to_lowercase();
+                let next = chars[i..].iter().find(|c| !c.is_whitespace());
+                let allowed = if next == Some(&'(') {
+                    FUNCTIONS.contains(&word.as_str())
+                        || KEYWORDS.contains(&word.as_str())
+                        || extra_functions.contains(&word)
+                } else {
+                    COLUMNS.contains(&word.as_str()) || KEYWORDS.contains(&word.as_str())
+                };
+                if !allowed {
+                    return fail(format!(
+                        "`{}` is not an allowed column or function (columns: {}; functions: {})",
+                        word,
+                        COLUMNS.join(", "),
+                        FUNCTIONS
+                            .iter()
+                            .copied()
+                            .chain(extra_functions.iter().map(String::as_str))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ));
+                }
+            }
+            '-' if chars.get(i + 1) == Some(&'-') => {
+                return fail("comments are not allowed".to_string())
+            }
+            '/' if chars.get(i + 1) == Some(&'*') => {
+                return fail("comments are not allowed".to_string())
+            }
+            '(' => {
+                depth += 1;
+                i += 1;
+            }
+            ')' => {
+                if depth == 0 {
+                    return fail("unbalanced parentheses".to_string());
+                }
+                depth -= 1;
+                i += 1;
+            }
+            '+' | '-' | '*' | '/' | '%' | '<' | '>' | '=' | '!' | '|' | ',' => i += 1,
+            _ => return fail(format!("`{}` is not allowed", c)),
+        }
+    }
+    if depth != 0 {
+        return fail("unbalanced parentheses".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        for ok in [
+            "strftime('%Y-%W', due_date)",
+            "coalesce(project, 'none') || ' / ' || priority",
+            "CASE WHEN due_date IS NULL THEN 'someday' ELSE substr(due_date, 1, 7) END",
+            "priority >= 1 AND NOT completed",
+            "'it''s'",
+        ] {
+            assert!(validate(ok, &[]).is_ok(), "{}", ok);
+        }
+        for bad in [
+            "",
+            "load_extension('x')",
+            "(SELECT 1)",
+            "due_date; DROP TABLE tasks",
+            "priority -- comment",
+            "\"priority\"",
+            "password",
+            "strftime('%Y', due_date",
+            "'open",
+            "?1",
+        ] {
+            assert!(validate(bad, &[]).is_err(), "{}", bad);
+        }
+
+        let extra = ["editdist3".to_string()];
+        assert!(validate("editdist3(title, 'invoice') < 200", &[]).is_err());
+        assert!(validate("editdist3(title, 'invoice') < 200", &extra).is_ok());
+    }
+}
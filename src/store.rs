@@ -0,0 +1,305 @@
+//! `TodoStore`: the task database behind `todo` for other programs. It
+//! checks its input the way the CLI does, journals every change for `todo
+//! undo`, and returns [`Error`] rather than printing.
+
+use std::path::Path;
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::db::{Database, DueRange, SomedayFilter, StatusFilter, TaskFilter, TaskOrder};
+use crate::models::{Note, Operation, Task};
+
+/// Why a store call failed; the same errors the CLI exits with.
+pub use crate::error::TodoError as Error;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+fn invalid(error: anyhow::Error) -> Error {
+    match error.downcast::<Error>() {
+        Ok(error) => error,
+        Err(error) => Error::Invalid(format!("{:#}", error)),
+    }
+}
+
+/// A task database: open one with [`TodoStore::open`], or wrap a
+/// [`Database`] you already have.
+pub struct TodoStore {
+    db: Database,
+}
+
+impl TodoStore {
+    /// Open the database at `path`, creating or migrating it as needed.
+    /// `:memory:` gives a throwaway database.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let db = Database::new(path.as_ref())?;
+        db.init()?;
+        Ok(Self { db })
+    }
+
+    /// Wrap an open, initialised database.
+    pub fn from_database(db: Database) -> Self {
+        Self { db }
+    }
+
+    /// The database underneath, for what the store does not cover.
+    pub fn database(&self) -> &Database {
+        &self.db
+    }
+
+    /// Pick up a schema migration made by another process; see
+    /// [`Database::reopen_if_migrated`]. Returns the new schema version.
+    pub fn reopen_if_migrated(&mut self) -> Result<Option<i64>> {
+        Ok(self.db.reopen_if_migrated()?)
+    }
+
+    /// Add `task` and return its ID. The title must not be blank; tags and
+    /// the project are normalised like `todo add` does, and a parent must
+    /// exist.
+    pub fn add(&self, task: &Task) -> Result<i32> {
+        let task = self.checked(task)?;
+        if let Some(parent) = task.parent_id {
+            self.get(parent)?;
+        }
+        Ok(self.db.add_task(&task)?)
+    }
+
+    /// The task with `id`, completed or not.
+    pub fn get(&self, id: i32) -> Result<Task> {
+        self.db.get_task_by_id(id)?.ok_or(Error::NotFound(id))
+    }
+
+    /// Tasks matching `filter`, in the order `todo list` shows them.
+    pub fn list(&self, filter: &TaskFilter) -> Result<Vec<Task>> {
+        Ok(self.db.get_tasks(filter)?)
+    }
+
+    /// Replace the task with `id` by `task`, checked like [`TodoStore::add`].
+    pub fn update(&self, id: i32, task: &Task) -> Result<()> {
+        self.get(id)?;
+        let task = self.checked(task)?;
+        Ok(self.db.edit_task(id, &task)?)
+    }
+
+    /// Complete the task with `id`. A repeating task gets its next
+    /// occurrence, whose ID is returned.
+    pub fn complete(&self, id: i32) -> Result<Option<i32>> {
+        self.get(id)?;
+        Ok(self.db.complete_task(id)?)
+    }
+
+    /// Move the task with `id` and its subtasks to the trash.
+    pub fn delete(&self, id: i32) -> Result<()> {
+        self.get(id)?;
+        Ok(self.db.delete_task(id)?)
+    }
+
+    /// Bring the task with `id` back from the trash.
+    pub fn restore(&self, id: i32) -> Result<()> {
+        if self.db.restore_task(id)? {
+            Ok(())
+        } else {
+            Err(Error::NotFound(id))
+        }
+    }
+
+    pub fn add_note(&self, id: i32, text: &str) -> Result<()> {
+        if text.trim().is_empty() {
+            return Err(Error::Invalid("Note text cannot be empty".to_string()));
+        }
+        self.get(id)?;
+        Ok(self.db.add_note(id, text)?)
+    }
+
+    pub fn notes(&self, id: i32) -> Result<Vec<Note>> {
+        self.get(id)?;
+        Ok(self.db.get_notes(id)?)
+    }
+
+    /// Reverse the last change, as `todo undo` does. Returns what was
+    /// undone; empty when there was nothing left.
+    pub fn undo(&self) -> Result<Vec<Operation>> {
+        Ok(self.db.undo_last()?)
+    }
+
+    fn checked(&self, task: &Task) -> Result<Task> {
+        if task.title.trim().is_empty() {
+            return Err(Error::Invalid("Task title cannot be empty".to_string()));
+        }
+        let mut task = task.clone();
+        task.tags = task
+            .tags
+            .iter()
+            .map(|tag| crate::tags::normalize(tag))
+            .collect::<anyhow::Result<_>>()
+            .map_err(invalid)?;
+        task.project = task
+            .project
+            .as_deref()
+            .map(crate::commands::normalize_project)
+            .transpose()
+            .map_err(invalid)?;
+        task.recurrence = task
+            .recurrence
+            .as_deref()
+            .map(crate::recurrence::normalize)
+            .transpose()
+            .map_err(invalid)?;
+        Ok(task)
+    }
+}
+
+impl TaskFilter {
+    /// Start a filter that, like plain `todo list`, keeps pending tasks
+    /// outside the someday/maybe bucket.
+    pub fn builder() -> TaskFilterBuilder {
+        TaskFilterBuilder::default()
+    }
+}
+
+/// Builds a [`TaskFilter`] one criterion at a time; every criterion must
+/// hold. Tags and the title pattern are checked by [`build`].
+///
+/// [`build`]: TaskFilterBuilder::build
+#[derive(Debug, Clone, Default)]
+pub struct TaskFilterBuilder {
+    filter: TaskFilter,
+}
+
+impl TaskFilterBuilder {
+    /// Include completed tasks too.
+    pub fn include_completed(mut self) -> Self {
+        self.filter.status = StatusFilter::Any;
+        self
+    }
+
+    /// Only completed tasks, or pending and completed ones with
+    /// [`StatusFilter::Any`].
+    pub fn status(mut self, status: StatusFilter) -> Self {
+        self.filter.status = status;
+        self
+    }
+
+    pub fn priority(mut self, priority: crate::Priority) -> Self {
+        self.filter.priority = Some(priority.to_int());
+        self
+    }
+
+    /// Pending tasks due between now and `now + window`.
+    pub fn due_within(mut self, window: Duration) -> Self {
+        self.filter.due_within = Some(window);
+        self
+    }
+
+    /// Pending tasks due before `until`, and not before `from` if given.
+    pub fn due_between(mut self, from: Option<DateTime<Utc>>, until: DateTime<Utc>) -> Self {
+        self.filter.due_range = Some(DueRange { from, until });
+        self
+    }
+
+    /// Tasks carrying `tag` or a tag nested beneath it; a glob such as
+    /// `work/*` is allowed. Repeat to require several.
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.filter.tags.push(tag.to_string());
+        self
+    }
+
+    /// Leave out tasks carrying `tag` or a tag nested beneath it.
+    pub fn exclude_tag(mut self, tag: &str) -> Self {
+        self.filter.exclude_tags.push(tag.to_string());
+        self
+    }
+
+    /// Tasks whose title matches the regular expression `pattern`.
+    pub fn title_matches(mut self, pattern: &str) -> Self {
+        self.filter.title_pattern = Some(pattern.to_string());
+        self
+    }
+
+    /// Tasks whose title or description contains every word of `text`, as
+    /// `todo search` matches them.
+    pub fn text(mut self, text: &str) -> Self {
+        self.filter.text = Some(text.to_string());
+        self
+    }
+
+    /// Leave out tasks without a due date.
+    pub fn exclude_undated(mut self) -> Self {
+        self.filter.exclude_undated = true;
+        self
+    }
+
+    pub fn project(mut self, project: &str) -> Self {
+        self.filter.project = Some(project.to_string());
+        self
+    }
+
+    pub fn exclude_project(mut self, project: &str) -> Self {
+        self.filter.exclude_projects.push(project.to_string());
+        self
+    }
+
+    pub fn someday(mut self, someday: SomedayFilter) -> Self {
+        self.filter.someday = someday;
+        self
+    }
+
+    /// The order results come in; priority order unless set.
+    pub fn order(mut self, order: TaskOrder) -> Self {
+        self.filter.order = order;
+        self
+    }
+
+    /// The filter, or [`Error::Invalid`] for a malformed tag or pattern.
+    pub fn build(self) -> Result<TaskFilter> {
+        if let Some(pattern) = &self.filter.title_pattern {
+            regex::Regex::new(pattern)
+                .map_err(|error| Error::Invalid(format!("Invalid title pattern: {}", error)))?;
+        }
+        for tag in self.filter.tags.iter().chain(&self.filter.exclude_tags) {
+            let name = tag.trim_end_matches(['*', '/']);
+            if !name.is_empty() {
+                crate::tags::normalize(name).map_err(invalid)?;
+            }
+        }
+        Ok(self.filter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_round_trip_and_errors() {
+        let store = TodoStore::open(":memory:").unwrap();
+        let mut task = Task::new("Write report".to_string(), None, None, 2);
+        task.tags = vec!["#work/reports/".to_string()];
+        task.project = Some("Q3".to_string());
+        let id = store.add(&task).unwrap();
+        assert_eq!(store.get(id).unwrap().tags, ["work/reports"]);
+
+        let blank = Task::new("  ".to_string(), None, None, 1);
+        assert!(matches!(store.add(&blank), Err(Error::Invalid(_))));
+        assert!(matches!(store.get(99), Err(Error::NotFound(99))));
+        assert!(matches!(store.complete(99), Err(Error::NotFound(99))));
+
+        let filter = TaskFilter::builder()
+            .tag("work")
+            .priority(crate::Priority::High)
+            .build()
+            .unwrap();
+        assert_eq!(store.list(&filter).unwrap().len(), 1);
+        assert!(TaskFilter::builder().title_matches("(").build().is_err());
+
+        store.complete(id).unwrap();
+        assert!(store.list(&filter).unwrap().is_empty());
+        let done = TaskFilter::builder().include_completed().build().unwrap();
+        assert_eq!(store.list(&done).unwrap().len(), 1);
+
+        store.undo().unwrap();
+        store.delete(id).unwrap();
+        assert!(matches!(store.get(id), Err(Error::NotFound(_))));
+        store.restore(id).unwrap();
+        assert!(!store.get(id).unwrap().completed);
+    }
+}
@@ -0,0 +1,439 @@
+//! How dates and colors look in terminal output, set once at startup from
+//! the `date_format` and `color_scheme` config keys and the `[colors]`
+//! table, whose `tags` and `projects` subtables color single tags and
+//! projects. Dates are shown in the configured time zone (see `zone`).
+//! Machine-readable formats (JSON, CSV, Markdown) keep ISO dates
+//! regardless.
+//!
+//! Whether to color at all is up to `colored`, which leaves out color when
+//! `NO_COLOR` is set or stdout is not a terminal (unless `CLICOLOR_FORCE`
+//! is set); `--no-color` and `color = false` turn it off outright.
+//!
+//! Plain mode (`--plain` or the `plain` key) is for screen readers and
+//! brittle scripts: no color, and messages printed with `outln!`/`errln!`
+//! lose their emoji, box drawing and other symbols.
+
+use chrono::{DateTime, NaiveDate, Utc};
+use colored::*;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::io::IsTerminal;
+use std::sync::OnceLock;
+
+use crate::Priority;
+
+pub const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Named sets of colors for priorities and due dates.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum ColorScheme {
+    /// Blue, yellow and red priorities; red when overdue
+    Default,
+    /// Avoids telling states apart by red versus green
+    Colorblind,
+    /// No hues, only bold and dim
+    Mono,
+}
+
+impl ColorScheme {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ColorScheme::Default => "default",
+            ColorScheme::Colorblind => "colorblind",
+            ColorScheme::Mono => "mono",
+        }
+    }
+}
+
+/// What the `[colors]` table can color: priorities, statuses and due dates.
+pub const COLOR_ROLES: &[&str] = &[
+    "low",
+    "medium",
+    "high",
+    "pending",
+    "in_progress",
+    "completed",
+    "someday",
+    "waiting",
+    "overdue",
+    "due_soon",
+];
+
+/// Colors chosen in `[colors]`, by role; other roles follow the scheme.
+pub type Colors = BTreeMap<&'static str, Color>;
+
+/// Colors of tag or project badges, from `[colors.tags]` and
+/// `[colors.projects]`, by name.
+pub type BadgeColors = BTreeMap<String, Color>;
+
+/// A color as written in the config: a name such as `red` or
+/// `bright blue` (`bright_blue` works too), or `#rrggbb`.
+pub fn parse_color(text: &str) -> Option<Color> {
+    if let Some(hex) = text.strip_prefix('#').filter(|hex| hex.len() == 6) {
+        let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+        return Some(Color::TrueColor {
+            r: channel(0)?,
+            g: channel(2)?,
+            b: channel(4)?,
+        });
+    }
+    text.replace('_', " ").parse().ok()
+}
+
+#[derive(Debug, Clone)]
+pub struct Style {
+    pub date_format: String,
+    pub scheme: ColorScheme,
+    pub colors: Colors,
+    pub tag_colors: BadgeColors,
+    pub project_colors: BadgeColors,
+    pub plain: bool,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            date_format: DEFAULT_DATE_FORMAT.to_string(),
+            scheme: ColorScheme::Default,
+            colors: Colors::new(),
+            tag_colors: BadgeColors::new(),
+            project_colors: BadgeColors::new(),
+            plain: false,
+        }
+    }
+}
+
+static STYLE: OnceLock<Style> = OnceLock::new();
+
+/// Install the style for this process. Later calls are ignored.
+pub fn init(style: Style) {
+    let _ = STYLE.set(style);
+}
+
+fn current() -> &'static Style {
+    STYLE.get_or_init(Style::default)
+}
+
+/// Whether output should be plain: no color, emoji or box drawing.
+pub fn plain() -> bool {
+    current().plain
+}
+
+/// `text` as plain mode prints it: emoji and symbols dropped, tree and box
+/// drawing turned into spaces, `→` spelled out, and lines that were only
+/// decoration (such as rules) left out. `None` when nothing is left. Outside
+/// plain mode the text is unchanged.
+pub fn plain_text(text: &str) -> Option<Cow<'_, str>> {
+    if !plain() {
+        return Some(Cow::Borrowed(text));
+    }
+    strip_decoration(text).map(Cow::Owned)
+}
+
+thread_local! {
+    /// What `outln!` printed while `capture` runs.
+    static CAPTURED: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Print one line for `outln!`, or keep it while `capture` runs.
+pub fn print_line(text: &str) {
+    CAPTURED.with(|captured| match captured.borrow_mut().as_mut() {
+        Some(buffer) => {
+            buffer.push_str(text);
+            buffer.push('\n');
+        }
+        None => println!("{}", text),
+    })
+}
+
+/// Run `f` and return what it printed with `outln!` instead of printing
+/// it, for `todo batch` to report per command.
+pub fn capture<T>(f: impl FnOnce() -> T) -> (T, String) {
+    let outer = CAPTURED.with(|captured| captured.replace(Some(String::new())));
+    let result = f();
+    let text = CAPTURED.with(|captured| captured.replace(outer));
+    (result, text.unwrap_or_default())
+}
+
+/// Run `f`, and when what it printed with `outln!` is taller than the
+/// terminal, show it through `$PAGER` (`less` by default) instead. Output
+/// that isn't a terminal, or an empty `PAGER`, is printed as usual.
+pub fn paged(f: impl FnOnce() -> anyhow::Result<()>) -> anyhow::Result<()> {
+    let capturing = CAPTURED.with(|captured| captured.borrow().is_some());
+    if capturing || !std::io::stdout().is_terminal() {
+        return f();
+    }
+    let (result, text) = capture(f);
+    let rows = ratatui::crossterm::terminal::size()
+        .ok()
+        .filter(|&(_, rows)| rows > 0)
+        .map_or(usize::MAX, |(_, rows)| rows as usize);
+    if text.lines().count() < rows || !run_pager(&text) {
+        print!("{}", text);
+    }
+    result
+}
+
+/// Feed `text` to `$PAGER` and wait for it; false when there is no pager
+/// or it can't be started.
+fn run_pager(text: &str) -> bool {
+    use std::io::Write;
+
+    let pager = std::env::var("PAGER").unwrap_or_else(|_| "less".to_string());
+    let mut words = pager.split_whitespace();
+    let Some(program) = words.next() else {
+        return false;
+    };
+    let mut command = std::process::Command::new(program);
+    command.args(words).stdin(std::process::Stdio::piped());
+    // As git does: keep colors, and leave the list on screen after quitting
+    if std::env::var_os("LESS").is_none() {
+        command.env("LESS", "FRX");
+    }
+    let Ok(mut child) = command.spawn() else {
+        return false;
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        // Quitting the pager early closes the pipe, which is fine
+        let _ = stdin.write_all(text.as_bytes());
+    }
+    let _ = child.wait();
+    true
+}
+
+fn strip_decoration(text: &str) -> Option<String> {
+    let mut lines = Vec::new();
+    for line in text.split('\n') {
+        if line.trim().is_empty() {
+            lines.push(String::new());
+            continue;
+        }
+        let mut out = String::new();
+        // Tree and box drawing in front of a line keeps its indentation;
+        // the spacing after a dropped symbol goes with it.
+        let mut leading = true;
+        let mut skip_space = false;
+        for c in line.chars() {
+            let is_box = matches!(c, '\u{2500}'..='\u{259F}');
+            if !c.is_whitespace() && !is_box {
+                leading = false;
+            }
+            match c {
+                _ if is_box && leading => out.push(' '),
+                _ if is_box => {}
+                '→' => out.push_str("to"),
+                '•' => out.push('-'),
+                '·' => {
+                    out.truncate(out.trim_end().len());
+                    out.push(',');
+                }
+                c if is_symbol(c) => {
+                    skip_space = out.is_empty() || out.ends_with(char::is_whitespace);
+                    continue;
+                }
+                c if c.is_whitespace() && skip_space => continue,
+                c => out.push(c),
+            }
+            skip_space = false;
+        }
+        let out = out.trim_end();
+        if !out.trim_start().is_empty() {
+            lines.push(out.to_string());
+        }
+    }
+    (!lines.iter().all(String::is_empty)).then(|| lines.join("\n"))
+}
+
+/// Emoji, dingbats, arrows and shapes that only decorate a message.
+fn is_symbol(c: char) -> bool {
+    matches!(c,
+        '\u{2190}'..='\u{21FF}'
+        | '\u{2300}'..='\u{23FF}'
+        | '\u{25A0}'..='\u{27BF}'
+        | '\u{2B00}'..='\u{2BFF}'
+        | '\u{1F000}'..='\u{1FAFF}'
+        | '\u{FE0F}'
+        | '\u{200D}'
+    )
+}
+
+/// Whether `format` is a strftime pattern chrono can render.
+pub fn is_valid_date_format(format: &str) -> bool {
+    use chrono::format::{Item, StrftimeItems};
+    !format.trim().is_empty() && StrftimeItems::new(format).all(|item| item != Item::Error)
+}
+
+/// The day `date` falls on in the configured zone, in the configured
+/// format.
+pub fn date(date: DateTime<Utc>) -> String {
+    date.with_timezone(&crate::zone::current())
+        .format(&current().date_format)
+        .to_string()
+}
+
+/// A day in the configured format.
+pub fn day(day: NaiveDate) -> String {
+    day.format(&current().date_format).to_string()
+}
+
+/// `text` in the color `[colors]` gives `role`, if any.
+fn configured(role: &str, text: &str) -> Option<ColoredString> {
+    current().colors.get(role).map(|&color| text.color(color))
+}
+
+pub fn priority(priority: &Priority) -> ColoredString {
+    let label = match priority {
+        Priority::Low => "LOW",
+        Priority::Medium => "MEDIUM",
+        Priority::High => "HIGH",
+    };
+    if let Some(colored) = configured(priority.name(), label) {
+        return colored;
+    }
+    match (current().scheme, priority) {
+        (ColorScheme::Default, Priority::Low) => label.blue(),
+        (ColorScheme::Default, Priority::Medium) => label.yellow(),
+        (ColorScheme::Default, Priority::High) => label.red(),
+        (ColorScheme::Colorblind, Priority::Low) => label.cyan(),
+        (ColorScheme::Colorblind, Priority::Medium) => label.yellow(),
+        (ColorScheme::Colorblind, Priority::High) => label.magenta().bold(),
+        (ColorScheme::Mono, Priority::Low) => label.dimmed(),
+        (ColorScheme::Mono, Priority::Medium) => label.normal(),
+        (ColorScheme::Mono, Priority::High) => label.bold(),
+    }
+}
+
+pub fn overdue(text: &str) -> ColoredString {
+    if let Some(colored) = configured("overdue", text) {
+        return colored;
+    }
+    match current().scheme {
+        ColorScheme::Default => text.red(),
+        ColorScheme::Colorblind => text.magenta().bold(),
+        ColorScheme::Mono => text.bold().underline(),
+    }
+}
+
+pub fn due_soon(text: &str) -> ColoredString {
+    if let Some(colored) = configured("due_soon", text) {
+        return colored;
+    }
+    match current().scheme {
+        ColorScheme::Default | ColorScheme::Colorblind => text.yellow(),
+        ColorScheme::Mono => text.bold(),
+    }
+}
+
+pub fn completed(text: &str) -> ColoredString {
+    if let Some(colored) = configured("completed", text) {
+        return colored;
+    }
+    match current().scheme {
+        ColorScheme::Default => text.green(),
+        ColorScheme::Colorblind => text.blue(),
+        ColorScheme::Mono => text.dimmed(),
+    }
+}
+
+pub fn in_progress(text: &str) -> ColoredString {
+    configured("in_progress", text).unwrap_or_else(|| match current().scheme {
+        ColorScheme::Default | ColorScheme::Colorblind => text.cyan(),
+        ColorScheme::Mono => text.bold(),
+    })
+}
+
+pub fn pending(text: &str) -> ColoredString {
+    configured("pending", text).unwrap_or_else(|| text.white())
+}
+
+pub fn someday(text: &str) -> ColoredString {
+    configured("someday", text).unwrap_or_else(|| text.dimmed())
+}
+
+pub fn waiting(text: &str) -> ColoredString {
+    configured("waiting", text).unwrap_or_else(|| text.dimmed())
+}
+
+/// The color `[colors.tags]` gives `tag`, if any.
+pub fn tag_color(tag: &str) -> Option<Color> {
+    current().tag_colors.get(tag).copied()
+}
+
+/// The color `[colors.projects]` gives `project`, if any.
+pub fn project_color(project: &str) -> Option<Color> {
+    current().project_colors.get(project).copied()
+}
+
+/// `#tag` in its own color, cyan by default.
+pub fn tag(tag: &str) -> ColoredString {
+    let text = format!("#{}", tag);
+    match tag_color(tag) {
+        Some(color) => text.color(color),
+        None => text.cyan(),
+    }
+}
+
+/// Tags as space-separated `#tag` badges, each in its own color.
+pub fn tags(tags: &[String]) -> String {
+    tags.iter()
+        .map(|name| tag(name).to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// `@project` in its own color, magenta by default.
+pub fn project(project: &str) -> ColoredString {
+    let text = format!("@{}", project);
+    match project_color(project) {
+        Some(color) => text.color(color),
+        None => text.magenta(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_decoration() {
+        assert_eq!(
+            strip_decoration("✅ Task added successfully with ID: 3").unwrap(),
+            "Task added successfully with ID: 3"
+        );
+        assert_eq!(strip_decoration(&"─".repeat(80)), None);
+        assert_eq!(
+            strip_decoration("[1] Call mom HIGH ○ PENDING\n  └── [2] Buy flowers").unwrap(),
+            "[1] Call mom HIGH PENDING\n      [2] Buy flowers"
+        );
+        assert_eq!(
+            strip_decoration("⚠️  due 2030-01-10 → 2030-01-12").unwrap(),
+            "due 2030-01-10 to 2030-01-12"
+        );
+    }
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(parse_color("red"), Some(Color::Red));
+        assert_eq!(parse_color("Bright_Blue"), Some(Color::BrightBlue));
+        assert_eq!(
+            parse_color("#ff8000"),
+            Some(Color::TrueColor {
+                r: 255,
+                g: 128,
+                b: 0
+            })
+        );
+        assert_eq!(parse_color("#ff80"), None);
+        assert_eq!(parse_color("#gg0000"), None);
+        assert_eq!(parse_color("reddish"), None);
+    }
+
+    #[test]
+    fn test_date_format_validation() {
+        assert!(is_valid_date_format("%d/%m/%Y"));
+        assert!(is_valid_date_format("%a %b %e"));
+        assert!(!is_valid_date_format("%Q"));
+        assert!(!is_valid_date_format(""));
+    }
+}
@@ -0,0 +1,165 @@
+//! "Did you mean" suggestions for mistyped subcommands and flags, matched by
+//! edit distance against clap's own command and argument names.
+
+use clap::error::{ContextKind, ContextValue, ErrorKind};
+use clap::Command;
+use std::ffi::OsString;
+
+/// A mistyped token and the equally close names it could have meant.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Suggestion {
+    pub typed: String,
+    pub candidates: Vec<String>,
+}
+
+impl Suggestion {
+    /// `args` with the typed token replaced by the only candidate. A flag
+    /// written as `--priorty=high` keeps its value.
+    pub fn apply(&self, args: &[OsString]) -> Option<Vec<OsString>> {
+        let [candidate] = self.candidates.as_slice() else {
+            return None;
+        };
+        let mut args = args.to_vec();
+        let position = args.iter().skip(1).position(|arg| {
+            let arg = arg.to_string_lossy();
+            arg == self.typed || arg.starts_with(&format!("{}=", self.typed))
+        })? + 1;
+        let value = args[position]
+            .to_string_lossy()
+            .split_once('=')
+            .filter(|_| self.typed.starts_with("--"))
+            .map(|(_, value)| value.to_string());
+        args[position] = match value {
+            Some(value) => format!("{}={}", candidate, value).into(),
+            None => candidate.into(),
+        };
+        Some(args)
+    }
+
+    pub fn hint(&self) -> String {
+        let names: Vec<String> = self
+            .candidates
+            .iter()
+            .map(|name| format!("`{}`", name))
+            .collect();
+        format!("did you mean {}?", names.join(" or "))
+    }
+}
+
+/// Optimal string alignment distance: insertions, deletions, substitutions
+/// and swaps of adjacent characters each cost one.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in rows[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut best = (rows[i - 1][j] + 1)
+                .min(rows[i][j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                best = best.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = best;
+        }
+    }
+    rows[a.len()][b.len()]
+}
+
+/// Names within a third of the typed length (at least one edit), keeping
+/// only the closest ones.
+pub fn closest(typed: &str, names: &[String]) -> Vec<String> {
+    let limit = (typed.trim_start_matches('-').chars().count() / 3).max(1);
+    let scored: Vec<(usize, &String)> = names
+        .iter()
+        .map(|name| (edit_distance(typed, name), name))
+        .filter(|(distance, _)| *distance <= limit)
+        .collect();
+    let Some(best) = scored.iter().map(|(distance, _)| *distance).min() else {
+        return Vec::new();
+    };
+    let mut matches: Vec<String> = scored
+        .into_iter()
+        .filter(|(distance, _)| *distance == best)
+        .map(|(_, name)| name.clone())
+        .collect();
+    matches.sort();
+    matches.dedup();
+    matches
+}
+
+/// The subcommand `args` had reached when parsing stopped.
+fn reached_command(root: &Command, args: &[OsString]) -> Command {
+    let mut command = root.clone();
+    for arg in args.iter().skip(1) {
+        let arg = arg.to_string_lossy();
+        let next = command
+            .get_subcommands()
+            .find(|sub| sub.get_name() == arg || sub.get_all_aliases().any(|alias| alias == arg))
+            .cloned();
+        if let Some(next) = next {
+            command = next;
+        }
+    }
+    command
+}
+
+/// Suggest replacements for the subcommand or flag clap rejected in `error`.
+pub fn suggest(root: &Command, error: &clap::Error, args: &[OsString]) -> Option<Suggestion> {
+    let (kind, typed) = match (error.kind(), error.get(ContextKind::InvalidSubcommand)) {
+        (ErrorKind::InvalidSubcommand, Some(ContextValue::String(typed))) => {
+            ("command", typed.clone())
+        }
+        (ErrorKind::UnknownArgument, _) => match error.get(ContextKind::InvalidArg) {
+            Some(ContextValue::String(typed)) if typed.starts_with("--") => {
+                let typed = typed.split('=').next().unwrap_or_default();
+                ("flag", typed.to_string())
+            }
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    let command = reached_command(root, args);
+    let names: Vec<String> = if kind == "command" {
+        command
+            .get_subcommands()
+            .filter(|sub| !sub.is_hide_set())
+            .flat_map(|sub| {
+                std::iter::once(sub.get_name().to_string())
+                    .chain(sub.get_visible_aliases().map(str::to_string))
+            })
+            .collect()
+    } else {
+        command
+            .get_arguments()
+            .chain(root.get_arguments().filter(|arg| arg.is_global_set()))
+            .filter_map(|arg| arg.get_long())
+            .chain(["help"])
+            .map(|long| format!("--{}", long))
+            .collect()
+    };
+
+    let candidates = closest(&typed, &names);
+    (!candidates.is_empty()).then_some(Suggestion { typed, candidates })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("lst", "list"), 1);
+        assert_eq!(edit_distance("lsit", "list"), 1);
+        assert_eq!(edit_distance("--priorty", "--priority"), 1);
+        assert_eq!(edit_distance("add", "list"), 4);
+    }
+}
@@ -0,0 +1,346 @@
+//! The `todo list` table: one row per task, in aligned columns chosen with
+//! `--columns`. Subtasks keep their tree connectors in front of the title.
+//! The title column takes whatever width the terminal has left; longer
+//! titles are cut off with `…`, or wrapped onto extra lines with `--wrap`.
+
+use chrono::Duration;
+use colored::*;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::models::Task;
+use crate::ListColumn;
+
+/// Titles are never squeezed narrower than this, even if the row then
+/// overflows the terminal.
+const MIN_TITLE_WIDTH: usize = 12;
+const GAP: &str = "  ";
+
+#[derive(Debug, Clone)]
+pub struct TableOptions {
+    pub columns: Vec<ListColumn>,
+    pub wrap: bool,
+}
+
+impl Default for TableOptions {
+    fn default() -> Self {
+        Self {
+            columns: ListColumn::DEFAULT.to_vec(),
+            wrap: false,
+        }
+    }
+}
+
+/// Width to fit the table in: the terminal's, or `COLUMNS` when output is
+/// not a terminal. `None` leaves titles whole.
+pub fn output_width() -> Option<usize> {
+    use std::io::IsTerminal;
+    if std::io::stdout().is_terminal() {
+        if let Ok((width, _)) = ratatui::crossterm::terminal::size() {
+            return Some(width as usize);
+        }
+    }
+    std::env::var("COLUMNS").ok()?.parse().ok()
+}
+
+/// Column widths worked out once for a set of rows, so rows printed in
+/// several groups still line up.
+pub struct Table<'a> {
+    options: &'a TableOptions,
+    widths: Vec<usize>,
+    due_soon_window: Duration,
+}
+
+impl<'a> Table<'a> {
+    /// Size the columns for `rows` (tree prefix and task), fitting them in
+    /// `max_width` when given.
+    pub fn new(
+        options: &'a TableOptions,
+        rows: &[(String, &Task)],
+        max_width: Option<usize>,
+        due_soon_window: Duration,
+    ) -> Self {
+        let mut widths: Vec<usize> = options
+            .columns
+            .iter()
+            .map(|column| {
+                rows.iter()
+                    .map(|(prefix, task)| width(&cell(task, prefix, *column, due_soon_window)))
+                    .chain([column.header().len()])
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+        if let (Some(max_width), Some(title)) = (
+            max_width,
+            options.columns.iter().position(|c| *c == ListColumn::Title),
+        ) {
+            let others: usize = widths
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| *index != title)
+                .map(|(_, width)| width + GAP.len())
+                .sum();
+            let room = max_width.saturating_sub(others).max(MIN_TITLE_WIDTH);
+            widths[title] = widths[title].min(room);
+        }
+        Self {
+            options,
+            widths,
+            due_soon_window,
+        }
+    }
+
+    pub fn header(&self) -> String {
+        let cells: Vec<String> = self
+            .options
+            .columns
+            .iter()
+            .map(|column| column.header().to_string())
+            .collect();
+        self.join(cells.into_iter().map(|text| text.dimmed()).collect())
+    }
+
+    /// The lines for `rows`: one per task, plus continuation lines for
+    /// wrapped titles.
+    pub fn lines(&self, rows: &[(String, &Task)]) -> Vec<String> {
+        let mut lines = Vec::new();
+        for (prefix, task) in rows {
+            let mut cells: Vec<ColoredString> = self
+                .options
+                .columns
+                .iter()
+                .map(|column| cell(task, prefix, *column, self.due_soon_window))
+                .collect();
+            let mut extra = Vec::new();
+            if let Some(title) = self.title_index() {
+                let (first, rest) = fit(&cells[title], self.widths[title], self.options.wrap);
+                cells[title] = first.normal();
+                // Continuation lines start under the title, past the connectors
+                let indent = " ".repeat(width(prefix).min(self.widths[title] / 2));
+                extra = rest
+                    .iter()
+                    .flat_map(|text| {
+                        let room = self.widths[title].saturating_sub(indent.len());
+                        let (first, more) = fit(text, room.max(1), true);
+                        std::iter::once(first).chain(more)
+                    })
+                    .map(|text| {
+                        let mut cells = vec!["".normal(); self.options.columns.len()];
+                        cells[title] = format!("{}{}", indent, text).normal();
+                        self.join(cells)
+                    })
+                    .collect();
+            }
+            lines.push(self.join(cells));
+            lines.extend(extra);
+        }
+        lines
+    }
+
+    fn title_index(&self) -> Option<usize> {
+        self.options
+            .columns
+            .iter()
+            .position(|c| *c == ListColumn::Title)
+    }
+
+    fn join(&self, cells: Vec<ColoredString>) -> String {
+        let last = cells.len().saturating_sub(1);
+        let line: Vec<String> = cells
+            .into_iter()
+            .enumerate()
+            .map(|(index, text)| {
+                let padding = if index == last {
+                    0
+                } else {
+                    self.widths[index].saturating_sub(width(&text))
+                };
+                format!("{}{}", text, " ".repeat(padding))
+            })
+            .collect();
+        line.join(GAP).trim_end().to_string()
+    }
+}
+
+/// Display width of a cell, without its color codes.
+fn width(text: &str) -> usize {
+    let mut plain = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // Skip the escape sequence up to its final letter
+            chars.by_ref().find(|c| c.is_ascii_alphabetic());
+        } else {
+            plain.push(c);
+        }
+    }
+    UnicodeWidthStr::width(plain.as_str())
+}
+
+fn cell(task: &Task, prefix: &str, column: ListColumn, due_soon_window: Duration) -> ColoredString {
+    match column {
+        ListColumn::Id => task.id.unwrap_or(0).to_string().normal(),
+        ListColumn::Title => format!("{}{}", prefix, task.title).normal(),
+        ListColumn::Priority => task.priority_color(),
+        ListColumn::Due if task.due_date.is_none() => "".normal(),
+        ListColumn::Due => task.due_date_colored(due_soon_window),
+        ListColumn::Start => task
+            .start_date
+            .map(|start| crate::style::date(start).normal())
+            .unwrap_or_else(|| "".normal()),
+        ListColumn::Status => task.status_text(),
+        // Each tag has its own color, so the cell carries the color codes
+        ListColumn::Tags => crate::style::tags(&task.tags).normal(),
+        ListColumn::Project => task
+            .project
+            .as_ref()
+            .map(|name| crate::style::project(name))
+            .unwrap_or_else(|| "".normal()),
+        ListColumn::Age => task.age_text().dimmed(),
+        ListColumn::Repeat => task
+            .recurrence
+            .as_ref()
+            .map(|rule| format!("↻ {}", rule).blue())
+            .unwrap_or_else(|| "".normal()),
+        ListColumn::Energy => task.energy.map(|e| e.name()).unwrap_or("").normal(),
+    }
+}
+
+/// `text` cut to `max` columns: the part that fits and, when wrapping, the
+/// rest split into further lines at spaces where possible. Without
+/// wrapping, text that doesn't fit ends in `…`.
+fn fit(text: &str, max: usize, wrap: bool) -> (String, Vec<String>) {
+    if width(text) <= max {
+        return (text.to_string(), Vec::new());
+    }
+    if !wrap {
+        let mut cut = String::new();
+        for c in text.chars() {
+            if width(&cut) + c.width().unwrap_or(0) + 1 > max {
+                break;
+            }
+            cut.push(c);
+        }
+        return (format!("{}…", cut.trim_end()), Vec::new());
+    }
+
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split(' ') {
+        let candidate = if line.is_empty() {
+            word.to_string()
+        } else {
+            format!("{} {}", line, word)
+        };
+        if width(&candidate) <= max {
+            line = candidate;
+            continue;
+        }
+        if !line.is_empty() {
+            lines.push(std::mem::take(&mut line));
+        }
+        // A word longer than a whole line is split where it overflows
+        let mut rest = word;
+        while width(rest) > max {
+            let mut taken = 0;
+            let mut split = 0;
+            for (index, c) in rest.char_indices() {
+                let w = c.width().unwrap_or(0);
+                if taken + w > max {
+                    break;
+                }
+                taken += w;
+                split = index + c.len_utf8();
+            }
+            lines.push(rest[..split].to_string());
+            rest = &rest[split..];
+        }
+        line = rest.to_string();
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    let first = lines.remove(0);
+    (first, lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(id: i32, title: &str) -> Task {
+        let mut task = Task::new(title.to_string(), None, None, 1);
+        task.id = Some(id);
+        task
+    }
+
+    fn strip_colors(lines: Vec<String>) -> Vec<String> {
+        let codes = regex::Regex::new("\x1b\\[[0-9;]*m").unwrap();
+        lines
+            .iter()
+            .map(|line| codes.replace_all(line, "").into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn test_table_alignment_and_fitting() {
+        let tasks = [
+            task(1, "Short"),
+            task(12, "A much longer title that will not fit"),
+        ];
+        let rows: Vec<(String, &Task)> = tasks.iter().map(|t| (String::new(), t)).collect();
+        let options = TableOptions {
+            columns: vec![ListColumn::Id, ListColumn::Title, ListColumn::Priority],
+            wrap: false,
+        };
+
+        let table = Table::new(&options, &rows, None, Duration::hours(24));
+        assert_eq!(
+            strip_colors(vec![table.header()]),
+            ["ID  TITLE                                  PRIORITY"]
+        );
+        assert_eq!(
+            strip_colors(table.lines(&rows)),
+            vec![
+                "1   Short                                  MEDIUM",
+                "12  A much longer title that will not fit  MEDIUM",
+            ]
+        );
+
+        let table = Table::new(&options, &rows, Some(36), Duration::hours(24));
+        assert_eq!(
+            strip_colors(table.lines(&rows))[1],
+            "12  A much longer title t…  MEDIUM"
+        );
+
+        let options = TableOptions {
+            wrap: true,
+            ..options
+        };
+        let table = Table::new(&options, &rows, Some(36), Duration::hours(24));
+        assert_eq!(
+            strip_colors(table.lines(&rows))[1..],
+            [
+                "12  A much longer title     MEDIUM",
+                "    that will not fit",
+            ]
+        );
+
+        assert_eq!(
+            fit("Supercalifragilistic", 8, true),
+            (
+                "Supercal".to_string(),
+                vec!["ifragili".to_string(), "stic".to_string()]
+            )
+        );
+    }
+
+    #[test]
+    fn test_width_ignores_color_codes() {
+        assert_eq!(
+            width("\x1b[36m#work\x1b[0m \x1b[38;2;255;128;0m#home\x1b[0m"),
+            11
+        );
+        assert_eq!(width("日本"), 4);
+    }
+}
@@ -0,0 +1,136 @@
+use anyhow::Result;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Separator between levels of a hierarchical tag, e.g. `work/clients/acme`.
+pub const SEPARATOR: char = '/';
+
+/// Normalize user input into a stored tag: strips a leading `#` and
+/// surrounding slashes, and rejects empty levels, commas, and whitespace.
+pub fn normalize(tag: &str) -> Result<String> {
+    let tag = tag.trim().trim_start_matches('#').trim_matches(SEPARATOR);
+    let valid = !tag.is_empty()
+        && !tag.contains(',')
+        && !tag.contains(char::is_whitespace)
+        && tag.split(SEPARATOR).all(|level| !level.is_empty());
+    if !valid {
+        return Err(anyhow::anyhow!(
+            "Invalid tag '{}'. Tags cannot be empty, contain commas or spaces, or have empty levels",
+            tag
+        ));
+    }
+    Ok(tag.to_string())
+}
+
+/// Normalize a context such as `@home` into the tag it is stored as.
+pub fn normalize_context(context: &str) -> Result<String> {
+    normalize(context.trim().trim_start_matches('@'))
+}
+
+/// `work/clients/acme` → `["work", "work/clients", "work/clients/acme"]`.
+pub fn ancestors(tag: &str) -> Vec<String> {
+    let mut prefixes = Vec::new();
+    let mut current = String::new();
+    for level in tag.split(SEPARATOR) {
+        if !current.is_empty() {
+            current.push(SEPARATOR);
+        }
+        current.push_str(level);
+        prefixes.push(current.clone());
+    }
+    prefixes
+}
+
+/// Roll `(tag, task_id)` pairs up the hierarchy, counting each task once per
+/// level. Returns `(tag, distinct task count)` sorted level by level, so
+/// each parent is directly followed by its children. Comparing whole strings
+/// would not do: `a-b` sorts between `a` and `a/b`.
+pub fn rollup(pairs: &[(String, i32)]) -> Vec<(String, usize)> {
+    let mut nodes: BTreeMap<String, BTreeSet<i32>> = BTreeMap::new();
+    for (tag, task_id) in pairs {
+        for prefix in ancestors(tag) {
+            nodes.entry(prefix).or_default().insert(*task_id);
+        }
+    }
+    let mut rows: Vec<(String, usize)> = nodes
+        .into_iter()
+        .map(|(tag, tasks)| (tag, tasks.len()))
+        .collect();
+    rows.sort_by(|(a, _), (b, _)| a.split(SEPARATOR).cmp(b.split(SEPARATOR)));
+    rows
+}
+
+/// Render a rolled-up tag list as an indented tree.
+pub fn render_tree(rows: &[(String, usize)]) -> String {
+    rows.iter()
+        .map(|(tag, count)| {
+            let depth = tag.matches(SEPARATOR).count();
+            let leaf = tag.rsplit(SEPARATOR).next().unwrap_or(tag);
+            format!("{}#{} ({})", "  ".repeat(depth), leaf, count)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize("#work/clients/").unwrap(), "work/clients");
+        assert_eq!(normalize(" home ").unwrap(), "home");
+        assert!(normalize("work//acme").is_err());
+        assert!(normalize("a,b").is_err());
+        assert!(normalize("two words").is_err());
+        assert!(normalize("#").is_err());
+    }
+
+    #[test]
+    fn test_normalize_context() {
+        assert_eq!(normalize_context("@home").unwrap(), "home");
+        assert_eq!(normalize_context(" errands ").unwrap(), "errands");
+        assert!(normalize_context("@").is_err());
+    }
+
+    #[test]
+    fn test_ancestors() {
+        assert_eq!(
+            ancestors("work/clients/acme"),
+            vec!["work", "work/clients", "work/clients/acme"]
+        );
+        assert_eq!(ancestors("home"), vec!["home"]);
+    }
+
+    #[test]
+    fn test_rollup_counts_tasks_once_per_level() {
+        let pairs = vec![
+            ("work/clients/acme".to_string(), 1),
+            ("work/clients/globex".to_string(), 1),
+            ("work/admin".to_string(), 2),
+            ("home".to_string(), 3),
+            ("a-b".to_string(), 4),
+            ("a/b".to_string(), 5),
+        ];
+        let rows = rollup(&pairs);
+        assert_eq!(
+            rows,
+            vec![
+                ("a".to_string(), 1),
+                ("a/b".to_string(), 1),
+                ("a-b".to_string(), 1),
+                ("home".to_string(), 1),
+                ("work".to_string(), 2),
+                ("work/admin".to_string(), 1),
+                ("work/clients".to_string(), 1),
+                ("work/clients/acme".to_string(), 1),
+                ("work/clients/globex".to_string(), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render_tree() {
+        let rows = vec![("work".to_string(), 2), ("work/clients".to_string(), 1)];
+        assert_eq!(render_tree(&rows), "#work (2)\n  #clients (1)");
+    }
+}
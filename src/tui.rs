@@ -0,0 +1,1671 @@
+//! `todo tui`: a full-screen view with a quick filter bar, the pending task
+//! list and the selected task's details. Keys follow vim: `j`/`k` move, `/`
+//! filters, `e`/`d`/`p` edit the title, due date and priority in place and
+//! `n` adds a note. `b` switches to a kanban board with To do, In progress
+//! and Done columns, where `H`/`L` move the selected task between columns.
+//! `c` shows a month calendar above the list, which then holds the agenda
+//! of the selected day. `u` and Ctrl-R undo and redo the edits, moves,
+//! completes and deletes of the session through the operation journal
+//! behind `todo undo`. Every key can be rebound in `[tui.keys]`; see
+//! `keymap`. Unless `tui_mouse` is off, clicks select tasks, columns and
+//! calendar days, the wheel scrolls, and the details pane gets Complete
+//! and Snooze buttons.
+
+use anyhow::Result;
+use chrono::{Datelike, Duration, NaiveDate, Utc, Weekday};
+use ratatui::crossterm::event::{
+    self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEvent,
+    MouseEventKind,
+};
+use ratatui::layout::{Constraint, Layout, Position, Rect};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+
+use crate::board::{Column, WipLimits};
+use crate::calendar;
+use crate::db::{Database, TaskFilter};
+use crate::keymap::{Action, Keymap};
+use crate::models::{Note, Task};
+use crate::Priority;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum View {
+    List,
+    Board,
+    /// The list narrowed to the tasks due on the calendar's selected day.
+    Calendar,
+}
+
+/// A change made in the session, kept to describe it and to redo it.
+#[derive(Debug, Clone)]
+struct Change {
+    id: i32,
+    kind: ChangeKind,
+    /// What the change was, e.g. `title edit` or `move to Done`.
+    label: String,
+}
+
+#[derive(Debug, Clone)]
+enum ChangeKind {
+    /// The task as it was saved.
+    Edit(Box<Task>),
+    Complete,
+    Delete,
+}
+
+impl Change {
+    fn apply(&self, db: &Database) -> Result<()> {
+        match &self.kind {
+            ChangeKind::Edit(task) => db.edit_task(self.id, task)?,
+            ChangeKind::Complete => {
+                db.complete_tasks(&[self.id])?;
+            }
+            ChangeKind::Delete => db.delete_task(self.id)?,
+        }
+        Ok(())
+    }
+}
+
+/// How `todo tui` starts, from the config.
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Initial quick filter.
+    pub filter: String,
+    pub limits: WipLimits,
+    pub keys: Keymap,
+    /// Take clicks and the scroll wheel.
+    pub mouse: bool,
+    /// First day of the week in the calendar.
+    pub week_start: Weekday,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            filter: String::new(),
+            limits: WipLimits::default(),
+            keys: Keymap::default(),
+            mouse: false,
+            week_start: Weekday::Mon,
+        }
+    }
+}
+
+/// A button in the details pane.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Button {
+    Complete,
+    Snooze,
+}
+
+/// Where the last frame drew the parts that take clicks; empty when not
+/// drawn.
+#[derive(Debug, Default)]
+struct Hits {
+    list: Rect,
+    calendar: Rect,
+    columns: [Rect; 3],
+    buttons: Vec<(Rect, Button)>,
+}
+
+/// What keys currently do.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Mode {
+    Normal,
+    /// Typing into the filter bar; the list narrows as you type.
+    Filter,
+    /// Editing one field of the selected task.
+    Edit {
+        field: Field,
+        input: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Field {
+    Title,
+    Due,
+    Priority,
+    /// A new note rather than an existing field.
+    Note,
+}
+
+impl Field {
+    fn label(&self) -> &'static str {
+        match self {
+            Field::Title => "Title",
+            Field::Due => "Due",
+            Field::Priority => "Priority",
+            Field::Note => "Note",
+        }
+    }
+}
+
+pub struct App {
+    /// Every pending task, in list order.
+    tasks: Vec<Task>,
+    /// Indices into `tasks` that match the filter.
+    visible: Vec<usize>,
+    list: ListState,
+    /// Subtasks of the selected task, shown as its checklist.
+    checklist: Vec<Task>,
+    notes: Vec<Note>,
+    /// Tasks completed in the last `board::DONE_DAYS` days, for the Done column.
+    done: Vec<Task>,
+    pub view: View,
+    /// The board column holding the cursor, and the cursor of each column.
+    column: Column,
+    rows: [ListState; 3],
+    limits: WipLimits,
+    keys: Keymap,
+    mouse: bool,
+    week_start: Weekday,
+    hits: Hits,
+    /// Changes of this session with the journal batch that undoes each,
+    /// most recent last.
+    undo: Vec<(i64, Change)>,
+    /// Undone changes, most recently undone last.
+    redo: Vec<Change>,
+    /// The day selected in the calendar.
+    pub day: NaiveDate,
+    pub filter: String,
+    pub mode: Mode,
+    /// Result of the last action, shown in the status line until the next key.
+    pub message: Option<String>,
+    pub quit: bool,
+}
+
+impl App {
+    pub fn new(db: &Database, options: Options) -> Result<Self> {
+        let mut app = Self {
+            tasks: Vec::new(),
+            visible: Vec::new(),
+            list: ListState::default(),
+            checklist: Vec::new(),
+            notes: Vec::new(),
+            done: Vec::new(),
+            view: View::List,
+            column: Column::Todo,
+            rows: Default::default(),
+            limits: options.limits,
+            keys: options.keys,
+            mouse: options.mouse,
+            week_start: options.week_start,
+            hits: Hits::default(),
+            undo: Vec::new(),
+            redo: Vec::new(),
+            day: crate::zone::day_of(Utc::now()),
+            filter: options.filter,
+            mode: Mode::Normal,
+            message: None,
+            quit: false,
+        };
+        app.reload(db)?;
+        Ok(app)
+    }
+
+    /// Re-read the tasks, keeping the selection on the same task if it is
+    /// still listed.
+    pub fn reload(&mut self, db: &Database) -> Result<()> {
+        let selected_id = self.selected().and_then(|task| task.id);
+        let all = db.get_tasks(&TaskFilter::any_status())?;
+        let now = Utc::now();
+        (self.done, self.tasks) = all.into_iter().partition(|task| task.completed);
+        self.done
+            .retain(|task| crate::board::recently_done(task, now));
+        self.done
+            .sort_by_key(|task| std::cmp::Reverse(task.updated_at));
+        self.apply_filter();
+        if let Some(id) = selected_id {
+            self.follow(id);
+        }
+        self.load_detail(db)
+    }
+
+    fn apply_filter(&mut self) {
+        self.visible = (0..self.tasks.len())
+            .filter(|&index| {
+                let task = &self.tasks[index];
+                self.shown(task)
+                    && (self.view != View::Calendar
+                        || task.due_date.map(crate::zone::day_of) == Some(self.day))
+            })
+            .collect();
+        let lengths = Column::ALL.map(|column| self.column_tasks(column).len());
+        for (cursor, len) in std::iter::once(&mut self.list)
+            .zip([self.visible.len()])
+            .chain(self.rows.iter_mut().zip(lengths))
+        {
+            let selected = cursor.selected().unwrap_or(0);
+            cursor.select(match len {
+                0 => None,
+                len => Some(selected.min(len - 1)),
+            });
+        }
+    }
+
+    /// Whether `task` matches every word of the filter.
+    fn shown(&self, task: &Task) -> bool {
+        self.filter
+            .split_whitespace()
+            .all(|term| matches(task, &term.to_lowercase()))
+    }
+
+    /// Put the cursor back on task `id` if it is still shown, following it
+    /// to another column on the board.
+    fn follow(&mut self, id: i32) {
+        match self.view {
+            View::List | View::Calendar => {
+                if let Some(position) = self
+                    .visible
+                    .iter()
+                    .position(|&index| self.tasks[index].id == Some(id))
+                {
+                    self.list.select(Some(position));
+                }
+            }
+            View::Board => {
+                for column in Column::ALL {
+                    if let Some(position) = self
+                        .column_tasks(column)
+                        .iter()
+                        .position(|task| task.id == Some(id))
+                    {
+                        self.column = column;
+                        self.rows[column as usize].select(Some(position));
+                    }
+                }
+            }
+        }
+    }
+
+    /// The shown tasks of a board column, in order.
+    fn column_tasks(&self, column: Column) -> Vec<&Task> {
+        let source = if column == Column::Done {
+            &self.done
+        } else {
+            &self.tasks
+        };
+        source
+            .iter()
+            .filter(|task| Column::of(task) == column && self.shown(task))
+            .collect()
+    }
+
+    /// Every task in a board column, filtered or not, as counted against
+    /// its WIP limit.
+    pub fn column_count(&self, column: Column) -> usize {
+        let source = if column == Column::Done {
+            &self.done
+        } else {
+            &self.tasks
+        };
+        source
+            .iter()
+            .filter(|task| Column::of(task) == column)
+            .count()
+    }
+
+    /// Titles of the shown tasks in a board column, in order.
+    pub fn column_titles(&self, column: Column) -> Vec<&str> {
+        self.column_tasks(column)
+            .into_iter()
+            .map(|task| task.title.as_str())
+            .collect()
+    }
+
+    /// The cursor of the current view.
+    fn cursor(&mut self) -> &mut ListState {
+        match self.view {
+            View::List | View::Calendar => &mut self.list,
+            View::Board => &mut self.rows[self.column as usize],
+        }
+    }
+
+    /// Load the subtasks and notes of the selected task.
+    fn load_detail(&mut self, db: &Database) -> Result<()> {
+        (self.checklist, self.notes) = match self.selected().and_then(|task| task.id) {
+            Some(id) => (db.get_subtasks(id)?, db.get_notes(id)?),
+            None => (Vec::new(), Vec::new()),
+        };
+        Ok(())
+    }
+
+    pub fn selected(&self) -> Option<&Task> {
+        match self.view {
+            View::List | View::Calendar => {
+                let position = self.list.selected()?;
+                self.visible.get(position).map(|&index| &self.tasks[index])
+            }
+            View::Board => {
+                let position = self.rows[self.column as usize].selected()?;
+                self.column_tasks(self.column).get(position).copied()
+            }
+        }
+    }
+
+    /// Titles of the listed tasks, in order.
+    pub fn visible_titles(&self) -> Vec<&str> {
+        self.visible
+            .iter()
+            .map(|&index| self.tasks[index].title.as_str())
+            .collect()
+    }
+
+    fn select(&mut self, db: &Database, position: usize) -> Result<()> {
+        let len = match self.view {
+            View::List | View::Calendar => self.visible.len(),
+            View::Board => self.column_tasks(self.column).len(),
+        };
+        if len > 0 {
+            self.cursor().select(Some(position.min(len - 1)));
+            self.load_detail(db)?;
+        }
+        Ok(())
+    }
+
+    /// Switch views, keeping the cursor on the selected task if the new
+    /// view shows it.
+    fn set_view(&mut self, db: &Database, view: View) -> Result<()> {
+        let selected_id = self.selected().and_then(|task| task.id);
+        self.view = view;
+        self.apply_filter();
+        if let Some(id) = selected_id {
+            self.follow(id);
+        }
+        self.load_detail(db)
+    }
+
+    /// Select `day` in the calendar and show its agenda.
+    fn select_day(&mut self, db: &Database, day: NaiveDate) -> Result<()> {
+        self.day = day;
+        self.list.select(Some(0));
+        self.apply_filter();
+        self.load_detail(db)
+    }
+
+    /// Move the board cursor to `column`, if there is one.
+    fn switch_column(&mut self, db: &Database, column: Option<Column>) -> Result<()> {
+        if let Some(column) = column {
+            self.column = column;
+            self.load_detail(db)?;
+        }
+        Ok(())
+    }
+
+    pub fn on_key(&mut self, db: &Database, key: KeyEvent) -> Result<()> {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            self.quit = true;
+            return Ok(());
+        }
+        self.message = None;
+        match self.mode.clone() {
+            Mode::Normal => self.on_normal_key(db, key),
+            Mode::Filter => {
+                match key.code {
+                    KeyCode::Enter => self.mode = Mode::Normal,
+                    KeyCode::Esc => {
+                        self.filter.clear();
+                        self.mode = Mode::Normal;
+                    }
+                    KeyCode::Backspace => {
+                        self.filter.pop();
+                    }
+                    KeyCode::Char(c) => self.filter.push(c),
+                    _ => return Ok(()),
+                }
+                self.apply_filter();
+                self.load_detail(db)
+            }
+            Mode::Edit { field, mut input } => {
+                match key.code {
+                    KeyCode::Enter => {
+                        self.mode = Mode::Normal;
+                        return self.save(db, field, &input);
+                    }
+                    KeyCode::Esc => {
+                        self.mode = Mode::Normal;
+                        return Ok(());
+                    }
+                    KeyCode::Backspace => {
+                        input.pop();
+                    }
+                    KeyCode::Char(c) => input.push(c),
+                    _ => {}
+                }
+                self.mode = Mode::Edit { field, input };
+                Ok(())
+            }
+        }
+    }
+
+    /// Clicks select the task, column or day under the pointer and press
+    /// the detail buttons; the wheel moves the selection of the list or
+    /// column under it, or the calendar by a week. Ignored while typing.
+    pub fn on_mouse(&mut self, db: &Database, event: MouseEvent) -> Result<()> {
+        if self.mode != Mode::Normal {
+            return Ok(());
+        }
+        let at = Position::new(event.column, event.row);
+        let step = match event.kind {
+            MouseEventKind::ScrollDown => 1,
+            MouseEventKind::ScrollUp => -1,
+            MouseEventKind::Down(MouseButton::Left) => 0,
+            _ => return Ok(()),
+        };
+        self.message = None;
+
+        if let Some(&(_, button)) = self.hits.buttons.iter().find(|(area, _)| area.contains(at)) {
+            return match button {
+                Button::Complete => self.complete(db),
+                Button::Snooze => self.snooze(db),
+            };
+        }
+        if self.hits.calendar.contains(at) {
+            if step != 0 {
+                return self.select_day(db, self.day + Duration::weeks(step));
+            }
+            return match self.day_at(at) {
+                Some(day) => self.select_day(db, day),
+                None => Ok(()),
+            };
+        }
+        let column = Column::ALL
+            .into_iter()
+            .find(|&column| self.hits.columns[column as usize].contains(at));
+        let area = match column {
+            Some(column) => {
+                self.switch_column(db, Some(column))?;
+                self.hits.columns[column as usize]
+            }
+            None if self.hits.list.contains(at) => self.hits.list,
+            None => return Ok(()),
+        };
+        let cursor = self.cursor();
+        let position = if step != 0 {
+            match cursor.selected() {
+                Some(position) => position.saturating_add_signed(step as isize),
+                None => 0,
+            }
+        } else {
+            // Rows start inside the border, one task per line.
+            match at.y.checked_sub(area.y + 1) {
+                Some(row) if row + 2 < area.height => cursor.offset() + row as usize,
+                _ => return Ok(()),
+            }
+        };
+        let len = match self.view {
+            View::List | View::Calendar => self.visible.len(),
+            View::Board => self.column_tasks(self.column).len(),
+        };
+        if position < len {
+            self.select(db, position)?;
+        }
+        Ok(())
+    }
+
+    /// The calendar day drawn at `at`, if any.
+    fn day_at(&self, at: Position) -> Option<NaiveDate> {
+        let area = self.hits.calendar;
+        // Inside the border, below the weekday header; days are 3 wide.
+        let week = at.y.checked_sub(area.y + 2)? as usize;
+        let weekday = (at.x.checked_sub(area.x + 1)? / 3) as usize;
+        calendar::month_weeks(self.day, self.week_start)
+            .get(week)?
+            .get(weekday)
+            .copied()
+            .flatten()
+    }
+
+    fn on_normal_key(&mut self, db: &Database, key: KeyEvent) -> Result<()> {
+        if key.code == KeyCode::Esc && !self.filter.is_empty() {
+            self.filter.clear();
+            self.apply_filter();
+            return self.load_detail(db);
+        }
+        let Some(action) = self.keys.action(&key) else {
+            return Ok(());
+        };
+        let position = self.cursor().selected().unwrap_or(0);
+        let board = self.view == View::Board;
+        let calendar = self.view == View::Calendar;
+        let day = self.day;
+        match action {
+            Action::Quit => self.quit = true,
+            Action::Down => self.select(db, position + 1)?,
+            Action::Up => self.select(db, position.saturating_sub(1))?,
+            Action::First => self.select(db, 0)?,
+            Action::Last => self.select(db, usize::MAX)?,
+            Action::Filter => self.mode = Mode::Filter,
+            Action::Reload => self.reload(db)?,
+            Action::Complete => self.complete(db)?,
+            Action::Snooze => self.snooze(db)?,
+            Action::Delete => self.delete(db)?,
+            Action::Undo => self.undo(db)?,
+            Action::Redo => self.redo(db)?,
+            Action::Board => self.set_view(db, if board { View::List } else { View::Board })?,
+            Action::Calendar => {
+                self.set_view(db, if calendar { View::List } else { View::Calendar })?
+            }
+            Action::Left if calendar => self.select_day(db, day - Duration::days(1))?,
+            Action::Right if calendar => self.select_day(db, day + Duration::days(1))?,
+            Action::MoveLeft if calendar => self.select_day(db, day - Duration::days(7))?,
+            Action::MoveRight if calendar => self.select_day(db, day + Duration::days(7))?,
+            Action::PreviousMonth if calendar => {
+                self.select_day(db, calendar::add_months(day, -1))?
+            }
+            Action::NextMonth if calendar => self.select_day(db, calendar::add_months(day, 1))?,
+            Action::Today if calendar => self.select_day(db, crate::zone::day_of(Utc::now()))?,
+            Action::Left if board => self.switch_column(db, self.column.left())?,
+            Action::Right if board => self.switch_column(db, self.column.right())?,
+            Action::MoveLeft if board => self.move_task(db, self.column.left())?,
+            Action::MoveRight if board => self.move_task(db, self.column.right())?,
+            Action::EditTitle if !board => self.edit(Field::Title),
+            Action::EditDue if !board => self.edit(Field::Due),
+            Action::EditPriority if !board => self.edit(Field::Priority),
+            Action::AddNote if !board => self.edit(Field::Note),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Key hints for the status line, from the current bindings.
+    fn help(&self) -> String {
+        let key = |action| self.keys.hint(action);
+        let pair = |a, b| format!("{}/{}", key(a), key(b));
+        let hints = match self.view {
+            View::List => vec![
+                (pair(Action::Down, Action::Up), "move"),
+                (key(Action::Filter), "filter"),
+                (key(Action::EditTitle), "title"),
+                (key(Action::EditDue), "due"),
+                (key(Action::EditPriority), "priority"),
+                (key(Action::AddNote), "note"),
+                (key(Action::Complete), "done"),
+                (key(Action::Snooze), "snooze"),
+                (key(Action::Delete), "delete"),
+                (pair(Action::Undo, Action::Redo), "undo/redo"),
+                (key(Action::Board), "board"),
+                (key(Action::Calendar), "calendar"),
+                (key(Action::Reload), "reload"),
+                (key(Action::Quit), "quit"),
+            ],
+            View::Board => vec![
+                (pair(Action::Left, Action::Right), "column"),
+                (pair(Action::Down, Action::Up), "move"),
+                (pair(Action::MoveLeft, Action::MoveRight), "move task"),
+                (key(Action::Filter), "filter"),
+                (key(Action::Complete), "done"),
+                (pair(Action::Undo, Action::Redo), "undo/redo"),
+                (key(Action::Board), "list"),
+                (key(Action::Reload), "reload"),
+                (key(Action::Quit), "quit"),
+            ],
+            View::Calendar => vec![
+                (pair(Action::Left, Action::Right), "day"),
+                (pair(Action::MoveLeft, Action::MoveRight), "week"),
+                (pair(Action::PreviousMonth, Action::NextMonth), "month"),
+                (key(Action::Today), "today"),
+                (pair(Action::Down, Action::Up), "move"),
+                (key(Action::EditTitle), "title"),
+                (key(Action::EditDue), "due"),
+                (key(Action::Complete), "done"),
+                (key(Action::Calendar), "list"),
+                (key(Action::Quit), "quit"),
+            ],
+        };
+        hints
+            .into_iter()
+            .map(|(keys, label)| format!("{} {}", keys, label))
+            .collect::<Vec<_>>()
+            .join("  ")
+    }
+
+    /// Start editing `field` of the selected task, prefilled with its value.
+    fn edit(&mut self, field: Field) {
+        let Some(task) = self.selected() else {
+            return;
+        };
+        let input = match field {
+            Field::Title => task.title.clone(),
+            Field::Due => task
+                .due_date
+                .map(|due| crate::zone::day_of(due).format("%Y-%m-%d").to_string())
+                .unwrap_or_default(),
+            Field::Priority => Priority::from_int(task.priority).name().to_string(),
+            Field::Note => String::new(),
+        };
+        self.mode = Mode::Edit { field, input };
+    }
+
+    fn save(&mut self, db: &Database, field: Field, input: &str) -> Result<()> {
+        let Some(mut task) = self.selected().cloned() else {
+            return Ok(());
+        };
+        let id = task.id.unwrap();
+        let input = input.trim();
+        if field == Field::Note {
+            if !input.is_empty() {
+                db.add_note(id, input)?;
+                self.message = Some(format!("Note added to task {}", id));
+            }
+            return self.load_detail(db);
+        }
+        let edited = match field {
+            Field::Title if input.is_empty() => Err(anyhow::anyhow!("Title cannot be empty")),
+            Field::Title => {
+                task.title = input.to_string();
+                Ok(())
+            }
+            Field::Due if input.is_empty() => {
+                task.due_date = None;
+                Ok(())
+            }
+            Field::Due => crate::commands::parse_due_date(input).map(|due| {
+                task.due_date = Some(due);
+            }),
+            Field::Priority => <Priority as clap::ValueEnum>::from_str(input, true)
+                .map(|priority| {
+                    task.priority = priority.to_int();
+                    task.priority_inherited = false;
+                })
+                .map_err(|_| anyhow::anyhow!("Priority must be low, medium or high")),
+            Field::Note => unreachable!("notes are added above"),
+        };
+        if let Err(error) = edited {
+            // Diagnostics span several lines; the first names the problem
+            let text = error.to_string();
+            self.message = Some(text.lines().next().unwrap_or_default().to_string());
+            return Ok(());
+        }
+
+        task.updated_at = Utc::now();
+        self.perform(
+            db,
+            Change {
+                id,
+                kind: ChangeKind::Edit(Box::new(task)),
+                label: format!("{} edit", field.label().to_lowercase()),
+            },
+        )?;
+        self.message = Some(format!("{} of task {} updated", field.label(), id));
+        self.reload(db)
+    }
+
+    fn complete(&mut self, db: &Database) -> Result<()> {
+        let Some(task) = self.selected() else {
+            return Ok(());
+        };
+        let id = task.id.unwrap();
+        let title = task.title.clone();
+        self.perform(
+            db,
+            Change {
+                id,
+                kind: ChangeKind::Complete,
+                label: "complete".to_string(),
+            },
+        )?;
+        self.message = Some(format!("Completed {}: {}", id, title));
+        self.reload(db)
+    }
+
+    fn delete(&mut self, db: &Database) -> Result<()> {
+        let Some(task) = self.selected() else {
+            return Ok(());
+        };
+        let id = task.id.unwrap();
+        let title = task.title.clone();
+        self.perform(
+            db,
+            Change {
+                id,
+                kind: ChangeKind::Delete,
+                label: "delete".to_string(),
+            },
+        )?;
+        self.message = Some(format!("Moved {} to the trash: {}", id, title));
+        self.reload(db)
+    }
+
+    /// Make a new change, which can be undone but leaves nothing to redo.
+    fn perform(&mut self, db: &Database, change: Change) -> Result<()> {
+        self.redo.clear();
+        self.record(db, change)
+    }
+
+    /// Apply `change` and remember the batch it was journaled under. A
+    /// change that journals nothing, such as completing a completed task,
+    /// is not remembered.
+    fn record(&mut self, db: &Database, change: Change) -> Result<()> {
+        let before = db.last_batch()?;
+        change.apply(db)?;
+        match db.last_batch()? {
+            Some(batch) if Some(batch) != before => self.undo.push((batch, change)),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn undo(&mut self, db: &Database) -> Result<()> {
+        let Some((batch, change)) = self.undo.pop() else {
+            self.message = Some("Nothing to undo in this session".to_string());
+            return Ok(());
+        };
+        if db.undo_batch(batch)?.is_empty() {
+            self.message = Some(format!(
+                "The {} of task {} was already undone elsewhere",
+                change.label, change.id
+            ));
+        } else {
+            self.message = Some(format!("Undid {} of task {}", change.label, change.id));
+            self.redo.push(change);
+        }
+        self.reload(db)
+    }
+
+    fn redo(&mut self, db: &Database) -> Result<()> {
+        let Some(change) = self.redo.pop() else {
+            self.message = Some("Nothing to redo".to_string());
+            return Ok(());
+        };
+        self.message = Some(format!("Redid {} of task {}", change.label, change.id));
+        self.record(db, change)?;
+        self.reload(db)
+    }
+
+    /// Move the selected board task into `column`: Done completes it,
+    /// the others set or clear its in-progress mark and reopen it.
+    fn move_task(&mut self, db: &Database, column: Option<Column>) -> Result<()> {
+        let (Some(column), Some(mut task)) = (column, self.selected().cloned()) else {
+            return Ok(());
+        };
+        let id = task.id.unwrap();
+        let kind = if column == Column::Done {
+            ChangeKind::Complete
+        } else {
+            task.completed = false;
+            task.in_progress = column == Column::InProgress;
+            task.updated_at = Utc::now();
+            ChangeKind::Edit(Box::new(task))
+        };
+        self.perform(
+            db,
+            Change {
+                id,
+                kind,
+                label: format!("move to {}", column.title()),
+            },
+        )?;
+        // The cursor follows the task into its new column
+        self.reload(db)?;
+
+        let count = self.column_count(column);
+        self.message = Some(match self.limits.of(column) {
+            Some(limit) if count > limit => format!(
+                "Moved {} to {}, over its WIP limit ({}/{})",
+                id,
+                column.title(),
+                count,
+                limit
+            ),
+            _ => format!("Moved {} to {}", id, column.title()),
+        });
+        Ok(())
+    }
+
+    /// Push the selected task's due date back a day, like `todo snooze`.
+    fn snooze(&mut self, db: &Database) -> Result<()> {
+        let Some(task) = self.selected() else {
+            return Ok(());
+        };
+        let id = task.id.unwrap();
+        let until = crate::commands::snooze_target(task, 1);
+        db.snooze_task(id, until)?;
+        self.message = Some(format!(
+            "Snoozed {} until {}",
+            id,
+            crate::style::date(until)
+        ));
+        self.reload(db)
+    }
+
+    pub fn render(&mut self, frame: &mut Frame) {
+        self.hits = Hits::default();
+        let [filter_area, main_area, status_area] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        self.render_filter(frame, filter_area);
+        match self.view {
+            View::List => {
+                let [list_area, detail_area] =
+                    Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)])
+                        .areas(main_area);
+                self.render_list(frame, list_area);
+                self.render_detail(frame, detail_area);
+            }
+            View::Calendar => {
+                let [left_area, detail_area] =
+                    Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)])
+                        .areas(main_area);
+                let [calendar_area, list_area] =
+                    Layout::vertical([Constraint::Length(10), Constraint::Min(0)]).areas(left_area);
+                self.render_calendar(frame, calendar_area);
+                self.render_list(frame, list_area);
+                self.render_detail(frame, detail_area);
+            }
+            View::Board => {
+                let areas: [Rect; 3] =
+                    Layout::horizontal([Constraint::Ratio(1, 3); 3]).areas(main_area);
+                for (column, area) in Column::ALL.into_iter().zip(areas) {
+                    self.render_column(frame, area, column);
+                }
+            }
+        }
+
+        let status = match (&self.mode, &self.message) {
+            (Mode::Filter, _) => "Enter keep filter  Esc clear  #tag matches a tag".to_string(),
+            (Mode::Edit { .. }, _) => "Enter save  Esc cancel".to_string(),
+            (Mode::Normal, Some(message)) => message.clone(),
+            (Mode::Normal, None) => self.help(),
+        };
+        frame.render_widget(Paragraph::new(status).dim(), status_area);
+    }
+
+    fn render_filter(&self, frame: &mut Frame, area: Rect) {
+        let active = self.mode == Mode::Filter;
+        let text = if self.filter.is_empty() && !active {
+            Line::from("press / to filter".dim())
+        } else {
+            Line::from(self.filter.as_str())
+        };
+        let block = Block::bordered()
+            .title(" Filter ")
+            .border_style(focus_style(active));
+        frame.render_widget(Paragraph::new(text).block(block), area);
+        if active {
+            let column = self.filter.chars().count() as u16;
+            frame.set_cursor_position((area.x + 1 + column, area.y + 1));
+        }
+    }
+
+    fn render_list(&mut self, frame: &mut Frame, area: Rect) {
+        let items: Vec<ListItem> = self
+            .visible
+            .iter()
+            .map(|&index| task_item(&self.tasks[index]))
+            .collect();
+        let title = if self.view == View::Calendar {
+            format!(
+                " Agenda {} ({}) ",
+                crate::style::day(self.day),
+                self.visible.len()
+            )
+        } else {
+            format!(" Tasks ({}/{}) ", self.visible.len(), self.tasks.len())
+        };
+        let list = List::new(items)
+            .block(
+                Block::bordered()
+                    .title(title)
+                    .border_style(focus_style(self.mode == Mode::Normal)),
+            )
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+            .highlight_symbol("› ");
+        frame.render_stateful_widget(list, area, &mut self.list);
+        self.hits.list = area;
+    }
+
+    /// The month of the selected day. Days with pending tasks due are
+    /// yellow, red once past; today is underlined.
+    fn render_calendar(&mut self, frame: &mut Frame, area: Rect) {
+        let counts = calendar::due_counts(self.tasks.iter().filter(|task| self.shown(task)));
+        let today = crate::zone::day_of(Utc::now());
+        let header = calendar::weekday_header(self.week_start);
+        let mut lines = vec![Line::from(header.dim())];
+        for week in calendar::month_weeks(self.day, self.week_start) {
+            let spans: Vec<Span> = week
+                .iter()
+                .map(|day| {
+                    let Some(day) = *day else {
+                        return Span::raw("   ");
+                    };
+                    let mut style = if !counts.contains_key(&day) {
+                        Style::new()
+                    } else if day < today {
+                        Style::new().fg(Color::Red).bold()
+                    } else {
+                        Style::new().fg(Color::Yellow).bold()
+                    };
+                    if day == today {
+                        style = style.underlined();
+                    }
+                    if day == self.day {
+                        style = style.reversed();
+                    }
+                    Span::styled(format!("{:>2}", day.day()), style)
+                })
+                .flat_map(|span| [span, Span::raw(" ")])
+                .collect();
+            lines.push(Line::from(spans));
+        }
+        let block = Block::bordered()
+            .title(format!(" {} ", self.day.format("%B %Y")))
+            .border_style(focus_style(self.mode == Mode::Normal));
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+        self.hits.calendar = area;
+    }
+
+    /// One board column. Its count turns red once it passes the WIP limit.
+    fn render_column(&mut self, frame: &mut Frame, area: Rect, column: Column) {
+        let items: Vec<ListItem> = self
+            .column_tasks(column)
+            .into_iter()
+            .map(|task| {
+                if column == Column::Done {
+                    task_item(task).dim()
+                } else {
+                    task_item(task)
+                }
+            })
+            .collect();
+        let count = self.column_count(column);
+        let (title, over) = match self.limits.of(column) {
+            Some(limit) => (
+                format!(" {} {}/{} ", column.title(), count, limit),
+                count > limit,
+            ),
+            None => (format!(" {} {} ", column.title(), count), false),
+        };
+        let focused = self.column == column && self.mode == Mode::Normal;
+        let mut block = Block::bordered().title(title);
+        block = if over {
+            block
+                .border_style(Style::new().fg(Color::Red))
+                .title_bottom(Line::from(" over WIP limit ".red().bold()))
+        } else {
+            block.border_style(focus_style(focused))
+        };
+        let (highlight, symbol) = if focused {
+            (Style::new().add_modifier(Modifier::REVERSED), "› ")
+        } else {
+            (Style::new(), "  ")
+        };
+        let list = List::new(items)
+            .block(block)
+            .highlight_style(highlight)
+            .highlight_symbol(symbol);
+        frame.render_stateful_widget(list, area, &mut self.rows[column as usize]);
+        self.hits.columns[column as usize] = area;
+    }
+
+    fn render_detail(&mut self, frame: &mut Frame, area: Rect) {
+        let editing = match &self.mode {
+            Mode::Edit { field, input } => Some((*field, input.as_str())),
+            _ => None,
+        };
+        let block = Block::bordered()
+            .title(" Details ")
+            .border_style(focus_style(editing.is_some()));
+        let Some(task) = self.selected() else {
+            let empty = Paragraph::new("No matching tasks".dim()).block(block);
+            frame.render_widget(empty, area);
+            return;
+        };
+
+        let field = |field: Field, value: Line<'static>| -> Line<'static> {
+            let label = Span::styled(format!("{:<9}", field.label()), Style::new().bold());
+            match editing {
+                Some((editing, input)) if editing == field => Line::from(vec![
+                    label,
+                    Span::styled(format!("{}▏", input), Style::new().fg(Color::Cyan)),
+                ]),
+                _ => {
+                    let mut spans = vec![label];
+                    spans.extend(value.spans);
+                    Line::from(spans)
+                }
+            }
+        };
+
+        let due = match task.due_date {
+            Some(due) => {
+                let text = crate::style::date(due);
+                if task.is_overdue() {
+                    Line::from(Span::styled(
+                        format!("{} (overdue)", text),
+                        Style::new().fg(Color::Red),
+                    ))
+                } else {
+                    Line::from(text)
+                }
+            }
+            None => Line::from("none".dim()),
+        };
+        let mut lines = vec![
+            field(Field::Title, Line::from(task.title.clone())),
+            field(Field::Due, due),
+            field(Field::Priority, Line::from(priority_span(task.priority))),
+            Line::from(format!("{:<9}{}", "ID", task.id.unwrap_or(0)).bold()),
+        ];
+        if let Some(project) = &task.project {
+            lines.push(Line::from(vec![
+                Span::styled(format!("{:<9}", "Project"), Style::new().bold()),
+                project_span(project),
+            ]));
+        }
+        if !task.tags.is_empty() {
+            let mut spans = vec![Span::styled(format!("{:<9}", "Tags"), Style::new().bold())];
+            for (index, tag) in task.tags.iter().enumerate() {
+                if index > 0 {
+                    spans.push(Span::raw(" "));
+                }
+                spans.push(tag_span(tag));
+            }
+            lines.push(Line::from(spans));
+        }
+        if let Some(rule) = &task.recurrence {
+            lines.push(Line::from(vec![
+                Span::styled(format!("{:<9}", "Repeats"), Style::new().bold()),
+                Span::raw(rule.clone()),
+            ]));
+        }
+        if let Some(description) = task.description.as_deref().filter(|d| !d.is_empty()) {
+            lines.push(Line::default());
+            lines.push(Line::from("Description".bold()));
+            lines.extend(description.lines().map(|line| Line::from(line.to_string())));
+        }
+        let adding_note = matches!(editing, Some((Field::Note, _)));
+        if !self.notes.is_empty() || adding_note {
+            lines.push(Line::default());
+            lines.push(Line::from("Notes".bold()));
+            for note in &self.notes {
+                lines.push(Line::from(vec![
+                    Span::styled(
+                        format!("{}  ", note.created_at.format("%Y-%m-%d %H:%M")),
+                        Style::new().dim(),
+                    ),
+                    Span::raw(note.text.clone()),
+                ]));
+            }
+            if adding_note {
+                lines.push(field(Field::Note, Line::default()));
+            }
+        }
+        if !self.checklist.is_empty() {
+            lines.push(Line::default());
+            lines.push(Line::from("Checklist".bold()));
+            for child in &self.checklist {
+                let mark = if child.completed { "[x]" } else { "[ ]" };
+                let line = format!("{} {}", mark, child.title);
+                lines.push(if child.completed {
+                    Line::from(line.dim())
+                } else {
+                    Line::from(line)
+                });
+            }
+        }
+
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+        let text_area = if self.mouse {
+            let [text_area, button_area] =
+                Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(inner);
+            self.render_buttons(frame, button_area);
+            text_area
+        } else {
+            inner
+        };
+        let detail = Paragraph::new(lines).wrap(Wrap { trim: false });
+        frame.render_widget(detail, text_area);
+    }
+
+    /// Clickable Complete and Snooze buttons along the bottom of the
+    /// details pane.
+    fn render_buttons(&mut self, frame: &mut Frame, area: Rect) {
+        let mut x = area.x;
+        for (button, label) in [
+            (Button::Complete, " Complete "),
+            (Button::Snooze, " Snooze "),
+        ] {
+            let width = (label.len() as u16).min(area.right().saturating_sub(x));
+            let button_area = Rect::new(x, area.y, width, 1);
+            let style = Style::new().fg(Color::Black).bg(Color::Cyan);
+            frame.render_widget(Paragraph::new(label).style(style), button_area);
+            self.hits.buttons.push((button_area, button));
+            x += width + 1;
+        }
+    }
+}
+
+/// Whether `task` matches one lowercased filter term: `#tag` matches the
+/// tag and anything nested beneath it, other words the title, notes or
+/// project.
+fn matches(task: &Task, term: &str) -> bool {
+    if let Some(tag) = term.strip_prefix('#') {
+        return task
+            .tags
+            .iter()
+            .any(|t| t == tag || t.starts_with(&format!("{}/", tag)));
+    }
+    task.title.to_lowercase().contains(term)
+        || task
+            .description
+            .as_deref()
+            .is_some_and(|d| d.to_lowercase().contains(term))
+        || task
+            .project
+            .as_deref()
+            .is_some_and(|p| p.to_lowercase().contains(term))
+}
+
+/// A task as a list row: id, title, priority, due date, project and tags.
+pub(crate) fn task_item(task: &Task) -> ListItem<'static> {
+    let mut spans = vec![
+        Span::raw(format!("[{}] ", task.id.unwrap_or(0))),
+        Span::raw(task.title.clone()),
+        Span::raw(" "),
+        priority_span(task.priority),
+    ];
+    if let Some(due) = task.due_date {
+        let style = if task.is_overdue() {
+            Style::new().fg(Color::Red)
+        } else {
+            Style::new().dim()
+        };
+        spans.push(Span::styled(format!(" {}", crate::style::date(due)), style));
+    }
+    if let Some(project) = &task.project {
+        spans.push(Span::raw(" "));
+        spans.push(project_span(project));
+    }
+    for tag in &task.tags {
+        spans.push(Span::raw(" "));
+        spans.push(tag_span(tag));
+    }
+    ListItem::new(Line::from(spans))
+}
+
+/// `@project` in its color from `[colors.projects]`, magenta by default.
+fn project_span(project: &str) -> Span<'static> {
+    let color = crate::style::project_color(project).map_or(Color::Magenta, terminal_color);
+    Span::styled(format!("@{}", project), Style::new().fg(color))
+}
+
+/// `#tag` in its color from `[colors.tags]`, cyan by default.
+fn tag_span(tag: &str) -> Span<'static> {
+    let color = crate::style::tag_color(tag).map_or(Color::Cyan, terminal_color);
+    Span::styled(format!("#{}", tag), Style::new().fg(color))
+}
+
+/// A color from the config, as ratatui names it.
+fn terminal_color(color: colored::Color) -> Color {
+    use colored::Color as Config;
+    match color {
+        Config::Black => Color::Black,
+        Config::Red => Color::Red,
+        Config::Green => Color::Green,
+        Config::Yellow => Color::Yellow,
+        Config::Blue => Color::Blue,
+        Config::Magenta => Color::Magenta,
+        Config::Cyan => Color::Cyan,
+        Config::White => Color::Gray,
+        Config::BrightBlack => Color::DarkGray,
+        Config::BrightRed => Color::LightRed,
+        Config::BrightGreen => Color::LightGreen,
+        Config::BrightYellow => Color::LightYellow,
+        Config::BrightBlue => Color::LightBlue,
+        Config::BrightMagenta => Color::LightMagenta,
+        Config::BrightCyan => Color::LightCyan,
+        Config::BrightWhite => Color::White,
+        Config::TrueColor { r, g, b } => Color::Rgb(r, g, b),
+    }
+}
+
+fn priority_span(priority: i32) -> Span<'static> {
+    let priority = Priority::from_int(priority);
+    let color = match priority {
+        Priority::Low => Color::Blue,
+        Priority::Medium => Color::Yellow,
+        Priority::High => Color::Red,
+    };
+    Span::styled(priority.name().to_uppercase(), Style::new().fg(color))
+}
+
+fn focus_style(focused: bool) -> Style {
+    if focused {
+        Style::new().fg(Color::Cyan)
+    } else {
+        Style::new()
+    }
+}
+
+/// `todo tui --show-keys`: every action with its keys.
+pub fn print_keys(keys: &Keymap) {
+    outln!("⌨️  todo tui keys (rebind them in [tui.keys]):");
+    for action in Action::ALL {
+        let bound: Vec<String> = keys
+            .keys(action)
+            .iter()
+            .map(|key| key.to_string())
+            .collect();
+        let bound = if bound.is_empty() {
+            "(unbound)".to_string()
+        } else {
+            bound.join(", ")
+        };
+        outln!(
+            "  {:<15} {:<14} {}",
+            action.name(),
+            bound,
+            action.description()
+        );
+    }
+    outln!("\nCtrl-C always quits; while typing, Enter saves and Esc cancels.");
+    if let Err(conflict) = keys.check_conflicts() {
+        outln!("⚠️  {}", conflict);
+    }
+}
+
+/// Run the interface until the user quits, restoring the terminal even when
+/// an error ends it early.
+pub fn run(db: &Database, options: Options) -> Result<()> {
+    use std::io::IsTerminal;
+    options.keys.check_conflicts().map_err(anyhow::Error::msg)?;
+    if !std::io::stdout().is_terminal() {
+        return Err(anyhow::anyhow!("todo tui needs an interactive terminal"));
+    }
+    let mouse = options.mouse;
+    let mut app = App::new(db, options)?;
+    let mut terminal = ratatui::init();
+    if mouse {
+        ratatui::crossterm::execute!(std::io::stdout(), event::EnableMouseCapture)?;
+    }
+    let result = event_loop(&mut app, &mut terminal, db);
+    if mouse {
+        ratatui::crossterm::execute!(std::io::stdout(), event::DisableMouseCapture)?;
+    }
+    ratatui::restore();
+    result
+}
+
+fn event_loop(app: &mut App, terminal: &mut DefaultTerminal, db: &Database) -> Result<()> {
+    while !app.quit {
+        terminal.draw(|frame| app.render(frame))?;
+        match event::read()? {
+            Event::Key(key) if key.kind == KeyEventKind::Press => app.on_key(db, key)?,
+            Event::Mouse(mouse) => app.on_mouse(db, mouse)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::create_test_db;
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+
+    fn press(app: &mut App, db: &Database, keys: &str) {
+        for c in keys.chars() {
+            let code = match c {
+                '\n' => KeyCode::Enter,
+                '\x1b' => KeyCode::Esc,
+                '\x08' => KeyCode::Backspace,
+                c => KeyCode::Char(c),
+            };
+            app.on_key(db, KeyEvent::from(code)).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_filter_and_inline_edits() {
+        let (db, _temp_file) = create_test_db();
+        let mut groceries = Task::new("Buy groceries".to_string(), None, None, 1);
+        groceries.tags = vec!["home".to_string()];
+        db.add_task(&groceries).unwrap();
+        db.add_task(&Task::new("Write report".to_string(), None, None, 1))
+            .unwrap();
+
+        let mut app = App::new(&db, Options::default()).unwrap();
+        assert_eq!(app.visible_titles(), ["Buy groceries", "Write report"]);
+
+        press(&mut app, &db, "/#home\n");
+        assert_eq!(app.visible_titles(), ["Buy groceries"]);
+        press(&mut app, &db, "\x1b");
+        assert_eq!(app.visible_titles().len(), 2);
+
+        // Edit the second task's title and priority in place
+        press(&mut app, &db, "je\x08\x08\x08\x08\x08\x08draft\n");
+        press(&mut app, &db, "p\x08\x08\x08\x08\x08\x08high\n");
+        let task = db.get_task_by_id(2).unwrap().unwrap();
+        assert_eq!(task.title, "Write draft");
+        assert_eq!(task.priority, 2);
+
+        // A bad due date leaves the task alone and says why
+        press(&mut app, &db, "dsomeday\n");
+        assert!(app.message.as_deref().unwrap().starts_with("Invalid date"));
+        assert_eq!(db.get_task_by_id(2).unwrap().unwrap().due_date, None);
+
+        press(&mut app, &db, "nCalled them\n");
+        assert_eq!(db.get_notes(2).unwrap()[0].text, "Called them");
+
+        // Completing drops the task from the list
+        press(&mut app, &db, "x");
+        assert_eq!(app.visible_titles(), ["Buy groceries"]);
+    }
+
+    #[test]
+    fn test_rebound_keys_and_snooze() {
+        let (db, _temp_file) = create_test_db();
+        db.add_task(&Task::new("Buy groceries".to_string(), None, None, 1))
+            .unwrap();
+        db.add_task(&Task::new("Write report".to_string(), None, None, 1))
+            .unwrap();
+        let mut keys = Keymap::default();
+        keys.bind(
+            Action::Complete,
+            vec![crate::keymap::Key::parse("space").unwrap()],
+        );
+        let mut app = App::new(
+            &db,
+            Options {
+                keys,
+                ..Options::default()
+            },
+        )
+        .unwrap();
+        assert!(app.help().contains("space done"));
+
+        press(&mut app, &db, "x");
+        assert_eq!(app.visible_titles().len(), 2);
+        press(&mut app, &db, " ");
+        assert_eq!(app.visible_titles(), ["Write report"]);
+
+        press(&mut app, &db, "s");
+        let due = db.get_task_by_id(2).unwrap().unwrap().due_date.unwrap();
+        assert!(due > Utc::now() + Duration::hours(23));
+        assert_eq!(db.get_snooze_count(2).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_undo_and_redo_session_changes() {
+        let (db, _temp_file) = create_test_db();
+        for title in ["Draft talk", "Book venue", "Send invites"] {
+            db.add_task(&Task::new(title.to_string(), None, None, 1))
+                .unwrap();
+        }
+        let mut app = App::new(&db, Options::default()).unwrap();
+        let redo = KeyEvent::new(KeyCode::Char('r'), KeyModifiers::CONTROL);
+
+        press(&mut app, &db, "e!\n");
+        press(&mut app, &db, "jx");
+        press(&mut app, &db, "D");
+        press(&mut app, &db, "bL");
+        assert!(db.get_task_by_id(1).unwrap().unwrap().in_progress);
+        assert_eq!(app.visible_titles(), ["Draft talk!"]);
+
+        // Undo walks back through the session, newest first
+        press(&mut app, &db, "u");
+        assert!(!db.get_task_by_id(1).unwrap().unwrap().in_progress);
+        assert_eq!(
+            app.message.as_deref(),
+            Some("Undid move to In progress of task 1")
+        );
+        press(&mut app, &db, "u");
+        assert_eq!(app.column_count(Column::Todo), 2);
+        press(&mut app, &db, "u");
+        assert!(!db.get_task_by_id(2).unwrap().unwrap().completed);
+        press(&mut app, &db, "u");
+        assert_eq!(db.get_task_by_id(1).unwrap().unwrap().title, "Draft talk");
+        press(&mut app, &db, "u");
+        assert_eq!(
+            app.message.as_deref(),
+            Some("Nothing to undo in this session")
+        );
+
+        // Redo replays them in order, and a new change drops what is left
+        app.on_key(&db, redo).unwrap();
+        assert_eq!(db.get_task_by_id(1).unwrap().unwrap().title, "Draft talk!");
+        app.on_key(&db, redo).unwrap();
+        assert!(db.get_task_by_id(2).unwrap().unwrap().completed);
+        press(&mut app, &db, "b");
+        press(&mut app, &db, "x");
+        app.on_key(&db, redo).unwrap();
+        assert_eq!(app.message.as_deref(), Some("Nothing to redo"));
+
+        // A change already undone from the CLI is skipped
+        db.undo_last().unwrap();
+        press(&mut app, &db, "u");
+        assert!(app.message.as_deref().unwrap().contains("already undone"));
+    }
+
+    #[test]
+    fn test_board_moves_tasks_between_columns() {
+        let (db, _temp_file) = create_test_db();
+        for title in ["Draft talk", "Book venue", "Send invites"] {
+            db.add_task(&Task::new(title.to_string(), None, None, 1))
+                .unwrap();
+        }
+        let options = Options {
+            limits: WipLimits {
+                todo: 0,
+                in_progress: 1,
+            },
+            ..Options::default()
+        };
+        let mut app = App::new(&db, options).unwrap();
+        press(&mut app, &db, "b");
+        assert_eq!(app.column_titles(Column::Todo).len(), 3);
+
+        // The cursor follows the moved task into its new column
+        press(&mut app, &db, "L");
+        assert_eq!(app.column_titles(Column::InProgress), ["Draft talk"]);
+        assert!(db.get_task_by_id(1).unwrap().unwrap().in_progress);
+        assert_eq!(app.selected().unwrap().title, "Draft talk");
+
+        // A second task passes the limit of one and says so
+        press(&mut app, &db, "hL");
+        assert_eq!(app.column_count(Column::InProgress), 2);
+        assert!(app
+            .message
+            .as_deref()
+            .unwrap()
+            .contains("over its WIP limit"));
+
+        // Done completes the task; moving it back reopens it
+        press(&mut app, &db, "L");
+        let task = db.get_task_by_id(2).unwrap().unwrap();
+        assert!(task.completed);
+        assert!(!task.in_progress);
+        assert_eq!(app.column_titles(Column::Done), ["Book venue"]);
+        press(&mut app, &db, "H");
+        let task = db.get_task_by_id(2).unwrap().unwrap();
+        assert!(!task.completed);
+        assert!(task.in_progress);
+
+        // Moves are edits, so undo reverses them
+        db.undo_last().unwrap();
+        assert!(db.get_task_by_id(2).unwrap().unwrap().completed);
+    }
+
+    #[test]
+    fn test_render_shows_three_panes() {
+        let (db, _temp_file) = create_test_db();
+        let parent = db
+            .add_task(&Task::new(
+                "Plan trip".to_string(),
+                Some("Book early".to_string()),
+                None,
+                2,
+            ))
+            .unwrap();
+        let mut child = Task::new("Flights".to_string(), None, None, 1);
+        child.parent_id = Some(parent);
+        db.add_task(&child).unwrap();
+        db.add_note(parent, "Ask Sam about dates").unwrap();
+
+        let mut app = App::new(&db, Options::default()).unwrap();
+        let mut terminal = Terminal::new(TestBackend::new(80, 20)).unwrap();
+        terminal.draw(|frame| app.render(frame)).unwrap();
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect();
+
+        assert!(screen.contains("Filter"));
+        assert!(screen.contains("Tasks (2/2)"));
+        assert!(screen.contains("[1] Plan trip HIGH"));
+        assert!(screen.contains("Book early"));
+        assert!(screen.contains("Ask Sam about dates"));
+        assert!(screen.contains("[ ] Flights"));
+        assert!(screen.contains("j/k move"));
+    }
+
+    #[test]
+    fn test_calendar_shows_agenda_of_selected_day() {
+        let (db, _temp_file) = create_test_db();
+        let today = Utc::now().date_naive();
+        let in_two_days = (today + Duration::days(2))
+            .and_hms_opt(9, 0, 0)
+            .unwrap()
+            .and_utc();
+        for title in ["Dentist", "Pay rent"] {
+            db.add_task(&Task::new(title.to_string(), None, Some(in_two_days), 1))
+                .unwrap();
+        }
+        db.add_task(&Task::new("Undated".to_string(), None, None, 1))
+            .unwrap();
+
+        let mut app = App::new(&db, Options::default()).unwrap();
+        press(&mut app, &db, "c");
+        assert_eq!(app.day, today);
+        assert!(app.visible_titles().is_empty());
+
+        press(&mut app, &db, "ll");
+        assert_eq!(app.visible_titles(), ["Dentist", "Pay rent"]);
+        press(&mut app, &db, "/rent\n");
+        assert_eq!(app.visible_titles(), ["Pay rent"]);
+        assert_eq!(app.selected().unwrap().title, "Pay rent");
+
+        let mut terminal = Terminal::new(TestBackend::new(80, 20)).unwrap();
+        terminal.draw(|frame| app.render(frame)).unwrap();
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect();
+        assert!(screen.contains(&app.day.format("%B %Y").to_string()));
+        assert!(screen.contains("Mo Tu We Th Fr Sa Su"));
+        assert!(screen.contains("Agenda"));
+
+        press(&mut app, &db, "]");
+        assert_eq!(app.day, calendar::add_months(today + Duration::days(2), 1));
+        press(&mut app, &db, "t");
+        assert_eq!(app.day, today);
+
+        // Leaving the calendar lists every task again
+        press(&mut app, &db, "\x1bc");
+        assert_eq!(app.visible_titles().len(), 3);
+    }
+
+    #[test]
+    fn test_mouse_selects_scrolls_and_presses_buttons() {
+        let (db, _temp_file) = create_test_db();
+        for title in ["Draft talk", "Book venue", "Send invites"] {
+            db.add_task(&Task::new(title.to_string(), None, None, 1))
+                .unwrap();
+        }
+        let options = Options {
+            mouse: true,
+            ..Options::default()
+        };
+        let mut app = App::new(&db, options).unwrap();
+        let mut terminal = Terminal::new(TestBackend::new(80, 20)).unwrap();
+        let mut mouse = |app: &mut App, kind: MouseEventKind, column: u16, row: u16| {
+            terminal.draw(|frame| app.render(frame)).unwrap();
+            let event = MouseEvent {
+                kind,
+                column,
+                row,
+                modifiers: KeyModifiers::NONE,
+            };
+            app.on_mouse(&db, event).unwrap();
+        };
+        let click = MouseEventKind::Down(MouseButton::Left);
+
+        // The list starts below the filter bar, inside its border
+        mouse(&mut app, click, 5, 5);
+        assert_eq!(app.selected().unwrap().title, "Book venue");
+        mouse(&mut app, MouseEventKind::ScrollDown, 5, 5);
+        assert_eq!(app.selected().unwrap().title, "Send invites");
+        mouse(&mut app, click, 5, 15);
+        assert_eq!(app.selected().unwrap().title, "Send invites");
+
+        let (complete, _) = app.hits.buttons[0];
+        mouse(&mut app, click, complete.x, complete.y);
+        assert!(db.get_task_by_id(3).unwrap().unwrap().completed);
+
+        // On the board a click picks the column as well as the task
+        press(&mut app, &db, "b");
+        mouse(&mut app, click, 70, 4);
+        assert_eq!(app.selected().unwrap().title, "Send invites");
+
+        // The wheel over the calendar moves a week
+        press(&mut app, &db, "bc");
+        let day = app.day;
+        mouse(&mut app, MouseEventKind::ScrollDown, 5, 6);
+        assert_eq!(app.day, day + Duration::weeks(1));
+    }
+
+    #[test]
+    fn test_render_board_marks_wip_limit() {
+        let (db, _temp_file) = create_test_db();
+        for title in ["Draft talk", "Book venue"] {
+            let id = db
+                .add_task(&Task::new(title.to_string(), None, None, 1))
+                .unwrap();
+            db.set_in_progress(id, true).unwrap();
+        }
+        db.add_task(&Task::new("Send invites".to_string(), None, None, 1))
+            .unwrap();
+        let options = Options {
+            limits: WipLimits {
+                todo: 0,
+                in_progress: 1,
+            },
+            ..Options::default()
+        };
+        let mut app = App::new(&db, options).unwrap();
+        press(&mut app, &db, "b");
+        let mut terminal = Terminal::new(TestBackend::new(90, 12)).unwrap();
+        terminal.draw(|frame| app.render(frame)).unwrap();
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect();
+
+        assert!(screen.contains("To do 1"));
+        assert!(screen.contains("In progress 2/1"));
+        assert!(screen.contains("over WIP limit"));
+        assert!(screen.contains("Done 0"));
+        assert!(screen.contains("Send invites"));
+        assert!(screen.contains("H/L move task"));
+    }
+}
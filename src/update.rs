@@ -0,0 +1,293 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+const RELEASES_URL: &str = "https://api.github.com/repos/XavierFabregat/TodoCLI/releases/latest";
+
+/// Release asset listing `<sha256>  <file name>` for every binary.
+pub const CHECKSUMS_ASSET: &str = "SHA256SUMS";
+
+/// Minisign signature of [`CHECKSUMS_ASSET`].
+pub const SIGNATURE_ASSET: &str = "SHA256SUMS.minisig";
+
+/// Minisign public key (base64) the release checksums are signed with. It is
+/// baked in from `TODO_RELEASE_KEY` when the release binaries are built, so
+/// a compromised release page can't swap it along with the files it vouches
+/// for.
+pub const RELEASE_PUBLIC_KEY: Option<&str> = option_env!("TODO_RELEASE_KEY");
+
+#[derive(Debug, Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    #[serde(default)]
+    pub assets: Vec<Asset>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Asset {
+    pub name: String,
+    pub browser_download_url: String,
+}
+
+impl Release {
+    pub fn asset(&self, name: &str) -> Option<&Asset> {
+        self.assets.iter().find(|asset| asset.name == name)
+    }
+}
+
+pub fn fetch_latest_release() -> Result<Release> {
+    ureq::get(RELEASES_URL)
+        .set("User-Agent", concat!("todo/", env!("CARGO_PKG_VERSION")))
+        .set("Accept", "application/vnd.github+json")
+        .call()
+        .context("Failed to reach GitHub releases")?
+        .into_json()
+        .context("Unexpected response from GitHub releases")
+}
+
+/// Parse `v1.2.3` or `1.2.3` into comparable parts.
+pub fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let mut parts = version.trim().trim_start_matches('v').splitn(3, '.');
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next()?.parse().ok()?;
+    let patch = parts.next()?.split(['-', '+']).next()?.parse().ok()?;
+    Some((major, minor, patch))
+}
+
+pub fn is_newer(candidate: &str, current: &str) -> bool {
+    match (parse_version(candidate), parse_version(current)) {
+        (Some(candidate), Some(current)) => candidate > current,
+        _ => false,
+    }
+}
+
+/// Release asset name for a platform, e.g. `todo-x86_64-unknown-linux-gnu`.
+pub fn asset_name(os: &str, arch: &str) -> Option<String> {
+    let (vendor_os, suffix) = match os {
+        "linux" => ("unknown-linux-gnu", ""),
+        "macos" => ("apple-darwin", ""),
+        "windows" => ("pc-windows-msvc", ".exe"),
+        _ => return None,
+    };
+    Some(format!("todo-{}-{}{}", arch, vendor_os, suffix))
+}
+
+/// Look up the expected digest of `file` in a `sha256sum`-style listing.
+pub fn expected_checksum(checksums: &str, file: &str) -> Option<String> {
+    checksums.lines().find_map(|line| {
+        let mut parts = line.split_whitespace();
+        let digest = parts.next()?;
+        let name = parts.next()?.trim_start_matches('*');
+        (name == file).then(|| digest.to_lowercase())
+    })
+}
+
+/// Check the minisign `signature` of a checksum listing against
+/// `public_key` and return the listing once it is trusted.
+pub fn verify_checksums(checksums: &[u8], signature: &str, public_key: &str) -> Result<String> {
+    let public_key = minisign_verify::PublicKey::from_base64(public_key.trim())
+        .map_err(|error| anyhow::anyhow!("Invalid release public key: {}", error))?;
+    let signature = minisign_verify::Signature::decode(signature)
+        .map_err(|error| anyhow::anyhow!("Invalid {}: {}", SIGNATURE_ASSET, error))?;
+    public_key
+        .verify(checksums, &signature, false)
+        .map_err(|error| {
+            anyhow::anyhow!(
+                "{} is not signed by the release key: {}",
+                CHECKSUMS_ASSET,
+                error
+            )
+        })?;
+    String::from_utf8(checksums.to_vec()).context("Checksum file is not valid UTF-8")
+}
+
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+fn download(url: &str) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    ureq::get(url)
+        .set("User-Agent", concat!("todo/", env!("CARGO_PKG_VERSION")))
+        .call()
+        .with_context(|| format!("Failed to download {}", url))?
+        .into_reader()
+        .read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Download the platform binary of `release`, check it against the
+/// published checksum, whose signature must match [`RELEASE_PUBLIC_KEY`],
+/// and swap it in place of `target`.
+pub fn install(release: &Release, target: &Path) -> Result<()> {
+    let public_key = RELEASE_PUBLIC_KEY.ok_or_else(|| {
+        anyhow::anyhow!(
+            "This build has no release key to verify updates with; \
+             download the new version from the releases page instead"
+        )
+    })?;
+    let name = asset_name(std::env::consts::OS, std::env::consts::ARCH)
+        .ok_or_else(|| anyhow::anyhow!("No prebuilt binaries for this platform"))?;
+    let binary = release
+        .asset(&name)
+        .ok_or_else(|| anyhow::anyhow!("Release {} has no {} asset", release.tag_name, name))?;
+    let checksums = release.asset(CHECKSUMS_ASSET).ok_or_else(|| {
+        anyhow::anyhow!(
+            "Release {} publishes no {}; refusing to install an unverified binary",
+            release.tag_name,
+            CHECKSUMS_ASSET
+        )
+    })?;
+
+    let signature = release.asset(SIGNATURE_ASSET).ok_or_else(|| {
+        anyhow::anyhow!(
+            "Release {} publishes no {}; refusing to trust unsigned checksums",
+            release.tag_name,
+            SIGNATURE_ASSET
+        )
+    })?;
+
+    let signature = String::from_utf8(download(&signature.browser_download_url)?)
+        .context("Signature file is not valid UTF-8")?;
+    let checksums = verify_checksums(
+        &download(&checksums.browser_download_url)?,
+        &signature,
+        public_key,
+    )?;
+    let expected = expected_checksum(&checksums, &name)
+        .ok_or_else(|| anyhow::anyhow!("{} has no entry for {}", CHECKSUMS_ASSET, name))?;
+
+    let bytes = download(&binary.browser_download_url)?;
+    let actual = sha256_hex(&bytes);
+    if actual != expected {
+        return Err(anyhow::anyhow!(
+            "Checksum mismatch for {}: expected {}, got {}",
+            name,
+            expected,
+            actual
+        ));
+    }
+
+    replace_binary(target, &bytes)
+}
+
+/// Write next to `target` and rename over it, so an interrupted update never
+/// leaves a half-written executable behind.
+pub fn replace_binary(target: &Path, bytes: &[u8]) -> Result<()> {
+    let dir = target
+        .parent()
+        .ok_or_else(|| anyhow::anyhow!("Cannot locate the directory of {}", target.display()))?;
+    let staged = dir.join(".todo-update.tmp");
+    fs::write(&staged, bytes).with_context(|| format!("Cannot write to {}", dir.display()))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&staged, fs::Permissions::from_mode(0o755))?;
+    }
+
+    // A running executable cannot be overwritten on Windows, but it can be
+    // renamed out of the way, and back if the new one can't take its place.
+    #[cfg(windows)]
+    let old = {
+        let old = target.with_extension("old.exe");
+        let _ = fs::remove_file(&old);
+        if let Err(error) = fs::rename(target, &old) {
+            let _ = fs::remove_file(&staged);
+            return Err(error).with_context(|| format!("Cannot replace {}", target.display()));
+        }
+        old
+    };
+
+    if let Err(error) = fs::rename(&staged, target) {
+        #[cfg(windows)]
+        let _ = fs::rename(&old, target);
+        let _ = fs::remove_file(&staged);
+        return Err(error).with_context(|| format!("Cannot replace {}", target.display()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_version_comparison() {
+        assert_eq!(parse_version("v1.2.3"), Some((1, 2, 3)));
+        assert_eq!(parse_version("1.10.0-beta.1"), Some((1, 10, 0)));
+        assert_eq!(parse_version("latest"), None);
+
+        assert!(is_newer("v1.10.0", "1.9.9"));
+        assert!(!is_newer("v1.0.2", "1.0.2"));
+        assert!(!is_newer("v1.0.1", "1.0.2"));
+        assert!(!is_newer("nightly", "1.0.2"));
+    }
+
+    #[test]
+    fn test_asset_name() {
+        assert_eq!(
+            asset_name("linux", "x86_64").unwrap(),
+            "todo-x86_64-unknown-linux-gnu"
+        );
+        assert_eq!(
+            asset_name("windows", "x86_64").unwrap(),
+            "todo-x86_64-pc-windows-msvc.exe"
+        );
+        assert!(asset_name("haiku", "x86_64").is_none());
+    }
+
+    #[test]
+    fn test_expected_checksum() {
+        let listing = "ABC123  todo-x86_64-unknown-linux-gnu\n\
+                       def456 *todo-aarch64-apple-darwin\n";
+        assert_eq!(
+            expected_checksum(listing, "todo-x86_64-unknown-linux-gnu").unwrap(),
+            "abc123"
+        );
+        assert_eq!(
+            expected_checksum(listing, "todo-aarch64-apple-darwin").unwrap(),
+            "def456"
+        );
+        assert!(expected_checksum(listing, "todo-x86_64-pc-windows-msvc.exe").is_none());
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn test_verify_checksums() {
+        // Test vector from the minisign-verify crate, signing `test`.
+        let public_key = "RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3";
+        let signature = "untrusted comment: signature from minisign secret key
+RUQf6LRCGA9i559r3g7V1qNyJDApGip8MfqcadIgT9CuhV3EMhHoN1mGTkUidF/z7SrlQgXdy8ofjb7bNJJylDOocrCo8KLzZwo=
+trusted comment: timestamp:1556193335\tfile:test
+y/rUw2y8/hOUYjZU71eHp/Wo1KZ40fGy2VJEDl34XMJM+TX48Ss/17u3IvIfbVR1FkZZSNCisQbuQY+bHwhEBg==";
+
+        assert_eq!(
+            verify_checksums(b"test", signature, public_key).unwrap(),
+            "test"
+        );
+        assert!(verify_checksums(b"tampered", signature, public_key).is_err());
+        assert!(verify_checksums(b"test", "garbage", public_key).is_err());
+
+        let other_key = "RWTAPRW2qy9FjsBiMFkWkI9HKZfOqyFEiQ5a3Hj5PDtTJPs6YbOrJGK8";
+        assert!(verify_checksums(b"test", signature, other_key).is_err());
+    }
+
+    #[test]
+    fn test_replace_binary() {
+        let dir = tempdir().unwrap();
+        let target = dir.path().join("todo");
+        fs::write(&target, b"old").unwrap();
+
+        replace_binary(&target, b"new").unwrap();
+        assert_eq!(fs::read(&target).unwrap(), b"new");
+        assert!(!dir.path().join(".todo-update.tmp").exists());
+    }
+}
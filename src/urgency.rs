@@ -0,0 +1,525 @@
+//! Urgency: one number per task saying how pressing it is, for `todo next`.
+//! It is a sum of terms between 0 and 1, each multiplied by its weight from
+//! the `urgency_*` config keys:
+//!
+//! - priority: 1 for high, 0.5 for medium, 0 for low
+//! - due: 1 once overdue, rising from 0.2 two weeks ahead to 1 on the due
+//!   date; 0 without a due date
+//! - age: days since the task was added over a year, at most 1
+//! - energy: only when you say how much energy you have, 1 for tasks that
+//!   take exactly that much and 0.5 for ones that take less
+//!
+//! The `urgency_formula` config key replaces the sum with an expression of
+//! the user's own; see `Formula`.
+
+use chrono::{DateTime, Utc};
+
+use crate::models::Task;
+use crate::Energy;
+
+/// Days before the due date at which the due term starts to rise.
+const DUE_HORIZON_DAYS: f64 = 14.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Weights {
+    pub priority: i64,
+    pub due: i64,
+    pub age: i64,
+    pub energy: i64,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Self {
+            priority: 6,
+            due: 12,
+            age: 2,
+            energy: 3,
+        }
+    }
+}
+
+/// The energy a task takes; tasks without one count as medium.
+pub fn energy_of(task: &Task) -> Energy {
+    task.energy.unwrap_or(Energy::Medium)
+}
+
+/// How pressing `task` is at `now`, for someone with `available` energy
+/// (`None` when not asked).
+pub fn urgency(
+    task: &Task,
+    now: DateTime<Utc>,
+    weights: &Weights,
+    available: Option<Energy>,
+) -> f64 {
+    let priority = f64::from(task.priority.clamp(0, 2)) / 2.0;
+
+    let due = match task.due_date {
+        None => 0.0,
+        Some(due) => {
+            let days_left = (due - now).num_minutes() as f64 / (24.0 * 60.0);
+            if days_left <= 0.0 {
+                1.0
+            } else if days_left >= DUE_HORIZON_DAYS {
+                0.2
+            } else {
+                1.0 - 0.8 * days_left / DUE_HORIZON_DAYS
+            }
+        }
+    };
+
+    let age = ((now - task.created_at).num_days() as f64 / 365.0).clamp(0.0, 1.0);
+
+    weights.priority as f64 * priority
+        + weights.due as f64 * due
+        + weights.age as f64 * age
+        + weights.energy as f64 * energy_term(task, available)
+}
+
+fn energy_term(task: &Task, available: Option<Energy>) -> f64 {
+    match available {
+        Some(available) if energy_of(task) == available => 1.0,
+        Some(available) if energy_of(task) < available => 0.5,
+        _ => 0.0,
+    }
+}
+
+/// A user-written urgency formula from the `urgency_formula` config key,
+/// such as `2*priority + overdue_days*0.5 + has_tag('work')*3`. It has
+/// numbers, `+ - * /`, parentheses and comparisons (`< <= > >= == !=`,
+/// 1 when true and 0 when false), and reads these per task:
+///
+/// - `priority`: 0 for low, 1 for medium, 2 for high
+/// - `age_days`: days since the task was added
+/// - `due_days`: days until the due date, negative once overdue; 0 without
+///   one
+/// - `overdue_days`: days past the due date, 0 when not overdue
+/// - `has_due`, `in_progress`: 1 or 0
+/// - `energy_match`: the energy term, 0 to 1
+/// - `urgency`: the built-in score from the weights
+/// - `has_tag('name')`, `in_project('name')`: 1 or 0
+/// - `min(a, b, ...)`, `max(a, b, ...)`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Formula {
+    expr: Expr,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Number(f64),
+    Variable(Variable),
+    HasTag(String),
+    InProject(String),
+    Min(Vec<Expr>),
+    Max(Vec<Expr>),
+    Negate(Box<Expr>),
+    Binary(Op, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Variable {
+    Priority,
+    AgeDays,
+    DueDays,
+    OverdueDays,
+    HasDue,
+    InProgress,
+    EnergyMatch,
+    Urgency,
+}
+
+impl Variable {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "priority" => Variable::Priority,
+            "age_days" => Variable::AgeDays,
+            "due_days" => Variable::DueDays,
+            "overdue_days" => Variable::OverdueDays,
+            "has_due" => Variable::HasDue,
+            "in_progress" => Variable::InProgress,
+            "energy_match" => Variable::EnergyMatch,
+            "urgency" => Variable::Urgency,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Equal,
+    NotEqual,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Name(String),
+    Text(String),
+    Symbol(&'static str),
+}
+
+impl Token {
+    fn unexpected(&self) -> String {
+        match self {
+            Token::Number(number) => format!("unexpected `{}`", number),
+            Token::Name(name) => format!("unexpected `{}`", name),
+            Token::Text(text) => format!("unexpected string '{}'", text),
+            Token::Symbol(symbol) => format!("unexpected `{}`", symbol),
+        }
+    }
+}
+
+const SYMBOLS: [&str; 14] = [
+    "<=", ">=", "==", "!=", "<", ">", "+", "-", "*", "/", "(", ")", ",", "=",
+];
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = text.trim_start();
+    while let Some(c) = rest.chars().next() {
+        if c.is_ascii_digit() || c == '.' {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+                .unwrap_or(rest.len());
+            let number = rest[..end]
+                .parse()
+                .map_err(|_| format!("invalid number `{}`", &rest[..end]))?;
+            tokens.push(Token::Number(number));
+            rest = &rest[end..];
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            tokens.push(Token::Name(rest[..end].to_string()));
+            rest = &rest[end..];
+        } else if c == '\'' || c == '"' {
+            let end = rest[1..]
+                .find(c)
+                .ok_or_else(|| "unterminated string".to_string())?;
+            tokens.push(Token::Text(rest[1..end + 1].to_string()));
+            rest = &rest[end + 2..];
+        } else {
+            let symbol = SYMBOLS
+                .iter()
+                .find(|symbol| rest.starts_with(**symbol))
+                .ok_or_else(|| format!("unexpected `{}`", c))?;
+            if *symbol == "=" {
+                return Err("use `==` to compare".to_string());
+            }
+            tokens.push(Token::Symbol(symbol));
+            rest = &rest[symbol.len()..];
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+/// A recursive-descent parser; comparisons bind loosest, then `+ -`, then
+/// `* /`, then unary minus.
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn eat(&mut self, symbol: &'static str) -> bool {
+        let found = self.peek() == Some(&Token::Symbol(symbol));
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, symbol: &'static str) -> Result<(), String> {
+        if self.eat(symbol) {
+            Ok(())
+        } else {
+            Err(format!("expected `{}`", symbol))
+        }
+    }
+
+    fn comparison(&mut self) -> Result<Expr, String> {
+        let left = self.sum()?;
+        let op = match self.peek() {
+            Some(Token::Symbol("<")) => Op::Less,
+            Some(Token::Symbol("<=")) => Op::LessEqual,
+            Some(Token::Symbol(">")) => Op::Greater,
+            Some(Token::Symbol(">=")) => Op::GreaterEqual,
+            Some(Token::Symbol("==")) => Op::Equal,
+            Some(Token::Symbol("!=")) => Op::NotEqual,
+            _ => return Ok(left),
+        };
+        self.position += 1;
+        let right = self.sum()?;
+        Ok(Expr::Binary(op, Box::new(left), Box::new(right)))
+    }
+
+    fn sum(&mut self) -> Result<Expr, String> {
+        let mut left = self.product()?;
+        loop {
+            let op = if self.eat("+") {
+                Op::Add
+            } else if self.eat("-") {
+                Op::Subtract
+            } else {
+                return Ok(left);
+            };
+            left = Expr::Binary(op, Box::new(left), Box::new(self.product()?));
+        }
+    }
+
+    fn product(&mut self) -> Result<Expr, String> {
+        let mut left = self.unary()?;
+        loop {
+            let op = if self.eat("*") {
+                Op::Multiply
+            } else if self.eat("/") {
+                Op::Divide
+            } else {
+                return Ok(left);
+            };
+            left = Expr::Binary(op, Box::new(left), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat("-") {
+            return Ok(Expr::Negate(Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Number(number)) => Ok(Expr::Number(number)),
+            Some(Token::Symbol("(")) => {
+                let expr = self.comparison()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Some(Token::Name(name)) if self.eat("(") => self.call(&name),
+            Some(Token::Name(name)) => Variable::from_name(&name)
+                .map(Expr::Variable)
+                .ok_or_else(|| format!("unknown variable `{}`", name)),
+            Some(token) => Err(token.unexpected()),
+            None => Err("unexpected end of formula".to_string()),
+        }
+    }
+
+    /// The arguments of function `name`, after its `(`.
+    fn call(&mut self, name: &str) -> Result<Expr, String> {
+        match name {
+            "has_tag" | "in_project" => {
+                let Some(Token::Text(text)) = self.next() else {
+                    return Err(format!("{}() takes a quoted name", name));
+                };
+                self.expect(")")?;
+                Ok(if name == "has_tag" {
+                    Expr::HasTag(text)
+                } else {
+                    Expr::InProject(text)
+                })
+            }
+            "min" | "max" => {
+                let mut args = vec![self.comparison()?];
+                while self.eat(",") {
+                    args.push(self.comparison()?);
+                }
+                self.expect(")")?;
+                Ok(if name == "min" {
+                    Expr::Min(args)
+                } else {
+                    Expr::Max(args)
+                })
+            }
+            _ => Err(format!("unknown function `{}`", name)),
+        }
+    }
+}
+
+/// What a formula can read about one task.
+struct Inputs<'a> {
+    task: &'a Task,
+    now: DateTime<Utc>,
+    weights: &'a Weights,
+    available: Option<Energy>,
+}
+
+impl Formula {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut parser = Parser {
+            tokens: tokenize(text)?,
+            position: 0,
+        };
+        let expr = parser.comparison()?;
+        if let Some(token) = parser.peek() {
+            return Err(token.unexpected());
+        }
+        Ok(Self { expr })
+    }
+
+    /// The formula's urgency for `task`, with the same inputs as `urgency`.
+    pub fn evaluate(
+        &self,
+        task: &Task,
+        now: DateTime<Utc>,
+        weights: &Weights,
+        available: Option<Energy>,
+    ) -> f64 {
+        let inputs = Inputs {
+            task,
+            now,
+            weights,
+            available,
+        };
+        eval(&self.expr, &inputs)
+    }
+}
+
+fn days(duration: chrono::Duration) -> f64 {
+    duration.num_minutes() as f64 / (24.0 * 60.0)
+}
+
+fn flag(value: bool) -> f64 {
+    if value {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+fn eval(expr: &Expr, inputs: &Inputs) -> f64 {
+    let task = inputs.task;
+    match expr {
+        Expr::Number(number) => *number,
+        Expr::Variable(variable) => {
+            let due_days = task.due_date.map(|due| days(due - inputs.now));
+            match variable {
+                Variable::Priority => f64::from(task.priority.clamp(0, 2)),
+                Variable::AgeDays => days(inputs.now - task.created_at).max(0.0),
+                Variable::DueDays => due_days.unwrap_or(0.0),
+                Variable::OverdueDays => due_days.map_or(0.0, |days| (-days).max(0.0)),
+                Variable::HasDue => flag(due_days.is_some()),
+                Variable::InProgress => flag(task.in_progress),
+                Variable::EnergyMatch => energy_term(task, inputs.available),
+                Variable::Urgency => urgency(task, inputs.now, inputs.weights, inputs.available),
+            }
+        }
+        Expr::HasTag(tag) => flag(task.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))),
+        Expr::InProject(project) => flag(
+            task.project
+                .as_deref()
+                .is_some_and(|p| p.eq_ignore_ascii_case(project)),
+        ),
+        Expr::Min(args) => args
+            .iter()
+            .map(|arg| eval(arg, inputs))
+            .fold(f64::INFINITY, f64::min),
+        Expr::Max(args) => args
+            .iter()
+            .map(|arg| eval(arg, inputs))
+            .fold(f64::NEG_INFINITY, f64::max),
+        Expr::Negate(inner) => -eval(inner, inputs),
+        Expr::Binary(op, left, right) => {
+            let (left, right) = (eval(left, inputs), eval(right, inputs));
+            match op {
+                Op::Add => left + right,
+                Op::Subtract => left - right,
+                Op::Multiply => left * right,
+                // Dividing by zero gives 0 rather than a score nothing sorts by
+                Op::Divide if right == 0.0 => 0.0,
+                Op::Divide => left / right,
+                Op::Less => flag(left < right),
+                Op::LessEqual => flag(left <= right),
+                Op::Greater => flag(left > right),
+                Op::GreaterEqual => flag(left >= right),
+                Op::Equal => flag(left == right),
+                Op::NotEqual => flag(left != right),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_urgency_terms() {
+        let now = Utc::now();
+        let weights = Weights::default();
+        let mut task = Task::new("Task".to_string(), None, None, 0);
+        task.created_at = now;
+        assert_eq!(urgency(&task, now, &weights, None), 0.0);
+
+        task.priority = 2;
+        assert_eq!(urgency(&task, now, &weights, None), 6.0);
+
+        task.due_date = Some(now - Duration::days(1));
+        assert_eq!(urgency(&task, now, &weights, None), 18.0);
+        task.due_date = Some(now + Duration::days(7));
+        assert!((urgency(&task, now, &weights, None) - 13.2).abs() < 0.01);
+        task.due_date = Some(now + Duration::days(30));
+        assert!((urgency(&task, now, &weights, None) - 8.4).abs() < 0.01);
+
+        task.due_date = None;
+        task.energy = Some(Energy::Low);
+        assert_eq!(urgency(&task, now, &weights, Some(Energy::Low)), 9.0);
+        assert_eq!(urgency(&task, now, &weights, Some(Energy::High)), 7.5);
+        task.energy = None;
+        assert_eq!(urgency(&task, now, &weights, Some(Energy::Low)), 6.0);
+    }
+
+    #[test]
+    fn test_formula() {
+        let now = Utc::now();
+        let weights = Weights::default();
+        let mut task = Task::new("Task".to_string(), None, None, 2);
+        task.created_at = now - Duration::days(10);
+        task.due_date = Some(now - Duration::days(4));
+        task.tags = vec!["work".to_string()];
+        let score = |text: &str| {
+            Formula::parse(text)
+                .unwrap()
+                .evaluate(&task, now, &weights, None)
+        };
+
+        assert_eq!(
+            score("2*priority + overdue_days*0.5 + has_tag('work')*3"),
+            9.0
+        );
+        assert_eq!(score("has_tag(\"home\") + in_project('x')"), 0.0);
+        assert_eq!(score("-(1 + 2) * 3 - -1"), -8.0);
+        assert_eq!(score("due_days < 0"), 1.0);
+        assert_eq!(score("max(age_days, 3) + min(1, 2, priority)"), 11.0);
+        assert_eq!(score("urgency"), urgency(&task, now, &weights, None));
+        assert_eq!(score("1 / (has_due - 1)"), 0.0);
+
+        let error = |text: &str| Formula::parse(text).unwrap_err();
+        assert_eq!(error("priorty * 2"), "unknown variable `priorty`");
+        assert_eq!(error("has_tag(work)"), "has_tag() takes a quoted name");
+        assert_eq!(error("(1 + 2"), "expected `)`");
+        assert_eq!(error("priority = 2"), "use `==` to compare");
+        assert_eq!(error("1 2"), "unexpected `2`");
+        assert_eq!(error(""), "unexpected end of formula");
+    }
+}
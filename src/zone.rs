@@ -0,0 +1,175 @@
+//! The time zone due dates are entered and shown in, from the `timezone`
+//! config key: `local` (the system's zone, or the one `TZ` names), `utc`,
+//! or a fixed offset such as `+05:30`. Dates are stored as UTC instants; a
+//! due date given as a day means the end of that day in this zone, and
+//! days (today, this week, the day a task is due) are this zone's days.
+//! Until `init` is called, as in tests and library use, the zone is UTC.
+
+use chrono::{
+    DateTime, Duration, FixedOffset, LocalResult, NaiveDate, NaiveDateTime, TimeZone, Utc,
+};
+use std::sync::OnceLock;
+
+use crate::calendar;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Zone {
+    /// The system's zone, daylight saving time included.
+    Local,
+    Fixed(FixedOffset),
+}
+
+impl Zone {
+    pub const UTC: Zone = Zone::Fixed(FixedOffset::east_opt(0).unwrap());
+
+    /// `local`, `utc`, or an offset from UTC such as `+05:30`, `-0800` or
+    /// `+01`.
+    pub fn parse(text: &str) -> Option<Zone> {
+        let text = text.trim();
+        match text.to_lowercase().as_str() {
+            "local" => return Some(Zone::Local),
+            "utc" | "z" => return Some(Zone::UTC),
+            _ => {}
+        }
+        let sign = match text.chars().next()? {
+            '+' => 1,
+            '-' => -1,
+            _ => return None,
+        };
+        let digits: String = text[1..].chars().filter(|c| *c != ':').collect();
+        if !digits.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        let (hours, minutes) = match digits.len() {
+            2 => (digits.parse::<i32>().ok()?, 0),
+            4 => (digits[..2].parse::<i32>().ok()?, digits[2..].parse().ok()?),
+            _ => return None,
+        };
+        if hours > 14 || minutes > 59 {
+            return None;
+        }
+        FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60)).map(Zone::Fixed)
+    }
+
+    /// The name `parse` reads back: `local`, `utc` or `+05:30`.
+    pub fn name(&self) -> String {
+        match self {
+            Zone::Local => "local".to_string(),
+            Zone::Fixed(offset) if offset.local_minus_utc() == 0 => "utc".to_string(),
+            Zone::Fixed(offset) => offset.to_string(),
+        }
+    }
+}
+
+impl TimeZone for Zone {
+    type Offset = FixedOffset;
+
+    fn from_offset(offset: &FixedOffset) -> Self {
+        Zone::Fixed(*offset)
+    }
+
+    fn offset_from_local_date(&self, local: &NaiveDate) -> LocalResult<FixedOffset> {
+        match self {
+            Zone::Local => chrono::Local.offset_from_local_date(local),
+            Zone::Fixed(offset) => LocalResult::Single(*offset),
+        }
+    }
+
+    fn offset_from_local_datetime(&self, local: &NaiveDateTime) -> LocalResult<FixedOffset> {
+        match self {
+            Zone::Local => chrono::Local.offset_from_local_datetime(local),
+            Zone::Fixed(offset) => LocalResult::Single(*offset),
+        }
+    }
+
+    fn offset_from_utc_date(&self, utc: &NaiveDate) -> FixedOffset {
+        match self {
+            Zone::Local => chrono::Local.offset_from_utc_date(utc),
+            Zone::Fixed(offset) => *offset,
+        }
+    }
+
+    fn offset_from_utc_datetime(&self, utc: &NaiveDateTime) -> FixedOffset {
+        match self {
+            Zone::Local => chrono::Local.offset_from_utc_datetime(utc),
+            Zone::Fixed(offset) => *offset,
+        }
+    }
+}
+
+static ZONE: OnceLock<Zone> = OnceLock::new();
+
+/// Install the zone for this process. Later calls are ignored.
+pub fn init(zone: Zone) {
+    let _ = ZONE.set(zone);
+}
+
+pub fn current() -> Zone {
+    ZONE.get().copied().unwrap_or(Zone::UTC)
+}
+
+/// The day `at` falls on.
+pub fn day_of(at: DateTime<Utc>) -> NaiveDate {
+    calendar::local_day(at, &current())
+}
+
+/// The instant `day` starts at.
+pub fn start_of(day: NaiveDate) -> DateTime<Utc> {
+    calendar::day_start(day, &current())
+}
+
+/// The last second of `day`, which is what `today` and `eod` mean.
+pub fn end_of(day: NaiveDate) -> DateTime<Utc> {
+    match day.succ_opt() {
+        Some(next) => start_of(next) - Duration::seconds(1),
+        None => start_of(day),
+    }
+}
+
+/// The instant a wall-clock time names. A time skipped by daylight saving
+/// time is taken as the first one after the gap, and a time that comes
+/// twice as the first.
+pub fn from_local(local: NaiveDateTime) -> DateTime<Utc> {
+    let zone = current();
+    (0..=24 * 60)
+        .map(|minutes| local + Duration::minutes(minutes))
+        .find_map(|local| zone.from_local_datetime(&local).earliest())
+        .map(|at| at.with_timezone(&Utc))
+        .unwrap_or_else(|| local.and_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_zone() {
+        assert_eq!(Zone::parse("local"), Some(Zone::Local));
+        assert_eq!(Zone::parse("UTC"), Some(Zone::UTC));
+        let india = Zone::parse("+05:30").unwrap();
+        assert_eq!(india, Zone::Fixed(FixedOffset::east_opt(19800).unwrap()));
+        assert_eq!(Zone::parse("+0530"), Some(india));
+        assert_eq!(
+            Zone::parse("-08"),
+            Some(Zone::Fixed(FixedOffset::west_opt(8 * 3600).unwrap()))
+        );
+        for invalid in ["Europe/Madrid", "+5", "+25:00", "+05:75", "05:00", ""] {
+            assert_eq!(Zone::parse(invalid), None, "{}", invalid);
+        }
+        assert_eq!(india.name(), "+05:30");
+        assert_eq!(Zone::UTC.name(), "utc");
+
+        let day = NaiveDate::from_ymd_opt(2030, 1, 10).unwrap();
+        let start = calendar::day_start(day, &india);
+        assert_eq!(start.to_rfc3339(), "2030-01-09T18:30:00+00:00");
+        assert_eq!(calendar::local_day(start, &india), day);
+    }
+
+    #[test]
+    fn test_days_default_to_utc() {
+        let day = NaiveDate::from_ymd_opt(2030, 1, 10).unwrap();
+        assert_eq!(start_of(day).to_rfc3339(), "2030-01-10T00:00:00+00:00");
+        assert_eq!(end_of(day).to_rfc3339(), "2030-01-10T23:59:59+00:00");
+        assert_eq!(day_of(end_of(day)), day);
+    }
+}